@@ -1,5 +1,10 @@
+use std::env;
+
 use clap::Parser;
 
+/// The environment variable giving the default account name.
+pub const ACCOUNT_ENV: &str = "HIMALAYA_ACCOUNT";
+
 /// The account name argument parser.
 #[derive(Debug, Parser)]
 pub struct AccountNameArg {
@@ -25,13 +30,24 @@ pub struct OptionalAccountNameArg {
 }
 
 /// The account name flag parser.
-#[derive(Debug, Default, Parser)]
+#[derive(Debug, Parser)]
 pub struct AccountNameFlag {
     /// Override the default account.
     ///
     /// An account name corresponds to an entry in the table at the
     /// root level of your TOML configuration file.
-    #[arg(long = "account", short = 'a')]
+    ///
+    /// The HIMALAYA_ACCOUNT environment variable is used when the
+    /// flag is omitted, before the account marked as default.
+    #[arg(long = "account", short = 'a', env = ACCOUNT_ENV)]
     #[arg(name = "account_name", value_name = "NAME")]
     pub name: Option<String>,
 }
+
+impl Default for AccountNameFlag {
+    fn default() -> Self {
+        Self {
+            name: env::var(ACCOUNT_ENV).ok(),
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use std::{fs, path::PathBuf};
+
+use clap::{Parser, ValueEnum};
+use color_eyre::{eyre::WrapErr, Result};
+use pimalaya_tui::terminal::cli::arg::path_parser;
+use toml::{Table, Value};
+
+/// The account settings arguments parser.
+///
+/// Settings configure an account without the wizard. They are given
+/// as TOML keys of the account, like `backend.host` for the IMAP
+/// host.
+#[derive(Debug, Default, Eq, PartialEq, Parser)]
+pub struct AccountSettingsArgs {
+    /// Read account settings from the given TOML file.
+    ///
+    /// The file contains options of the account, as found in its
+    /// table of the configuration file (for example `email = "…"` or
+    /// `backend.type = "imap"`). Other arguments take precedence.
+    #[arg(long, value_name = "PATH", value_parser = path_parser)]
+    pub from_file: Option<PathBuf>,
+
+    /// The email address of the account.
+    #[arg(long, value_name = "ADDRESS")]
+    pub email: Option<String>,
+
+    /// The display name of the account.
+    #[arg(long, value_name = "NAME")]
+    pub display_name: Option<String>,
+
+    /// The backend of the account.
+    ///
+    /// Implied by IMAP and Maildir arguments.
+    #[arg(long, value_enum, value_name = "BACKEND")]
+    pub backend: Option<BackendKind>,
+
+    /// The IMAP server host name.
+    #[arg(long, value_name = "HOST")]
+    pub imap_host: Option<String>,
+
+    /// The IMAP server port.
+    ///
+    /// Defaults to 993, or to 143 without SSL/TLS encryption.
+    #[arg(long, value_name = "PORT")]
+    pub imap_port: Option<u16>,
+
+    /// The IMAP server encryption.
+    #[arg(long, value_enum, value_name = "ENCRYPTION")]
+    pub imap_encryption: Option<EncryptionKind>,
+
+    /// The IMAP login.
+    ///
+    /// Defaults to the email address of the account.
+    #[arg(long, value_name = "LOGIN")]
+    pub imap_login: Option<String>,
+
+    /// The shell command printing the IMAP password.
+    #[arg(long, value_name = "COMMAND")]
+    pub imap_password_cmd: Option<String>,
+
+    /// The Maildir root directory.
+    #[arg(long, value_name = "PATH")]
+    pub maildir_root_dir: Option<PathBuf>,
+
+    /// The sending backend of the account.
+    ///
+    /// Implied by SMTP and sendmail arguments.
+    #[arg(long, value_enum, value_name = "BACKEND")]
+    pub sending_backend: Option<SendingBackendKind>,
+
+    /// The SMTP server host name.
+    #[arg(long, value_name = "HOST")]
+    pub smtp_host: Option<String>,
+
+    /// The SMTP server port.
+    ///
+    /// Defaults to 465, to 587 with StartTLS encryption or to 25
+    /// without encryption.
+    #[arg(long, value_name = "PORT")]
+    pub smtp_port: Option<u16>,
+
+    /// The SMTP server encryption.
+    #[arg(long, value_enum, value_name = "ENCRYPTION")]
+    pub smtp_encryption: Option<EncryptionKind>,
+
+    /// The SMTP login.
+    ///
+    /// Defaults to the email address of the account.
+    #[arg(long, value_name = "LOGIN")]
+    pub smtp_login: Option<String>,
+
+    /// The shell command printing the SMTP password.
+    #[arg(long, value_name = "COMMAND")]
+    pub smtp_password_cmd: Option<String>,
+
+    /// The sendmail command.
+    #[arg(long, value_name = "COMMAND")]
+    pub sendmail_cmd: Option<String>,
+}
+
+/// The kind of backend of an account.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum BackendKind {
+    Imap,
+    Maildir,
+    Notmuch,
+}
+
+/// The kind of sending backend of an account.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum SendingBackendKind {
+    Smtp,
+    Sendmail,
+}
+
+/// The kind of encryption of a server connection.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum EncryptionKind {
+    Tls,
+    StartTls,
+    None,
+}
+
+impl AccountSettingsArgs {
+    /// Return `true` if no setting is given.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Return the account settings as a TOML table, or [`None`] if no
+    /// setting is given.
+    ///
+    /// Settings of the file come first, then the ones of the other
+    /// arguments.
+    pub fn into_table(self) -> Result<Option<Table>> {
+        let mut table = match &self.from_file {
+            Some(path) => {
+                let content = fs::read_to_string(path)
+                    .wrap_err_with(|| format!("cannot read settings at {}", path.display()))?;
+                content
+                    .parse::<Table>()
+                    .wrap_err_with(|| format!("cannot parse settings at {}", path.display()))?
+            }
+            None => Table::new(),
+        };
+
+        let imap = self.imap_host.is_some()
+            || self.imap_port.is_some()
+            || self.imap_encryption.is_some()
+            || self.imap_login.is_some()
+            || self.imap_password_cmd.is_some();
+        let smtp = self.smtp_host.is_some()
+            || self.smtp_port.is_some()
+            || self.smtp_encryption.is_some()
+            || self.smtp_login.is_some()
+            || self.smtp_password_cmd.is_some();
+
+        let backend = match self.backend {
+            Some(backend) => Some(backend),
+            None if imap => Some(BackendKind::Imap),
+            None if self.maildir_root_dir.is_some() => Some(BackendKind::Maildir),
+            None => None,
+        };
+
+        let sending_backend = match self.sending_backend {
+            Some(backend) => Some(backend),
+            None if smtp => Some(SendingBackendKind::Smtp),
+            None if self.sendmail_cmd.is_some() => Some(SendingBackendKind::Sendmail),
+            None => None,
+        };
+
+        let mut settings = Table::new();
+        let mut set = |key: &str, value: Option<Value>| {
+            if let Some(value) = value {
+                insert(&mut settings, key, value);
+            }
+        };
+
+        set("email", self.email.map(Value::String));
+        set("display-name", self.display_name.map(Value::String));
+        set("backend.type", backend.map(to_value));
+        set("backend.host", self.imap_host.map(Value::String));
+        set("backend.port", self.imap_port.map(to_integer));
+        set(
+            "backend.encryption.type",
+            self.imap_encryption.map(to_value),
+        );
+        set("backend.login", self.imap_login.map(Value::String));
+        set("backend.auth", self.imap_password_cmd.map(to_password_cmd));
+        set("backend.root-dir", self.maildir_root_dir.map(to_path));
+        set("message.send.backend.type", sending_backend.map(to_value));
+        set(
+            "message.send.backend.host",
+            self.smtp_host.map(Value::String),
+        );
+        set("message.send.backend.port", self.smtp_port.map(to_integer));
+        set(
+            "message.send.backend.encryption.type",
+            self.smtp_encryption.map(to_value),
+        );
+        set(
+            "message.send.backend.login",
+            self.smtp_login.map(Value::String),
+        );
+        set(
+            "message.send.backend.auth",
+            self.smtp_password_cmd.map(to_password_cmd),
+        );
+        set(
+            "message.send.backend.cmd",
+            self.sendmail_cmd.map(Value::String),
+        );
+
+        if self.from_file.is_none() && settings.is_empty() {
+            return Ok(None);
+        }
+
+        merge(&mut table, settings);
+        Ok(Some(table))
+    }
+}
+
+/// Insert the given value at the given dotted key of the given table,
+/// creating intermediate tables when needed.
+pub fn insert(table: &mut Table, key: &str, value: Value) {
+    let (parents, key) = match key.rsplit_once('.') {
+        Some((parents, key)) => (Some(parents), key),
+        None => (None, key),
+    };
+
+    let mut table = table;
+
+    for parent in parents.into_iter().flat_map(|parents| parents.split('.')) {
+        let entry = table
+            .entry(parent)
+            .or_insert_with(|| Value::Table(Table::new()));
+
+        if !entry.is_table() {
+            *entry = Value::Table(Table::new());
+        }
+
+        let Value::Table(child) = entry else {
+            unreachable!()
+        };
+
+        table = child;
+    }
+
+    table.insert(key.to_owned(), value);
+}
+
+/// Get the value at the given dotted key of the given table.
+pub fn get<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let mut keys = key.split('.');
+    let mut value = table.get(keys.next()?)?;
+
+    for key in keys {
+        value = value.as_table()?.get(key)?;
+    }
+
+    Some(value)
+}
+
+/// Merge the given settings into the given table.
+///
+/// Tables are merged recursively, except tables of different types
+/// (like an IMAP backend replaced by a Maildir one), which are
+/// replaced as a whole so that no option of the previous type is
+/// left. Authentication tables are always replaced, since their
+/// secret is either a raw value, a command or a keyring entry.
+pub fn merge(table: &mut Table, settings: Table) {
+    for (key, value) in settings {
+        match (table.get_mut(&key), value) {
+            (Some(Value::Table(prev)), Value::Table(next))
+                if key != "auth"
+                    && (prev.get("type").is_none()
+                        || next.get("type").is_none()
+                        || prev.get("type") == next.get("type")) =>
+            {
+                merge(prev, next)
+            }
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+fn to_value(kind: impl ValueEnum) -> Value {
+    let name = kind
+        .to_possible_value()
+        .map(|value| value.get_name().to_owned());
+    Value::String(name.unwrap_or_default())
+}
+
+fn to_integer(port: u16) -> Value {
+    Value::Integer(port.into())
+}
+
+fn to_path(path: PathBuf) -> Value {
+    Value::String(path.to_string_lossy().into_owned())
+}
+
+/// Build the authentication table of the given password command.
+pub fn to_password_cmd(cmd: String) -> Value {
+    let mut auth = Table::new();
+    auth.insert("type".into(), Value::String("password".into()));
+    auth.insert("cmd".into(), Value::String(cmd));
+    Value::Table(auth)
+}
@@ -0,0 +1,340 @@
+//! Module dedicated to account checks.
+//!
+//! Checks are shared by the doctor and the wizard. Each check is
+//! reported with a ✓ or a ✗ followed by the error, and failures do
+//! not prevent next checks from running.
+
+use std::{future::Future, sync::Arc};
+
+use color_eyre::{eyre::eyre, Result, Section};
+#[cfg(feature = "imap")]
+use email::imap::config::ImapAuthConfig;
+#[cfg(feature = "imap")]
+use email::imap::ImapContextBuilder;
+#[cfg(feature = "maildir")]
+use email::maildir::MaildirContextBuilder;
+#[cfg(feature = "notmuch")]
+use email::notmuch::NotmuchContextBuilder;
+#[cfg(feature = "sendmail")]
+use email::sendmail::SendmailContextBuilder;
+#[cfg(feature = "smtp")]
+use email::smtp::config::SmtpAuthConfig;
+use email::{
+    account::config::AccountConfig,
+    backend::{context::BackendContextBuilder, BackendBuilder},
+    folder::list::ListFolders,
+};
+#[cfg(any(feature = "imap", feature = "maildir", feature = "notmuch"))]
+use pimalaya_tui::himalaya::config::Backend;
+#[cfg(any(feature = "smtp", feature = "sendmail"))]
+use pimalaya_tui::himalaya::config::SendingBackend;
+
+#[cfg(feature = "smtp")]
+use crate::message::send::smtp;
+use crate::{
+    account::config::{TomlAccountConfig, TomlAccountExtraConfig},
+    printer::Printer,
+};
+
+/// Check that the credentials of the given account can be resolved,
+/// that its backends can be reached and that its folders can be
+/// listed.
+#[cfg_attr(
+    not(all(
+        feature = "smtp",
+        any(
+            feature = "imap",
+            feature = "maildir",
+            feature = "notmuch",
+            feature = "sendmail"
+        )
+    )),
+    allow(unused_variables)
+)]
+pub async fn check_account(
+    checks: &mut Checks<'_, impl Printer>,
+    toml_account_config: TomlAccountConfig,
+    extra_config: &TomlAccountExtraConfig,
+    account_config: Arc<AccountConfig>,
+) {
+    #[cfg(feature = "imap")]
+    if let Some(auth) = toml_account_config.imap_auth_config() {
+        checks
+            .run("Resolving IMAP credentials", get_imap_secret(auth))
+            .await;
+    }
+
+    #[cfg(feature = "smtp")]
+    if let Some(auth) = toml_account_config.smtp_auth_config() {
+        checks
+            .run("Resolving SMTP credentials", get_smtp_secret(auth))
+            .await;
+    }
+
+    match toml_account_config.backend {
+        #[cfg(feature = "maildir")]
+        Some(Backend::Maildir(mdir_config)) => {
+            let ctx = MaildirContextBuilder::new(account_config.clone(), Arc::new(mdir_config));
+            checks
+                .run_backend("Maildir", account_config.clone(), ctx, true)
+                .await;
+        }
+        #[cfg(feature = "imap")]
+        Some(Backend::Imap(imap_config)) => {
+            let ctx = ImapContextBuilder::new(account_config.clone(), Arc::new(imap_config))
+                .with_pool_size(1);
+            checks
+                .run_backend("IMAP", account_config.clone(), ctx, true)
+                .await;
+        }
+        #[cfg(feature = "notmuch")]
+        Some(Backend::Notmuch(notmuch_config)) => {
+            let ctx = NotmuchContextBuilder::new(account_config.clone(), Arc::new(notmuch_config));
+            checks
+                .run_backend("Notmuch", account_config.clone(), ctx, true)
+                .await;
+        }
+        _ => (),
+    }
+
+    let sending_backend = toml_account_config
+        .message
+        .and_then(|msg| msg.send)
+        .and_then(|send| send.backend);
+
+    match sending_backend {
+        #[cfg(feature = "smtp")]
+        Some(SendingBackend::Smtp(smtp_config)) => {
+            // messages are sent by the SMTP client of the CLI, so it is
+            // the one checked, with the same options
+            let step = match extra_config.smtp_proxy() {
+                Some(proxy) => format!("Connecting to SMTP backend through proxy {proxy}"),
+                None => String::from("Connecting to SMTP backend"),
+            };
+
+            let check = smtp::check(smtp_config, extra_config.smtp_options());
+            checks.run(&step, check).await;
+        }
+        #[cfg(feature = "sendmail")]
+        Some(SendingBackend::Sendmail(sendmail_config)) => {
+            let ctx =
+                SendmailContextBuilder::new(account_config.clone(), Arc::new(sendmail_config));
+            checks
+                .run_backend("Sendmail", account_config.clone(), ctx, false)
+                .await;
+        }
+        _ => (),
+    }
+}
+
+/// The checks of an account, reported as they run.
+///
+/// Reports are informational messages of the given printer, so that
+/// they are silenced in quiet mode.
+pub struct Checks<'a, P: Printer> {
+    printer: &'a mut P,
+    failures: usize,
+    /// The option accepting the certificate refused by the SMTP
+    /// server, if any.
+    #[cfg(feature = "smtp")]
+    invalid_certificate: Option<&'static str>,
+}
+
+impl<'a, P: Printer> Checks<'a, P> {
+    pub fn new(printer: &'a mut P) -> Self {
+        Self {
+            printer,
+            failures: 0,
+            #[cfg(feature = "smtp")]
+            invalid_certificate: None,
+        }
+    }
+
+    /// Get the printer of the checks, to report other steps.
+    pub fn printer(&mut self) -> &mut P {
+        self.printer
+    }
+
+    /// Run the given check, and report its result.
+    ///
+    /// Returns the output of the check, or `None` if it failed.
+    pub async fn run<T>(
+        &mut self,
+        step: &str,
+        check: impl Future<Output = Result<T>>,
+    ) -> Option<T> {
+        // a report that cannot be printed does not change the result
+        // of the check
+        let _ = self.printer.info(format!("{step}… "));
+
+        match check.await {
+            Ok(output) => {
+                let _ = self.printer.info("✓\n");
+                Some(output)
+            }
+            Err(err) => {
+                #[cfg(feature = "smtp")]
+                if let Some(cert) = err
+                    .chain()
+                    .find_map(|err| err.downcast_ref::<smtp::InvalidCertificate>())
+                {
+                    self.invalid_certificate = Some(cert.option);
+                }
+
+                let mut report = String::from("✗\n");
+
+                for cause in err.chain() {
+                    report.push_str(&format!("  {cause}\n"));
+                }
+
+                let _ = self.printer.info(report);
+
+                self.failures += 1;
+                None
+            }
+        }
+    }
+
+    /// Check that the given backend can be reached, then that its
+    /// folders can be listed.
+    pub async fn run_backend<CB>(
+        &mut self,
+        name: &str,
+        account_config: Arc<AccountConfig>,
+        ctx: CB,
+        list_folders: bool,
+    ) where
+        CB: BackendContextBuilder + 'static,
+    {
+        let builder = BackendBuilder::new(account_config, ctx);
+
+        let step = format!("Connecting to {name} backend");
+        let connected = self
+            .run(&step, async { Ok(builder.clone().check_up().await?) })
+            .await;
+
+        if connected.is_none() || !list_folders {
+            return;
+        }
+
+        self.run("Listing folders", async {
+            let folders = builder.build().await?.list_folders().await?;
+
+            if folders.is_empty() {
+                return Err(eyre!("no folder found"));
+            }
+
+            Ok(())
+        })
+        .await;
+    }
+
+    /// Return `true` if all checks passed.
+    pub fn is_ok(&self) -> bool {
+        self.failures == 0
+    }
+
+    /// Get the option accepting the certificate refused by the SMTP
+    /// server, if a check failed because of it.
+    #[cfg(feature = "smtp")]
+    pub fn invalid_certificate(&self) -> Option<&'static str> {
+        self.invalid_certificate
+    }
+
+    /// Fail if any check failed.
+    pub fn finish(self, fix: bool) -> Result<()> {
+        let err = match self.failures {
+            0 => return Ok(()),
+            1 => eyre!("1 check failed"),
+            n => eyre!("{n} checks failed"),
+        };
+
+        if fix {
+            Err(err)
+        } else {
+            Err(err.note("Run with --fix to (re)configure your account."))
+        }
+    }
+}
+
+/// Get the secret of the given IMAP authentication, either the
+/// password or the OAuth 2.0 access token.
+#[cfg(feature = "imap")]
+async fn get_imap_secret(auth: &ImapAuthConfig) -> Result<()> {
+    match auth {
+        ImapAuthConfig::Password(passwd) => passwd.get().await?,
+        #[cfg(feature = "oauth2")]
+        ImapAuthConfig::OAuth2(oauth2) => oauth2.access_token.get().await?,
+    };
+
+    Ok(())
+}
+
+/// Get the secret of the given SMTP authentication, either the
+/// password or the OAuth 2.0 access token.
+#[cfg(feature = "smtp")]
+async fn get_smtp_secret(auth: &SmtpAuthConfig) -> Result<()> {
+    match auth {
+        SmtpAuthConfig::Password(passwd) => passwd.get().await?,
+        #[cfg(feature = "oauth2")]
+        SmtpAuthConfig::OAuth2(oauth2) => oauth2.access_token.get().await?,
+    };
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+
+    use color_eyre::{eyre::eyre, Result};
+    use pimalaya_tui::terminal::cli::printer;
+    use serde::Serialize;
+
+    use super::Checks;
+    use crate::printer::Printer;
+
+    /// A printer recording informational messages.
+    #[derive(Default)]
+    struct Recorder(String);
+
+    impl printer::Printer for Recorder {
+        fn out<T: fmt::Display + Serialize>(&mut self, data: T) -> Result<()> {
+            self.0.push_str(&data.to_string());
+            Ok(())
+        }
+    }
+
+    impl Printer for Recorder {}
+
+    #[tokio::test]
+    async fn report_checks_through_printer() {
+        let mut printer = Recorder::default();
+        let mut checks = Checks::new(&mut printer);
+
+        let output = checks.run("Resolving credentials", async { Ok(42) }).await;
+        assert_eq!(output, Some(42));
+
+        let output = checks
+            .run("Connecting to IMAP backend", async {
+                Err::<(), _>(eyre!("connection refused").wrap_err("cannot connect"))
+            })
+            .await;
+        assert_eq!(output, None);
+
+        assert!(!checks.is_ok());
+        assert_eq!(
+            checks.finish(true).unwrap_err().to_string(),
+            "1 check failed"
+        );
+
+        let report = [
+            "Resolving credentials… ✓",
+            "Connecting to IMAP backend… ✗",
+            "  cannot connect",
+            "  connection refused",
+            "",
+        ];
+        assert_eq!(printer.0, report.join("\n"));
+    }
+}
@@ -3,50 +3,88 @@ use std::path::PathBuf;
 use clap::Parser;
 use color_eyre::Result;
 
-use crate::{account::arg::name::AccountNameArg, config::TomlConfig};
+use crate::{
+    account::arg::{name::AccountNameArg, settings::AccountSettingsArgs},
+    config::TomlConfig,
+    printer::Printer,
+};
 
 /// Configure the given account.
 ///
 /// This command allows you to configure an existing account or to
-/// create a new one, using the wizard. The `wizard` cargo feature is
-/// required.
+/// create a new one, using the wizard. When settings are given as
+/// arguments, the wizard is skipped: missing settings are prompted
+/// when stdin is a terminal, otherwise the command fails. The
+/// `wizard` cargo feature is required.
 #[derive(Debug, Parser)]
 pub struct AccountConfigureCommand {
     #[command(flatten)]
     pub account: AccountNameArg,
+
+    /// Reset keyring entries of the given account.
+    ///
+    /// Stored secrets (IMAP, SMTP and PGP passwords, OAuth 2.0
+    /// tokens) are deleted from the keyring before running the
+    /// wizard, which prompts for new ones. The `keyring` cargo
+    /// feature is required.
+    #[cfg(feature = "keyring")]
+    #[arg(long, short)]
+    pub reset: bool,
+
+    /// Do not check the account before saving it.
+    ///
+    /// By default, credentials are resolved and backends are reached
+    /// once the wizard is done, and the wizard can be run again when
+    /// a check fails. Skipping checks is useful to configure an
+    /// account offline.
+    #[arg(long)]
+    pub skip_checks: bool,
+
+    #[command(flatten)]
+    pub settings: AccountSettingsArgs,
 }
 
 impl AccountConfigureCommand {
     #[cfg(feature = "wizard")]
     pub async fn execute(
         self,
-        mut config: TomlConfig,
-        config_path: Option<&PathBuf>,
+        printer: &mut impl Printer,
+        config: TomlConfig,
+        config_paths: &[PathBuf],
     ) -> Result<()> {
-        use pimalaya_tui::{himalaya::wizard, terminal::config::TomlConfig as _};
         use tracing::info;
 
+        use crate::account::wizard;
+
         info!("executing account configure command");
 
-        let path = match config_path {
-            Some(path) => path.clone(),
-            None => TomlConfig::default_path()?,
-        };
+        let path = TomlConfig::account_path(config_paths, &self.account.name)?;
 
         let account_name = Some(self.account.name.as_str());
 
-        let account_config = config
-            .accounts
-            .remove(&self.account.name)
-            .unwrap_or_default();
+        #[cfg(feature = "keyring")]
+        if self.reset {
+            if let Some(account_config) = config.accounts.get(&self.account.name) {
+                crate::account::reset_keyring_entries(account_config).await?;
+            }
+        }
 
-        wizard::edit(path, config, account_name, account_config).await?;
+        match self.settings.into_table()? {
+            Some(settings) => {
+                let name = &self.account.name;
+                wizard::configure(printer, &path, name, settings, self.skip_checks).await?
+            }
+            None => {
+                let config = config.himalaya;
+                wizard::edit(printer, &path, config, account_name, self.skip_checks).await?;
+            }
+        }
 
         Ok(())
     }
 
     #[cfg(not(feature = "wizard"))]
-    pub async fn execute(self, _: TomlConfig, _: Option<&PathBuf>) -> Result<()> {
+    pub async fn execute(self, _: &mut impl Printer, _: TomlConfig, _: &[PathBuf]) -> Result<()> {
         color_eyre::eyre::bail!("This command requires the `wizard` cargo feature to work");
     }
 }
@@ -1,39 +1,32 @@
-use std::{
-    io::{stdout, Write},
-    sync::Arc,
-};
+use std::sync::Arc;
 
 use clap::Parser;
-use color_eyre::{Result, Section};
+use color_eyre::Result;
+use email::config::Config;
 #[cfg(all(feature = "keyring", feature = "imap"))]
 use email::imap::config::ImapAuthConfig;
-#[cfg(feature = "imap")]
-use email::imap::ImapContextBuilder;
-#[cfg(feature = "maildir")]
-use email::maildir::MaildirContextBuilder;
-#[cfg(feature = "notmuch")]
-use email::notmuch::NotmuchContextBuilder;
-#[cfg(feature = "sendmail")]
-use email::sendmail::SendmailContextBuilder;
 #[cfg(all(feature = "keyring", feature = "smtp"))]
 use email::smtp::config::SmtpAuthConfig;
-#[cfg(feature = "smtp")]
-use email::smtp::SmtpContextBuilder;
-use email::{backend::BackendBuilder, config::Config};
+use pimalaya_tui::terminal::config::TomlConfig as _;
 #[cfg(feature = "keyring")]
 use pimalaya_tui::terminal::prompt;
-use pimalaya_tui::{
-    himalaya::config::{Backend, SendingBackend},
-    terminal::config::TomlConfig as _,
-};
 
-use crate::{account::arg::name::OptionalAccountNameArg, config::TomlConfig};
+use crate::{
+    account::{
+        arg::name::OptionalAccountNameArg,
+        check::{self, Checks},
+    },
+    config::TomlConfig,
+    printer::Printer,
+};
 
 /// Diagnose and fix the given account.
 ///
 /// This command diagnoses the given account and can even try to fix
-/// it. It mostly checks if the configuration is valid, if backends
-/// can be instanciated and if sessions work as expected.
+/// it. It checks if the configuration is valid, if credentials can be
+/// resolved, if backends can be reached and if folders can be listed.
+/// Each check is reported with a ✓ or a ✗ followed by the error, and
+/// the command fails if any check failed.
 #[derive(Debug, Parser)]
 pub struct AccountDoctorCommand {
     #[command(flatten)]
@@ -48,31 +41,34 @@ pub struct AccountDoctorCommand {
 }
 
 impl AccountDoctorCommand {
-    pub async fn execute(self, config: &TomlConfig) -> Result<()> {
-        let mut stdout = stdout();
-
-        if let Some(name) = self.account.name.as_ref() {
-            print!("Checking TOML configuration integrity for account {name}… ");
-        } else {
-            print!("Checking TOML configuration integrity for default account… ");
-        }
-
-        stdout.flush()?;
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        let mut checks = Checks::new(printer);
+
+        let step = match self.account.name.as_ref() {
+            Some(name) => format!("Checking TOML configuration of account {name}"),
+            None => String::from("Checking TOML configuration of default account"),
+        };
+
+        let configs = checks
+            .run(&step, async {
+                Ok(config
+                    .clone()
+                    .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
+                        c.account(name).ok()
+                    })?)
+            })
+            .await;
+
+        let Some((toml_account_config, account_config)) = configs else {
+            return checks.finish(self.fix);
+        };
 
-        let (toml_account_config, account_config) = config
-            .clone()
-            .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
-                c.account(name).ok()
-            })?;
         let account_config = Arc::new(account_config);
 
-        println!("OK");
-
         #[cfg(feature = "keyring")]
         if self.fix {
             if prompt::bool("Would you like to reset existing keyring entries?", false)? {
-                print!("Resetting keyring entries… ");
-                stdout.flush()?;
+                checks.printer().info("Resetting keyring entries… ")?;
 
                 #[cfg(feature = "imap")]
                 match toml_account_config.imap_auth_config() {
@@ -95,7 +91,7 @@ impl AccountDoctorCommand {
                     config.reset().await?;
                 }
 
-                println!("OK");
+                checks.printer().info("OK\n")?;
             }
 
             #[cfg(feature = "imap")]
@@ -140,94 +136,15 @@ impl AccountDoctorCommand {
             }
         }
 
-        match toml_account_config.backend {
-            #[cfg(feature = "maildir")]
-            Some(Backend::Maildir(mdir_config)) => {
-                print!("Checking Maildir integrity… ");
-                stdout.flush()?;
-
-                let ctx = MaildirContextBuilder::new(account_config.clone(), Arc::new(mdir_config));
-                BackendBuilder::new(account_config.clone(), ctx)
-                    .check_up()
-                    .await?;
-
-                println!("OK");
-            }
-            #[cfg(feature = "imap")]
-            Some(Backend::Imap(imap_config)) => {
-                print!("Checking IMAP integrity… ");
-                stdout.flush()?;
-
-                let ctx = ImapContextBuilder::new(account_config.clone(), Arc::new(imap_config))
-                    .with_pool_size(1);
-                let res = BackendBuilder::new(account_config.clone(), ctx)
-                    .check_up()
-                    .await;
-
-                if self.fix {
-                    res?;
-                } else {
-                    res.note("Run with --fix to (re)configure your account.")?;
-                }
-
-                println!("OK");
-            }
-            #[cfg(feature = "notmuch")]
-            Some(Backend::Notmuch(notmuch_config)) => {
-                print!("Checking Notmuch integrity… ");
-                stdout.flush()?;
-
-                let ctx =
-                    NotmuchContextBuilder::new(account_config.clone(), Arc::new(notmuch_config));
-                BackendBuilder::new(account_config.clone(), ctx)
-                    .check_up()
-                    .await?;
-
-                println!("OK");
-            }
-            _ => (),
-        }
-
-        let sending_backend = toml_account_config
-            .message
-            .and_then(|msg| msg.send)
-            .and_then(|send| send.backend);
-
-        match sending_backend {
-            #[cfg(feature = "smtp")]
-            Some(SendingBackend::Smtp(smtp_config)) => {
-                print!("Checking SMTP integrity… ");
-                stdout.flush()?;
-
-                let ctx = SmtpContextBuilder::new(account_config.clone(), Arc::new(smtp_config));
-                let res = BackendBuilder::new(account_config.clone(), ctx)
-                    .check_up()
-                    .await;
-
-                if self.fix {
-                    res?;
-                } else {
-                    res.note("Run with --fix to (re)configure your account.")?;
-                }
-
-                println!("OK");
-            }
-            #[cfg(feature = "sendmail")]
-            Some(SendingBackend::Sendmail(sendmail_config)) => {
-                print!("Checking Sendmail integrity… ");
-                stdout.flush()?;
-
-                let ctx =
-                    SendmailContextBuilder::new(account_config.clone(), Arc::new(sendmail_config));
-                BackendBuilder::new(account_config.clone(), ctx)
-                    .check_up()
-                    .await?;
-
-                println!("OK");
-            }
-            _ => (),
-        }
+        let extra_config = config.account_extra_config(&account_config.name);
+        check::check_account(
+            &mut checks,
+            toml_account_config,
+            &extra_config,
+            account_config,
+        )
+        .await;
 
-        Ok(())
+        checks.finish(self.fix)
     }
 }
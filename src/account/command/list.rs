@@ -1,17 +1,18 @@
 use clap::Parser;
 use color_eyre::Result;
-use pimalaya_tui::{
-    himalaya::config::{Accounts, AccountsTable},
-    terminal::cli::printer::Printer,
-};
 use tracing::info;
 
-use crate::config::TomlConfig;
+use crate::{
+    account::table::{AccountColumn, AccountsTable},
+    config::TomlConfig,
+    printer::Printer,
+};
 
 /// List all existing accounts.
 ///
 /// This command lists all the accounts defined in your TOML
-/// configuration file.
+/// configuration file, along with their email address, their
+/// backends and their defaultness.
 #[derive(Debug, Parser)]
 pub struct AccountListCommand {
     /// The maximum width the table should not exceed.
@@ -19,22 +20,34 @@ pub struct AccountListCommand {
     /// This argument will force the table not to exceed the given
     /// width, in pixels. Columns may shrink with ellipsis in order to
     /// fit the width.
+    ///
+    /// Defaults to the width of the terminal, or to the
+    /// ui.table.piped-width option when the output is not a terminal.
     #[arg(long = "max-width", short = 'w')]
     #[arg(name = "table_max_width", value_name = "PIXELS")]
     pub table_max_width: Option<u16>,
+
+    /// The columns of the table, in order.
+    ///
+    /// Columns are separated by commas (for example
+    /// "name,email,default"). The JSON output always contains all
+    /// the fields.
+    #[arg(long, value_enum, value_name = "COLUMNS", value_delimiter = ',')]
+    pub columns: Option<Vec<AccountColumn>>,
 }
 
 impl AccountListCommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing list accounts command");
 
-        let accounts = Accounts::from(config.accounts.iter());
-        let table = AccountsTable::from(accounts)
+        let table = AccountsTable::from(config.accounts.iter())
             .with_some_width(self.table_max_width)
+            .with_some_columns(self.columns)
             .with_some_preset(config.account_list_table_preset())
             .with_some_name_color(config.account_list_table_name_color())
             .with_some_backends_color(config.account_list_table_backends_color())
-            .with_some_default_color(config.account_list_table_default_color());
+            .with_some_default_color(config.account_list_table_default_color())
+            .with_table_config(config.table_config());
 
         printer.out(table)
     }
@@ -1,41 +1,50 @@
 mod configure;
 mod doctor;
 mod list;
+mod remove;
 
 use std::path::PathBuf;
 
 use clap::Subcommand;
 use color_eyre::Result;
-use pimalaya_tui::terminal::cli::printer::Printer;
 
-use crate::config::TomlConfig;
+use crate::{config::TomlConfig, printer::Printer};
 
 use self::{
     configure::AccountConfigureCommand, doctor::AccountDoctorCommand, list::AccountListCommand,
+    remove::AccountRemoveCommand,
 };
 
-/// Configure, list and diagnose your accounts.
+/// Configure, list, diagnose and remove your accounts.
 ///
 /// An account is a group of settings, identified by a unique
 /// name. This subcommand allows you to manage your accounts.
 #[derive(Debug, Subcommand)]
 pub enum AccountSubcommand {
-    Configure(AccountConfigureCommand),
+    Configure(Box<AccountConfigureCommand>),
     Doctor(AccountDoctorCommand),
     List(AccountListCommand),
+    Remove(AccountRemoveCommand),
 }
 
 impl AccountSubcommand {
+    /// Return `true` if the subcommand can create the configuration
+    /// file, which is the case of the non-interactive configuration.
+    pub fn creates_config(&self) -> bool {
+        matches!(self, Self::Configure(cmd) if !cmd.settings.is_empty())
+    }
+
     pub async fn execute(
         self,
         printer: &mut impl Printer,
         config: TomlConfig,
-        config_path: Option<&PathBuf>,
+        config_paths: &[PathBuf],
     ) -> Result<()> {
         match self {
-            Self::Configure(cmd) => cmd.execute(config, config_path).await,
-            Self::Doctor(cmd) => cmd.execute(&config).await,
+            Self::Configure(cmd) => cmd.execute(printer, config, config_paths).await,
+            Self::Doctor(cmd) => cmd.execute(printer, &config).await,
             Self::List(cmd) => cmd.execute(printer, &config).await,
+            Self::Remove(cmd) => cmd.execute(printer, config, config_paths).await,
         }
     }
 }
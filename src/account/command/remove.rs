@@ -0,0 +1,272 @@
+use std::{fs, path::PathBuf, process};
+
+use clap::Parser;
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use pimalaya_tui::terminal::prompt;
+use toml_edit::{value, DocumentMut, Item};
+use tracing::info;
+
+use crate::{
+    account::arg::name::AccountNameArg,
+    config::TomlConfig,
+    message::{html, mdn},
+    outbox::Outbox,
+    printer::Printer,
+};
+
+/// Remove the given account.
+///
+/// This command removes the account from the TOML configuration
+/// file defining it, while keeping the rest of the file as it is. Once the file
+/// is written, its keyring entries and its local data (outbox, read
+/// receipts, address book and HTML caches) are deleted as well. When
+/// the removed account was the default one, the first remaining
+/// account becomes the default.
+///
+/// Accounts defined in included files or in configuration
+/// directories are removed from the file defining them.
+/// The id aliases of local backends are kept: they are stored by the
+/// id mapper, which does not expose a way to drop them.
+#[derive(Debug, Parser)]
+pub struct AccountRemoveCommand {
+    #[command(flatten)]
+    pub account: AccountNameArg,
+
+    /// Remove the account without asking for confirmation.
+    #[arg(long, short)]
+    pub yes: bool,
+}
+
+impl AccountRemoveCommand {
+    pub async fn execute(
+        self,
+        printer: &mut impl Printer,
+        config: TomlConfig,
+        config_paths: &[PathBuf],
+    ) -> Result<()> {
+        info!("executing account remove command");
+
+        let name = &self.account.name;
+
+        let Some(account_config) = config.accounts.get(name) else {
+            bail!("cannot find account {name}");
+        };
+
+        let path = TomlConfig::account_path(config_paths, name)?;
+
+        let content = fs::read_to_string(&path)
+            .wrap_err_with(|| format!("cannot read configuration at {}", path.display()))?;
+        let mut doc: DocumentMut = content
+            .parse()
+            .wrap_err_with(|| format!("cannot parse configuration at {}", path.display()))?;
+
+        let accounts = doc
+            .get_mut("accounts")
+            .and_then(Item::as_table_like_mut)
+            .filter(|accounts| accounts.contains_key(name));
+
+        let Some(accounts) = accounts else {
+            let path = path.display();
+            printer.log(format!(
+                "Account {name} is not defined in a configuration file, \
+                 it cannot be removed from {path}.\n"
+            ))?;
+            return Ok(());
+        };
+
+        let outbox = Outbox::new(name)?;
+        let queued = outbox.list()?.len();
+
+        if !self.yes {
+            let mut confirm = format!("Do you really want to remove the account {name}?");
+
+            if queued > 0 {
+                confirm.push_str(&format!(" {queued} message(s) of its outbox will be lost."));
+            }
+
+            if !prompt::bool(confirm, false)? {
+                process::exit(0);
+            };
+        }
+
+        accounts.remove(name);
+
+        let has_default = config
+            .accounts
+            .iter()
+            .any(|(other, config)| other != name && config.default == Some(true));
+
+        let mut new_default = None;
+
+        if account_config.default == Some(true) && !has_default {
+            if let Some((other, table)) = accounts.iter_mut().next() {
+                if let Some(table) = table.as_table_like_mut() {
+                    table.insert("default", value(true));
+                    new_default = Some(other.get().to_owned());
+                }
+            }
+        }
+
+        fs::write(&path, doc.to_string())
+            .wrap_err_with(|| format!("cannot write configuration at {}", path.display()))?;
+
+        #[cfg(feature = "keyring")]
+        crate::account::reset_keyring_entries(account_config).await?;
+
+        outbox.remove_all()?;
+        mdn::remove_records(name)?;
+        html::remove_cache(name)?;
+
+        #[cfg(feature = "carddav")]
+        crate::address::carddav::remove_cache(name)?;
+
+        match new_default {
+            Some(other) => printer.log(format!("Account {other} is now the default account.\n"))?,
+            None if account_config.default == Some(true) && !has_default => {
+                printer.log("WARNING: no default account remains\n")?
+            }
+            None => (),
+        }
+
+        printer.info(format!("Account {name} successfully removed!\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::temp_dir, fs, path::PathBuf};
+
+    use uuid::Uuid;
+
+    use super::AccountRemoveCommand;
+    use crate::{
+        account::arg::name::AccountNameArg, config::TomlConfig, outbox::Outbox,
+        printer::StdoutPrinter,
+    };
+
+    /// A temporary configuration file defining accounts with unique
+    /// names, so that their local data does not clash with the user
+    /// one. Removed when dropped.
+    struct ConfigFile {
+        path: PathBuf,
+        id: Uuid,
+    }
+
+    impl ConfigFile {
+        /// Write the given configuration, whose `{id}` placeholders
+        /// are replaced by the unique id of the file.
+        fn new(content: &str) -> Self {
+            let id = Uuid::new_v4();
+            let path = temp_dir().join(format!("himalaya-config-{id}.toml"));
+            fs::write(&path, content.replace("{id}", &id.to_string())).unwrap();
+            Self { path, id }
+        }
+
+        fn name(&self, name: &str) -> String {
+            format!("{name}-{}", self.id)
+        }
+
+        fn read(&self) -> String {
+            fs::read_to_string(&self.path)
+                .unwrap()
+                .replace(&self.id.to_string(), "{id}")
+        }
+
+        /// Remove the given account, without confirmation.
+        async fn remove(&self, name: &str) -> color_eyre::Result<()> {
+            let content = fs::read_to_string(&self.path).unwrap();
+            let config: TomlConfig = toml::from_str(&content).unwrap();
+
+            let cmd = AccountRemoveCommand {
+                account: AccountNameArg {
+                    name: self.name(name),
+                },
+                yes: true,
+            };
+
+            let mut printer = StdoutPrinter::default();
+            cmd.execute(&mut printer, config, &[self.path.clone()])
+                .await
+        }
+    }
+
+    impl Drop for ConfigFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    const CONFIG: &str = r#"# Global options
+display-name = "Me"
+
+# The personal account
+[accounts.home-{id}]
+default = true
+email = "home@localhost"
+
+# The work account
+[accounts.work-{id}]
+email = "work@localhost" # with a comment
+
+[accounts.other-{id}]
+email = "other@localhost"
+"#;
+
+    #[tokio::test]
+    async fn remove_account_and_keep_the_rest() {
+        let file = ConfigFile::new(CONFIG);
+        file.remove("work").await.unwrap();
+
+        let expected = r#"# Global options
+display-name = "Me"
+
+# The personal account
+[accounts.home-{id}]
+default = true
+email = "home@localhost"
+
+[accounts.other-{id}]
+email = "other@localhost"
+"#;
+
+        assert_eq!(file.read(), expected);
+    }
+
+    #[tokio::test]
+    async fn move_default_to_next_account() {
+        let file = ConfigFile::new(CONFIG);
+        file.remove("home").await.unwrap();
+
+        let config = file.read();
+        assert!(!config.contains("[accounts.home-"), "{config}");
+        assert!(
+            config.contains("[accounts.work-{id}]\nemail = \"work@localhost\" # with a comment\ndefault = true\n"),
+            "{config}"
+        );
+        assert!(config.contains("[accounts.other-{id}]\nemail = \"other@localhost\"\n"));
+    }
+
+    #[tokio::test]
+    async fn remove_outbox() {
+        let file = ConfigFile::new(CONFIG);
+        let outbox = Outbox::new(&file.name("work")).unwrap();
+        outbox.push(b"Subject: queued\r\n\r\n", None).unwrap();
+
+        file.remove("work").await.unwrap();
+
+        assert!(outbox.list().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuse_unknown_account() {
+        let file = ConfigFile::new(CONFIG);
+
+        let err = file.remove("unknown").await.unwrap_err().to_string();
+
+        assert_eq!(err, format!("cannot find account {}", file.name("unknown")));
+        assert_eq!(file.read(), CONFIG);
+    }
+}
@@ -1,3 +1,361 @@
+use std::num::NonZeroU64;
+#[cfg(feature = "smtp")]
+use std::time::Duration;
+
+#[cfg(feature = "notmuch")]
+use email::notmuch::config::NotmuchConfig;
 use pimalaya_tui::himalaya::config::HimalayaTomlAccountConfig;
+use process::Command;
+use serde::Deserialize;
+
+#[cfg(feature = "carddav")]
+use crate::address::carddav::CardDavConfig;
+#[cfg(feature = "smtp")]
+use crate::message::send::smtp::{self, SmtpOptions};
+use crate::{
+    envelope::{arg::order::EnvelopeOrder, table::EnvelopeColumn},
+    message::send::{sendmail::SendmailRecipients, smtp::HeloHostname},
+    proxy::ProxyConfig,
+};
 
 pub type TomlAccountConfig = HimalayaTomlAccountConfig;
+
+/// The account configuration specific to the CLI.
+///
+/// Options live in the same `accounts.<name>` table as the ones from
+/// [`TomlAccountConfig`], which are shared with other Himalaya
+/// projects.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TomlAccountExtraConfig {
+    /// The command used to search addresses in the address book.
+    ///
+    /// The `%s` placeholder is replaced by the query.
+    pub address_book_cmd: Option<Command>,
+    /// Other email addresses of the account.
+    ///
+    /// They are never given as recipients of replies to all.
+    pub alternates: Option<Vec<String>>,
+    pub backend: Option<BackendExtraConfig>,
+    /// The CardDAV address book.
+    #[cfg(feature = "carddav")]
+    pub carddav: Option<CardDavConfig>,
+    pub envelope: Option<EnvelopeExtraConfig>,
+    pub hooks: Option<HooksExtraConfig>,
+    pub message: Option<MessageExtraConfig>,
+}
+
+impl TomlAccountExtraConfig {
+    pub fn is_backend_insecure(&self) -> bool {
+        self.backend
+            .as_ref()
+            .and_then(|backend| backend.insecure)
+            .unwrap_or_default()
+    }
+
+    pub fn backend_delimiter(&self) -> Option<char> {
+        self.backend.as_ref().and_then(|backend| backend.delimiter)
+    }
+
+    pub fn envelope_list_columns(&self) -> Option<Vec<EnvelopeColumn>> {
+        self.envelope
+            .as_ref()
+            .and_then(|envelope| envelope.list.as_ref())
+            .and_then(|list| list.columns.clone())
+    }
+
+    pub fn envelope_list_order(&self) -> EnvelopeOrder {
+        self.envelope
+            .as_ref()
+            .and_then(|envelope| envelope.list.as_ref())
+            .and_then(|list| list.order)
+            .unwrap_or_default()
+    }
+
+    #[cfg(feature = "notmuch")]
+    pub fn envelope_search_notmuch(&self) -> Option<&NotmuchConfig> {
+        self.envelope
+            .as_ref()
+            .and_then(|envelope| envelope.search.as_ref())
+            .and_then(|search| search.notmuch.as_ref())
+    }
+
+    pub fn pre_send_hook(&self) -> Option<&Command> {
+        self.hooks
+            .as_ref()
+            .and_then(|hooks| hooks.pre_send.as_ref())
+    }
+
+    pub fn alternates(&self) -> &[String] {
+        self.alternates.as_deref().unwrap_or_default()
+    }
+
+    pub fn message_reply_copy_headers(&self) -> &[String] {
+        self.message
+            .as_ref()
+            .and_then(|message| message.reply.as_ref())
+            .and_then(|reply| reply.copy_headers.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn message_forward_copy_headers(&self) -> &[String] {
+        self.message
+            .as_ref()
+            .and_then(|message| message.forward.as_ref())
+            .and_then(|forward| forward.copy_headers.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn sendmail_recipients(&self) -> SendmailRecipients {
+        self.message
+            .as_ref()
+            .and_then(|message| message.send.as_ref())
+            .and_then(|send| send.backend.as_ref())
+            .and_then(|backend| backend.recipients)
+            .unwrap_or_default()
+    }
+
+    pub fn smtp_proxy(&self) -> Option<&ProxyConfig> {
+        self.message
+            .as_ref()
+            .and_then(|message| message.send.as_ref())
+            .and_then(|send| send.backend.as_ref())
+            .and_then(|backend| backend.proxy.as_ref())
+    }
+
+    pub fn smtp_helo_hostname(&self) -> Option<&HeloHostname> {
+        self.message
+            .as_ref()
+            .and_then(|message| message.send.as_ref())
+            .and_then(|send| send.backend.as_ref())
+            .and_then(|backend| backend.helo_hostname.as_ref())
+    }
+
+    /// Get the options of the SMTP client of the CLI.
+    #[cfg(feature = "smtp")]
+    pub fn smtp_options(&self) -> SmtpOptions {
+        let backend = self
+            .message
+            .as_ref()
+            .and_then(|message| message.send.as_ref())
+            .and_then(|send| send.backend.as_ref());
+
+        let timeout = |secs: Option<NonZeroU64>| match secs {
+            Some(secs) => Duration::from_secs(secs.get()),
+            None => smtp::DEFAULT_TIMEOUT,
+        };
+
+        SmtpOptions {
+            helo_hostname: self.smtp_helo_hostname().cloned(),
+            proxy: self.smtp_proxy().cloned(),
+            accept_invalid_certs: backend
+                .and_then(|backend| backend.accept_invalid_certs)
+                .unwrap_or_default(),
+            accept_invalid_hostnames: backend
+                .and_then(|backend| backend.accept_invalid_hostnames)
+                .unwrap_or_default(),
+            connect_timeout: timeout(backend.and_then(|backend| backend.connect_timeout)),
+            timeout: timeout(backend.and_then(|backend| backend.timeout)),
+            retries: backend
+                .and_then(|backend| backend.retries)
+                .unwrap_or(smtp::DEFAULT_RETRIES),
+            retry_delay: backend
+                .and_then(|backend| backend.retry_delay)
+                .map(Duration::from_secs)
+                .unwrap_or(smtp::DEFAULT_RETRY_DELAY),
+        }
+    }
+
+    pub fn is_mdn_requested(&self) -> bool {
+        self.message
+            .as_ref()
+            .and_then(|message| message.request_mdn)
+            .unwrap_or_default()
+    }
+
+    pub fn is_message_read_mark_seen_enabled(&self) -> bool {
+        self.message
+            .as_ref()
+            .and_then(|message| message.read.as_ref())
+            .and_then(|read| read.mark_seen)
+            .unwrap_or(true)
+    }
+
+    pub fn is_send_queue_on_failure_enabled(&self) -> bool {
+        self.message
+            .as_ref()
+            .and_then(|message| message.send.as_ref())
+            .and_then(|send| send.queue_on_failure)
+            .unwrap_or_default()
+    }
+}
+
+/// The backend configuration specific to the CLI.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BackendExtraConfig {
+    /// Allow unencrypted connections to non-loopback hosts.
+    pub insecure: Option<bool>,
+    /// The hierarchy delimiter of IMAP folder names.
+    ///
+    /// Defaults to the delimiter announced by the server.
+    pub delimiter: Option<char>,
+}
+
+/// The envelope configuration specific to the CLI.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EnvelopeExtraConfig {
+    pub list: Option<ListEnvelopesExtraConfig>,
+    pub search: Option<SearchEnvelopesExtraConfig>,
+}
+
+/// The envelope listing configuration specific to the CLI.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ListEnvelopesExtraConfig {
+    /// The columns of the envelopes table, in order.
+    pub columns: Option<Vec<EnvelopeColumn>>,
+    /// The date order of envelopes without sort query.
+    pub order: Option<EnvelopeOrder>,
+}
+
+/// The envelope search configuration specific to the CLI.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SearchEnvelopesExtraConfig {
+    /// The notmuch index answering full-text searches.
+    ///
+    /// Used when the account backend is not notmuch, for example an
+    /// IMAP account synchronized to a Maildir indexed by notmuch.
+    #[cfg(feature = "notmuch")]
+    pub notmuch: Option<NotmuchConfig>,
+}
+
+/// The hooks configuration specific to the CLI.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HooksExtraConfig {
+    /// The command every outgoing message is piped to before being
+    /// sent.
+    ///
+    /// A non-empty output replaces the message, a non-zero exit
+    /// status aborts the sending.
+    pub pre_send: Option<Command>,
+}
+
+/// The message configuration specific to the CLI.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MessageExtraConfig {
+    /// Request a read receipt for composed and sent messages.
+    pub request_mdn: Option<bool>,
+    pub read: Option<ReadMessageExtraConfig>,
+    pub reply: Option<MessageTemplateExtraConfig>,
+    pub forward: Option<MessageTemplateExtraConfig>,
+    pub send: Option<SendMessageExtraConfig>,
+}
+
+/// The message reading configuration specific to the CLI.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReadMessageExtraConfig {
+    /// Apply the seen flag to messages when reading them.
+    pub mark_seen: Option<bool>,
+}
+
+/// The reply and forward templates configuration specific to the
+/// CLI.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MessageTemplateExtraConfig {
+    /// Headers of the original message to copy into the template.
+    ///
+    /// Names are case-insensitive and may contain `*` wildcards.
+    pub copy_headers: Option<Vec<String>>,
+}
+
+/// The message sending configuration specific to the CLI.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SendMessageExtraConfig {
+    pub backend: Option<SendingBackendExtraConfig>,
+    /// Queue messages in the outbox when they cannot be sent.
+    pub queue_on_failure: Option<bool>,
+}
+
+/// The sending backend configuration specific to the CLI.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SendingBackendExtraConfig {
+    /// How recipients are given to the sendmail command.
+    pub recipients: Option<SendmailRecipients>,
+    /// The hostname given to the SMTP server in the EHLO command.
+    pub helo_hostname: Option<HeloHostname>,
+    /// Accept any TLS certificate of the SMTP server.
+    pub accept_invalid_certs: Option<bool>,
+    /// Accept valid TLS certificates of the SMTP server issued for
+    /// another hostname.
+    pub accept_invalid_hostnames: Option<bool>,
+    /// The maximum amount of time to open a session with the SMTP
+    /// server, in seconds.
+    pub connect_timeout: Option<NonZeroU64>,
+    /// The maximum amount of time the SMTP server is given to answer
+    /// each command, in seconds.
+    pub timeout: Option<NonZeroU64>,
+    /// The number of times sending a message is retried after a
+    /// transient failure.
+    pub retries: Option<u32>,
+    /// The delay before the first retry, in seconds, doubled before
+    /// each next one.
+    pub retry_delay: Option<u64>,
+    /// The proxy SMTP connections go through.
+    ///
+    /// IMAP connections are opened by the IMAP backend itself, which
+    /// cannot go through it.
+    pub proxy: Option<ProxyConfig>,
+}
+
+#[cfg(all(test, feature = "smtp"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::TomlAccountExtraConfig;
+    use crate::message::send::smtp::SmtpOptions;
+
+    #[test]
+    fn default_smtp_options() {
+        let config: TomlAccountExtraConfig = toml::from_str("").unwrap();
+        assert_eq!(config.smtp_options(), SmtpOptions::default());
+    }
+
+    #[test]
+    fn parse_smtp_options() {
+        let config: TomlAccountExtraConfig = toml::from_str(concat!(
+            "message.send.backend.accept-invalid-hostnames = true\n",
+            "message.send.backend.connect-timeout = 5\n",
+            "message.send.backend.timeout = 120\n",
+            "message.send.backend.retries = 5\n",
+            "message.send.backend.retry-delay = 0\n",
+            "message.send.backend.proxy = \"socks5h://127.0.0.1:9050\"\n",
+        ))
+        .unwrap();
+
+        let options = config.smtp_options();
+        assert!(!options.accept_invalid_certs);
+        assert!(options.accept_invalid_hostnames);
+        assert_eq!(options.connect_timeout, Duration::from_secs(5));
+        assert_eq!(options.timeout, Duration::from_secs(120));
+        assert_eq!(options.retries, 5);
+        assert_eq!(options.retry_delay, Duration::ZERO);
+
+        let proxy = options.proxy.unwrap();
+        assert_eq!(proxy.to_string(), "socks5h://127.0.0.1:9050");
+    }
+
+    #[test]
+    fn refuse_zero_timeout() {
+        let config = toml::from_str::<TomlAccountExtraConfig>("message.send.backend.timeout = 0");
+        assert!(config.is_err());
+    }
+}
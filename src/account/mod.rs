@@ -1,3 +1,46 @@
 pub mod arg;
+pub mod check;
 pub mod command;
 pub mod config;
+pub mod table;
+#[cfg(feature = "wizard")]
+pub mod wizard;
+
+#[cfg(feature = "keyring")]
+use color_eyre::Result;
+
+#[cfg(feature = "keyring")]
+use self::config::TomlAccountConfig;
+
+/// Delete keyring entries referenced by the given account
+/// configuration.
+#[cfg(feature = "keyring")]
+pub async fn reset_keyring_entries(config: &TomlAccountConfig) -> Result<()> {
+    #[cfg(feature = "imap")]
+    use email::imap::config::ImapAuthConfig;
+    #[cfg(feature = "smtp")]
+    use email::smtp::config::SmtpAuthConfig;
+
+    #[cfg(feature = "imap")]
+    match config.imap_auth_config() {
+        Some(ImapAuthConfig::Password(config)) => config.reset().await?,
+        #[cfg(feature = "oauth2")]
+        Some(ImapAuthConfig::OAuth2(config)) => config.reset().await?,
+        _ => (),
+    }
+
+    #[cfg(feature = "smtp")]
+    match config.smtp_auth_config() {
+        Some(SmtpAuthConfig::Password(config)) => config.reset().await?,
+        #[cfg(feature = "oauth2")]
+        Some(SmtpAuthConfig::OAuth2(config)) => config.reset().await?,
+        _ => (),
+    }
+
+    #[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+    if let Some(config) = &config.pgp {
+        config.reset().await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,230 @@
+use std::{collections::hash_map::Iter, fmt};
+
+use clap::ValueEnum;
+use comfy_table::{Cell, ContentArrangement, Row, Table};
+use crossterm::style::Color;
+use pimalaya_tui::himalaya::config::ListAccountsTableConfig;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{
+    account::config::TomlAccountConfig,
+    table::{Column, TableConfig},
+};
+
+/// The default columns of the accounts table.
+const COLUMNS: [AccountColumn; 5] = [
+    AccountColumn::Name,
+    AccountColumn::Email,
+    AccountColumn::Backend,
+    AccountColumn::Sender,
+    AccountColumn::Default,
+];
+
+/// The column of the accounts table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccountColumn {
+    /// The account name.
+    Name,
+    /// The email address.
+    Email,
+    /// The backend and the sending backend, comma-separated.
+    Backends,
+    /// The backend, like IMAP or Maildir.
+    Backend,
+    /// The sending backend, like SMTP or Sendmail.
+    Sender,
+    /// The default account marker.
+    Default,
+}
+
+impl AccountColumn {
+    /// Get the declaration of the column.
+    pub const fn column(self) -> Column {
+        match self {
+            Self::Name => Column::left("name", "NAME"),
+            Self::Email => Column::left("email", "EMAIL"),
+            Self::Backends => Column::left("backends", "BACKENDS"),
+            Self::Backend => Column::left("backend", "BACKEND"),
+            Self::Sender => Column::left("sender", "SENDER"),
+            Self::Default => Column::left("default", "DEFAULT"),
+        }
+    }
+}
+
+/// The printable account.
+///
+/// The `backend` field joins both backends, as it did before the
+/// dedicated `default_backend` and `send_backend` fields.
+#[derive(Clone, Debug, Serialize)]
+pub struct Account {
+    pub name: String,
+    pub email: String,
+    pub backend: String,
+    pub default_backend: Option<String>,
+    pub send_backend: Option<String>,
+    pub default: bool,
+}
+
+impl Account {
+    pub fn new(name: &str, config: &TomlAccountConfig) -> Self {
+        let default_backend = config.backend.as_ref().map(ToString::to_string);
+        let send_backend = config.message_send_backend().map(ToString::to_string);
+
+        let backend = [&default_backend, &send_backend]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        Self {
+            name: name.to_owned(),
+            email: config.email.clone(),
+            backend,
+            default_backend,
+            send_backend,
+            default: config.default.unwrap_or_default(),
+        }
+    }
+}
+
+/// The printable table of accounts, sorted by name.
+pub struct AccountsTable {
+    accounts: Vec<Account>,
+    width: Option<u16>,
+    columns: Option<Vec<AccountColumn>>,
+    config: ListAccountsTableConfig,
+    table_config: TableConfig,
+}
+
+impl AccountsTable {
+    pub fn with_some_width(mut self, width: Option<u16>) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Show the given columns, in the given order.
+    ///
+    /// Defaults to the name, email, backend, sender and default
+    /// columns.
+    pub fn with_some_columns(mut self, columns: Option<Vec<AccountColumn>>) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    pub fn with_some_preset(mut self, preset: Option<String>) -> Self {
+        self.config.preset = preset;
+        self
+    }
+
+    pub fn with_some_name_color(mut self, color: Option<Color>) -> Self {
+        self.config.name_color = color;
+        self
+    }
+
+    pub fn with_some_backends_color(mut self, color: Option<Color>) -> Self {
+        self.config.backends_color = color;
+        self
+    }
+
+    pub fn with_some_default_color(mut self, color: Option<Color>) -> Self {
+        self.config.default_color = color;
+        self
+    }
+
+    pub fn with_table_config(mut self, config: TableConfig) -> Self {
+        self.table_config = config;
+        self
+    }
+}
+
+impl From<Iter<'_, String, TomlAccountConfig>> for AccountsTable {
+    fn from(configs: Iter<'_, String, TomlAccountConfig>) -> Self {
+        let mut accounts: Vec<_> = configs
+            .map(|(name, config)| Account::new(name, config))
+            .collect();
+
+        accounts.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            accounts,
+            width: None,
+            columns: None,
+            config: Default::default(),
+            table_config: Default::default(),
+        }
+    }
+}
+
+impl fmt::Display for AccountsTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns = match &self.columns {
+            Some(columns) => columns.clone(),
+            None => COLUMNS.to_vec(),
+        };
+
+        let mut table = Table::new();
+
+        table
+            .load_preset(self.config.preset())
+            .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+            .add_rows(
+                self.accounts
+                    .iter()
+                    .map(|account| to_row(account, &columns, &self.config, &self.table_config)),
+            );
+
+        let columns: Vec<Column> = columns.iter().map(|col| col.column()).collect();
+
+        self.table_config.apply(&mut table, &columns, self.width);
+
+        writeln!(f)?;
+        write!(f, "{table}")?;
+        writeln!(f)?;
+        Ok(())
+    }
+}
+
+impl Serialize for AccountsTable {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.accounts.serialize(serializer)
+    }
+}
+
+fn to_row(
+    account: &Account,
+    columns: &[AccountColumn],
+    config: &ListAccountsTableConfig,
+    table_config: &TableConfig,
+) -> Row {
+    let mut row = Row::new();
+    row.max_height(1);
+
+    for column in columns {
+        let cell = match column {
+            AccountColumn::Name => Cell::new(&account.name).fg(config.name_color()),
+            AccountColumn::Email => Cell::new(&account.email),
+            AccountColumn::Backends => Cell::new(&account.backend).fg(config.backends_color()),
+            AccountColumn::Backend => {
+                let backend = account.default_backend.as_deref().unwrap_or_default();
+                Cell::new(backend).fg(config.backends_color())
+            }
+            AccountColumn::Sender => {
+                let backend = account.send_backend.as_deref().unwrap_or_default();
+                Cell::new(backend).fg(config.backends_color())
+            }
+            AccountColumn::Default => {
+                let default = if account.default { "yes" } else { "" };
+                Cell::new(default).fg(config.default_color())
+            }
+        };
+
+        row.add_cell(table_config.style(column.column().key, cell));
+    }
+
+    row
+}
@@ -0,0 +1,573 @@
+//! Module dedicated to the account wizard.
+//!
+//! The wizard itself comes from Pimalaya TUI. This module checks the
+//! account once configured, so that a broken configuration is not
+//! discovered on the first real command, and preserves the rest of
+//! the configuration file. When the SMTP server presents an invalid
+//! certificate, the wizard offers to accept it.
+
+use std::{
+    fs,
+    io::{stdin, IsTerminal},
+    path::Path,
+    sync::Arc,
+};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use email::config::Config;
+use pimalaya_tui::{
+    himalaya::{config::HimalayaTomlConfig, wizard},
+    terminal::{config::TomlConfig as _, prompt},
+};
+use toml_edit::{DocumentMut, Item, Table, TableLike, Value};
+
+use crate::{
+    account::{
+        arg::settings,
+        check::{self, Checks},
+        config::TomlAccountExtraConfig,
+    },
+    config::TomlConfig,
+    printer::Printer,
+};
+
+/// Configure the given account using the wizard, then check it.
+///
+/// When a check fails, the wizard can be run again with the previous
+/// answers as defaults. If the user neither edits the account again
+/// nor keeps it, the previous configuration file is restored.
+///
+/// When the SMTP check fails because of an invalid certificate, the
+/// user is first offered to accept it, in which case the matching
+/// option is enabled and the account checked again.
+///
+/// Only the options changed by the wizard are written back to the
+/// previous configuration file, so that its comments, its layout and
+/// the options unknown to the wizard are kept.
+pub async fn edit(
+    printer: &mut impl Printer,
+    path: &Path,
+    config: HimalayaTomlConfig,
+    account_name: Option<&str>,
+    skip_checks: bool,
+) -> Result<HimalayaTomlConfig> {
+    let backup = fs::read_to_string(path).ok();
+
+    let mut others = config.clone();
+    let mut account_config = account_name
+        .and_then(|name| others.accounts.remove(name))
+        .unwrap_or_default();
+
+    // options accepting invalid certificates, enabled by the user
+    let mut accepted = toml::Table::new();
+    let mut edited_name = None;
+
+    let edited = loop {
+        let edited = wizard::edit(path, others.clone(), account_name, account_config).await?;
+
+        if skip_checks {
+            break edited;
+        }
+
+        // the edited account is the only one not present before,
+        // since it has been removed from the other accounts
+        let edited_account = edited
+            .accounts
+            .iter()
+            .find(|(name, _)| !others.accounts.contains_key(*name));
+
+        let Some((name, edited_account)) = edited_account else {
+            break edited;
+        };
+
+        edited_name = Some(name.clone());
+
+        let checked = loop {
+            let mut toml_config = TomlConfig {
+                himalaya: edited.clone(),
+                ..Default::default()
+            };
+
+            let extra: TomlAccountExtraConfig = toml::Value::Table(accepted.clone()).try_into()?;
+            toml_config.extras.insert(name.clone(), extra);
+
+            let checks = check(printer, toml_config, name).await?;
+
+            if checks.ok {
+                break true;
+            }
+
+            let Some(option) = checks.invalid_certificate else {
+                break false;
+            };
+
+            let key = format!("message.send.backend.{option}");
+
+            if settings::get(&accepted, &key).is_some() {
+                break false;
+            }
+
+            printer.info("\n")?;
+
+            let confirm = format!(
+                "The certificate of the SMTP server is not valid. \
+                 Would you like to accept it anyway ({key})?"
+            );
+
+            if !prompt::bool(confirm, false)? {
+                break false;
+            }
+
+            settings::insert(&mut accepted, &key, toml::Value::Boolean(true));
+        };
+
+        if checked {
+            break edited;
+        }
+
+        printer.info("\n")?;
+
+        if prompt::bool("Would you like to edit the account again?", true)? {
+            account_config = edited_account.clone();
+            continue;
+        }
+
+        if prompt::bool("Would you like to keep the configuration anyway?", false)? {
+            break edited;
+        }
+
+        match &backup {
+            Some(backup) => fs::write(path, backup)?,
+            None => fs::remove_file(path)?,
+        }
+
+        bail!("account configuration discarded, use --skip-checks to configure it offline");
+    };
+
+    // the wizard may have saved the configuration somewhere else
+    if let Some(backup) = backup {
+        if fs::read_to_string(path).is_ok_and(|content| content != backup) {
+            let prev = toml::Table::try_from(&config)?;
+            let next = toml::Table::try_from(&edited)?;
+            fs::write(path, merge(&backup, &prev, &next)?)?;
+        }
+    }
+
+    if let (Some(name), false) = (edited_name, accepted.is_empty()) {
+        accept_certificates(path, &name, accepted)?;
+    }
+
+    Ok(edited)
+}
+
+/// Enable the given options accepting invalid certificates in the
+/// given account of the given configuration file.
+fn accept_certificates(path: &Path, account_name: &str, options: toml::Table) -> Result<()> {
+    let doc = fs::read_to_string(path)?;
+    let prev: toml::Table = doc
+        .parse()
+        .wrap_err_with(|| format!("cannot parse config file at {}", path.display()))?;
+
+    let mut next = prev.clone();
+    let account = next
+        .get_mut("accounts")
+        .and_then(|accounts| accounts.get_mut(account_name));
+
+    if let Some(toml::Value::Table(account)) = account {
+        settings::merge(account, options);
+    }
+
+    fs::write(path, merge(&doc, &prev, &next)?)?;
+    Ok(())
+}
+
+/// Configure the given account using the given settings, without
+/// the wizard.
+///
+/// Settings are merged over the current options of the account.
+/// Missing options are prompted when stdin is a terminal, otherwise
+/// the configuration fails with the list of the missing arguments.
+/// The account is saved only once checked, unless checks are
+/// skipped.
+pub async fn configure(
+    printer: &mut impl Printer,
+    path: &Path,
+    account_name: &str,
+    settings: toml::Table,
+    skip_checks: bool,
+) -> Result<()> {
+    let backup = fs::read_to_string(path).ok();
+    let prev: toml::Table = match &backup {
+        Some(backup) => backup
+            .parse()
+            .wrap_err_with(|| format!("cannot parse config file at {}", path.display()))?,
+        None => toml::Table::new(),
+    };
+
+    let mut account = prev
+        .get("accounts")
+        .and_then(|accounts| accounts.get(account_name))
+        .and_then(toml::Value::as_table)
+        .cloned()
+        .unwrap_or_default();
+
+    settings::merge(&mut account, settings);
+    complete(&mut account, account_name)?;
+
+    let mut next = prev.clone();
+    let accounts = next
+        .entry("accounts")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+
+    if let toml::Value::Table(accounts) = accounts {
+        accounts.insert(account_name.to_owned(), toml::Value::Table(account));
+    }
+
+    let toml_config: TomlConfig = toml::Value::Table(next.clone())
+        .try_into()
+        .wrap_err_with(|| format!("cannot parse settings of account {account_name}"))?;
+
+    if !skip_checks && !check(printer, toml_config, account_name).await?.ok {
+        bail!("account configuration discarded, use --skip-checks to configure it offline");
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let doc = merge(backup.as_deref().unwrap_or_default(), &prev, &next)?;
+    fs::write(path, doc)?;
+
+    printer.info(format!(
+        "\nAccount {account_name} configured at {}\n",
+        path.display()
+    ))
+}
+
+/// The outcome of the checks of an account.
+struct Checked {
+    /// All checks passed.
+    ok: bool,
+    /// The option accepting the certificate refused by the SMTP
+    /// server, if any.
+    invalid_certificate: Option<&'static str>,
+}
+
+/// Check the given account of the given configuration.
+async fn check(printer: &mut impl Printer, toml_config: TomlConfig, name: &str) -> Result<Checked> {
+    printer.info(format!("\nChecking account {name}…\n"))?;
+
+    let mut checks = Checks::new(printer);
+
+    let extra_config = toml_config.account_extra_config(name);
+
+    let configs = checks
+        .run("Checking TOML configuration", async {
+            Ok(toml_config
+                .into_account_configs(Some(name), |c: &Config, name| c.account(name).ok())?)
+        })
+        .await;
+
+    if let Some((toml_account_config, account_config)) = configs {
+        let account_config = Arc::new(account_config);
+        check::check_account(
+            &mut checks,
+            toml_account_config,
+            &extra_config,
+            account_config,
+        )
+        .await;
+    }
+
+    #[cfg(feature = "smtp")]
+    let invalid_certificate = checks.invalid_certificate();
+    #[cfg(not(feature = "smtp"))]
+    let invalid_certificate = None;
+
+    Ok(Checked {
+        ok: checks.is_ok(),
+        invalid_certificate,
+    })
+}
+
+/// An account option required to configure an account.
+struct Required {
+    key: &'static str,
+    arg: &'static str,
+    prompt: &'static str,
+}
+
+/// Get the options required by the given account settings that are
+/// still missing.
+///
+/// Required options depend on the backends of the account, so that
+/// they need to be computed again once backends are known.
+fn missing(account: &toml::Table) -> Vec<Required> {
+    let mut missing = Vec::new();
+    let kind = |key| settings::get(account, key).and_then(toml::Value::as_str);
+
+    let mut require = |key, arg, prompt| {
+        if settings::get(account, key).is_none() {
+            missing.push(Required { key, arg, prompt })
+        }
+    };
+
+    require("email", "--email", "Email address");
+    require(
+        "backend.type",
+        "--backend",
+        "Backend (imap, maildir or notmuch)",
+    );
+
+    match kind("backend.type") {
+        Some("imap") => {
+            require("backend.host", "--imap-host", "IMAP host");
+            require(
+                "backend.auth",
+                "--imap-password-cmd",
+                "IMAP password command",
+            );
+        }
+        Some("maildir") => {
+            require(
+                "backend.root-dir",
+                "--maildir-root-dir",
+                "Maildir root directory",
+            );
+        }
+        _ => (),
+    }
+
+    require(
+        "message.send.backend.type",
+        "--sending-backend",
+        "Sending backend (smtp or sendmail)",
+    );
+
+    if kind("message.send.backend.type") == Some("smtp") {
+        require("message.send.backend.host", "--smtp-host", "SMTP host");
+        require(
+            "message.send.backend.auth",
+            "--smtp-password-cmd",
+            "SMTP password command",
+        );
+    }
+
+    missing
+}
+
+/// Complete the given account settings.
+///
+/// Missing options are prompted when stdin is a terminal, then ports
+/// and logins default to the ones matching the encryption and the
+/// email address.
+fn complete(account: &mut toml::Table, account_name: &str) -> Result<()> {
+    loop {
+        let missing = missing(account);
+
+        if missing.is_empty() {
+            break;
+        }
+
+        if !stdin().is_terminal() {
+            let args: Vec<_> = missing.iter().map(|required| required.arg).collect();
+            bail!(
+                "cannot configure account {account_name}: missing {}",
+                args.join(", ")
+            );
+        }
+
+        for required in missing {
+            let answer = prompt::text(format!("{}:", required.prompt), None)?;
+
+            let value = if required.key.ends_with(".auth") {
+                settings::to_password_cmd(answer)
+            } else {
+                toml::Value::String(answer)
+            };
+
+            settings::insert(account, required.key, value);
+        }
+    }
+
+    let email = account.get("email").cloned();
+    let servers = [
+        ("backend", "imap", [993, 143, 143]),
+        ("message.send.backend", "smtp", [465, 587, 25]),
+    ];
+
+    for (key, kind, [tls, start_tls, none]) in servers {
+        let get = |name: &str| {
+            let value = settings::get(account, &format!("{key}.{name}"));
+            value.map(|value| value.as_str().unwrap_or_default().to_owned())
+        };
+
+        if get("type").as_deref() != Some(kind) {
+            continue;
+        }
+
+        let port = match get("encryption.type").as_deref() {
+            Some("start-tls") => start_tls,
+            Some("none") => none,
+            _ => tls,
+        };
+
+        let (has_port, has_login) = (get("port").is_some(), get("login").is_some());
+
+        if !has_port {
+            settings::insert(account, &format!("{key}.port"), toml::Value::Integer(port));
+        }
+
+        if let (false, Some(email)) = (has_login, &email) {
+            settings::insert(account, &format!("{key}.login"), email.clone());
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply the changes between the given tables to the given TOML
+/// document.
+fn merge(doc: &str, prev: &toml::Table, next: &toml::Table) -> Result<String> {
+    let mut doc: DocumentMut = doc.parse()?;
+    merge_table(doc.as_table_mut(), prev, next, 0)?;
+    Ok(doc.to_string())
+}
+
+/// Apply the changes between the given tables to the given TOML
+/// table, at the given depth.
+///
+/// Keys unknown to both tables are left untouched. New tables follow
+/// the layout of the wizard: accounts are standard tables, while
+/// their options are dotted keys.
+fn merge_table(
+    doc: &mut dyn TableLike,
+    prev: &toml::Table,
+    next: &toml::Table,
+    depth: usize,
+) -> Result<()> {
+    for key in prev.keys() {
+        if !next.contains_key(key) {
+            doc.remove(key);
+        }
+    }
+
+    for (key, value) in next {
+        let prev_value = prev.get(key);
+
+        if prev_value == Some(value) {
+            continue;
+        }
+
+        let toml::Value::Table(table) = value else {
+            let mut value: Value = value.to_string().parse()?;
+
+            // keep the comments around the previous value
+            if let Some(Item::Value(prev_value)) = doc.get_mut(key) {
+                *value.decor_mut() = prev_value.decor().clone();
+                *prev_value = value;
+            } else {
+                doc.insert(key, Item::Value(value));
+            }
+
+            continue;
+        };
+
+        let empty = toml::Table::new();
+        let prev_table = match prev_value {
+            Some(toml::Value::Table(prev_table)) => prev_table,
+            _ => &empty,
+        };
+
+        if let Some(doc_table) = doc.get_mut(key).and_then(Item::as_table_like_mut) {
+            merge_table(doc_table, prev_table, table, depth + 1)?;
+            continue;
+        }
+
+        let mut doc_table = Table::new();
+        doc_table.set_implicit(depth == 0);
+        doc_table.set_dotted(depth > 1);
+        merge_table(&mut doc_table, &empty, table, depth + 1)?;
+        doc.insert(key, Item::Table(doc_table));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge;
+
+    const DOC: &str = r#"# Himalaya configuration
+downloads-dir = "~/Downloads"
+
+# The work account
+[accounts.work]
+default = true
+# The address of the account
+email = "work@localhost"
+display-name = "Work" # shown in the From header
+backend.type = "maildir"
+backend.root-dir = "~/Mail/work"
+# The signature of the account
+signature = "Regards"
+"#;
+
+    #[test]
+    fn keep_unchanged_file() {
+        let prev: toml::Table = toml::from_str(DOC).unwrap();
+        assert_eq!(merge(DOC, &prev, &prev).unwrap(), DOC);
+    }
+
+    #[test]
+    fn merge_changes() {
+        let prev: toml::Table = toml::from_str(DOC).unwrap();
+
+        let mut next = prev.clone();
+        let accounts = next["accounts"].as_table_mut().unwrap();
+
+        let work = accounts["work"].as_table_mut().unwrap();
+        work.insert("email".into(), "work@example.org".into());
+        work.insert("display-name".into(), "Pro".into());
+        work.remove("signature");
+
+        let home: toml::Table = toml::from_str(
+            r#"
+            email = "home@localhost"
+            backend.type = "imap"
+            backend.host = "localhost"
+            "#,
+        )
+        .unwrap();
+        accounts.insert("home".into(), home.into());
+
+        let doc = merge(DOC, &prev, &next).unwrap();
+        assert_eq!(toml::from_str::<toml::Table>(&doc).unwrap(), next);
+
+        // comments and untouched options are preserved
+        assert!(doc.starts_with("# Himalaya configuration\n"), "{doc}");
+        assert!(
+            doc.contains("\n# The work account\n[accounts.work]\n"),
+            "{doc}"
+        );
+        assert!(doc.contains("\nbackend.type = \"maildir\"\n"), "{doc}");
+
+        // edited options keep their comments
+        let email = "\n# The address of the account\nemail = \"work@example.org\"\n";
+        assert!(doc.contains(email), "{doc}");
+        let display_name = "\ndisplay-name = \"Pro\" # shown in the From header\n";
+        assert!(doc.contains(display_name), "{doc}");
+
+        // removed options are removed with their comments
+        assert!(!doc.contains("signature"), "{doc}");
+
+        // new accounts follow the layout of the wizard
+        assert!(doc.contains("\n[accounts.home]\n"), "{doc}");
+        assert!(doc.contains("\nbackend.type = \"imap\"\n"), "{doc}");
+        assert!(doc.contains("\nbackend.host = \"localhost\"\n"), "{doc}");
+        assert!(!doc.contains("[accounts]"), "{doc}");
+    }
+}
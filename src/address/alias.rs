@@ -0,0 +1,278 @@
+//! Module dedicated to recipient aliases.
+//!
+//! Aliases are short names standing for one or many recipients, set
+//! in the global `aliases` table of the configuration. They are
+//! expanded in the To, Cc and Bcc headers of messages and templates
+//! before they are compiled or sent. Members of an alias can be
+//! addresses or other aliases.
+
+use std::{collections::HashMap, slice};
+
+use color_eyre::{eyre::bail, Result};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::{address::Address, message::header, printer::Printer};
+
+/// The headers whose aliases are expanded.
+const HEADERS: [&str; 3] = ["To", "Cc", "Bcc"];
+
+/// The recipient aliases, by name.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct Aliases(HashMap<String, Alias>);
+
+/// The members of an alias.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Alias {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Alias {
+    fn members(&self) -> &[String] {
+        match self {
+            Self::One(member) => slice::from_ref(member),
+            Self::Many(members) => members,
+        }
+    }
+}
+
+impl Aliases {
+    /// Return `true` if no alias is configured.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Find the alias matching the given name, case-insensitively.
+    fn get(&self, name: &str) -> Option<(&String, &Alias)> {
+        self.0
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+    }
+
+    /// Expand the given alias into addresses, recursively.
+    ///
+    /// Fails when the alias is unknown, when one of its members is
+    /// neither an address nor an alias, or when aliases refer to each
+    /// other in a cycle.
+    pub fn expand(&self, name: &str) -> Result<Vec<String>> {
+        let mut addrs = Vec::new();
+        self.expand_into(name, &mut Vec::new(), &mut addrs)?;
+        Ok(addrs)
+    }
+
+    fn expand_into<'a>(
+        &'a self,
+        name: &str,
+        path: &mut Vec<&'a str>,
+        addrs: &mut Vec<String>,
+    ) -> Result<()> {
+        let Some((key, alias)) = self.get(name) else {
+            match path.first() {
+                Some(root) => bail!("cannot expand alias {root}: cannot find alias {name:?}"),
+                None => bail!("cannot find alias {name:?}"),
+            }
+        };
+
+        if path.iter().any(|prev| prev.eq_ignore_ascii_case(key)) {
+            let root = path[0];
+            let cycle = path.join(" -> ");
+            bail!("cannot expand alias {root}: cycle detected ({cycle} -> {key})");
+        }
+
+        path.push(key);
+
+        for member in alias.members() {
+            let member = member.trim();
+
+            if is_address(member) {
+                if !addrs.iter().any(|addr| addr.eq_ignore_ascii_case(member)) {
+                    addrs.push(member.to_owned());
+                }
+            } else if !member.is_empty() {
+                self.expand_into(member, path, addrs)?;
+            }
+        }
+
+        path.pop();
+        Ok(())
+    }
+
+    /// Expand the aliases of the given recipients header value.
+    ///
+    /// Returns the expanded recipients, or `None` if the value has no
+    /// alias. Recipients that are not addresses must be aliases,
+    /// otherwise the expansion fails: such recipients would bounce.
+    pub fn expand_recipients(&self, value: &str) -> Result<Option<Vec<String>>> {
+        let mut expanded = false;
+        let mut recipients = Vec::new();
+
+        for recipient in split(value) {
+            if recipient.is_empty() || is_address(recipient) {
+                recipients.push(recipient.to_owned());
+                continue;
+            }
+
+            if self.get(recipient).is_none() {
+                bail!("cannot find alias {recipient:?}: recipients must be addresses or aliases");
+            }
+
+            expanded = true;
+
+            for addr in self.expand(recipient)? {
+                if !recipients.iter().any(|r| r.eq_ignore_ascii_case(&addr)) {
+                    recipients.push(addr);
+                }
+            }
+        }
+
+        recipients.retain(|r| !r.is_empty());
+        Ok(expanded.then_some(recipients))
+    }
+
+    /// Expand the aliases of the To, Cc and Bcc headers of the given
+    /// raw message or template.
+    ///
+    /// Headers without alias are left untouched, and so is the whole
+    /// message when no alias is configured.
+    pub fn expand_headers(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        if self.is_empty() {
+            return Ok(msg.to_vec());
+        }
+
+        let (headers, body) = header::split(msg);
+        let headers_len: usize = headers.iter().map(|header| header.len()).sum();
+        let mut output = Vec::with_capacity(msg.len());
+
+        for raw in headers {
+            let expanded = match String::from_utf8_lossy(raw).split_once(':') {
+                Some((name, value)) if HEADERS.iter().any(|h| h.eq_ignore_ascii_case(name)) => {
+                    let eol = if raw.ends_with(b"\r\n") { "\r\n" } else { "\n" };
+                    let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+
+                    self.expand_recipients(&value)?
+                        .map(|recipients| format!("{name}: {}{eol}", fold(&recipients, name, eol)))
+                }
+                _ => None,
+            };
+
+            match expanded {
+                Some(header) => output.extend_from_slice(header.as_bytes()),
+                None => output.extend_from_slice(raw),
+            }
+        }
+
+        // keeps the empty line separating headers from the body
+        output.extend_from_slice(&msg[headers_len..msg.len() - body.len()]);
+        output.extend_from_slice(body);
+        Ok(output)
+    }
+
+    /// Expand the aliases of the To, Cc and Bcc headers of the given
+    /// template.
+    pub fn expand_tpl(&self, tpl: &str) -> Result<String> {
+        let tpl = self.expand_headers(tpl.as_bytes())?;
+        Ok(String::from_utf8_lossy(&tpl).into_owned())
+    }
+
+    /// Search the aliases matching the given query.
+    ///
+    /// Aliases match when their name or one of their addresses
+    /// contains the query, case-insensitively. The address of an
+    /// alias is the list of its expanded addresses. Aliases that
+    /// cannot be expanded are skipped, with a warning printed using
+    /// the given printer.
+    pub fn search(&self, printer: &mut impl Printer, query: &str) -> Result<Vec<Address>> {
+        let query = query.to_lowercase();
+        let mut names: Vec<_> = self.0.keys().collect();
+        names.sort();
+
+        let mut found = Vec::new();
+
+        for name in names {
+            let addrs = match self.expand(name) {
+                Ok(addrs) => addrs,
+                Err(err) => {
+                    warn!("cannot expand alias {name}, skipping it");
+                    debug!("{err:?}");
+                    printer.log(format!("WARNING: {err}\n"))?;
+                    continue;
+                }
+            };
+
+            let matches = name.to_lowercase().contains(&query)
+                || addrs.iter().any(|a| a.to_lowercase().contains(&query));
+
+            if matches {
+                found.push(Address {
+                    name: Some(name.clone()),
+                    email: addrs.join(", "),
+                });
+            }
+        }
+
+        Ok(found)
+    }
+}
+
+/// Return `true` if the given recipient is an address rather than an
+/// alias: a mailbox, a named mailbox or a group.
+fn is_address(recipient: &str) -> bool {
+    recipient.contains(['@', '<', ':'])
+}
+
+/// Split the given recipients header value on commas, ignoring
+/// commas of quoted strings, angle addresses and comments.
+fn split(value: &str) -> impl Iterator<Item = &str> {
+    let mut recipients = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut depth = 0usize;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '<' | '(' if !quoted => depth += 1,
+            '>' | ')' if !quoted => depth = depth.saturating_sub(1),
+            ',' if !quoted && depth == 0 => {
+                recipients.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+
+    recipients.push(value[start..].trim());
+    recipients.into_iter()
+}
+
+/// Join the given recipients of the given header, folding lines so
+/// that they do not exceed 78 characters.
+fn fold(recipients: &[String], name: &str, eol: &str) -> String {
+    let mut folded = String::new();
+    let mut line_len = name.len() + 2;
+
+    for (i, recipient) in recipients.iter().enumerate() {
+        if i > 0 {
+            if line_len + recipient.len() + 2 > 78 {
+                folded.push(',');
+                folded.push_str(eol);
+                folded.push(' ');
+                line_len = 1;
+            } else {
+                folded.push_str(", ");
+                line_len += 2;
+            }
+        }
+
+        folded.push_str(recipient);
+        line_len += recipient.len();
+    }
+
+    folded
+}
@@ -0,0 +1,307 @@
+//! Module dedicated to the HTTP client of the CardDAV address book.
+//!
+//! The available HTTP clients only accept standard methods, which
+//! excludes the WebDAV ones (PROPFIND, REPORT). This minimal HTTP/1.1
+//! client sends one request per connection.
+
+use std::{io::ErrorKind, sync::Arc};
+
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use rustls_platform_verifier::ConfigVerifierExt;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    rustls::{pki_types::ServerName, ClientConfig},
+    TlsConnector,
+};
+use tracing::debug;
+use url::Url;
+
+/// The HTTP response.
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Get the value of the first header matching the given name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, val)| val.as_str())
+    }
+}
+
+/// Send the given request and wait for its response.
+pub async fn send(
+    method: &str,
+    url: &Url,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> Result<Response> {
+    let Some(host) = url.host_str() else {
+        bail!("cannot send request to {url}: missing host");
+    };
+
+    let Some(port) = url.port_or_known_default() else {
+        bail!("cannot send request to {url}: missing port");
+    };
+
+    let mut path = url.path().to_owned();
+
+    if let Some(query) = url.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+
+    let host_header = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_owned(),
+    };
+
+    let mut req = format!(
+        "{method} {path} HTTP/1.1\r\n\
+         Host: {host_header}\r\n\
+         Connection: close\r\n\
+         Content-Length: {}\r\n",
+        body.len()
+    );
+
+    for (key, val) in headers {
+        req.push_str(&format!("{key}: {val}\r\n"));
+    }
+
+    req.push_str("\r\n");
+
+    let mut req = req.into_bytes();
+    req.extend_from_slice(body);
+
+    debug!("sending {method} request to {url}");
+
+    let tcp = TcpStream::connect((host, port)).await?;
+
+    let res = match url.scheme() {
+        "http" => exchange(tcp, &req).await?,
+        "https" => {
+            let config = ClientConfig::with_platform_verifier();
+            let connector = TlsConnector::from(Arc::new(config));
+            let server_name = ServerName::try_from(host.to_owned())?;
+            let tls = connector.connect(server_name, tcp).await?;
+            exchange(tls, &req).await?
+        }
+        scheme => bail!("cannot send request to {url}: unsupported scheme {scheme}"),
+    };
+
+    parse_response(&res)
+}
+
+/// Write the given request to the given stream, then read the
+/// response until the server closes the connection.
+async fn exchange(mut stream: impl AsyncRead + AsyncWrite + Unpin, req: &[u8]) -> Result<Vec<u8>> {
+    stream.write_all(req).await?;
+    stream.flush().await?;
+
+    let mut res = Vec::new();
+
+    match stream.read_to_end(&mut res).await {
+        Ok(_) => Ok(res),
+        // some servers close TLS connections without notification
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof && !res.is_empty() => Ok(res),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn parse_response(raw: &[u8]) -> Result<Response> {
+    let Some(i) = raw.windows(4).position(|w| w == b"\r\n\r\n") else {
+        bail!("cannot parse HTTP response: missing headers");
+    };
+
+    let head = String::from_utf8_lossy(&raw[..i]);
+    let mut lines = head.split("\r\n");
+
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or(eyre!("cannot parse HTTP response status"))?;
+
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, val)| (key.trim().to_owned(), val.trim().to_owned()))
+        .collect();
+
+    let mut res = Response {
+        status,
+        headers,
+        body: Vec::new(),
+    };
+
+    let body = &raw[i + 4..];
+
+    let is_chunked = res
+        .header("Transfer-Encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+
+    let len = res
+        .header("Content-Length")
+        .and_then(|len| len.parse::<usize>().ok());
+
+    res.body = if is_chunked {
+        dechunk(body)?
+    } else if let Some(len) = len {
+        body[..len.min(body.len())].to_vec()
+    } else {
+        body.to_vec()
+    };
+
+    Ok(res)
+}
+
+/// Decode the given chunked body.
+fn dechunk(mut body: &[u8]) -> Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+
+    loop {
+        let Some(i) = body.windows(2).position(|w| w == b"\r\n") else {
+            bail!("cannot parse HTTP response: invalid chunk");
+        };
+
+        // chunk extensions follow the size
+        let size = String::from_utf8_lossy(&body[..i]);
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let Ok(size) = usize::from_str_radix(size, 16) else {
+            bail!("cannot parse HTTP response: invalid chunk size {size:?}");
+        };
+
+        if size == 0 {
+            return Ok(decoded);
+        }
+
+        let start = i + 2;
+        let end = start.checked_add(size);
+
+        let Some(chunk) = end.and_then(|end| body.get(start..end)) else {
+            bail!("cannot parse HTTP response: truncated chunk");
+        };
+
+        decoded.extend_from_slice(chunk);
+        body = body.get(start + size + 2..).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dechunk, parse_response};
+
+    #[test]
+    fn parse_responses() {
+        let res = parse_response(
+            b"HTTP/1.1 207 Multi-Status\r\n\
+              Content-Type: application/xml; charset=utf-8\r\n\
+              content-length: 5\r\n\
+              \r\n\
+              <a/>\ntrailing",
+        )
+        .unwrap();
+
+        assert_eq!(res.status, 207);
+        assert_eq!(
+            res.header("Content-Type"),
+            Some("application/xml; charset=utf-8")
+        );
+        assert_eq!(res.header("Content-Length"), Some("5"));
+        assert_eq!(res.header("ETag"), None);
+        assert_eq!(res.body, b"<a/>\n");
+    }
+
+    #[test]
+    fn parse_responses_without_length() {
+        let res = parse_response(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nbody").unwrap();
+        assert_eq!(res.body, b"body");
+
+        let res = parse_response(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+        assert_eq!(res.status, 204);
+        assert!(res.headers.is_empty());
+        assert!(res.body.is_empty());
+
+        // a body shorter than announced is kept as is
+        let res = parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nbody").unwrap();
+        assert_eq!(res.body, b"body");
+    }
+
+    #[test]
+    fn parse_chunked_responses() {
+        let res = parse_response(
+            b"HTTP/1.1 200 OK\r\n\
+              Transfer-Encoding: Chunked\r\n\
+              \r\n\
+              4\r\nWiki\r\n\
+              6;name=value\r\npedia \r\n\
+              E\r\nin \r\n\r\nchunks.\r\n\
+              0\r\n\
+              \r\n",
+        )
+        .unwrap();
+
+        assert_eq!(res.body, b"Wikipedia in \r\n\r\nchunks.");
+    }
+
+    #[test]
+    fn refuse_malformed_responses() {
+        let err = parse_response(b"HTTP/1.1 200 OK\r\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot parse HTTP response: missing headers"
+        );
+
+        for raw in [
+            &b"HTTP/1.1 OK\r\n\r\n"[..],
+            b"\r\n\r\n",
+            b"HTTP/1.1 999999 OK\r\n\r\n",
+        ] {
+            let err = parse_response(raw).unwrap_err();
+            assert_eq!(err.to_string(), "cannot parse HTTP response status");
+        }
+    }
+
+    #[test]
+    fn dechunk_bodies() {
+        assert_eq!(dechunk(b"0\r\n\r\n").unwrap(), b"");
+        assert_eq!(dechunk(b"3\r\nabc\r\n0\r\n").unwrap(), b"abc");
+        assert_eq!(
+            dechunk(b"a \r\n0123456789\r\n0\r\n").unwrap(),
+            b"0123456789"
+        );
+        assert_eq!(dechunk(b"1\r\na\r\n1\r\nb\r\n00\r\n").unwrap(), b"ab");
+    }
+
+    #[test]
+    fn refuse_malformed_chunks() {
+        let err = |body: &[u8]| dechunk(body).unwrap_err().to_string();
+
+        let invalid = "cannot parse HTTP response: invalid chunk";
+        assert_eq!(err(b""), invalid);
+        assert_eq!(err(b"3\r\nabc\r\n"), invalid);
+
+        let size = |size| format!("cannot parse HTTP response: invalid chunk size {size:?}");
+        assert_eq!(err(b"zz\r\nabc\r\n0\r\n"), size("zz"));
+        assert_eq!(err(b"-3\r\nabc\r\n0\r\n"), size("-3"));
+        assert_eq!(err(b"\r\nabc\r\n0\r\n"), size(""));
+        assert_eq!(
+            err(b"fffffffffffffffff\r\nabc\r\n"),
+            size("fffffffffffffffff")
+        );
+
+        let truncated = "cannot parse HTTP response: truncated chunk";
+        assert_eq!(err(b"5\r\nabc"), truncated);
+        assert_eq!(err(b"ffffffffffffffff\r\nabc\r\n"), truncated);
+    }
+}
@@ -0,0 +1,427 @@
+//! Module dedicated to the CardDAV address book.
+//!
+//! Address books are discovered from the configured URL, which can
+//! point to an address book, to an address book home, to a principal
+//! or to the root of the server (in which case the
+//! `.well-known/carddav` URL is used, see RFC 6764). Only the FN and
+//! EMAIL properties of vCards are used.
+
+mod http;
+
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::{
+    eyre::{bail, eyre, WrapErr},
+    Result,
+};
+use secret::Secret;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use url::Url;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::address::{self, Address};
+
+/// The default lifetime of cached addresses, in seconds.
+const DEFAULT_CACHE_TTL: u64 = 3600;
+
+/// The maximum amount of redirections followed by one request.
+const MAX_REDIRECTS: usize = 5;
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:carddav">
+  <d:prop>
+    <d:resourcetype/>
+    <d:current-user-principal/>
+    <c:addressbook-home-set/>
+  </d:prop>
+</d:propfind>"#;
+
+const REPORT_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:addressbook-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:carddav">
+  <d:prop>
+    <c:address-data>
+      <c:prop name="FN"/>
+      <c:prop name="EMAIL"/>
+    </c:address-data>
+  </d:prop>
+</c:addressbook-query>"#;
+
+/// The CardDAV address book configuration.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CardDavConfig {
+    /// The URL of the CardDAV server or address book.
+    pub url: String,
+
+    /// The login used to authenticate, if any.
+    pub login: Option<String>,
+
+    /// The password used to authenticate.
+    #[serde(default)]
+    pub passwd: Secret,
+
+    /// The amount of seconds addresses are cached locally.
+    ///
+    /// Defaults to one hour.
+    pub cache_ttl: Option<u64>,
+}
+
+/// The addresses cache of an account.
+#[derive(Debug, Deserialize, Serialize)]
+struct Cache {
+    url: String,
+    fetched_at: u64,
+    addresses: Vec<Address>,
+}
+
+/// Search addresses matching the given query in the CardDAV address
+/// books of the given account.
+///
+/// Addresses are fetched once then cached locally, until the cache
+/// expires.
+pub async fn search(account: &str, config: &CardDavConfig, query: &str) -> Result<Vec<Address>> {
+    let addresses = match read_cache(account, config) {
+        Some(addresses) => addresses,
+        None => {
+            let addresses = fetch(config).await?;
+            write_cache(account, config, &addresses);
+            addresses
+        }
+    };
+
+    let query = query.to_lowercase();
+
+    let addresses = addresses
+        .into_iter()
+        .filter(|addr| {
+            let name = addr.name.as_deref().unwrap_or_default().to_lowercase();
+            name.contains(&query) || addr.email.to_lowercase().contains(&query)
+        })
+        .collect();
+
+    Ok(addresses)
+}
+
+/// Fetch addresses of all address books.
+async fn fetch(config: &CardDavConfig) -> Result<Vec<Address>> {
+    let dav = Dav::new(config).await?;
+    let mut url = Url::parse(&config.url)?;
+
+    if url.path().trim_matches('/').is_empty() {
+        url = url.join("/.well-known/carddav")?;
+    }
+
+    let (url, res) = dav.propfind(url, 0).await?;
+    let res = res.into_iter().next().unwrap_or_default();
+
+    let books = if res.is_addressbook {
+        vec![url]
+    } else {
+        let home = match res.home {
+            Some(home) => url.join(&home)?,
+            None => {
+                let Some(principal) = res.principal else {
+                    bail!("cannot find CardDAV address books at {url}");
+                };
+                let (principal, res) = dav.propfind(url.join(&principal)?, 0).await?;
+                let Some(home) = res.into_iter().find_map(|res| res.home) else {
+                    bail!("cannot find CardDAV address book home of {principal}");
+                };
+                principal.join(&home)?
+            }
+        };
+
+        let (home, res) = dav.propfind(home, 1).await?;
+
+        res.into_iter()
+            .filter(|res| res.is_addressbook)
+            .map(|res| home.join(&res.href))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let mut addresses = Vec::new();
+
+    for book in books {
+        debug!("fetching vcards from address book {book}");
+
+        for res in dav.report(book).await? {
+            let Some(vcard) = res.address_data else {
+                continue;
+            };
+
+            address::merge(&mut addresses, parse_vcard(&vcard));
+        }
+    }
+
+    Ok(addresses)
+}
+
+/// The WebDAV client.
+struct Dav {
+    auth: Option<String>,
+}
+
+impl Dav {
+    async fn new(config: &CardDavConfig) -> Result<Self> {
+        let auth = match &config.login {
+            None => None,
+            Some(login) => {
+                let passwd = config.passwd.get().await?;
+                let credentials = STANDARD.encode(format!("{login}:{passwd}"));
+                Some(format!("Basic {credentials}"))
+            }
+        };
+
+        Ok(Self { auth })
+    }
+
+    /// Find the properties of the given URL, and of its children if
+    /// the depth is 1.
+    ///
+    /// Returns the URL the properties were found at, after
+    /// redirections.
+    async fn propfind(&self, url: Url, depth: u8) -> Result<(Url, Vec<DavResponse>)> {
+        self.send("PROPFIND", url, depth, PROPFIND_BODY).await
+    }
+
+    /// Get the vCards of the given address book.
+    async fn report(&self, url: Url) -> Result<Vec<DavResponse>> {
+        let (_, res) = self.send("REPORT", url, 1, REPORT_BODY).await?;
+        Ok(res)
+    }
+
+    async fn send(
+        &self,
+        method: &str,
+        mut url: Url,
+        depth: u8,
+        body: &str,
+    ) -> Result<(Url, Vec<DavResponse>)> {
+        let depth = depth.to_string();
+
+        let mut headers = vec![
+            ("Depth", depth.as_str()),
+            ("Content-Type", "application/xml; charset=utf-8"),
+        ];
+
+        if let Some(auth) = &self.auth {
+            headers.push(("Authorization", auth));
+        }
+
+        for _ in 0..MAX_REDIRECTS {
+            let res = http::send(method, &url, &headers, body.as_bytes()).await?;
+
+            if (300..400).contains(&res.status) {
+                let Some(location) = res.header("Location") else {
+                    bail!("cannot find redirection location of {url}");
+                };
+                url = url.join(location)?;
+                continue;
+            }
+
+            if res.status != 207 {
+                let status = res.status;
+                bail!("cannot send {method} request to {url}: status {status}");
+            }
+
+            let body = String::from_utf8_lossy(&res.body);
+            return Ok((url, parse_multistatus(&body)?));
+        }
+
+        bail!("cannot send {method} request to {url}: too many redirections")
+    }
+}
+
+/// The properties of a WebDAV resource.
+#[derive(Debug, Default)]
+struct DavResponse {
+    href: String,
+    is_addressbook: bool,
+    principal: Option<String>,
+    home: Option<String>,
+    address_data: Option<String>,
+}
+
+/// Parse the given WebDAV multi-status response.
+fn parse_multistatus(xml: &str) -> Result<Vec<DavResponse>> {
+    let mut responses: Vec<DavResponse> = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+
+    for event in EventReader::from_str(xml) {
+        match event? {
+            XmlEvent::StartElement { name, .. } => {
+                if name.local_name == "response" {
+                    responses.push(DavResponse::default());
+                }
+
+                if name.local_name == "addressbook" && path.ends_with(&["resourcetype".into()]) {
+                    if let Some(res) = responses.last_mut() {
+                        res.is_addressbook = true;
+                    }
+                }
+
+                path.push(name.local_name);
+            }
+            XmlEvent::EndElement { .. } => {
+                path.pop();
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                let Some(res) = responses.last_mut() else {
+                    continue;
+                };
+
+                let mut names = path.iter().rev().map(String::as_str);
+
+                match (names.next(), names.next()) {
+                    (Some("href"), Some("response")) => res.href = text.trim().to_owned(),
+                    (Some("href"), Some("current-user-principal")) => {
+                        res.principal = Some(text.trim().to_owned())
+                    }
+                    (Some("href"), Some("addressbook-home-set")) => {
+                        res.home = Some(text.trim().to_owned())
+                    }
+                    (Some("address-data"), _) => res
+                        .address_data
+                        .get_or_insert_with(String::new)
+                        .push_str(&text),
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok(responses)
+}
+
+/// Parse the FN and EMAIL properties of the given vCard.
+fn parse_vcard(vcard: &str) -> Vec<Address> {
+    let mut name = None;
+    let mut emails = Vec::new();
+
+    // folded lines start with a space or a tab
+    let unfolded = vcard
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+
+    for line in unfolded.lines() {
+        let Some((key, val)) = line.split_once(':') else {
+            continue;
+        };
+
+        // properties can be grouped (`item1.EMAIL`) and have
+        // parameters (`EMAIL;TYPE=work`)
+        let key = key.split(';').next().unwrap_or_default();
+        let key = key.rsplit('.').next().unwrap_or_default();
+
+        if key.eq_ignore_ascii_case("FN") {
+            name = Some(unescape(val)).filter(|name| !name.is_empty());
+        } else if key.eq_ignore_ascii_case("EMAIL") {
+            let email = unescape(val);
+            let email = email.strip_prefix("mailto:").unwrap_or(&email);
+            if email.contains('@') {
+                emails.push(email.to_owned());
+            }
+        }
+    }
+
+    emails
+        .into_iter()
+        .map(|email| Address {
+            name: name.clone(),
+            email,
+        })
+        .collect()
+}
+
+fn unescape(val: &str) -> String {
+    let mut unescaped = String::with_capacity(val.len());
+    let mut chars = val.trim().chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => unescaped.push(' '),
+                Some(c) => unescaped.push(c),
+                None => (),
+            },
+            c => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
+
+fn cache_path(account: &str) -> Option<PathBuf> {
+    let dir = dirs::cache_dir()?.join("himalaya").join("carddav");
+    Some(dir.join(format!("{account}.json")))
+}
+
+/// Remove the cached addresses of the given account, if any.
+pub fn remove_cache(account: &str) -> Result<()> {
+    let Some(path) = cache_path(account) else {
+        return Ok(());
+    };
+
+    match fs::remove_file(&path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            let path = path.display();
+            Err(err).wrap_err_with(|| format!("cannot remove carddav cache at {path}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Read the cached addresses of the given account, if the cache
+/// exists and has not expired.
+fn read_cache(account: &str, config: &CardDavConfig) -> Option<Vec<Address>> {
+    let path = cache_path(account)?;
+    let cache: Cache = serde_json::from_slice(&fs::read(path).ok()?).ok()?;
+
+    let ttl = Duration::from_secs(config.cache_ttl.unwrap_or(DEFAULT_CACHE_TTL));
+    let fetched_at = UNIX_EPOCH + Duration::from_secs(cache.fetched_at);
+    let expired = fetched_at.elapsed().map_or(true, |elapsed| elapsed > ttl);
+
+    if expired || cache.url != config.url {
+        debug!("carddav cache of account {account} expired");
+        return None;
+    }
+
+    Some(cache.addresses)
+}
+
+/// Write the given addresses to the cache of the given account.
+///
+/// Failures are logged, since addresses can still be fetched again.
+fn write_cache(account: &str, config: &CardDavConfig, addresses: &[Address]) {
+    let Some(path) = cache_path(account) else {
+        return;
+    };
+
+    let cache = Cache {
+        url: config.url.clone(),
+        fetched_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        addresses: addresses.to_vec(),
+    };
+
+    let res = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .map_err(|err| eyre!(err))
+        .and_then(|()| Ok(serde_json::to_vec(&cache)?))
+        .and_then(|json| Ok(fs::write(&path, json)?));
+
+    if let Err(err) = res {
+        warn!("cannot write carddav cache at {}: {err}", path.display());
+    }
+}
@@ -0,0 +1,26 @@
+mod search;
+
+use clap::Subcommand;
+use color_eyre::Result;
+
+use crate::{config::TomlConfig, printer::Printer};
+
+use self::search::AddressSearchCommand;
+
+/// Search addresses in your address book.
+///
+/// The address book is an external command set up in the account
+/// configuration (see `address-book-cmd`), like `khard` or `abook`.
+#[derive(Debug, Subcommand)]
+pub enum AddressSubcommand {
+    #[command(alias = "query")]
+    Search(AddressSearchCommand),
+}
+
+impl AddressSubcommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        match self {
+            Self::Search(cmd) => cmd.execute(printer, config).await,
+        }
+    }
+}
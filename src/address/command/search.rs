@@ -0,0 +1,111 @@
+use clap::Parser;
+use color_eyre::{eyre::bail, Result};
+use email::config::Config;
+use pimalaya_tui::terminal::config::TomlConfig as _;
+use tracing::{debug, info, warn};
+
+#[cfg(feature = "carddav")]
+use crate::address::carddav;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    address::{self, table::AddressesTable, Address},
+    config::TomlConfig,
+    printer::Printer,
+};
+
+/// Search addresses matching the given query.
+///
+/// This command searches the address books of the account (the
+/// address book command and the CardDAV address book) and the
+/// recipient aliases, then prints the addresses found. A failing
+/// address book leads to no address.
+#[derive(Debug, Parser)]
+pub struct AddressSearchCommand {
+    /// The query given to the address book command.
+    #[arg(value_name = "QUERY", num_args = 1.., required = true)]
+    pub query: Vec<String>,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+
+    /// The maximum width the table should not exceed.
+    ///
+    /// This argument will force the table not to exceed the given
+    /// width, in pixels. Columns may shrink with ellipsis in order to
+    /// fit the width.
+    ///
+    /// Defaults to the width of the terminal, or to the
+    /// ui.table.piped-width option when the output is not a terminal.
+    #[arg(long = "max-width", short = 'w')]
+    #[arg(name = "table_max_width", value_name = "PIXELS")]
+    pub table_max_width: Option<u16>,
+}
+
+impl AddressSearchCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing search addresses command");
+
+        let (_, account_config) = config
+            .clone()
+            .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
+                c.account(name).ok()
+            })?;
+
+        let extra_config = config.account_extra_config(&account_config.name);
+
+        let name = &account_config.name;
+        let query = self.query.join(" ");
+        let mut addresses = Vec::new();
+        let mut configured = false;
+
+        if let Some(cmd) = &extra_config.address_book_cmd {
+            configured = true;
+            let res = address::search(cmd, &query).await;
+            merge_or_warn(printer, &mut addresses, "address book command", res)?;
+        }
+
+        #[cfg(feature = "carddav")]
+        if let Some(carddav_config) = &extra_config.carddav {
+            configured = true;
+            let res = carddav::search(name, carddav_config, &query).await;
+            merge_or_warn(printer, &mut addresses, "CardDAV", res)?;
+        }
+
+        if !config.aliases.is_empty() {
+            configured = true;
+            address::merge(&mut addresses, config.aliases.search(printer, &query)?);
+        }
+
+        if !configured {
+            bail!("cannot search addresses: no address book set up for account {name}");
+        }
+
+        let table = AddressesTable::from(addresses)
+            .with_some_width(self.table_max_width)
+            .with_table_config(config.table_config());
+
+        printer.out(table)
+    }
+}
+
+/// Merge the found addresses, or warn about the failing address book
+/// so that the search degrades to fewer addresses.
+fn merge_or_warn(
+    printer: &mut impl Printer,
+    addresses: &mut Vec<Address>,
+    source: &str,
+    res: Result<Vec<Address>>,
+) -> Result<()> {
+    match res {
+        Ok(found) => address::merge(addresses, found),
+        Err(err) => {
+            warn!("cannot search addresses using {source}, skipping it");
+            debug!("{err:?}");
+            printer.log(format!(
+                "WARNING: cannot search addresses using {source}: {err}\n"
+            ))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+pub mod alias;
+#[cfg(feature = "carddav")]
+pub mod carddav;
+pub mod command;
+pub mod table;
+
+use std::fmt;
+
+use color_eyre::Result;
+use process::Command;
+use serde::{Deserialize, Serialize};
+
+/// The address book entry.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Address {
+    pub name: Option<String>,
+    pub email: String,
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{name} <{}>", self.email),
+            None => write!(f, "{}", self.email),
+        }
+    }
+}
+
+/// Search addresses matching the given query using the given address
+/// book command.
+///
+/// The `%s` placeholder of the command is replaced by the query. If
+/// the command does not contain any placeholder, the query is given
+/// as last argument.
+pub async fn search(cmd: &Command, query: &str) -> Result<Vec<Address>> {
+    let query = quote(query);
+
+    let cmd = if cmd.contains("%s") {
+        cmd.clone().replace("%s", query)
+    } else {
+        Command::new(format!("{} {query}", cmd.as_str()))
+    };
+
+    let output = cmd.run().await?;
+    Ok(parse(&output.to_string_lossy()))
+}
+
+/// Parse the given address book command output.
+///
+/// Both the mutt query format (`<email>\t<name>\t<other>`) and the
+/// `Name <email>` format are accepted, one address per line. Lines
+/// without email address are ignored, which covers the informational
+/// first line of the mutt query format.
+pub fn parse(output: &str) -> Vec<Address> {
+    let mut addrs = Vec::new();
+    merge(&mut addrs, output.lines().filter_map(parse_line));
+    addrs
+}
+
+/// Merge the given addresses into the given list, skipping addresses
+/// already in the list.
+pub fn merge(addrs: &mut Vec<Address>, others: impl IntoIterator<Item = Address>) {
+    for addr in others {
+        let exists = addrs
+            .iter()
+            .any(|a| a.email.eq_ignore_ascii_case(&addr.email));
+
+        if !exists {
+            addrs.push(addr);
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Option<Address> {
+    let line = line.trim();
+
+    let (name, email) = if line.contains('\t') {
+        let mut fields = line.split('\t').map(str::trim);
+        let email = fields.next()?;
+        (fields.next(), email)
+    } else if let Some((name, email)) = line.rsplit_once('<') {
+        let name = name.trim().trim_matches('"').trim();
+        (Some(name), email.strip_suffix('>')?.trim())
+    } else {
+        (None, line)
+    };
+
+    if !email.contains('@') || email.contains(char::is_whitespace) {
+        return None;
+    }
+
+    Some(Address {
+        name: name.filter(|name| !name.is_empty()).map(ToOwned::to_owned),
+        email: email.to_owned(),
+    })
+}
+
+/// Quote the given argument for the shell.
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
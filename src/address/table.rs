@@ -0,0 +1,67 @@
+use std::fmt;
+
+use comfy_table::{presets, ContentArrangement, Row, Table};
+use serde::{Serialize, Serializer};
+
+use crate::{
+    address::Address,
+    table::{Column, TableConfig},
+};
+
+const COLUMNS: [Column; 2] = [Column::left("name", "NAME"), Column::left("email", "EMAIL")];
+
+/// The printable table of addresses.
+pub struct AddressesTable {
+    addresses: Vec<Address>,
+    width: Option<u16>,
+    table_config: TableConfig,
+}
+
+impl AddressesTable {
+    pub fn with_some_width(mut self, width: Option<u16>) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_table_config(mut self, config: TableConfig) -> Self {
+        self.table_config = config;
+        self
+    }
+}
+
+impl From<Vec<Address>> for AddressesTable {
+    fn from(addresses: Vec<Address>) -> Self {
+        Self {
+            addresses,
+            width: None,
+            table_config: Default::default(),
+        }
+    }
+}
+
+impl fmt::Display for AddressesTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Table::new();
+
+        table
+            .load_preset(presets::ASCII_MARKDOWN)
+            .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+            .add_rows(self.addresses.iter().map(|addr| {
+                let name = addr.name.as_deref().unwrap_or_default();
+                Row::from([name, &addr.email])
+            }));
+
+        self.table_config.apply(&mut table, &COLUMNS, self.width);
+
+        writeln!(f)?;
+        write!(f, "{table}")?;
+        writeln!(f)?;
+        Ok(())
+    }
+}
+
+impl Serialize for AddressesTable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.addresses.serialize(serializer)
+    }
+}
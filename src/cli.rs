@@ -4,19 +4,15 @@ use clap::{Parser, Subcommand};
 use color_eyre::Result;
 use pimalaya_tui::{
     long_version,
-    terminal::{
-        cli::{
-            arg::path_parser,
-            printer::{OutputFmt, Printer},
-        },
-        config::TomlConfig as _,
-    },
+    terminal::cli::{arg::path_parser, printer::OutputFmt},
 };
+use tracing_subscriber::filter::LevelFilter;
 
 use crate::{
     account::command::AccountSubcommand,
+    address::command::AddressSubcommand,
     completion::command::CompletionGenerateCommand,
-    config::TomlConfig,
+    config::{command::ConfigSubcommand, TomlConfig},
     envelope::command::EnvelopeSubcommand,
     flag::command::FlagSubcommand,
     folder::command::FolderSubcommand,
@@ -25,6 +21,9 @@ use crate::{
         attachment::command::AttachmentSubcommand, command::MessageSubcommand,
         template::command::TemplateSubcommand,
     },
+    outbox::command::OutboxSubcommand,
+    printer::Printer,
+    tag::command::TagSubcommand,
 };
 
 #[derive(Parser, Debug)]
@@ -41,9 +40,17 @@ pub struct Cli {
     /// The given paths are shell-expanded then canonicalized (if
     /// applicable). If the first path does not point to a valid file,
     /// the wizard will propose to assist you in the creation of the
-    /// configuration file. Other paths are merged with the first one,
-    /// which allows you to separate your public config from your
-    /// private(s) one(s).
+    /// configuration file, when running in a terminal. Other paths
+    /// are merged with the first one, which allows you to separate
+    /// your public config from your private(s) one(s). They must
+    /// exist.
+    ///
+    /// Paths can also point to directories, whose *.toml files are
+    /// merged in alphabetical order. Later values override earlier
+    /// ones.
+    ///
+    /// The HIMALAYA_CONFIG environment variable is also used by
+    /// mailto URLs given as first argument.
     #[arg(short, long = "config", global = true, env = "HIMALAYA_CONFIG")]
     #[arg(value_name = "PATH", value_parser = path_parser)]
     pub config_paths: Vec<PathBuf>,
@@ -63,19 +70,72 @@ pub struct Cli {
     #[arg(value_name = "FORMAT", value_enum, default_value_t = Default::default())]
     pub output: OutputFmt,
 
+    /// Write the output to the given file instead of the standard
+    /// output.
+    ///
+    /// Tables, JSON and raw outputs (like raw messages) are written
+    /// to the file once the command succeeds. The file is replaced
+    /// atomically. Informational messages are still printed to the
+    /// standard output, unless --quiet is given.
+    #[arg(long, global = true, value_name = "PATH", value_parser = path_parser)]
+    pub output_file: Option<PathBuf>,
+
+    /// Disable colors and text attributes of tables.
+    ///
+    /// Colors are also disabled when the `NO_COLOR` environment
+    /// variable is set to a non-empty value, or when the standard
+    /// output is not a terminal.
+    #[arg(long, global = true)]
+    pub no_color: bool,
+
+    /// Silence informational messages.
+    ///
+    /// Success messages (like "Message successfully sent!") and
+    /// progress messages are not printed anymore, while requested
+    /// data (like tables or messages) still is. Errors are still
+    /// printed to the standard error. The exit code is then the only
+    /// success signal.
+    #[arg(long, short, global = true)]
+    pub quiet: bool,
+
     /// Enable logs with spantrace.
     ///
-    /// This is the same as running the command with `RUST_LOG=debug`
-    /// environment variable.
+    /// This takes precedence over the `RUST_LOG` environment
+    /// variable. Logs are written to the standard error, so that they
+    /// never mix with the output.
     #[arg(long, global = true, conflicts_with = "trace")]
     pub debug: bool,
 
     /// Enable verbose logs with backtrace.
     ///
-    /// This is the same as running the command with `RUST_LOG=trace`
-    /// and `RUST_BACKTRACE=1` environment variables.
+    /// This takes precedence over the `RUST_LOG` environment
+    /// variable, and is the same as running the command with
+    /// `RUST_LOG=trace` and `RUST_BACKTRACE=1` environment variables.
     #[arg(long, global = true, conflicts_with = "debug")]
     pub trace: bool,
+
+    /// Append logs to the given file.
+    ///
+    /// Logs are written to the file with timestamps, at the debug
+    /// level unless --trace or the `RUST_LOG` environment variable
+    /// says otherwise. They are written to the standard error as
+    /// well only when --debug, --trace or `RUST_LOG` is given.
+    /// Secrets like passwords and tokens are redacted from logs.
+    #[arg(long, global = true, value_name = "PATH", value_parser = path_parser)]
+    pub log_file: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Get the log level given from the command line, if any.
+    pub fn log_level(&self) -> Option<LevelFilter> {
+        if self.trace {
+            Some(LevelFilter::TRACE)
+        } else if self.debug {
+            Some(LevelFilter::DEBUG)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -84,6 +144,13 @@ pub enum HimalayaCommand {
     #[command(alias = "accounts")]
     Account(AccountSubcommand),
 
+    #[command(subcommand)]
+    Config(ConfigSubcommand),
+
+    #[command(subcommand)]
+    #[command(alias = "addresses", alias = "addr")]
+    Address(AddressSubcommand),
+
     #[command(subcommand)]
     #[command(visible_alias = "mailbox", aliases = ["mailboxes", "mboxes", "mbox"])]
     #[command(alias = "folders")]
@@ -97,6 +164,10 @@ pub enum HimalayaCommand {
     #[command(alias = "flags")]
     Flag(FlagSubcommand),
 
+    #[command(subcommand)]
+    #[command(alias = "tags")]
+    Tag(TagSubcommand),
+
     #[command(subcommand)]
     #[command(alias = "messages", alias = "msgs", alias = "msg")]
     Message(MessageSubcommand),
@@ -109,6 +180,9 @@ pub enum HimalayaCommand {
     #[command(alias = "templates", alias = "tpls", alias = "tpl")]
     Template(TemplateSubcommand),
 
+    #[command(subcommand)]
+    Outbox(OutboxSubcommand),
+
     #[command(arg_required_else_help = true)]
     #[command(alias = "manuals", alias = "mans")]
     Manual(ManualGenerateCommand),
@@ -122,31 +196,49 @@ impl HimalayaCommand {
     pub async fn execute(self, printer: &mut impl Printer, config_paths: &[PathBuf]) -> Result<()> {
         match self {
             Self::Account(cmd) => {
-                let config = TomlConfig::from_paths_or_default(config_paths).await?;
-                cmd.execute(printer, config, config_paths.first()).await
+                let paths = TomlConfig::resolve_paths(config_paths)?;
+                let config = if cmd.creates_config() && !paths[0].exists() {
+                    TomlConfig::default()
+                } else {
+                    TomlConfig::from_cli_paths(config_paths).await?
+                };
+                cmd.execute(printer, config, &paths).await
+            }
+            Self::Config(cmd) => cmd.execute(printer, config_paths).await,
+            Self::Address(cmd) => {
+                let config = TomlConfig::from_cli_paths(config_paths).await?;
+                cmd.execute(printer, &config).await
             }
             Self::Folder(cmd) => {
-                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                let config = TomlConfig::from_cli_paths(config_paths).await?;
                 cmd.execute(printer, &config).await
             }
             Self::Envelope(cmd) => {
-                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                let config = TomlConfig::from_cli_paths(config_paths).await?;
                 cmd.execute(printer, &config).await
             }
             Self::Flag(cmd) => {
-                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                let config = TomlConfig::from_cli_paths(config_paths).await?;
+                cmd.execute(printer, &config).await
+            }
+            Self::Tag(cmd) => {
+                let config = TomlConfig::from_cli_paths(config_paths).await?;
                 cmd.execute(printer, &config).await
             }
             Self::Message(cmd) => {
-                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                let config = TomlConfig::from_cli_paths(config_paths).await?;
                 cmd.execute(printer, &config).await
             }
             Self::Attachment(cmd) => {
-                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                let config = TomlConfig::from_cli_paths(config_paths).await?;
                 cmd.execute(printer, &config).await
             }
             Self::Template(cmd) => {
-                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                let config = TomlConfig::from_cli_paths(config_paths).await?;
+                cmd.execute(printer, &config).await
+            }
+            Self::Outbox(cmd) => {
+                let config = TomlConfig::from_cli_paths(config_paths).await?;
                 cmd.execute(printer, &config).await
             }
             Self::Manual(cmd) => cmd.execute(printer).await,
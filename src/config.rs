@@ -1,3 +0,0 @@
-use pimalaya_tui::himalaya::config::HimalayaTomlConfig;
-
-pub type TomlConfig = HimalayaTomlConfig;
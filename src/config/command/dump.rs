@@ -0,0 +1,45 @@
+use std::{fmt, path::PathBuf};
+
+use clap::Parser;
+use color_eyre::Result;
+use serde::Serialize;
+use toml::Table;
+use tracing::info;
+
+use crate::{
+    config::{include, TomlConfig},
+    printer::Printer,
+};
+
+/// Print the effective configuration.
+///
+/// This command prints the configuration resulting from the merge of
+/// all configuration files, including the ones given by directories
+/// and by the include option. The configuration is printed as it is
+/// merged, without being validated, so that invalid merges can be
+/// debugged.
+#[derive(Debug, Parser)]
+pub struct ConfigDumpCommand;
+
+impl ConfigDumpCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config_paths: &[PathBuf]) -> Result<()> {
+        info!("executing dump config command");
+
+        let paths = TomlConfig::resolve_paths(config_paths)?;
+        let config = include::read(&paths, !config_paths.is_empty())?;
+
+        printer.out(ConfigDump(config))
+    }
+}
+
+/// The printable merged configuration.
+#[derive(Serialize)]
+#[serde(transparent)]
+struct ConfigDump(Table);
+
+impl fmt::Display for ConfigDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let config = toml::to_string(&self.0).map_err(|_| fmt::Error)?;
+        write!(f, "{config}")
+    }
+}
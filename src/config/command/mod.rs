@@ -0,0 +1,26 @@
+mod dump;
+
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use color_eyre::Result;
+
+use self::dump::ConfigDumpCommand;
+use crate::printer::Printer;
+
+/// Inspect your configuration.
+///
+/// The configuration can be split across several files. This
+/// subcommand allows you to inspect the result of their merge.
+#[derive(Debug, Subcommand)]
+pub enum ConfigSubcommand {
+    Dump(ConfigDumpCommand),
+}
+
+impl ConfigSubcommand {
+    pub async fn execute(self, printer: &mut impl Printer, config_paths: &[PathBuf]) -> Result<()> {
+        match self {
+            Self::Dump(cmd) => cmd.execute(printer, config_paths).await,
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Module dedicated to environment variables interpolation.
+//!
+//! String values of configuration files can reference environment
+//! variables using `${VAR}`, expanded when the configuration is
+//! loaded. Undefined variables lead to an error, unless a default
+//! value is given using `${VAR:-default}`, which can be empty. A
+//! literal `$` followed by `{` or `$` is written `$$`.
+//!
+//! Files whose values must be kept as is, like passwords containing
+//! such sequences, can disable expansion with the `expand-env`
+//! option.
+
+use std::env::{self, VarError};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use toml::{Table, Value};
+
+/// Expand environment variables of all string values of the given
+/// table.
+pub fn expand_table(table: &mut Table) -> Result<()> {
+    expand_table_at(table, "")
+}
+
+fn expand_table_at(table: &mut Table, prefix: &str) -> Result<()> {
+    for (key, value) in table.iter_mut() {
+        expand_value(value, &format!("{prefix}{key}"))?;
+    }
+
+    Ok(())
+}
+
+fn expand_value(value: &mut Value, key: &str) -> Result<()> {
+    match value {
+        Value::String(string) => {
+            *string = expand(string).wrap_err_with(|| format!("cannot expand {key}"))?;
+        }
+        Value::Array(values) => {
+            for (i, value) in values.iter_mut().enumerate() {
+                expand_value(value, &format!("{key}[{i}]"))?;
+            }
+        }
+        Value::Table(table) => {
+            expand_table_at(table, &format!("{key}."))?;
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// Expand environment variables of the given string.
+pub fn expand(input: &str) -> Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(i) = rest.find('$') {
+        output.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+
+        if let Some(next) = rest.strip_prefix('$') {
+            output.push('$');
+            rest = next;
+            continue;
+        }
+
+        let Some(next) = rest.strip_prefix('{') else {
+            output.push('$');
+            continue;
+        };
+
+        let Some((expr, next)) = next.split_once('}') else {
+            bail!("unclosed variable in {input:?}, use $$ for a literal $");
+        };
+
+        let (name, default) = match expr.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expr, None),
+        };
+
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            bail!("invalid variable name {name:?}, use $$ for a literal $");
+        }
+
+        match (env::var(name), default) {
+            (Ok(value), Some(default)) if value.is_empty() => output.push_str(default),
+            (Ok(value), _) => output.push_str(&value),
+            (Err(VarError::NotPresent), Some(default)) => output.push_str(default),
+            (Err(VarError::NotPresent), None) => {
+                bail!("environment variable {name} is not defined, use ${{{name}:-}} to default to an empty value");
+            }
+            (Err(err), _) => {
+                return Err(err)
+                    .wrap_err_with(|| format!("cannot read environment variable {name}"));
+            }
+        }
+
+        rest = next;
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::expand;
+
+    #[test]
+    fn expand_variables() {
+        env::set_var("HIMALAYA_TEST_ENV_USER", "alice");
+        env::set_var("HIMALAYA_TEST_ENV_EMPTY", "");
+        env::remove_var("HIMALAYA_TEST_ENV_UNDEFINED");
+
+        let expand = |input| expand(input).unwrap();
+
+        assert_eq!(
+            expand("${HIMALAYA_TEST_ENV_USER}@localhost"),
+            "alice@localhost"
+        );
+        assert_eq!(expand("${HIMALAYA_TEST_ENV_USER:-bob}"), "alice");
+        assert_eq!(expand("${HIMALAYA_TEST_ENV_EMPTY:-bob}"), "bob");
+        assert_eq!(expand("${HIMALAYA_TEST_ENV_UNDEFINED:-bob}"), "bob");
+        assert_eq!(expand("${HIMALAYA_TEST_ENV_UNDEFINED:-}"), "");
+        assert_eq!(expand("$HOME and $"), "$HOME and $");
+        assert_eq!(expand("p$${a}$$$$b"), "p${a}$$b");
+    }
+
+    #[test]
+    fn refuse_invalid_variables() {
+        env::remove_var("HIMALAYA_TEST_ENV_MISSING");
+
+        let err = |input| expand(input).unwrap_err().to_string();
+
+        assert_eq!(
+            err("${HIMALAYA_TEST_ENV_MISSING}"),
+            "environment variable HIMALAYA_TEST_ENV_MISSING is not defined, use ${HIMALAYA_TEST_ENV_MISSING:-} to default to an empty value",
+        );
+        assert_eq!(
+            err("pa${ss"),
+            "unclosed variable in \"pa${ss\", use $$ for a literal $",
+        );
+        assert_eq!(
+            err("${a b}"),
+            "invalid variable name \"a b\", use $$ for a literal $",
+        );
+    }
+}
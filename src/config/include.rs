@@ -0,0 +1,371 @@
+//! Module dedicated to configuration includes.
+//!
+//! A configuration can be split across several files: the ones given
+//! from the command line, the `*.toml` files of directories, and the
+//! files or directories listed in the `include` option of a file.
+//! They are merged in order, later values overriding earlier ones.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use shellexpand_utils::shellexpand_path;
+use toml::{Table, Value};
+use tracing::warn;
+
+use crate::config::env;
+
+/// Read and merge the configuration files at the given paths.
+///
+/// Paths can point to files or to directories. When they come from
+/// the command line, a warning is logged for each array overriding
+/// the one of a previous path, since such arrays used to be
+/// concatenated.
+pub fn read(paths: &[PathBuf], from_cli: bool) -> Result<Table> {
+    let mut config = Table::new();
+    let mut stack = Vec::new();
+
+    for path in paths {
+        let table = read_path(path, &mut stack, &mut Vec::new())?;
+
+        if from_cli {
+            for key in overridden_arrays(&config, &table) {
+                warn!(
+                    "{key} of {} overrides the previous one instead of being concatenated to it, \
+                     as it used to be when merging --config paths",
+                    path.display()
+                );
+            }
+        }
+
+        merge(&mut config, table);
+    }
+
+    Ok(config)
+}
+
+/// Get the keys of the non-empty arrays of the given configuration
+/// overridden by different arrays of the given table.
+fn overridden_arrays(config: &Table, table: &Table) -> Vec<String> {
+    let mut keys = Vec::new();
+
+    for (key, value) in table {
+        match (config.get(key), value) {
+            (Some(Value::Table(config)), Value::Table(table)) => {
+                let nested = overridden_arrays(config, table);
+                keys.extend(nested.into_iter().map(|nested| format!("{key}.{nested}")));
+            }
+            (Some(Value::Array(prev)), Value::Array(next)) if !prev.is_empty() && prev != next => {
+                keys.push(key.clone());
+            }
+            _ => (),
+        }
+    }
+
+    keys
+}
+
+/// Find the file defining the given account, among the configuration
+/// files at the given paths.
+///
+/// When several files define the account, the last one read is
+/// returned, since its options override the ones of the others.
+pub fn find_account(paths: &[PathBuf], name: &str) -> Result<Option<PathBuf>> {
+    let mut stack = Vec::new();
+    let mut accounts = Vec::new();
+
+    for path in paths {
+        read_path(path, &mut stack, &mut accounts)?;
+    }
+
+    let path = accounts
+        .into_iter()
+        .rev()
+        .find(|(account, _)| account == name)
+        .map(|(_, path)| path);
+
+    Ok(path)
+}
+
+/// Merge the given table into the given configuration.
+///
+/// Tables are merged recursively, while other values of the given
+/// table replace the ones of the configuration.
+pub fn merge(config: &mut Table, table: Table) {
+    for (key, value) in table {
+        match (config.get_mut(&key), value) {
+            (Some(Value::Table(config)), Value::Table(table)) => merge(config, table),
+            (_, value) => {
+                config.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Read the configuration at the given path.
+///
+/// The stack contains the paths being read, in order to detect
+/// include cycles. The names of the accounts defined by each file
+/// read are pushed to the given list, along with the file path.
+fn read_path(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    accounts: &mut Vec<(String, PathBuf)>,
+) -> Result<Table> {
+    let path = fs::canonicalize(path)
+        .wrap_err_with(|| format!("cannot find configuration file at {}", path.display()))?;
+
+    if stack.contains(&path) {
+        let cycle: Vec<_> = stack
+            .iter()
+            .skip_while(|p| **p != path)
+            .chain([&path])
+            .map(|p| p.display().to_string())
+            .collect();
+
+        bail!(
+            "cannot include configuration files in cycle: {}",
+            cycle.join(" → ")
+        );
+    }
+
+    stack.push(path.clone());
+
+    let config = if path.is_dir() {
+        read_dir(&path, stack, accounts)
+    } else {
+        read_file(&path, stack, accounts)
+    };
+
+    stack.pop();
+    config
+}
+
+/// Read the `*.toml` files of the given directory, sorted by name.
+fn read_dir(
+    dir: &Path,
+    stack: &mut Vec<PathBuf>,
+    accounts: &mut Vec<(String, PathBuf)>,
+) -> Result<Table> {
+    let entries = fs::read_dir(dir)
+        .wrap_err_with(|| format!("cannot read configuration directory at {}", dir.display()))?;
+
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+
+    paths.sort();
+
+    let mut config = Table::new();
+
+    for path in paths {
+        merge(&mut config, read_path(&path, stack, accounts)?);
+    }
+
+    Ok(config)
+}
+
+/// Read the given file, then the files it includes.
+///
+/// Unless the file disables the `expand-env` option, its environment
+/// variables are expanded first, so that includes can reference them.
+/// Relative includes are resolved from the directory of the file.
+fn read_file(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    accounts: &mut Vec<(String, PathBuf)>,
+) -> Result<Table> {
+    let content = fs::read_to_string(path)
+        .wrap_err_with(|| format!("cannot read config file at {}", path.display()))?;
+
+    let mut config: Table = content
+        .parse()
+        .wrap_err_with(|| format!("cannot parse config file at {}", path.display()))?;
+
+    let expand_env = match config.remove("expand-env") {
+        None => true,
+        Some(Value::Boolean(expand_env)) => expand_env,
+        Some(_) => bail!(
+            "invalid expand-env in {}, expected a boolean",
+            path.display()
+        ),
+    };
+
+    if expand_env {
+        env::expand_table(&mut config)
+            .wrap_err_with(|| format!("cannot read config file at {}", path.display()))?;
+    }
+
+    let includes = match config.remove("include") {
+        None => Vec::new(),
+        Some(Value::String(include)) => vec![include],
+        Some(Value::Array(includes)) => includes
+            .into_iter()
+            .map(|include| match include {
+                Value::String(include) => Ok(include),
+                _ => bail!("invalid include in {}, expected a path", path.display()),
+            })
+            .collect::<Result<_>>()?,
+        Some(_) => bail!(
+            "invalid include in {}, expected a path or a list of paths",
+            path.display()
+        ),
+    };
+
+    if let Some(Value::Table(table)) = config.get("accounts") {
+        let names = table.keys().map(|name| (name.clone(), path.to_owned()));
+        accounts.extend(names);
+    }
+
+    let dir = path.parent().unwrap_or(Path::new("/"));
+
+    for include in includes {
+        let include = dir.join(shellexpand_path(&include));
+        merge(&mut config, read_path(&include, stack, accounts)?);
+    }
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use uuid::Uuid;
+
+    use super::{find_account, overridden_arrays, read};
+
+    /// A temporary configuration file, removed when dropped.
+    struct ConfigFile(PathBuf);
+
+    impl ConfigFile {
+        fn new(content: &str) -> Self {
+            let path = env::temp_dir().join(format!("himalaya-config-{}.toml", Uuid::new_v4()));
+            fs::write(&path, content).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for ConfigFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn expand_variables_by_default() {
+        env::set_var("HIMALAYA_TEST_INCLUDE_DIR", "/tmp/downloads");
+
+        let file = ConfigFile::new(concat!(
+            "downloads-dir = \"${HIMALAYA_TEST_INCLUDE_DIR}\"\n",
+            "signature = \"${HIMALAYA_TEST_INCLUDE_UNDEFINED:-}\"\n",
+            "password = \"pa$${ss$$$$word\"\n",
+        ));
+
+        let config = read(&[file.0.clone()], false).unwrap();
+
+        assert_eq!(config["downloads-dir"].as_str(), Some("/tmp/downloads"));
+        assert_eq!(config["signature"].as_str(), Some(""));
+        assert_eq!(config["password"].as_str(), Some("pa${ss$$word"));
+    }
+
+    #[test]
+    fn refuse_undefined_variables() {
+        env::remove_var("HIMALAYA_TEST_INCLUDE_MISSING");
+
+        let file = ConfigFile::new("downloads-dir = \"${HIMALAYA_TEST_INCLUDE_MISSING}\"\n");
+
+        let err = read(&[file.0.clone()], false).unwrap_err();
+        let err = format!("{err:#}");
+
+        assert!(err.contains("cannot expand downloads-dir"), "{err}");
+        assert!(
+            err.contains("HIMALAYA_TEST_INCLUDE_MISSING is not defined"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn keep_variables_when_disabled() {
+        let file = ConfigFile::new(concat!(
+            "expand-env = false\n",
+            "downloads-dir = \"${HIMALAYA_TEST_INCLUDE_DIR}\"\n",
+            "password = \"pa${ss$$word\"\n",
+        ));
+
+        let config = read(&[file.0.clone()], false).unwrap();
+
+        assert_eq!(
+            config["downloads-dir"].as_str(),
+            Some("${HIMALAYA_TEST_INCLUDE_DIR}")
+        );
+        assert_eq!(config["password"].as_str(), Some("pa${ss$$word"));
+        assert!(!config.contains_key("expand-env"));
+    }
+
+    #[test]
+    fn refuse_invalid_expand_env() {
+        let file = ConfigFile::new("expand-env = \"yes\"\n");
+
+        let err = read(&[file.0.clone()], false).unwrap_err().to_string();
+
+        assert_eq!(
+            err,
+            format!(
+                "invalid expand-env in {}, expected a boolean",
+                file.0.display()
+            )
+        );
+    }
+
+    #[test]
+    fn find_account_in_included_file() {
+        let included = ConfigFile::new("[accounts.work]\nemail = \"work@localhost\"\n");
+        let main = ConfigFile::new(&format!(
+            "include = \"{}\"\n[accounts.home]\nemail = \"home@localhost\"\n",
+            included.0.display()
+        ));
+
+        let paths = [main.0.clone()];
+        let home = fs::canonicalize(&main.0).unwrap();
+        let work = fs::canonicalize(&included.0).unwrap();
+
+        assert_eq!(find_account(&paths, "home").unwrap(), Some(home));
+        assert_eq!(find_account(&paths, "work").unwrap(), Some(work));
+        assert_eq!(find_account(&paths, "other").unwrap(), None);
+    }
+
+    #[test]
+    fn find_overridden_arrays() {
+        let config: toml::Table = toml::from_str(concat!(
+            "emails = [\"a\"]\n",
+            "empty = []\n",
+            "same = [\"a\"]\n",
+            "[accounts.work]\n",
+            "envelope.list.columns = [\"id\", \"subject\"]\n",
+        ))
+        .unwrap();
+
+        let table: toml::Table = toml::from_str(concat!(
+            "emails = [\"b\"]\n",
+            "empty = [\"b\"]\n",
+            "same = [\"a\"]\n",
+            "added = [\"b\"]\n",
+            "[accounts.work]\n",
+            "envelope.list.columns = [\"id\"]\n",
+        ))
+        .unwrap();
+
+        let mut keys = overridden_arrays(&config, &table);
+        keys.sort();
+
+        assert_eq!(keys, ["accounts.work.envelope.list.columns", "emails"]);
+    }
+}
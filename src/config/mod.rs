@@ -0,0 +1,422 @@
+pub mod command;
+pub mod env;
+pub mod include;
+
+#[cfg(feature = "imap")]
+use std::net::IpAddr;
+use std::{
+    collections::HashMap,
+    io::{stdin, IsTerminal},
+    ops::{Deref, DerefMut},
+    path::PathBuf,
+};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use email::config::Config;
+#[cfg(feature = "imap")]
+use email::tls::Encryption;
+#[cfg(feature = "imap")]
+use pimalaya_tui::himalaya::config::Backend;
+use pimalaya_tui::{
+    himalaya::config::HimalayaTomlConfig, terminal::config::TomlConfig as TomlConfigTrait,
+};
+use serde::{de::Error as _, Deserialize, Deserializer};
+use toml::{Table, Value};
+use tracing::debug;
+#[cfg(feature = "imap")]
+use tracing::warn;
+
+use crate::{
+    account::config::{TomlAccountConfig, TomlAccountExtraConfig},
+    address::alias::Aliases,
+    folder,
+    table::TableConfig,
+};
+
+/// The main TOML configuration.
+///
+/// Wraps the configuration shared with other Himalaya projects, and
+/// adds options specific to the CLI. Both are read from the same
+/// TOML files.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TomlConfig {
+    pub himalaya: HimalayaTomlConfig,
+    pub ui: Option<UiConfig>,
+    /// The recipient aliases, shared by all accounts.
+    pub aliases: Aliases,
+    pub extras: HashMap<String, TomlAccountExtraConfig>,
+}
+
+/// The user interface configuration.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct UiConfig {
+    pub table: Option<TableConfig>,
+}
+
+impl TomlConfig {
+    /// Read the configuration from the given paths, or from the
+    /// default paths if none is given.
+    ///
+    /// Paths can point to files or to directories, whose `*.toml`
+    /// files are read in order. Files can also include other files
+    /// using the `include` option. See [`include`].
+    ///
+    /// Missing files lead to an error containing their path, except
+    /// for the first one which can be created using the wizard when
+    /// running in a terminal.
+    pub async fn from_cli_paths(cli_paths: &[PathBuf]) -> Result<Self> {
+        let paths = Self::resolve_paths(cli_paths)?;
+
+        if let Some(path) = paths.iter().skip(1).find(|path| !path.exists()) {
+            bail!("cannot find configuration file at {}", path.display());
+        }
+
+        if !paths[0].exists() {
+            if !cfg!(feature = "wizard") || !stdin().is_terminal() {
+                bail!("cannot find configuration file at {}", paths[0].display());
+            }
+
+            return Ok(Self::from_paths_or_default(&paths[..1]).await?);
+        }
+
+        let config = include::read(&paths, !cli_paths.is_empty())?;
+
+        Value::Table(config)
+            .try_into()
+            .wrap_err_with(|| format!("cannot parse config file at {}", paths[0].display()))
+    }
+
+    /// Get the configuration paths to read.
+    ///
+    /// Defaults to the first valid default path, followed by the
+    /// `config.d` directory next to it if any.
+    pub fn resolve_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        if !paths.is_empty() {
+            return Ok(paths.to_vec());
+        }
+
+        let Some(path) = Self::first_valid_default_path() else {
+            return Ok(vec![Self::default_path()?]);
+        };
+
+        let dir = path
+            .parent()
+            .filter(|_| path.ends_with("config.toml"))
+            .map(|parent| parent.join("config.d"))
+            .filter(|dir| dir.is_dir());
+
+        Ok([path].into_iter().chain(dir).collect())
+    }
+
+    /// Get the configuration file defining the given account, or the
+    /// one to add it to.
+    ///
+    /// The account is searched in all the configuration files read,
+    /// including the ones of directories and includes. A new account
+    /// is added to the first path, which must not be a directory.
+    pub fn account_path(paths: &[PathBuf], name: &str) -> Result<PathBuf> {
+        let existing: Vec<_> = paths.iter().filter(|p| p.exists()).cloned().collect();
+
+        if let Some(path) = include::find_account(&existing, name)? {
+            return Ok(path);
+        }
+
+        let Some(path) = paths.first() else {
+            return Ok(Self::default_path()?);
+        };
+
+        if path.is_dir() {
+            bail!(
+                "cannot add account {name} to directory {}, \
+                 give the path of a configuration file instead",
+                path.display()
+            );
+        }
+
+        Ok(path.clone())
+    }
+
+    /// Get the CLI specific configuration of the given account.
+    pub fn account_extra_config(&self, name: &str) -> TomlAccountExtraConfig {
+        self.extras.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Get the table configuration shared by all listings.
+    pub fn table_config(&self) -> TableConfig {
+        self.ui
+            .as_ref()
+            .and_then(|ui| ui.table.clone())
+            .unwrap_or_default()
+    }
+
+    /// Warn about unencrypted connections of the given account.
+    #[cfg(feature = "imap")]
+    fn warn_insecure_account(config: &TomlAccountConfig) {
+        if let Some(Backend::Imap(imap_config)) = &config.backend {
+            if let Some(Encryption::None) = imap_config.encryption {
+                let host = &imap_config.host;
+                warn!("unencrypted IMAP connection to {host}");
+            }
+        }
+    }
+}
+
+/// Check that the given account does not connect in plain text to a
+/// remote host by mistake.
+#[cfg(feature = "imap")]
+fn check_account_security(
+    config: &TomlAccountConfig,
+    extra: &TomlAccountExtraConfig,
+) -> Result<(), String> {
+    if let Some(Backend::Imap(imap_config)) = &config.backend {
+        let host = &imap_config.host;
+        let plain = matches!(imap_config.encryption, Some(Encryption::None));
+
+        if plain && !is_loopback(host) && !extra.is_backend_insecure() {
+            return Err(format!(
+                "refusing unencrypted IMAP connection to {host}, \
+                 set backend.insecure = true to allow it"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+impl Deref for TomlConfig {
+    type Target = HimalayaTomlConfig;
+
+    fn deref(&self) -> &Self::Target {
+        &self.himalaya
+    }
+}
+
+impl DerefMut for TomlConfig {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.himalaya
+    }
+}
+
+impl From<TomlConfig> for Config {
+    fn from(config: TomlConfig) -> Self {
+        config.himalaya.into()
+    }
+}
+
+impl<'de> Deserialize<'de> for TomlConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut table = Table::deserialize(deserializer)?;
+
+        let ui = match table.remove("ui") {
+            Some(ui) => Some(ui.try_into().map_err(D::Error::custom)?),
+            None => None,
+        };
+
+        let aliases = match table.remove("aliases") {
+            Some(aliases) => aliases.try_into().map_err(D::Error::custom)?,
+            None => Aliases::default(),
+        };
+
+        let mut extras = HashMap::new();
+
+        if let Some(Value::Table(accounts)) = table.get_mut("accounts") {
+            for (name, account) in accounts.iter_mut() {
+                if let Value::Table(account) = account {
+                    rename_folder_alias(account);
+                }
+
+                let extra = account.clone().try_into().map_err(D::Error::custom)?;
+                extras.insert(name.clone(), extra);
+            }
+        }
+
+        let himalaya: HimalayaTomlConfig =
+            Value::Table(table).try_into().map_err(D::Error::custom)?;
+
+        Ok(Self {
+            himalaya,
+            ui,
+            aliases,
+            extras,
+        })
+    }
+}
+
+/// Rename the `folder.alias` table of the given account into
+/// `folder.aliases`.
+///
+/// Previous versions of the sample configuration documented the
+/// singular form, which was silently ignored.
+fn rename_folder_alias(account: &mut Table) {
+    let Some(Value::Table(folder)) = account.get_mut("folder") else {
+        return;
+    };
+
+    let Some(Value::Table(alias)) = folder.remove("alias") else {
+        return;
+    };
+
+    match folder.get_mut("aliases") {
+        Some(Value::Table(aliases)) => {
+            for (name, folder) in alias {
+                aliases.entry(name).or_insert(folder);
+            }
+        }
+        _ => {
+            folder.insert("aliases".into(), Value::Table(alias));
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TomlConfigTrait for TomlConfig {
+    type TomlAccountConfig = TomlAccountConfig;
+
+    fn project_name() -> &'static str {
+        HimalayaTomlConfig::project_name()
+    }
+
+    fn get_default_account_config(&self) -> Option<(String, Self::TomlAccountConfig)> {
+        self.himalaya.get_default_account_config()
+    }
+
+    fn get_account_config(&self, name: &str) -> Option<(String, Self::TomlAccountConfig)> {
+        self.himalaya.get_account_config(name)
+    }
+
+    #[cfg(feature = "wizard")]
+    async fn from_wizard(path: &std::path::Path) -> color_eyre::Result<Self> {
+        // the configuration is loaded before the printer of the
+        // command is set up, so the wizard gets its own
+        let mut printer = crate::printer::StdoutPrinter::default();
+        let config = Default::default();
+        let himalaya =
+            crate::account::wizard::edit(&mut printer, path, config, None, false).await?;
+
+        Ok(Self {
+            himalaya,
+            ..Default::default()
+        })
+    }
+
+    fn into_account_configs<C, A>(
+        self,
+        account_name: Option<&str>,
+        get_account: impl Fn(&C, &str) -> Option<A>,
+    ) -> pimalaya_tui::Result<(Self::TomlAccountConfig, A)>
+    where
+        Self: Into<C>,
+    {
+        let (name, mut toml_account_config) = self.to_toml_account_config(account_name)?;
+        debug!("using account {name}");
+
+        folder::add_maildirpp_aliases(&mut toml_account_config);
+
+        let mut config = self;
+        config
+            .himalaya
+            .accounts
+            .insert(name.clone(), toml_account_config.clone());
+
+        // only the selected account is checked, so that other
+        // accounts can still be used (or fixed) meanwhile
+        #[cfg(feature = "imap")]
+        {
+            let extra = config.account_extra_config(&name);
+            check_account_security(&toml_account_config, &extra).map_err(|err| {
+                pimalaya_tui::Error::BuildAccountConfigError(format!("{name}: {err}"))
+            })?;
+            Self::warn_insecure_account(&toml_account_config);
+        }
+
+        let account_config = get_account(&config.into(), &name)
+            .ok_or_else(|| pimalaya_tui::Error::BuildAccountConfigError(name))?;
+
+        Ok((toml_account_config, account_config))
+    }
+}
+
+/// Return `true` if the given host points to the local machine.
+#[cfg(feature = "imap")]
+fn is_loopback(host: &str) -> bool {
+    match host.parse::<IpAddr>() {
+        Ok(ip) => ip.is_loopback(),
+        Err(_) => host.eq_ignore_ascii_case("localhost"),
+    }
+}
+
+#[cfg(all(test, feature = "imap", feature = "maildir"))]
+mod tests {
+    use std::{env, fs};
+
+    use email::config::Config;
+    use pimalaya_tui::terminal::config::TomlConfig as _;
+    use uuid::Uuid;
+
+    use super::TomlConfig;
+
+    const CONFIG: &str = r#"
+        [accounts.local]
+        default = true
+        email = "me@localhost"
+        backend.type = "maildir"
+        backend.root-dir = "/tmp/mail"
+
+        [accounts.remote]
+        email = "me@example.org"
+        backend.type = "imap"
+        backend.host = "imap.example.org"
+        backend.port = 143
+        backend.encryption.type = "none"
+        backend.login = "me"
+        backend.auth.type = "password"
+        backend.auth.raw = "secret"
+    "#;
+
+    fn select(config: &TomlConfig, name: &str) -> Result<(), String> {
+        config
+            .clone()
+            .into_account_configs(Some(name), |c: &Config, name| c.account(name).ok())
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn refuse_only_selected_unencrypted_account() {
+        let config: TomlConfig = toml::from_str(CONFIG).unwrap();
+
+        assert_eq!(select(&config, "local"), Ok(()));
+
+        let err = select(&config, "remote").unwrap_err();
+        let expected = "remote: refusing unencrypted IMAP connection to imap.example.org, \
+                        set backend.insecure = true to allow it";
+        assert!(err.ends_with(expected), "unexpected error: {err}");
+
+        let config = format!("{CONFIG}\nbackend.insecure = true\n");
+        let config: TomlConfig = toml::from_str(&config).unwrap();
+        assert_eq!(select(&config, "remote"), Ok(()));
+    }
+
+    #[test]
+    fn refuse_new_account_in_directory() {
+        let dir = env::temp_dir().join(format!("himalaya-config-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("work.toml"), "[accounts.work]\n").unwrap();
+
+        let paths = [dir.clone()];
+        let work_path = fs::canonicalize(dir.join("work.toml")).unwrap();
+        let work = TomlConfig::account_path(&paths, "work");
+        let home = TomlConfig::account_path(&paths, "home");
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(work.unwrap(), work_path);
+        assert!(home
+            .unwrap_err()
+            .to_string()
+            .starts_with("cannot add account home"));
+    }
+}
@@ -0,0 +1,242 @@
+use chrono::{Days, Local, Months, NaiveDate};
+use clap::Parser;
+use email::search_query::{filter::SearchEmailsFilterQuery, SearchEmailsQuery};
+
+/// The envelopes date range flags parser.
+#[derive(Debug, Default, Parser)]
+pub struct EnvelopeDateRangeFlags {
+    /// Show only envelopes dated on or after the given date.
+    ///
+    /// The date can be absolute (yyyy-mm-dd) or relative to today
+    /// (for example 7d, 2w, 1m or 1y). Dates are evaluated in the
+    /// local timezone.
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    pub after: Option<NaiveDate>,
+
+    /// Show only envelopes dated strictly before the given date.
+    ///
+    /// The date can be absolute (yyyy-mm-dd) or relative to today
+    /// (for example 7d, 2w, 1m or 1y). Dates are evaluated in the
+    /// local timezone.
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    pub before: Option<NaiveDate>,
+}
+
+impl EnvelopeDateRangeFlags {
+    /// Restrict the given query to the date range.
+    pub fn apply(&self, query: Option<SearchEmailsQuery>) -> Option<SearchEmailsQuery> {
+        let Some(range) = self.to_filter() else {
+            return query;
+        };
+
+        let mut query = query.unwrap_or(SearchEmailsQuery {
+            filter: None,
+            sort: None,
+        });
+
+        query.filter = Some(match query.filter.take() {
+            Some(filter) => SearchEmailsFilterQuery::And(Box::new(filter), Box::new(range)),
+            None => range,
+        });
+
+        Some(query)
+    }
+
+    fn to_filter(&self) -> Option<SearchEmailsFilterQuery> {
+        // the after filter of backends is exclusive, while the
+        // --after flag is inclusive
+        let after = self
+            .after
+            .and_then(|date| date.checked_sub_days(Days::new(1)))
+            .map(SearchEmailsFilterQuery::AfterDate);
+        let before = self.before.map(SearchEmailsFilterQuery::BeforeDate);
+
+        match (after, before) {
+            (Some(after), Some(before)) => Some(SearchEmailsFilterQuery::And(
+                Box::new(after),
+                Box::new(before),
+            )),
+            (after, before) => after.or(before),
+        }
+    }
+}
+
+/// Parse the given absolute or relative date.
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        return Ok(date);
+    }
+
+    let err = || format!("invalid date `{date}`, expected yyyy-mm-dd or <n><d|w|m|y>");
+
+    let unit = date.chars().last().ok_or_else(err)?;
+    let n: u32 = date[..date.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| err())?;
+
+    let today = Local::now().date_naive();
+
+    let relative = match unit {
+        'd' => today.checked_sub_days(Days::new(n.into())),
+        'w' => today.checked_sub_days(Days::new(u64::from(n) * 7)),
+        'm' => today.checked_sub_months(Months::new(n)),
+        'y' => n
+            .checked_mul(12)
+            .and_then(|n| today.checked_sub_months(Months::new(n))),
+        _ => return Err(err()),
+    };
+
+    relative.ok_or_else(|| format!("date `{date}` is out of range"))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Days, Local, Months, NaiveDate};
+
+    use super::parse_date;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn parse_absolute_dates() {
+        assert_eq!(parse_date("2024-02-29"), Ok(date(2024, 2, 29)));
+        assert!(parse_date("2023-02-29").is_err());
+        assert!(parse_date("29/02/2024").is_err());
+    }
+
+    #[test]
+    fn parse_relative_dates() {
+        let today = Local::now().date_naive();
+
+        assert_eq!(parse_date("0d"), Ok(today));
+        assert_eq!(parse_date("7d"), Ok(today - Days::new(7)));
+        assert_eq!(parse_date("2w"), Ok(today - Days::new(14)));
+        assert_eq!(parse_date("1m"), Ok(today - Months::new(1)));
+        assert_eq!(parse_date("1y"), Ok(today - Months::new(12)));
+
+        assert!(parse_date("").is_err());
+        assert!(parse_date("d").is_err());
+        assert!(parse_date("7x").is_err());
+        assert!(parse_date("-7d").is_err());
+        assert!(parse_date("4294967295y").is_err());
+    }
+
+    #[cfg(feature = "maildir")]
+    mod maildir {
+        use std::sync::Arc;
+
+        use super::date;
+        use crate::{envelope::arg::date::EnvelopeDateRangeFlags, fixture::MaildirRoot};
+        use chrono::{FixedOffset, Local, NaiveDate, Offset, TimeZone};
+        use email::{
+            backend::context::BackendContextBuilder,
+            envelope::list::{maildir::ListMaildirEnvelopes, ListEnvelopes, ListEnvelopesOptions},
+            maildir::{config::MaildirConfig, MaildirContextBuilder, MaildirContextSync},
+            message::add::{maildir::AddMaildirMessage, AddMessage},
+        };
+
+        /// The messages of the inbox, named after their local date:
+        /// one minute around each midnight of the 10th of March.
+        const MESSAGES: [(&str, u32, u32, u32); 4] = [
+            ("9 late", 9, 23, 59),
+            ("10 early", 10, 0, 0),
+            ("10 late", 10, 23, 59),
+            ("11 early", 11, 0, 0),
+        ];
+
+        /// Format the given local date with an offset 12 hours away
+        /// from the local one, so that the date written in the header
+        /// differs from the local date around midnight.
+        fn header_date(day: u32, hour: u32, min: u32) -> String {
+            let date = Local
+                .with_ymd_and_hms(2024, 3, day, hour, min, 0)
+                .single()
+                .unwrap();
+
+            let offset = match date.offset().fix().local_minus_utc() >= 0 {
+                true => FixedOffset::west_opt(12 * 3600).unwrap(),
+                false => FixedOffset::east_opt(12 * 3600).unwrap(),
+            };
+
+            date.with_timezone(&offset).to_rfc2822()
+        }
+
+        async fn inbox() -> (MaildirRoot, MaildirContextSync) {
+            let root = MaildirRoot::new();
+            root.folder("", &[]);
+
+            let config = MaildirConfig {
+                root_dir: root.0.clone(),
+                maildirpp: true,
+            };
+
+            let ctx = MaildirContextBuilder::new(Arc::default(), Arc::new(config))
+                .build()
+                .await
+                .unwrap();
+
+            for (subject, day, hour, min) in MESSAGES {
+                let date = header_date(day, hour, min);
+                let msg = format!("Date: {date}\r\nSubject: {subject}\r\n\r\n\r\n");
+                AddMaildirMessage::new(&ctx)
+                    .add_message("INBOX", msg.as_bytes())
+                    .await
+                    .unwrap();
+            }
+
+            (root, ctx)
+        }
+
+        /// List the subjects of the inbox messages matching the given
+        /// date range.
+        async fn list(
+            ctx: &MaildirContextSync,
+            after: Option<NaiveDate>,
+            before: Option<NaiveDate>,
+        ) -> Vec<String> {
+            let opts = ListEnvelopesOptions {
+                page_size: 0,
+                page: 0,
+                query: EnvelopeDateRangeFlags { after, before }.apply(None),
+            };
+
+            let envelopes = ListMaildirEnvelopes::new(ctx)
+                .list_envelopes("INBOX", opts)
+                .await
+                .unwrap();
+
+            let mut subjects: Vec<_> = envelopes.iter().map(|e| e.subject.clone()).collect();
+            subjects.sort();
+            subjects
+        }
+
+        #[tokio::test]
+        async fn after_is_inclusive() {
+            let (_root, ctx) = inbox().await;
+
+            let subjects = list(&ctx, Some(date(2024, 3, 10)), None).await;
+            assert_eq!(subjects, ["10 early", "10 late", "11 early"]);
+        }
+
+        #[tokio::test]
+        async fn before_is_exclusive() {
+            let (_root, ctx) = inbox().await;
+
+            let subjects = list(&ctx, None, Some(date(2024, 3, 11))).await;
+            assert_eq!(subjects, ["10 early", "10 late", "9 late"]);
+        }
+
+        #[tokio::test]
+        async fn range_of_one_day() {
+            let (_root, ctx) = inbox().await;
+
+            let subjects = list(&ctx, Some(date(2024, 3, 10)), Some(date(2024, 3, 11))).await;
+            assert_eq!(subjects, ["10 early", "10 late"]);
+
+            let subjects = list(&ctx, Some(date(2024, 3, 10)), Some(date(2024, 3, 10))).await;
+            assert!(subjects.is_empty());
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use clap::ValueEnum;
+use email::search_query::{filter::SearchEmailsFilterQuery, SearchEmailsQuery};
+
+/// The engine answering envelope searches.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum SearchEngine {
+    /// The backend of the account, like IMAP SEARCH.
+    #[value(alias = "imap")]
+    Backend,
+    /// The notmuch index of the account.
+    Notmuch,
+}
+
+impl SearchEngine {
+    /// Select the engine answering the given query.
+    ///
+    /// The notmuch index answers queries with body conditions when
+    /// available, the backend answers other queries.
+    pub fn select(query: Option<&SearchEmailsQuery>, has_index: bool) -> Self {
+        let has_body = query
+            .and_then(|query| query.filter.as_ref())
+            .is_some_and(has_body);
+
+        if has_index && has_body {
+            Self::Notmuch
+        } else {
+            Self::Backend
+        }
+    }
+}
+
+/// Return `true` if the given filter has a body condition.
+fn has_body(filter: &SearchEmailsFilterQuery) -> bool {
+    match filter {
+        SearchEmailsFilterQuery::And(left, right) | SearchEmailsFilterQuery::Or(left, right) => {
+            has_body(left) || has_body(right)
+        }
+        SearchEmailsFilterQuery::Not(filter) => has_body(filter),
+        SearchEmailsFilterQuery::Body(_) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,338 @@
+use std::fmt;
+
+use clap::Parser;
+use color_eyre::Result;
+use email::{
+    flag::Flag,
+    search_query::{filter::SearchEmailsFilterQuery, SearchEmailsQuery},
+};
+
+use crate::account::config::TomlAccountConfig;
+
+/// The envelopes flag filters parser.
+#[derive(Debug, Default, Parser)]
+pub struct EnvelopeFlagFilterFlags {
+    /// Show only envelopes having the given flag.
+    ///
+    /// The flag can be seen, answered, flagged, deleted, draft or a
+    /// custom keyword. Negated flags like unseen, unanswered,
+    /// unflagged or undeleted are accepted as well. The argument can
+    /// be repeated, envelopes must then match all the given flags.
+    #[arg(long = "flag", value_name = "FLAG", value_parser = parse_flag_filter)]
+    pub flags: Vec<FlagFilter>,
+
+    /// Show only envelopes not having the given flag.
+    ///
+    /// The argument can be repeated, and can be combined with
+    /// --flag. Envelopes must then match all the given flags.
+    #[arg(long = "not-flag", value_name = "FLAG", value_parser = parse_flag_filter)]
+    pub not_flags: Vec<FlagFilter>,
+}
+
+impl EnvelopeFlagFilterFlags {
+    /// Return `true` if no flag filter has been given.
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty() && self.not_flags.is_empty()
+    }
+
+    /// Get the active flag filters, with negated flags moved to the
+    /// flags envelopes must not have.
+    pub fn to_filters(&self) -> FlagFilters {
+        let mut filters = FlagFilters::default();
+
+        let flags = self.flags.iter().map(|filter| (filter, false));
+        let not_flags = self.not_flags.iter().map(|filter| (filter, true));
+
+        for (filter, negated) in flags.chain(not_flags) {
+            let flags = if filter.negated != negated {
+                &mut filters.not_flags
+            } else {
+                &mut filters.flags
+            };
+
+            if !flags.contains(&filter.flag) {
+                flags.push(filter.flag.clone());
+            }
+        }
+
+        filters
+    }
+
+    /// Restrict the given query to the envelopes matching the flag
+    /// filters, for the backend of the given account.
+    ///
+    /// Filters are given to the backend: they are translated into
+    /// SEARCH criteria with IMAP, and into tags with notmuch.
+    pub fn apply(
+        &self,
+        config: &TomlAccountConfig,
+        query: Option<SearchEmailsQuery>,
+    ) -> Result<Option<SearchEmailsQuery>> {
+        let filters = self.to_filters();
+
+        let flags = filters.flags.iter().map(|flag| (flag, false));
+        let not_flags = filters.not_flags.iter().map(|flag| (flag, true));
+
+        let mut filter = None;
+
+        for (flag, negated) in flags.chain(not_flags) {
+            let (flag, negated) = to_backend_flag(config, flag, negated)?;
+
+            let next = if negated {
+                SearchEmailsFilterQuery::Not(Box::new(SearchEmailsFilterQuery::Flag(flag)))
+            } else {
+                SearchEmailsFilterQuery::Flag(flag)
+            };
+
+            filter = Some(match filter {
+                Some(filter) => SearchEmailsFilterQuery::And(Box::new(filter), Box::new(next)),
+                None => next,
+            });
+        }
+
+        let Some(filter) = filter else {
+            return Ok(query);
+        };
+
+        let mut query = query.unwrap_or(SearchEmailsQuery {
+            filter: None,
+            sort: None,
+        });
+
+        query.filter = Some(match query.filter.take() {
+            Some(query) => SearchEmailsFilterQuery::And(Box::new(query), Box::new(filter)),
+            None => filter,
+        });
+
+        Ok(Some(query))
+    }
+}
+
+/// A flag given to --flag or --not-flag.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FlagFilter {
+    pub flag: Flag,
+    /// Whether the flag was given in its negated form, like unseen.
+    pub negated: bool,
+}
+
+/// The active flag filters.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FlagFilters {
+    /// The flags envelopes must have.
+    pub flags: Vec<Flag>,
+    /// The flags envelopes must not have.
+    pub not_flags: Vec<Flag>,
+}
+
+impl fmt::Display for FlagFilters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = self.flags.iter().map(|flag| flag.to_string());
+        let not_flags = self.not_flags.iter().map(|flag| format!("not {flag}"));
+        let filters: Vec<_> = flags.chain(not_flags).collect();
+        write!(f, "{}", filters.join(", "))
+    }
+}
+
+/// Get the flag understood by the backend of the given account.
+///
+/// Notmuch has no seen flag, the notmuch backend reads it from the
+/// absence of the unread tag. Other flags are searched as tags, using
+/// the names given to them by the notmuch backend.
+#[allow(unused)]
+fn to_backend_flag(config: &TomlAccountConfig, flag: &Flag, negated: bool) -> Result<(Flag, bool)> {
+    #[cfg(any(feature = "imap", feature = "notmuch"))]
+    use pimalaya_tui::himalaya::config::Backend;
+
+    match &config.backend {
+        #[cfg(feature = "notmuch")]
+        Some(Backend::Notmuch(_)) => Ok(match flag {
+            Flag::Seen => (Flag::custom("unread"), !negated),
+            Flag::Answered => (Flag::custom("replied"), negated),
+            flag => (flag.clone(), negated),
+        }),
+        #[cfg(feature = "imap")]
+        Some(Backend::Imap(_)) => {
+            use color_eyre::eyre::bail;
+
+            if let Flag::Custom(keyword) = flag {
+                if !crate::tag::is_imap_keyword(keyword) {
+                    bail!("cannot use {keyword:?} as IMAP keyword: invalid characters");
+                }
+            }
+
+            Ok((flag.clone(), negated))
+        }
+        _ => Ok((flag.clone(), negated)),
+    }
+}
+
+/// Parse the given flag, accepting negated flags like unseen.
+fn parse_flag_filter(flag: &str) -> Result<FlagFilter, String> {
+    let flag = flag.trim();
+
+    if flag.is_empty() {
+        return Err(String::from("flag cannot be empty"));
+    }
+
+    let negated = match flag.to_ascii_lowercase().as_str() {
+        "unseen" | "unread" => Some(Flag::Seen),
+        "unanswered" | "unreplied" => Some(Flag::Answered),
+        "unflagged" => Some(Flag::Flagged),
+        "undeleted" => Some(Flag::Deleted),
+        _ => None,
+    };
+
+    Ok(match negated {
+        Some(flag) => FlagFilter {
+            flag,
+            negated: true,
+        },
+        None => FlagFilter {
+            flag: Flag::from(flag),
+            negated: false,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use email::flag::Flag;
+
+    use super::{parse_flag_filter, EnvelopeFlagFilterFlags, FlagFilter, FlagFilters};
+
+    fn filter(flag: Flag, negated: bool) -> FlagFilter {
+        FlagFilter { flag, negated }
+    }
+
+    #[test]
+    fn parse_flags() {
+        assert_eq!(parse_flag_filter("seen"), Ok(filter(Flag::Seen, false)));
+        assert_eq!(
+            parse_flag_filter(" flagged "),
+            Ok(filter(Flag::Flagged, false))
+        );
+        assert_eq!(parse_flag_filter("draft"), Ok(filter(Flag::Draft, false)));
+    }
+
+    #[test]
+    fn parse_negated_flags() {
+        assert_eq!(parse_flag_filter("unseen"), Ok(filter(Flag::Seen, true)));
+        assert_eq!(parse_flag_filter("Unread"), Ok(filter(Flag::Seen, true)));
+        assert_eq!(
+            parse_flag_filter("unanswered"),
+            Ok(filter(Flag::Answered, true))
+        );
+        assert_eq!(
+            parse_flag_filter("unreplied"),
+            Ok(filter(Flag::Answered, true))
+        );
+        assert_eq!(
+            parse_flag_filter("UNFLAGGED"),
+            Ok(filter(Flag::Flagged, true))
+        );
+        assert_eq!(
+            parse_flag_filter("undeleted"),
+            Ok(filter(Flag::Deleted, true))
+        );
+    }
+
+    #[test]
+    fn parse_custom_keywords() {
+        let important = filter(Flag::custom("$Important"), false);
+        assert_eq!(parse_flag_filter("$Important"), Ok(important));
+
+        // only known flags have a negated form
+        let undone = filter(Flag::custom("undone"), false);
+        assert_eq!(parse_flag_filter("undone"), Ok(undone));
+
+        assert!(parse_flag_filter("").is_err());
+        assert!(parse_flag_filter("  ").is_err());
+    }
+
+    #[test]
+    fn to_filters() {
+        let flags = EnvelopeFlagFilterFlags {
+            flags: vec![
+                filter(Flag::Seen, true),
+                filter(Flag::custom("work"), false),
+            ],
+            not_flags: vec![
+                filter(Flag::Deleted, false),
+                filter(Flag::Seen, false),
+                filter(Flag::Flagged, true),
+            ],
+        };
+
+        let filters = flags.to_filters();
+
+        let expected = FlagFilters {
+            flags: vec![Flag::custom("work"), Flag::Flagged],
+            not_flags: vec![Flag::Seen, Flag::Deleted],
+        };
+
+        assert_eq!(filters, expected);
+        assert_eq!(filters.to_string(), "work, flagged, not seen, not deleted");
+    }
+
+    #[cfg(any(feature = "imap", feature = "notmuch"))]
+    fn config(backend: &str) -> crate::account::config::TomlAccountConfig {
+        toml::from_str(&format!("email = \"me@localhost\"\n{backend}")).unwrap()
+    }
+
+    #[cfg(feature = "notmuch")]
+    #[test]
+    fn notmuch_flags() {
+        use super::to_backend_flag;
+
+        let config = config(concat!(
+            "backend.type = \"notmuch\"\n",
+            "backend.database-path = \"/tmp/mail\"\n",
+        ));
+
+        let unread = Flag::custom("unread");
+        let seen = to_backend_flag(&config, &Flag::Seen, false).unwrap();
+        assert_eq!(seen, (unread.clone(), true));
+        let unseen = to_backend_flag(&config, &Flag::Seen, true).unwrap();
+        assert_eq!(unseen, (unread, false));
+
+        let replied = Flag::custom("replied");
+        let answered = to_backend_flag(&config, &Flag::Answered, false).unwrap();
+        assert_eq!(answered, (replied.clone(), false));
+        let unanswered = to_backend_flag(&config, &Flag::Answered, true).unwrap();
+        assert_eq!(unanswered, (replied, true));
+
+        let work = Flag::custom("work");
+        let custom = to_backend_flag(&config, &work, true).unwrap();
+        assert_eq!(custom, (work, true));
+    }
+
+    #[cfg(feature = "imap")]
+    #[test]
+    fn imap_keywords() {
+        use super::to_backend_flag;
+
+        let config = config(concat!(
+            "backend.type = \"imap\"\n",
+            "backend.host = \"localhost\"\n",
+            "backend.port = 143\n",
+            "backend.encryption.type = \"none\"\n",
+            "backend.login = \"me\"\n",
+            "backend.auth.type = \"password\"\n",
+            "backend.auth.raw = \"secret\"\n",
+        ));
+
+        let seen = to_backend_flag(&config, &Flag::Seen, true).unwrap();
+        assert_eq!(seen, (Flag::Seen, true));
+
+        let important = Flag::custom("$Important");
+        let custom = to_backend_flag(&config, &important, false).unwrap();
+        assert_eq!(custom, (important, false));
+
+        for keyword in ["to do", "(work)", "\"quoted\"", "café"] {
+            let err = to_backend_flag(&config, &Flag::custom(keyword), false).unwrap_err();
+            let expected = format!("cannot use {keyword:?} as IMAP keyword: invalid characters");
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+}
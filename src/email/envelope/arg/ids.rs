@@ -1,17 +1,25 @@
 use clap::Parser;
 
+use crate::envelope::picker;
+
 /// The envelope id argument parser.
 #[derive(Debug, Parser)]
 pub struct EnvelopeIdArg {
     /// The envelope id.
-    #[arg(value_name = "ID", required = true)]
-    pub id: usize,
+    ///
+    /// When omitted and stdin is a terminal, the envelope is picked
+    /// interactively from the envelopes of the folder.
+    #[arg(value_name = "ID", required = !picker::is_enabled())]
+    pub id: Option<usize>,
 }
 
 /// The envelopes ids arguments parser.
 #[derive(Debug, Parser)]
 pub struct EnvelopeIdsArgs {
     /// The list of envelopes ids.
-    #[arg(value_name = "ID", required = true)]
+    ///
+    /// When omitted and stdin is a terminal, envelopes are picked
+    /// interactively from the envelopes of the folder.
+    #[arg(value_name = "ID", required = !picker::is_enabled())]
     pub ids: Vec<usize>,
 }
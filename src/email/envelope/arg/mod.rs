@@ -1 +1,7 @@
+pub mod date;
+#[cfg(feature = "notmuch")]
+pub mod engine;
+pub mod flag;
 pub mod ids;
+pub mod order;
+pub mod query;
@@ -0,0 +1,42 @@
+use clap::ValueEnum;
+use email::search_query::{
+    sort::{SearchEmailsSorter, SearchEmailsSorterKind, SearchEmailsSorterOrder},
+    SearchEmailsQuery,
+};
+use serde::Deserialize;
+
+/// The date order of listed envelopes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnvelopeOrder {
+    /// Oldest envelopes first.
+    Asc,
+    /// Most recent envelopes first.
+    #[default]
+    Desc,
+}
+
+impl EnvelopeOrder {
+    /// Sort the given query by date in this order, unless the query
+    /// already has a sort query.
+    ///
+    /// Backends sort envelopes by descending date by default, so only
+    /// the ascending order needs a sort query.
+    pub fn apply(self, query: Option<SearchEmailsQuery>) -> Option<SearchEmailsQuery> {
+        if self == Self::Desc || query.as_ref().is_some_and(|query| query.sort.is_some()) {
+            return query;
+        }
+
+        let mut query = query.unwrap_or(SearchEmailsQuery {
+            filter: None,
+            sort: None,
+        });
+
+        query.sort = Some(vec![SearchEmailsSorter(
+            SearchEmailsSorterKind::Date,
+            SearchEmailsSorterOrder::Ascending,
+        )]);
+
+        Some(query)
+    }
+}
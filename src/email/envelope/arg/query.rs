@@ -0,0 +1,678 @@
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use email::search_query::sort::SearchEmailsSorterOrder;
+
+use crate::envelope::size::{self, SizeQuery};
+
+/// Filter keywords, which all expect a pattern.
+///
+/// The size keywords are not understood by backends, they are taken
+/// out of the query by [`join_with_size`].
+const FILTER_KEYWORDS: [&str; 10] = [
+    "date", "before", "after", "from", "to", "subject", "body", "flag", "larger", "smaller",
+];
+
+/// Return `true` if the given word starts a query, given the word
+/// following it if any.
+///
+/// A query starts with a parenthesis, a negation, a filter (alone or
+/// written as `<keyword>:<pattern>`) or a sort query (`order by`).
+pub fn is_query_start(word: &str, next: Option<&str>) -> bool {
+    let is = |word: &str, expected: &str| word.eq_ignore_ascii_case(expected);
+    let kw = word.split(':').next().unwrap_or_default();
+
+    word.starts_with('(')
+        || is(word, "not")
+        || (is(word, "order") && next.is_some_and(|next| is(next, "by")))
+        || FILTER_KEYWORDS.iter().any(|keyword| is(kw, keyword))
+}
+
+/// Split the given argument before the first word starting a query,
+/// see [`is_query_start`].
+///
+/// The first word is never considered as the start of a query.
+pub fn split_query(arg: &str) -> (&str, &str) {
+    let words = words(arg);
+
+    for (n, (i, word)) in words.iter().enumerate().skip(1) {
+        let next = words.get(n + 1).map(|(_, word)| *word);
+
+        if is_query_start(word, next) {
+            return (&arg[..*i], &arg[*i..]);
+        }
+    }
+
+    (arg, "")
+}
+
+/// Join the given query arguments into a search query.
+///
+/// On top of the search query syntax, the following shorthands are
+/// supported:
+///
+///  - filters can be written as `<keyword>:<pattern>` (for example
+///    `from:alice` or `subject:"quarterly report"`)
+///  - quoted patterns are unquoted and their spaces escaped
+///  - filters following each other are joined with `and` (for
+///    example `from:alice not flag:seen`)
+pub fn join(args: &[String]) -> String {
+    to_query(&to_tokens(args))
+}
+
+/// Join the given query arguments into a search query, taking the
+/// message size criteria out of it.
+///
+/// Size filters (`larger:<size>` and `smaller:<size>`) must apply to
+/// all envelopes: they cannot be negated, grouped in parentheses nor
+/// joined with `or`. Sorting by size must come first in the sort
+/// query (for example `order by size desc date`).
+pub fn join_with_size(args: &[String]) -> Result<(String, SizeQuery)> {
+    let mut tokens = to_tokens(args);
+    let mut query = SizeQuery::default();
+
+    take_size_filters(&mut tokens, &mut query)?;
+    take_size_sort(&mut tokens, &mut query)?;
+
+    Ok((to_query(&tokens), query))
+}
+
+fn to_tokens(args: &[String]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut sorting = false;
+
+    for arg in args {
+        if sorting {
+            tokens.push(Token::Raw(arg.clone()));
+            continue;
+        }
+
+        // an argument like `subject:quarterly report` comes from a
+        // shell-quoted `subject:"quarterly report"`, the value is the
+        // pattern up to the next operator or sort query (other
+        // arguments like `from:bob)` are tokenized)
+        if let Some((kw, pattern)) = split_keyword(arg) {
+            let is_quoted = pattern.contains(char::is_whitespace);
+            let (pattern, rest) = split_operator(pattern);
+
+            let is_single_filter = is_quoted
+                && !pattern.starts_with('"')
+                && !pattern
+                    .split_whitespace()
+                    .any(|word| split_keyword(word).is_some());
+
+            if is_single_filter {
+                tokens.push(Token::Filter(kw, escape(pattern.trim_end())));
+                sorting = tokenize(rest, &mut tokens);
+                continue;
+            }
+        }
+
+        // an argument following a lone filter keyword is the pattern
+        // of this filter, even if it contains spaces
+        if let Some(Token::Raw(word)) = tokens.last() {
+            let kw = FILTER_KEYWORDS
+                .into_iter()
+                .find(|kw| kw.eq_ignore_ascii_case(word));
+
+            if let Some(kw) = kw {
+                let pattern = if arg.starts_with('"') || !arg.contains(char::is_whitespace) {
+                    to_pattern(arg)
+                } else {
+                    escape(arg)
+                };
+
+                tokens.pop();
+                tokens.push(Token::Filter(kw, pattern));
+                continue;
+            }
+        }
+
+        sorting = tokenize(arg, &mut tokens);
+    }
+
+    tokens
+}
+
+fn to_query(tokens: &[Token]) -> String {
+    let mut query = String::new();
+    let mut prev: Option<&Token> = None;
+
+    for token in tokens {
+        let ends_filter = matches!(prev, Some(Token::Filter(..) | Token::RParen));
+        let starts_filter = matches!(token, Token::Filter(..) | Token::Not | Token::LParen);
+
+        if ends_filter && starts_filter {
+            query.push_str(" and");
+        }
+
+        if !query.is_empty() && !query.ends_with('(') && *token != Token::RParen {
+            query.push(' ');
+        }
+
+        match token {
+            Token::LParen => query.push('('),
+            Token::RParen => query.push(')'),
+            Token::Not => query.push_str("not"),
+            Token::Filter(kw, pattern) => {
+                query.push_str(kw);
+                query.push(' ');
+                query.push_str(pattern);
+            }
+            Token::Raw(raw) => query.push_str(raw),
+        }
+
+        prev = Some(token);
+    }
+
+    query
+}
+
+/// Take the size filters out of the given tokens.
+fn take_size_filters(tokens: &mut Vec<Token>, query: &mut SizeQuery) -> Result<()> {
+    let mut i = 0;
+    let mut depth = 0usize;
+
+    while i < tokens.len() {
+        let (kw, pattern) = match &tokens[i] {
+            Token::LParen => {
+                depth += 1;
+                i += 1;
+                continue;
+            }
+            Token::RParen => {
+                depth = depth.saturating_sub(1);
+                i += 1;
+                continue;
+            }
+            Token::Filter(kw @ ("larger" | "smaller"), pattern) => (*kw, pattern),
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+
+        let is_or = |token: Option<&Token>| matches!(token, Some(Token::Raw(op)) if op.eq_ignore_ascii_case("or"));
+
+        let prev = i.checked_sub(1).and_then(|i| tokens.get(i));
+        let next = tokens.get(i + 1);
+
+        if depth > 0 || matches!(prev, Some(Token::Not)) || is_or(prev) || is_or(next) {
+            bail!("cannot negate, group or join with or the size filter {kw}:{pattern}");
+        }
+
+        let size = size::parse(&pattern.replace('\\', "")).map_err(|err| eyre!(err))?;
+
+        match kw {
+            "larger" => query.larger = Some(query.larger.map_or(size, |prev| prev.max(size))),
+            _ => query.smaller = Some(query.smaller.map_or(size, |prev| prev.min(size))),
+        }
+
+        tokens.remove(i);
+
+        let is_and = |token: Option<&Token>| matches!(token, Some(Token::Raw(op)) if op.eq_ignore_ascii_case("and"));
+
+        if i > 0 && is_and(tokens.get(i - 1)) {
+            tokens.remove(i - 1);
+            i -= 1;
+        } else if is_and(tokens.get(i)) {
+            tokens.remove(i);
+        }
+    }
+
+    Ok(())
+}
+
+/// Take the size sort kind out of the sort query of the given tokens.
+fn take_size_sort(tokens: &mut Vec<Token>, query: &mut SizeQuery) -> Result<()> {
+    let start = tokens.iter().position(
+        |token| matches!(token, Token::Raw(raw) if next_word(raw).0.eq_ignore_ascii_case("order")),
+    );
+
+    let Some(start) = start else {
+        return Ok(());
+    };
+
+    let sort: Vec<String> = tokens
+        .drain(start..)
+        .filter_map(|token| match token {
+            Token::Raw(raw) => Some(raw),
+            _ => None,
+        })
+        .collect();
+    let sort = sort.join(" ");
+
+    let mut words = sort.split_whitespace().peekable();
+    let mut kept: Vec<&str> = words.by_ref().take(2).collect();
+    let mut kinds = 0;
+
+    while let Some(word) = words.next() {
+        let order =
+            words.next_if(|word| ["asc", "desc"].iter().any(|o| o.eq_ignore_ascii_case(word)));
+
+        if !word.eq_ignore_ascii_case("size") {
+            kinds += 1;
+            kept.push(word);
+            kept.extend(order);
+            continue;
+        }
+
+        if kinds > 0 {
+            bail!("cannot sort by size after other kinds, size must be the first sort kind");
+        }
+
+        query.sort = Some(match order {
+            Some(order) if order.eq_ignore_ascii_case("desc") => {
+                SearchEmailsSorterOrder::Descending
+            }
+            _ => SearchEmailsSorterOrder::Ascending,
+        });
+    }
+
+    if kinds > 0 || query.sort.is_none() {
+        tokens.push(Token::Raw(kept.join(" ")));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Not,
+    Filter(&'static str, String),
+    /// Operators, sort query and anything else, kept as it is.
+    Raw(String),
+}
+
+/// Split the given query argument into tokens.
+///
+/// Returns `true` when the sort query has been reached, in which
+/// case the rest of the query is kept as it is.
+fn tokenize(arg: &str, tokens: &mut Vec<Token>) -> bool {
+    let mut rest = arg;
+
+    loop {
+        rest = rest.trim_start();
+
+        let Some(c) = rest.chars().next() else {
+            return false;
+        };
+
+        match c {
+            '(' => {
+                tokens.push(Token::LParen);
+                rest = &rest[1..];
+                continue;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                rest = &rest[1..];
+                continue;
+            }
+            _ => (),
+        }
+
+        let (word, next) = next_word(rest);
+
+        if word.eq_ignore_ascii_case("order") {
+            tokens.push(Token::Raw(rest.to_owned()));
+            return true;
+        }
+
+        if word.eq_ignore_ascii_case("not") {
+            tokens.push(Token::Not);
+            rest = next;
+            continue;
+        }
+
+        if let Some((kw, pattern)) = split_keyword(word) {
+            tokens.push(Token::Filter(kw, to_pattern(pattern)));
+            rest = next;
+            continue;
+        }
+
+        let kw = FILTER_KEYWORDS
+            .into_iter()
+            .find(|kw| kw.eq_ignore_ascii_case(word));
+
+        if let Some(kw) = kw {
+            let (pattern, after) = next_word(next.trim_start());
+
+            if !pattern.is_empty() {
+                tokens.push(Token::Filter(kw, to_pattern(pattern)));
+                rest = after;
+                continue;
+            }
+        }
+
+        tokens.push(Token::Raw(word.to_owned()));
+        rest = next;
+    }
+}
+
+/// Read the next word of the given input.
+///
+/// A word ends with a whitespace or a parenthesis, unless they are
+/// quoted or escaped.
+fn next_word(input: &str) -> (&str, &str) {
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (i, c) in input.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            c if quoted => _ = c,
+            c if c.is_whitespace() || c == '(' || c == ')' => {
+                return (&input[..i], &input[i..]);
+            }
+            _ => (),
+        }
+    }
+
+    (input, "")
+}
+
+/// Split the given pattern before its first operator (`and`, `or`
+/// and `not`) or sort query (`order by`), if any.
+///
+/// The first word always belongs to the pattern, so that a pattern
+/// like `not found` is kept as it is.
+fn split_operator(pattern: &str) -> (&str, &str) {
+    let words = words(pattern);
+    let is = |word: &str, expected: &str| word.eq_ignore_ascii_case(expected);
+
+    for (n, (i, word)) in words.iter().enumerate().skip(1) {
+        let is_operator = ["and", "or", "not"].iter().any(|op| is(word, op));
+        let is_sort = is(word, "order") && words.get(n + 1).is_some_and(|(_, w)| is(w, "by"));
+
+        if is_operator || is_sort {
+            return (&pattern[..*i], &pattern[*i..]);
+        }
+    }
+
+    (pattern, "")
+}
+
+/// Split the given input into whitespace-separated words, along with
+/// their byte offset.
+fn words(input: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+
+    for (i, c) in input.char_indices().chain([(input.len(), ' ')]) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(begin), true) => {
+                words.push((begin, &input[begin..i]));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    words
+}
+
+/// Split the given `<keyword>:<pattern>` into the lowercase keyword
+/// and the pattern.
+fn split_keyword(word: &str) -> Option<(&'static str, &str)> {
+    let (kw, pattern) = word.split_once(':')?;
+
+    if pattern.is_empty() {
+        return None;
+    }
+
+    FILTER_KEYWORDS
+        .into_iter()
+        .find(|keyword| keyword.eq_ignore_ascii_case(kw))
+        .map(|kw| (kw, pattern))
+}
+
+/// Turn the given word into a pattern.
+///
+/// Quoted words are unquoted and escaped, other words are considered
+/// already escaped.
+fn to_pattern(word: &str) -> String {
+    let Some(quoted) = word.strip_prefix('"') else {
+        return word.to_owned();
+    };
+
+    let mut pattern = String::new();
+    let mut chars = quoted.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => pattern.extend(chars.next()),
+            c => pattern.push(c),
+        }
+    }
+
+    escape(&pattern)
+}
+
+/// Escape characters that cannot appear unescaped in a pattern.
+fn escape(pattern: &str) -> String {
+    let mut escaped = String::with_capacity(pattern.len());
+
+    for c in pattern.chars() {
+        if matches!(c, '\\' | ' ' | '(' | ')') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use email::{
+        flag::Flag,
+        search_query::{
+            filter::SearchEmailsFilterQuery::{self, *},
+            sort::{SearchEmailsSorter, SearchEmailsSorterKind, SearchEmailsSorterOrder},
+            SearchEmailsQuery,
+        },
+    };
+
+    use super::{join, join_with_size};
+    use crate::envelope::size;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    fn parse(query: &[&str]) -> SearchEmailsQuery {
+        join(&args(query)).parse().unwrap()
+    }
+
+    fn filter(query: &[&str]) -> SearchEmailsFilterQuery {
+        parse(query).filter.unwrap()
+    }
+
+    fn and(a: SearchEmailsFilterQuery, b: SearchEmailsFilterQuery) -> SearchEmailsFilterQuery {
+        And(Box::new(a), Box::new(b))
+    }
+
+    fn or(a: SearchEmailsFilterQuery, b: SearchEmailsFilterQuery) -> SearchEmailsFilterQuery {
+        Or(Box::new(a), Box::new(b))
+    }
+
+    fn not(a: SearchEmailsFilterQuery) -> SearchEmailsFilterQuery {
+        Not(Box::new(a))
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    #[test]
+    fn keyword_shorthands() {
+        assert_eq!(join(&args(&["from:alice"])), "from alice");
+        assert_eq!(join(&args(&["FROM:alice"])), "from alice");
+        assert_eq!(join(&args(&["from", "alice"])), "from alice");
+        assert_eq!(join(&args(&["from alice"])), "from alice");
+        assert_eq!(join(&args(&["to:bob@localhost"])), "to bob@localhost");
+    }
+
+    #[test]
+    fn quoted_patterns() {
+        let expected = "subject quarterly\\ report";
+
+        // quotes kept by the shell
+        assert_eq!(join(&args(&["subject:\"quarterly report\""])), expected);
+        assert_eq!(join(&args(&["subject \"quarterly report\""])), expected);
+        // quotes removed by the shell
+        assert_eq!(join(&args(&["subject:quarterly report"])), expected);
+        assert_eq!(join(&args(&["subject", "quarterly report"])), expected);
+
+        assert_eq!(
+            join(&args(&["subject:\"a \\\"b\\\" (c)\""])),
+            "subject a\\ \"b\"\\ \\(c\\)"
+        );
+
+        assert_eq!(
+            filter(&["subject:\"quarterly report\"", "from:alice"]),
+            and(
+                Subject(String::from("quarterly report")),
+                From(String::from("alice"))
+            ),
+        );
+    }
+
+    #[test]
+    fn implicit_and() {
+        assert_eq!(
+            join(&args(&["from:alice", "not", "flag:seen"])),
+            "from alice and not flag seen"
+        );
+
+        assert_eq!(
+            filter(&["from:alice subject:report"]),
+            and(From(String::from("alice")), Subject(String::from("report"))),
+        );
+    }
+
+    #[test]
+    fn grouped_operators() {
+        let query = ["(from:alice", "or", "from:bob)", "and", "not", "flag:seen"];
+        assert_eq!(
+            join(&args(&query)),
+            "(from alice or from bob) and not flag seen"
+        );
+        assert_eq!(
+            filter(&query),
+            and(
+                or(From(String::from("alice")), From(String::from("bob"))),
+                not(Flag(Flag::Seen)),
+            ),
+        );
+
+        assert_eq!(
+            filter(&["not (from:alice or subject:\"weekly report\")"]),
+            not(or(
+                From(String::from("alice")),
+                Subject(String::from("weekly report"))
+            )),
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            filter(&["after:2023-12-01 before:2024-01-01"]),
+            and(
+                AfterDate(date("2023-12-01")),
+                BeforeDate(date("2024-01-01"))
+            ),
+        );
+        assert_eq!(filter(&["date", "2024-02-29"]), Date(date("2024-02-29")));
+
+        assert!(join(&args(&["before:2024-13-01"]))
+            .parse::<SearchEmailsQuery>()
+            .is_err());
+        assert!(join(&args(&["date:yesterday"]))
+            .parse::<SearchEmailsQuery>()
+            .is_err());
+    }
+
+    #[test]
+    fn single_argument_stops_at_operators() {
+        assert_eq!(
+            join(&args(&["from:alice order by date"])),
+            "from alice order by date"
+        );
+        assert_eq!(
+            parse(&["from:alice order by date desc"]).sort,
+            Some(vec![SearchEmailsSorter(
+                SearchEmailsSorterKind::Date,
+                SearchEmailsSorterOrder::Descending
+            )]),
+        );
+
+        assert_eq!(join(&args(&["subject:foo or bar"])), "subject foo or bar");
+        assert_eq!(
+            filter(&["subject:quarterly report or from:bob"]),
+            or(
+                Subject(String::from("quarterly report")),
+                From(String::from("bob"))
+            ),
+        );
+        assert_eq!(
+            join(&args(&["subject:foo AND not flag:seen"])),
+            "subject foo AND not flag seen"
+        );
+
+        // the first word always belongs to the pattern
+        assert_eq!(join(&args(&["subject:not found"])), "subject not\\ found");
+    }
+
+    #[test]
+    fn sort_query() {
+        assert_eq!(
+            join(&args(&["order", "by", "date", "desc", "subject"])),
+            "order by date desc subject"
+        );
+        assert_eq!(
+            parse(&["from:alice order by date desc subject"]).sort,
+            Some(vec![
+                SearchEmailsSorter(
+                    SearchEmailsSorterKind::Date,
+                    SearchEmailsSorterOrder::Descending
+                ),
+                SearchEmailsSorter(
+                    SearchEmailsSorterKind::Subject,
+                    SearchEmailsSorterOrder::Ascending
+                ),
+            ]),
+        );
+    }
+
+    #[test]
+    fn size_criteria() {
+        let (query, size_query) = join_with_size(&args(&[
+            "larger:500k",
+            "and",
+            "from:alice",
+            "order by size desc date",
+        ]))
+        .unwrap();
+
+        assert_eq!(query, "from alice order by date");
+        assert_eq!(size_query.larger, Some(size::parse("500k").unwrap()));
+        assert_eq!(size_query.smaller, None);
+        assert_eq!(size_query.sort, Some(SearchEmailsSorterOrder::Descending));
+
+        assert!(join_with_size(&args(&["not", "larger:1M"])).is_err());
+        assert!(join_with_size(&args(&["from:alice", "or", "smaller:1M"])).is_err());
+        assert!(join_with_size(&args(&["(larger:1M)"])).is_err());
+        assert!(join_with_size(&args(&["order by date size"])).is_err());
+    }
+}
@@ -1,23 +1,57 @@
-use std::{process::exit, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::Deref,
+    process::exit,
+    sync::Arc,
+};
 
 use ariadne::{Color, Label, Report, ReportKind, Source};
 use clap::Parser;
-use color_eyre::Result;
+use color_eyre::{eyre::bail, Result};
 use email::{
-    backend::feature::BackendFeatureSource, config::Config, email::search_query,
-    envelope::list::ListEnvelopesOptions, search_query::SearchEmailsQuery,
+    account::config::AccountConfig,
+    backend::feature::BackendFeatureSource,
+    config::Config,
+    email::search_query,
+    envelope::list::{ListEnvelopes, ListEnvelopesOptions},
+    search_query::SearchEmailsQuery,
 };
 use pimalaya_tui::{
-    himalaya::{backend::BackendBuilder, config::EnvelopesTable},
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
+    himalaya::{
+        backend::{Backend, BackendBuilder},
+        config::Envelopes,
+        id_mapper::IdMapper,
+    },
+    terminal::config::TomlConfig as _,
 };
+use serde::Serialize;
 use tracing::info;
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig,
-    folder::arg::name::FolderNameOptionalFlag,
+    account::{arg::name::AccountNameFlag, config::TomlAccountConfig},
+    config::TomlConfig,
+    envelope::{
+        arg::{
+            date::EnvelopeDateRangeFlags, flag::EnvelopeFlagFilterFlags, order::EnvelopeOrder,
+            query,
+        },
+        group::EnvelopeGrouping,
+        size::{self, SizeQuery},
+        table::{EnvelopeColumn, EnvelopesTable, FilteredTable},
+        tree::{self, EnvelopeTreesTable, EnvelopesWithParents},
+    },
+    folder::{
+        self,
+        arg::name::{is_folder_like_query, FolderNameOptionalFlag, FOLDER_PREFIX},
+    },
+    printer::Printer,
+    tag,
 };
 
+#[cfg(feature = "notmuch")]
+use crate::envelope::arg::engine::SearchEngine;
+
 /// Search and sort envelopes as a list.
 ///
 /// This command allows you to list envelopes included in the given
@@ -40,6 +74,12 @@ pub struct EnvelopeListCommand {
     #[arg(long, short = 's', value_name = "NUMBER")]
     pub page_size: Option<usize>,
 
+    #[command(flatten)]
+    pub dates: EnvelopeDateRangeFlags,
+
+    #[command(flatten)]
+    pub flags: EnvelopeFlagFilterFlags,
+
     #[command(flatten)]
     pub account: AccountNameFlag,
 
@@ -48,17 +88,44 @@ pub struct EnvelopeListCommand {
     /// This argument will force the table not to exceed the given
     /// width in pixels. Columns may shrink with ellipsis in order to
     /// fit the width.
+    ///
+    /// Defaults to the width of the terminal, or to the
+    /// ui.table.piped-width option when the output is not a terminal.
     #[arg(long = "max-width", short = 'w')]
     #[arg(name = "table_max_width", value_name = "PIXELS")]
     pub table_max_width: Option<u16>,
 
+    /// The columns of the table, in order.
+    ///
+    /// Columns are separated by commas (for example
+    /// "id,date,from,subject"). This argument overrides the
+    /// envelope.list.columns option of the account.
+    #[arg(long, value_enum, value_name = "COLUMNS", value_delimiter = ',')]
+    pub columns: Option<Vec<EnvelopeColumn>>,
+
+    /// The date order of envelopes.
+    ///
+    /// Envelopes are listed by descending date by default (most
+    /// recent first), the first page being the most recent one. This
+    /// argument overrides the envelope.list.order option of the
+    /// account, and is ignored when the query has a sort query.
+    #[arg(long, value_enum, value_name = "ORDER")]
+    pub order: Option<EnvelopeOrder>,
+
     /// The list envelopes filter and sort query.
     ///
     /// The query can be a filter query, a sort query or both
     /// together.
     ///
+    /// Arguments are parsed as a query, unless the first one names an
+    /// existing folder (for example "Archives order by date"). A
+    /// folder whose name also reads as a query (like "from:alice") is
+    /// refused as ambiguous. To select the folder without ambiguity,
+    /// either use the --folder argument or prefix the first argument
+    /// with "folder:" (for example "folder:Archives order by date").
+    ///
     /// A filter query is composed of operators and conditions. There
-    /// is 3 operators and 8 conditions:
+    /// is 3 operators and 10 conditions:
     ///
     ///  • not <condition> → filter envelopes that do not match the
     /// condition
@@ -92,8 +159,18 @@ pub struct EnvelopeListCommand {
     ///
     ///  ◦ flag <flag> → filter envelopes matching the given flag
     ///
+    ///  ◦ larger <size> → filter envelopes with messages strictly
+    /// larger than the given size (for example 500k or 10M)
+    ///
+    ///  ◦ smaller <size> → filter envelopes with messages strictly
+    /// smaller than the given size
+    ///
+    /// Filters can also be written as <condition>:<pattern> (for
+    /// example from:alice or subject:"quarterly report"). Filters
+    /// following each other without operator are joined with "and".
+    ///
     /// A sort query starts by "order by", and is composed of kinds
-    /// and orders. There is 4 kinds and 2 orders:
+    /// and orders. There is 5 kinds and 2 orders:
     ///
     ///  • date [order] → sort envelopes by date
     ///
@@ -103,6 +180,8 @@ pub struct EnvelopeListCommand {
     ///
     ///  • subject [order] → sort envelopes by subject
     ///
+    ///  • size [order] → sort envelopes by message size
+    ///
     ///  ◦ <kind> asc → sort envelopes by the given kind in ascending
     /// order
     ///
@@ -119,8 +198,63 @@ pub struct EnvelopeListCommand {
     ///
     /// subject foo and body bar order by date desc subject →
     /// combination of the 2 previous examples
+    ///
+    /// from:alice subject:"quarterly report" not flag:seen → filter
+    /// unseen envelopes sent by "alice" with "quarterly report" in
+    /// their subject
+    ///
+    /// Size conditions and the size kind are applied after listing:
+    /// size conditions cannot be negated, grouped nor joined with
+    /// "or", and the size kind must come first.
     #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
     pub query: Option<Vec<String>>,
+
+    /// The raw notmuch query.
+    ///
+    /// The query is given as it is to the notmuch database, which
+    /// gives access to the full notmuch query syntax (for example
+    /// "tag:inbox and not tag:killed and date:2024.."). It is still
+    /// restricted to the selected folder. This argument requires the
+    /// notmuch backend.
+    #[cfg(feature = "notmuch")]
+    #[arg(long = "query", value_name = "QUERY")]
+    #[arg(conflicts_with_all = ["query", "threads"])]
+    pub notmuch_query: Option<String>,
+
+    /// The engine answering the query.
+    ///
+    /// Queries with body conditions are answered by the notmuch index
+    /// of the account when it has one (see the
+    /// envelope.search.notmuch option), other queries by the backend.
+    /// Envelopes found by notmuch are mapped back to the backend using
+    /// their Message-ID, so that their ids can be given to other
+    /// commands. This argument forces one engine or the other.
+    #[cfg(feature = "notmuch")]
+    #[arg(long, value_enum, value_name = "ENGINE")]
+    #[arg(conflicts_with_all = ["notmuch_query", "threads"])]
+    pub engine: Option<SearchEngine>,
+
+    /// Group envelopes into conversations.
+    ///
+    /// Replies are indented under the envelope they answer, and
+    /// conversations are sorted by their most recent envelope. Pages
+    /// contain conversations instead of envelopes. Replies whose
+    /// parent is not in the folder are grouped by subject. With the
+    /// JSON output, replies are nested in a "children" array.
+    #[arg(long = "threads", visible_alias = "tree")]
+    pub threads: bool,
+
+    /// Group envelopes into sections.
+    ///
+    /// Grouping by date gives a section for today, yesterday and this
+    /// week, then one section per month, based on the date of
+    /// envelopes in the local time zone. Envelopes are listed most
+    /// recent first, so this argument cannot be used along with a
+    /// sort query. With the JSON output, envelopes get a "group"
+    /// label instead.
+    #[arg(long, value_enum, value_name = "GROUPING")]
+    #[arg(conflicts_with_all = ["threads", "order"])]
+    pub group_by: Option<EnvelopeGrouping>,
 }
 
 impl Default for EnvelopeListCommand {
@@ -129,17 +263,30 @@ impl Default for EnvelopeListCommand {
             folder: Default::default(),
             page: 1,
             page_size: Default::default(),
+            dates: Default::default(),
+            flags: Default::default(),
             account: Default::default(),
             query: Default::default(),
+            #[cfg(feature = "notmuch")]
+            notmuch_query: Default::default(),
+            #[cfg(feature = "notmuch")]
+            engine: Default::default(),
             table_max_width: Default::default(),
+            columns: Default::default(),
+            order: Default::default(),
+            threads: Default::default(),
+            group_by: Default::default(),
         }
     }
 }
 
 impl EnvelopeListCommand {
-    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+    pub async fn execute(mut self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing list envelopes command");
 
+        let mut args = self.query.take();
+        self.folder.take_from_query(&mut args)?;
+
         let (toml_account_config, account_config) = config
             .clone()
             .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
@@ -147,29 +294,117 @@ impl EnvelopeListCommand {
             })?;
 
         let toml_account_config = Arc::new(toml_account_config);
-
-        let folder = &self.folder.name;
-        let page = 1.max(self.page) - 1;
-        let page_size = self
-            .page_size
-            .unwrap_or_else(|| account_config.get_envelope_list_page_size());
+        let account_config = Arc::new(account_config);
+        let threads = self.threads;
+        let first_arg = self.folder.first_arg(&args);
+        let check_folder = first_arg.is_some();
 
         let backend = BackendBuilder::new(
             toml_account_config.clone(),
-            Arc::new(account_config),
+            account_config.clone(),
             |builder| {
-                builder
+                let builder = builder
                     .without_features()
-                    .with_list_envelopes(BackendFeatureSource::Context)
+                    .with_list_envelopes(BackendFeatureSource::Context);
+
+                // the first argument may name a folder
+                let builder = if check_folder {
+                    builder.with_list_folders(BackendFeatureSource::Context)
+                } else {
+                    builder
+                };
+
+                if threads {
+                    builder.with_thread_envelopes(BackendFeatureSource::Context)
+                } else {
+                    builder
+                }
             },
         )
         .without_sending_backend()
         .build()
         .await?;
 
-        let query = self
-            .query
-            .map(|query| query.join(" ").parse::<SearchEmailsQuery>());
+        if let Some(name) = first_arg {
+            let exists = folder::exists(
+                config,
+                &toml_account_config,
+                &account_config,
+                &backend,
+                name,
+            );
+
+            if exists.await? {
+                self.folder.take_first_arg(&mut args)?;
+            }
+        }
+
+        let extra_config = config.account_extra_config(&account_config.name);
+        let columns = self
+            .columns
+            .take()
+            .or_else(|| extra_config.envelope_list_columns());
+        let order = match self.group_by {
+            Some(_) => EnvelopeOrder::Desc,
+            None => self
+                .order
+                .unwrap_or_else(|| extra_config.envelope_list_order()),
+        };
+
+        let folder = &folder::resolve(
+            config,
+            &toml_account_config,
+            &account_config,
+            &self.folder.name,
+        )
+        .await?;
+        let page = 1.max(self.page) - 1;
+        let page_size = self
+            .page_size
+            .unwrap_or_else(|| account_config.get_envelope_list_page_size());
+
+        #[cfg(feature = "notmuch")]
+        if let Some(query) = &self.notmuch_query {
+            use pimalaya_tui::himalaya::config::Backend;
+
+            let Some(Backend::Notmuch(notmuch_config)) = &toml_account_config.backend else {
+                bail!("raw queries (--query) require the notmuch backend");
+            };
+
+            if self.group_by.is_some() {
+                bail!("cannot group envelopes listed using a raw query");
+            }
+
+            let envelopes = crate::envelope::notmuch::list_envelopes(
+                &account_config,
+                notmuch_config,
+                folder,
+                query,
+                self.flags
+                    .apply(&toml_account_config, self.dates.apply(order.apply(None)))?,
+                page,
+                page_size,
+            )?;
+
+            let table = to_table(
+                envelopes,
+                self.table_max_width,
+                columns.clone(),
+                &toml_account_config,
+                config,
+            );
+            return print(printer, &self.flags, table);
+        }
+
+        let (query, size_query) = match args.as_deref().map(query::join_with_size) {
+            Some(joined) => {
+                let (query, size_query) = joined?;
+                (Some(query).filter(|query| !query.is_empty()), size_query)
+            }
+            None => (None, SizeQuery::default()),
+        };
+
+        let query = query.map(|query| query.parse::<SearchEmailsQuery>());
         let query = match query {
             None => None,
             Some(Ok(query)) => Some(query),
@@ -189,30 +424,583 @@ impl EnvelopeListCommand {
                         .unwrap();
                 }
 
+                if !self.folder.explicit
+                    && is_folder_like_query(args.as_deref().unwrap_or_default())
+                {
+                    printer.log(format!(
+                        "Note: no folder matches the first argument, which is parsed as a \
+                         query. Use --folder <NAME> or {FOLDER_PREFIX}<NAME> to select a \
+                         folder.\n"
+                    ))?;
+                }
+
                 exit(0)
             }
         };
 
+        if self.group_by.is_some()
+            && (size_query.sort.is_some() || query.as_ref().is_some_and(|q| q.sort.is_some()))
+        {
+            bail!("cannot group envelopes: sort queries are not supported");
+        }
+
         let opts = ListEnvelopesOptions {
             page,
             page_size,
-            query,
+            query: self
+                .flags
+                .apply(&toml_account_config, self.dates.apply(order.apply(query)))?,
         };
 
-        let envelopes = backend.list_envelopes(folder, opts).await?;
-        let table = EnvelopesTable::from(envelopes)
-            .with_some_width(self.table_max_width)
-            .with_some_preset(toml_account_config.envelope_list_table_preset())
-            .with_some_unseen_char(toml_account_config.envelope_list_table_unseen_char())
-            .with_some_replied_char(toml_account_config.envelope_list_table_replied_char())
-            .with_some_flagged_char(toml_account_config.envelope_list_table_flagged_char())
-            .with_some_attachment_char(toml_account_config.envelope_list_table_attachment_char())
-            .with_some_id_color(toml_account_config.envelope_list_table_id_color())
-            .with_some_flags_color(toml_account_config.envelope_list_table_flags_color())
-            .with_some_subject_color(toml_account_config.envelope_list_table_subject_color())
-            .with_some_sender_color(toml_account_config.envelope_list_table_sender_color())
-            .with_some_date_color(toml_account_config.envelope_list_table_date_color());
+        if self.threads {
+            if !size_query.is_empty() {
+                bail!("cannot list envelope threads: size criteria are not supported");
+            }
+
+            let opts = ListEnvelopesOptions {
+                page: 0,
+                page_size: 0,
+                ..opts
+            };
+
+            let notmuch =
+                thread_notmuch_envelopes(&toml_account_config, &account_config, folder, &opts);
+
+            let (envelopes, parents) = match notmuch {
+                Some(threads) => threads?,
+                None => thread_envelopes(&backend, folder, &opts).await?,
+            };
+
+            let mut trees = tree::build(envelopes, &parents, &opts);
+
+            if page_size > 0 {
+                let page_begin = page * page_size;
+
+                if page_begin > trees.len() {
+                    bail!(
+                        "cannot list envelope threads: page {} out of bounds",
+                        page + 1
+                    );
+                }
+
+                trees.truncate(page_begin + page_size);
+                trees.drain(..page_begin);
+            }
+
+            let table = EnvelopeTreesTable::try_new(&account_config, &trees, |envelopes| {
+                to_table(
+                    envelopes,
+                    self.table_max_width,
+                    columns,
+                    &toml_account_config,
+                    config,
+                )
+            })?;
+
+            return print(printer, &self.flags, table);
+        }
+
+        #[cfg(feature = "notmuch")]
+        let message_ids = search_notmuch_index(
+            self.engine,
+            &toml_account_config,
+            &extra_config,
+            opts.query.as_ref(),
+        )?;
+        #[cfg(not(feature = "notmuch"))]
+        let message_ids: Option<HashSet<String>> = None;
+
+        let sized = !size_query.is_empty()
+            || message_ids.is_some()
+            || columns
+                .as_ref()
+                .is_some_and(|columns| columns.contains(&EnvelopeColumn::Size));
+
+        if !sized {
+            let (envelopes, groups) = match self.group_by {
+                None => (backend.list_envelopes(folder, opts).await?, HashMap::new()),
+                Some(grouping) => {
+                    // envelopes are listed by the inner backend, so
+                    // that they keep their date
+                    let mut envelopes = backend.deref().list_envelopes(folder, opts).await?;
+                    let id_mapper = id_mapper(&toml_account_config, &account_config, folder)?;
+                    let groups = grouping.apply(&mut envelopes, &id_mapper)?;
+                    let envelopes =
+                        Envelopes::try_from_backend(&account_config, &id_mapper, envelopes)?;
+                    (envelopes, groups)
+                }
+            };
+
+            let table = to_table(
+                envelopes,
+                self.table_max_width,
+                columns,
+                &toml_account_config,
+                config,
+            )
+            .with_groups(groups);
 
+            return print(printer, &self.flags, table);
+        }
+
+        let (envelopes, sizes, groups) = list_sized_envelopes(
+            &backend,
+            &toml_account_config,
+            &account_config,
+            folder,
+            opts,
+            &size_query,
+            message_ids.as_ref(),
+            self.group_by,
+        )
+        .await?;
+
+        let table = to_table(
+            envelopes,
+            self.table_max_width,
+            columns,
+            &toml_account_config,
+            config,
+        )
+        .with_sizes(sizes)
+        .with_groups(groups);
+
+        print(printer, &self.flags, table)
+    }
+}
+
+/// Print the given table, along with the flag filters if any.
+fn print<T>(printer: &mut impl Printer, flags: &EnvelopeFlagFilterFlags, table: T) -> Result<()>
+where
+    T: fmt::Display + Serialize,
+{
+    if flags.is_empty() {
         printer.out(table)
+    } else {
+        printer.out(FilteredTable::new(flags.to_filters(), table))
+    }
+}
+
+/// List envelopes of the given folder matching the given options,
+/// along with the size of their message indexed by envelope
+/// identifier.
+///
+/// When the given size query is not empty, all envelopes are listed
+/// to be filtered and sorted by size, then paginated.
+///
+/// When message ids are given, the filter query has already been
+/// answered by another engine: all envelopes are listed, then only
+/// the ones whose Message-ID is part of the given ones are kept.
+///
+/// When a grouping is given, envelopes of the page are also labelled
+/// with their group, see [`EnvelopeGrouping::apply`].
+#[allow(clippy::too_many_arguments)]
+async fn list_sized_envelopes(
+    backend: &Backend,
+    toml_account_config: &TomlAccountConfig,
+    account_config: &Arc<AccountConfig>,
+    folder: &str,
+    opts: ListEnvelopesOptions,
+    size_query: &SizeQuery,
+    message_ids: Option<&HashSet<String>>,
+    group_by: Option<EnvelopeGrouping>,
+) -> Result<(Envelopes, HashMap<String, u64>, HashMap<String, String>)> {
+    let (page, page_size) = (opts.page, opts.page_size);
+    let paginated = size_query.is_empty() && message_ids.is_none();
+
+    let opts = match paginated {
+        true => opts,
+        false => ListEnvelopesOptions {
+            page: 0,
+            page_size: 0,
+            query: match message_ids {
+                Some(_) => opts.query.map(|query| SearchEmailsQuery {
+                    filter: None,
+                    sort: query.sort,
+                }),
+                None => opts.query,
+            },
+        },
+    };
+
+    // envelopes are listed by the inner backend, so that they keep
+    // the identifiers of the backend, which sizes are fetched with
+    let mut envelopes = backend.deref().list_envelopes(folder, opts).await?;
+
+    if let Some(message_ids) = message_ids {
+        envelopes.retain(|envelope| {
+            let message_id = envelope.message_id.trim_matches(['<', '>']);
+            message_ids.contains(message_id)
+        });
+    }
+
+    let ids = envelopes
+        .iter()
+        .map(|envelope| envelope.id.as_str())
+        .collect();
+    let sizes = size::fetch(toml_account_config, account_config, folder, &ids).await?;
+
+    if !paginated {
+        size_query.apply(&mut envelopes, &sizes);
+
+        let page_begin = page * page_size;
+
+        if page_begin > envelopes.len() {
+            bail!("cannot list envelopes: page {} out of bounds", page + 1);
+        }
+
+        if page_size > 0 {
+            envelopes.truncate(page_begin + page_size);
+        }
+
+        envelopes.drain(..page_begin);
+    }
+
+    let id_mapper = id_mapper(toml_account_config, account_config, folder)?;
+
+    let groups = match group_by {
+        Some(grouping) => grouping.apply(&mut envelopes, &id_mapper)?,
+        None => HashMap::new(),
+    };
+
+    let mut aliased_sizes = HashMap::new();
+
+    for envelope in envelopes.iter() {
+        if let Some(size) = sizes.get(&envelope.id) {
+            aliased_sizes.insert(id_mapper.get_or_create_alias(&envelope.id)?, *size);
+        }
+    }
+
+    let envelopes = Envelopes::try_from_backend(account_config, &id_mapper, envelopes)?;
+
+    Ok((envelopes, aliased_sizes, groups))
+}
+
+/// Build the mapper of backend identifiers to envelope identifiers
+/// of the given folder, the same way the backend does.
+#[cfg_attr(
+    not(any(feature = "maildir", feature = "notmuch")),
+    allow(unused_variables)
+)]
+fn id_mapper(
+    toml_account_config: &TomlAccountConfig,
+    account_config: &AccountConfig,
+    folder: &str,
+) -> Result<IdMapper> {
+    #[cfg(any(feature = "maildir", feature = "notmuch"))]
+    use pimalaya_tui::himalaya::config::Backend;
+
+    match &toml_account_config.backend {
+        #[cfg(feature = "maildir")]
+        Some(Backend::Maildir(_)) => IdMapper::new(account_config, folder),
+        #[cfg(feature = "notmuch")]
+        Some(Backend::Notmuch(_)) => IdMapper::new(account_config, folder),
+        _ => Ok(IdMapper::Dummy),
+    }
+}
+
+/// Get envelopes of the given folder matching the given options,
+/// with the parent of each reply indexed by envelope identifier.
+///
+/// Threads are built by the backend, using the IMAP THREAD command
+/// or the In-Reply-To header for Maildir.
+async fn thread_envelopes(
+    backend: &Backend,
+    folder: &str,
+    opts: &ListEnvelopesOptions,
+) -> Result<EnvelopesWithParents> {
+    let threads = backend.thread_envelopes(folder, opts.clone()).await?;
+
+    // the virtual root of threads has its message id set to "0"
+    let parents = threads
+        .graph()
+        .all_edges()
+        .filter(|(parent, _, _)| parent.message_id != "0")
+        .map(|(parent, child, _)| (child.id.to_owned(), parent.id.to_owned()))
+        .collect();
+
+    let envelopes = threads.map().values().cloned().collect();
+
+    Ok((envelopes, parents))
+}
+
+/// Search the notmuch index of the given account for messages
+/// matching the given query, if the query should be answered by
+/// notmuch rather than by the backend.
+///
+/// Returns the Message-ID of matching messages. Accounts using the
+/// notmuch backend answer queries by themselves.
+#[cfg(feature = "notmuch")]
+fn search_notmuch_index(
+    engine: Option<SearchEngine>,
+    toml_account_config: &TomlAccountConfig,
+    extra_config: &crate::account::config::TomlAccountExtraConfig,
+    query: Option<&SearchEmailsQuery>,
+) -> Result<Option<HashSet<String>>> {
+    use pimalaya_tui::himalaya::config::Backend;
+
+    if let Some(Backend::Notmuch(_)) = &toml_account_config.backend {
+        return Ok(None);
+    }
+
+    let index = extra_config.envelope_search_notmuch();
+    let engine = engine.unwrap_or_else(|| SearchEngine::select(query, index.is_some()));
+
+    if engine == SearchEngine::Backend {
+        return Ok(None);
+    }
+
+    let Some(index) = index else {
+        bail!("cannot search envelopes using notmuch: missing envelope.search.notmuch option");
+    };
+
+    let message_ids = crate::envelope::notmuch::search_message_ids(index, query)?;
+    Ok(Some(message_ids))
+}
+
+/// Thread envelopes using notmuch threads, if the given account uses
+/// the notmuch backend.
+#[cfg(feature = "notmuch")]
+fn thread_notmuch_envelopes(
+    toml_account_config: &TomlAccountConfig,
+    account_config: &AccountConfig,
+    folder: &str,
+    opts: &ListEnvelopesOptions,
+) -> Option<Result<EnvelopesWithParents>> {
+    use pimalaya_tui::himalaya::config::Backend;
+
+    let Some(Backend::Notmuch(notmuch_config)) = &toml_account_config.backend else {
+        return None;
+    };
+
+    Some(crate::envelope::notmuch::thread_envelopes(
+        account_config,
+        notmuch_config,
+        folder,
+        opts.query.as_ref(),
+    ))
+}
+
+/// Thread envelopes using notmuch threads, if the given account uses
+/// the notmuch backend.
+#[cfg(not(feature = "notmuch"))]
+fn thread_notmuch_envelopes(
+    _: &TomlAccountConfig,
+    _: &AccountConfig,
+    _: &str,
+    _: &ListEnvelopesOptions,
+) -> Option<Result<EnvelopesWithParents>> {
+    None
+}
+
+/// Build the printable table of the given envelopes.
+fn to_table(
+    envelopes: Envelopes,
+    width: Option<u16>,
+    columns: Option<Vec<EnvelopeColumn>>,
+    toml_account_config: &TomlAccountConfig,
+    config: &TomlConfig,
+) -> EnvelopesTable {
+    EnvelopesTable::from(envelopes)
+        .with_some_width(width)
+        .with_tags(tag::is_notmuch(toml_account_config))
+        .with_some_columns(columns)
+        .with_some_preset(toml_account_config.envelope_list_table_preset())
+        .with_some_unseen_char(toml_account_config.envelope_list_table_unseen_char())
+        .with_some_replied_char(toml_account_config.envelope_list_table_replied_char())
+        .with_some_flagged_char(toml_account_config.envelope_list_table_flagged_char())
+        .with_some_attachment_char(toml_account_config.envelope_list_table_attachment_char())
+        .with_some_id_color(toml_account_config.envelope_list_table_id_color())
+        .with_some_flags_color(toml_account_config.envelope_list_table_flags_color())
+        .with_some_subject_color(toml_account_config.envelope_list_table_subject_color())
+        .with_some_sender_color(toml_account_config.envelope_list_table_sender_color())
+        .with_some_date_color(toml_account_config.envelope_list_table_date_color())
+        .with_table_config(config.table_config())
+}
+
+#[cfg(all(test, feature = "maildir"))]
+mod tests {
+    use std::fs;
+
+    use clap::{Arg, CommandFactory, FromArgMatches};
+    use pimalaya_tui::terminal::cli::printer::OutputFmt;
+    use uuid::Uuid;
+
+    use super::EnvelopeListCommand;
+    use crate::{
+        account::arg::name::ACCOUNT_ENV,
+        config::TomlConfig,
+        fixture::{block_on, retry_locked, IdMapperDb, MaildirRoot},
+        folder::arg::name::FOLDER_ENV,
+        printer::StdoutPrinter,
+    };
+
+    /// Two accounts of their own, the first one being the default,
+    /// each with an inbox and an archives folder holding one message
+    /// whose subject tells where it is.
+    struct Accounts {
+        roots: [MaildirRoot; 2],
+        names: [String; 2],
+        config: TomlConfig,
+        _ids: Vec<IdMapperDb>,
+    }
+
+    impl Accounts {
+        fn new() -> Self {
+            let names = ["home", "work"].map(|name| format!("{name}-{}", Uuid::new_v4()));
+            let roots = [MaildirRoot::new(), MaildirRoot::new()];
+
+            let mut config = String::new();
+            let mut ids = Vec::new();
+
+            for (i, (name, root)) in names.iter().zip(&roots).enumerate() {
+                for (dir, folder) in [("", "INBOX"), (".Archives", "Archives")] {
+                    let dir = root.folder(dir, &[]);
+                    let msg = format!("Subject: {name} {folder}\r\n\r\ntest\r\n");
+                    fs::write(dir.join("cur").join("1700000000.test:2,S"), msg).unwrap();
+                    ids.push(IdMapperDb::new(name, folder));
+                }
+
+                config.push_str(&format!(
+                    "[accounts.{name}]\n\
+                     default = {default}\n\
+                     email = \"me@localhost\"\n\
+                     backend.type = \"maildir\"\n\
+                     backend.root-dir = {root:?}\n\
+                     backend.maildirpp = true\n",
+                    default = i == 0,
+                    root = root.0,
+                ));
+            }
+
+            Self {
+                config: toml::from_str(&config).unwrap(),
+                roots,
+                names,
+                _ids: ids,
+            }
+        }
+
+        /// List envelopes with the given arguments and the given
+        /// values of the account and folder environment variables,
+        /// then return the listed subjects.
+        ///
+        /// The variables are renamed to ones of the test, so that they
+        /// do not leak to the other tests.
+        fn list(&self, account: Option<&str>, folder: Option<&str>, args: &[&str]) -> Vec<String> {
+            self.try_list(account, folder, args).unwrap()
+        }
+
+        fn try_list(
+            &self,
+            account: Option<&str>,
+            folder: Option<&str>,
+            args: &[&str],
+        ) -> color_eyre::Result<Vec<String>> {
+            let id = Uuid::new_v4().to_simple();
+            let account_env = set_test_var(format!("HIMALAYA_TEST_ACCOUNT_{id}"), account);
+            let folder_env = set_test_var(format!("HIMALAYA_TEST_FOLDER_{id}"), folder);
+
+            let cmd = EnvelopeListCommand::command()
+                .mut_arg("account_name", |arg| {
+                    rename_env(arg, ACCOUNT_ENV, account_env)
+                })
+                .mut_arg("folder_name", |arg| rename_env(arg, FOLDER_ENV, folder_env));
+            let matches = cmd.try_get_matches_from(["list"].iter().chain(args))?;
+
+            let output = self.roots[0].0.join(format!("output-{id}"));
+            let mut printer =
+                StdoutPrinter::new(OutputFmt::Json).with_some_output_file(Some(output.clone()));
+
+            retry_locked(|| {
+                let cmd = EnvelopeListCommand::from_arg_matches(&matches)?;
+                block_on(cmd.execute(&mut printer, &self.config))
+            })?;
+
+            printer.flush()?;
+
+            let envelopes: serde_json::Value = serde_json::from_slice(&fs::read(output)?)?;
+            let subjects = envelopes
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|envelope| envelope["subject"].as_str().unwrap().to_owned())
+                .collect();
+
+            Ok(subjects)
+        }
+
+        fn subject(&self, account: usize, folder: &str) -> Vec<String> {
+            vec![format!("{} {folder}", self.names[account])]
+        }
+    }
+
+    fn set_test_var(name: String, value: Option<&str>) -> &'static str {
+        if let Some(value) = value {
+            std::env::set_var(&name, value);
+        }
+        Box::leak(name.into_boxed_str())
+    }
+
+    fn rename_env(arg: Arg, env: &str, test_env: &'static str) -> Arg {
+        assert_eq!(arg.get_env().and_then(|env| env.to_str()), Some(env));
+        arg.env(test_env)
+    }
+
+    #[test]
+    fn default_account_and_folder() {
+        let accounts = Accounts::new();
+
+        let subjects = accounts.list(None, None, &[]);
+
+        assert_eq!(subjects, accounts.subject(0, "INBOX"));
+    }
+
+    #[test]
+    fn account_and_folder_from_env() {
+        let accounts = Accounts::new();
+
+        let subjects = accounts.list(Some(&accounts.names[1]), Some("Archives"), &[]);
+
+        assert_eq!(subjects, accounts.subject(1, "Archives"));
+    }
+
+    #[test]
+    fn flags_override_env() {
+        let accounts = Accounts::new();
+        let work = Some(accounts.names[1].as_str());
+        let home = accounts.names[0].as_str();
+
+        let subjects = accounts.list(work, Some("Archives"), &["--folder", "INBOX"]);
+        assert_eq!(subjects, accounts.subject(1, "INBOX"));
+
+        let subjects = accounts.list(work, Some("Archives"), &["--account", home]);
+        assert_eq!(subjects, accounts.subject(0, "Archives"));
+    }
+
+    #[test]
+    fn folder_query_overrides_env() {
+        let accounts = Accounts::new();
+
+        let subjects = accounts.list(None, Some("Archives"), &["folder:INBOX"]);
+        assert_eq!(subjects, accounts.subject(0, "INBOX"));
+
+        let err = accounts
+            .try_list(None, Some("Archives"), &["-f", "Archives", "folder:INBOX"])
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("ambiguous folder"), "{err}");
+    }
+
+    #[test]
+    fn refuse_unknown_account_from_env() {
+        let accounts = Accounts::new();
+
+        let err = accounts
+            .try_list(Some("unknown"), None, &[])
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("unknown"), "{err}");
     }
 }
@@ -3,9 +3,8 @@ pub mod thread;
 
 use clap::Subcommand;
 use color_eyre::Result;
-use pimalaya_tui::terminal::cli::printer::Printer;
 
-use crate::config::TomlConfig;
+use crate::{config::TomlConfig, printer::Printer};
 
 use self::{list::EnvelopeListCommand, thread::EnvelopeThreadCommand};
 
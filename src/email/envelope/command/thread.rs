@@ -7,14 +7,20 @@ use email::{
 };
 use pimalaya_tui::{
     himalaya::{backend::BackendBuilder, config::EnvelopesTree},
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
+    terminal::config::TomlConfig as _,
 };
 use std::{process::exit, sync::Arc};
 use tracing::info;
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig,
-    folder::arg::name::FolderNameOptionalFlag,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    envelope::arg::query,
+    folder::{
+        self,
+        arg::name::{is_folder_like_query, FolderNameOptionalFlag, FOLDER_PREFIX},
+    },
+    printer::Printer,
 };
 
 /// Search and sort envelopes as a thread.
@@ -41,34 +47,69 @@ pub struct EnvelopeThreadCommand {
 }
 
 impl EnvelopeThreadCommand {
-    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+    pub async fn execute(mut self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing thread envelopes command");
 
+        let mut args = self.query.take();
+        self.folder.take_from_query(&mut args)?;
+
         let (toml_account_config, account_config) = config
             .clone()
             .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
                 c.account(name).ok()
             })?;
 
+        let toml_account_config = Arc::new(toml_account_config);
         let account_config = Arc::new(account_config);
-        let folder = &self.folder.name;
+
+        let first_arg = self.folder.first_arg(&args);
+        let check_folder = first_arg.is_some();
 
         let backend = BackendBuilder::new(
-            Arc::new(toml_account_config),
+            toml_account_config.clone(),
             account_config.clone(),
             |builder| {
-                builder
+                let builder = builder
                     .without_features()
-                    .with_thread_envelopes(BackendFeatureSource::Context)
+                    .with_thread_envelopes(BackendFeatureSource::Context);
+
+                // the first argument may name a folder
+                if check_folder {
+                    builder.with_list_folders(BackendFeatureSource::Context)
+                } else {
+                    builder
+                }
             },
         )
         .without_sending_backend()
         .build()
         .await?;
 
-        let query = self
-            .query
-            .map(|query| query.join(" ").parse::<SearchEmailsQuery>());
+        if let Some(name) = first_arg {
+            let exists = folder::exists(
+                config,
+                &toml_account_config,
+                &account_config,
+                &backend,
+                name,
+            );
+
+            if exists.await? {
+                self.folder.take_first_arg(&mut args)?;
+            }
+        }
+
+        let folder = &folder::resolve(
+            config,
+            &toml_account_config,
+            &account_config,
+            &self.folder.name,
+        )
+        .await?;
+
+        let query = args
+            .as_ref()
+            .map(|query| query::join(query).parse::<SearchEmailsQuery>());
         let query = match query {
             None => None,
             Some(Ok(query)) => Some(query),
@@ -88,6 +129,16 @@ impl EnvelopeThreadCommand {
                         .unwrap();
                 }
 
+                if !self.folder.explicit
+                    && is_folder_like_query(args.as_deref().unwrap_or_default())
+                {
+                    printer.log(format!(
+                        "Note: no folder matches the first argument, which is parsed as a \
+                         query. Use --folder <NAME> or {FOLDER_PREFIX}<NAME> to select a \
+                         folder.\n"
+                    ))?;
+                }
+
                 exit(0)
             }
         };
@@ -8,7 +8,9 @@ pub struct IdsAndFlagsArgs {
     /// The list of ids and/or flags.
     ///
     /// Every argument that can be parsed as an integer is considered
-    /// an id, otherwise it is considered as a flag.
+    /// an id, otherwise it is considered as a flag. When ids are
+    /// omitted and stdin is a terminal, envelopes are picked
+    /// interactively.
     #[arg(value_name = "ID-OR-FLAG", required = true)]
     pub ids_and_flags: Vec<IdOrFlag>,
 }
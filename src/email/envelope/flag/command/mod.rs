@@ -4,9 +4,8 @@ mod set;
 
 use clap::Subcommand;
 use color_eyre::Result;
-use pimalaya_tui::terminal::cli::printer::Printer;
 
-use crate::config::TomlConfig;
+use crate::{config::TomlConfig, printer::Printer};
 
 use self::{add::FlagAddCommand, remove::FlagRemoveCommand, set::FlagSetCommand};
 
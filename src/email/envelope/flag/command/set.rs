@@ -3,17 +3,19 @@ use std::sync::Arc;
 use clap::Parser;
 use color_eyre::Result;
 use email::{backend::feature::BackendFeatureSource, config::Config};
-use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
 use crate::{
     account::arg::name::AccountNameFlag,
     config::TomlConfig,
-    flag::arg::ids_and_flags::{into_tuple, IdsAndFlagsArgs},
-    folder::arg::name::FolderNameOptionalFlag,
+    envelope::picker,
+    flag::{
+        self,
+        arg::ids_and_flags::{into_tuple, IdsAndFlagsArgs},
+    },
+    folder::{self, arg::name::FolderNameOptionalFlag},
+    printer::Printer,
 };
 
 /// Replace flag(s) of a given envelope.
@@ -44,12 +46,18 @@ impl FlagSetCommand {
                 c.account(name).ok()
             })?;
 
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        let toml_account_config = Arc::new(toml_account_config);
+
         let backend = BackendBuilder::new(
-            Arc::new(toml_account_config),
+            toml_account_config.clone(),
             Arc::new(account_config),
             |builder| {
                 builder
                     .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
                     .with_set_flags(BackendFeatureSource::Context)
             },
         )
@@ -57,8 +65,10 @@ impl FlagSetCommand {
         .build()
         .await?;
 
-        backend.set_flags(folder, &ids, &flags).await?;
+        let ids = picker::ids_or_pick(&backend, folder, &ids, true).await?;
+
+        flag::set(&backend, &toml_account_config, folder, &ids, &flags).await?;
 
-        printer.out(format!("Flag(s) {flags} successfully replaced!\n"))
+        printer.info(format!("Flag(s) {flags} successfully replaced!\n"))
     }
 }
@@ -0,0 +1,342 @@
+//! Module dedicated to Maildir flags.
+//!
+//! Flags of Maildir messages live in the info suffix of their file
+//! name, as letters sorted in ASCII order (`1700000000.1.host:2,FS`).
+//! Flags are updated by renaming files atomically, which also moves
+//! them from `new` to `cur`: only messages of `cur` carry an info.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use email::{
+    account::config::AccountConfig,
+    flag::{Flag, Flags},
+    folder::FolderKind,
+    maildir::config::MaildirConfig,
+};
+use shellexpand_utils::shellexpand_path;
+use tracing::debug;
+
+/// The separator between the unique name and the flags of a Maildir
+/// message file name.
+const INFO: &str = ":2,";
+
+/// The flag of Maildir messages that have been resent, forwarded or
+/// bounced, which has no standard equivalent.
+const PASSED: &str = "passed";
+
+/// Get the Maildir letter of the given flag.
+pub fn to_letter(flag: &Flag) -> Option<char> {
+    match flag {
+        Flag::Draft => Some('D'),
+        Flag::Flagged => Some('F'),
+        Flag::Custom(flag) if flag.eq_ignore_ascii_case(PASSED) => Some('P'),
+        Flag::Answered => Some('R'),
+        Flag::Seen => Some('S'),
+        Flag::Deleted => Some('T'),
+        Flag::Custom(_) => None,
+    }
+}
+
+/// Get the flag of the given Maildir letter.
+///
+/// This is the reverse of [`to_letter`].
+pub fn from_letter(letter: char) -> Option<Flag> {
+    match letter {
+        'D' => Some(Flag::Draft),
+        'F' => Some(Flag::Flagged),
+        'P' => Some(Flag::custom(PASSED)),
+        'R' => Some(Flag::Answered),
+        'S' => Some(Flag::Seen),
+        'T' => Some(Flag::Deleted),
+        _ => None,
+    }
+}
+
+/// Convert the given flags into Maildir letters.
+fn to_letters(flags: &Flags) -> Result<BTreeSet<char>> {
+    flags
+        .iter()
+        .map(|flag| match to_letter(flag) {
+            Some(letter) => Ok(letter),
+            None => bail!(
+                "cannot use flag {flag} with the Maildir backend, use seen, answered, \
+                 flagged, deleted, draft or passed instead"
+            ),
+        })
+        .collect()
+}
+
+/// Add the given flags to the given Maildir messages.
+pub fn add(
+    config: &MaildirConfig,
+    account_config: &AccountConfig,
+    folder: &str,
+    ids: &[String],
+    flags: &Flags,
+) -> Result<()> {
+    let letters = to_letters(flags)?;
+    update(config, account_config, folder, ids, |info| {
+        info.extend(&letters)
+    })
+}
+
+/// Remove the given flags from the given Maildir messages.
+pub fn remove(
+    config: &MaildirConfig,
+    account_config: &AccountConfig,
+    folder: &str,
+    ids: &[String],
+    flags: &Flags,
+) -> Result<()> {
+    let letters = to_letters(flags)?;
+    update(config, account_config, folder, ids, |info| {
+        info.retain(|letter| !letters.contains(letter))
+    })
+}
+
+/// Replace the flags of the given Maildir messages.
+///
+/// Letters without matching flag, like the lowercase keywords of
+/// some servers, are kept.
+pub fn set(
+    config: &MaildirConfig,
+    account_config: &AccountConfig,
+    folder: &str,
+    ids: &[String],
+    flags: &Flags,
+) -> Result<()> {
+    let letters = to_letters(flags)?;
+    update(config, account_config, folder, ids, |info| {
+        info.retain(|letter| from_letter(*letter).is_none());
+        info.extend(&letters);
+    })
+}
+
+/// Update the flag letters of the given Maildir messages.
+///
+/// Messages are renamed into `cur` with their new letters, unless
+/// their letters did not change. Every message is found before
+/// anything is renamed.
+fn update(
+    config: &MaildirConfig,
+    account_config: &AccountConfig,
+    folder: &str,
+    ids: &[String],
+    f: impl Fn(&mut BTreeSet<char>),
+) -> Result<()> {
+    let dir = to_dir(config, account_config, folder);
+    let paths = find(&dir, ids)?;
+
+    if let Some(id) = ids.iter().find(|id| !paths.contains_key(id.as_str())) {
+        bail!(
+            "cannot find message {id} in Maildir folder {}",
+            dir.display()
+        );
+    }
+
+    for (id, path) in paths {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let prev_letters: BTreeSet<char> = match file_name.split_once(INFO) {
+            Some((_, letters)) => letters.chars().collect(),
+            None => BTreeSet::new(),
+        };
+
+        let mut letters = prev_letters.clone();
+        f(&mut letters);
+
+        if letters == prev_letters {
+            continue;
+        }
+
+        let letters: String = letters.into_iter().collect();
+        let new_path = dir.join("cur").join(format!("{id}{INFO}{letters}"));
+        debug!("renaming Maildir message {path:?} to {new_path:?}");
+
+        fs::rename(&path, &new_path).wrap_err_with(|| {
+            format!("cannot rename {} to {}", path.display(), new_path.display())
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Find the files of the given Maildir messages, in `cur` or `new`.
+fn find<'a>(dir: &Path, ids: &'a [String]) -> Result<HashMap<&'a str, PathBuf>> {
+    let ids: HashSet<&str> = ids.iter().map(String::as_str).collect();
+    let mut paths = HashMap::new();
+
+    for subdir in ["cur", "new"] {
+        let subdir = dir.join(subdir);
+        let entries = fs::read_dir(&subdir)
+            .wrap_err_with(|| format!("cannot read Maildir directory {}", subdir.display()))?;
+
+        for entry in entries {
+            let path = entry?.path();
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let id = match file_name.split_once(INFO) {
+                Some((id, _)) => id,
+                None => &file_name,
+            };
+
+            if let Some(id) = ids.get(id) {
+                paths.insert(*id, path);
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Get the directory of the given Maildir folder, the same way the
+/// Maildir backend does.
+fn to_dir(config: &MaildirConfig, account_config: &AccountConfig, folder: &str) -> PathBuf {
+    let folder = account_config.get_folder_alias(folder);
+    let mut dir = shellexpand_path(&config.root_dir);
+
+    if !config.maildirpp {
+        dir.push(folder);
+    } else if !FolderKind::matches_inbox(&folder) {
+        for component in Path::new(&folder).components() {
+            if let Component::Normal(component) = component {
+                let component = component.to_string_lossy();
+                dir.push(format!(".{}", component.trim_start_matches('.')));
+            }
+        }
+    }
+
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use email::{
+        account::config::AccountConfig,
+        flag::{Flag, Flags},
+        maildir::config::MaildirConfig,
+    };
+
+    use crate::fixture::MaildirRoot;
+
+    fn config(root: &MaildirRoot, maildirpp: bool) -> MaildirConfig {
+        MaildirConfig {
+            root_dir: root.0.clone(),
+            maildirpp,
+        }
+    }
+
+    fn flags(flags: impl IntoIterator<Item = Flag>) -> Flags {
+        Flags::from_iter(flags)
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(ToString::to_string).collect()
+    }
+
+    fn files(dir: &Path, subdir: &str) -> Vec<String> {
+        let mut files: Vec<_> = fs::read_dir(dir.join(subdir))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn add_moves_new_messages_to_cur() {
+        let root = MaildirRoot::new();
+        let dir = root.folder("Work", &["new/1", "new/2", "cur/3:2,S"]);
+        let config = config(&root, false);
+        let account_config = AccountConfig::default();
+
+        let seen = flags([Flag::Seen]);
+        super::add(&config, &account_config, "Work", &ids(&["1", "3"]), &seen).unwrap();
+
+        assert_eq!(files(&dir, "new"), ["2"]);
+        assert_eq!(files(&dir, "cur"), ["1:2,S", "3:2,S"]);
+    }
+
+    #[test]
+    fn add_sorts_letters() {
+        let root = MaildirRoot::new();
+        let dir = root.folder("Work", &["cur/1:2,S"]);
+        let config = config(&root, false);
+        let account_config = AccountConfig::default();
+
+        let flags = flags([Flag::Flagged, Flag::Draft, Flag::custom("passed")]);
+        super::add(&config, &account_config, "Work", &ids(&["1"]), &flags).unwrap();
+
+        assert_eq!(files(&dir, "cur"), ["1:2,DFPS"]);
+    }
+
+    #[test]
+    fn keep_unknown_lowercase_letters() {
+        let root = MaildirRoot::new();
+        let dir = root.folder("Work", &["cur/1:2,RSab", "cur/2:2,Sc"]);
+        let config = config(&root, false);
+        let account_config = AccountConfig::default();
+
+        let flagged = flags([Flag::Flagged]);
+        super::set(&config, &account_config, "Work", &ids(&["1"]), &flagged).unwrap();
+        let seen = flags([Flag::Seen]);
+        super::remove(&config, &account_config, "Work", &ids(&["2"]), &seen).unwrap();
+
+        assert_eq!(files(&dir, "cur"), ["1:2,Fab", "2:2,c"]);
+    }
+
+    #[test]
+    fn refuse_flags_without_letter() {
+        let root = MaildirRoot::new();
+        let dir = root.folder("Work", &["cur/1:2,S"]);
+        let config = config(&root, false);
+        let account_config = AccountConfig::default();
+
+        let custom = flags([Flag::custom("important")]);
+        let err = super::add(&config, &account_config, "Work", &ids(&["1"]), &custom);
+
+        assert!(err.is_err());
+        assert_eq!(files(&dir, "cur"), ["1:2,S"]);
+    }
+
+    #[test]
+    fn refuse_unknown_messages_before_renaming() {
+        let root = MaildirRoot::new();
+        let dir = root.folder("Work", &["new/1"]);
+        let config = config(&root, false);
+        let account_config = AccountConfig::default();
+
+        let seen = flags([Flag::Seen]);
+        let err = super::add(&config, &account_config, "Work", &ids(&["1", "2"]), &seen);
+
+        assert!(err.is_err());
+        assert_eq!(files(&dir, "new"), ["1"]);
+    }
+
+    #[test]
+    fn update_nested_maildirpp_folders() {
+        let root = MaildirRoot::new();
+        let inbox = root.folder("", &["new/1"]);
+        let dir = root.folder(".Clients.ACME", &["new/2", "cur/3:2,"]);
+        let config = config(&root, true);
+        let account_config = AccountConfig::default();
+
+        let seen = flags([Flag::Seen]);
+        super::add(&config, &account_config, "INBOX", &ids(&["1"]), &seen).unwrap();
+        let answered = flags([Flag::Answered]);
+        let ids = ids(&["2", "3"]);
+        super::add(&config, &account_config, "Clients.ACME", &ids, &answered).unwrap();
+
+        assert_eq!(files(&inbox, "cur"), ["1:2,S"]);
+        assert!(files(&dir, "new").is_empty());
+        assert_eq!(files(&dir, "cur"), ["2:2,R", "3:2,R"]);
+    }
+}
@@ -1,2 +1,93 @@
 pub mod arg;
 pub mod command;
+#[cfg(feature = "maildir")]
+pub mod maildir;
+
+use color_eyre::Result;
+use email::flag::Flags;
+use pimalaya_tui::himalaya::backend::Backend;
+
+use crate::account::config::TomlAccountConfig;
+
+/// Add the given flags to the given envelopes.
+///
+/// Flags of Maildir messages are updated by renaming their file, see
+/// [`maildir`]. Other backends update flags by themselves.
+#[cfg_attr(not(feature = "maildir"), allow(unused_variables))]
+pub async fn add(
+    backend: &Backend,
+    toml_account_config: &TomlAccountConfig,
+    folder: &str,
+    ids: &[usize],
+    flags: &Flags,
+) -> Result<()> {
+    #[cfg(feature = "maildir")]
+    if let Some(pimalaya_tui::himalaya::config::Backend::Maildir(config)) =
+        &toml_account_config.backend
+    {
+        let ids = maildir_ids(backend, folder, ids)?;
+        return maildir::add(config, &backend.account_config, folder, &ids, flags);
+    }
+
+    backend.add_flags(folder, ids, flags).await?;
+    Ok(())
+}
+
+/// Remove the given flags from the given envelopes.
+///
+/// See [`add`].
+#[cfg_attr(not(feature = "maildir"), allow(unused_variables))]
+pub async fn remove(
+    backend: &Backend,
+    toml_account_config: &TomlAccountConfig,
+    folder: &str,
+    ids: &[usize],
+    flags: &Flags,
+) -> Result<()> {
+    #[cfg(feature = "maildir")]
+    if let Some(pimalaya_tui::himalaya::config::Backend::Maildir(config)) =
+        &toml_account_config.backend
+    {
+        let ids = maildir_ids(backend, folder, ids)?;
+        return maildir::remove(config, &backend.account_config, folder, &ids, flags);
+    }
+
+    backend.remove_flags(folder, ids, flags).await?;
+    Ok(())
+}
+
+/// Replace the flags of the given envelopes.
+///
+/// See [`add`].
+#[cfg_attr(not(feature = "maildir"), allow(unused_variables))]
+pub async fn set(
+    backend: &Backend,
+    toml_account_config: &TomlAccountConfig,
+    folder: &str,
+    ids: &[usize],
+    flags: &Flags,
+) -> Result<()> {
+    #[cfg(feature = "maildir")]
+    if let Some(pimalaya_tui::himalaya::config::Backend::Maildir(config)) =
+        &toml_account_config.backend
+    {
+        let ids = maildir_ids(backend, folder, ids)?;
+        return maildir::set(config, &backend.account_config, folder, &ids, flags);
+    }
+
+    backend.set_flags(folder, ids, flags).await?;
+    Ok(())
+}
+
+/// Get the Maildir identifiers of the given envelope identifiers.
+///
+/// Unlike the backend, unknown envelope identifiers lead to an error
+/// instead of being skipped.
+#[cfg(feature = "maildir")]
+fn maildir_ids(backend: &Backend, folder: &str, ids: &[usize]) -> Result<Vec<String>> {
+    use pimalaya_tui::himalaya::id_mapper::IdMapper;
+
+    let id_mapper = IdMapper::new(&backend.account_config, folder)?;
+
+    ids.iter().map(|id| id_mapper.get_id(id)).collect()
+}
@@ -0,0 +1,193 @@
+//! Module dedicated to the grouping of listed envelopes.
+//!
+//! Groups are shown as section rows of the envelopes table, and as a
+//! `group` label of envelopes in the JSON output.
+
+use std::collections::HashMap;
+
+use chrono::{Datelike, Days, Local, NaiveDate};
+use clap::ValueEnum;
+use color_eyre::Result;
+use email::envelope::Envelopes;
+use pimalaya_tui::himalaya::id_mapper::IdMapper;
+
+/// The grouping of listed envelopes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum EnvelopeGrouping {
+    /// Today, yesterday, this week, then one group per month.
+    Date,
+}
+
+impl EnvelopeGrouping {
+    /// Sort the given envelopes for this grouping, then label them
+    /// with their group, indexed by envelope identifier.
+    ///
+    /// Envelopes come from the inner backend, so their identifiers
+    /// are aliased using the given mapper.
+    pub fn apply(
+        self,
+        envelopes: &mut Envelopes,
+        id_mapper: &IdMapper,
+    ) -> Result<HashMap<String, String>> {
+        match self {
+            Self::Date => by_date(envelopes, id_mapper),
+        }
+    }
+}
+
+/// Sort the given envelopes by descending date, then label them with
+/// their date section.
+fn by_date(envelopes: &mut Envelopes, id_mapper: &IdMapper) -> Result<HashMap<String, String>> {
+    envelopes.sort_by(|a, b| b.date.cmp(&a.date));
+
+    let today = Local::now().date_naive();
+    let mut groups = HashMap::new();
+
+    for envelope in envelopes.iter() {
+        let date = envelope.date.with_timezone(&Local).date_naive();
+        let id = id_mapper.get_or_create_alias(&envelope.id)?;
+        groups.insert(id, date_label(date, today));
+    }
+
+    Ok(groups)
+}
+
+/// Get the label of the date section of the given day, relatively to
+/// the given current day.
+///
+/// Weeks start on Monday. Days in the future are part of today, so
+/// that messages with a skewed date are not hidden at the bottom.
+fn date_label(date: NaiveDate, today: NaiveDate) -> String {
+    let week = today - Days::new(today.weekday().num_days_from_monday().into());
+
+    if date >= today {
+        String::from("Today")
+    } else if today.pred_opt() == Some(date) {
+        String::from("Yesterday")
+    } else if date >= week {
+        String::from("This week")
+    } else {
+        date.format("%B %Y").to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Days, Local, NaiveDate};
+    use email::envelope::{Envelope, Envelopes};
+    use pimalaya_tui::himalaya::id_mapper::IdMapper;
+
+    use super::{date_label, EnvelopeGrouping};
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    /// Get the labels of the given days, relatively to the given
+    /// current day.
+    fn labels<const N: usize>(today: NaiveDate, days: [NaiveDate; N]) -> [String; N] {
+        days.map(|date| date_label(date, today))
+    }
+
+    #[test]
+    fn label_days_of_the_week() {
+        // Wednesday 12 June 2024
+        let today = day(2024, 6, 12);
+
+        assert_eq!(
+            labels(
+                today,
+                [
+                    day(2024, 6, 13),
+                    day(2024, 6, 12),
+                    day(2024, 6, 11),
+                    day(2024, 6, 10),
+                    day(2024, 6, 9),
+                    day(2024, 6, 1),
+                ]
+            ),
+            [
+                "Today",
+                "Today",
+                "Yesterday",
+                "This week",
+                "June 2024",
+                "June 2024",
+            ]
+        );
+    }
+
+    #[test]
+    fn label_yesterday_before_the_week() {
+        // Monday 10 June 2024, yesterday being the previous week
+        let today = day(2024, 6, 10);
+
+        assert_eq!(
+            labels(today, [day(2024, 6, 10), day(2024, 6, 9), day(2024, 6, 8)]),
+            ["Today", "Yesterday", "June 2024"]
+        );
+
+        // Sunday 16 June 2024, the week starting on Monday
+        let today = day(2024, 6, 16);
+
+        assert_eq!(
+            labels(today, [day(2024, 6, 15), day(2024, 6, 10), day(2024, 6, 9)]),
+            ["Yesterday", "This week", "June 2024"]
+        );
+    }
+
+    #[test]
+    fn label_weeks_across_months_and_years() {
+        // Wednesday 3 January 2024, the week starting in 2024
+        let today = day(2024, 1, 3);
+
+        assert_eq!(
+            labels(
+                today,
+                [
+                    day(2024, 1, 2),
+                    day(2024, 1, 1),
+                    day(2023, 12, 31),
+                    day(2023, 11, 30),
+                ]
+            ),
+            ["Yesterday", "This week", "December 2023", "November 2023"]
+        );
+
+        // Tuesday 2 July 2024, the week starting in June
+        let today = day(2024, 7, 2);
+
+        assert_eq!(
+            labels(today, [day(2024, 7, 1), day(2024, 6, 30), day(2024, 6, 29)]),
+            ["Yesterday", "June 2024", "June 2024"]
+        );
+    }
+
+    #[test]
+    fn sort_and_label_envelopes() {
+        let now = Local::now().fixed_offset();
+        let envelope = |id: &str, days: u64| Envelope {
+            id: id.to_owned(),
+            date: now - Days::new(days),
+            ..Default::default()
+        };
+
+        let mut envelopes = Envelopes::from_iter([
+            envelope("old", 400),
+            envelope("today", 0),
+            envelope("yesterday", 1),
+        ]);
+
+        let groups = EnvelopeGrouping::Date
+            .apply(&mut envelopes, &IdMapper::Dummy)
+            .unwrap();
+
+        let ids: Vec<_> = envelopes.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["today", "yesterday", "old"]);
+
+        let old = (now - Days::new(400)).format("%B %Y").to_string();
+        assert_eq!(groups["today"], "Today");
+        assert_eq!(groups["yesterday"], "Yesterday");
+        assert_eq!(groups["old"], old);
+    }
+}
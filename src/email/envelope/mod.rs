@@ -1,3 +1,11 @@
 pub mod arg;
 pub mod command;
 pub mod flag;
+pub mod group;
+#[cfg(feature = "notmuch")]
+pub mod notmuch;
+pub mod picker;
+pub mod size;
+pub mod table;
+pub mod tag;
+pub mod tree;
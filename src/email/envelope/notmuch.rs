@@ -0,0 +1,292 @@
+//! Module dedicated to raw notmuch queries.
+//!
+//! Raw queries bypass the generic search query, and are given as they
+//! are to the notmuch database.
+//!
+//! The notmuch index of an account can also answer full-text searches
+//! on behalf of another backend, matching messages being mapped back
+//! using their Message-ID.
+
+use std::{
+    collections::{HashMap, HashSet},
+    process::Command,
+};
+
+use color_eyre::{
+    eyre::{bail, eyre},
+    Report, Result,
+};
+use email::{
+    account::config::AccountConfig,
+    envelope::{list::ListEnvelopesOptions, Envelope, Envelopes},
+    folder::FolderKind,
+    notmuch::config::NotmuchConfig,
+    search_query::SearchEmailsQuery,
+};
+use notmuch::{Database, DatabaseMode, Message, Sort};
+use pimalaya_tui::himalaya::{config, id_mapper::IdMapper};
+use shellexpand_utils::shellexpand_path;
+use tracing::{debug, info};
+
+use crate::envelope::tree::EnvelopesWithParents;
+
+/// List envelopes of the given folder matching the given raw notmuch
+/// query.
+///
+/// The query is restricted to the given folder, then to the given
+/// date range and flag filters query (if any). Envelopes are sorted the same way the
+/// notmuch backend does, most recent first unless the filters query
+/// has a sort query.
+pub fn list_envelopes(
+    account_config: &AccountConfig,
+    notmuch_config: &NotmuchConfig,
+    folder: &str,
+    query: &str,
+    filter: Option<SearchEmailsQuery>,
+    page: usize,
+    page_size: usize,
+) -> Result<config::Envelopes> {
+    info!("listing notmuch envelopes from folder {folder} matching raw query");
+
+    let db_path = notmuch_config.database_path.as_ref().map(shellexpand_path);
+    let config_path = notmuch_config.find_config_path();
+    let profile = notmuch_config.find_profile();
+    let db = Database::open_with_config(db_path, DatabaseMode::ReadOnly, config_path, profile)?;
+
+    let folder_alias = account_config.get_folder_alias(folder);
+    let folder_query = if notmuch_config.maildirpp && FolderKind::matches_inbox(&folder_alias) {
+        String::from("folder:\"\"")
+    } else {
+        format!("folder:{folder_alias:?}")
+    };
+
+    let mut final_query = format!("{folder_query} and ({query})");
+
+    let notmuch_filter = filter
+        .as_ref()
+        .map(|filter| filter.to_notmuch_search_query());
+
+    if let Some(filter) = notmuch_filter.filter(|filter| !filter.is_empty()) {
+        final_query.push_str(&format!(" and ({filter})"));
+    }
+
+    debug!("notmuch query: {final_query:?}");
+
+    let notmuch_query = db.create_query(&final_query)?;
+    notmuch_query.set_sort(Sort::NewestFirst);
+
+    let msgs = notmuch_query
+        .search_messages()
+        .map_err(|err| query_error(notmuch_config, &final_query, err))?;
+
+    let mut envelopes = Envelopes::from_notmuch_msgs(msgs);
+
+    let opts = ListEnvelopesOptions {
+        page,
+        page_size,
+        query: filter,
+    };
+
+    opts.sort_envelopes(&mut envelopes);
+
+    let page_begin = page * page_size;
+
+    if page_begin > envelopes.len() {
+        bail!(
+            "cannot list notmuch envelopes: page {} out of bounds",
+            page + 1
+        );
+    }
+
+    let page_end = if page_size == 0 {
+        envelopes.len()
+    } else {
+        envelopes.len().min(page_begin + page_size)
+    };
+
+    *envelopes = envelopes[page_begin..page_end].into();
+
+    let id_mapper = IdMapper::new(account_config, folder)?;
+    let envelopes = config::Envelopes::try_from_backend(account_config, &id_mapper, envelopes)?;
+
+    db.close()?;
+
+    Ok(envelopes)
+}
+
+/// Search the given notmuch index for messages matching the given
+/// filter query, across all folders.
+///
+/// Returns the Message-ID of matching messages, without angle
+/// brackets, so that they can be mapped back to the envelopes of
+/// another backend.
+pub fn search_message_ids(
+    notmuch_config: &NotmuchConfig,
+    query: Option<&SearchEmailsQuery>,
+) -> Result<HashSet<String>> {
+    info!("searching message ids from notmuch index");
+
+    let db_path = notmuch_config.database_path.as_ref().map(shellexpand_path);
+    let config_path = notmuch_config.find_config_path();
+    let profile = notmuch_config.find_profile();
+    let db = Database::open_with_config(db_path, DatabaseMode::ReadOnly, config_path, profile)?;
+
+    let final_query = query
+        .map(|query| query.to_notmuch_search_query())
+        .filter(|query| !query.is_empty())
+        .unwrap_or_else(|| String::from("*"));
+
+    debug!("notmuch query: {final_query:?}");
+
+    let ids = db
+        .create_query(&final_query)?
+        .search_messages()
+        .map_err(|err| query_error(notmuch_config, &final_query, err))?
+        .map(|msg| msg.id().to_string())
+        .collect();
+
+    db.close()?;
+
+    Ok(ids)
+}
+
+/// Thread envelopes of the given folder matching the given query,
+/// using notmuch threads.
+///
+/// Returns the matching envelopes, and the parent of each reply
+/// indexed by envelope identifier. Messages of a thread that do not
+/// match the query are skipped, their replies being attached to the
+/// closest matching ancestor.
+pub fn thread_envelopes(
+    account_config: &AccountConfig,
+    notmuch_config: &NotmuchConfig,
+    folder: &str,
+    query: Option<&SearchEmailsQuery>,
+) -> Result<EnvelopesWithParents> {
+    info!("threading notmuch envelopes from folder {folder}");
+
+    let db_path = notmuch_config.database_path.as_ref().map(shellexpand_path);
+    let config_path = notmuch_config.find_config_path();
+    let profile = notmuch_config.find_profile();
+    let db = Database::open_with_config(db_path, DatabaseMode::ReadOnly, config_path, profile)?;
+
+    let folder_alias = account_config.get_folder_alias(folder);
+    let mut final_query = if notmuch_config.maildirpp && FolderKind::matches_inbox(&folder_alias) {
+        String::from("folder:\"\"")
+    } else {
+        format!("folder:{folder_alias:?}")
+    };
+
+    let filter = query.map(|query| query.to_notmuch_search_query());
+
+    if let Some(filter) = filter.filter(|filter| !filter.is_empty()) {
+        final_query.push_str(&format!(" and ({filter})"));
+    }
+
+    debug!("notmuch query: {final_query:?}");
+
+    let matched: HashSet<String> = db
+        .create_query(&final_query)?
+        .search_messages()?
+        .map(|msg| msg.id().to_string())
+        .collect();
+
+    let mut envelopes = Vec::new();
+    let mut parents = HashMap::new();
+
+    for thread in db.create_query(&final_query)?.search_threads()? {
+        for msg in thread.toplevel_messages() {
+            walk_thread(msg, None, &matched, &mut envelopes, &mut parents);
+        }
+    }
+
+    let id_mapper = IdMapper::new(account_config, folder)?;
+
+    for envelope in &mut envelopes {
+        envelope.id = id_mapper.get_or_create_alias(&envelope.id)?;
+    }
+
+    let parents = parents
+        .into_iter()
+        .map(|(id, parent)| {
+            let id = id_mapper.get_or_create_alias(&id)?;
+            let parent = id_mapper.get_or_create_alias(&parent)?;
+            Ok((id, parent))
+        })
+        .collect::<Result<_>>()?;
+
+    db.close()?;
+
+    Ok((envelopes, parents))
+}
+
+fn walk_thread(
+    msg: Message,
+    parent: Option<&str>,
+    matched: &HashSet<String>,
+    envelopes: &mut Vec<Envelope>,
+    parents: &mut HashMap<String, String>,
+) {
+    let replies: Vec<Message> = msg.replies().collect();
+
+    let id = if matched.contains(msg.id().as_ref()) {
+        let envelope = Envelope::from_notmuch_msg(msg);
+
+        if let Some(parent) = parent {
+            parents.insert(envelope.id.clone(), parent.to_owned());
+        }
+
+        let id = envelope.id.clone();
+        envelopes.push(envelope);
+        Some(id)
+    } else {
+        None
+    };
+
+    let parent = id.as_deref().or(parent);
+
+    for reply in replies {
+        walk_thread(reply, parent, matched, envelopes, parents);
+    }
+}
+
+/// Build the error of the given failing query.
+///
+/// The notmuch library keeps the reason of query failures in the
+/// database status, which is not exposed by the bindings. The reason
+/// is taken from the notmuch command instead, when available.
+fn query_error(config: &NotmuchConfig, query: &str, err: notmuch::Error) -> Report {
+    let mut cmd = Command::new("notmuch");
+
+    if let Some(path) = &config.database_path {
+        cmd.env("NOTMUCH_DATABASE", shellexpand_path(path));
+    }
+
+    if let Some(path) = config.find_config_path() {
+        cmd.env("NOTMUCH_CONFIG", shellexpand_path(path));
+    }
+
+    if let Some(profile) = config.find_profile() {
+        cmd.env("NOTMUCH_PROFILE", profile);
+    }
+
+    let reason = match cmd.args(["count", "--", query]).output() {
+        Ok(output) if !output.status.success() => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.trim().trim_start_matches("notmuch count: ").trim();
+            (!reason.is_empty()).then(|| reason.to_owned())
+        }
+        Ok(_) => None,
+        Err(err) => {
+            debug!("cannot run notmuch command: {err}");
+            None
+        }
+    };
+
+    let err = match reason {
+        Some(reason) => eyre!(err).wrap_err(reason),
+        None => eyre!(err),
+    };
+
+    err.wrap_err(format!("cannot search notmuch messages matching {query:?}"))
+}
@@ -0,0 +1,94 @@
+//! Module dedicated to the interactive envelope picker.
+//!
+//! Commands taking envelope ids let users pick envelopes from a
+//! fuzzy-searchable list when no id is given and stdin is a terminal.
+//! Otherwise, ids stay required so that scripts never hang on a
+//! prompt.
+
+use std::io::{stdin, IsTerminal};
+
+use color_eyre::{eyre::bail, Result};
+use email::envelope::list::ListEnvelopesOptions;
+use inquire::{list_option::ListOption, MultiSelect, Select};
+use pimalaya_tui::himalaya::backend::Backend;
+
+/// Return `true` if envelopes can be picked interactively.
+///
+/// Used by id arguments to decide whether ids are required.
+pub fn is_enabled() -> bool {
+    stdin().is_terminal()
+}
+
+/// Return the given ids, or pick envelopes of the given folder when
+/// none is given.
+///
+/// The backend must be built with the list envelopes feature.
+pub async fn ids_or_pick(
+    backend: &Backend,
+    folder: &str,
+    ids: &[usize],
+    multiple: bool,
+) -> Result<Vec<usize>> {
+    if !ids.is_empty() {
+        return Ok(ids.to_vec());
+    }
+
+    if !is_enabled() {
+        bail!("cannot find envelope id: give at least one id, or run in a terminal to pick it");
+    }
+
+    pick(backend, folder, multiple).await
+}
+
+/// Return the given id, or pick an envelope of the given folder when
+/// none is given.
+///
+/// The backend must be built with the list envelopes feature.
+pub async fn id_or_pick(backend: &Backend, folder: &str, id: Option<usize>) -> Result<usize> {
+    let ids = ids_or_pick(backend, folder, id.as_slice(), false).await?;
+    Ok(ids[0])
+}
+
+/// Pick envelopes from the first page of the given folder, most
+/// recent first.
+pub async fn pick(backend: &Backend, folder: &str, multiple: bool) -> Result<Vec<usize>> {
+    let opts = ListEnvelopesOptions {
+        page: 0,
+        page_size: backend.account_config.get_envelope_list_page_size(),
+        query: None,
+    };
+
+    let envelopes = backend.list_envelopes(folder, opts).await?;
+
+    if envelopes.is_empty() {
+        bail!("cannot pick envelope: folder {folder} is empty");
+    }
+
+    let options: Vec<_> = envelopes
+        .iter()
+        .map(|envelope| {
+            let from = envelope.from.name.as_ref().unwrap_or(&envelope.from.addr);
+            format!("{} — {from} — {}", envelope.date, envelope.subject)
+        })
+        .collect();
+
+    let picked = if multiple {
+        MultiSelect::new("Pick envelopes:", options)
+            .with_help_message("↑↓ to move, space to select, type to filter, enter to confirm")
+            .raw_prompt()?
+    } else {
+        vec![Select::new("Pick an envelope:", options).raw_prompt()?]
+    };
+
+    if picked.is_empty() {
+        bail!("cannot pick envelope: no envelope selected");
+    }
+
+    picked
+        .iter()
+        .map(|ListOption { index, .. }| {
+            let id = &envelopes[*index].id;
+            Ok(id.parse()?)
+        })
+        .collect()
+}
@@ -0,0 +1,518 @@
+//! Module dedicated to message sizes.
+//!
+//! Envelopes listed by backends do not carry the size of their
+//! message, so sizes are fetched apart: IMAP sizes using the
+//! RFC822.SIZE item of the FETCH command, Maildir and notmuch sizes
+//! from the size of message files on disk.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use color_eyre::{eyre::bail, Result};
+use email::{
+    account::config::AccountConfig, envelope::Envelopes,
+    search_query::sort::SearchEmailsSorterOrder,
+};
+
+use crate::account::config::TomlAccountConfig;
+
+/// The size units, in powers of 1024.
+const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+/// The message size criteria of a query.
+///
+/// Backends cannot filter nor sort envelopes by size, so these
+/// criteria are applied to listed envelopes.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SizeQuery {
+    /// Keep only messages strictly larger than the given size.
+    pub larger: Option<u64>,
+    /// Keep only messages strictly smaller than the given size.
+    pub smaller: Option<u64>,
+    /// Sort messages by size, before any other sort kind.
+    pub sort: Option<SearchEmailsSorterOrder>,
+}
+
+impl SizeQuery {
+    /// Return `true` if the query has no size criterion.
+    pub fn is_empty(&self) -> bool {
+        self.larger.is_none() && self.smaller.is_none() && self.sort.is_none()
+    }
+
+    /// Filter and sort the given envelopes using the given sizes.
+    ///
+    /// Envelopes of the same size keep their order, which makes the
+    /// size the first sort kind. Envelopes whose size is unknown are
+    /// filtered out by size filters.
+    pub fn apply(&self, envelopes: &mut Envelopes, sizes: &HashMap<String, u64>) {
+        let size = |id: &str| sizes.get(id).copied();
+
+        envelopes.retain(|envelope| {
+            let size = size(&envelope.id);
+            let larger = self
+                .larger
+                .map_or(true, |min| size.is_some_and(|s| s > min));
+            let smaller = self
+                .smaller
+                .map_or(true, |max| size.is_some_and(|s| s < max));
+            larger && smaller
+        });
+
+        if let Some(order) = &self.sort {
+            envelopes.sort_by(|a, b| {
+                let ord = size(&a.id).cmp(&size(&b.id));
+                match order {
+                    SearchEmailsSorterOrder::Ascending => ord,
+                    SearchEmailsSorterOrder::Descending => ord.reverse(),
+                }
+            });
+        }
+    }
+}
+
+/// Get the sizes of the messages of the given folder matching the
+/// given backend identifiers, using the backend of the given account.
+///
+/// Identifiers are the ones of the backend, not their aliases: UIDs
+/// for IMAP, Maildir entry identifiers and notmuch message ids.
+/// Messages whose size cannot be found are left out of the returned
+/// map.
+#[cfg_attr(
+    not(any(feature = "imap", feature = "maildir")),
+    allow(unused_variables)
+)]
+pub async fn fetch(
+    toml_account_config: &TomlAccountConfig,
+    account_config: &Arc<AccountConfig>,
+    folder: &str,
+    ids: &HashSet<&str>,
+) -> Result<HashMap<String, u64>> {
+    #[cfg(any(feature = "imap", feature = "maildir", feature = "notmuch"))]
+    use pimalaya_tui::himalaya::config::Backend;
+
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    match &toml_account_config.backend {
+        #[cfg(feature = "imap")]
+        Some(Backend::Imap(imap_config)) => {
+            imap::fetch(account_config, imap_config, folder, ids).await
+        }
+        #[cfg(feature = "maildir")]
+        Some(Backend::Maildir(mdir_config)) => {
+            maildir::fetch(account_config, mdir_config, folder, ids).await
+        }
+        #[cfg(feature = "notmuch")]
+        Some(Backend::Notmuch(notmuch_config)) => notmuch::fetch(notmuch_config, ids),
+        _ => bail!("message sizes are only supported by the IMAP, Maildir and notmuch backends"),
+    }
+}
+
+/// Format the given size in bytes using the largest unit keeping it
+/// above 1 (for example 512 B, 1.5 KiB or 25.0 MiB).
+pub fn format(size: u64) -> String {
+    let mut value = size as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{size} {}", UNITS[0]),
+        _ => format!("{value:.1} {}", UNITS[unit]),
+    }
+}
+
+/// Parse the given size, made of a number optionally followed by a
+/// unit (for example 2048, 500k, 1.5M or 10MiB).
+///
+/// Units are powers of 1024, with or without the B or iB suffix.
+pub fn parse(size: &str) -> Result<u64, String> {
+    let err = || format!("invalid size `{size}`, expected a number followed by k, m or g");
+
+    let i = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+
+    let (value, unit) = size.split_at(i);
+    let value: f64 = value.parse().map_err(|_| err())?;
+
+    let unit = unit.trim().to_ascii_lowercase();
+    let unit = unit
+        .strip_suffix("ib")
+        .or_else(|| unit.strip_suffix('b'))
+        .unwrap_or(&unit);
+
+    let exp = match unit {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        _ => return Err(err()),
+    };
+
+    Ok((value * 1024f64.powi(exp)).round() as u64)
+}
+
+#[cfg(feature = "imap")]
+mod imap {
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::Arc,
+    };
+
+    use color_eyre::Result;
+    use email::{
+        account::config::AccountConfig, imap::config::ImapConfig, imap::ImapClientBuilder,
+    };
+    use imap_client::imap_types::{
+        fetch::{MacroOrMessageDataItemNames, MessageDataItem, MessageDataItemName},
+        sequence::SequenceSet,
+    };
+    use tracing::debug;
+    use utf7_imap::encode_utf7_imap as encode_utf7;
+
+    /// The maximum number of UID ranges of a FETCH command.
+    ///
+    /// Some servers reject command lines longer than 8000 bytes, which
+    /// 250 ranges of 10-digit UIDs stay under.
+    const MAX_RANGES: usize = 250;
+
+    /// Fetch the RFC822.SIZE of the given UIDs, over a dedicated
+    /// connection.
+    ///
+    /// Large UID sets are fetched in chunks, one FETCH command each.
+    pub async fn fetch(
+        account_config: &AccountConfig,
+        config: &ImapConfig,
+        folder: &str,
+        ids: &HashSet<&str>,
+    ) -> Result<HashMap<String, u64>> {
+        let mut uids: Vec<u32> = ids.iter().filter_map(|id| id.parse().ok()).collect();
+
+        if uids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        uids.sort_unstable();
+
+        let mut client = ImapClientBuilder::new(Arc::new(config.clone()), None)
+            .build()
+            .await?;
+
+        let folder = encode_utf7(account_config.get_folder_alias(folder));
+        client.examine(folder).await?;
+
+        let mut sizes = HashMap::new();
+
+        for sequence in to_sequences(&uids) {
+            let items = MacroOrMessageDataItemNames::MessageDataItemNames(vec![
+                MessageDataItemName::Rfc822Size,
+            ]);
+
+            let uids = SequenceSet::try_from(sequence.as_str())?;
+
+            for (uid, items) in client.uid_fetch(uids, items).await? {
+                let size = items.as_ref().iter().find_map(|item| match item {
+                    MessageDataItem::Rfc822Size(size) => Some(*size),
+                    _ => None,
+                });
+
+                match size {
+                    Some(size) => {
+                        sizes.insert(uid.to_string(), size.into());
+                    }
+                    None => debug!("cannot get size of message {uid}, skipping it"),
+                }
+            }
+        }
+
+        Ok(sizes)
+    }
+
+    /// Turn the given sorted UIDs into sequence sets, grouping
+    /// consecutive UIDs into ranges and splitting them into chunks of
+    /// at most [`MAX_RANGES`] ranges, to keep commands short.
+    fn to_sequences(uids: &[u32]) -> Vec<String> {
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+
+        for &uid in uids {
+            match ranges.last_mut() {
+                Some((_, end)) if *end == uid || *end + 1 == uid => *end = uid,
+                _ => ranges.push((uid, uid)),
+            }
+        }
+
+        ranges
+            .chunks(MAX_RANGES)
+            .map(|chunk| {
+                let ranges: Vec<_> = chunk
+                    .iter()
+                    .map(|(start, end)| match start == end {
+                        true => start.to_string(),
+                        false => format!("{start}:{end}"),
+                    })
+                    .collect();
+
+                ranges.join(",")
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{
+            collections::{HashMap, HashSet},
+            sync::Arc,
+        };
+
+        use email::{
+            account::config::{passwd::PasswordConfig, AccountConfig},
+            imap::config::{ImapAuthConfig, ImapConfig},
+            tls::Encryption,
+        };
+        use secret::Secret;
+        use tokio::{
+            io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+            net::TcpListener,
+            sync::Mutex,
+        };
+
+        use super::{fetch, to_sequences, MAX_RANGES};
+
+        /// Every other UID of 20k messages, then a run of 100
+        /// consecutive ones: 10k UIDs that mostly cannot be grouped.
+        fn uids() -> Vec<u32> {
+            let mut uids: Vec<u32> = (1..20_000).step_by(2).take(9_900).collect();
+            uids.extend(19_801..=19_900);
+            uids
+        }
+
+        /// Expand the given sequence set into its UIDs.
+        fn expand(sequence: &str) -> Vec<u32> {
+            sequence
+                .split(',')
+                .flat_map(|range| match range.split_once(':') {
+                    Some((start, end)) => start.parse().unwrap()..=end.parse().unwrap(),
+                    None => {
+                        let uid = range.parse().unwrap();
+                        uid..=uid
+                    }
+                })
+                .collect()
+        }
+
+        /// A local IMAP server holding 20k messages, accepting any
+        /// credentials and recording UID FETCH commands.
+        #[derive(Default)]
+        struct Server {
+            fetches: Mutex<Vec<String>>,
+        }
+
+        impl Server {
+            async fn start() -> (Arc<Self>, u16) {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let port = listener.local_addr().unwrap().port();
+                let server = Arc::new(Self::default());
+
+                let serving = server.clone();
+                tokio::spawn(async move {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    serving.serve(stream).await
+                });
+
+                (server, port)
+            }
+
+            async fn serve(&self, stream: tokio::net::TcpStream) {
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                writer
+                    .write_all(b"* OK [CAPABILITY IMAP4rev1] ready\r\n")
+                    .await
+                    .unwrap();
+
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let (tag, cmd) = line.split_once(' ').unwrap();
+                    let upper = cmd.to_uppercase();
+
+                    let mut reply = String::new();
+
+                    if upper.starts_with("CAPABILITY") {
+                        reply.push_str("* CAPABILITY IMAP4rev1\r\n");
+                    } else if upper.starts_with("EXAMINE") {
+                        reply.push_str("* 20000 EXISTS\r\n* OK [UIDVALIDITY 1] ok\r\n");
+                    } else if upper.starts_with("UID FETCH") {
+                        let sequence = cmd.split(' ').nth(2).unwrap();
+                        for uid in expand(sequence) {
+                            let size = uid * 10;
+                            reply += &format!("* {uid} FETCH (UID {uid} RFC822.SIZE {size})\r\n");
+                        }
+                        self.fetches.lock().await.push(line.clone());
+                    } else if upper.starts_with("LOGOUT") {
+                        reply.push_str("* BYE\r\n");
+                    }
+
+                    reply += &format!("{tag} OK done\r\n");
+                    writer.write_all(reply.as_bytes()).await.unwrap();
+                }
+            }
+        }
+
+        #[test]
+        fn chunk_sequences() {
+            let uids = uids();
+            let sequences = to_sequences(&uids);
+
+            assert_eq!(sequences.len(), 40);
+
+            for sequence in &sequences {
+                assert!(sequence.split(',').count() <= MAX_RANGES);
+                assert!(format!("A1 UID FETCH {sequence} (RFC822.SIZE)\r\n").len() < 8000);
+            }
+
+            let expanded: Vec<u32> = sequences.iter().flat_map(|seq| expand(seq)).collect();
+            assert_eq!(expanded, uids);
+        }
+
+        #[test]
+        fn group_consecutive_uids() {
+            assert_eq!(to_sequences(&[1, 2, 3, 5, 7, 8, 8]), vec!["1:3,5,7:8"]);
+            assert!(to_sequences(&[]).is_empty());
+        }
+
+        /// Fetch the sizes of 10k UIDs, which takes one FETCH command
+        /// per chunk of ranges.
+        #[tokio::test(flavor = "multi_thread")]
+        async fn fetch_10k_uids() {
+            let (server, port) = Server::start().await;
+
+            let config = ImapConfig {
+                host: String::from("127.0.0.1"),
+                port,
+                encryption: Some(Encryption::None),
+                login: String::from("alice"),
+                auth: ImapAuthConfig::Password(PasswordConfig(Secret::new_raw("password"))),
+                ..Default::default()
+            };
+
+            let ids: Vec<String> = uids().iter().map(ToString::to_string).collect();
+            let ids: HashSet<&str> = ids.iter().map(String::as_str).collect();
+
+            let sizes = fetch(&AccountConfig::default(), &config, "INBOX", &ids)
+                .await
+                .unwrap();
+
+            let expected: HashMap<String, u64> = uids()
+                .into_iter()
+                .map(|uid| (uid.to_string(), uid as u64 * 10))
+                .collect();
+
+            assert_eq!(sizes, expected);
+
+            let fetches = server.fetches.lock().await;
+            assert_eq!(fetches.len(), 40);
+            assert!(fetches.iter().all(|line| line.len() < 8000));
+        }
+    }
+}
+
+#[cfg(feature = "maildir")]
+mod maildir {
+    use std::{
+        collections::{HashMap, HashSet},
+        fs,
+        sync::Arc,
+    };
+
+    use color_eyre::Result;
+    use email::{
+        account::config::AccountConfig,
+        backend::context::BackendContextBuilder,
+        maildir::{config::MaildirConfig, MaildirContextBuilder},
+    };
+    use tracing::debug;
+
+    /// Get the size on disk of the message files matching the given
+    /// Maildir entry identifiers.
+    pub async fn fetch(
+        account_config: &Arc<AccountConfig>,
+        config: &MaildirConfig,
+        folder: &str,
+        ids: &HashSet<&str>,
+    ) -> Result<HashMap<String, u64>> {
+        let ctx = MaildirContextBuilder::new(account_config.clone(), Arc::new(config.clone()))
+            .build()
+            .await?;
+        let mdir = ctx.lock().await.get_maildir_from_folder_alias(folder)?;
+
+        let mut sizes = HashMap::new();
+
+        for entry in mdir.read()? {
+            let Ok(id) = entry.id() else {
+                continue;
+            };
+
+            if !ids.contains(id) {
+                continue;
+            }
+
+            match fs::metadata(entry.path()) {
+                Ok(metadata) => {
+                    sizes.insert(id.to_owned(), metadata.len());
+                }
+                Err(err) => debug!("cannot get size of message {id}: {err}"),
+            }
+        }
+
+        Ok(sizes)
+    }
+}
+
+#[cfg(feature = "notmuch")]
+mod notmuch {
+    use std::{
+        collections::{HashMap, HashSet},
+        fs,
+    };
+
+    use color_eyre::Result;
+    use email::notmuch::config::NotmuchConfig;
+    use notmuch::{Database, DatabaseMode};
+    use shellexpand_utils::shellexpand_path;
+    use tracing::debug;
+
+    /// Get the size on disk of the message files matching the given
+    /// notmuch message ids.
+    pub fn fetch(config: &NotmuchConfig, ids: &HashSet<&str>) -> Result<HashMap<String, u64>> {
+        let db_path = config.database_path.as_ref().map(shellexpand_path);
+        let config_path = config.find_config_path();
+        let profile = config.find_profile();
+        let db = Database::open_with_config(db_path, DatabaseMode::ReadOnly, config_path, profile)?;
+
+        let mut sizes = HashMap::new();
+
+        for id in ids {
+            let Some(msg) = db.find_message(id)? else {
+                debug!("cannot find notmuch message {id}, skipping it");
+                continue;
+            };
+
+            match fs::metadata(msg.filename()) {
+                Ok(metadata) => {
+                    sizes.insert(id.to_string(), metadata.len());
+                }
+                Err(err) => debug!("cannot get size of message {id}: {err}"),
+            }
+        }
+
+        db.close()?;
+
+        Ok(sizes)
+    }
+}
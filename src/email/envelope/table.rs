@@ -0,0 +1,463 @@
+use std::{collections::HashMap, fmt};
+
+use clap::ValueEnum;
+use comfy_table::{Cell, ContentArrangement, Row, Table};
+use crossterm::style::Color;
+use pimalaya_tui::himalaya::config::{Envelope, Envelopes, Flag, ListEnvelopesTableConfig};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{
+    envelope::{arg::flag::FlagFilters, size},
+    table::{Column, ColumnWidth, TableConfig},
+    tag,
+};
+
+/// The default columns of the envelopes table.
+const COLUMNS: [EnvelopeColumn; 5] = [
+    EnvelopeColumn::Id,
+    EnvelopeColumn::Flags,
+    EnvelopeColumn::Subject,
+    EnvelopeColumn::From,
+    EnvelopeColumn::Date,
+];
+
+/// The column of the envelopes table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnvelopeColumn {
+    /// The envelope identifier.
+    Id,
+    /// The flagged, unseen, attachment and replied markers.
+    Flags,
+    /// The subject.
+    Subject,
+    /// The sender.
+    From,
+    /// The first recipient.
+    To,
+    /// The date.
+    Date,
+    /// The notmuch tags.
+    Tags,
+    /// The message size, in a human-readable unit.
+    Size,
+}
+
+impl EnvelopeColumn {
+    /// Get the declaration of the column.
+    pub const fn column(self) -> Column {
+        match self {
+            Self::Id => Column::left("id", "ID").with_width(ColumnWidth::Content),
+            Self::Flags => Column::left("flags", "FLAGS").with_width(ColumnWidth::Content),
+            Self::Subject => Column::left("subject", "SUBJECT"),
+            Self::From => Column::left("from", "FROM"),
+            Self::To => Column::left("to", "TO"),
+            Self::Date => Column::left("date", "DATE").with_width(ColumnWidth::Content),
+            Self::Tags => Column::left("tags", "TAGS"),
+            Self::Size => Column::right("size", "SIZE").with_width(ColumnWidth::Content),
+        }
+    }
+}
+
+/// The printable table of envelopes.
+pub struct EnvelopesTable {
+    envelopes: Envelopes,
+    width: Option<u16>,
+    tags: bool,
+    columns: Option<Vec<EnvelopeColumn>>,
+    sizes: HashMap<String, u64>,
+    groups: HashMap<String, String>,
+    config: ListEnvelopesTableConfig,
+    table_config: TableConfig,
+}
+
+impl EnvelopesTable {
+    pub fn with_some_width(mut self, width: Option<u16>) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Show the notmuch tags of envelopes in a dedicated column.
+    pub fn with_tags(mut self, tags: bool) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Show the given columns, in the given order.
+    ///
+    /// Defaults to the identifier, flags, subject, sender and date
+    /// columns, followed by the tags column when tags are shown.
+    pub fn with_some_columns(mut self, columns: Option<Vec<EnvelopeColumn>>) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Set the message sizes, indexed by envelope identifier.
+    ///
+    /// Sizes are shown in the size column, and added to envelopes of
+    /// the JSON output.
+    pub fn with_sizes(mut self, sizes: HashMap<String, u64>) -> Self {
+        self.sizes = sizes;
+        self
+    }
+
+    /// Set the group labels, indexed by envelope identifier.
+    ///
+    /// A section row is shown above each run of envelopes sharing the
+    /// same label, and labels are added to envelopes of the JSON
+    /// output.
+    pub fn with_groups(mut self, groups: HashMap<String, String>) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    pub fn with_some_preset(mut self, preset: Option<String>) -> Self {
+        self.config.preset = preset;
+        self
+    }
+
+    pub fn with_some_unseen_char(mut self, char: Option<char>) -> Self {
+        self.config.unseen_char = char;
+        self
+    }
+
+    pub fn with_some_replied_char(mut self, char: Option<char>) -> Self {
+        self.config.replied_char = char;
+        self
+    }
+
+    pub fn with_some_flagged_char(mut self, char: Option<char>) -> Self {
+        self.config.flagged_char = char;
+        self
+    }
+
+    pub fn with_some_attachment_char(mut self, char: Option<char>) -> Self {
+        self.config.attachment_char = char;
+        self
+    }
+
+    pub fn with_some_id_color(mut self, color: Option<Color>) -> Self {
+        self.config.id_color = color;
+        self
+    }
+
+    pub fn with_some_flags_color(mut self, color: Option<Color>) -> Self {
+        self.config.flags_color = color;
+        self
+    }
+
+    pub fn with_some_subject_color(mut self, color: Option<Color>) -> Self {
+        self.config.subject_color = color;
+        self
+    }
+
+    pub fn with_some_sender_color(mut self, color: Option<Color>) -> Self {
+        self.config.sender_color = color;
+        self
+    }
+
+    pub fn with_some_date_color(mut self, color: Option<Color>) -> Self {
+        self.config.date_color = color;
+        self
+    }
+
+    pub fn with_table_config(mut self, config: TableConfig) -> Self {
+        self.table_config = config;
+        self
+    }
+}
+
+impl From<Envelopes> for EnvelopesTable {
+    fn from(envelopes: Envelopes) -> Self {
+        Self {
+            envelopes,
+            width: None,
+            tags: false,
+            columns: None,
+            sizes: Default::default(),
+            groups: Default::default(),
+            config: Default::default(),
+            table_config: Default::default(),
+        }
+    }
+}
+
+impl fmt::Display for EnvelopesTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns = match &self.columns {
+            Some(columns) => columns.clone(),
+            None if self.tags => [COLUMNS.as_slice(), &[EnvelopeColumn::Tags]].concat(),
+            None => COLUMNS.to_vec(),
+        };
+
+        let mut table = Table::new();
+
+        table
+            .load_preset(self.config.preset())
+            .set_content_arrangement(ContentArrangement::DynamicFullWidth);
+
+        let mut group = None;
+
+        for envelope in self.envelopes.iter() {
+            let label = self.groups.get(&envelope.id);
+
+            if let Some(label) = label.filter(|label| group != Some(*label)) {
+                table.add_row(to_section_row(label, &columns, &self.table_config));
+                group = Some(label);
+            }
+
+            table.add_row(to_row(
+                envelope,
+                &columns,
+                &self.sizes,
+                &self.config,
+                &self.table_config,
+            ));
+        }
+
+        let columns: Vec<Column> = columns.iter().map(|col| col.column()).collect();
+
+        self.table_config.apply(&mut table, &columns, self.width);
+
+        writeln!(f)?;
+        write!(f, "{table}")?;
+        writeln!(f)?;
+        Ok(())
+    }
+}
+
+impl Serialize for EnvelopesTable {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if self.sizes.is_empty() && self.groups.is_empty() {
+            return self.envelopes.serialize(serializer);
+        }
+
+        serializer.collect_seq(self.envelopes.iter().map(|envelope| ExtendedEnvelope {
+            envelope,
+            size: match self.sizes.is_empty() {
+                true => None,
+                false => Some(self.sizes.get(&envelope.id).copied()),
+            },
+            group: self.groups.get(&envelope.id),
+        }))
+    }
+}
+
+/// An envelope along with the size of its message and its group.
+///
+/// The size is only given when sizes have been fetched, and the
+/// group when envelopes are grouped.
+#[derive(Serialize)]
+struct ExtendedEnvelope<'a> {
+    #[serde(flatten)]
+    envelope: &'a Envelope,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<&'a String>,
+}
+
+/// A printable table of envelopes listed using flag filters.
+///
+/// The filters are shown above the table. The JSON output is the
+/// same as the unfiltered table, so that scripts get one shape.
+pub struct FilteredTable<T> {
+    filters: FlagFilters,
+    table: T,
+}
+
+impl<T> FilteredTable<T> {
+    pub fn new(filters: FlagFilters, table: T) -> Self {
+        Self { filters, table }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for FilteredTable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f)?;
+        write!(f, "Flags: {}", self.filters)?;
+        write!(f, "{}", self.table)
+    }
+}
+
+impl<T: Serialize> Serialize for FilteredTable<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.table.serialize(serializer)
+    }
+}
+
+fn to_row(
+    envelope: &Envelope,
+    columns: &[EnvelopeColumn],
+    sizes: &HashMap<String, u64>,
+    config: &ListEnvelopesTableConfig,
+    table_config: &TableConfig,
+) -> Row {
+    let unseen = !envelope.flags.contains(&Flag::Seen);
+    let flagged = envelope.flags.contains(&Flag::Flagged);
+
+    let mut row = Row::new();
+    row.max_height(1);
+
+    for column in columns {
+        let cell = match column {
+            EnvelopeColumn::Id => Cell::new(&envelope.id).fg(config.id_color()),
+            EnvelopeColumn::Flags => {
+                let mut flags = String::new();
+
+                flags.push(config.flagged_char(flagged));
+                flags.push(config.unseen_char(unseen));
+                flags.push(config.attachment_char(envelope.has_attachment));
+                flags.push(config.replied_char(envelope.flags.contains(&Flag::Answered)));
+
+                Cell::new(flags).fg(config.flags_color())
+            }
+            EnvelopeColumn::Subject => Cell::new(&envelope.subject).fg(config.subject_color()),
+            EnvelopeColumn::From => {
+                let sender = envelope.from.name.as_ref().unwrap_or(&envelope.from.addr);
+                Cell::new(sender).fg(config.sender_color())
+            }
+            EnvelopeColumn::To => {
+                let recipient = envelope.to.name.as_ref().unwrap_or(&envelope.to.addr);
+                Cell::new(recipient).fg(config.sender_color())
+            }
+            EnvelopeColumn::Date => Cell::new(&envelope.date).fg(config.date_color()),
+            EnvelopeColumn::Tags => {
+                let tags = tag::from_notmuch_flags(envelope.flags.iter()).join(" ");
+                Cell::new(tags).fg(config.flags_color())
+            }
+            EnvelopeColumn::Size => {
+                let size = sizes.get(&envelope.id).copied().map(size::format);
+                Cell::new(size.unwrap_or_default())
+            }
+        };
+
+        let key = column.column().key;
+        row.add_cell(table_config.style_envelope(key, cell, unseen, flagged));
+    }
+
+    row
+}
+
+/// Build the section row of the given group label.
+///
+/// The label is shown in the subject column, or in the first column
+/// when the subject is hidden.
+fn to_section_row(label: &str, columns: &[EnvelopeColumn], table_config: &TableConfig) -> Row {
+    let at = columns
+        .iter()
+        .position(|column| *column == EnvelopeColumn::Subject)
+        .unwrap_or_default();
+
+    let mut row = Row::new();
+    row.max_height(1);
+
+    for (i, _) in columns.iter().enumerate() {
+        let cell = Cell::new(if i == at { label } else { "" });
+        row.add_cell(table_config.style_section(cell));
+    }
+
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use email::{account::config::AccountConfig, envelope::Envelope, flag::Flag};
+    use pimalaya_tui::himalaya::{config::Envelopes, id_mapper::IdMapper};
+
+    use crate::envelope::arg::flag::FlagFilters;
+
+    use super::{EnvelopesTable, FilteredTable};
+
+    /// Build a table of envelopes with the given identifiers and
+    /// group labels.
+    fn grouped_table(groups: &[(&str, &str)]) -> EnvelopesTable {
+        let envelopes = groups.iter().map(|(id, _)| Envelope {
+            id: id.to_string(),
+            subject: format!("subject {id}"),
+            ..Default::default()
+        });
+
+        let groups = groups
+            .iter()
+            .map(|(id, label)| (id.to_string(), label.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        let envelopes = Envelopes::try_from_backend(
+            &AccountConfig::default(),
+            &IdMapper::Dummy,
+            envelopes.collect(),
+        )
+        .unwrap();
+
+        EnvelopesTable::from(envelopes).with_groups(groups)
+    }
+
+    #[test]
+    fn filtered_table_json_is_unfiltered_json() {
+        let filters = FlagFilters {
+            flags: vec![Flag::Seen],
+            not_flags: vec![Flag::Flagged],
+        };
+        let table = FilteredTable::new(filters, vec!["1", "2"]);
+
+        let json = serde_json::to_string(&table).unwrap();
+        assert_eq!(json, serde_json::to_string(&vec!["1", "2"]).unwrap());
+    }
+
+    #[test]
+    fn filtered_table_shows_filters() {
+        let filters = FlagFilters {
+            flags: vec![Flag::Seen],
+            not_flags: vec![Flag::Flagged],
+        };
+        let table = FilteredTable::new(filters, "\ntable");
+
+        assert_eq!(table.to_string(), "\nFlags: seen, not flagged\ntable");
+    }
+
+    #[test]
+    fn show_one_section_row_per_group() {
+        let table = grouped_table(&[
+            ("1", "Today"),
+            ("2", "Today"),
+            ("3", "Yesterday"),
+            ("4", "June 2024"),
+        ])
+        .to_string();
+
+        for label in ["Today", "Yesterday", "June 2024"] {
+            assert_eq!(table.matches(label).count(), 1, "{table}");
+        }
+
+        let rows = [
+            "Today",
+            "subject 1",
+            "subject 2",
+            "Yesterday",
+            "subject 3",
+            "June 2024",
+            "subject 4",
+        ]
+        .map(|row| table.find(row).unwrap());
+
+        assert!(rows.windows(2).all(|rows| rows[0] < rows[1]), "{table}");
+    }
+
+    #[test]
+    fn add_groups_to_json() {
+        let table = grouped_table(&[("1", "Today"), ("2", "June 2024")]);
+
+        let json = serde_json::to_value(&table).unwrap();
+
+        assert_eq!(json[0]["id"], "1");
+        assert_eq!(json[0]["group"], "Today");
+        assert_eq!(json[1]["group"], "June 2024");
+        assert!(json[0].get("size").is_none());
+    }
+}
@@ -0,0 +1,37 @@
+use clap::Parser;
+use color_eyre::{eyre::bail, Result};
+
+/// The ids and tags arguments parser.
+#[derive(Debug, Parser)]
+pub struct IdsAndTagsArgs {
+    /// The list of ids followed by the list of tags.
+    ///
+    /// Leading arguments that can be parsed as integers are
+    /// considered ids, the remaining ones are considered tags. Tags
+    /// can start with a hyphen, which means that options need to be
+    /// given before ids. When ids are omitted and stdin is a
+    /// terminal, envelopes are picked interactively.
+    #[arg(value_name = "ID-OR-TAG", required = true)]
+    #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+    pub ids_and_tags: Vec<String>,
+}
+
+impl IdsAndTagsArgs {
+    /// Split arguments into ids and tags.
+    pub fn to_tuple(&self) -> Result<(Vec<usize>, Vec<String>)> {
+        let mut args = self.ids_and_tags.iter().peekable();
+        let mut ids = Vec::new();
+
+        while let Some(id) = args.next_if(|arg| arg.parse::<usize>().is_ok()) {
+            ids.push(id.parse()?);
+        }
+
+        let tags: Vec<String> = args.cloned().collect();
+
+        if tags.is_empty() {
+            bail!("missing tags, ids must be followed by at least one tag");
+        }
+
+        Ok((ids, tags))
+    }
+}
@@ -0,0 +1 @@
+pub mod ids_and_tags;
@@ -0,0 +1,35 @@
+mod add;
+mod remove;
+
+use clap::Subcommand;
+use color_eyre::Result;
+
+use crate::{config::TomlConfig, printer::Printer};
+
+use self::{add::TagAddCommand, remove::TagRemoveCommand};
+
+/// Add and remove your envelopes tags.
+///
+/// A tag is an arbitrary label associated to an envelope. Tags are
+/// natively supported by the notmuch backend, and are mapped to
+/// keywords by the IMAP backend. The Maildir backend does not support
+/// them.
+#[derive(Debug, Subcommand)]
+pub enum TagSubcommand {
+    #[command(arg_required_else_help = true)]
+    #[command(alias = "create")]
+    Add(TagAddCommand),
+
+    #[command(arg_required_else_help = true)]
+    #[command(aliases = ["rm", "delete", "del"])]
+    Remove(TagRemoveCommand),
+}
+
+impl TagSubcommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        match self {
+            Self::Add(cmd) => cmd.execute(printer, config).await,
+            Self::Remove(cmd) => cmd.execute(printer, config).await,
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use clap::Parser;
+use color_eyre::Result;
+use email::{backend::feature::BackendFeatureSource, config::Config};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
+use tracing::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    envelope::picker,
+    folder::{self, arg::name::FolderNameOptionalFlag},
+    printer::Printer,
+    tag::arg::ids_and_tags::IdsAndTagsArgs,
+};
+
+/// Remove tag(s) from the given envelope.
+///
+/// This command allows you to remove the given tag(s) from the given
+/// envelope(s).
+#[derive(Debug, Parser)]
+pub struct TagRemoveCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+
+    #[command(flatten)]
+    pub args: IdsAndTagsArgs,
+}
+
+impl TagRemoveCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing remove tag(s) command");
+
+        let folder = &self.folder.name;
+        let (ids, tags) = self.args.to_tuple()?;
+        let (toml_account_config, account_config) = config
+            .clone()
+            .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
+                c.account(name).ok()
+            })?;
+
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        let (flags, inverted_flags) = crate::tag::into_flags(&toml_account_config, &tags)?;
+
+        let backend = BackendBuilder::new(
+            Arc::new(toml_account_config),
+            Arc::new(account_config),
+            |builder| {
+                builder
+                    .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
+                    .with_add_flags(BackendFeatureSource::Context)
+                    .with_remove_flags(BackendFeatureSource::Context)
+            },
+        )
+        .without_sending_backend()
+        .build()
+        .await?;
+
+        let ids = picker::ids_or_pick(&backend, folder, &ids, true).await?;
+
+        if !flags.is_empty() {
+            backend.remove_flags(folder, &ids, &flags).await?;
+        }
+
+        if !inverted_flags.is_empty() {
+            backend.add_flags(folder, &ids, &inverted_flags).await?;
+        }
+
+        let tags = tags.join(" ");
+        printer.info(format!("Tag(s) {tags} successfully removed!\n"))
+    }
+}
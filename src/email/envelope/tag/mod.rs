@@ -0,0 +1,128 @@
+//! Module dedicated to envelope tags.
+//!
+//! Tags are arbitrary labels attached to envelopes. Notmuch tags are
+//! mapped to flags the same way the notmuch backend reads them, IMAP
+//! tags are mapped to keywords, and Maildir does not support them.
+
+pub mod arg;
+pub mod command;
+
+use color_eyre::{eyre::bail, Result};
+use email::flag::Flags;
+use pimalaya_tui::himalaya::config::Flag;
+
+use crate::account::config::TomlAccountConfig;
+
+/// Check if the given account uses the notmuch backend.
+#[cfg(feature = "notmuch")]
+pub fn is_notmuch(config: &TomlAccountConfig) -> bool {
+    use pimalaya_tui::himalaya::config::Backend;
+
+    matches!(config.backend, Some(Backend::Notmuch(_)))
+}
+
+/// Check if the given account uses the notmuch backend.
+#[cfg(not(feature = "notmuch"))]
+pub fn is_notmuch(_: &TomlAccountConfig) -> bool {
+    false
+}
+
+/// Convert the given tags into flags understood by the backend of
+/// the given account.
+///
+/// The first flags have the same effect as the tags, while the
+/// second ones have the opposite effect: adding the notmuch `unread`
+/// tag is the same as removing the seen flag.
+#[cfg_attr(
+    not(any(feature = "imap", feature = "notmuch")),
+    allow(unused_variables, unreachable_code)
+)]
+pub fn into_flags(config: &TomlAccountConfig, tags: &[String]) -> Result<(Flags, Flags)> {
+    #[cfg(any(feature = "imap", feature = "maildir", feature = "notmuch"))]
+    use pimalaya_tui::himalaya::config::Backend;
+
+    #[allow(unused_mut)]
+    let mut flags = Flags::default();
+    #[allow(unused_mut)]
+    let mut inverted_flags = Flags::default();
+
+    match &config.backend {
+        #[cfg(feature = "notmuch")]
+        Some(Backend::Notmuch(_)) => {
+            use email::flag::Flag;
+
+            for tag in tags {
+                match tag.as_str() {
+                    "unread" => inverted_flags.insert(Flag::Seen),
+                    "replied" => flags.insert(Flag::Answered),
+                    "flagged" => flags.insert(Flag::Flagged),
+                    "deleted" => flags.insert(Flag::Deleted),
+                    "draft" => flags.insert(Flag::Draft),
+                    tag => flags.insert(Flag::custom(tag)),
+                };
+            }
+        }
+        #[cfg(feature = "imap")]
+        Some(Backend::Imap(_)) => {
+            use email::flag::Flag;
+
+            for tag in tags {
+                if !is_imap_keyword(tag) {
+                    bail!("cannot use {tag:?} as IMAP keyword: invalid characters");
+                }
+
+                flags.insert(Flag::custom(tag));
+            }
+        }
+        #[cfg(feature = "maildir")]
+        Some(Backend::Maildir(_)) => {
+            bail!(
+                "tags are not supported by the Maildir backend, use flag commands \
+                 with seen, answered, flagged, deleted or draft instead"
+            );
+        }
+        _ => {
+            bail!("tags are only supported by the notmuch and IMAP backends");
+        }
+    }
+
+    Ok((flags, inverted_flags))
+}
+
+/// Check if the given tag can be used as IMAP keyword.
+#[cfg(feature = "imap")]
+pub fn is_imap_keyword(tag: &str) -> bool {
+    // atom-specials, see RFC 3501 section 9
+    const SPECIALS: &[char] = &['(', ')', '{', ' ', '%', '*', '"', '\\', ']'];
+
+    !tag.is_empty()
+        && !tag.contains(SPECIALS)
+        && tag.chars().all(|c| c.is_ascii() && !c.is_ascii_control())
+}
+
+/// Get the notmuch tags matching the given envelope flags.
+///
+/// This reverts the mapping made by the notmuch backend when listing
+/// envelopes.
+pub fn from_notmuch_flags<'a>(flags: impl IntoIterator<Item = &'a Flag>) -> Vec<String> {
+    let mut seen = false;
+    let mut tags = Vec::new();
+
+    for flag in flags {
+        match flag {
+            Flag::Seen => seen = true,
+            Flag::Answered => tags.push(String::from("replied")),
+            Flag::Flagged => tags.push(String::from("flagged")),
+            Flag::Deleted => tags.push(String::from("deleted")),
+            Flag::Draft => tags.push(String::from("draft")),
+            Flag::Custom(tag) => tags.push(tag.clone()),
+        }
+    }
+
+    if !seen {
+        tags.push(String::from("unread"));
+    }
+
+    tags.sort();
+    tags
+}
@@ -0,0 +1,276 @@
+//! Module dedicated to envelope trees.
+//!
+//! Trees group envelopes into conversations, using the parents given
+//! by the backend. Replies whose parent is not part of the folder are
+//! grouped with the conversation matching their subject.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use chrono::{DateTime, FixedOffset};
+use color_eyre::Result;
+use email::{
+    account::config::AccountConfig,
+    envelope::{list::ListEnvelopesOptions, Envelope, Envelopes},
+};
+use pimalaya_tui::himalaya::{config, id_mapper::IdMapper};
+use serde::{Serialize, Serializer};
+
+use crate::envelope::table::EnvelopesTable;
+
+/// Envelopes with the parent of each reply, indexed by envelope
+/// identifier.
+pub type EnvelopesWithParents = (Vec<Envelope>, HashMap<String, String>);
+
+/// A conversation, made of an envelope and its replies.
+#[derive(Clone, Debug)]
+pub struct EnvelopeTree {
+    pub envelope: Envelope,
+    pub children: Vec<EnvelopeTree>,
+}
+
+impl EnvelopeTree {
+    /// Get the date of the most recent envelope of the tree.
+    pub fn latest_date(&self) -> DateTime<FixedOffset> {
+        self.children
+            .iter()
+            .map(EnvelopeTree::latest_date)
+            .fold(self.envelope.date, DateTime::max)
+    }
+}
+
+/// Build conversations from the given envelopes.
+///
+/// Parents are given by envelope identifier. Envelopes without
+/// parent start a new conversation, unless they look like a reply
+/// and an older conversation has the same subject.
+///
+/// Conversations are sorted by their most recent envelope, using the
+/// sort query of the given options (most recent first by default),
+/// while replies are sorted from the oldest to the most recent.
+pub fn build(
+    envelopes: impl IntoIterator<Item = Envelope>,
+    parents: &HashMap<String, String>,
+    opts: &ListEnvelopesOptions,
+) -> Vec<EnvelopeTree> {
+    let mut envelopes: HashMap<String, Envelope> = envelopes
+        .into_iter()
+        .map(|envelope| (envelope.id.clone(), envelope))
+        .collect();
+
+    let mut children: HashMap<String, Vec<String>> = HashMap::new();
+    let mut roots = Vec::new();
+
+    for envelope in envelopes.values() {
+        let parent = parents
+            .get(&envelope.id)
+            .filter(|parent| **parent != envelope.id && envelopes.contains_key(*parent));
+
+        match parent {
+            Some(parent) => children
+                .entry(parent.clone())
+                .or_default()
+                .push(envelope.id.clone()),
+            None => roots.push((envelope.date, envelope.id.clone())),
+        }
+    }
+
+    roots.sort();
+
+    let mut heads: HashMap<String, String> = HashMap::new();
+    let mut threads = Vec::new();
+
+    for (_, id) in roots {
+        let envelope = &envelopes[&id];
+        let (subject, is_reply) = normalize_subject(&envelope.subject);
+        let is_reply = is_reply || envelope.in_reply_to.is_some();
+
+        match heads.get(&subject) {
+            Some(head) if is_reply && !subject.is_empty() => {
+                children.entry(head.clone()).or_default().push(id);
+            }
+            _ => {
+                heads.entry(subject).or_insert_with(|| id.clone());
+                threads.push(id);
+            }
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut trees: Vec<EnvelopeTree> = threads
+        .into_iter()
+        .filter_map(|id| build_tree(id, &mut envelopes, &children, &mut visited))
+        .collect();
+
+    // conversations are sorted as if they were made of their root
+    // envelope dated by their most recent envelope
+    let mut heads: Envelopes = trees
+        .iter()
+        .map(|tree| Envelope {
+            date: tree.latest_date(),
+            ..tree.envelope.clone()
+        })
+        .collect();
+
+    opts.sort_envelopes(&mut heads);
+
+    let positions: HashMap<&str, usize> = heads
+        .iter()
+        .enumerate()
+        .map(|(i, envelope)| (envelope.id.as_str(), i))
+        .collect();
+
+    trees.sort_by_key(|tree| positions[tree.envelope.id.as_str()]);
+    trees
+}
+
+fn build_tree(
+    id: String,
+    envelopes: &mut HashMap<String, Envelope>,
+    children: &HashMap<String, Vec<String>>,
+    visited: &mut HashSet<String>,
+) -> Option<EnvelopeTree> {
+    if !visited.insert(id.clone()) {
+        return None;
+    }
+
+    let envelope = envelopes.remove(&id)?;
+
+    let mut children: Vec<EnvelopeTree> = children
+        .get(&id)
+        .into_iter()
+        .flatten()
+        .filter_map(|id| build_tree(id.clone(), envelopes, children, visited))
+        .collect();
+
+    children.sort_by_key(|tree| tree.envelope.date);
+
+    Some(EnvelopeTree { envelope, children })
+}
+
+/// Strip reply and forward prefixes of the given subject.
+///
+/// Returns the lowercased subject, and whether a prefix was found.
+fn normalize_subject(subject: &str) -> (String, bool) {
+    let mut subject = subject.trim();
+    let mut is_reply = false;
+
+    'strip: loop {
+        for prefix in ["re:", "fw:", "fwd:"] {
+            let Some(start) = subject.get(..prefix.len()) else {
+                continue;
+            };
+
+            if start.eq_ignore_ascii_case(prefix) {
+                subject = subject[prefix.len()..].trim_start();
+                is_reply = true;
+                continue 'strip;
+            }
+        }
+
+        break;
+    }
+
+    (subject.to_lowercase(), is_reply)
+}
+
+/// The printable table of conversations.
+///
+/// Conversations are shown as a table of envelopes, replies being
+/// indented under their parent. The JSON output keeps the nested
+/// structure, replies being listed in a `children` array.
+pub struct EnvelopeTreesTable {
+    table: EnvelopesTable,
+    trees: Vec<EnvelopeNode>,
+}
+
+impl EnvelopeTreesTable {
+    pub fn try_new(
+        config: &AccountConfig,
+        trees: &[EnvelopeTree],
+        to_table: impl FnOnce(config::Envelopes) -> EnvelopesTable,
+    ) -> Result<Self> {
+        let mut envelopes = Vec::new();
+
+        for tree in trees {
+            flatten(tree, String::new(), None, &mut envelopes);
+        }
+
+        let envelopes = config::Envelopes::try_from_backend(
+            config,
+            &IdMapper::Dummy,
+            Envelopes::from_iter(envelopes),
+        )?;
+
+        let trees = trees
+            .iter()
+            .map(|tree| EnvelopeNode::try_from_tree(config, tree))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            table: to_table(envelopes),
+            trees,
+        })
+    }
+}
+
+impl fmt::Display for EnvelopeTreesTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.table)
+    }
+}
+
+impl Serialize for EnvelopeTreesTable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.trees.serialize(serializer)
+    }
+}
+
+/// Flatten the given tree, prefixing subjects of replies with the
+/// branches leading to them.
+fn flatten(tree: &EnvelopeTree, pad: String, is_last: Option<bool>, out: &mut Vec<Envelope>) {
+    let (prefix, child_pad) = match is_last {
+        None => (String::new(), String::new()),
+        Some(false) => (format!("{pad}├─ "), format!("{pad}│  ")),
+        Some(true) => (format!("{pad}└─ "), format!("{pad}   ")),
+    };
+
+    out.push(Envelope {
+        subject: format!("{prefix}{}", tree.envelope.subject),
+        ..tree.envelope.clone()
+    });
+
+    let count = tree.children.len();
+
+    for (i, child) in tree.children.iter().enumerate() {
+        flatten(child, child_pad.clone(), Some(i + 1 == count), out);
+    }
+}
+
+/// The serializable version of [`EnvelopeTree`].
+#[derive(Serialize)]
+struct EnvelopeNode {
+    #[serde(flatten)]
+    envelope: config::Envelope,
+    children: Vec<EnvelopeNode>,
+}
+
+impl EnvelopeNode {
+    fn try_from_tree(config: &AccountConfig, tree: &EnvelopeTree) -> Result<Self> {
+        let envelopes = Envelopes::from_iter([tree.envelope.clone()]);
+        let envelopes = config::Envelopes::try_from_backend(config, &IdMapper::Dummy, envelopes)?;
+
+        let children = tree
+            .children
+            .iter()
+            .map(|child| Self::try_from_tree(config, child))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            envelope: envelopes[0].clone(),
+            children,
+        })
+    }
+}
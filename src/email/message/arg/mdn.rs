@@ -0,0 +1,27 @@
+use clap::Parser;
+
+/// The read receipt flags parser.
+#[derive(Debug, Default, Parser)]
+pub struct MessageMdnFlags {
+    /// Request a read receipt.
+    ///
+    /// A Disposition-Notification-To header containing the address
+    /// of the account is added to the message. Defaults to the
+    /// message.request-mdn option of the account.
+    #[arg(long)]
+    #[arg(conflicts_with = "no_mdn")]
+    pub mdn: bool,
+
+    /// Do not request a read receipt, whatever the
+    /// message.request-mdn option of the account.
+    #[arg(long)]
+    pub no_mdn: bool,
+}
+
+impl MessageMdnFlags {
+    /// Return `true` if a read receipt should be requested, given
+    /// the default of the account.
+    pub fn is_requested(&self, default: bool) -> bool {
+        self.mdn || (default && !self.no_mdn)
+    }
+}
@@ -2,6 +2,7 @@ use clap::Parser;
 
 pub mod body;
 pub mod header;
+pub mod mdn;
 pub mod reply;
 
 /// The raw message argument parser.
@@ -5,8 +5,18 @@ use clap::Parser;
 pub struct MessageReplyAllArg {
     /// Reply to all recipients.
     ///
-    /// This argument will add all recipients for the To and Cc
-    /// headers.
+    /// The reply goes to the sender (or to the Reply-To addresses)
+    /// and to the To addresses of the message, while its Cc
+    /// addresses are kept in copy. The account addresses, including
+    /// the alternates ones, are left out.
     #[arg(long, short = 'A')]
     pub all: bool,
+
+    /// Reply to the mailing list.
+    ///
+    /// The reply goes to the address of the List-Post header of the
+    /// message. When replying to all, the other recipients are put
+    /// in copy.
+    #[arg(long)]
+    pub list: bool,
 }
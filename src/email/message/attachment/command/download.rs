@@ -1,23 +1,38 @@
 use clap::Parser;
-use color_eyre::{eyre::Context, Result};
-use email::{backend::feature::BackendFeatureSource, config::Config};
+use color_eyre::{
+    eyre::{bail, Context},
+    Result,
+};
+use email::{
+    account::config::AccountConfig, backend::feature::BackendFeatureSource, config::Config,
+};
 use pimalaya_tui::{
     himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
+    terminal::{cli::arg::path_parser, config::TomlConfig as _},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
-use std::{fs, path::PathBuf, sync::Arc};
 use tracing::info;
 use uuid::Uuid;
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig, envelope::arg::ids::EnvelopeIdsArgs,
-    folder::arg::name::FolderNameOptionalFlag,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    envelope::{arg::ids::EnvelopeIdsArgs, picker},
+    folder::{self, arg::name::FolderNameOptionalFlag},
+    message::attachment::list,
+    printer::Printer,
 };
 
 /// Download all attachments found in the given message.
 ///
 /// This command allows you to download all attachments found for the
-/// given message to your downloads directory.
+/// given message to your downloads directory. A single attachment can
+/// be downloaded using its index or its name, as given by the
+/// attachment list command.
 #[derive(Debug, Parser)]
 pub struct AttachmentDownloadCommand {
     #[command(flatten)]
@@ -26,6 +41,31 @@ pub struct AttachmentDownloadCommand {
     #[command(flatten)]
     pub envelopes: EnvelopeIdsArgs,
 
+    /// Download only the attachment at the given index.
+    ///
+    /// Indexes start from 1, in the order given by the attachment
+    /// list command. IMAP attachments are fetched alone, without the
+    /// rest of the message.
+    #[arg(long, value_name = "N", conflicts_with = "name")]
+    pub index: Option<usize>,
+
+    /// Download only the attachments matching the given file name.
+    ///
+    /// Names are compared case-insensitively, and can contain the *
+    /// (any characters) and ? (any character) wildcards, like
+    /// "*.pdf". IMAP attachments are fetched alone, without the rest
+    /// of the message.
+    #[arg(long, value_name = "NAME")]
+    pub name: Option<String>,
+
+    /// Where attachments should be downloaded to.
+    ///
+    /// The destination can point to a directory, or to a file when a
+    /// single attachment is downloaded. Defaults to the downloads
+    /// directory of the account.
+    #[arg(long, short, alias = "dest", value_name = "PATH", value_parser = path_parser)]
+    pub destination: Option<PathBuf>,
+
     #[command(flatten)]
     pub account: AccountNameFlag,
 }
@@ -35,7 +75,6 @@ impl AttachmentDownloadCommand {
         info!("executing download attachment(s) command");
 
         let folder = &self.folder.name;
-        let ids = &self.envelopes.ids;
 
         let (toml_account_config, account_config) = config
             .clone()
@@ -43,21 +82,73 @@ impl AttachmentDownloadCommand {
                 c.account(name).ok()
             })?;
 
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        let toml_account_config = Arc::new(toml_account_config);
         let account_config = Arc::new(account_config);
 
         let backend = BackendBuilder::new(
-            Arc::new(toml_account_config),
+            toml_account_config.clone(),
             account_config.clone(),
             |builder| {
                 builder
                     .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
                     .with_get_messages(BackendFeatureSource::Context)
+                    .with_peek_messages(BackendFeatureSource::Context)
             },
         )
         .without_sending_backend()
         .build()
         .await?;
 
+        let select = self.index.is_some() || self.name.is_some();
+        let ids = &picker::ids_or_pick(&backend, folder, &self.envelopes.ids, !select).await?;
+
+        if select {
+            let [id] = ids[..] else {
+                bail!("cannot select attachments of more than one message at a time");
+            };
+
+            let attachments =
+                list::list(&backend, &toml_account_config, &account_config, folder, id).await?;
+            let attachments = list::select(attachments, id, self.index, self.name.as_deref())?;
+            let single = attachments.len() == 1;
+
+            for attachment in attachments {
+                let body = list::get(
+                    &backend,
+                    &toml_account_config,
+                    &account_config,
+                    folder,
+                    id,
+                    &attachment,
+                )
+                .await?;
+
+                let filepath = to_file_path(
+                    &account_config,
+                    self.destination.as_deref(),
+                    attachment.filename,
+                    single,
+                )?;
+
+                fs::write(&filepath, body)
+                    .with_context(|| format!("cannot save attachment at {filepath:?}"))?;
+
+                printer.info(format!(
+                    "Attachment {} of message {id} downloaded to {}!\n",
+                    attachment.index,
+                    filepath.display()
+                ))?;
+            }
+
+            return Ok(());
+        }
+
+        let single = self.destination.is_some() && ids.len() == 1;
+
         let emails = backend.get_messages(folder, ids).await?;
 
         let mut emails_count = 0;
@@ -80,12 +171,15 @@ impl AttachmentDownloadCommand {
                 attachments.len()
             ))?;
 
+            let single = single && attachments.len() == 1;
+
             for attachment in attachments {
-                let filename: PathBuf = attachment
-                    .filename
-                    .unwrap_or_else(|| Uuid::new_v4().to_string())
-                    .into();
-                let filepath = account_config.get_download_file_path(&filename)?;
+                let filepath = to_file_path(
+                    &account_config,
+                    self.destination.as_deref(),
+                    attachment.filename,
+                    single,
+                )?;
                 printer.log(format!("Downloading {:?}…\n", filepath))?;
                 fs::write(&filepath, &attachment.body)
                     .with_context(|| format!("cannot save attachment at {filepath:?}"))?;
@@ -94,12 +188,214 @@ impl AttachmentDownloadCommand {
         }
 
         match attachments_count {
-            0 => printer.out("No attachment found!\n"),
-            1 => printer.out("Downloaded 1 attachment!\n"),
-            n => printer.out(format!(
+            0 => printer.info("No attachment found!\n"),
+            1 => printer.info("Downloaded 1 attachment!\n"),
+            n => printer.info(format!(
                 "Downloaded {} attachment(s) from {} messages(s)!\n",
                 n, emails_count,
             )),
         }
     }
 }
+
+/// Return the path the attachment of the given file name should be
+/// downloaded to.
+///
+/// Attachments are downloaded to the given destination when it is a
+/// directory, or when it is a file and a single attachment is
+/// downloaded. They are downloaded to the downloads directory of the
+/// account otherwise.
+fn to_file_path(
+    account_config: &AccountConfig,
+    destination: Option<&Path>,
+    filename: Option<String>,
+    single: bool,
+) -> Result<PathBuf> {
+    let filename: PathBuf = filename
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+        .into();
+
+    match destination {
+        None => Ok(account_config.get_download_file_path(&filename)?),
+        Some(dir) if dir.is_dir() => {
+            let Some(filename) = filename.file_name() else {
+                bail!("cannot find file name of attachment {filename:?}");
+            };
+            Ok(dir.join(filename))
+        }
+        Some(path) if single => Ok(path.to_owned()),
+        Some(path) => bail!(
+            "cannot download several attachments to file {}, use a directory instead",
+            path.display()
+        ),
+    }
+}
+
+#[cfg(all(test, feature = "maildir"))]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use uuid::Uuid;
+
+    use super::AttachmentDownloadCommand;
+    use crate::{
+        config::TomlConfig,
+        fixture::{block_on, envelope_ids, retry_locked, IdMapperDb, MaildirRoot},
+        printer::StdoutPrinter,
+    };
+
+    const MSG: &str = concat!(
+        "Subject: attachments\r\n",
+        "Content-Type: multipart/mixed; boundary=\"mixed\"\r\n",
+        "\r\n",
+        "--mixed\r\n",
+        "Content-Type: text/plain\r\n",
+        "\r\n",
+        "Hello!\r\n",
+        "--mixed\r\n",
+        "Content-Type: application/pdf\r\n",
+        "Content-Disposition: attachment; filename=\"report.pdf\"\r\n",
+        "Content-Transfer-Encoding: base64\r\n",
+        "\r\n",
+        "cmVwb3J0\r\n",
+        "--mixed\r\n",
+        "Content-Type: text/plain; name=\"notes.txt\"\r\n",
+        "Content-Disposition: attachment\r\n",
+        "\r\n",
+        "notes\r\n",
+        "--mixed--\r\n",
+    );
+
+    /// A temporary Maildir++ inbox holding one message with two
+    /// attachments, read from an account of its own.
+    struct Inbox {
+        root: MaildirRoot,
+        config: TomlConfig,
+        _ids: IdMapperDb,
+    }
+
+    impl Inbox {
+        fn new() -> Self {
+            let account = Uuid::new_v4().to_string();
+            let ids = IdMapperDb::new(&account, "INBOX");
+
+            let root = MaildirRoot::new();
+            let dir = root.folder("", &[]);
+            fs::write(dir.join("cur").join("1700000000.test:2,S"), MSG).unwrap();
+
+            let config = toml::from_str(&format!(
+                "[accounts.{account}]\n\
+                 default = true\n\
+                 email = \"me@localhost\"\n\
+                 backend.type = \"maildir\"\n\
+                 backend.root-dir = {root:?}\n\
+                 backend.maildirpp = true\n",
+                root = root.0,
+            ))
+            .unwrap();
+
+            Self {
+                root,
+                config,
+                _ids: ids,
+            }
+        }
+
+        /// Run the download command with the given arguments on the
+        /// message of the inbox, then return the names and the
+        /// contents of the downloaded files.
+        fn download(&self, args: &[&str]) -> Vec<(String, String)> {
+            let dest = self.root.0.join("downloads");
+            fs::create_dir_all(&dest).unwrap();
+
+            self.try_download(args, dest.to_str().unwrap()).unwrap();
+
+            let mut files: Vec<_> = fs::read_dir(dest)
+                .unwrap()
+                .map(|entry| {
+                    let path = entry.unwrap().path();
+                    let name = path.file_name().unwrap().to_str().unwrap().to_owned();
+                    (name, fs::read_to_string(path).unwrap())
+                })
+                .collect();
+            files.sort();
+            files
+        }
+
+        fn try_download(&self, args: &[&str], dest: &str) -> color_eyre::Result<()> {
+            let ids = envelope_ids(&self.config, "INBOX");
+
+            let args: Vec<_> = ["download", "--destination", dest]
+                .iter()
+                .chain(args)
+                .copied()
+                .chain([ids[0].as_str()])
+                .collect();
+            let mut printer = StdoutPrinter::default();
+
+            retry_locked(|| {
+                let cmd = AttachmentDownloadCommand::try_parse_from(&args).unwrap();
+                block_on(cmd.execute(&mut printer, &self.config))
+            })
+        }
+    }
+
+    fn file(name: &str, content: &str) -> (String, String) {
+        (name.to_owned(), content.to_owned())
+    }
+
+    #[test]
+    fn download_all() {
+        let inbox = Inbox::new();
+
+        let files = inbox.download(&[]);
+
+        assert_eq!(
+            files,
+            [file("notes.txt", "notes"), file("report.pdf", "report")]
+        );
+    }
+
+    #[test]
+    fn download_by_index() {
+        let inbox = Inbox::new();
+
+        let files = inbox.download(&["--index", "2"]);
+
+        assert_eq!(files, [file("notes.txt", "notes")]);
+    }
+
+    #[test]
+    fn download_by_name() {
+        let inbox = Inbox::new();
+
+        let files = inbox.download(&["--name", "*.PDF"]);
+
+        assert_eq!(files, [file("report.pdf", "report")]);
+    }
+
+    #[test]
+    fn download_single_attachment_to_file() {
+        let inbox = Inbox::new();
+        let path = inbox.root.0.join("report");
+
+        inbox
+            .try_download(&["--index", "1"], path.to_str().unwrap())
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(path).unwrap(), "report");
+    }
+
+    #[test]
+    fn refuse_unknown_index() {
+        let inbox = Inbox::new();
+        let dest = inbox.root.0.to_str().unwrap();
+
+        let err = inbox.try_download(&["--index", "3"], dest).unwrap_err();
+
+        assert!(err
+            .to_string()
+            .starts_with("cannot find attachment at index 3 in message"));
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use clap::Parser;
+use color_eyre::Result;
+use email::{backend::feature::BackendFeatureSource, config::Config};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
+use tracing::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    envelope::{arg::ids::EnvelopeIdArg, picker},
+    folder::{self, arg::name::FolderNameOptionalFlag},
+    message::attachment::{list, table::AttachmentsTable},
+    printer::Printer,
+};
+
+/// List the attachments of the given message.
+///
+/// This command lists the index, the file name, the content type and
+/// the size of the attachments of the given message, without
+/// downloading them. Inline parts with a file name, like embedded
+/// images, are listed as well.
+#[derive(Debug, Parser)]
+pub struct AttachmentListCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[command(flatten)]
+    pub envelope: EnvelopeIdArg,
+
+    /// The maximum width the table should not exceed.
+    ///
+    /// This argument will force the table not to exceed the given
+    /// width, in pixels. Columns may shrink with ellipsis in order to
+    /// fit the width.
+    ///
+    /// Defaults to the width of the terminal, or to the
+    /// ui.table.piped-width option when the output is not a terminal.
+    #[arg(long = "max-width", short = 'w')]
+    #[arg(name = "table_max_width", value_name = "PIXELS")]
+    pub table_max_width: Option<u16>,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl AttachmentListCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing list attachments command");
+
+        let folder = &self.folder.name;
+
+        let (toml_account_config, account_config) = config
+            .clone()
+            .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
+                c.account(name).ok()
+            })?;
+
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        let toml_account_config = Arc::new(toml_account_config);
+        let account_config = Arc::new(account_config);
+
+        let backend = BackendBuilder::new(
+            toml_account_config.clone(),
+            account_config.clone(),
+            |builder| {
+                builder
+                    .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
+                    .with_peek_messages(BackendFeatureSource::Context)
+            },
+        )
+        .without_sending_backend()
+        .build()
+        .await?;
+
+        let id = picker::id_or_pick(&backend, folder, self.envelope.id).await?;
+        let attachments =
+            list::list(&backend, &toml_account_config, &account_config, folder, id).await?;
+
+        let table = AttachmentsTable::from(attachments)
+            .with_some_width(self.table_max_width)
+            .with_table_config(config.table_config());
+
+        printer.out(table)
+    }
+}
@@ -1,28 +1,32 @@
 mod download;
+mod list;
 
 use clap::Subcommand;
 use color_eyre::Result;
-use pimalaya_tui::terminal::cli::printer::Printer;
 
-use crate::config::TomlConfig;
+use crate::{config::TomlConfig, envelope::picker, printer::Printer};
 
-use self::download::AttachmentDownloadCommand;
+use self::{download::AttachmentDownloadCommand, list::AttachmentListCommand};
 
-/// Download your message attachments.
+/// List and download your message attachments.
 ///
 /// A message body can be composed of multiple MIME parts. An
 /// attachment is the representation of a binary part of a message
 /// body.
 #[derive(Debug, Subcommand)]
 pub enum AttachmentSubcommand {
-    #[command(arg_required_else_help = true, alias = "dl")]
+    #[command(arg_required_else_help = !picker::is_enabled(), alias = "dl")]
     Download(AttachmentDownloadCommand),
+
+    #[command(arg_required_else_help = !picker::is_enabled(), alias = "lst")]
+    List(AttachmentListCommand),
 }
 
 impl AttachmentSubcommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         match self {
             Self::Download(cmd) => cmd.execute(printer, config).await,
+            Self::List(cmd) => cmd.execute(printer, config).await,
         }
     }
 }
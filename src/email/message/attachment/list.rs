@@ -0,0 +1,818 @@
+//! Module dedicated to the listing of attachments.
+//!
+//! Attachments of IMAP messages are listed from their BODYSTRUCTURE,
+//! without fetching their content, then fetched one by one using
+//! their part number. Other backends do not expose the structure of
+//! messages, so the whole message is fetched then parsed.
+//!
+//! Attachments are the leaf parts of a message having a file name or
+//! an attachment disposition. Inline parts with a file name (like
+//! embedded images) are listed as well, marked as inline. Attached
+//! messages are listed as a whole.
+
+use std::sync::Arc;
+
+use color_eyre::{eyre::bail, Result};
+use email::account::config::AccountConfig;
+use mail_parser::{MessagePart, MimeHeaders, PartType};
+use pimalaya_tui::himalaya::backend::Backend;
+use serde::Serialize;
+
+use crate::{account::config::TomlAccountConfig, envelope::size};
+
+/// An attachment of a message.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Attachment {
+    /// The position of the attachment in the message, starting
+    /// from 1.
+    pub index: usize,
+    /// The MIME part number of the attachment, like `2.3`.
+    pub section: String,
+    /// The file name of the attachment, if any.
+    pub filename: Option<String>,
+    /// The content type of the attachment, like `application/pdf`.
+    pub content_type: String,
+    /// The size of the attachment as transferred, in bytes.
+    ///
+    /// Encoded attachments are a third larger than their decoded
+    /// content.
+    pub size: u64,
+    /// Whether the attachment is displayed inline.
+    pub inline: bool,
+    /// The content transfer encoding of the attachment, like
+    /// `base64`.
+    #[serde(skip)]
+    pub encoding: Option<String>,
+}
+
+impl Attachment {
+    /// Return `true` if the file name of the attachment matches the
+    /// given name, case-insensitively.
+    ///
+    /// The name can contain the `*` (any characters) and `?` (any
+    /// character) wildcards.
+    pub fn matches(&self, name: &str) -> bool {
+        let Some(filename) = &self.filename else {
+            return false;
+        };
+
+        let name: Vec<char> = name.to_lowercase().chars().collect();
+        let filename: Vec<char> = filename.to_lowercase().chars().collect();
+
+        glob(&name, &filename)
+    }
+}
+
+/// List the attachments of the message matching the given envelope
+/// id.
+///
+/// The backend must be built with the peek messages feature, which
+/// is used by backends other than IMAP.
+#[cfg_attr(not(feature = "imap"), allow(unused_variables))]
+pub async fn list(
+    backend: &Backend,
+    toml_account_config: &TomlAccountConfig,
+    account_config: &Arc<AccountConfig>,
+    folder: &str,
+    id: usize,
+) -> Result<Vec<Attachment>> {
+    #[cfg(feature = "imap")]
+    if let Some(pimalaya_tui::himalaya::config::Backend::Imap(imap_config)) =
+        &toml_account_config.backend
+    {
+        return imap::list(account_config, imap_config, folder, id).await;
+    }
+
+    let msgs = backend.peek_messages(folder, &[id]).await?;
+
+    let Some(msg) = msgs.first() else {
+        bail!("cannot find message {id}");
+    };
+
+    Ok(from_message(msg.parsed()?))
+}
+
+/// Select the attachments of the message matching the given envelope
+/// id, by index or by name (see [`Attachment::matches`]).
+///
+/// Fails with the list of available attachments when none matches.
+pub fn select(
+    attachments: Vec<Attachment>,
+    id: usize,
+    index: Option<usize>,
+    name: Option<&str>,
+) -> Result<Vec<Attachment>> {
+    if attachments.is_empty() {
+        bail!("cannot find attachment: message {id} has no attachment");
+    }
+
+    let (selected, selector): (Vec<_>, _) = match (index, name) {
+        (Some(index), _) => {
+            let selected = attachments
+                .iter()
+                .filter(|attachment| attachment.index == index)
+                .cloned()
+                .collect();
+            (selected, format!("at index {index}"))
+        }
+        (None, Some(name)) => {
+            let selected = attachments
+                .iter()
+                .filter(|attachment| attachment.matches(name))
+                .cloned()
+                .collect();
+            (selected, format!("matching {name:?}"))
+        }
+        (None, None) => return Ok(attachments),
+    };
+
+    if selected.is_empty() {
+        let available: Vec<_> = attachments
+            .iter()
+            .map(|attachment| {
+                let name = attachment.filename.as_deref().unwrap_or("(no name)");
+                let ctype = &attachment.content_type;
+                let size = size::format(attachment.size);
+                format!("  {}. {name} ({ctype}, {size})", attachment.index)
+            })
+            .collect();
+
+        let available = available.join("\n");
+        bail!("cannot find attachment {selector} in message {id}, available attachments:\n{available}");
+    }
+
+    Ok(selected)
+}
+
+/// Get the decoded content of the given attachment of the message
+/// matching the given envelope id.
+///
+/// IMAP attachments are fetched alone, using their part number. The
+/// backend must be built with the peek messages feature, which is
+/// used by backends other than IMAP.
+#[cfg_attr(not(feature = "imap"), allow(unused_variables))]
+pub async fn get(
+    backend: &Backend,
+    toml_account_config: &TomlAccountConfig,
+    account_config: &Arc<AccountConfig>,
+    folder: &str,
+    id: usize,
+    attachment: &Attachment,
+) -> Result<Vec<u8>> {
+    #[cfg(feature = "imap")]
+    if let Some(pimalaya_tui::himalaya::config::Backend::Imap(imap_config)) =
+        &toml_account_config.backend
+    {
+        return imap::get(account_config, imap_config, folder, id, attachment).await;
+    }
+
+    let msgs = backend.peek_messages(folder, &[id]).await?;
+
+    let Some(msg) = msgs.first() else {
+        bail!("cannot find message {id}");
+    };
+
+    let msg = msg.parsed()?;
+
+    let Some(part) = find_part(msg, &attachment.section) else {
+        bail!("cannot find part {} of message {id}", attachment.section);
+    };
+
+    Ok(part.contents().to_vec())
+}
+
+/// List the attachments of the given parsed message.
+pub fn from_message(msg: &mail_parser::Message) -> Vec<Attachment> {
+    let mut attachments = Vec::new();
+    collect_parsed(msg, 0, "", &mut attachments);
+    attachments
+}
+
+fn collect_parsed(
+    msg: &mail_parser::Message,
+    index: usize,
+    section: &str,
+    attachments: &mut Vec<Attachment>,
+) {
+    let Some(part) = msg.part(index) else {
+        return;
+    };
+
+    if let PartType::Multipart(children) = &part.body {
+        for (i, child) in children.iter().enumerate() {
+            collect_parsed(msg, *child, &subsection(section, i), attachments);
+        }
+        return;
+    }
+
+    let content_type = match part.content_type() {
+        Some(ctype) => match ctype.subtype() {
+            Some(subtype) => format!("{}/{subtype}", ctype.ctype()),
+            None => ctype.ctype().to_owned(),
+        },
+        None => String::from("text/plain"),
+    };
+
+    let disposition = part.content_disposition().map(|d| d.ctype());
+
+    let attachment = Attachment {
+        index: 0,
+        section: leaf_section(section),
+        filename: part.attachment_name().map(ToOwned::to_owned),
+        content_type,
+        size: (part.offset_end - part.offset_body) as u64,
+        inline: false,
+        encoding: part.content_transfer_encoding().map(ToOwned::to_owned),
+    };
+
+    push(
+        attachments,
+        attachment,
+        disposition,
+        part.content_id().is_some(),
+    );
+}
+
+/// Find the part of the given parsed message matching the given part
+/// number.
+fn find_part<'a>(msg: &'a mail_parser::Message, section: &str) -> Option<&'a MessagePart<'a>> {
+    let mut part = msg.part(0)?;
+
+    for n in section.split('.') {
+        let n: usize = n.parse().ok()?;
+
+        part = match &part.body {
+            PartType::Multipart(children) => msg.part(*children.get(n.checked_sub(1)?)?)?,
+            _ if n == 1 => part,
+            _ => return None,
+        };
+    }
+
+    Some(part)
+}
+
+/// Add the given leaf part to the given attachments, if it has a file
+/// name or an attachment disposition.
+///
+/// The index and the inline state of the attachment are computed
+/// here, from its position and from the given disposition.
+fn push(
+    attachments: &mut Vec<Attachment>,
+    mut attachment: Attachment,
+    disposition: Option<&str>,
+    has_content_id: bool,
+) {
+    let is_attachment = disposition.is_some_and(|d| d.eq_ignore_ascii_case("attachment"));
+
+    if attachment.filename.is_none() && !is_attachment {
+        return;
+    }
+
+    attachment.index = attachments.len() + 1;
+    attachment.content_type.make_ascii_lowercase();
+    attachment.inline = match disposition {
+        Some(disposition) => disposition.eq_ignore_ascii_case("inline"),
+        None => has_content_id,
+    };
+
+    attachments.push(attachment);
+}
+
+/// Return the part number of the child at the given position of the
+/// given multipart section.
+fn subsection(section: &str, i: usize) -> String {
+    match section {
+        "" => (i + 1).to_string(),
+        section => format!("{section}.{}", i + 1),
+    }
+}
+
+/// Return the part number of the given leaf section, the body of
+/// single part messages being the part 1.
+fn leaf_section(section: &str) -> String {
+    match section {
+        "" => String::from("1"),
+        section => section.to_owned(),
+    }
+}
+
+/// Return `true` if the given name matches the given pattern, made
+/// of characters and of `*` and `?` wildcards.
+fn glob(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| glob(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && glob(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && glob(rest, &name[1..]),
+    }
+}
+
+#[cfg(feature = "imap")]
+mod imap {
+    use std::{borrow::Cow, num::NonZeroU32, sync::Arc};
+
+    use color_eyre::{eyre::bail, Result};
+    use email::{
+        account::config::AccountConfig, imap::config::ImapConfig, imap::ImapClientBuilder,
+    };
+    use imap_client::{
+        client::tokio::Client,
+        imap_types::{
+            body::{BodyStructure, SpecificFields},
+            core::{IString, Vec1},
+            fetch::{
+                MacroOrMessageDataItemNames, MessageDataItem, MessageDataItemName, Part, Section,
+            },
+            sequence::SequenceSet,
+        },
+    };
+    use mail_parser::{MessageParser, MimeHeaders};
+    use utf7_imap::encode_utf7_imap as encode_utf7;
+
+    use super::Attachment;
+
+    /// List the attachments of the given UID from its BODYSTRUCTURE,
+    /// over a dedicated connection.
+    pub async fn list(
+        account_config: &AccountConfig,
+        config: &ImapConfig,
+        folder: &str,
+        uid: usize,
+    ) -> Result<Vec<Attachment>> {
+        let mut client = examine(account_config, config, folder).await?;
+
+        let items = MacroOrMessageDataItemNames::MessageDataItemNames(vec![
+            MessageDataItemName::BodyStructure,
+        ]);
+
+        let uids = SequenceSet::try_from(uid.to_string().as_str())?;
+        let fetches = client.uid_fetch(uids, items).await?;
+
+        let structure = fetches
+            .values()
+            .flat_map(|items| items.as_ref().iter())
+            .find_map(|item| match item {
+                MessageDataItem::BodyStructure(structure) => Some(structure),
+                _ => None,
+            });
+
+        let Some(structure) = structure else {
+            bail!("cannot find message {uid}");
+        };
+
+        let mut attachments = Vec::new();
+        collect(structure, "", &mut attachments);
+        Ok(attachments)
+    }
+
+    /// Fetch the content of the given attachment of the given UID
+    /// using its part number, over a dedicated connection.
+    pub async fn get(
+        account_config: &AccountConfig,
+        config: &ImapConfig,
+        folder: &str,
+        uid: usize,
+        attachment: &Attachment,
+    ) -> Result<Vec<u8>> {
+        let mut client = examine(account_config, config, folder).await?;
+
+        let part = attachment
+            .section
+            .split('.')
+            .map(str::parse)
+            .collect::<Result<Vec<NonZeroU32>, _>>()?;
+
+        let items =
+            MacroOrMessageDataItemNames::MessageDataItemNames(vec![MessageDataItemName::BodyExt {
+                section: Some(Section::Part(Part(Vec1::try_from(part)?))),
+                partial: None,
+                peek: true,
+            }]);
+
+        let uids = SequenceSet::try_from(uid.to_string().as_str())?;
+        let fetches = client.uid_fetch(uids, items).await?;
+
+        let data = fetches
+            .values()
+            .flat_map(|items| items.as_ref().iter())
+            .find_map(|item| match item {
+                MessageDataItem::BodyExt { data, .. } => data.0.as_ref(),
+                _ => None,
+            });
+
+        let Some(data) = data else {
+            bail!("cannot find part {} of message {uid}", attachment.section);
+        };
+
+        Ok(decode(data.as_ref(), attachment.encoding.as_deref()))
+    }
+
+    /// Connect to the IMAP server, then examine the given folder.
+    async fn examine(
+        account_config: &AccountConfig,
+        config: &ImapConfig,
+        folder: &str,
+    ) -> Result<Client> {
+        let mut client = ImapClientBuilder::new(Arc::new(config.clone()), None)
+            .build()
+            .await?;
+
+        let folder = encode_utf7(account_config.get_folder_alias(folder));
+        client.examine(folder).await?;
+
+        Ok(client)
+    }
+
+    /// Decode the given part content using the given content transfer
+    /// encoding.
+    ///
+    /// The content is given a header to be decoded by the parser.
+    fn decode(data: &[u8], encoding: Option<&str>) -> Vec<u8> {
+        let Some(encoding) = encoding else {
+            return data.to_vec();
+        };
+
+        let mut part = format!(
+            "Content-Type: application/octet-stream\r\n\
+             Content-Transfer-Encoding: {encoding}\r\n\r\n"
+        )
+        .into_bytes();
+        part.extend_from_slice(data);
+
+        match MessageParser::new().parse(&part) {
+            Some(msg) => msg
+                .part(0)
+                .map_or_else(|| data.to_vec(), |part| part.contents().to_vec()),
+            None => data.to_vec(),
+        }
+    }
+
+    fn collect(structure: &BodyStructure, section: &str, attachments: &mut Vec<Attachment>) {
+        let (body, extension_data) = match structure {
+            BodyStructure::Multi { bodies, .. } => {
+                for (i, body) in bodies.as_ref().iter().enumerate() {
+                    collect(body, &super::subsection(section, i), attachments);
+                }
+                return;
+            }
+            BodyStructure::Single {
+                body,
+                extension_data,
+            } => (body, extension_data),
+        };
+
+        let content_type = match &body.specific {
+            SpecificFields::Basic { r#type, subtype } => {
+                format!("{}/{}", to_str(r#type), to_str(subtype))
+            }
+            SpecificFields::Message { .. } => String::from("message/rfc822"),
+            SpecificFields::Text { subtype, .. } => format!("text/{}", to_str(subtype)),
+        };
+
+        let params = body
+            .basic
+            .parameter_list
+            .iter()
+            .map(|(key, val)| (to_str(key), to_str(val)))
+            .collect::<Vec<_>>();
+        let params = params.iter().map(|(key, val)| (key.as_ref(), val.as_ref()));
+
+        let disposition = extension_data
+            .as_ref()
+            .and_then(|data| data.tail.as_ref())
+            .and_then(|tail| tail.disposition.as_ref())
+            .map(|(kind, params)| {
+                let params = params
+                    .iter()
+                    .map(|(key, val)| (to_str(key), to_str(val)))
+                    .collect::<Vec<_>>();
+                (to_str(kind).into_owned(), params)
+            });
+
+        let filename = parse_filename(
+            &to_header(&content_type, params),
+            disposition
+                .as_ref()
+                .map(|(kind, params)| {
+                    let params = params.iter().map(|(key, val)| (key.as_ref(), val.as_ref()));
+                    to_header(kind, params)
+                })
+                .as_deref(),
+        );
+
+        let attachment = Attachment {
+            index: 0,
+            section: super::leaf_section(section),
+            filename,
+            content_type,
+            size: body.basic.size.into(),
+            inline: false,
+            encoding: Some(to_str(&body.basic.content_transfer_encoding).into_owned()),
+        };
+
+        super::push(
+            attachments,
+            attachment,
+            disposition.as_ref().map(|(kind, _)| kind.as_str()),
+            body.basic.id.0.is_some(),
+        );
+    }
+
+    /// Parse the file name of a part from its content type and
+    /// disposition headers.
+    ///
+    /// Parameters are given raw by IMAP servers, so they are turned
+    /// back into headers to be decoded (RFC 2047 and RFC 2231) by the
+    /// parser.
+    pub(super) fn parse_filename(content_type: &str, disposition: Option<&str>) -> Option<String> {
+        let mut headers = format!("Content-Type: {content_type}\r\n");
+
+        if let Some(disposition) = disposition {
+            headers.push_str(&format!("Content-Disposition: {disposition}\r\n"));
+        }
+
+        headers.push_str("\r\n");
+
+        let msg = MessageParser::new().parse_headers(headers.as_bytes())?;
+        msg.attachment_name().map(ToOwned::to_owned)
+    }
+
+    /// Format the given header value followed by the given parameters.
+    fn to_header<'a>(value: &str, params: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+        let mut header = value.to_owned();
+
+        for (key, val) in params {
+            let val = val.replace('\\', "\\\\").replace('"', "\\\"");
+            header.push_str(&format!("; {key}=\"{val}\""));
+        }
+
+        header
+    }
+
+    fn to_str<'a>(string: &'a IString) -> Cow<'a, str> {
+        String::from_utf8_lossy(string.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mail_parser::MessageParser;
+
+    use super::{find_part, from_message, select, Attachment};
+
+    const MSG: &str = concat!(
+        "Subject: attachments\r\n",
+        "Content-Type: multipart/mixed; boundary=\"mixed\"\r\n",
+        "\r\n",
+        "--mixed\r\n",
+        "Content-Type: multipart/related; boundary=\"related\"\r\n",
+        "\r\n",
+        "--related\r\n",
+        "Content-Type: text/html\r\n",
+        "\r\n",
+        "<img src=\"cid:logo\">\r\n",
+        "--related\r\n",
+        "Content-Type: image/PNG; name=\"logo.png\"\r\n",
+        "Content-ID: <logo>\r\n",
+        "Content-Transfer-Encoding: base64\r\n",
+        "\r\n",
+        "aW1hZ2U=\r\n",
+        "--related--\r\n",
+        "--mixed\r\n",
+        "Content-Type: application/pdf\r\n",
+        "Content-Disposition: attachment; filename=\"report.pdf\"\r\n",
+        "Content-Transfer-Encoding: base64\r\n",
+        "\r\n",
+        "cmVwb3J0\r\n",
+        "--mixed\r\n",
+        "Content-Type: text/plain\r\n",
+        "Content-Disposition: attachment\r\n",
+        "\r\n",
+        "notes\r\n",
+        "--mixed\r\n",
+        "Content-Type: message/rfc822\r\n",
+        "Content-Disposition: attachment; filename=\"fwd.eml\"\r\n",
+        "\r\n",
+        "Subject: forwarded\r\n",
+        "Content-Type: multipart/mixed; boundary=\"fwd\"\r\n",
+        "\r\n",
+        "--fwd\r\n",
+        "Content-Type: text/plain; name=\"inner.txt\"\r\n",
+        "\r\n",
+        "inner\r\n",
+        "--fwd--\r\n",
+        "--mixed--\r\n",
+    );
+
+    fn attachment(
+        index: usize,
+        section: &str,
+        filename: Option<&str>,
+        content_type: &str,
+    ) -> Attachment {
+        Attachment {
+            index,
+            section: section.to_owned(),
+            filename: filename.map(ToOwned::to_owned),
+            content_type: content_type.to_owned(),
+            size: 0,
+            inline: false,
+            encoding: None,
+        }
+    }
+
+    #[test]
+    fn list_attachments() {
+        let msg = MessageParser::new().parse(MSG).unwrap();
+
+        let attachments: Vec<_> = from_message(&msg)
+            .into_iter()
+            .map(|attachment| {
+                let Attachment {
+                    index,
+                    section,
+                    filename,
+                    content_type,
+                    inline,
+                    encoding,
+                    ..
+                } = attachment;
+                (index, section, filename, content_type, inline, encoding)
+            })
+            .collect();
+
+        let expected = [
+            (
+                1,
+                "1.2",
+                Some("logo.png"),
+                "image/png",
+                true,
+                Some("base64"),
+            ),
+            (
+                2,
+                "2",
+                Some("report.pdf"),
+                "application/pdf",
+                false,
+                Some("base64"),
+            ),
+            (3, "3", None, "text/plain", false, None),
+            (4, "4", Some("fwd.eml"), "message/rfc822", false, None),
+        ]
+        .map(|(index, section, filename, ctype, inline, encoding)| {
+            (
+                index,
+                section.to_owned(),
+                filename.map(ToOwned::to_owned),
+                ctype.to_owned(),
+                inline,
+                encoding.map(ToOwned::to_owned),
+            )
+        });
+
+        assert_eq!(attachments, expected);
+    }
+
+    #[test]
+    fn list_single_part_attachment() {
+        let msg = concat!(
+            "Subject: single\r\n",
+            "Content-Type: application/pdf; name=\"report.pdf\"\r\n",
+            "\r\n",
+            "report\r\n",
+        );
+        let msg = MessageParser::new().parse(msg).unwrap();
+
+        let attachments = from_message(&msg);
+
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].section, "1");
+        assert_eq!(attachments[0].filename.as_deref(), Some("report.pdf"));
+    }
+
+    #[test]
+    fn list_no_attachment() {
+        let msg = MessageParser::new()
+            .parse("Subject: plain\r\n\r\nHello!\r\n")
+            .unwrap();
+
+        assert!(from_message(&msg).is_empty());
+    }
+
+    #[test]
+    fn match_names() {
+        let report = attachment(1, "1", Some("Report.PDF"), "application/pdf");
+
+        assert!(report.matches("report.pdf"));
+        assert!(report.matches("*.pdf"));
+        assert!(report.matches("rep?rt.*"));
+        assert!(report.matches("*"));
+        assert!(!report.matches("report"));
+        assert!(!report.matches("?report.pdf"));
+        assert!(!report.matches("*.txt"));
+
+        let unnamed = attachment(2, "2", None, "text/plain");
+        assert!(!unnamed.matches("*"));
+    }
+
+    #[cfg(feature = "imap")]
+    #[test]
+    fn parse_imap_filenames() {
+        use super::imap::parse_filename;
+
+        assert_eq!(
+            parse_filename("application/pdf; name=\"report.pdf\"", None).as_deref(),
+            Some("report.pdf")
+        );
+        assert_eq!(
+            parse_filename(
+                "application/pdf",
+                Some("attachment; filename=\"=?utf-8?q?r=C3=A9sum=C3=A9.pdf?=\"")
+            )
+            .as_deref(),
+            Some("résumé.pdf")
+        );
+        assert_eq!(
+            parse_filename(
+                "application/pdf",
+                Some("attachment; filename*=\"utf-8''r%C3%A9sum%C3%A9.pdf\"")
+            )
+            .as_deref(),
+            Some("résumé.pdf")
+        );
+        assert_eq!(parse_filename("text/plain", Some("attachment")), None);
+    }
+
+    fn attachments() -> Vec<Attachment> {
+        vec![
+            attachment(1, "1.2", Some("logo.png"), "image/png"),
+            attachment(2, "2", Some("report.pdf"), "application/pdf"),
+            attachment(3, "3", Some("slides.PDF"), "application/pdf"),
+            attachment(4, "4", None, "text/plain"),
+        ]
+    }
+
+    fn indexes(attachments: Vec<Attachment>) -> Vec<usize> {
+        attachments.iter().map(|a| a.index).collect()
+    }
+
+    #[test]
+    fn select_by_index_or_name() {
+        let all = select(attachments(), 1, None, None).unwrap();
+        assert_eq!(indexes(all), [1, 2, 3, 4]);
+
+        let third = select(attachments(), 1, Some(3), None).unwrap();
+        assert_eq!(indexes(third), [3]);
+
+        let pdfs = select(attachments(), 1, None, Some("*.pdf")).unwrap();
+        assert_eq!(indexes(pdfs), [2, 3]);
+
+        // the index takes precedence over the name
+        let second = select(attachments(), 1, Some(2), Some("logo.png")).unwrap();
+        assert_eq!(indexes(second), [2]);
+    }
+
+    #[test]
+    fn refuse_unknown_attachment() {
+        let err = select(attachments(), 7, Some(5), None).unwrap_err();
+        let expected = "cannot find attachment at index 5 in message 7, available attachments:\n  \
+                        1. logo.png (image/png, 0 B)\n  \
+                        2. report.pdf (application/pdf, 0 B)\n  \
+                        3. slides.PDF (application/pdf, 0 B)\n  \
+                        4. (no name) (text/plain, 0 B)";
+        assert_eq!(err.to_string(), expected);
+
+        let err = select(attachments(), 7, None, Some("*.txt")).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("cannot find attachment matching \"*.txt\" in message 7"));
+
+        let err = select(Vec::new(), 7, Some(1), None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot find attachment: message 7 has no attachment"
+        );
+    }
+
+    #[test]
+    fn find_parts() {
+        let msg = MessageParser::new().parse(MSG).unwrap();
+
+        let contents = |section| find_part(&msg, section).map(|part| part.contents().to_vec());
+
+        assert_eq!(contents("1.2").as_deref(), Some(&b"image"[..]));
+        assert_eq!(contents("2").as_deref(), Some(&b"report"[..]));
+        assert_eq!(contents("3").as_deref(), Some(&b"notes"[..]));
+        assert_eq!(contents("1.3"), None);
+        assert_eq!(contents("5"), None);
+        assert_eq!(contents("2.2"), None);
+        assert_eq!(contents("0"), None);
+
+        // the body of a leaf part is its part 1, like the body of
+        // single part messages
+        assert_eq!(contents("2.1").as_deref(), Some(&b"report"[..]));
+    }
+}
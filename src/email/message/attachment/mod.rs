@@ -1 +1,3 @@
 pub mod command;
+pub mod list;
+pub mod table;
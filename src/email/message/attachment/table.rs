@@ -0,0 +1,85 @@
+use std::fmt;
+
+use comfy_table::{presets, Cell, ContentArrangement, Row, Table};
+use serde::{Serialize, Serializer};
+
+use crate::{
+    envelope::size,
+    message::attachment::list::Attachment,
+    table::{Column, TableConfig},
+};
+
+const COLUMNS: [Column; 5] = [
+    Column::right("index", "INDEX"),
+    Column::left("name", "NAME"),
+    Column::left("type", "TYPE"),
+    Column::right("size", "SIZE"),
+    Column::left("inline", "INLINE"),
+];
+
+/// The printable table of attachments.
+pub struct AttachmentsTable {
+    attachments: Vec<Attachment>,
+    width: Option<u16>,
+    table_config: TableConfig,
+}
+
+impl AttachmentsTable {
+    pub fn with_some_width(mut self, width: Option<u16>) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_table_config(mut self, config: TableConfig) -> Self {
+        self.table_config = config;
+        self
+    }
+}
+
+impl From<Vec<Attachment>> for AttachmentsTable {
+    fn from(attachments: Vec<Attachment>) -> Self {
+        Self {
+            attachments,
+            width: None,
+            table_config: Default::default(),
+        }
+    }
+}
+
+impl fmt::Display for AttachmentsTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Table::new();
+
+        table
+            .load_preset(presets::ASCII_MARKDOWN)
+            .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+            .add_rows(self.attachments.iter().map(|attachment| {
+                let style = |key, cell| self.table_config.style(key, cell);
+
+                let name = attachment.filename.as_deref().unwrap_or_default();
+                let inline = if attachment.inline { "yes" } else { "" };
+
+                let mut row = Row::new();
+                row.max_height(1);
+                row.add_cell(style("index", Cell::new(attachment.index)));
+                row.add_cell(style("name", Cell::new(name)));
+                row.add_cell(style("type", Cell::new(&attachment.content_type)));
+                row.add_cell(style("size", Cell::new(size::format(attachment.size))));
+                row.add_cell(style("inline", Cell::new(inline)));
+                row
+            }));
+
+        self.table_config.apply(&mut table, &COLUMNS, self.width);
+
+        writeln!(f)?;
+        write!(f, "{table}")?;
+        writeln!(f)?;
+        Ok(())
+    }
+}
+
+impl Serialize for AttachmentsTable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.attachments.serialize(serializer)
+    }
+}
@@ -3,15 +3,15 @@ use std::sync::Arc;
 use clap::Parser;
 use color_eyre::Result;
 use email::{backend::feature::BackendFeatureSource, config::Config};
-use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig, envelope::arg::ids::EnvelopeIdsArgs,
-    folder::arg::name::FolderNameOptionalFlag,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    envelope::{arg::ids::EnvelopeIdsArgs, picker},
+    folder::{self, arg::name::FolderNameOptionalFlag, special_use::FolderRole},
+    printer::Printer,
 };
 
 /// Mark as deleted the message associated to the given envelope id(s).
@@ -37,20 +37,30 @@ impl MessageDeleteCommand {
         info!("executing delete message(s) command");
 
         let folder = &self.folder.name;
-        let ids = &self.envelopes.ids;
 
-        let (toml_account_config, account_config) = config
+        let (toml_account_config, mut account_config) = config
             .clone()
             .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
                 c.account(name).ok()
             })?;
 
+        let backend_folder =
+            folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        folder::special_use::add_aliases(
+            &toml_account_config,
+            &mut account_config,
+            &[FolderRole::Trash],
+        )
+        .await;
+
         let backend = BackendBuilder::new(
             Arc::new(toml_account_config),
             Arc::new(account_config),
             |builder| {
                 builder
                     .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
                     .with_delete_messages(BackendFeatureSource::Context)
             },
         )
@@ -58,8 +68,11 @@ impl MessageDeleteCommand {
         .build()
         .await?;
 
-        backend.delete_messages(folder, ids).await?;
+        let ids =
+            &picker::ids_or_pick(&backend, &backend_folder, &self.envelopes.ids, true).await?;
+
+        backend.delete_messages(&backend_folder, ids).await?;
 
-        printer.out(format!("Message(s) successfully removed from {folder}!\n"))
+        printer.info(format!("Message(s) successfully removed from {folder}!\n"))
     }
 }
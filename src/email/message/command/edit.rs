@@ -3,15 +3,16 @@ use std::sync::Arc;
 use clap::Parser;
 use color_eyre::{eyre::eyre, Result};
 use email::{backend::feature::BackendFeatureSource, config::Config};
-use pimalaya_tui::{
-    himalaya::{backend::BackendBuilder, editor},
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig, envelope::arg::ids::EnvelopeIdArg,
-    folder::arg::name::FolderNameOptionalFlag,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    envelope::{arg::ids::EnvelopeIdArg, picker},
+    folder::{self, arg::name::FolderNameOptionalFlag, special_use::FolderRole},
+    message::{editor, send},
+    printer::Printer,
 };
 
 /// Edit the message associated to the given envelope id.
@@ -55,29 +56,42 @@ impl MessageEditCommand {
 
         let folder = &self.folder.name;
 
-        let (toml_account_config, account_config) = config
+        let (toml_account_config, mut account_config) = config
             .clone()
             .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
                 c.account(name).ok()
             })?;
 
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        let extra_config = config.account_extra_config(&account_config.name);
+        folder::special_use::add_aliases(
+            &toml_account_config,
+            &mut account_config,
+            &[FolderRole::Sent, FolderRole::Drafts, FolderRole::Trash],
+        )
+        .await;
+
         let account_config = Arc::new(account_config);
 
         let backend = BackendBuilder::new(
             Arc::new(toml_account_config),
             account_config.clone(),
             |builder| {
-                builder
+                let builder = builder
                     .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
                     .with_add_message(BackendFeatureSource::Context)
-                    .with_send_message(BackendFeatureSource::Context)
-                    .with_delete_messages(BackendFeatureSource::Context)
+                    .with_delete_messages(BackendFeatureSource::Context);
+
+                send::with_send_message(builder, &extra_config)
             },
         )
         .build()
         .await?;
 
-        let id = self.envelope.id;
+        let id = picker::id_or_pick(&backend, folder, self.envelope.id).await?;
         let tpl = backend
             .get_messages(folder, &[id])
             .await?
@@ -92,7 +106,15 @@ impl MessageEditCommand {
             })
             .await?;
 
-        editor::edit_tpl_with_editor(account_config, printer, &backend, tpl).await?;
+        editor::edit_tpl_with_editor(
+            account_config,
+            printer,
+            &backend,
+            tpl,
+            &config.aliases,
+            None,
+        )
+        .await?;
 
         if self.on_place {
             backend.delete_messages(folder, &[id]).await?;
@@ -1,10 +1,4 @@
-use std::{
-    env::temp_dir,
-    fs,
-    io::{stdout, Write},
-    path::PathBuf,
-    sync::Arc,
-};
+use std::{env::temp_dir, fs, path::PathBuf, sync::Arc};
 
 use clap::Parser;
 use color_eyre::{eyre::eyre, Result};
@@ -13,8 +7,11 @@ use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConf
 use tracing::info;
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig, envelope::arg::ids::EnvelopeIdArg,
-    folder::arg::name::FolderNameOptionalFlag,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    envelope::{arg::ids::EnvelopeIdArg, picker},
+    folder::{self, arg::name::FolderNameOptionalFlag},
+    printer::Printer,
 };
 
 /// Export the message associated to the given envelope id.
@@ -59,11 +56,10 @@ pub struct MessageExportCommand {
 }
 
 impl MessageExportCommand {
-    pub async fn execute(self, config: &TomlConfig) -> Result<()> {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing export message command");
 
         let folder = &self.folder.name;
-        let id = &self.envelope.id;
 
         let (toml_account_config, account_config) = config
             .clone()
@@ -71,6 +67,9 @@ impl MessageExportCommand {
                 c.account(name).ok()
             })?;
 
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
         let account_config = Arc::new(account_config);
 
         let backend = BackendBuilder::new(
@@ -79,6 +78,7 @@ impl MessageExportCommand {
             |builder| {
                 builder
                     .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
                     .with_get_messages(BackendFeatureSource::Context)
             },
         )
@@ -86,7 +86,8 @@ impl MessageExportCommand {
         .build()
         .await?;
 
-        let msgs = backend.get_messages(folder, &[*id]).await?;
+        let id = picker::id_or_pick(&backend, folder, self.envelope.id).await?;
+        let msgs = backend.get_messages(folder, &[id]).await?;
         let msg = msgs.first().ok_or(eyre!("cannot find message {id}"))?;
 
         if self.full {
@@ -97,30 +98,30 @@ impl MessageExportCommand {
                     dest.push(format!("{id}.eml"));
                     fs::write(&dest, bytes)?;
                     let dest = dest.display();
-                    println!("Message {id} successfully exported at {dest}!");
+                    printer.info(format!("Message {id} successfully exported at {dest}!\n"))?;
                 }
                 Some(dest) => {
                     fs::write(&dest, bytes)?;
                     let dest = dest.display();
-                    println!("Message {id} successfully exported at {dest}!");
+                    printer.info(format!("Message {id} successfully exported at {dest}!\n"))?;
                 }
                 None => {
-                    stdout().write_all(bytes)?;
+                    printer.out_bytes(bytes)?;
                 }
             };
         } else {
             let dest = match self.destination {
                 Some(dest) if dest.is_dir() => {
-                    let dest = msg.download_parts(&dest)?;
+                    let dest = msg.download_parts(dest)?;
                     let d = dest.display();
-                    println!("Message {id} successfully exported in {d}!");
+                    printer.info(format!("Message {id} successfully exported in {d}!\n"))?;
                     dest
                 }
                 Some(dest) if dest.is_file() => {
                     let dest = dest.parent().unwrap_or(&dest);
-                    let dest = msg.download_parts(&dest)?;
+                    let dest = msg.download_parts(dest)?;
                     let d = dest.display();
-                    println!("Message {id} successfully exported in {d}!");
+                    printer.info(format!("Message {id} successfully exported in {d}!\n"))?;
                     dest
                 }
                 Some(dest) => {
@@ -128,9 +129,9 @@ impl MessageExportCommand {
                 }
                 None => {
                     let dest = temp_dir();
-                    let dest = msg.download_parts(&dest)?;
+                    let dest = msg.download_parts(dest)?;
                     let d = dest.display();
-                    println!("Message {id} successfully exported in {d}!");
+                    printer.info(format!("Message {id} successfully exported in {d}!\n"))?;
                     dest
                 }
             };
@@ -146,7 +147,7 @@ impl MessageExportCommand {
                     return Ok(open::that(plain_txt)?);
                 }
 
-                println!("--open was passed but nothing to open, ignoring");
+                printer.info("--open was passed but nothing to open, ignoring\n")?;
             }
         }
 
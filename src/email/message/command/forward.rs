@@ -2,19 +2,20 @@ use std::sync::Arc;
 
 use clap::Parser;
 use color_eyre::{eyre::eyre, Result};
-use email::{backend::feature::BackendFeatureSource, config::Config};
-use pimalaya_tui::{
-    himalaya::{backend::BackendBuilder, editor},
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use email::{backend::feature::BackendFeatureSource, config::Config, message::Message};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
 use crate::{
     account::arg::name::AccountNameFlag,
     config::TomlConfig,
-    envelope::arg::ids::EnvelopeIdArg,
-    folder::arg::name::FolderNameOptionalFlag,
-    message::arg::{body::MessageRawBodyArg, header::HeaderRawArgs},
+    envelope::{arg::ids::EnvelopeIdArg, picker},
+    folder::{self, arg::name::FolderNameOptionalFlag, special_use::FolderRole},
+    message::{
+        arg::{body::MessageRawBodyArg, header::HeaderRawArgs, mdn::MessageMdnFlags},
+        editor, forward, header, mdn, send,
+    },
+    printer::Printer,
 };
 
 /// Forward the message associated to the given envelope id.
@@ -31,12 +32,23 @@ pub struct MessageForwardCommand {
     #[command(flatten)]
     pub envelope: EnvelopeIdArg,
 
+    /// Forward the message as an attachment.
+    ///
+    /// The message is attached verbatim, keeping its headers,
+    /// signatures and structure, instead of being quoted in the body
+    /// of the new message.
+    #[arg(long)]
+    pub as_attachment: bool,
+
     #[command(flatten)]
     pub headers: HeaderRawArgs,
 
     #[command(flatten)]
     pub body: MessageRawBodyArg,
 
+    #[command(flatten)]
+    pub mdn: MessageMdnFlags,
+
     #[command(flatten)]
     pub account: AccountNameFlag,
 }
@@ -47,38 +59,90 @@ impl MessageForwardCommand {
 
         let folder = &self.folder.name;
 
-        let (toml_account_config, account_config) = config
+        let (toml_account_config, mut account_config) = config
             .clone()
             .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
                 c.account(name).ok()
             })?;
 
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        let extra_config = config.account_extra_config(&account_config.name);
+        folder::special_use::add_aliases(
+            &toml_account_config,
+            &mut account_config,
+            &[FolderRole::Sent, FolderRole::Drafts],
+        )
+        .await;
+
         let account_config = Arc::new(account_config);
 
         let backend = BackendBuilder::new(
             Arc::new(toml_account_config),
             account_config.clone(),
             |builder| {
-                builder
+                let builder = builder
                     .without_features()
-                    .with_add_message(BackendFeatureSource::Context)
-                    .with_send_message(BackendFeatureSource::Context)
+                    .with_list_envelopes(BackendFeatureSource::Context)
+                    .with_add_message(BackendFeatureSource::Context);
+
+                send::with_send_message(builder, &extra_config)
             },
         )
         .build()
         .await?;
 
-        let id = self.envelope.id;
-        let tpl = backend
-            .get_messages(folder, &[id])
-            .await?
-            .first()
-            .ok_or(eyre!("cannot find message"))?
-            .to_forward_tpl_builder(account_config.clone())
-            .with_headers(self.headers.raw)
-            .with_body(self.body.raw())
-            .build()
-            .await?;
-        editor::edit_tpl_with_editor(account_config, printer, &backend, tpl).await
+        let id = picker::id_or_pick(&backend, folder, self.envelope.id).await?;
+        let msgs = backend.get_messages(folder, &[id]).await?;
+        let msg = msgs.first().ok_or(eyre!("cannot find message"))?;
+
+        let copied_headers = header::collect(msg, extra_config.message_forward_copy_headers())?;
+
+        let mut attachment = None;
+
+        let mut tpl = if self.as_attachment {
+            let subject = self
+                .headers
+                .raw
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case("Subject"))
+                .map(|(_, val)| val.clone());
+
+            let mut tpl = Message::new_tpl_builder(account_config.clone())
+                .with_headers(self.headers.raw)
+                .with_body(self.body.raw())
+                .build()
+                .await?;
+
+            attachment = Some(forward::attach(&mut tpl, msg, subject.as_deref())?);
+            tpl
+        } else {
+            msg.to_forward_tpl_builder(account_config.clone())
+                .with_headers(self.headers.raw)
+                .with_body(self.body.raw())
+                .build()
+                .await?
+        };
+
+        header::insert(&mut tpl, &copied_headers);
+
+        if let Some(hostname) = extra_config.smtp_helo_hostname() {
+            header::insert_message_id(&mut tpl, hostname.as_str());
+        }
+
+        if self.mdn.is_requested(extra_config.is_mdn_requested()) {
+            mdn::request(&mut tpl, &account_config.email);
+        }
+
+        editor::edit_tpl_with_editor(
+            account_config,
+            printer,
+            &backend,
+            tpl,
+            &config.aliases,
+            attachment.as_ref(),
+        )
+        .await
     }
 }
@@ -3,14 +3,17 @@ use std::sync::Arc;
 use clap::Parser;
 use color_eyre::Result;
 use email::{backend::feature::BackendFeatureSource, config::Config};
-use pimalaya_tui::{
-    himalaya::{backend::BackendBuilder, editor},
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 use url::Url;
 
-use crate::{account::arg::name::AccountNameFlag, config::TomlConfig};
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    folder::{self, special_use::FolderRole},
+    message::editor,
+    printer::Printer,
+};
 
 /// Parse and edit a message from the given mailto URL string.
 ///
@@ -39,12 +42,19 @@ impl MessageMailtoCommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing mailto message command");
 
-        let (toml_account_config, account_config) = config
+        let (toml_account_config, mut account_config) = config
             .clone()
             .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
                 c.account(name).ok()
             })?;
 
+        folder::special_use::add_aliases(
+            &toml_account_config,
+            &mut account_config,
+            &[FolderRole::Sent, FolderRole::Drafts],
+        )
+        .await;
+
         let account_config = Arc::new(account_config);
 
         let backend = BackendBuilder::new(
@@ -93,6 +103,14 @@ impl MessageMailtoCommand {
             .await?
             .into();
 
-        editor::edit_tpl_with_editor(account_config, printer, &backend, tpl).await
+        editor::edit_tpl_with_editor(
+            account_config,
+            printer,
+            &backend,
+            tpl,
+            &config.aliases,
+            None,
+        )
+        .await
     }
 }
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use clap::Parser;
+use color_eyre::{eyre::bail, Result};
+use email::{
+    backend::feature::BackendFeatureSource,
+    config::Config,
+    envelope::{get::GetEnvelope, SingleId},
+    flag::Flag,
+    message::send::SendMessage,
+};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
+use tracing::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    envelope::{arg::ids::EnvelopeIdArg, picker},
+    folder::{self, arg::name::FolderNameOptionalFlag},
+    message::{mdn, send},
+    printer::Printer,
+};
+
+/// Send the read receipt requested by the given message.
+///
+/// This command sends a message disposition notification to the
+/// address given in the Disposition-Notification-To header of the
+/// message, stating that the message has been displayed. The message
+/// is then marked, so that its read receipt cannot be sent twice.
+/// Read receipts are never sent automatically.
+#[derive(Debug, Parser)]
+pub struct MessageMdnCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[command(flatten)]
+    pub envelope: EnvelopeIdArg,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl MessageMdnCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing send read receipt command");
+
+        let folder = &self.folder.name;
+
+        let (toml_account_config, account_config) = config
+            .clone()
+            .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
+                c.account(name).ok()
+            })?;
+
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        let extra_config = config.account_extra_config(&account_config.name);
+        let toml_account_config = Arc::new(toml_account_config);
+        let account_config = Arc::new(account_config);
+
+        let backend = BackendBuilder::new(
+            toml_account_config.clone(),
+            account_config.clone(),
+            |builder| {
+                let builder = builder
+                    .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
+                    .with_peek_messages(BackendFeatureSource::Context)
+                    .with_get_envelope(BackendFeatureSource::Context)
+                    .with_add_flags(BackendFeatureSource::Context);
+
+                send::with_send_message(builder, &extra_config)
+            },
+        )
+        .build()
+        .await?;
+
+        let id = picker::id_or_pick(&backend, folder, self.envelope.id).await?;
+        let keyword_id = mdn::keyword_id(&toml_account_config, &account_config, folder, id)?;
+
+        let msgs = backend.peek_messages(folder, &[id]).await?;
+
+        let Some(msg) = msgs.first() else {
+            bail!("cannot find message {id}");
+        };
+
+        let msg = msg.parsed()?;
+
+        let Some(to) = mdn::requested_by(msg) else {
+            bail!("message {id} does not request a read receipt");
+        };
+
+        let msg_id = msg.message_id();
+
+        let sent = match (&keyword_id, msg_id) {
+            (Some(keyword_id), _) => {
+                let envelope = backend
+                    .get_envelope(folder, &SingleId::from(keyword_id.clone()))
+                    .await?;
+                envelope.flags.contains(&Flag::custom(mdn::MDN_SENT))
+            }
+            (None, Some(msg_id)) => mdn::is_recorded(&account_config.name, msg_id)?,
+            (None, None) => {
+                bail!("cannot keep track of the read receipt of message {id}: missing Message-ID")
+            }
+        };
+
+        if sent {
+            bail!("read receipt of message {id} already sent");
+        }
+
+        let mdn = mdn::build(&account_config, msg, &to);
+        backend.send_message(&mdn).await?;
+
+        match (keyword_id, msg_id) {
+            (Some(_), _) => {
+                let flag = Flag::custom(mdn::MDN_SENT);
+                backend.add_flag(folder, &[id], flag).await?;
+            }
+            (None, Some(msg_id)) => mdn::record(&account_config.name, msg_id)?,
+            (None, None) => (),
+        }
+
+        printer.info(format!("Read receipt successfully sent to {to}!"))
+    }
+}
@@ -4,6 +4,7 @@ pub mod edit;
 pub mod export;
 pub mod forward;
 pub mod mailto;
+pub mod mdn;
 pub mod r#move;
 pub mod read;
 pub mod reply;
@@ -14,16 +15,15 @@ pub mod write;
 
 use clap::Subcommand;
 use color_eyre::Result;
-use pimalaya_tui::terminal::cli::printer::Printer;
 
-use crate::config::TomlConfig;
+use crate::{config::TomlConfig, envelope::picker, printer::Printer};
 
 use self::{
     copy::MessageCopyCommand, delete::MessageDeleteCommand, edit::MessageEditCommand,
     export::MessageExportCommand, forward::MessageForwardCommand, mailto::MessageMailtoCommand,
-    r#move::MessageMoveCommand, read::MessageReadCommand, reply::MessageReplyCommand,
-    save::MessageSaveCommand, send::MessageSendCommand, thread::MessageThreadCommand,
-    write::MessageWriteCommand,
+    mdn::MessageMdnCommand, r#move::MessageMoveCommand, read::MessageReadCommand,
+    reply::MessageReplyCommand, save::MessageSaveCommand, send::MessageSendCommand,
+    thread::MessageThreadCommand, write::MessageWriteCommand,
 };
 
 /// Read, write, send, copy, move and delete your messages.
@@ -34,13 +34,13 @@ use self::{
 /// subcommand allows you to manage them.
 #[derive(Debug, Subcommand)]
 pub enum MessageSubcommand {
-    #[command(arg_required_else_help = true)]
+    #[command(arg_required_else_help = !picker::is_enabled())]
     Read(MessageReadCommand),
 
-    #[command(arg_required_else_help = true)]
+    #[command(arg_required_else_help = !picker::is_enabled())]
     Export(MessageExportCommand),
 
-    #[command(arg_required_else_help = true)]
+    #[command(arg_required_else_help = !picker::is_enabled())]
     Thread(MessageThreadCommand),
 
     #[command(aliases = ["add", "create", "new", "compose"])]
@@ -59,6 +59,9 @@ pub enum MessageSubcommand {
 
     Send(MessageSendCommand),
 
+    #[command(arg_required_else_help = !picker::is_enabled())]
+    Mdn(MessageMdnCommand),
+
     #[command(arg_required_else_help = true)]
     #[command(aliases = ["cpy", "cp"])]
     Copy(MessageCopyCommand),
@@ -67,7 +70,7 @@ pub enum MessageSubcommand {
     #[command(alias = "mv")]
     Move(MessageMoveCommand),
 
-    #[command(arg_required_else_help = true)]
+    #[command(arg_required_else_help = !picker::is_enabled())]
     #[command(aliases = ["remove", "rm"])]
     Delete(MessageDeleteCommand),
 }
@@ -77,7 +80,7 @@ impl MessageSubcommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         match self {
             Self::Read(cmd) => cmd.execute(printer, config).await,
-            Self::Export(cmd) => cmd.execute(config).await,
+            Self::Export(cmd) => cmd.execute(printer, config).await,
             Self::Thread(cmd) => cmd.execute(printer, config).await,
             Self::Write(cmd) => cmd.execute(printer, config).await,
             Self::Reply(cmd) => cmd.execute(printer, config).await,
@@ -86,6 +89,7 @@ impl MessageSubcommand {
             Self::Mailto(cmd) => cmd.execute(printer, config).await,
             Self::Save(cmd) => cmd.execute(printer, config).await,
             Self::Send(cmd) => cmd.execute(printer, config).await,
+            Self::Mdn(cmd) => cmd.execute(printer, config).await,
             Self::Copy(cmd) => cmd.execute(printer, config).await,
             Self::Move(cmd) => cmd.execute(printer, config).await,
             Self::Delete(cmd) => cmd.execute(printer, config).await,
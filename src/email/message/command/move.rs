@@ -3,18 +3,19 @@ use std::sync::Arc;
 use clap::Parser;
 use color_eyre::Result;
 use email::{backend::feature::BackendFeatureSource, config::Config};
-use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
 #[allow(unused)]
 use crate::{
     account::arg::name::AccountNameFlag,
     config::TomlConfig,
-    envelope::arg::ids::EnvelopeIdsArgs,
-    folder::arg::name::{SourceFolderNameOptionalFlag, TargetFolderNameArg},
+    envelope::{arg::ids::EnvelopeIdsArgs, picker},
+    folder::{
+        self,
+        arg::name::{SourceFolderNameOptionalFlag, TargetFolderNameArg},
+    },
+    printer::Printer,
 };
 
 /// Move the message associated to the given envelope id(s) to the
@@ -40,7 +41,6 @@ impl MessageMoveCommand {
 
         let source = &self.source_folder.name;
         let target = &self.target_folder.name;
-        let ids = &self.envelopes.ids;
 
         let (toml_account_config, account_config) = config
             .clone()
@@ -48,12 +48,21 @@ impl MessageMoveCommand {
                 c.account(name).ok()
             })?;
 
+        let folders = folder::resolve_many(
+            config,
+            &toml_account_config,
+            &account_config,
+            &[source, target],
+        )
+        .await?;
+
         let backend = BackendBuilder::new(
             Arc::new(toml_account_config),
             Arc::new(account_config),
             |builder| {
                 builder
                     .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
                     .with_move_messages(BackendFeatureSource::Context)
             },
         )
@@ -61,9 +70,11 @@ impl MessageMoveCommand {
         .build()
         .await?;
 
-        backend.move_messages(source, target, ids).await?;
+        let ids = &picker::ids_or_pick(&backend, &folders[0], &self.envelopes.ids, true).await?;
+
+        backend.move_messages(&folders[0], &folders[1], ids).await?;
 
-        printer.out(format!(
+        printer.info(format!(
             "Message(s) successfully moved from {source} to {target}!\n"
         ))
     }
@@ -1,26 +1,45 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use clap::Parser;
-use color_eyre::Result;
-use email::{backend::feature::BackendFeatureSource, config::Config};
-use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
+use color_eyre::{eyre::bail, Result};
+#[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+use email::message::Message;
+use email::{
+    backend::feature::BackendFeatureSource,
+    config::Config,
+    flag::{Flag, Flags},
 };
+use mml::message::FilterParts;
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
+#[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+use crate::message::pgp;
 #[allow(unused)]
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig, envelope::arg::ids::EnvelopeIdsArgs,
-    folder::arg::name::FolderNameOptionalFlag,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    envelope::{arg::ids::EnvelopeIdsArgs, picker},
+    flag,
+    folder::{self, arg::name::FolderNameOptionalFlag},
+    message::{html, mdn},
+    printer::Printer,
 };
 
 /// Read a human-friendly version of the message associated to the
 /// given envelope id(s).
 ///
 /// This command allows you to read a message. When reading a message,
-/// the "seen" flag is automatically applied to the corresponding
-/// envelope. To prevent this behaviour, use the "--preview" flag.
+/// the "seen" flag is applied to the corresponding envelope, unless
+/// the message.read.mark-seen option of the account is disabled. To
+/// override this behaviour, use the "--peek" or "--no-peek" flag.
+///
+/// Encrypted messages are decrypted, and the signature of signed
+/// messages is verified, using the PGP configuration of the account.
+///
+/// When a message requests a read receipt, it is mentioned above the
+/// message. The receipt is never sent automatically, use the message
+/// mdn command to send it.
 #[derive(Debug, Parser)]
 pub struct MessageReadCommand {
     #[command(flatten)]
@@ -31,9 +50,19 @@ pub struct MessageReadCommand {
 
     /// Read the message without applying the "seen" flag to its
     /// corresponding envelope.
-    #[arg(long, short)]
+    ///
+    /// Flags are left untouched, whatever the message.read.mark-seen
+    /// option of the account.
+    #[arg(long, short, visible_alias = "peek")]
+    #[arg(conflicts_with = "no_peek")]
     pub preview: bool,
 
+    /// Read the message and apply the "seen" flag to its
+    /// corresponding envelope, whatever the message.read.mark-seen
+    /// option of the account.
+    #[arg(long)]
+    pub no_peek: bool,
+
     /// Read only the body of the message.
     ///
     /// All headers will be removed from the message.
@@ -44,13 +73,85 @@ pub struct MessageReadCommand {
     /// List of headers that should be visible at the top of the
     /// message.
     ///
-    /// If a given header is not found in the message, it will not be
-    /// visible. If no header is given, defaults to the one set up in
-    /// your TOML configuration file.
+    /// Header names are case-insensitive. If a given header is not
+    /// found in the message, it will not be visible. If no header is
+    /// given, defaults to the one set up in your TOML configuration
+    /// file.
     #[arg(long = "header", short = 'H', value_name = "NAME")]
     #[arg(conflicts_with = "no_headers")]
     pub headers: Vec<String>,
 
+    /// Show all the headers of the message.
+    #[arg(long)]
+    #[arg(conflicts_with_all = ["no_headers", "headers"])]
+    pub all_headers: bool,
+
+    /// Show the source of HTML parts instead of rendering them as
+    /// plain text.
+    ///
+    /// By default, messages without plain text part are read from
+    /// their HTML part, rendered as plain text.
+    #[arg(long)]
+    #[arg(conflicts_with = "raw")]
+    pub raw_html: bool,
+
+    /// Print the raw source of the message.
+    ///
+    /// The message is printed as it is on the backend: not decoded
+    /// nor decrypted. The output can be redirected into a .eml file.
+    /// When using the JSON output, the source is printed as a JSON
+    /// string.
+    #[arg(long, short = 'r')]
+    #[arg(conflicts_with_all = ["no_headers", "headers", "all_headers", "serve", "open_html"])]
+    pub raw: bool,
+
+    /// Print the message as an MML template.
+    ///
+    /// All the parts of the message are shown, using MML markup for
+    /// multiparts and attachments, so that the output can be edited
+    /// then sent again using the template send command. Attachments
+    /// refer to files of the downloads directory, use the attachment
+    /// download command to save them there.
+    #[arg(long)]
+    #[arg(conflicts_with_all = ["raw", "raw_html", "serve", "open_html"])]
+    pub mml: bool,
+
+    /// Serve the HTML version of the message on localhost.
+    ///
+    /// The HTML part of the message is served on 127.0.0.1, with
+    /// embedded images inlined and remote content blocked. The
+    /// server stops after the first successful request, or after
+    /// the idle timeout. Useful to read HTML messages from a remote
+    /// shell, using port forwarding. The HTML is not sanitized: its
+    /// scripts are only blocked by the content security policy sent
+    /// along, which the browser enforces.
+    #[arg(long)]
+    pub serve: bool,
+
+    /// Port the preview server should listen to.
+    ///
+    /// Defaults to a random available port.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    #[arg(requires = "serve")]
+    pub port: u16,
+
+    /// Number of seconds the preview server waits for a request
+    /// before shutting down.
+    #[arg(long, value_name = "SECS", default_value_t = 300)]
+    #[arg(requires = "serve")]
+    pub idle_timeout: u64,
+
+    /// Open the HTML version of the message in the browser.
+    ///
+    /// The HTML part of the message is written to the cache
+    /// directory, under himalaya/html/<account>/<folder>/<id>, along
+    /// with its embedded images. It is then opened with the default
+    /// application of the system. Remote content is not blocked,
+    /// opening the message may load it.
+    #[arg(long)]
+    #[arg(conflicts_with = "serve")]
+    pub open_html: bool,
+
     #[command(flatten)]
     pub account: AccountNameFlag,
 }
@@ -62,52 +163,182 @@ impl MessageReadCommand {
         let folder = &self.folder.name;
         let ids = &self.envelopes.ids;
 
+        if self.serve && ids.len() > 1 {
+            bail!("cannot serve more than one message at a time");
+        }
+
+        if self.open_html && ids.len() > 1 {
+            bail!("cannot open more than one message at a time");
+        }
+
+        if self.raw && ids.len() > 1 {
+            bail!("cannot print the raw source of more than one message at a time");
+        }
+
         let (toml_account_config, account_config) = config
             .clone()
             .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
                 c.account(name).ok()
             })?;
 
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        let mark_seen = if self.preview {
+            false
+        } else {
+            self.no_peek
+                || config
+                    .account_extra_config(&account_config.name)
+                    .is_message_read_mark_seen_enabled()
+        };
+
         let account_config = Arc::new(account_config);
 
+        let toml_account_config = Arc::new(toml_account_config);
+
         let backend = BackendBuilder::new(
-            Arc::new(toml_account_config),
+            toml_account_config.clone(),
             account_config.clone(),
             |builder| {
                 builder
                     .without_features()
-                    .with_get_messages(BackendFeatureSource::Context)
+                    .with_list_envelopes(BackendFeatureSource::Context)
                     .with_peek_messages(BackendFeatureSource::Context)
+                    .with_add_flags(BackendFeatureSource::Context)
             },
         )
         .without_sending_backend()
         .build()
         .await?;
 
-        let emails = if self.preview {
-            backend.peek_messages(folder, ids).await
-        } else {
-            backend.get_messages(folder, ids).await
-        }?;
+        let ids = &picker::ids_or_pick(
+            &backend,
+            folder,
+            ids,
+            !self.serve && !self.raw && !self.open_html,
+        )
+        .await?;
+
+        // messages are always peeked, then explicitly marked as seen,
+        // so that all backends behave the same
+        let emails = backend.peek_messages(folder, ids).await?;
+
+        if mark_seen {
+            let flags = Flags::from_iter([Flag::Seen]);
+            flag::add(&backend, &toml_account_config, folder, ids, &flags).await?;
+        }
+
+        if self.raw {
+            let Some(email) = emails.first() else {
+                bail!("cannot find message {}", ids[0]);
+            };
+
+            let raw = email.raw()?;
+
+            if printer.is_json() {
+                return printer.out(String::from_utf8_lossy(raw));
+            }
+
+            return printer.out_bytes(raw);
+        }
+
+        if self.serve {
+            let Some(email) = emails.first() else {
+                bail!("cannot find message {}", ids[0]);
+            };
+
+            let html = html::to_inlined_html(email)?;
+            let idle = Duration::from_secs(self.idle_timeout);
+            return html::serve(printer, html, self.port, idle).await;
+        }
+
+        if self.open_html {
+            let Some(email) = emails.first() else {
+                bail!("cannot find message {}", ids[0]);
+            };
+
+            let dir = html::cache_dir(&account_config.name, folder, ids[0])?;
+            let path = html::write_to_dir(email, &dir)?;
+
+            printer.log("Remote content is not blocked, opening the message may load it.\n")?;
+            open::that(&path)?;
+
+            let path = path.display();
+            return printer.info(format!("Message {} opened from {path}!\n", ids[0]));
+        }
 
         let mut glue = "";
         let mut bodies = String::default();
 
-        for email in emails.to_vec() {
+        for (id, email) in ids.iter().zip(emails.to_vec()) {
             bodies.push_str(glue);
 
+            #[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+            let (decrypted, status) = pgp::read(email.raw()?, &account_config).await?;
+            #[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+            let decrypted = decrypted.map(Message::from);
+            #[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+            let email = decrypted.as_ref().unwrap_or(email);
+
+            #[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+            if let Some(status) = status.filter(|_| !self.mml) {
+                bodies.push_str(&status);
+                bodies.push_str("\n\n");
+            }
+
+            if !self.no_headers && !self.mml {
+                if let Some(addr) = mdn::requested_by(email.parsed()?) {
+                    bodies.push_str(&format!("A read receipt was requested by {addr}, "));
+                    bodies.push_str(&format!("use `message mdn {id}` to send it."));
+                    bodies.push_str("\n\n");
+                }
+            }
+
+            let text = if self.raw_html || self.mml {
+                None
+            } else {
+                html::to_text_fallback(email)?
+            };
+
             let tpl = email
                 .to_read_tpl(&account_config, |mut tpl| {
                     if self.no_headers {
                         tpl = tpl.with_hide_all_headers();
+                    } else if self.all_headers {
+                        tpl = tpl.with_show_all_headers();
                     } else if !self.headers.is_empty() {
                         tpl = tpl.with_show_only_headers(&self.headers);
                     }
 
+                    if self.mml {
+                        tpl = tpl
+                            .with_show_multiparts(true)
+                            .with_filter_parts(FilterParts::All)
+                            .with_show_attachments(true)
+                            .with_show_inline_attachments(true);
+                    } else if self.raw_html {
+                        tpl = tpl.with_filter_parts(FilterParts::Only("text/html".into()));
+                    } else if text.is_some() {
+                        // the HTML part is rendered below
+                        tpl = tpl.with_filter_parts(FilterParts::Only("text/plain".into()));
+                    }
+
                     tpl
                 })
                 .await?;
-            bodies.push_str(&tpl);
+
+            match text {
+                Some(text) => {
+                    let headers = tpl.trim_end();
+                    if !headers.is_empty() {
+                        bodies.push_str(headers);
+                        bodies.push_str("\n\n");
+                    }
+                    bodies.push_str(&text);
+                }
+                None => bodies.push_str(&tpl),
+            }
 
             glue = "\n\n";
         }
@@ -115,3 +346,140 @@ impl MessageReadCommand {
         printer.out(bodies)
     }
 }
+
+#[cfg(all(test, feature = "maildir"))]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use uuid::Uuid;
+
+    use super::MessageReadCommand;
+    use crate::{
+        config::TomlConfig,
+        fixture::{block_on, envelope_ids, retry_locked, IdMapperDb, MaildirRoot},
+        printer::StdoutPrinter,
+    };
+
+    /// A temporary Maildir++ inbox holding one unseen message, read
+    /// from an account of its own.
+    struct Inbox {
+        root: MaildirRoot,
+        account: String,
+        _ids: IdMapperDb,
+    }
+
+    impl Inbox {
+        fn new() -> Self {
+            let account = Uuid::new_v4().to_string();
+            let ids = IdMapperDb::new(&account, "INBOX");
+
+            let root = MaildirRoot::new();
+            let dir = root.folder("", &[]);
+
+            let msg =
+                "From: alice@localhost\r\nTo: me@localhost\r\nSubject: test\r\n\r\nHello!\r\n";
+            fs::write(dir.join("cur").join("1700000000.test:2,"), msg).unwrap();
+
+            Self {
+                root,
+                account,
+                _ids: ids,
+            }
+        }
+
+        /// Read the configuration of an account using the inbox, with
+        /// the given extra options.
+        fn config(&self, options: &str) -> TomlConfig {
+            toml::from_str(&format!(
+                "[accounts.{name}]\n\
+                 default = true\n\
+                 email = \"me@localhost\"\n\
+                 backend.type = \"maildir\"\n\
+                 backend.root-dir = {root:?}\n\
+                 backend.maildirpp = true\n\
+                 {options}\n",
+                name = self.account,
+                root = self.root.0,
+            ))
+            .unwrap()
+        }
+
+        /// Run the read command with the given arguments on the
+        /// message of the inbox, then return the printed message.
+        ///
+        /// Listing envelopes creates the alias of the message.
+        fn read(&self, config: &TomlConfig, args: &[&str]) -> String {
+            let ids = envelope_ids(config, "INBOX");
+
+            let args: Vec<_> = ["read"]
+                .iter()
+                .chain(args)
+                .copied()
+                .chain([ids[0].as_str()])
+                .collect();
+            let output = self.root.0.join("output");
+            let mut printer = StdoutPrinter::default().with_some_output_file(Some(output.clone()));
+
+            retry_locked(|| {
+                let cmd = MessageReadCommand::try_parse_from(&args).unwrap();
+                block_on(cmd.execute(&mut printer, config))
+            })
+            .unwrap();
+
+            printer.flush().unwrap();
+
+            fs::read_to_string(output).unwrap()
+        }
+
+        fn is_seen(&self) -> bool {
+            let entry = fs::read_dir(self.root.0.join("cur"))
+                .unwrap()
+                .next()
+                .unwrap();
+            let name = entry.unwrap().file_name().into_string().unwrap();
+            name.rsplit_once(":2,").unwrap().1.contains('S')
+        }
+    }
+
+    #[test]
+    fn mark_seen_by_default() {
+        let inbox = Inbox::new();
+        let config = inbox.config("");
+
+        let msg = inbox.read(&config, &[]);
+
+        assert!(msg.contains("Hello!"), "{msg}");
+        assert!(inbox.is_seen());
+    }
+
+    #[test]
+    fn peek() {
+        let inbox = Inbox::new();
+        let config = inbox.config("");
+
+        let msg = inbox.read(&config, &["--peek"]);
+
+        assert!(msg.contains("Hello!"), "{msg}");
+        assert!(!inbox.is_seen());
+    }
+
+    #[test]
+    fn disable_mark_seen() {
+        let inbox = Inbox::new();
+        let config = inbox.config("message.read.mark-seen = false");
+
+        let msg = inbox.read(&config, &[]);
+        assert!(msg.contains("Hello!"), "{msg}");
+        assert!(!inbox.is_seen());
+
+        inbox.read(&config, &["--no-peek"]);
+        assert!(inbox.is_seen());
+    }
+
+    #[test]
+    fn refuse_peek_and_no_peek() {
+        let args = ["read", "--peek", "--no-peek", "1"];
+        assert!(MessageReadCommand::try_parse_from(args).is_err());
+    }
+}
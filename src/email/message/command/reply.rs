@@ -2,19 +2,30 @@ use std::sync::Arc;
 
 use clap::Parser;
 use color_eyre::{eyre::eyre, Result};
-use email::{backend::feature::BackendFeatureSource, config::Config, flag::Flag};
-use pimalaya_tui::{
-    himalaya::{backend::BackendBuilder, editor},
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
+use email::{
+    backend::feature::BackendFeatureSource,
+    config::Config,
+    flag::{Flag, Flags},
 };
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
 use crate::{
     account::arg::name::AccountNameFlag,
     config::TomlConfig,
-    envelope::arg::ids::EnvelopeIdArg,
-    folder::arg::name::FolderNameOptionalFlag,
-    message::arg::{body::MessageRawBodyArg, header::HeaderRawArgs, reply::MessageReplyAllArg},
+    envelope::{arg::ids::EnvelopeIdArg, picker},
+    flag,
+    folder::{self, arg::name::FolderNameOptionalFlag, special_use::FolderRole},
+    message::{
+        arg::{
+            body::MessageRawBodyArg, header::HeaderRawArgs, mdn::MessageMdnFlags,
+            reply::MessageReplyAllArg,
+        },
+        editor, header, mdn,
+        reply::{self, Recipients},
+        send,
+    },
+    printer::Printer,
 };
 
 /// Reply to the message associated to the given envelope id.
@@ -40,6 +51,9 @@ pub struct MessageReplyCommand {
     #[command(flatten)]
     pub body: MessageRawBodyArg,
 
+    #[command(flatten)]
+    pub mdn: MessageMdnFlags,
+
     #[command(flatten)]
     pub account: AccountNameFlag,
 }
@@ -49,33 +63,49 @@ impl MessageReplyCommand {
         info!("executing reply message command");
 
         let folder = &self.folder.name;
-        let (toml_account_config, account_config) = config
+        let (toml_account_config, mut account_config) = config
             .clone()
             .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
                 c.account(name).ok()
             })?;
 
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        let extra_config = config.account_extra_config(&account_config.name);
+        folder::special_use::add_aliases(
+            &toml_account_config,
+            &mut account_config,
+            &[FolderRole::Sent, FolderRole::Drafts],
+        )
+        .await;
+
         let account_config = Arc::new(account_config);
 
+        let toml_account_config = Arc::new(toml_account_config);
+
         let backend = BackendBuilder::new(
-            Arc::new(toml_account_config),
+            toml_account_config.clone(),
             account_config.clone(),
             |builder| {
-                builder
+                let builder = builder
                     .without_features()
-                    .with_add_message(BackendFeatureSource::Context)
-                    .with_send_message(BackendFeatureSource::Context)
+                    .with_list_envelopes(BackendFeatureSource::Context)
+                    .with_add_message(BackendFeatureSource::Context);
+
+                send::with_send_message(builder, &extra_config)
             },
         )
         .build()
         .await?;
 
-        let id = self.envelope.id;
-        let tpl = backend
-            .get_messages(folder, &[id])
-            .await?
-            .first()
-            .ok_or(eyre!("cannot find message {id}"))?
+        let id = picker::id_or_pick(&backend, folder, self.envelope.id).await?;
+        let msgs = backend.get_messages(folder, &[id]).await?;
+        let msg = msgs.first().ok_or(eyre!("cannot find message {id}"))?;
+
+        let copied_headers = header::collect(msg, extra_config.message_reply_copy_headers())?;
+
+        let mut tpl = msg
             .to_reply_tpl_builder(account_config.clone())
             .with_headers(self.headers.raw)
             .with_body(self.body.raw())
@@ -83,9 +113,35 @@ impl MessageReplyCommand {
             .build()
             .await?;
 
-        editor::edit_tpl_with_editor(account_config, printer, &backend, tpl).await?;
+        let own = [account_config.email.as_str()]
+            .into_iter()
+            .chain(extra_config.alternates().iter().map(String::as_str))
+            .collect::<Vec<_>>();
+        Recipients::new(msg, &own, self.reply.all, self.reply.list)?.set(&mut tpl);
+        reply::thread(&mut tpl, msg)?;
+
+        header::insert(&mut tpl, &copied_headers);
+
+        if let Some(hostname) = extra_config.smtp_helo_hostname() {
+            header::insert_message_id(&mut tpl, hostname.as_str());
+        }
+
+        if self.mdn.is_requested(extra_config.is_mdn_requested()) {
+            mdn::request(&mut tpl, &account_config.email);
+        }
+
+        editor::edit_tpl_with_editor(
+            account_config,
+            printer,
+            &backend,
+            tpl,
+            &config.aliases,
+            None,
+        )
+        .await?;
 
-        backend.add_flag(folder, &[id], Flag::Answered).await?;
+        let flags = Flags::from_iter([Flag::Answered]);
+        flag::add(&backend, &toml_account_config, folder, &[id], &flags).await?;
 
         Ok(())
     }
@@ -1,29 +1,56 @@
 use clap::Parser;
-use color_eyre::Result;
-use email::{backend::feature::BackendFeatureSource, config::Config};
-use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
 };
+use email::{
+    backend::feature::BackendFeatureSource,
+    config::Config,
+    flag::{Flag, Flags},
+};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use std::{
+    fs,
     io::{self, BufRead, IsTerminal},
+    path::PathBuf,
     sync::Arc,
 };
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig,
-    folder::arg::name::FolderNameOptionalFlag, message::arg::MessageRawArg,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    folder::{self, arg::name::FolderNameOptionalFlag},
+    message::{arg::MessageRawArg, header},
+    printer::Printer,
 };
 
 /// Save the given raw message to the given folder.
 ///
 /// This command allows you to add a raw message to the given folder.
+/// Raw messages can also be imported from files (like .eml files)
+/// using the --file argument.
 #[derive(Debug, Parser)]
 pub struct MessageSaveCommand {
     #[command(flatten)]
     pub folder: FolderNameOptionalFlag,
 
+    /// Import the raw message from the given file.
+    ///
+    /// This argument can be repeated to import multiple files at
+    /// once. Files that cannot be read or that do not look like
+    /// messages are skipped with a warning.
+    #[arg(long = "file", short = 'F', value_name = "PATH")]
+    #[arg(conflicts_with = "message_raw")]
+    pub files: Vec<PathBuf>,
+
+    /// Set the given flag to saved messages.
+    ///
+    /// This argument can be repeated to set multiple flags (for
+    /// example --flag seen --flag flagged).
+    #[arg(long = "flag", value_name = "FLAG")]
+    pub flags: Vec<String>,
+
     #[command(flatten)]
     pub message: MessageRawArg,
 
@@ -36,6 +63,7 @@ impl MessageSaveCommand {
         info!("executing save message command");
 
         let folder = &self.folder.name;
+        let flags = Flags::from_iter(self.flags.iter().map(|flag| Flag::from(flag.as_str())));
 
         let (toml_account_config, account_config) = config
             .clone()
@@ -43,6 +71,9 @@ impl MessageSaveCommand {
                 c.account(name).ok()
             })?;
 
+        let backend_folder =
+            folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
         let backend = BackendBuilder::new(
             Arc::new(toml_account_config),
             Arc::new(account_config),
@@ -56,6 +87,41 @@ impl MessageSaveCommand {
         .build()
         .await?;
 
+        if !self.files.is_empty() {
+            let total = self.files.len();
+            let mut skipped = 0;
+
+            for path in &self.files {
+                let msg = fs::read(path)
+                    .map_err(|err| eyre!(err))
+                    .and_then(|msg| check(&msg).map(|()| msg));
+
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    Err(err) => {
+                        let path = path.display();
+                        warn!("cannot import message from {path}: {err:?}");
+                        printer.log(format!("Skipping {path}: {err}\n"))?;
+                        skipped += 1;
+                        continue;
+                    }
+                };
+
+                backend
+                    .add_message_with_flags(&backend_folder, &crlf(&msg), &flags)
+                    .await?;
+            }
+
+            let saved = total - skipped;
+            let mut summary = format!("{saved} message(s) successfully saved to {folder}");
+
+            if skipped > 0 {
+                summary.push_str(&format!(", {skipped} file(s) skipped"));
+            }
+
+            return printer.info(format!("{summary}!\n"));
+        }
+
         let is_tty = io::stdin().is_terminal();
         let is_json = printer.is_json();
         let msg = if is_tty || is_json {
@@ -69,8 +135,49 @@ impl MessageSaveCommand {
                 .join("\r\n")
         };
 
-        backend.add_message(folder, msg.as_bytes()).await?;
+        backend
+            .add_message_with_flags(&backend_folder, msg.as_bytes(), &flags)
+            .await?;
+
+        printer.info(format!("Message successfully saved to {folder}!\n"))
+    }
+}
+
+/// Check that the given raw message starts with a valid header
+/// block, as defined by RFC 5322.
+fn check(msg: &[u8]) -> Result<()> {
+    let (headers, _) = header::split(msg);
+
+    if headers.is_empty() {
+        bail!("missing message headers");
+    }
+
+    for header in headers {
+        let name = header.split(|b| *b == b':').next().unwrap_or_default();
+        let is_valid = name.len() < header.len()
+            && !name.is_empty()
+            && name.iter().all(|b| (33..=126).contains(b));
 
-        printer.out(format!("Message successfully saved to {folder}!\n"))
+        if !is_valid {
+            let header = String::from_utf8_lossy(header);
+            let header: String = header.trim_end().chars().take(40).collect();
+            bail!("invalid header line {header:?}");
+        }
     }
+
+    Ok(())
+}
+
+/// Terminate lines of the given raw message by CRLF.
+fn crlf(msg: &[u8]) -> Vec<u8> {
+    let mut crlf = Vec::with_capacity(msg.len());
+
+    for line in msg.split_inclusive(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        crlf.extend_from_slice(line);
+        crlf.extend_from_slice(b"\r\n");
+    }
+
+    crlf
 }
@@ -1,17 +1,45 @@
-use clap::Parser;
-use color_eyre::Result;
-use email::{backend::feature::BackendFeatureSource, config::Config};
+use chrono::{DateTime, Local};
+use clap::{Parser, ValueEnum};
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use email::{
+    account::config::AccountConfig, backend::feature::BackendFeatureSource, config::Config,
+};
+use mail_parser::{MessageParser, PartType};
 use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
+    himalaya::backend::{Backend, BackendBuilder},
+    terminal::{cli::arg::path_parser, config::TomlConfig as _},
 };
 use std::{
-    io::{self, BufRead, IsTerminal},
+    fs,
+    io::{self, BufRead, IsTerminal, Read},
+    path::{Path, PathBuf},
     sync::Arc,
 };
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::{account::arg::name::AccountNameFlag, config::TomlConfig, message::arg::MessageRawArg};
+use crate::{
+    account::{
+        arg::name::AccountNameFlag,
+        config::{TomlAccountConfig, TomlAccountExtraConfig},
+    },
+    config::TomlConfig,
+    folder::{self, special_use::FolderRole},
+    message::{
+        arg::{mdn::MessageMdnFlags, MessageRawArg},
+        mbox, mdn,
+        send::{
+            self, dir,
+            dry_run::{self, DryRuns},
+            hook,
+            rate::{SendRate, Throttle},
+        },
+    },
+    outbox::{self, arg::schedule::SendLaterFlags, Outbox},
+    printer::Printer,
+};
 
 /// Send the given raw message.
 ///
@@ -22,46 +50,620 @@ pub struct MessageSendCommand {
     #[command(flatten)]
     pub message: MessageRawArg,
 
+    /// Send multiple messages at once.
+    ///
+    /// The given raw content is split into messages according to the
+    /// batch format. All messages are sent using the same backend
+    /// sessions. Failures do not stop the batch, they are reported
+    /// at the end.
+    #[arg(long)]
+    pub batch: bool,
+
+    /// The format used to split messages in batch mode.
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t)]
+    #[arg(requires = "batch")]
+    pub batch_format: BatchFormat,
+
+    /// Send the .eml files of the given directory.
+    ///
+    /// Each file is sent as a message, then moved to the sent
+    /// subdirectory. Running the command again only sends the
+    /// remaining files, and files whose name is already in the sent
+    /// subdirectory are skipped. Failures do not stop the sending,
+    /// they are reported at the end.
+    #[arg(long, value_name = "DIR", value_parser = path_parser)]
+    #[arg(conflicts_with_all = ["batch", "queue", "send_at", "send_in"])]
+    pub from_dir: Option<PathBuf>,
+
+    /// Send at most the given number of messages per minute.
+    ///
+    /// Only applies when sending multiple messages, using --batch or
+    /// --from-dir. The rate is given as <n>/min, for example 30/min.
+    #[arg(long, value_name = "N/min")]
+    pub rate: Option<SendRate>,
+
+    /// Queue the message in the outbox instead of sending it.
+    ///
+    /// Queued messages are sent later on, using the outbox flush
+    /// command.
+    #[arg(long)]
+    pub queue: bool,
+
+    #[command(flatten)]
+    pub schedule: SendLaterFlags,
+
+    #[command(flatten)]
+    pub mdn: MessageMdnFlags,
+
+    /// Prepare the message without sending it.
+    ///
+    /// The message is prepared the same way the sending backend does
+    /// (pre-send hooks, Bcc removal), then printed along with its
+    /// envelope and the backend that would send it. Nothing is sent,
+    /// queued nor saved. The command still fails if the message
+    /// could not be sent, for example if a recipient address cannot
+    /// be parsed.
+    #[arg(long)]
+    #[arg(conflicts_with_all = ["queue", "send_at", "send_in"])]
+    pub dry_run: bool,
+
+    /// Do not run the pre-send hooks of the account.
+    ///
+    /// The message is sent as is, without being piped to the
+    /// hooks.pre-send nor to the message.send.pre-hook command.
+    #[arg(long)]
+    pub no_hooks: bool,
+
     #[command(flatten)]
     pub account: AccountNameFlag,
 }
 
+/// The format of a batch of messages.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum BatchFormat {
+    /// Messages delimited by lines starting with "From ".
+    #[default]
+    Mbox,
+
+    /// Messages contained in a MIME digest (multipart/digest).
+    Digest,
+}
+
+impl BatchFormat {
+    /// Split the given content into raw messages.
+    pub fn split(&self, content: &[u8]) -> Result<Vec<Vec<u8>>> {
+        match self {
+            Self::Mbox => Ok(mbox::split(content)),
+            Self::Digest => {
+                let Some(digest) = MessageParser::new().parse(content) else {
+                    bail!("cannot parse MIME digest");
+                };
+
+                let msgs = digest
+                    .parts
+                    .iter()
+                    .filter_map(|part| match &part.body {
+                        PartType::Message(msg) => Some(msg.raw_message().to_vec()),
+                        _ => None,
+                    })
+                    .collect();
+
+                Ok(msgs)
+            }
+        }
+    }
+}
+
 impl MessageSendCommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing send message command");
 
-        let (toml_account_config, account_config) = config
+        let (toml_account_config, mut account_config) = config
             .clone()
             .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
                 c.account(name).ok()
             })?;
 
-        let backend = BackendBuilder::new(
-            Arc::new(toml_account_config),
-            Arc::new(account_config),
-            |builder| {
-                builder
-                    .without_features()
-                    .with_add_message(BackendFeatureSource::Context)
-                    .with_send_message(BackendFeatureSource::Context)
-            },
-        )
-        .build()
-        .await?;
-
-        let msg = if io::stdin().is_terminal() {
-            self.message.raw()
+        let mut extra_config = config.account_extra_config(&account_config.name);
+
+        if self.no_hooks {
+            hook::disable(&mut extra_config, &mut account_config);
+        }
+
+        if let Some(dir) = &self.from_dir {
+            return self
+                .send_dir(
+                    printer,
+                    config,
+                    dir,
+                    toml_account_config,
+                    account_config,
+                    extra_config,
+                )
+                .await;
+        }
+
+        let msgs = if self.batch {
+            let content = if io::stdin().is_terminal() {
+                self.message.raw().into_bytes()
+            } else {
+                let mut content = Vec::new();
+                io::stdin().lock().read_to_end(&mut content)?;
+                content
+            };
+
+            self.batch_format.split(&content)?
+        } else {
+            let msg = if io::stdin().is_terminal() {
+                self.message.raw()
+            } else {
+                io::stdin()
+                    .lock()
+                    .lines()
+                    .map_while(Result::ok)
+                    .collect::<Vec<_>>()
+                    .join("\r\n")
+            };
+
+            vec![msg.into_bytes()]
+        };
+
+        let msgs = msgs
+            .iter()
+            .map(|msg| config.aliases.expand_headers(msg))
+            .collect::<Result<Vec<_>>>()?;
+
+        let msgs = if self.mdn.is_requested(extra_config.is_mdn_requested()) {
+            msgs.iter()
+                .map(|msg| mdn::request_raw(msg, &account_config.email))
+                .collect()
         } else {
-            io::stdin()
-                .lock()
-                .lines()
-                .map_while(Result::ok)
-                .collect::<Vec<_>>()
-                .join("\r\n")
+            msgs
+        };
+
+        if self.dry_run && !self.batch {
+            let dry_run = dry_run::prepare(
+                &toml_account_config,
+                &account_config,
+                &extra_config,
+                &msgs[0],
+            )
+            .await?;
+            return printer.out(dry_run);
+        }
+
+        if self.dry_run {
+            let total = msgs.len();
+            let mut dry_runs = Vec::with_capacity(total);
+
+            for (i, msg) in msgs.iter().enumerate() {
+                let n = i + 1;
+                let dry_run =
+                    dry_run::prepare(&toml_account_config, &account_config, &extra_config, msg)
+                        .await
+                        .wrap_err_with(|| format!("cannot prepare message {n}/{total}"))?;
+                dry_runs.push(dry_run);
+            }
+
+            return printer.out(DryRuns(dry_runs));
+        }
+
+        let outbox = Outbox::new(&account_config.name)?;
+
+        let send_at = self.schedule.send_at();
+
+        if self.queue || send_at.is_some() {
+            let ids = msgs
+                .iter()
+                .map(|msg| outbox.push(msg, send_at))
+                .collect::<Result<Vec<_>>>()?;
+
+            return print_queued(printer, &ids, self.batch, send_at);
+        }
+
+        let queue_on_failure = extra_config.is_send_queue_on_failure_enabled();
+
+        let backend = build_backend(toml_account_config, account_config, &extra_config).await;
+
+        let backend = match backend {
+            Ok(backend) => backend,
+            Err(err) if queue_on_failure => {
+                let ids = msgs
+                    .iter()
+                    .map(|msg| outbox::queue_failed(printer, &outbox, msg, &err))
+                    .collect::<Result<Vec<_>>>()?;
+
+                return print_queued(printer, &ids, self.batch, None);
+            }
+            Err(err) => return Err(err),
         };
 
-        backend.send_message_then_save_copy(msg.as_bytes()).await?;
+        if !self.batch {
+            return match send_message(printer, &backend, &msgs[0], queue_on_failure).await {
+                Ok(()) => printer.info("Message successfully sent!"),
+                Err(err) if queue_on_failure && !hook::is_hook_error(&err) => {
+                    let id = outbox::queue_failed(printer, &outbox, &msgs[0], &err)?;
+                    printer.info(format!("Message queued in the outbox as {id}!"))
+                }
+                Err(err) => Err(err),
+            };
+        }
+
+        let total = msgs.len();
+        let mut failures = 0;
+        let mut queued = 0;
+        let mut throttle = Throttle::new(self.rate);
+
+        for (i, msg) in msgs.iter().enumerate() {
+            let n = i + 1;
+            throttle.wait().await;
+
+            match send_message(printer, &backend, msg, queue_on_failure).await {
+                Ok(()) => {
+                    printer.log(format!("Message {n}/{total} successfully sent!\n"))?;
+                }
+                Err(err) if queue_on_failure && !hook::is_hook_error(&err) => {
+                    let id = outbox::queue_failed(printer, &outbox, msg, &err)?;
+                    printer.log(format!(
+                        "Message {n}/{total} queued in the outbox as {id}!\n"
+                    ))?;
+                    queued += 1;
+                }
+                Err(err) => {
+                    warn!("cannot send message {n}/{total}: {err:?}");
+                    printer.log(format!("Message {n}/{total} could not be sent: {err}\n"))?;
+                    failures += 1;
+                }
+            }
+        }
+
+        if failures > 0 {
+            bail!("{failures} out of {total} message(s) could not be sent");
+        }
+
+        if queued > 0 {
+            let sent = total - queued;
+            return printer.info(format!(
+                "{sent} message(s) successfully sent, {queued} queued in the outbox!"
+            ));
+        }
+
+        printer.info(format!("{total} message(s) successfully sent!"))
+    }
+
+    /// Send the .eml files of the given directory.
+    async fn send_dir(
+        &self,
+        printer: &mut impl Printer,
+        config: &TomlConfig,
+        dir: &Path,
+        toml_account_config: TomlAccountConfig,
+        account_config: AccountConfig,
+        extra_config: TomlAccountExtraConfig,
+    ) -> Result<()> {
+        let files = dir::list(dir)?;
+        let total = files.pending.len();
+        let skipped = files.skipped.len();
+
+        let mdn = self.mdn.is_requested(extra_config.is_mdn_requested());
+        let read = |path: &Path| -> Result<Vec<u8>> {
+            let msg = fs::read(path).wrap_err("cannot read message")?;
+            let msg = config.aliases.expand_headers(&msg)?;
+
+            if mdn {
+                Ok(mdn::request_raw(&msg, &account_config.email))
+            } else {
+                Ok(msg)
+            }
+        };
+
+        if self.dry_run {
+            let mut dry_runs = Vec::with_capacity(total);
+
+            for path in &files.pending {
+                let name = dir::file_name(path);
+                let msg = read(path).wrap_err_with(|| format!("cannot prepare {name}"))?;
+                let dry_run =
+                    dry_run::prepare(&toml_account_config, &account_config, &extra_config, &msg)
+                        .await
+                        .wrap_err_with(|| format!("cannot prepare {name}"))?;
+                dry_runs.push(dry_run);
+            }
+
+            return printer.out(DryRuns(dry_runs));
+        }
+
+        for path in &files.skipped {
+            let name = dir::file_name(path);
+            printer.log(format!("{name} already sent, skipped\n"))?;
+        }
+
+        let msgs = files
+            .pending
+            .iter()
+            .map(|path| read(path))
+            .collect::<Vec<_>>();
+
+        let backend = build_backend(toml_account_config, account_config, &extra_config).await?;
+
+        let mut sent = 0;
+        let mut failures = 0;
+        let mut throttle = Throttle::new(self.rate);
+
+        for (i, (path, msg)) in files.pending.iter().zip(msgs).enumerate() {
+            let n = i + 1;
+            let name = dir::file_name(path);
+
+            // a copy that cannot be saved does not fail the sending,
+            // otherwise the file would be sent again by the next run
+            let result = match msg {
+                Ok(msg) => {
+                    throttle.wait().await;
+                    send_message(printer, &backend, &msg, true).await
+                }
+                Err(err) => Err(err),
+            };
+
+            if let Err(err) = result {
+                warn!("cannot send {name}: {err:?}");
+                printer.log(format!("{name} ({n}/{total}) could not be sent: {err}\n"))?;
+                failures += 1;
+                continue;
+            }
+
+            if let Err(err) = dir::mark_sent(path) {
+                warn!("cannot move sent file {name}: {err:?}");
+                printer.log(format!("{name} ({n}/{total}) sent, but {err}\n"))?;
+                failures += 1;
+                continue;
+            }
+
+            printer.log(format!("{name} ({n}/{total}) successfully sent!\n"))?;
+            sent += 1;
+        }
+
+        let summary = format!("{sent} sent, {failures} failed, {skipped} skipped");
+
+        if failures > 0 {
+            bail!("{failures} out of {total} file(s) could not be sent ({summary})");
+        }
+
+        printer.info(format!("Directory successfully sent ({summary})!"))
+    }
+}
+
+/// Build the backend used to send messages and to save their copy.
+async fn build_backend(
+    toml_account_config: TomlAccountConfig,
+    mut account_config: AccountConfig,
+    extra_config: &TomlAccountExtraConfig,
+) -> Result<Backend> {
+    folder::special_use::add_aliases(
+        &toml_account_config,
+        &mut account_config,
+        &[FolderRole::Sent],
+    )
+    .await;
+
+    BackendBuilder::new(
+        Arc::new(toml_account_config),
+        Arc::new(account_config),
+        |builder| {
+            let builder = builder
+                .without_features()
+                .with_add_message(BackendFeatureSource::Context);
+
+            send::with_send_message(builder, extra_config)
+        },
+    )
+    .build()
+    .await
+}
+
+/// Send the given message then save a copy of it.
+///
+/// When messages are queued on failure, a copy that cannot be saved
+/// does not fail the sending, otherwise the message would be queued
+/// and sent twice.
+async fn send_message(
+    printer: &mut impl Printer,
+    backend: &Backend,
+    msg: &[u8],
+    queue_on_failure: bool,
+) -> Result<()> {
+    if queue_on_failure {
+        outbox::send(printer, backend, msg).await
+    } else {
+        Ok(backend.send_message_then_save_copy(msg).await?)
+    }
+}
+
+fn print_queued(
+    printer: &mut impl Printer,
+    ids: &[String],
+    batch: bool,
+    send_at: Option<DateTime<Local>>,
+) -> Result<()> {
+    let queued = match ids {
+        [id] if !batch => format!("Message queued in the outbox as {id}"),
+        ids => format!("{} message(s) queued in the outbox", ids.len()),
+    };
+
+    match send_at {
+        Some(send_at) => printer.info(format!(
+            "{queued}, to be sent at {}!",
+            outbox::format(send_at)
+        )),
+        None => printer.info(format!("{queued}!")),
+    }
+}
+
+#[cfg(all(test, feature = "maildir", feature = "sendmail"))]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use uuid::Uuid;
+
+    use super::MessageSendCommand;
+    use crate::{
+        config::TomlConfig,
+        fixture::{block_on, MaildirRoot},
+        printer::StdoutPrinter,
+    };
+
+    /// A temporary account sending messages with a fake sendmail
+    /// script, which records the messages it is given and fails on
+    /// the ones whose subject is `fail`.
+    ///
+    /// The account directory holds the script, the recorded messages
+    /// and the `msgs` directory of messages to send.
+    struct Account {
+        root: MaildirRoot,
+        config: TomlConfig,
+    }
+
+    impl Account {
+        fn new() -> Self {
+            let root = MaildirRoot::new();
+            root.folder("", &[]);
+            fs::create_dir(root.0.join("msgs")).unwrap();
+
+            let script = format!(
+                "msg=$(cat)\n\
+                 case \"$msg\" in *'Subject: fail'*) exit 1 ;; esac\n\
+                 printf '%s\\n' \"$msg\" >>{:?}\n",
+                root.0.join("sent.log"),
+            );
+            let script_path = root.0.join("sendmail.sh");
+            fs::write(&script_path, script).unwrap();
+
+            let config = toml::from_str(&format!(
+                "[accounts.{name}]\n\
+                 default = true\n\
+                 email = \"me@localhost\"\n\
+                 backend.type = \"maildir\"\n\
+                 backend.root-dir = {root:?}\n\
+                 backend.maildirpp = true\n\
+                 message.send.save-copy = false\n\
+                 message.send.backend.type = \"sendmail\"\n\
+                 message.send.backend.cmd = \"sh {script_path}\"\n",
+                name = Uuid::new_v4(),
+                root = root.0,
+                script_path = script_path.display(),
+            ))
+            .unwrap();
+
+            Self { root, config }
+        }
+
+        fn write(&self, name: &str, subject: &str) {
+            let msg = format!(
+                "From: me@localhost\r\nTo: alice@localhost\r\nSubject: {subject}\r\n\r\nHello!\r\n"
+            );
+            fs::write(self.root.0.join("msgs").join(name), msg).unwrap();
+        }
+
+        fn send_dir(&self, args: &[&str]) -> color_eyre::Result<()> {
+            let dir = self.root.0.join("msgs");
+            let args: Vec<_> = ["send", "--from-dir", dir.to_str().unwrap()]
+                .iter()
+                .chain(args)
+                .copied()
+                .collect();
+
+            let cmd = MessageSendCommand::try_parse_from(args).unwrap();
+            block_on(cmd.execute(&mut StdoutPrinter::default(), &self.config))
+        }
+
+        /// Get the subjects of the messages given to the sendmail
+        /// script, in order.
+        fn sent(&self) -> Vec<String> {
+            let log = fs::read_to_string(self.root.0.join("sent.log")).unwrap_or_default();
+            log.lines()
+                .filter_map(|line| line.strip_prefix("Subject: "))
+                .map(|subject| subject.trim_end().to_owned())
+                .collect()
+        }
+
+        /// Get the names of the files of the given subdirectory of the
+        /// messages directory, sorted.
+        fn files(&self, dir: &str) -> Vec<String> {
+            let Ok(entries) = fs::read_dir(self.root.0.join("msgs").join(dir)) else {
+                return Vec::new();
+            };
+
+            let mut names: Vec<_> = entries
+                .map(|entry| entry.unwrap())
+                .filter(|entry| entry.path().is_file())
+                .map(|entry| entry.file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            names
+        }
+    }
+
+    #[test]
+    fn send_dir_in_name_order() {
+        let account = Account::new();
+        account.write("2.eml", "second");
+        account.write("1.eml", "first");
+        account.write("notes.txt", "ignored");
+
+        account.send_dir(&[]).unwrap();
+
+        assert_eq!(account.sent(), ["first", "second"]);
+        assert_eq!(account.files(""), ["notes.txt"]);
+        assert_eq!(account.files("sent"), ["1.eml", "2.eml"]);
+    }
+
+    #[test]
+    fn resume_after_failures() {
+        let account = Account::new();
+        account.write("1.eml", "first");
+        account.write("2.eml", "fail");
+        account.write("3.eml", "third");
+
+        let err = account.send_dir(&[]).unwrap_err().to_string();
+
+        assert_eq!(
+            err,
+            "1 out of 3 file(s) could not be sent (2 sent, 1 failed, 0 skipped)"
+        );
+        assert_eq!(account.sent(), ["first", "third"]);
+        assert_eq!(account.files(""), ["2.eml"]);
+        assert_eq!(account.files("sent"), ["1.eml", "3.eml"]);
+
+        // once fixed, only the failed file is sent again
+        account.write("2.eml", "second");
+        account.send_dir(&[]).unwrap();
+
+        assert_eq!(account.sent(), ["first", "third", "second"]);
+        assert_eq!(account.files(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn skip_already_sent_files() {
+        let account = Account::new();
+        account.write("1.eml", "first");
+        account.send_dir(&[]).unwrap();
+
+        account.write("1.eml", "first again");
+        account.write("2.eml", "second");
+        account.send_dir(&[]).unwrap();
+
+        assert_eq!(account.sent(), ["first", "second"]);
+        assert_eq!(account.files(""), ["1.eml"]);
+    }
+
+    #[test]
+    fn dry_run_dir() {
+        let account = Account::new();
+        account.write("1.eml", "first");
+
+        account.send_dir(&["--dry-run"]).unwrap();
 
-        printer.out("Message successfully sent!")
+        assert!(account.sent().is_empty());
+        assert_eq!(account.files(""), ["1.eml"]);
     }
 }
@@ -3,17 +3,17 @@ use std::sync::Arc;
 use clap::Parser;
 use color_eyre::Result;
 use email::{backend::feature::BackendFeatureSource, config::Config};
-use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
 use crate::envelope::arg::ids::EnvelopeIdArg;
 #[allow(unused)]
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig, envelope::arg::ids::EnvelopeIdsArgs,
-    folder::arg::name::FolderNameOptionalFlag,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    envelope::{arg::ids::EnvelopeIdsArgs, picker},
+    folder::{self, arg::name::FolderNameOptionalFlag},
+    printer::Printer,
 };
 
 /// Read human-friendly version of messages associated to the
@@ -61,7 +61,6 @@ impl MessageThreadCommand {
         info!("executing thread message(s) command");
 
         let folder = &self.folder.name;
-        let id = &self.envelope.id;
 
         let (toml_account_config, account_config) = config
             .clone()
@@ -69,6 +68,9 @@ impl MessageThreadCommand {
                 c.account(name).ok()
             })?;
 
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
         let account_config = Arc::new(account_config);
 
         let backend = BackendBuilder::new(
@@ -77,6 +79,7 @@ impl MessageThreadCommand {
             |builder| {
                 builder
                     .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
                     .with_get_messages(BackendFeatureSource::Context)
                     .with_peek_messages(BackendFeatureSource::Context)
                     .with_thread_envelopes(BackendFeatureSource::Context)
@@ -86,8 +89,10 @@ impl MessageThreadCommand {
         .build()
         .await?;
 
+        let id = picker::id_or_pick(&backend, folder, self.envelope.id).await?;
+
         let envelopes = backend
-            .thread_envelope(folder, *id, Default::default())
+            .thread_envelope(folder, id, Default::default())
             .await?;
 
         let ids: Vec<_> = envelopes
@@ -6,16 +6,18 @@ use email::{
     config::Config,
     {backend::feature::BackendFeatureSource, message::Message},
 };
-use pimalaya_tui::{
-    himalaya::{backend::BackendBuilder, editor},
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
 use crate::{
     account::arg::name::AccountNameFlag,
     config::TomlConfig,
-    message::arg::{body::MessageRawBodyArg, header::HeaderRawArgs},
+    folder::{self, special_use::FolderRole},
+    message::{
+        arg::{body::MessageRawBodyArg, header::HeaderRawArgs, mdn::MessageMdnFlags},
+        editor, header, mdn, send,
+    },
+    printer::Printer,
 };
 
 /// Compose a new message, from scratch.
@@ -32,6 +34,9 @@ pub struct MessageWriteCommand {
     #[command(flatten)]
     pub body: MessageRawBodyArg,
 
+    #[command(flatten)]
+    pub mdn: MessageMdnFlags,
+
     #[command(flatten)]
     pub account: AccountNameFlag,
 }
@@ -40,33 +45,58 @@ impl MessageWriteCommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing write message command");
 
-        let (toml_account_config, account_config) = config
+        let (toml_account_config, mut account_config) = config
             .clone()
             .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
                 c.account(name).ok()
             })?;
 
+        let extra_config = config.account_extra_config(&account_config.name);
+        folder::special_use::add_aliases(
+            &toml_account_config,
+            &mut account_config,
+            &[FolderRole::Sent, FolderRole::Drafts],
+        )
+        .await;
+
         let account_config = Arc::new(account_config);
 
         let backend = BackendBuilder::new(
             Arc::new(toml_account_config),
             account_config.clone(),
             |builder| {
-                builder
+                let builder = builder
                     .without_features()
-                    .with_add_message(BackendFeatureSource::Context)
-                    .with_send_message(BackendFeatureSource::Context)
+                    .with_add_message(BackendFeatureSource::Context);
+
+                send::with_send_message(builder, &extra_config)
             },
         )
         .build()
         .await?;
 
-        let tpl = Message::new_tpl_builder(account_config.clone())
+        let mut tpl = Message::new_tpl_builder(account_config.clone())
             .with_headers(self.headers.raw)
             .with_body(self.body.raw())
             .build()
             .await?;
 
-        editor::edit_tpl_with_editor(account_config, printer, &backend, tpl).await
+        if let Some(hostname) = extra_config.smtp_helo_hostname() {
+            header::insert_message_id(&mut tpl, hostname.as_str());
+        }
+
+        if self.mdn.is_requested(extra_config.is_mdn_requested()) {
+            mdn::request(&mut tpl, &account_config.email);
+        }
+
+        editor::edit_tpl_with_editor(
+            account_config,
+            printer,
+            &backend,
+            tpl,
+            &config.aliases,
+            None,
+        )
+        .await
     }
 }
@@ -0,0 +1,140 @@
+//! Module dedicated to the edition of messages.
+//!
+//! Messages are edited the same way as [`pimalaya_tui::himalaya::editor`]
+//! does, except that templates are compiled by the CLI: recipient
+//! aliases are expanded first, and MML parsing errors point at the
+//! offending line of the template.
+
+use std::sync::Arc;
+
+use color_eyre::Result;
+use email::{
+    account::config::AccountConfig,
+    email::utils::{local_draft_path, remove_local_draft},
+    flag::{Flag, Flags},
+    folder::DRAFTS,
+    template::Template,
+};
+use mml::MmlCompilerBuilder;
+use pimalaya_tui::himalaya::{
+    backend::Backend,
+    choice::{self, PostEditChoice, PreEditChoice},
+    editor,
+};
+
+use crate::{
+    address::alias::Aliases,
+    message::{forward::Attachment, template::compiler},
+    printer::Printer,
+};
+
+/// Edit the given template using the editor of the environment, then
+/// prompt for what to do with the edited message.
+///
+/// The given forwarded message, if any, is attached to the message
+/// once compiled. It is not part of the template, so it is not kept
+/// in local drafts.
+pub async fn edit_tpl_with_editor(
+    config: Arc<AccountConfig>,
+    printer: &mut impl Printer,
+    backend: &Backend,
+    mut tpl: Template,
+    aliases: &Aliases,
+    attachment: Option<&Attachment>,
+) -> Result<()> {
+    if local_draft_path().exists() {
+        loop {
+            match choice::pre_edit() {
+                Ok(PreEditChoice::Edit) => {
+                    tpl = editor::open_with_local_draft().await?;
+                    break;
+                }
+                Ok(PreEditChoice::Discard) => {
+                    tpl = editor::open_with_tpl(tpl).await?;
+                    break;
+                }
+                Ok(PreEditChoice::Quit) => return Ok(()),
+                Err(err) => {
+                    printer.log(format!("{err}\n"))?;
+                    continue;
+                }
+            }
+        }
+    } else {
+        tpl = editor::open_with_tpl(tpl).await?;
+    }
+
+    loop {
+        match choice::post_edit() {
+            Ok(PostEditChoice::Send) => {
+                printer.log("Sending message…\n")?;
+
+                let msg = compile(&config, tpl.as_str(), aliases, attachment).await?;
+                backend.send_message_then_save_copy(&msg).await?;
+
+                remove_local_draft()?;
+                printer.info("Message successfully sent!\n")?;
+                break;
+            }
+            Ok(PostEditChoice::Edit) => {
+                tpl = editor::open_with_tpl(tpl).await?;
+                continue;
+            }
+            Ok(PostEditChoice::LocalDraft) => {
+                if attachment.is_some() {
+                    printer.log("The forwarded message is not kept in the local draft\n")?;
+                }
+
+                printer.info("Message successfully saved locally!\n")?;
+                break;
+            }
+            Ok(PostEditChoice::RemoteDraft) => {
+                let msg = compile(&config, tpl.as_str(), aliases, attachment).await?;
+                let flags = Flags::from_iter([Flag::Seen, Flag::Draft]);
+                backend.add_message_with_flags(DRAFTS, &msg, &flags).await?;
+
+                remove_local_draft()?;
+                printer.info("Message successfully saved to drafts!\n")?;
+                break;
+            }
+            Ok(PostEditChoice::Discard) => {
+                remove_local_draft()?;
+                break;
+            }
+            Err(err) => {
+                printer.out(format!("{err}\n"))?;
+                continue;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Compile the given edited template, after expanding its recipient
+/// aliases, then add the given forwarded message to it.
+#[cfg_attr(
+    not(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native")),
+    allow(unused_variables)
+)]
+async fn compile(
+    config: &AccountConfig,
+    tpl: &str,
+    aliases: &Aliases,
+    attachment: Option<&Attachment>,
+) -> Result<Vec<u8>> {
+    let tpl = aliases.expand_tpl(tpl)?;
+
+    #[allow(unused_mut)]
+    let mut compiler = MmlCompilerBuilder::new();
+
+    #[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+    compiler.set_some_pgp(config.pgp.clone());
+
+    let msg = compiler::compile(compiler, &tpl).await?;
+
+    match attachment {
+        Some(attachment) => attachment.add_to(&msg),
+        None => Ok(msg),
+    }
+}
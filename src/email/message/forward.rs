@@ -0,0 +1,250 @@
+//! Module dedicated to forwarding messages as attachments.
+//!
+//! The original message is embedded verbatim in a `message/rfc822`
+//! part, so that its headers, signatures and structure are kept. The
+//! part is added to the message once its template has been compiled,
+//! so that the original message is never written to disk.
+
+use color_eyre::{eyre::bail, Result};
+use email::message::{template::Template, Message};
+use uuid::Uuid;
+
+use crate::message::header;
+
+/// The name given to attached messages without subject.
+const NONAME: &str = "forwarded message";
+
+/// A message forwarded as an attachment.
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    raw: Vec<u8>,
+    name: String,
+}
+
+/// Prepare the given template to forward the given message as an
+/// attachment.
+///
+/// The subject of the template is set to the subject of the message
+/// prefixed by `Fwd:`, unless the given one is not empty. Returns the
+/// attachment, to be added to the compiled template with
+/// [`Attachment::add_to`].
+pub fn attach(tpl: &mut Template, msg: &Message, subject: Option<&str>) -> Result<Attachment> {
+    let parsed = msg.parsed()?;
+
+    let original = parsed.subject().unwrap_or_default().trim();
+    let name = sanitize(original);
+    let name = if name.is_empty() {
+        NONAME.to_owned()
+    } else {
+        name
+    };
+
+    let subject = match subject {
+        Some(subject) if !subject.trim().is_empty() => subject.trim().to_owned(),
+        _ => format!("Fwd: {}", trim_prefix(original)),
+    };
+
+    header::replace(tpl, "Subject", &subject);
+
+    Ok(Attachment {
+        raw: msg.raw()?.to_vec(),
+        name,
+    })
+}
+
+impl Attachment {
+    /// Add the attached message to the given compiled message.
+    ///
+    /// The body of the compiled message becomes the first part of a
+    /// `multipart/mixed` body, followed by the attached message.
+    /// Encrypted bodies are refused, since the attached message would
+    /// be sent in clear next to them.
+    pub fn add_to(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let ctype = header::get(msg, "Content-Type").unwrap_or_default();
+
+        if ctype
+            .to_ascii_lowercase()
+            .starts_with("multipart/encrypted")
+        {
+            bail!("cannot forward a message as attachment of an encrypted message");
+        }
+
+        let (headers, body) = header::split(msg);
+
+        let mut body_part = Vec::new();
+        let mut output = Vec::new();
+
+        for header in headers {
+            if header::is_content(header) {
+                body_part.extend_from_slice(header);
+            } else {
+                output.extend_from_slice(header);
+            }
+        }
+
+        let boundary = Uuid::new_v4().to_simple().to_string();
+        let encoding = if self.raw.is_ascii() { "7bit" } else { "8bit" };
+        let filename = to_param("filename", &format!("{}.eml", self.name.replace('/', "_")));
+
+        output.extend_from_slice(
+            format!(
+                "Content-Type: multipart/mixed; boundary=\"{boundary}\"\r\n\
+                 \r\n\
+                 --{boundary}\r\n"
+            )
+            .as_bytes(),
+        );
+
+        output.extend_from_slice(&body_part);
+        output.extend_from_slice(b"\r\n");
+        output.extend_from_slice(body);
+
+        if !body.ends_with(b"\n") {
+            output.extend_from_slice(b"\r\n");
+        }
+
+        output.extend_from_slice(
+            format!(
+                "--{boundary}\r\n\
+                 Content-Type: message/rfc822; {name}\r\n\
+                 Content-Disposition: attachment; {filename}\r\n\
+                 Content-Transfer-Encoding: {encoding}\r\n\
+                 \r\n",
+                name = to_param("name", &self.name),
+            )
+            .as_bytes(),
+        );
+
+        output.extend_from_slice(&self.raw);
+
+        if !self.raw.ends_with(b"\n") {
+            output.extend_from_slice(b"\r\n");
+        }
+
+        output.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+
+        Ok(output)
+    }
+}
+
+/// Format the given MIME parameter.
+///
+/// Values made of printable ASCII characters are quoted, others are
+/// percent-encoded as UTF-8 (RFC 2231).
+fn to_param(key: &str, val: &str) -> String {
+    if val.bytes().all(|b| b.is_ascii_graphic() || b == b' ') {
+        return format!("{key}=\"{val}\"");
+    }
+
+    let mut encoded = String::new();
+
+    for b in val.bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' | b'_' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+
+    format!("{key}*=utf-8''{encoded}")
+}
+
+/// Remove the `Fwd:` prefixes of the given subject.
+fn trim_prefix(subject: &str) -> &str {
+    let mut subject = subject.trim_start();
+
+    while let Some((prefix, rest)) = subject.split_once(':') {
+        if !prefix.trim().eq_ignore_ascii_case("fwd") {
+            break;
+        }
+
+        subject = rest.trim_start();
+    }
+
+    subject
+}
+
+/// Make the given subject usable as a quoted MIME parameter value.
+///
+/// Quoted values cannot contain double quotes nor backslashes, and
+/// must hold on a single line.
+fn sanitize(subject: &str) -> String {
+    subject
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('"', "'")
+        .replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use email::{account::config::AccountConfig, message::Message};
+    use mail_parser::{MessageParser, MimeHeaders, PartType};
+    use mml::MmlCompilerBuilder;
+
+    use super::attach;
+    use crate::message::template::compiler;
+
+    const MSG: &str = concat!(
+        "From: alice@localhost\r\n",
+        "To: bob@localhost\r\n",
+        "Subject: Fwd: Café\r\n",
+        "Content-Type: text/plain; charset=utf-8\r\n",
+        "\r\n",
+        "Hello!\r\n",
+    );
+
+    #[tokio::test]
+    async fn forward_as_attachment() {
+        let config = Arc::new(AccountConfig {
+            email: "me@localhost".into(),
+            ..Default::default()
+        });
+
+        let mut tpl = Message::new_tpl_builder(config)
+            .with_body("See below.")
+            .build()
+            .await
+            .unwrap();
+
+        let attachment = attach(&mut tpl, &Message::from(MSG), None).unwrap();
+        assert!(!tpl.contains("<#part"), "{}", tpl.as_str());
+
+        let msg = compiler::compile(MmlCompilerBuilder::new(), tpl.as_str())
+            .await
+            .unwrap();
+        let msg = attachment.add_to(&msg).unwrap();
+        let msg = MessageParser::new().parse(&msg).unwrap();
+
+        assert_eq!(msg.subject(), Some("Fwd: Café"));
+        assert_eq!(msg.body_text(0).unwrap().trim(), "See below.");
+        assert_eq!(msg.attachment_count(), 1);
+
+        let part = msg.attachment(0).unwrap();
+        assert!(part.is_content_type("message", "rfc822"));
+        assert_eq!(part.attachment_name(), Some("Fwd: Café.eml"));
+
+        let PartType::Message(forwarded) = &part.body else {
+            panic!("unexpected part {:?}", part.body);
+        };
+        // the line ending preceding the boundary belongs to it
+        assert_eq!(forwarded.raw_message(), MSG.trim_end().as_bytes());
+    }
+
+    #[test]
+    fn refuse_encrypted_messages() {
+        let msg = concat!(
+            "Content-Type: multipart/encrypted; boundary=b\r\n",
+            "\r\n",
+            "--b--\r\n",
+        );
+
+        let err = attach(&mut Default::default(), &Message::from(MSG), None)
+            .unwrap()
+            .add_to(msg.as_bytes())
+            .unwrap_err();
+        assert!(err.to_string().contains("encrypted"), "{err}");
+    }
+}
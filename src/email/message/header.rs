@@ -0,0 +1,383 @@
+use color_eyre::Result;
+use email::message::{template::Template, Message};
+use uuid::Uuid;
+
+/// Collect headers of the given message matching the given patterns.
+///
+/// Header names are matched case-insensitively, and patterns may
+/// contain `*` wildcards (for example `X-Org-*`). All instances of
+/// multi-valued headers are collected, in their original order.
+/// Folded values are unfolded on a single line.
+pub fn collect(msg: &Message, patterns: &[String]) -> Result<Vec<(String, String)>> {
+    if patterns.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let headers = msg
+        .parsed()?
+        .headers_raw()
+        .filter(|(name, _)| patterns.iter().any(|p| matches(p, name)))
+        .map(|(name, val)| {
+            let val = val.split_whitespace().collect::<Vec<_>>().join(" ");
+            (name.to_owned(), val)
+        })
+        .collect();
+
+    Ok(headers)
+}
+
+/// Match the given header name against the given wildcard pattern,
+/// case-insensitively.
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let name = name.to_lowercase();
+
+    let mut parts = pattern.split('*');
+
+    // a pattern without wildcard needs to match the whole name
+    let Some(first) = parts.next() else {
+        return name.is_empty();
+    };
+
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts = parts.peekable();
+
+    if parts.peek().is_none() {
+        return rest.is_empty();
+    }
+
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.ends_with(part);
+        }
+
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    true
+}
+
+/// Insert the given headers at the end of the headers block of the
+/// given template.
+///
+/// Headers are inserted after the template has been built, since
+/// template interpreters keep only one value per shown header.
+pub fn insert(tpl: &mut Template, headers: &[(String, String)]) {
+    if headers.is_empty() {
+        return;
+    }
+
+    let mut lines = String::new();
+
+    for (name, val) in headers {
+        lines.push_str(&format!("{name}: {val}\n"));
+    }
+
+    let i = match tpl.content.find("\n\n") {
+        Some(i) => i + 1,
+        None => tpl.content.len(),
+    };
+
+    tpl.content.insert_str(i, &lines);
+    tpl.cursor.row += headers.len();
+}
+
+/// Replace the given header of the given template by the given
+/// value.
+///
+/// Previous instances of the header are removed, folded lines
+/// included, and the new header takes the place of the first one.
+/// When the template has no such header, it is inserted at the end
+/// of the headers block. An empty value removes the header.
+pub fn replace(tpl: &mut Template, name: &str, val: &str) {
+    let (headers, body) = match tpl.content.find("\n\n") {
+        Some(i) => tpl.content.split_at(i + 1),
+        None => (tpl.content.as_str(), ""),
+    };
+
+    let mut lines = Vec::new();
+    let mut pos = None;
+    let mut removed = 0;
+    let mut matches = false;
+
+    for line in headers.split_inclusive('\n') {
+        if !line.starts_with([' ', '\t']) {
+            matches = line
+                .split_once(':')
+                .is_some_and(|(key, _)| key.trim().eq_ignore_ascii_case(name));
+        }
+
+        if matches {
+            pos.get_or_insert(lines.len());
+            removed += 1;
+        } else {
+            lines.push(line);
+        }
+    }
+
+    let header = format!("{name}: {val}\n");
+    let added = if val.is_empty() {
+        0
+    } else {
+        lines.insert(pos.unwrap_or(lines.len()), &header);
+        1
+    };
+
+    tpl.content = lines.concat() + body;
+    tpl.cursor.row = (tpl.cursor.row + added).saturating_sub(removed);
+}
+
+/// Insert a Message-ID header using the given domain at the end of
+/// the headers block of the given template, unless the template
+/// already has one.
+pub fn insert_message_id(tpl: &mut Template, domain: &str) {
+    if !contains_tpl(tpl, "Message-ID") {
+        let id = format!("<{}@{domain}>", Uuid::new_v4());
+        insert(tpl, &[(String::from("Message-ID"), id)]);
+    }
+}
+
+/// Check if the headers block of the given template contains a
+/// header matching the given name, case-insensitively.
+pub fn contains_tpl(tpl: &Template, name: &str) -> bool {
+    tpl.content
+        .lines()
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .any(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+}
+
+/// Split the given raw message into its raw headers, folded lines
+/// included, and its body.
+pub fn split(msg: &[u8]) -> (Vec<&[u8]>, &[u8]) {
+    let mut headers: Vec<&[u8]> = Vec::new();
+    let mut offset = 0;
+
+    while offset < msg.len() {
+        let end = msg[offset..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(msg.len(), |i| offset + i + 1);
+
+        let line = &msg[offset..end];
+
+        if line == b"\r\n" || line == b"\n" {
+            return (headers, &msg[end..]);
+        }
+
+        match headers.last_mut() {
+            Some(header) if line.starts_with(b" ") || line.starts_with(b"\t") => {
+                let start = end - line.len() - header.len();
+                *header = &msg[start..end];
+            }
+            _ => headers.push(line),
+        }
+
+        offset = end;
+    }
+
+    (headers, &[])
+}
+
+/// Check if the given raw header is a MIME content header.
+pub fn is_content(header: &[u8]) -> bool {
+    header
+        .get(..8)
+        .is_some_and(|name| name.eq_ignore_ascii_case(b"content-"))
+}
+
+/// Check if the given raw header matches the given name,
+/// case-insensitively.
+fn is_named(header: &[u8], name: &str) -> bool {
+    header
+        .get(..name.len() + 1)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(format!("{name}:").as_bytes()))
+}
+
+/// Check if the given raw message contains a header matching the
+/// given name, case-insensitively.
+pub fn contains(msg: &[u8], name: &str) -> bool {
+    split(msg).0.iter().any(|header| is_named(header, name))
+}
+
+/// Get the unfolded value of the first header matching the given
+/// name from the given raw message, case-insensitively.
+pub fn get(msg: &[u8], name: &str) -> Option<String> {
+    let header = split(msg).0.into_iter().find(|h| is_named(h, name))?;
+    let val = String::from_utf8_lossy(&header[name.len() + 1..]);
+    Some(val.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Append the given header at the end of the headers of the given raw
+/// message, using the line ending of the message.
+pub fn append(msg: &[u8], name: &str, value: &str) -> Vec<u8> {
+    let (headers, _) = split(msg);
+    let headers_len: usize = headers.iter().map(|header| header.len()).sum();
+
+    let crlf = headers
+        .first()
+        .map_or(true, |header| header.ends_with(b"\r\n"));
+    let eol = if crlf { "\r\n" } else { "\n" };
+
+    let mut output = Vec::with_capacity(msg.len() + name.len() + value.len() + 4);
+    output.extend_from_slice(&msg[..headers_len]);
+
+    // the last header of a message without body may lack a line ending
+    if !output.is_empty() && !output.ends_with(b"\n") {
+        output.extend_from_slice(eol.as_bytes());
+    }
+
+    output.extend_from_slice(format!("{name}: {value}{eol}").as_bytes());
+    output.extend_from_slice(&msg[headers_len..]);
+    output
+}
+
+/// Remove all instances of the header matching the given name from
+/// the given raw message, case-insensitively.
+pub fn remove(msg: &[u8], name: &str) -> Vec<u8> {
+    let (headers, body) = split(msg);
+    let headers_len: usize = headers.iter().map(|header| header.len()).sum();
+    let mut output = Vec::with_capacity(msg.len());
+
+    for header in headers {
+        if !is_named(header, name) {
+            output.extend_from_slice(header);
+        }
+    }
+
+    // keeps the empty line separating headers from the body
+    output.extend_from_slice(&msg[headers_len..msg.len() - body.len()]);
+    output.extend_from_slice(body);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use email::{
+        account::config::AccountConfig,
+        message::{template::Template, Message},
+    };
+
+    const MSG: &str = concat!(
+        "From: alice@localhost\n",
+        "To: me@localhost\n",
+        "Subject: Ticket\n",
+        "X-Ticket-ID: 42\n",
+        "X-Tag: first\n",
+        "x-org-team: mail\n",
+        "X-Tag: second,\n",
+        "  folded\n",
+        "X-Organization: none\n",
+        "\n",
+        "Hello\n",
+    );
+
+    fn collect(patterns: &[&str]) -> Vec<(String, String)> {
+        let patterns: Vec<String> = patterns.iter().map(ToString::to_string).collect();
+        super::collect(&Message::from(MSG), &patterns).unwrap()
+    }
+
+    fn header(name: &str, val: &str) -> (String, String) {
+        (name.to_owned(), val.to_owned())
+    }
+
+    /// Return the headers block of the given template.
+    fn headers(tpl: &Template) -> Vec<&str> {
+        tpl.content
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .collect()
+    }
+
+    fn account_config() -> Arc<AccountConfig> {
+        Arc::new(AccountConfig {
+            email: String::from("me@localhost"),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn collect_case_insensitively() {
+        let expected = vec![header("X-Ticket-ID", "42")];
+        assert_eq!(collect(&["x-ticket-id"]), expected);
+        assert_eq!(collect(&["X-TICKET-ID"]), expected);
+        assert!(collect(&["X-Ticket"]).is_empty());
+    }
+
+    #[test]
+    fn collect_all_instances() {
+        assert_eq!(
+            collect(&["x-tag"]),
+            vec![header("X-Tag", "first"), header("X-Tag", "second, folded")],
+        );
+    }
+
+    #[test]
+    fn collect_wildcards() {
+        assert_eq!(collect(&["X-Org-*"]), vec![header("x-org-team", "mail")]);
+        assert_eq!(
+            collect(&["*-id", "*team"]),
+            vec![header("X-Ticket-ID", "42"), header("x-org-team", "mail")],
+        );
+        assert!(collect(&[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn copy_headers_into_reply() {
+        let msg = Message::from(MSG);
+        let copied = collect(&["X-Tag", "x-ticket-id"]);
+
+        let mut tpl = msg
+            .to_reply_tpl_builder(account_config())
+            .build()
+            .await
+            .unwrap();
+        let row = tpl.cursor.row;
+
+        super::insert(&mut tpl, &copied);
+
+        let headers = headers(&tpl);
+        let copied: Vec<_> = headers
+            .iter()
+            .filter(|h| h.starts_with("X-"))
+            .copied()
+            .collect();
+        assert_eq!(
+            copied,
+            ["X-Ticket-ID: 42", "X-Tag: first", "X-Tag: second, folded"],
+        );
+        assert!(headers.contains(&"Subject: Re: Ticket"));
+        assert_eq!(tpl.cursor.row, row + 3);
+    }
+
+    #[tokio::test]
+    async fn copy_headers_into_forward() {
+        let msg = Message::from(MSG);
+        let copied = collect(&["x-tag"]);
+
+        let mut tpl = msg
+            .to_forward_tpl_builder(account_config())
+            .build()
+            .await
+            .unwrap();
+
+        super::insert(&mut tpl, &copied);
+
+        let headers = headers(&tpl);
+        let copied: Vec<_> = headers
+            .iter()
+            .filter(|h| h.starts_with("X-"))
+            .copied()
+            .collect();
+        assert_eq!(copied, ["X-Tag: first", "X-Tag: second, folded"]);
+        assert!(headers.contains(&"Subject: Fwd: Ticket"));
+    }
+}
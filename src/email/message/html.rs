@@ -0,0 +1,880 @@
+use std::{
+    fs,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::{
+    eyre::{bail, eyre, WrapErr},
+    Result,
+};
+use email::message::Message;
+use mail_parser::{MimeHeaders, PartType};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    select,
+    task::JoinSet,
+    time::{timeout, timeout_at, Instant},
+};
+use tracing::{debug, warn};
+
+use crate::printer::Printer;
+
+/// Content security policy sent along with served HTML.
+///
+/// Scripts, frames and remote resources are blocked: only inline
+/// styles and inlined (data URI) images are allowed.
+const CONTENT_SECURITY_POLICY: &str =
+    "default-src 'none'; img-src data:; style-src 'unsafe-inline'; form-action 'none'";
+
+/// Extract the HTML body of the given message.
+///
+/// Parts referenced by their content id (`cid:`) are inlined as
+/// data URIs, so the resulting HTML does not depend on any other
+/// resource.
+pub fn to_inlined_html(msg: &Message) -> Result<String> {
+    let parsed = msg.parsed()?;
+
+    let mut html = parsed
+        .body_html(0)
+        .ok_or(eyre!("cannot find HTML part in message"))?
+        .into_owned();
+
+    for part in parsed.parts.iter() {
+        let Some(cid) = part.content_id() else {
+            continue;
+        };
+
+        let cid = cid.trim_start_matches('<').trim_end_matches('>');
+
+        let mime = match part.content_type() {
+            Some(ctype) => match ctype.subtype() {
+                Some(subtype) => format!("{}/{subtype}", ctype.ctype()),
+                None => ctype.ctype().to_owned(),
+            },
+            None => String::from("application/octet-stream"),
+        };
+
+        let data = STANDARD.encode(part.contents());
+        html = replace_cid(&html, cid, &format!("data:{mime};base64,{data}"));
+    }
+
+    Ok(html)
+}
+
+/// Write the HTML body of the given message to the given directory,
+/// so that it can be opened in a browser.
+///
+/// Parts referenced by their content id (`cid:`) are written next to
+/// the HTML file, and references are rewritten to point at them.
+/// Remote content is left untouched. The directory is emptied first,
+/// so that files of previous runs do not pile up.
+///
+/// Returns the path of the HTML file.
+pub fn write_to_dir(msg: &Message, dir: &Path) -> Result<PathBuf> {
+    let parsed = msg.parsed()?;
+
+    let mut html = parsed
+        .body_html(0)
+        .ok_or(eyre!("cannot find HTML part in message"))?
+        .into_owned();
+
+    if dir.exists() {
+        fs::remove_dir_all(dir)
+            .wrap_err_with(|| format!("cannot clean directory {}", dir.display()))?;
+    }
+
+    fs::create_dir_all(dir)
+        .wrap_err_with(|| format!("cannot create directory {}", dir.display()))?;
+
+    for (i, part) in parsed.parts.iter().enumerate() {
+        let Some(cid) = part.content_id() else {
+            continue;
+        };
+
+        let cid = cid.trim_start_matches('<').trim_end_matches('>');
+        let name = format!(
+            "{i}-{}",
+            to_file_name(part.attachment_name().unwrap_or(cid))
+        );
+        let path = dir.join(&name);
+
+        let replaced = replace_cid(&html, cid, &name);
+
+        if replaced == html {
+            continue;
+        }
+
+        fs::write(&path, part.contents())
+            .wrap_err_with(|| format!("cannot write inline part at {}", path.display()))?;
+
+        html = replaced;
+    }
+
+    let path = dir.join("index.html");
+
+    fs::write(&path, html)
+        .wrap_err_with(|| format!("cannot write HTML part at {}", path.display()))?;
+
+    Ok(path)
+}
+
+/// Replace references to the given content id with the given value.
+///
+/// Only whole references are replaced: `cid:` must end an attribute
+/// or a CSS `url()` value, so that `cid:a` does not match the start
+/// of `cid:ab`.
+fn replace_cid(html: &str, cid: &str, with: &str) -> String {
+    let reference = format!("cid:{cid}");
+    let mut replaced = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find(&reference) {
+        let end = start + reference.len();
+        let is_whole = rest[end..]
+            .starts_with(|c: char| matches!(c, '"' | '\'' | ')' | '>') || c.is_whitespace());
+
+        replaced.push_str(&rest[..start]);
+        replaced.push_str(if is_whole { with } else { &reference });
+        rest = &rest[end..];
+    }
+
+    replaced.push_str(rest);
+    replaced
+}
+
+/// Return the directory the HTML body of the given message is written
+/// to, see [`write_to_dir`].
+///
+/// The directory lives in the cache directory, under
+/// `himalaya/html/<account>/<folder>/<id>`.
+pub fn cache_dir(account: &str, folder: &str, id: usize) -> Result<PathBuf> {
+    let Some(dir) = dirs::cache_dir() else {
+        bail!("cannot find cache directory for the HTML of message {id}");
+    };
+
+    let dir = dir
+        .join("himalaya")
+        .join("html")
+        .join(to_file_name(account))
+        .join(to_file_name(folder))
+        .join(id.to_string());
+
+    Ok(dir)
+}
+
+/// Remove the HTML written for the messages of the given account, if
+/// any, see [`cache_dir`].
+pub fn remove_cache(account: &str) -> Result<()> {
+    let Some(dir) = dirs::cache_dir() else {
+        return Ok(());
+    };
+
+    let dir = dir
+        .join("himalaya")
+        .join("html")
+        .join(to_file_name(account));
+
+    match fs::remove_dir_all(&dir) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            let dir = dir.display();
+            Err(err).wrap_err_with(|| format!("cannot remove HTML cache at {dir}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Turn the given name into a safe file name, by replacing any
+/// character other than alphanumerics, dots, hyphens and underscores
+/// with an underscore.
+fn to_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() => c,
+            '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+
+    match name.trim_start_matches('.') {
+        "" => String::from("_"),
+        name => name.to_owned(),
+    }
+}
+
+/// Render the HTML body of the given message as plain text, if the
+/// message does not have any text/plain body.
+///
+/// Returns `None` when the message has a text/plain body, or when it
+/// does not have any HTML body.
+pub fn to_text_fallback(msg: &Message) -> Result<Option<String>> {
+    let parsed = msg.parsed()?;
+
+    let has_plain = parsed
+        .text_body
+        .iter()
+        .filter_map(|i| parsed.part(*i))
+        .any(|part| matches!(part.body, PartType::Text(_)));
+
+    if has_plain {
+        return Ok(None);
+    }
+
+    let texts: Vec<String> = parsed
+        .html_body
+        .iter()
+        .filter_map(|i| match &parsed.part(*i)?.body {
+            PartType::Html(html) => Some(to_text(html)),
+            _ => None,
+        })
+        .collect();
+
+    if texts.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(texts.join("\n\n")))
+}
+
+/// Render the given HTML as plain text.
+///
+/// Tags are stripped, block elements and line breaks become new
+/// lines, links are rendered as `text <url>` and entities are
+/// decoded. Scripts, styles and the document head are dropped.
+pub fn to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut hidden = 0usize;
+    let mut links: Vec<(usize, Option<String>)> = Vec::new();
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            if hidden == 0 {
+                push_text(&mut text, rest);
+            }
+            break;
+        };
+
+        if hidden == 0 {
+            push_text(&mut text, &rest[..start]);
+        }
+
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = match comment.find("-->") {
+                Some(end) => &comment[end + 3..],
+                None => "",
+            };
+            continue;
+        }
+
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match name.as_str() {
+            "head" | "script" | "style" | "title" if closing => {
+                hidden = hidden.saturating_sub(1);
+            }
+            "head" | "script" | "style" | "title" => {
+                hidden += 1;
+            }
+            _ if hidden > 0 => (),
+            "br" => {
+                text.push('\n');
+            }
+            "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "ul" | "ol" | "table"
+            | "blockquote" | "pre" => {
+                push_break(&mut text, 2);
+            }
+            "div" | "tr" | "hr" | "section" | "article" | "header" | "footer" => {
+                push_break(&mut text, 1);
+            }
+            "li" if !closing => {
+                push_break(&mut text, 1);
+                text.push_str("- ");
+            }
+            "td" | "th" if !closing => {
+                if !text.is_empty() && !text.ends_with(char::is_whitespace) {
+                    text.push(' ');
+                }
+            }
+            "img" => {
+                if let Some(alt) = attr(tag, "alt").filter(|alt| !alt.trim().is_empty()) {
+                    push_text(&mut text, &alt);
+                }
+            }
+            "a" if closing => {
+                if let Some((offset, Some(href))) = links.pop() {
+                    let label = text[offset..].trim();
+                    let url = href.strip_prefix("mailto:").unwrap_or(&href);
+
+                    if label.is_empty() {
+                        text.push_str(url);
+                    } else if label != url {
+                        text.push_str(&format!(" <{url}>"));
+                    }
+                }
+            }
+            "a" => {
+                let href = attr(tag, "href").filter(|href| {
+                    !href.is_empty() && !href.starts_with('#') && !href.starts_with("javascript:")
+                });
+                links.push((text.len(), href));
+            }
+            _ => (),
+        }
+    }
+
+    let mut lines: Vec<&str> = Vec::new();
+
+    for line in text.lines().map(str::trim) {
+        // keep at most one blank line between paragraphs
+        if line.is_empty() && lines.last().map_or(true, |last| last.is_empty()) {
+            continue;
+        }
+
+        lines.push(line);
+    }
+
+    lines.join("\n").trim().to_owned()
+}
+
+/// Push the given HTML text, collapsing whitespaces and decoding
+/// entities.
+fn push_text(text: &mut String, html: &str) {
+    let mut words = html.split_whitespace().peekable();
+
+    if words.peek().is_none() {
+        if !html.is_empty() && !text.is_empty() && !text.ends_with(char::is_whitespace) {
+            text.push(' ');
+        }
+        return;
+    }
+
+    if html.starts_with(char::is_whitespace)
+        && !text.is_empty()
+        && !text.ends_with(char::is_whitespace)
+    {
+        text.push(' ');
+    }
+
+    text.push_str(&decode_entities(&words.collect::<Vec<_>>().join(" ")));
+
+    if html.ends_with(char::is_whitespace) {
+        text.push(' ');
+    }
+}
+
+/// Make sure the text ends with the given amount of line breaks.
+fn push_break(text: &mut String, n: usize) {
+    if text.trim().is_empty() {
+        return;
+    }
+
+    while text.ends_with(' ') {
+        text.pop();
+    }
+
+    let breaks = text.len() - text.trim_end_matches('\n').len();
+
+    for _ in breaks..n {
+        text.push('\n');
+    }
+}
+
+/// Get the decoded value of the given attribute of the given tag.
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag.split_once(char::is_whitespace)?.1;
+
+    loop {
+        rest = rest.trim_start();
+
+        let end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+
+        if end == 0 {
+            return None;
+        }
+
+        let key = &rest[..end];
+        rest = rest[end..].trim_start();
+
+        let val = match rest.strip_prefix('=') {
+            None => "",
+            Some(val) => {
+                let val = val.trim_start();
+                let (val, next) = match val.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let val = &val[1..];
+                        let end = val.find(quote).unwrap_or(val.len());
+                        (&val[..end], val.get(end + 1..).unwrap_or_default())
+                    }
+                    _ => {
+                        let end = val.find(char::is_whitespace).unwrap_or(val.len());
+                        (&val[..end], &val[end..])
+                    }
+                };
+                rest = next;
+                val
+            }
+        };
+
+        if key.eq_ignore_ascii_case(name) {
+            return Some(decode_entities(val.trim()));
+        }
+    }
+}
+
+/// Decode HTML entities of the given text.
+///
+/// Numeric entities are all supported, named entities are limited to
+/// the most common ones. Unknown entities are kept as is.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| Some((end, decode_entity(&rest[1..end + 1])?)));
+
+        match entity {
+            Some((end, c)) => {
+                decoded.push(c);
+                rest = &rest[end + 2..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(num) = entity.strip_prefix('#') {
+        let code = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+
+    let c = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "euro" => '€',
+        "pound" => '£',
+        "yen" => '¥',
+        "cent" => '¢',
+        "sect" => '§',
+        "deg" => '°',
+        "plusmn" => '±',
+        "times" => '×',
+        "divide" => '÷',
+        "middot" => '·',
+        "bull" => '•',
+        "hellip" => '…',
+        "ndash" => '–',
+        "mdash" => '—',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "agrave" => 'à',
+        "aacute" => 'á',
+        "acirc" => 'â',
+        "auml" => 'ä',
+        "ccedil" => 'ç',
+        "egrave" => 'è',
+        "eacute" => 'é',
+        "ecirc" => 'ê',
+        "euml" => 'ë',
+        "icirc" => 'î',
+        "iuml" => 'ï',
+        "ocirc" => 'ô',
+        "ouml" => 'ö',
+        "ugrave" => 'ù',
+        "ucirc" => 'û',
+        "uuml" => 'ü',
+        "szlig" => 'ß',
+        "Agrave" => 'À',
+        "Eacute" => 'É',
+        "Ccedil" => 'Ç',
+        _ => return None,
+    };
+
+    Some(c)
+}
+
+/// Serve the given HTML on localhost.
+///
+/// The server binds to `127.0.0.1` only and answers every request to
+/// `/` with the given HTML, any other path leads to a 404. It stops
+/// after serving the HTML once, or after `idle` without any incoming
+/// connection.
+///
+/// The HTML is not sanitized: scripts and event handlers are kept
+/// as is, and only the [`CONTENT_SECURITY_POLICY`] sent along
+/// prevents the browser from running them or loading remote
+/// content.
+pub async fn serve(
+    printer: &mut impl Printer,
+    html: String,
+    port: u16,
+    idle: Duration,
+) -> Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+    let addr = listener.local_addr()?;
+
+    printer.info(format!(
+        "Serving message at http://{addr}/ (press Ctrl-C to stop)\n"
+    ))?;
+
+    if !accept(listener, Arc::from(html), idle).await? {
+        let secs = idle.as_secs();
+        printer.info(format!(
+            "No request received for {secs}s, stopping server\n"
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Answer connections of the given listener until the HTML has been
+/// served, or until `idle` without any incoming connection.
+///
+/// Connections are answered concurrently, so that a connection
+/// opened in advance by a browser does not hold the others. A
+/// connection failing or not sending its request within `idle` is
+/// logged and closed. Returns `true` when the HTML has been served.
+async fn accept(listener: TcpListener, html: Arc<str>, idle: Duration) -> Result<bool> {
+    let mut connections = JoinSet::new();
+    let mut deadline = Instant::now() + idle;
+
+    loop {
+        select! {
+            accepted = timeout_at(deadline, listener.accept()) => {
+                let Ok(accepted) = accepted else {
+                    return Ok(false);
+                };
+
+                let (stream, peer) = accepted?;
+                debug!("accepted connection from {peer}");
+                deadline = Instant::now() + idle;
+
+                let html = html.clone();
+                connections.spawn(async move { (peer, respond(stream, &html, idle).await) });
+            }
+            Some(joined) = connections.join_next() => {
+                match joined {
+                    Ok((_, Ok(true))) => return Ok(true),
+                    Ok((_, Ok(false))) => (),
+                    Ok((peer, Err(err))) => warn!("cannot answer request from {peer}: {err}"),
+                    Err(err) => warn!("cannot answer request: {err}"),
+                }
+            }
+        }
+    }
+}
+
+/// Answer a single HTTP request, which should be received within
+/// `idle`.
+///
+/// Returns `true` when the HTML has been served.
+async fn respond(mut stream: TcpStream, html: &str, idle: Duration) -> Result<bool> {
+    let mut buf = [0; 4096];
+    let n = timeout(idle, stream.read(&mut buf))
+        .await
+        .map_err(|_| eyre!("no request received for {}s", idle.as_secs()))??;
+    let req = String::from_utf8_lossy(&buf[..n]);
+
+    let path = req
+        .lines()
+        .next()
+        .and_then(|line| {
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                Some("GET") => parts.next(),
+                _ => None,
+            }
+        })
+        .unwrap_or_default();
+
+    let served = path == "/";
+
+    let (status, body) = if served {
+        ("200 OK", html)
+    } else {
+        ("404 Not Found", "")
+    };
+
+    let res = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/html; charset=utf-8\r\n\
+         Content-Security-Policy: {CONTENT_SECURITY_POLICY}\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len(),
+    );
+
+    stream.write_all(res.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(served)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        task::JoinHandle,
+    };
+
+    use email::message::Message;
+
+    use super::{accept, replace_cid, to_inlined_html, to_text, to_text_fallback};
+
+    const HTML: &str = "<p>Hello!</p>";
+
+    /// A newsletter made of layout tables, the way mailing services
+    /// generate them.
+    const NEWSLETTER: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Weekly digest</title>
+  <style type="text/css">
+    body { margin: 0; } .preheader { display: none; }
+  </style>
+  <!--[if mso]><xml><o:OfficeDocumentSettings></o:OfficeDocumentSettings></xml><![endif]-->
+</head>
+<body>
+  <table role="presentation" width="100%" cellpadding="0" cellspacing="0">
+    <tr>
+      <td align="center">
+        <img src="https://example.com/logo.png" alt="Example&nbsp;News" width="120">
+      </td>
+    </tr>
+    <tr>
+      <td>
+        <h1>This week at Example</h1>
+        <p>Hi Alice,<br>here are the   news you
+          missed &mdash; enjoy!</p>
+        <ul>
+          <li><a href="https://example.com/a?x=1&amp;y=2">Release 1.0 is out</a></li>
+          <li>Meetup in Paris &amp; Berlin</li>
+          <li><a href="https://example.com/jobs">https://example.com/jobs</a></li>
+        </ul>
+        <table>
+          <tr><th>Event</th><th>Date</th></tr>
+          <tr><td>Meetup</td><td>12/03</td></tr>
+        </table>
+        <p><a href="https://example.com/read" style="color: #fff"><img src="btn.png" alt=""></a></p>
+      </td>
+    </tr>
+    <tr>
+      <td class="footer">
+        <hr>
+        <p>&copy; 2024 Example &ndash; <a href="mailto:news@example.com">Contact us</a>
+        &middot; <a href="https://example.com/unsubscribe">Unsubscribe</a></p>
+        <script>track();</script>
+      </td>
+    </tr>
+  </table>
+</body>
+</html>
+"#;
+
+    async fn start(idle: Duration) -> (SocketAddr, JoinHandle<bool>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server =
+            tokio::spawn(async move { accept(listener, Arc::from(HTML), idle).await.unwrap() });
+
+        (addr, server)
+    }
+
+    async fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let req = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        stream.write_all(req.as_bytes()).await.unwrap();
+
+        let mut res = String::new();
+        stream.read_to_string(&mut res).await.unwrap();
+        res
+    }
+
+    #[tokio::test]
+    async fn serve_html_once() {
+        let (addr, server) = start(Duration::from_secs(5)).await;
+
+        // a connection opened in advance without request, like
+        // browsers do, does not hold the next ones
+        let _preconnect = TcpStream::connect(addr).await.unwrap();
+
+        let res = get(addr, "/favicon.ico").await;
+        assert!(res.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        // a connection closed before sending its request does not
+        // stop the server
+        drop(TcpStream::connect(addr).await.unwrap());
+
+        let res = get(addr, "/").await;
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(res.contains("Content-Security-Policy: "));
+        assert!(res.ends_with(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n{HTML}",
+            HTML.len()
+        )));
+
+        assert!(server.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn stop_when_idle() {
+        let (_, server) = start(Duration::from_millis(100)).await;
+        assert!(!server.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn stop_when_idle_despite_silent_connection() {
+        let (addr, server) = start(Duration::from_millis(100)).await;
+        let _silent = TcpStream::connect(addr).await.unwrap();
+        assert!(!server.await.unwrap());
+    }
+
+    #[test]
+    fn replace_whole_cids_only() {
+        let html = r#"<img src="cid:a"><img src='cid:ab'><div style="background: url(cid:a)"><img src=cid:a>"#;
+        let expected = r#"<img src="a.png"><img src='cid:ab'><div style="background: url(a.png)"><img src=a.png>"#;
+        assert_eq!(replace_cid(html, "a", "a.png"), expected);
+    }
+
+    #[test]
+    fn inline_cids_sharing_a_prefix() {
+        let msg = concat!(
+            "From: alice@localhost\n",
+            "Content-Type: multipart/related; boundary=b\n",
+            "\n",
+            "--b\n",
+            "Content-Type: text/html\n",
+            "\n",
+            "<img src=\"cid:a\"><img src=\"cid:ab\">\n",
+            "--b\n",
+            "Content-Type: image/png\n",
+            "Content-ID: <a>\n",
+            "Content-Transfer-Encoding: base64\n",
+            "\n",
+            "YQ==\n",
+            "--b\n",
+            "Content-Type: image/gif\n",
+            "Content-ID: <ab>\n",
+            "Content-Transfer-Encoding: base64\n",
+            "\n",
+            "YWI=\n",
+            "--b--\n",
+        );
+
+        let html = to_inlined_html(&Message::from(msg)).unwrap();
+        assert_eq!(
+            html.trim_end(),
+            r#"<img src="data:image/png;base64,YQ=="><img src="data:image/gif;base64,YWI=">"#
+        );
+    }
+
+    #[test]
+    fn render_newsletter() {
+        let expected = [
+            "Example News",
+            "",
+            "This week at Example",
+            "",
+            "Hi Alice,",
+            "here are the news you missed — enjoy!",
+            "",
+            "- Release 1.0 is out <https://example.com/a?x=1&y=2>",
+            "- Meetup in Paris & Berlin",
+            "- https://example.com/jobs",
+            "",
+            "Event Date",
+            "Meetup 12/03",
+            "",
+            "https://example.com/read",
+            "",
+            "© 2024 Example – Contact us <news@example.com> · Unsubscribe <https://example.com/unsubscribe>",
+        ];
+
+        assert_eq!(to_text(NEWSLETTER), expected.join("\n"));
+    }
+
+    #[test]
+    fn render_html_only_messages() {
+        let msg = format!(
+            "From: news@example.com\nContent-Type: text/html; charset=utf-8\n\n{NEWSLETTER}"
+        );
+        let text = to_text_fallback(&Message::from(msg.as_str())).unwrap();
+        assert_eq!(text, Some(to_text(NEWSLETTER)));
+
+        let msg = format!(
+            concat!(
+                "From: news@example.com\n",
+                "Content-Type: multipart/alternative; boundary=b\n",
+                "\n",
+                "--b\n",
+                "Content-Type: text/plain\n",
+                "\n",
+                "This week at Example\n",
+                "--b\n",
+                "Content-Type: text/html\n",
+                "\n",
+                "{}\n",
+                "--b--\n",
+            ),
+            NEWSLETTER
+        );
+        let text = to_text_fallback(&Message::from(msg.as_str())).unwrap();
+        assert_eq!(text, None);
+    }
+}
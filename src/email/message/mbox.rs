@@ -0,0 +1,209 @@
+//! Module dedicated to the mbox format.
+//!
+//! Messages are delimited by lines starting with `From `. Lines of
+//! message bodies starting with `From ` (optionally preceded by `>`
+//! characters) are quoted with an extra `>`, following the mboxrd
+//! variant.
+
+use std::io::{self, Write};
+
+use chrono::{DateTime, Utc};
+
+/// Split the given mbox content into raw messages.
+///
+/// Delimiter lines are removed, quoted `From ` lines are unquoted and
+/// lines are terminated by CRLF.
+pub fn split(mbox: &[u8]) -> Vec<Vec<u8>> {
+    let mut msgs = Vec::new();
+    let mut lines: Option<Vec<&[u8]>> = None;
+
+    // the last line feed terminates the last line, it does not start
+    // a new one
+    let mbox = mbox.strip_suffix(b"\n").unwrap_or(mbox);
+
+    for line in mbox.split(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        if line.starts_with(b"From ") {
+            if let Some(lines) = lines.take() {
+                msgs.push(join(lines));
+            }
+            lines = Some(Vec::new());
+            continue;
+        }
+
+        // content before the first delimiter is not part of any
+        // message
+        let Some(lines) = lines.as_mut() else {
+            continue;
+        };
+
+        lines.push(unquote(line));
+    }
+
+    if let Some(lines) = lines {
+        msgs.push(join(lines));
+    }
+
+    msgs
+}
+
+/// Write the given raw message to the given mbox.
+///
+/// The message is preceded by a delimiter line built from the given
+/// sender and date, its `From ` lines are quoted and its lines are
+/// terminated by LF.
+pub fn write(
+    mbox: &mut impl Write,
+    msg: &[u8],
+    sender: &str,
+    date: &DateTime<Utc>,
+) -> io::Result<()> {
+    let sender = if sender.is_empty() || sender.contains(char::is_whitespace) {
+        "MAILER-DAEMON"
+    } else {
+        sender
+    };
+
+    let date = date.format("%a %b %e %H:%M:%S %Y");
+    writeln!(mbox, "From {sender} {date}")?;
+
+    let msg = msg.strip_suffix(b"\n").unwrap_or(msg);
+
+    for line in msg.split(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        if is_from_line(line) {
+            mbox.write_all(b">")?;
+        }
+
+        mbox.write_all(line)?;
+        mbox.write_all(b"\n")?;
+    }
+
+    // the blank line preceding the next delimiter
+    mbox.write_all(b"\n")
+}
+
+/// Check if the given line starts with `From `, optionally preceded
+/// by `>` characters.
+fn is_from_line(line: &[u8]) -> bool {
+    let quoted = line.iter().position(|b| *b != b'>').unwrap_or(line.len());
+    line[quoted..].starts_with(b"From ")
+}
+
+fn unquote(line: &[u8]) -> &[u8] {
+    if line.starts_with(b">") && is_from_line(line) {
+        &line[1..]
+    } else {
+        line
+    }
+}
+
+fn join(mut lines: Vec<&[u8]>) -> Vec<u8> {
+    // the blank line preceding the next delimiter belongs to the
+    // mbox, not to the message
+    if lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+
+    let mut msg = Vec::new();
+
+    for line in lines {
+        msg.extend_from_slice(line);
+        msg.extend_from_slice(b"\r\n");
+    }
+
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{split, write};
+
+    #[test]
+    fn split_messages() {
+        let mbox = b"ignored preamble\n\
+                     From alice@localhost Mon Jan  1 00:00:00 2024\n\
+                     Subject: first\n\
+                     \n\
+                     >From the start\n\
+                     >>From the quote\n\
+                     > From a quote\n\
+                     \n\
+                     From bob@localhost Tue Jan  2 00:00:00 2024\n\
+                     Subject: second\n\
+                     \n\
+                     Bye\n\
+                     \n";
+
+        let msgs = split(mbox);
+
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(
+            msgs[0],
+            b"Subject: first\r\n\r\nFrom the start\r\n>From the quote\r\n> From a quote\r\n"
+        );
+        assert_eq!(msgs[1], b"Subject: second\r\n\r\nBye\r\n");
+    }
+
+    #[test]
+    fn split_crlf_messages() {
+        let mbox = b"From alice@localhost Mon Jan  1 00:00:00 2024\r\n\
+                     Subject: first\r\n\
+                     \r\n\
+                     >From CRLF\r\n\
+                     \r\n\
+                     From bob@localhost Tue Jan  2 00:00:00 2024\r\n\
+                     Subject: second\r\n\
+                     \r\n\
+                     Bye\r\n\
+                     \r\n";
+
+        let msgs = split(mbox);
+
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0], b"Subject: first\r\n\r\nFrom CRLF\r\n");
+        assert_eq!(msgs[1], b"Subject: second\r\n\r\nBye\r\n");
+    }
+
+    #[test]
+    fn write_then_split() {
+        let date = Utc.with_ymd_and_hms(2024, 1, 2, 9, 5, 0).unwrap();
+
+        let msgs: [&[u8]; 3] = [
+            b"Subject: first\r\n\r\nFrom the start\r\n>From the quote\r\n>>From deeper\r\n",
+            b"Subject: second\r\n\r\nFrom:not a delimiter\r\n\r\nFrom \r\n",
+            b"Subject: third\r\n\r\n",
+        ];
+
+        let mut mbox = Vec::new();
+        write(&mut mbox, msgs[0], "alice@localhost", &date).unwrap();
+        write(&mut mbox, msgs[1], "", &date).unwrap();
+        write(&mut mbox, msgs[2], "bob @localhost", &date).unwrap();
+
+        let expected = "From alice@localhost Tue Jan  2 09:05:00 2024\n\
+                        Subject: first\n\
+                        \n\
+                        >From the start\n\
+                        >>From the quote\n\
+                        >>>From deeper\n\
+                        \n\
+                        From MAILER-DAEMON Tue Jan  2 09:05:00 2024\n\
+                        Subject: second\n\
+                        \n\
+                        From:not a delimiter\n\
+                        \n\
+                        >From \n\
+                        \n\
+                        From MAILER-DAEMON Tue Jan  2 09:05:00 2024\n\
+                        Subject: third\n\
+                        \n\
+                        \n";
+
+        assert_eq!(String::from_utf8_lossy(&mbox), expected);
+        assert_eq!(split(&mbox), msgs);
+    }
+}
@@ -0,0 +1,235 @@
+//! Module dedicated to message disposition notifications (MDN).
+//!
+//! A read receipt is requested by adding a Disposition-Notification-To
+//! header to a message, see RFC 8098. Receipts are never sent
+//! automatically: they are built and sent on demand by the message
+//! mdn command, which then marks the original message so that its
+//! receipt is not sent twice.
+//!
+//! Backends supporting keywords (IMAP and notmuch) mark the message
+//! with the `$MDNSent` keyword. Other backends record the Message-ID
+//! of the message in a local file, one file per account under the
+//! data directory.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Local;
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use email::{account::config::AccountConfig, message::template::Template};
+use mail_parser::Message;
+use uuid::Uuid;
+
+use crate::{account::config::TomlAccountConfig, message::header};
+
+/// The header requesting a read receipt.
+pub const HEADER: &str = "Disposition-Notification-To";
+
+/// The keyword marking messages whose read receipt has been sent.
+pub const MDN_SENT: &str = "$MDNSent";
+
+/// Request a read receipt for the given template, to be sent to the
+/// given address, unless the template already requests one.
+pub fn request(tpl: &mut Template, email: &str) {
+    if !header::contains_tpl(tpl, HEADER) {
+        header::insert(tpl, &[(String::from(HEADER), email.to_owned())]);
+    }
+}
+
+/// Request a read receipt for the given raw message, to be sent to
+/// the given address, unless the message already requests one.
+pub fn request_raw(msg: &[u8], email: &str) -> Vec<u8> {
+    if header::contains(msg, HEADER) {
+        msg.to_vec()
+    } else {
+        header::append(msg, HEADER, email)
+    }
+}
+
+/// Get the address the read receipt of the given message should be
+/// sent to, if the message requests one.
+pub fn requested_by(msg: &Message) -> Option<String> {
+    let addr = msg.header_raw(HEADER)?;
+    let addr = addr.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!addr.is_empty()).then_some(addr)
+}
+
+/// Get the backend identifier of the given envelope, if the backend
+/// of the given account supports the `$MDNSent` keyword.
+#[allow(unused)]
+pub fn keyword_id(
+    toml_account_config: &TomlAccountConfig,
+    account_config: &AccountConfig,
+    folder: &str,
+    id: usize,
+) -> Result<Option<String>> {
+    #[cfg(any(feature = "imap", feature = "notmuch"))]
+    use pimalaya_tui::himalaya::config::Backend;
+
+    match &toml_account_config.backend {
+        #[cfg(feature = "imap")]
+        Some(Backend::Imap(_)) => Ok(Some(id.to_string())),
+        #[cfg(feature = "notmuch")]
+        Some(Backend::Notmuch(_)) => {
+            use pimalaya_tui::himalaya::id_mapper::IdMapper;
+
+            let id_mapper = IdMapper::new(account_config, folder)?;
+            Ok(Some(id_mapper.get_id(id)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Return `true` if the read receipt of the message matching the
+/// given Message-ID has been recorded as sent by the given account.
+pub fn is_recorded(account: &str, msg_id: &str) -> Result<bool> {
+    let path = record_path(account)?;
+
+    match fs::read_to_string(&path) {
+        Ok(ids) => Ok(ids.lines().any(|id| id == msg_id)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => {
+            let path = path.display();
+            Err(err).wrap_err_with(|| format!("cannot read sent read receipts at {path}"))
+        }
+    }
+}
+
+/// Record the read receipt of the message matching the given
+/// Message-ID as sent by the given account.
+pub fn record(account: &str, msg_id: &str) -> Result<()> {
+    let path = record_path(account)?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .wrap_err_with(|| format!("cannot create directory at {}", dir.display()))?;
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{msg_id}"))
+        .wrap_err_with(|| format!("cannot record sent read receipt at {}", path.display()))
+}
+
+/// Remove the read receipts recorded as sent by the given account.
+pub fn remove_records(account: &str) -> Result<()> {
+    let path = record_path(account)?;
+
+    match fs::remove_file(&path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            let path = path.display();
+            Err(err).wrap_err_with(|| format!("cannot remove sent read receipts at {path}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn record_path(account: &str) -> Result<PathBuf> {
+    let Some(dir) = dirs::data_dir() else {
+        bail!("cannot find data directory for the read receipts of account {account}");
+    };
+
+    Ok(dir.join("himalaya").join("mdn").join(account))
+}
+
+/// Build the read receipt of the given message, sent by the given
+/// account to the given address.
+///
+/// The receipt states that the message has been displayed, after an
+/// explicit action of the user.
+pub fn build(config: &AccountConfig, msg: &Message, to: &str) -> Vec<u8> {
+    let email = &config.email;
+    let domain = email.rsplit_once('@').map_or("localhost", |(_, d)| d);
+    let boundary = Uuid::new_v4().to_simple().to_string();
+
+    let from = match config.display_name.as_deref() {
+        Some(name) if !name.trim().is_empty() => format!("{} <{email}>", phrase(name)),
+        _ => email.clone(),
+    };
+
+    let subject = msg.subject().unwrap_or_default();
+    let date = msg.date().map(|date| date.to_rfc822());
+    let msg_id = msg.message_id().map(|id| format!("<{id}>"));
+
+    let mut headers = vec![
+        format!("From: {from}"),
+        format!("To: {to}"),
+        format!("Subject: {}", encode(&format!("Read: {subject}"))),
+        format!("Date: {}", Local::now().to_rfc2822()),
+        format!("Message-ID: <{}@{domain}>", Uuid::new_v4()),
+    ];
+
+    if let Some(id) = &msg_id {
+        headers.push(format!("In-Reply-To: {id}"));
+        headers.push(format!("References: {id}"));
+    }
+
+    headers.push(String::from("MIME-Version: 1.0"));
+    headers.push(format!(
+        "Content-Type: multipart/report; report-type=disposition-notification;\r\n boundary=\"{boundary}\""
+    ));
+
+    let mut text = format!("The message with subject \"{subject}\"");
+    if let Some(date) = &date {
+        text.push_str(&format!(" sent on {date}"));
+    }
+    text.push_str(" has been displayed.\r\n");
+    text.push_str("This is no guarantee that the message has been read or understood.\r\n");
+
+    let mut report = vec![
+        format!(
+            "Reporting-UA: {domain}; {}/{}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        ),
+        format!("Final-Recipient: rfc822; {email}"),
+    ];
+
+    if let Some(id) = &msg_id {
+        report.push(format!("Original-Message-ID: {id}"));
+    }
+
+    report.push(String::from(
+        "Disposition: manual-action/MDN-sent-manually; displayed",
+    ));
+
+    let mut mdn = headers.join("\r\n");
+    mdn.push_str("\r\n\r\n");
+    mdn.push_str(&format!("--{boundary}\r\n"));
+    mdn.push_str("Content-Type: text/plain; charset=utf-8\r\n");
+    mdn.push_str("Content-Transfer-Encoding: 8bit\r\n\r\n");
+    mdn.push_str(&text);
+    mdn.push_str(&format!("\r\n--{boundary}\r\n"));
+    mdn.push_str("Content-Type: message/disposition-notification\r\n\r\n");
+    mdn.push_str(&report.join("\r\n"));
+    mdn.push_str(&format!("\r\n\r\n--{boundary}--\r\n"));
+    mdn.into_bytes()
+}
+
+/// Encode the given header value as a MIME encoded word, if it is
+/// not plain ASCII.
+fn encode(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_owned()
+    } else {
+        format!("=?utf-8?b?{}?=", STANDARD.encode(value))
+    }
+}
+
+/// Format the given display name as a header phrase.
+fn phrase(name: &str) -> String {
+    if name.is_ascii() {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        encode(name)
+    }
+}
@@ -1,4 +1,14 @@
 pub mod arg;
 pub mod attachment;
 pub mod command;
+pub mod editor;
+pub mod forward;
+pub mod header;
+pub mod html;
+pub mod mbox;
+pub mod mdn;
+#[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+pub mod pgp;
+pub mod reply;
+pub mod send;
 pub mod template;
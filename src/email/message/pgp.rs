@@ -0,0 +1,363 @@
+//! Module dedicated to reading PGP messages.
+//!
+//! Both PGP/MIME (RFC 3156) and inline PGP messages are supported.
+//! Messages are decrypted and verified before being interpreted, so
+//! that failures can be reported instead of being silently skipped
+//! by the MML interpreter.
+
+use std::error::Error as StdError;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::{eyre::eyre, Result};
+use email::account::config::AccountConfig;
+use mail_parser::{MessageParser, MessagePart, MimeHeaders, PartType};
+use mml::pgp::Pgp;
+use tracing::debug;
+
+use crate::message::header;
+
+const BEGIN_MESSAGE: &str = "-----BEGIN PGP MESSAGE-----";
+const END_MESSAGE: &str = "-----END PGP MESSAGE-----";
+const BEGIN_SIGNED_MESSAGE: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+const BEGIN_SIGNATURE: &str = "-----BEGIN PGP SIGNATURE-----";
+const END_SIGNATURE: &str = "-----END PGP SIGNATURE-----";
+
+/// Content headers of messages rebuilt from inline PGP text parts.
+const TEXT_HEADERS: &[u8] =
+    b"Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n";
+
+/// Decrypt and verify the given raw message.
+///
+/// Returns the decrypted message, if the given one was encrypted or
+/// clear-signed, and a one-line verification status, if the given
+/// one was signed.
+pub async fn read(raw: &[u8], config: &AccountConfig) -> Result<(Option<Vec<u8>>, Option<String>)> {
+    let pgp = config.pgp.clone().map(Pgp::from);
+    let mut decrypted = decrypt(raw, &config.email, pgp.as_ref()).await?;
+
+    let Some(pgp) = pgp else {
+        return Ok((decrypted, None));
+    };
+
+    let msg = decrypted.as_deref().unwrap_or(raw);
+    let (verified, status) = verify(msg, &pgp).await;
+
+    if verified.is_some() {
+        decrypted = verified;
+    }
+
+    Ok((decrypted, status))
+}
+
+/// Decrypt the given raw message, if it is encrypted.
+async fn decrypt(raw: &[u8], recipient: &str, pgp: Option<&Pgp>) -> Result<Option<Vec<u8>>> {
+    let Some(msg) = MessageParser::new().parse(raw) else {
+        return Ok(None);
+    };
+
+    let root = msg.root_part();
+
+    match (&root.body, content_type(root).as_str()) {
+        (PartType::Multipart(ids), "multipart/encrypted") => {
+            let Some(encrypted_part) = ids.get(1).and_then(|id| msg.part(*id)) else {
+                return Ok(None);
+            };
+
+            let encrypted = encrypted_part.contents();
+            let clear_part = decrypt_bytes(encrypted, recipient, pgp).await?;
+            Ok(Some(replace_body(raw, &[], &clear_part)))
+        }
+        (PartType::Text(text), "text/plain") => {
+            let Some((before, block, after)) = find_block(text, BEGIN_MESSAGE, END_MESSAGE) else {
+                return Ok(None);
+            };
+
+            let clear_text = decrypt_bytes(block.as_bytes(), recipient, pgp).await?;
+            let body = [before.as_bytes(), &clear_text, after.as_bytes()].concat();
+            Ok(Some(replace_body(raw, TEXT_HEADERS, &body)))
+        }
+        _ => Ok(None),
+    }
+}
+
+async fn decrypt_bytes(encrypted: &[u8], recipient: &str, pgp: Option<&Pgp>) -> Result<Vec<u8>> {
+    let err = match pgp {
+        None => eyre!("PGP is not configured for this account"),
+        Some(pgp) => match pgp.decrypt(recipient, encrypted.to_vec()).await {
+            Ok(decrypted) => return Ok(decrypted),
+            Err(err) => eyre!(err),
+        },
+    };
+
+    let ids = key_ids(encrypted);
+
+    let err = if ids.is_empty() {
+        err.wrap_err("cannot decrypt PGP message")
+    } else {
+        let ids = ids.join(", ");
+        err.wrap_err(format!(
+            "cannot decrypt PGP message encrypted for key(s) {ids}"
+        ))
+    };
+
+    Err(err)
+}
+
+/// Verify the signature of the given raw message, if it is signed.
+///
+/// Returns the message without its signature, if it was
+/// clear-signed, and the verification status.
+async fn verify(raw: &[u8], pgp: &Pgp) -> (Option<Vec<u8>>, Option<String>) {
+    let Some(msg) = MessageParser::new().parse(raw) else {
+        return (None, None);
+    };
+
+    let sender = msg
+        .from()
+        .and_then(|from| from.first())
+        .and_then(|addr| addr.address())
+        .unwrap_or_default()
+        .to_owned();
+
+    let root = msg.root_part();
+
+    match (&root.body, content_type(root).as_str()) {
+        (PartType::Multipart(ids), "multipart/signed") => {
+            let (Some(signed_part), Some(signature_part)) = (
+                ids.first().and_then(|id| msg.part(*id)),
+                ids.get(1).and_then(|id| msg.part(*id)),
+            ) else {
+                return (None, None);
+            };
+
+            let signed =
+                raw[signed_part.raw_header_offset()..signed_part.raw_end_offset()].to_vec();
+            let signature = signature_part.contents().to_vec();
+            let res = pgp.verify(&sender, signature, signed).await;
+
+            (None, Some(status(&sender, res)))
+        }
+        (PartType::Text(text), "text/plain") => {
+            let Some((before, block, after)) =
+                find_block(text, BEGIN_SIGNED_MESSAGE, END_SIGNATURE)
+            else {
+                return (None, None);
+            };
+
+            let res = pgp
+                .verify(&sender, block.as_bytes().to_vec(), Vec::new())
+                .await;
+            let body = [before, &unsign(block), after].concat();
+            let msg = replace_body(raw, TEXT_HEADERS, body.as_bytes());
+
+            (Some(msg), Some(status(&sender, res)))
+        }
+        _ => (None, None),
+    }
+}
+
+/// Build the one-line verification status from the given verify
+/// result.
+fn status(sender: &str, res: mml::Result<()>) -> String {
+    match res {
+        Ok(()) => format!("PGP: good signature from {sender}"),
+        Err(err) if is_unknown_key(&err) => {
+            debug!("cannot verify signature: {err:?}");
+            format!("PGP: cannot verify signature from {sender}, unknown key")
+        }
+        Err(err) => {
+            debug!("cannot verify signature: {err:?}");
+            format!("PGP: bad signature from {sender}")
+        }
+    }
+}
+
+/// Check if the given verify error is due to a missing public key.
+fn is_unknown_key(err: &mml::Error) -> bool {
+    if matches!(err, mml::Error::FindPgpPublicKeyError(_)) {
+        return true;
+    }
+
+    // other backends only expose the missing key in their messages
+    let mut err: Option<&dyn StdError> = Some(err);
+
+    while let Some(source) = err {
+        if source.to_string().contains("No public key") {
+            return true;
+        }
+        err = source.source();
+    }
+
+    false
+}
+
+/// Replace the content headers and the body of the given raw
+/// message.
+///
+/// The given body can start with its own content headers.
+fn replace_body(raw: &[u8], content_headers: &[u8], body: &[u8]) -> Vec<u8> {
+    let (headers, _) = header::split(raw);
+
+    let mut msg = Vec::new();
+
+    for header in headers {
+        if !header::is_content(header) {
+            msg.extend_from_slice(header);
+        }
+    }
+
+    msg.extend_from_slice(content_headers);
+
+    if !content_headers.is_empty() {
+        msg.extend_from_slice(b"\r\n");
+    }
+
+    msg.extend_from_slice(body);
+    msg
+}
+
+fn content_type(part: &MessagePart) -> String {
+    match part.content_type() {
+        Some(ctype) => {
+            let subtype = ctype.subtype().unwrap_or_default();
+            format!("{}/{subtype}", ctype.ctype()).to_lowercase()
+        }
+        None => String::from("text/plain"),
+    }
+}
+
+/// Find the armored block delimited by the given lines in the given
+/// text, and split the text around it.
+fn find_block<'a>(text: &'a str, begin: &str, end: &str) -> Option<(&'a str, &'a str, &'a str)> {
+    let start = text.find(begin)?;
+    let end = start + text[start..].find(end)? + end.len();
+
+    Some((&text[..start], &text[start..end], &text[end..]))
+}
+
+/// Extract the text of the given clear-signed block.
+fn unsign(block: &str) -> String {
+    let mut lines = block.lines();
+    let mut text = Vec::new();
+
+    // skip the armor header line and the armor headers
+    for line in lines.by_ref() {
+        if line.trim().is_empty() {
+            break;
+        }
+    }
+
+    for line in lines {
+        if line.starts_with(BEGIN_SIGNATURE) {
+            break;
+        }
+
+        text.push(line.strip_prefix("- ").unwrap_or(line));
+    }
+
+    text.join("\n")
+}
+
+/// Get the ids of the keys the given PGP message was encrypted for.
+///
+/// Ids are read from the public-key encrypted session key packets
+/// preceding the encrypted data. Anonymous recipients have the id
+/// `0000000000000000`.
+fn key_ids(encrypted: &[u8]) -> Vec<String> {
+    let data = dearmor(encrypted).unwrap_or_else(|| encrypted.to_vec());
+
+    let mut ids = Vec::new();
+    let mut offset = 0;
+
+    while let Some((tag, body, next)) = next_packet(&data, offset) {
+        match tag {
+            // only v3 packets contain a key id
+            1 if body.first() == Some(&3) && body.len() >= 9 => {
+                let mut id = String::new();
+                for b in &body[1..9] {
+                    id.push_str(&format!("{b:02X}"));
+                }
+                ids.push(id);
+            }
+            1 | 3 => (),
+            _ => break,
+        }
+
+        offset = next;
+    }
+
+    ids
+}
+
+/// Read the packet starting at the given offset.
+///
+/// Returns the packet tag, its body and the offset of the next
+/// packet.
+fn next_packet(data: &[u8], offset: usize) -> Option<(u8, &[u8], usize)> {
+    let header = *data.get(offset)?;
+
+    if header & 0x80 == 0 {
+        return None;
+    }
+
+    let (tag, len, start) = if header & 0x40 == 0 {
+        // old format
+        let tag = (header >> 2) & 0x0f;
+        let size = match header & 0x03 {
+            0 => 1,
+            1 => 2,
+            2 => 4,
+            _ => return None,
+        };
+        let len = data
+            .get(offset + 1..offset + 1 + size)?
+            .iter()
+            .fold(0, |len, b| (len << 8) | *b as usize);
+        (tag, len, offset + 1 + size)
+    } else {
+        // new format, partial body lengths are never used by session
+        // key packets
+        let tag = header & 0x3f;
+        match *data.get(offset + 1)? as usize {
+            len @ 0..=191 => (tag, len, offset + 2),
+            len @ 192..=223 => {
+                let next = *data.get(offset + 2)? as usize;
+                (tag, ((len - 192) << 8) + next + 192, offset + 3)
+            }
+            255 => {
+                let len = data
+                    .get(offset + 2..offset + 6)?
+                    .iter()
+                    .fold(0, |len, b| (len << 8) | *b as usize);
+                (tag, len, offset + 6)
+            }
+            _ => return None,
+        }
+    };
+
+    let body = data.get(start..start + len)?;
+    Some((tag, body, start + len))
+}
+
+/// Decode the given ASCII-armored data.
+fn dearmor(data: &[u8]) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(data).ok()?;
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| !line.starts_with("-----BEGIN PGP"))
+        .skip(1);
+
+    // armor headers end with a blank line
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+    }
+
+    let base64: String = lines
+        .take_while(|line| !line.starts_with('=') && !line.starts_with("-----"))
+        .collect();
+
+    STANDARD.decode(base64).ok()
+}
@@ -0,0 +1,416 @@
+//! Module dedicated to reply recipients and threading headers.
+//!
+//! Recipients of reply templates are computed by the CLI rather than
+//! by the template builder: the Reply-To header only replaces the
+//! sender, and all the addresses of the account are left out, not
+//! only its main one. The template builder does not set the
+//! References header either, see [`thread`].
+
+use color_eyre::{eyre::bail, Result};
+use email::message::{template::Template, Message};
+use mail_parser::Address as ParsedAddress;
+
+use crate::{
+    address::{self, Address},
+    message::header,
+};
+
+/// The maximum length of the References header line, as advised by
+/// RFC 5322.
+const REFERENCES_MAX_LEN: usize = 998;
+
+/// The recipients of a reply.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Recipients {
+    pub to: Vec<Address>,
+    pub cc: Vec<Address>,
+}
+
+impl Recipients {
+    /// Compute the recipients of a reply to the given message.
+    ///
+    /// The reply goes to the Reply-To addresses of the message, or
+    /// to its sender when it has none. When replying to all, the To
+    /// addresses are added to the recipients and the Cc addresses
+    /// are kept in copy. When replying to the mailing list, the
+    /// reply goes to the List-Post address, and the other recipients
+    /// are put in copy when replying to all.
+    ///
+    /// The given own addresses are never recipients, and addresses
+    /// are deduplicated case-insensitively. When the message was
+    /// sent by one of the own addresses, the reply goes to its
+    /// recipients instead.
+    pub fn new(msg: &Message, own: &[&str], all: bool, list: bool) -> Result<Self> {
+        let parsed = msg.parsed()?;
+
+        let sender = parsed
+            .reply_to()
+            .or_else(|| parsed.from())
+            .or_else(|| parsed.sender());
+
+        let mut recipients = Self::default();
+
+        if list {
+            let Some(list) = list_post(parsed.header_raw("List-Post")) else {
+                bail!("cannot reply to mailing list: message has no List-Post address");
+            };
+
+            recipients.push_to(own, [list]);
+
+            if all {
+                recipients.push_cc(own, addresses(sender));
+                recipients.push_cc(own, addresses(parsed.to()));
+                recipients.push_cc(own, addresses(parsed.cc()));
+            }
+        } else {
+            recipients.push_to(own, addresses(sender));
+
+            if all || recipients.to.is_empty() {
+                recipients.push_to(own, addresses(parsed.to()));
+            }
+
+            if all {
+                recipients.push_cc(own, addresses(parsed.cc()));
+            }
+        }
+
+        Ok(recipients)
+    }
+
+    /// Set the To and Cc headers of the given template.
+    pub fn set(&self, tpl: &mut Template) {
+        header::replace(tpl, "To", &join(&self.to));
+        header::replace(tpl, "Cc", &join(&self.cc));
+    }
+
+    fn push_to(&mut self, own: &[&str], addrs: impl IntoIterator<Item = Address>) {
+        let addrs = self.filter(own, addrs);
+        address::merge(&mut self.to, addrs);
+    }
+
+    fn push_cc(&mut self, own: &[&str], addrs: impl IntoIterator<Item = Address>) {
+        let addrs = self.filter(own, addrs);
+        address::merge(&mut self.cc, addrs);
+    }
+
+    /// Keep the given addresses that are neither own addresses nor
+    /// already recipients.
+    fn filter(&self, own: &[&str], addrs: impl IntoIterator<Item = Address>) -> Vec<Address> {
+        addrs
+            .into_iter()
+            .filter(|addr| {
+                let is = |email: &str| email.eq_ignore_ascii_case(&addr.email);
+                !own.iter().any(|email| is(email))
+                    && !self.to.iter().any(|a| is(&a.email))
+                    && !self.cc.iter().any(|a| is(&a.email))
+            })
+            .collect()
+    }
+}
+
+/// Set the In-Reply-To and References headers of the given reply
+/// template.
+///
+/// In-Reply-To holds the Message-ID of the message, which is
+/// appended to its References (or to its In-Reply-To, when it has
+/// no References). Both headers are removed when the message has no
+/// Message-ID.
+pub fn thread(tpl: &mut Template, msg: &Message) -> Result<()> {
+    let parsed = msg.parsed()?;
+
+    let Some(id) = parsed.message_id().filter(|id| !id.trim().is_empty()) else {
+        header::replace(tpl, "In-Reply-To", "");
+        header::replace(tpl, "References", "");
+        return Ok(());
+    };
+
+    let parents = parsed
+        .references()
+        .as_text_list()
+        .or_else(|| parsed.in_reply_to().as_text_list())
+        .unwrap_or_default();
+
+    header::replace(tpl, "In-Reply-To", &format!("<{}>", id.trim()));
+    header::replace(tpl, "References", &references(&parents, id).join(" "));
+
+    Ok(())
+}
+
+/// Build the References of a reply to the message with the given id
+/// and the given References.
+///
+/// When the header line gets too long, the ids following the first
+/// one are dropped, oldest first, so that the root of the thread and
+/// the most recent ids are kept.
+fn references(parents: &[&str], id: &str) -> Vec<String> {
+    let mut refs: Vec<String> = Vec::new();
+
+    for parent in parents.iter().chain([&id]) {
+        let parent = format!("<{}>", parent.trim());
+
+        if parent.len() > 2 && !refs.contains(&parent) {
+            refs.push(parent);
+        }
+    }
+
+    let len =
+        |refs: &[String]| "References: ".len() + refs.iter().map(|id| id.len() + 1).sum::<usize>();
+
+    while refs.len() > 2 && len(&refs) > REFERENCES_MAX_LEN {
+        refs.remove(1);
+    }
+
+    refs
+}
+
+/// Collect the addresses of the given parsed header value.
+fn addresses(addrs: Option<&ParsedAddress>) -> Vec<Address> {
+    let Some(addrs) = addrs else {
+        return Vec::new();
+    };
+
+    addrs
+        .iter()
+        .filter_map(|addr| {
+            let email = addr.address.as_deref()?.trim();
+
+            if email.is_empty() {
+                return None;
+            }
+
+            Some(Address {
+                name: addr
+                    .name
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(ToOwned::to_owned),
+                email: email.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Extract the posting address of the given List-Post header value.
+///
+/// The value is a list of URLs between angle brackets, or `NO` when
+/// posting to the list is not allowed.
+fn list_post(val: Option<&str>) -> Option<Address> {
+    val?.split(['<', '>'])
+        .map(str::trim)
+        .find_map(|url| url.strip_prefix("mailto:"))
+        .map(|email| email.split('?').next().unwrap_or_default())
+        .filter(|email| email.contains('@'))
+        .map(|email| Address {
+            name: None,
+            email: email.to_owned(),
+        })
+}
+
+/// Join the given addresses into a header value.
+///
+/// Names containing special characters are quoted.
+fn join(addrs: &[Address]) -> String {
+    addrs
+        .iter()
+        .map(|addr| match &addr.name {
+            Some(name) if name.contains(|c| "()<>[]:;@\\,.\"".contains(c)) => {
+                let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+                format!("\"{name}\" <{}>", addr.email)
+            }
+            _ => addr.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use email::message::{template::Template, Message};
+
+    use super::Recipients;
+    use crate::{address::Address, message::header};
+
+    const OWN: [&str; 2] = ["me@localhost", "me@alias.localhost"];
+
+    /// Compute the recipients of a reply to the given raw message,
+    /// then return their emails.
+    fn recipients(msg: &str, all: bool, list: bool) -> (Vec<String>, Vec<String>) {
+        let recipients = Recipients::new(&Message::from(msg), &OWN, all, list).unwrap();
+        let emails = |addrs: Vec<Address>| addrs.into_iter().map(|addr| addr.email).collect();
+        (emails(recipients.to), emails(recipients.cc))
+    }
+
+    #[test]
+    fn reply_to_sender() {
+        let msg = "From: alice@localhost\nTo: me@localhost, bob@localhost\n\
+                   Cc: carol@localhost\n\nHello\n";
+
+        let (to, cc) = recipients(msg, false, false);
+
+        assert_eq!(to, ["alice@localhost"]);
+        assert!(cc.is_empty());
+    }
+
+    #[test]
+    fn reply_all_when_in_to() {
+        let msg = "From: alice@localhost\nTo: ME@localhost, bob@localhost\n\
+                   Cc: carol@localhost, Alice@Localhost\n\nHello\n";
+
+        let (to, cc) = recipients(msg, true, false);
+
+        assert_eq!(to, ["alice@localhost", "bob@localhost"]);
+        assert_eq!(cc, ["carol@localhost"]);
+    }
+
+    #[test]
+    fn reply_all_when_in_cc() {
+        let msg = "From: alice@localhost\nTo: bob@localhost\n\
+                   Cc: me@alias.localhost, carol@localhost, BOB@localhost\n\nHello\n";
+
+        let (to, cc) = recipients(msg, true, false);
+
+        assert_eq!(to, ["alice@localhost", "bob@localhost"]);
+        assert_eq!(cc, ["carol@localhost"]);
+    }
+
+    #[test]
+    fn reply_all_with_reply_to_elsewhere() {
+        let msg = "From: alice@localhost\nReply-To: team@localhost\n\
+                   To: me@localhost, bob@localhost\n\nHello\n";
+
+        let (to, cc) = recipients(msg, false, false);
+        assert_eq!(to, ["team@localhost"]);
+        assert!(cc.is_empty());
+
+        let (to, cc) = recipients(msg, true, false);
+        assert_eq!(to, ["team@localhost", "bob@localhost"]);
+        assert!(cc.is_empty());
+    }
+
+    #[test]
+    fn reply_to_own_message() {
+        let msg = "From: me@localhost\nTo: alice@localhost\nCc: bob@localhost\n\nHello\n";
+
+        let (to, cc) = recipients(msg, false, false);
+
+        assert_eq!(to, ["alice@localhost"]);
+        assert!(cc.is_empty());
+    }
+
+    #[test]
+    fn reply_to_list() {
+        let msg = "From: alice@localhost\nTo: list@localhost, me@localhost\n\
+                   Cc: bob@localhost\n\
+                   List-Post: <mailto:list@localhost?subject=list>\n\nHello\n";
+
+        let (to, cc) = recipients(msg, false, true);
+        assert_eq!(to, ["list@localhost"]);
+        assert!(cc.is_empty());
+
+        let (to, cc) = recipients(msg, true, true);
+        assert_eq!(to, ["list@localhost"]);
+        assert_eq!(cc, ["alice@localhost", "bob@localhost"]);
+    }
+
+    #[test]
+    fn reply_to_list_without_list_post() {
+        let msg = Message::from("From: alice@localhost\nTo: me@localhost\n\nHello\n");
+        assert!(Recipients::new(&msg, &OWN, false, true).is_err());
+    }
+
+    #[test]
+    fn set_recipients_headers() {
+        let msg = "From: \"Doe, Alice\" <alice@localhost>\nTo: me@localhost\n\
+                   Cc: Bob <bob@localhost>\n\nHello\n";
+        let recipients = Recipients::new(&Message::from(msg), &OWN, true, false).unwrap();
+        let mut tpl = Template::new("To: me@localhost\nCc: \nSubject: Re\n\nHi\n");
+
+        recipients.set(&mut tpl);
+
+        let to = header::get(tpl.as_bytes(), "To");
+        assert_eq!(to.as_deref(), Some("\"Doe, Alice\" <alice@localhost>"));
+        let cc = header::get(tpl.as_bytes(), "Cc");
+        assert_eq!(cc.as_deref(), Some("Bob <bob@localhost>"));
+    }
+
+    /// Build the reply template of the given raw message, with its
+    /// threading headers.
+    fn reply(msg: &str) -> Template {
+        let mut tpl =
+            Template::new("From: bob@localhost\nTo: alice@localhost\nSubject: Re\n\nHi\n");
+        super::thread(&mut tpl, &Message::from(msg)).unwrap();
+        tpl
+    }
+
+    /// Turn the given reply template into a message with the given id.
+    fn send(tpl: Template, id: &str) -> String {
+        format!("Message-ID: {id}\n{}", tpl.content)
+    }
+
+    fn get(tpl: &Template, name: &str) -> Option<String> {
+        header::get(tpl.as_bytes(), name)
+    }
+
+    #[test]
+    fn reply_chain() {
+        let a = "Message-ID: <a@localhost>\nFrom: alice@localhost\nSubject: A\n\nHello\n";
+
+        let b = reply(a);
+        assert_eq!(get(&b, "In-Reply-To").as_deref(), Some("<a@localhost>"));
+        assert_eq!(get(&b, "References").as_deref(), Some("<a@localhost>"));
+
+        let c = reply(&send(b, "<b@localhost>"));
+        assert_eq!(get(&c, "In-Reply-To").as_deref(), Some("<b@localhost>"));
+        assert_eq!(
+            get(&c, "References").as_deref(),
+            Some("<a@localhost> <b@localhost>")
+        );
+
+        let d = reply(&send(c, "<c@localhost>"));
+        assert_eq!(get(&d, "In-Reply-To").as_deref(), Some("<c@localhost>"));
+        assert_eq!(
+            get(&d, "References").as_deref(),
+            Some("<a@localhost> <b@localhost> <c@localhost>")
+        );
+    }
+
+    #[test]
+    fn fall_back_to_in_reply_to() {
+        let msg = "Message-ID: <b@localhost>\nIn-Reply-To: <a@localhost>\n\nHello\n";
+
+        let tpl = reply(msg);
+
+        assert_eq!(
+            get(&tpl, "References").as_deref(),
+            Some("<a@localhost> <b@localhost>")
+        );
+    }
+
+    #[test]
+    fn omit_headers_without_message_id() {
+        let msg = "References: <a@localhost>\nIn-Reply-To: <a@localhost>\n\nHello\n";
+        let mut tpl = Template::new("In-Reply-To: <a@localhost>\nSubject: Re\n\nHi\n");
+
+        super::thread(&mut tpl, &Message::from(msg)).unwrap();
+
+        assert_eq!(get(&tpl, "In-Reply-To"), None);
+        assert_eq!(get(&tpl, "References"), None);
+    }
+
+    #[test]
+    fn shorten_long_references() {
+        let ids: Vec<String> = (0..60)
+            .map(|i| format!("message-{i:02}@localhost"))
+            .collect();
+        let parents: Vec<&str> = ids.iter().map(String::as_str).collect();
+
+        let refs = super::references(&parents, "reply@localhost");
+        let line = format!("References: {}", refs.join(" "));
+
+        assert!(line.len() <= super::REFERENCES_MAX_LEN, "{}", line.len());
+        assert_eq!(refs[0], "<message-00@localhost>");
+        assert_eq!(refs[refs.len() - 2], "<message-59@localhost>");
+        assert_eq!(refs[refs.len() - 1], "<reply@localhost>");
+    }
+}
@@ -0,0 +1,171 @@
+//! Module dedicated to sending messages from a directory.
+//!
+//! Each `.eml` file of the directory is a message. Once sent, a file
+//! is moved to the `sent` subdirectory, so that sending the same
+//! directory again only sends the remaining files.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::WrapErr, Result};
+use tracing::debug;
+
+/// The name of the subdirectory sent files are moved to.
+pub const SENT_DIR: &str = "sent";
+
+/// The messages of a directory.
+#[derive(Debug, Default)]
+pub struct MessageFiles {
+    /// The files waiting to be sent, sorted by name.
+    pub pending: Vec<PathBuf>,
+
+    /// The files skipped because a file with the same name was
+    /// already sent.
+    pub skipped: Vec<PathBuf>,
+}
+
+/// List the `.eml` files of the given directory.
+///
+/// Subdirectories and files with another extension are ignored.
+pub fn list(dir: &Path) -> Result<MessageFiles> {
+    let sent_dir = dir.join(SENT_DIR);
+    let mut files = MessageFiles::default();
+
+    let entries =
+        fs::read_dir(dir).wrap_err_with(|| format!("cannot read directory {}", dir.display()))?;
+
+    for entry in entries {
+        let path = entry
+            .wrap_err_with(|| format!("cannot read directory {}", dir.display()))?
+            .path();
+
+        let is_eml = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("eml"));
+
+        if !path.is_file() || !is_eml {
+            debug!("ignoring {}", path.display());
+            continue;
+        }
+
+        match path.file_name() {
+            Some(name) if sent_dir.join(name).exists() => files.skipped.push(path),
+            _ => files.pending.push(path),
+        }
+    }
+
+    files.pending.sort();
+    files.skipped.sort();
+
+    Ok(files)
+}
+
+/// Move the given sent file to the `sent` subdirectory.
+pub fn mark_sent(path: &Path) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let sent_dir = dir.join(SENT_DIR);
+
+    fs::create_dir_all(&sent_dir)
+        .wrap_err_with(|| format!("cannot create directory {}", sent_dir.display()))?;
+
+    let name = path.file_name().unwrap_or_default();
+    let dest = sent_dir.join(name);
+
+    fs::rename(path, &dest)
+        .wrap_err_with(|| format!("cannot move {} to {}", path.display(), dest.display()))
+}
+
+/// The display name of the given file.
+pub fn file_name(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use uuid::Uuid;
+
+    use super::{list, mark_sent, SENT_DIR};
+
+    /// A temporary directory of messages, removed once dropped.
+    struct Dir(PathBuf);
+
+    impl Dir {
+        fn new(files: &[&str]) -> Self {
+            let dir = temp_dir().join(format!("himalaya-send-dir-{}", Uuid::new_v4()));
+            fs::create_dir_all(dir.join(SENT_DIR)).unwrap();
+
+            for file in files {
+                fs::write(dir.join(file), "Subject: test\r\n\r\ntest\r\n").unwrap();
+            }
+
+            Self(dir)
+        }
+
+        fn names(&self, paths: &[PathBuf]) -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| path.strip_prefix(&self.0).unwrap())
+                .map(|path| path.to_str().unwrap().to_owned())
+                .collect()
+        }
+    }
+
+    impl Drop for Dir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn list_eml_files_by_name() {
+        let dir = Dir::new(&["2.eml", "10.eml", "1.EML", "notes.txt", "eml"]);
+        fs::create_dir(dir.0.join("3.eml")).unwrap();
+
+        let files = list(&dir.0).unwrap();
+
+        assert_eq!(dir.names(&files.pending), ["1.EML", "10.eml", "2.eml"]);
+        assert!(files.skipped.is_empty());
+    }
+
+    #[test]
+    fn skip_sent_files() {
+        let dir = Dir::new(&["1.eml", "2.eml", "sent/2.eml", "sent/3.eml"]);
+
+        let files = list(&dir.0).unwrap();
+
+        assert_eq!(dir.names(&files.pending), ["1.eml"]);
+        assert_eq!(dir.names(&files.skipped), ["2.eml"]);
+    }
+
+    #[test]
+    fn move_sent_files() {
+        let dir = Dir::new(&["1.eml"]);
+        fs::remove_dir(dir.0.join(SENT_DIR)).unwrap();
+
+        mark_sent(&dir.0.join("1.eml")).unwrap();
+
+        assert!(!dir.0.join("1.eml").exists());
+        assert!(dir.0.join("sent/1.eml").is_file());
+        assert!(list(&dir.0).unwrap().pending.is_empty());
+    }
+
+    #[test]
+    fn refuse_missing_directory() {
+        let err = list(Path::new("/nonexistent/himalaya")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot read directory /nonexistent/himalaya"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Module dedicated to dry runs of message sending.
+//!
+//! A dry run prepares a message the same way the sending backend of
+//! the account does, then shows the result instead of transmitting
+//! it: the pre-send hooks are executed, the envelope is resolved and
+//! the Bcc header is removed when the backend would remove it.
+
+use std::fmt;
+
+use color_eyre::{eyre::bail, Result};
+use email::account::config::AccountConfig;
+use mail_parser::MessageParser;
+use pimalaya_tui::himalaya::config::SendingBackend;
+use serde::Serialize;
+
+use crate::{
+    account::config::{TomlAccountConfig, TomlAccountExtraConfig},
+    message::{
+        header,
+        send::{self, hook},
+    },
+};
+
+/// A message prepared for sending, but not sent.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DryRun {
+    /// The sending backend that would send the message.
+    pub backend: String,
+    /// The envelope sender of the message.
+    pub sender: String,
+    /// The envelope recipients of the message.
+    pub recipients: Vec<String>,
+    /// The raw message, as it would be transmitted.
+    pub message: String,
+}
+
+impl fmt::Display for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Backend: {}", self.backend)?;
+        writeln!(f, "Sender: {}", self.sender)?;
+        writeln!(f, "Recipients: {}", self.recipients.join(", "))?;
+        writeln!(f)?;
+        write!(f, "{}", self.message)
+    }
+}
+
+/// A batch of messages prepared for sending, but not sent.
+#[derive(Clone, Debug, Serialize)]
+#[serde(transparent)]
+pub struct DryRuns(pub Vec<DryRun>);
+
+impl fmt::Display for DryRuns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.0.len();
+
+        for (i, dry_run) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            writeln!(f, "Message {}/{total}", i + 1)?;
+            writeln!(f, "{dry_run}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Prepare the given raw message for sending, without sending it.
+///
+/// Fails when the message could not be sent, for example when its
+/// sender or one of its recipients cannot be parsed.
+#[cfg_attr(not(feature = "sendmail"), allow(unused_variables))]
+#[cfg_attr(
+    not(any(feature = "smtp", feature = "sendmail")),
+    allow(unreachable_code)
+)]
+pub async fn prepare(
+    toml_account_config: &TomlAccountConfig,
+    account_config: &AccountConfig,
+    extra_config: &TomlAccountExtraConfig,
+    msg: &[u8],
+) -> Result<DryRun> {
+    let sending_backend = toml_account_config
+        .message
+        .as_ref()
+        .and_then(|config| config.send.as_ref())
+        .and_then(|config| config.backend.as_ref());
+
+    let (backend, remove_bcc): (&str, bool) = match sending_backend {
+        #[cfg(feature = "smtp")]
+        Some(SendingBackend::Smtp(_)) => ("smtp", true),
+        #[cfg(feature = "sendmail")]
+        Some(SendingBackend::Sendmail(_)) => {
+            use super::sendmail::SendmailRecipients;
+
+            let remove_bcc = extra_config.sendmail_recipients() == SendmailRecipients::Args;
+            ("sendmail", remove_bcc)
+        }
+        Some(SendingBackend::None) | None => {
+            bail!("cannot send message: no sending backend configured")
+        }
+    };
+
+    let msg = hook::run(&hook::commands(extra_config, account_config), msg).await?;
+
+    send::validate(&msg)?;
+
+    let Some(parsed) = MessageParser::new().parse_headers(&msg) else {
+        bail!("cannot parse message");
+    };
+
+    let sender = parsed
+        .from()
+        .and_then(|addrs| addrs.iter().next())
+        .and_then(|addr| addr.address.as_deref())
+        .map(str::trim)
+        .unwrap_or_default();
+
+    let recipients = send::recipients(&parsed);
+
+    let msg = if remove_bcc {
+        header::remove(&msg, "Bcc")
+    } else {
+        msg.clone()
+    };
+
+    Ok(DryRun {
+        backend: backend.to_owned(),
+        sender: sender.to_owned(),
+        recipients,
+        message: String::from_utf8_lossy(&msg).into_owned(),
+    })
+}
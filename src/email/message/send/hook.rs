@@ -0,0 +1,289 @@
+//! Module dedicated to the pre-send hooks.
+//!
+//! The pre-send hooks are commands run over every outgoing message,
+//! right before it is given to the sending backend: after templates
+//! are compiled, attachments embedded and messages signed or
+//! encrypted, and before the removal of the Bcc header. The
+//! hooks.pre-send command runs first, then the message.send.pre-hook
+//! command. The raw message is piped to each command, whose output
+//! replaces the message when not empty. The message is not sent if a
+//! command fails.
+
+use std::{any::Any, error, fmt, sync::Arc};
+
+use async_trait::async_trait;
+use color_eyre::Report;
+use email::{
+    account::config::AccountConfig, backend::feature::BackendFeature, message::send::SendMessage,
+    AnyBoxedError, AnyError, AnyResult,
+};
+use pimalaya_tui::himalaya::backend::Context;
+use process::Command;
+use tracing::debug;
+
+use crate::account::config::TomlAccountExtraConfig;
+
+/// The error of a pre-send hook.
+#[derive(Debug)]
+pub enum HookError {
+    /// The command exited with a non-zero status.
+    Rejected(String, i32, String),
+    /// The command could not be executed.
+    Execute(String, process::Error),
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rejected(cmd, code, stderr) if stderr.trim().is_empty() => {
+                write!(
+                    f,
+                    "message rejected by pre-send hook `{cmd}` (exit code {code})"
+                )
+            }
+            Self::Rejected(cmd, code, stderr) => write!(
+                f,
+                "message rejected by pre-send hook `{cmd}` (exit code {code}): {}",
+                stderr.trim()
+            ),
+            Self::Execute(cmd, err) => write!(f, "cannot execute pre-send hook `{cmd}`: {err}"),
+        }
+    }
+}
+
+impl error::Error for HookError {}
+
+impl AnyError for HookError {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Get the pre-send hooks of the given account, in the order they
+/// run.
+pub fn commands(config: &TomlAccountExtraConfig, account_config: &AccountConfig) -> Vec<Command> {
+    let legacy = account_config.find_message_pre_send_hook();
+    config
+        .pre_send_hook()
+        .into_iter()
+        .chain(legacy)
+        .cloned()
+        .collect()
+}
+
+/// Disable the pre-send hooks of the given account.
+pub fn disable(config: &mut TomlAccountExtraConfig, account_config: &mut AccountConfig) {
+    config.hooks = None;
+    take_legacy(account_config);
+}
+
+/// Take the message.send.pre-hook command out of the given account
+/// configuration, so that sending backends do not run it by
+/// themselves.
+pub fn take_legacy(account_config: &mut AccountConfig) -> Option<Command> {
+    account_config
+        .message
+        .as_mut()
+        .and_then(|config| config.send.as_mut())
+        .and_then(|config| config.pre_hook.take())
+}
+
+/// Run the given pre-send hooks on the given raw message, in order.
+///
+/// Returns the message given by the output of the last command, or
+/// the given message if all outputs are empty.
+pub async fn run(cmds: &[Command], msg: &[u8]) -> Result<Vec<u8>, HookError> {
+    let mut msg = msg.to_vec();
+
+    for cmd in cmds {
+        debug!("running pre-send hook {cmd:?}");
+
+        match cmd.run_with(&msg).await {
+            Ok(output) if output.iter().all(u8::is_ascii_whitespace) => (),
+            Ok(output) => msg = output.to_vec(),
+            Err(process::Error::GetExitStatusCodeNonZeroError(_, code, stderr)) => {
+                return Err(HookError::Rejected(cmd.to_string(), code, stderr))
+            }
+            Err(err) => return Err(HookError::Execute(cmd.to_string(), err)),
+        }
+    }
+
+    Ok(msg)
+}
+
+/// Return `true` if the given error comes from a pre-send hook.
+///
+/// Such messages should not be queued in the outbox: the hook would
+/// reject them again.
+pub fn is_hook_error(err: &Report) -> bool {
+    if err.downcast_ref::<HookError>().is_some() {
+        return true;
+    }
+
+    err.downcast_ref::<AnyBoxedError>()
+        .is_some_and(|err| err.as_any().is::<HookError>())
+}
+
+/// Wrap the given send message feature, so that messages go through
+/// the given pre-send hooks first.
+pub fn wrap(
+    feature: BackendFeature<Context, dyn SendMessage>,
+    cmds: Vec<Command>,
+) -> BackendFeature<Context, dyn SendMessage> {
+    Arc::new(move |ctx| {
+        let send = feature(ctx)?;
+        let cmds = cmds.clone();
+        Some(Box::new(SendHookedMessage { cmds, send }))
+    })
+}
+
+/// The send message feature running the pre-send hooks first.
+struct SendHookedMessage {
+    cmds: Vec<Command>,
+    send: Box<dyn SendMessage>,
+}
+
+#[async_trait]
+impl SendMessage for SendHookedMessage {
+    async fn send_message(&self, msg: &[u8]) -> AnyResult<()> {
+        let msg = run(&self.cmds, msg)
+            .await
+            .map_err(|err| Box::new(err) as AnyBoxedError)?;
+
+        self.send.send_message(&msg).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use email::{
+        account::config::AccountConfig,
+        message::{config::MessageConfig, send::config::MessageSendConfig, send::SendMessage},
+        AnyResult,
+    };
+    use process::Command;
+
+    use super::{HookError, SendHookedMessage};
+    use crate::account::config::{HooksExtraConfig, TomlAccountExtraConfig};
+
+    const MSG: &[u8] = b"Subject: test\r\n\r\nfoo\r\n";
+
+    fn configs(
+        pre_send: Option<&str>,
+        pre_hook: Option<&str>,
+    ) -> (TomlAccountExtraConfig, AccountConfig) {
+        let config = TomlAccountExtraConfig {
+            hooks: Some(HooksExtraConfig {
+                pre_send: pre_send.map(Command::new),
+            }),
+            ..Default::default()
+        };
+
+        let account_config = AccountConfig {
+            message: Some(MessageConfig {
+                send: Some(MessageSendConfig {
+                    pre_hook: pre_hook.map(Command::new),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        (config, account_config)
+    }
+
+    fn cmds(cmds: &[&str]) -> Vec<Command> {
+        cmds.iter().map(Command::new).collect()
+    }
+
+    #[test]
+    fn commands_in_order() {
+        let (config, account_config) = configs(Some("first"), Some("second"));
+
+        let cmds: Vec<_> = super::commands(&config, &account_config)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(cmds, ["first", "second"]);
+    }
+
+    #[test]
+    fn disable_both_commands() {
+        let (mut config, mut account_config) = configs(Some("first"), Some("second"));
+
+        super::disable(&mut config, &mut account_config);
+
+        assert!(super::commands(&config, &account_config).is_empty());
+    }
+
+    #[tokio::test]
+    async fn run_in_order() {
+        let cmds = cmds(&["sed s/foo/bar/", "sed s/bar/baz/"]);
+
+        let msg = super::run(&cmds, MSG).await.unwrap();
+
+        assert_eq!(msg, b"Subject: test\r\n\r\nbaz\r\n");
+    }
+
+    #[tokio::test]
+    async fn keep_message_on_empty_output() {
+        let cmds = cmds(&["cat >/dev/null", "sed s/foo/bar/", "echo"]);
+
+        let msg = super::run(&cmds, MSG).await.unwrap();
+
+        assert_eq!(msg, b"Subject: test\r\n\r\nbar\r\n");
+    }
+
+    #[tokio::test]
+    async fn abort_on_failure() {
+        let cmds = cmds(&[
+            "sed s/foo/bar/",
+            "echo rejected >&2; exit 3",
+            "sed s/bar/baz/",
+        ]);
+
+        let err = super::run(&cmds, MSG).await.unwrap_err();
+
+        assert!(
+            matches!(&err, HookError::Rejected(cmd, 3, stderr) if cmd.starts_with("echo") && stderr.trim() == "rejected"),
+            "{err:?}"
+        );
+    }
+
+    /// A send message feature recording sent messages.
+    #[derive(Default)]
+    struct SendMessages(Mutex<Vec<Vec<u8>>>);
+
+    #[async_trait]
+    impl SendMessage for &'static SendMessages {
+        async fn send_message(&self, msg: &[u8]) -> AnyResult<()> {
+            self.0.lock().unwrap().push(msg.to_vec());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn send_hooked_messages() {
+        let sent: &'static SendMessages = Box::leak(Default::default());
+        let send = SendHookedMessage {
+            cmds: cmds(&[
+                "sed s/foo/bar/",
+                "awk '/bar/ { f = 1 } { print } END { exit !f }'",
+            ]),
+            send: Box::new(sent),
+        };
+
+        send.send_message(MSG).await.unwrap();
+        assert!(send
+            .send_message(b"Subject: test\r\n\r\nqux\r\n")
+            .await
+            .is_err());
+
+        assert_eq!(*sent.0.lock().unwrap(), [b"Subject: test\r\n\r\nbar\r\n"]);
+    }
+}
@@ -0,0 +1,183 @@
+//! Module dedicated to message sending.
+//!
+//! Messages are sent by the sending backend of the account context,
+//! unless the CLI needs its own sending feature: SMTP messages are
+//! always sent by the CLI, so that Bcc recipients are kept out of the
+//! transmitted message.
+
+pub mod dir;
+pub mod dry_run;
+pub mod hook;
+pub mod rate;
+pub mod sendmail;
+pub mod smtp;
+
+use color_eyre::{eyre::bail, Result};
+use email::{
+    backend::{
+        context::BackendContextBuilder,
+        feature::{BackendFeature, BackendFeatureSource},
+        BackendBuilder,
+    },
+    message::send::SendMessage,
+};
+use pimalaya_tui::himalaya::backend::{Context, ContextBuilder};
+
+use crate::account::config::TomlAccountExtraConfig;
+
+/// Set up the send message feature of the given backend builder,
+/// using the given account configuration.
+///
+/// When the account has pre-send hooks, messages go through them
+/// before reaching the send message feature, see [`hook`].
+pub fn with_send_message(
+    mut builder: BackendBuilder<ContextBuilder>,
+    config: &TomlAccountExtraConfig,
+) -> BackendBuilder<ContextBuilder> {
+    let cmds = hook::commands(config, &builder.account_config);
+
+    // the sendmail backend runs message.send.pre-hook by itself,
+    // ignoring its failures, so it is left to the hooks feature
+    #[cfg(feature = "sendmail")]
+    if let Some(ctx) = builder.ctx_builder.sendmail.as_mut() {
+        let mut account_config = (*ctx.account_config).clone();
+        hook::take_legacy(&mut account_config);
+        ctx.account_config = std::sync::Arc::new(account_config);
+    }
+
+    let feature = send_message_feature(&mut builder, config);
+
+    if cmds.is_empty() {
+        return match feature {
+            Some(feature) => builder.with_send_message(BackendFeatureSource::Backend(feature)),
+            None => builder.with_send_message(BackendFeatureSource::Context),
+        };
+    }
+
+    match feature.or_else(|| builder.ctx_builder.send_message()) {
+        Some(feature) => {
+            let feature = hook::wrap(feature, cmds);
+            builder.with_send_message(BackendFeatureSource::Backend(feature))
+        }
+        None => builder.with_send_message(BackendFeatureSource::Context),
+    }
+}
+
+/// Get the send message feature of the CLI, if the sending backend
+/// of the given account needs one.
+#[cfg_attr(not(feature = "smtp"), allow(unused_variables))]
+fn send_message_feature(
+    builder: &mut BackendBuilder<ContextBuilder>,
+    config: &TomlAccountExtraConfig,
+) -> Option<BackendFeature<Context, dyn SendMessage>> {
+    #[cfg(feature = "smtp")]
+    {
+        use pimalaya_tui::himalaya::config::SendingBackend;
+
+        let smtp_config = match &builder.ctx_builder.sending_backend {
+            Some(SendingBackend::Smtp(smtp_config)) => Some(smtp_config.clone()),
+            _ => None,
+        };
+
+        // the SMTP context connects when built, so it is replaced by
+        // a client connecting when the first message is sent
+        if let (Some(smtp_config), Some(_)) = (smtp_config, builder.ctx_builder.smtp.take()) {
+            return Some(smtp::feature(smtp_config, config.smtp_options()));
+        }
+    }
+
+    #[cfg(feature = "sendmail")]
+    if config.sendmail_recipients() == sendmail::SendmailRecipients::Args {
+        return Some(sendmail::feature());
+    }
+
+    None
+}
+
+/// Validate the headers of the given raw message before sending it.
+///
+/// Fails with the name of the offending header when the message has
+/// no sender, no recipient, or an address that cannot be parsed.
+pub fn validate(msg: &[u8]) -> Result<()> {
+    use mail_parser::{HeaderName, HeaderValue, MessageParser};
+
+    let Some(parsed) = MessageParser::new().parse_headers(msg) else {
+        bail!("cannot parse message headers");
+    };
+
+    let headers = [
+        (HeaderName::From, "From"),
+        (HeaderName::Sender, "Sender"),
+        (HeaderName::ReplyTo, "Reply-To"),
+        (HeaderName::To, "To"),
+        (HeaderName::Cc, "Cc"),
+        (HeaderName::Bcc, "Bcc"),
+    ];
+
+    for (header, name) in headers {
+        for value in parsed.header_values(header) {
+            let addrs = match value {
+                HeaderValue::Empty => continue,
+                HeaderValue::Address(addrs) => addrs,
+                _ => bail!("cannot parse {name} header: invalid address list"),
+            };
+
+            for addr in addrs.iter() {
+                let email = addr.address.as_deref().map(str::trim).unwrap_or_default();
+
+                if !is_valid_email(email) {
+                    let addr = addr.name.as_deref().map(str::trim).unwrap_or(email);
+                    bail!("cannot parse {name} header: invalid address {addr:?}");
+                }
+            }
+        }
+    }
+
+    if parsed.from().is_none() {
+        bail!("cannot send message: missing From header");
+    }
+
+    if recipients(&parsed).is_empty() {
+        bail!("cannot send message: missing To, Cc or Bcc header");
+    }
+
+    Ok(())
+}
+
+/// Return `true` if the given email address has a local part and a
+/// domain, without whitespaces.
+fn is_valid_email(email: &str) -> bool {
+    match email.rsplit_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty() && !domain.is_empty() && !email.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+/// Collect the recipients of the given message.
+///
+/// Recipients are the addresses of all the To, Cc and Bcc headers,
+/// including the members of groups, without duplicates.
+pub fn recipients(msg: &mail_parser::Message) -> Vec<String> {
+    use mail_parser::{Address, HeaderName, HeaderValue};
+
+    let mut recipients: Vec<String> = Vec::new();
+
+    let addrs = [HeaderName::To, HeaderName::Cc, HeaderName::Bcc]
+        .into_iter()
+        .flat_map(|name| msg.header_values(name))
+        .filter_map(HeaderValue::as_address)
+        .flat_map(Address::iter)
+        .filter_map(|addr| addr.address.as_deref())
+        .map(str::trim)
+        .filter(|addr| !addr.is_empty());
+
+    for addr in addrs {
+        if !recipients.iter().any(|r| r == addr) {
+            recipients.push(addr.to_owned());
+        }
+    }
+
+    recipients
+}
@@ -0,0 +1,60 @@
+//! Module dedicated to the sending rate limit.
+
+use std::{str::FromStr, time::Duration};
+
+use tokio::time::{sleep_until, Instant};
+
+/// The maximum number of messages sent per minute.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SendRate(u32);
+
+impl SendRate {
+    /// The minimal delay between two messages.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(60) / self.0
+    }
+}
+
+impl FromStr for SendRate {
+    type Err = String;
+
+    fn from_str(rate: &str) -> Result<Self, Self::Err> {
+        let n = rate.trim();
+        let n = n.strip_suffix("/min").unwrap_or(n).trim_end();
+
+        match n.parse() {
+            Ok(0) => Err(String::from("rate must be greater than 0")),
+            Ok(n) => Ok(Self(n)),
+            Err(_) => Err(format!("invalid rate `{rate}`, expected <n>/min")),
+        }
+    }
+}
+
+/// Spread messages over time according to a sending rate.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    interval: Option<Duration>,
+    next: Option<Instant>,
+}
+
+impl Throttle {
+    pub fn new(rate: Option<SendRate>) -> Self {
+        Self {
+            interval: rate.map(|rate| rate.interval()),
+            next: None,
+        }
+    }
+
+    /// Wait until the next message can be sent.
+    pub async fn wait(&mut self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+
+        if let Some(next) = self.next {
+            sleep_until(next).await;
+        }
+
+        self.next = Some(Instant::now() + interval);
+    }
+}
@@ -0,0 +1,218 @@
+//! Module dedicated to the sendmail sending backend.
+//!
+//! By default the sendmail command is expected to read recipients
+//! from the headers of the message (like `sendmail -t`). Recipients
+//! can also be passed as arguments of the command, after `--`.
+
+#[cfg(feature = "sendmail")]
+use email::{backend::feature::BackendFeature, message::send::SendMessage};
+#[cfg(feature = "sendmail")]
+use pimalaya_tui::himalaya::backend::Context;
+use serde::Deserialize;
+
+/// How recipients are given to the sendmail command.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SendmailRecipients {
+    /// The command reads recipients from the headers of the message.
+    #[default]
+    Headers,
+
+    /// Recipients are passed as arguments of the command, after
+    /// `--`. The Bcc header is removed from the message.
+    Args,
+}
+
+/// Get the send message feature passing recipients as arguments of
+/// the sendmail command.
+#[cfg(feature = "sendmail")]
+pub fn feature() -> BackendFeature<Context, dyn SendMessage> {
+    std::sync::Arc::new(args::SendSendmailMessage::some_new_boxed)
+}
+
+#[cfg(feature = "sendmail")]
+mod args {
+    use async_trait::async_trait;
+    use email::{
+        message::send::SendMessage,
+        sendmail::{Error, SendmailContextSync},
+        AnyResult,
+    };
+    use mail_parser::MessageParser;
+    use pimalaya_tui::himalaya::backend::Context;
+    use process::Command;
+    use tracing::{debug, info};
+
+    use crate::message::{header, send};
+
+    /// The send message feature passing recipients as arguments of
+    /// the sendmail command.
+    #[derive(Clone)]
+    pub struct SendSendmailMessage {
+        ctx: SendmailContextSync,
+    }
+
+    impl SendSendmailMessage {
+        pub fn some_new_boxed(ctx: &Context) -> Option<Box<dyn SendMessage>> {
+            let ctx: &Option<SendmailContextSync> = ctx.as_ref();
+            let ctx = ctx.clone()?;
+            Some(Box::new(Self { ctx }))
+        }
+    }
+
+    #[async_trait]
+    impl SendMessage for SendSendmailMessage {
+        async fn send_message(&self, msg: &[u8]) -> AnyResult<()> {
+            info!("sending sendmail message with recipients as arguments");
+
+            let recipients = recipients(msg);
+            let msg = header::remove(msg, "Bcc");
+
+            let mut cmd = self.ctx.sendmail_config.cmd().to_string();
+            cmd.push_str(" --");
+
+            for recipient in recipients {
+                cmd.push(' ');
+                cmd.push_str(&quote(&recipient));
+            }
+
+            Command::new(cmd)
+                .run_with(msg)
+                .await
+                .map_err(Error::ExecuteCommandError)?;
+
+            Ok(())
+        }
+    }
+
+    /// Collect the recipients of the given raw message.
+    fn recipients(msg: &[u8]) -> Vec<String> {
+        match MessageParser::new().parse_headers(msg) {
+            Some(msg) => send::recipients(&msg),
+            None => {
+                debug!("cannot parse raw message");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Quote the given argument for the shell running the command.
+    fn quote(arg: &str) -> String {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{env::temp_dir, error::Error, fs, path::PathBuf, sync::Arc};
+
+        use email::{
+            backend::context::BackendContextBuilder,
+            message::send::SendMessage,
+            sendmail::{config::SendmailConfig, SendmailContextBuilder},
+        };
+        use process::Command;
+        use uuid::Uuid;
+
+        use super::SendSendmailMessage;
+
+        const MSG: &str = concat!(
+            "From: me@localhost\r\n",
+            "To: Alice <alice@localhost>, bob@localhost\r\n",
+            "Cc: o'brien@localhost\r\n",
+            "Bcc: carol@localhost, alice@localhost\r\n",
+            "Subject: test\r\n",
+            "\r\n",
+            "Hello!\r\n",
+        );
+
+        /// A temporary directory holding a fake sendmail script, which
+        /// records its arguments and its standard input.
+        struct Sendmail(PathBuf);
+
+        impl Sendmail {
+            fn new(exit: &str) -> Self {
+                let dir = temp_dir().join(format!("himalaya-sendmail-{}", Uuid::new_v4()));
+                fs::create_dir_all(&dir).unwrap();
+
+                let script =
+                    format!("printf '%s\\n' \"$@\" >{dir:?}/args\ncat >{dir:?}/stdin\n{exit}\n");
+                fs::write(dir.join("sendmail.sh"), script).unwrap();
+
+                Self(dir)
+            }
+
+            async fn send(&self, msg: &str) -> email::AnyResult<()> {
+                let config = SendmailConfig {
+                    cmd: Some(Command::new(format!(
+                        "sh {:?} -i",
+                        self.0.join("sendmail.sh")
+                    ))),
+                };
+
+                let ctx = SendmailContextBuilder::new(Arc::default(), Arc::new(config))
+                    .build()
+                    .await
+                    .unwrap();
+
+                SendSendmailMessage { ctx }
+                    .send_message(msg.as_bytes())
+                    .await
+            }
+
+            fn args(&self) -> Vec<String> {
+                let args = fs::read_to_string(self.0.join("args")).unwrap();
+                args.lines().map(ToOwned::to_owned).collect()
+            }
+
+            fn stdin(&self) -> String {
+                fs::read_to_string(self.0.join("stdin")).unwrap()
+            }
+        }
+
+        impl Drop for Sendmail {
+            fn drop(&mut self) {
+                let _ = fs::remove_dir_all(&self.0);
+            }
+        }
+
+        #[tokio::test]
+        async fn pass_recipients_as_args() {
+            let sendmail = Sendmail::new("exit 0");
+
+            sendmail.send(MSG).await.unwrap();
+
+            let args = [
+                "-i",
+                "--",
+                "alice@localhost",
+                "bob@localhost",
+                "o'brien@localhost",
+                "carol@localhost",
+            ];
+            assert_eq!(sendmail.args(), args);
+
+            let stdin = MSG.replace("Bcc: carol@localhost, alice@localhost\r\n", "");
+            assert_eq!(sendmail.stdin(), stdin);
+        }
+
+        #[tokio::test]
+        async fn surface_stderr_on_failure() {
+            let sendmail = Sendmail::new("echo 'carol@localhost: unknown user' >&2; exit 67");
+
+            let err = sendmail.send(MSG).await.unwrap_err();
+
+            let mut chain = Vec::new();
+            let mut source: Option<&dyn Error> = Some(&err);
+            while let Some(err) = source {
+                chain.push(err.to_string());
+                source = err.source();
+            }
+
+            assert_eq!(chain[0], "cannot execute sendmail command");
+            assert!(
+                chain[1].ends_with("exit status code 67: carol@localhost: unknown user\n"),
+                "{chain:?}"
+            );
+        }
+    }
+}
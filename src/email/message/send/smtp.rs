@@ -0,0 +1,1508 @@
+//! Module dedicated to the SMTP sending backend.
+//!
+//! Messages are sent by a client of the CLI rather than by the SMTP
+//! context of the account: Bcc recipients are added to the envelope
+//! while the Bcc header is removed from the transmitted message, and
+//! the server can be greeted with a custom HELO hostname. The
+//! connection can also go through a proxy, accept invalid TLS
+//! certificates, time out and be retried after transient failures,
+//! see [`SmtpOptions`].
+//!
+//! The session is still opened by email-lib, from a client builder
+//! set up with these options. Only sessions going through a proxy are
+//! opened by the CLI, since email-lib connects by itself.
+
+use std::fmt;
+#[cfg(feature = "smtp")]
+use std::{any::Any, error, time::Duration};
+
+#[cfg(feature = "smtp")]
+use email::{
+    backend::feature::BackendFeature, message::send::SendMessage, smtp::config::SmtpConfig,
+    AnyError,
+};
+#[cfg(feature = "smtp")]
+use pimalaya_tui::himalaya::backend::Context;
+use serde::Deserialize;
+#[cfg(feature = "smtp")]
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "smtp")]
+use crate::proxy::ProxyConfig;
+
+/// The default maximum amount of time to open a session with the
+/// SMTP server, and given to the server to answer each command.
+#[cfg(feature = "smtp")]
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The minimum rate, in bytes per second, at which a message is
+/// expected to be transmitted to the SMTP server.
+///
+/// The time given to transmit a message grows with its size, so that
+/// large attachments do not hit the timeout of commands.
+#[cfg(feature = "smtp")]
+pub const MIN_DATA_RATE: u64 = 10 * 1024;
+
+/// The default number of times sending a message is retried after a
+/// transient failure.
+///
+/// Retries are opt-in, so that a failure is reported right away
+/// unless configured otherwise.
+#[cfg(feature = "smtp")]
+pub const DEFAULT_RETRIES: u32 = 0;
+
+/// The default delay before the first retry.
+#[cfg(feature = "smtp")]
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// The hostname given to the SMTP server in the EHLO command.
+///
+/// Whitespaces and control characters are refused, so that the
+/// hostname can neither inject SMTP commands nor headers.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct HeloHostname(String);
+
+impl TryFrom<String> for HeloHostname {
+    type Error = String;
+
+    fn try_from(hostname: String) -> Result<Self, Self::Error> {
+        if hostname.is_empty() {
+            return Err(String::from("invalid HELO hostname: cannot be empty"));
+        }
+
+        if let Some(c) = hostname
+            .chars()
+            .find(|c| c.is_whitespace() || c.is_control())
+        {
+            return Err(format!(
+                "invalid HELO hostname {hostname:?}: unexpected character {c:?}"
+            ));
+        }
+
+        Ok(Self(hostname))
+    }
+}
+
+impl HeloHostname {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for HeloHostname {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The options of the SMTP client of the CLI.
+#[cfg(feature = "smtp")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SmtpOptions {
+    /// The hostname given to the server in the EHLO command.
+    ///
+    /// Defaults to the hostname of the machine.
+    pub helo_hostname: Option<HeloHostname>,
+    /// The proxy the connection goes through.
+    pub proxy: Option<ProxyConfig>,
+    /// Accept any TLS certificate, like self-signed or expired ones.
+    pub accept_invalid_certs: bool,
+    /// Accept valid TLS certificates issued for another hostname.
+    pub accept_invalid_hostnames: bool,
+    /// The maximum amount of time to open a session with the server:
+    /// connecting, greeting it and authenticating.
+    pub connect_timeout: Duration,
+    /// The maximum amount of time the server is given to answer each
+    /// command.
+    ///
+    /// The transmission of the message is given more time, see
+    /// [`data_timeout`].
+    pub timeout: Duration,
+    /// The number of times sending a message is retried after a
+    /// transient failure.
+    pub retries: u32,
+    /// The delay before the first retry, doubled before each next
+    /// one.
+    pub retry_delay: Duration,
+}
+
+#[cfg(feature = "smtp")]
+impl Default for SmtpOptions {
+    fn default() -> Self {
+        Self {
+            helo_hostname: None,
+            proxy: None,
+            accept_invalid_certs: false,
+            accept_invalid_hostnames: false,
+            connect_timeout: DEFAULT_TIMEOUT,
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+}
+
+/// Get the maximum amount of time given to transmit a message of the
+/// given size, then for the server to accept it.
+#[cfg(feature = "smtp")]
+pub fn data_timeout(timeout: Duration, size: usize) -> Duration {
+    timeout.saturating_add(Duration::from_secs(size as u64 / MIN_DATA_RATE))
+}
+
+/// The error of an SMTP server which did not answer in time.
+#[cfg(feature = "smtp")]
+#[derive(Debug)]
+pub enum TimeoutError {
+    /// The connection to the server cannot be established.
+    Connect(String, Duration),
+    /// The server did not answer a command.
+    Command(String, Duration),
+    /// The message was not transmitted nor accepted in time.
+    Data(String, Duration),
+}
+
+#[cfg(feature = "smtp")]
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connect(host, timeout) => {
+                let secs = timeout.as_secs();
+                write!(
+                    f,
+                    "cannot connect to SMTP server {host}: timed out after {secs}s"
+                )
+            }
+            Self::Command(host, timeout) => {
+                let secs = timeout.as_secs();
+                write!(f, "SMTP server {host} did not answer within {secs}s")
+            }
+            Self::Data(host, timeout) => {
+                let secs = timeout.as_secs();
+                write!(
+                    f,
+                    "SMTP server {host} did not accept the message within {secs}s"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "smtp")]
+impl error::Error for TimeoutError {}
+
+#[cfg(feature = "smtp")]
+impl AnyError for TimeoutError {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// The TLS certificate of an SMTP server refused by the client.
+///
+/// It is the last cause of connection errors, hinting at the option
+/// accepting the certificate.
+#[cfg(feature = "smtp")]
+#[derive(Debug, Eq, PartialEq)]
+pub struct InvalidCertificate {
+    /// The option accepting the certificate, under
+    /// `message.send.backend`.
+    pub option: &'static str,
+}
+
+#[cfg(feature = "smtp")]
+impl fmt::Display for InvalidCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let option = self.option;
+        write!(
+            f,
+            "set message.send.backend.{option} to true to accept this certificate"
+        )
+    }
+}
+
+#[cfg(feature = "smtp")]
+impl error::Error for InvalidCertificate {}
+
+/// Get the SMTP send message feature, using the given options.
+///
+/// The connection is opened when the first message is sent, then
+/// reused by the following ones, and closed once the backend is
+/// dropped.
+#[cfg(feature = "smtp")]
+pub fn feature(
+    smtp_config: SmtpConfig,
+    options: SmtpOptions,
+) -> BackendFeature<Context, dyn SendMessage> {
+    let feature = client::SendSmtpMessage::new(smtp_config, options);
+    std::sync::Arc::new(move |_| Some(Box::new(feature.clone())))
+}
+
+/// Check the given SMTP server, by connecting to it using the given
+/// options and authenticating.
+///
+/// Invalid certificates come with an [`InvalidCertificate`] cause
+/// naming the option accepting them.
+#[cfg(feature = "smtp")]
+pub async fn check(smtp_config: SmtpConfig, options: SmtpOptions) -> color_eyre::Result<()> {
+    let feature = client::SendSmtpMessage::new(smtp_config, options);
+
+    let mut client = match feature.connect().await {
+        Ok(client) => client,
+        Err(err) => return Err(client::into_report(&*err)),
+    };
+
+    client.noop().await?;
+    Ok(())
+}
+
+/// The tasks closing the SMTP sessions of dropped features.
+#[cfg(feature = "smtp")]
+static CLOSING: std::sync::Mutex<Vec<JoinHandle<()>>> = std::sync::Mutex::new(Vec::new());
+
+/// Wait for the SMTP sessions of dropped features to be closed.
+///
+/// Sessions are closed with QUIT by spawned tasks, which the runtime
+/// cancels when it stops, so this is awaited before exiting.
+#[cfg(feature = "smtp")]
+pub async fn wait_closing_sessions() {
+    let tasks = match CLOSING.lock() {
+        Ok(mut tasks) => std::mem::take(&mut *tasks),
+        Err(_) => return,
+    };
+
+    for task in tasks {
+        let _ = task.await;
+    }
+}
+
+#[cfg(feature = "smtp")]
+mod client {
+    use std::{sync::Arc, time::Duration};
+
+    use async_trait::async_trait;
+    use color_eyre::{eyre::eyre, Report};
+    use email::{
+        message::send::SendMessage,
+        smtp::{self, config::SmtpConfig, Error, SmtpClientStream},
+        AnyBoxedError, AnyError, AnyResult,
+    };
+    use mail_parser::MessageParser;
+    use mail_send::{
+        smtp::{message::Message, AssertReply},
+        SmtpClient, SmtpClientBuilder,
+    };
+    use tokio::{
+        io::{AsyncRead, AsyncWrite},
+        net::TcpStream,
+        runtime::Handle,
+        sync::Mutex,
+    };
+    use tokio_rustls::{
+        rustls::{
+            self,
+            client::{
+                danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+                WebPkiServerVerifier,
+            },
+            pki_types::{CertificateDer, ServerName, UnixTime},
+            CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+        },
+        TlsConnector,
+    };
+    use tracing::{debug, info, warn};
+
+    use super::{data_timeout, InvalidCertificate, SmtpOptions, TimeoutError, CLOSING};
+    use crate::{
+        message::header,
+        message::send,
+        proxy::{self, ProxyConfig, ProxyError},
+    };
+
+    /// The maximum amount of time the server is given to answer the
+    /// QUIT command.
+    const QUIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// The SMTP send message feature.
+    #[derive(Clone)]
+    pub struct SendSmtpMessage {
+        smtp_config: Arc<SmtpConfig>,
+        options: Arc<SmtpOptions>,
+        session: Arc<Session>,
+    }
+
+    /// The SMTP session shared by the clones of the feature.
+    ///
+    /// The session is closed with QUIT by a spawned task once the last
+    /// clone is dropped, which usually happens when the command exits.
+    #[derive(Default)]
+    struct Session(Mutex<Option<SmtpClientStream>>);
+
+    impl Drop for Session {
+        fn drop(&mut self) {
+            let Some(client) = self.0.get_mut().take() else {
+                return;
+            };
+
+            let Ok(handle) = Handle::try_current() else {
+                return;
+            };
+
+            let task = handle.spawn(async {
+                match tokio::time::timeout(QUIT_TIMEOUT, quit(client)).await {
+                    Ok(Ok(())) => debug!("smtp session closed"),
+                    Ok(Err(err)) => debug!("cannot close smtp session: {err}"),
+                    Err(_) => debug!("cannot close smtp session: timed out"),
+                }
+            });
+
+            if let Ok(mut tasks) = CLOSING.lock() {
+                tasks.push(task);
+            }
+        }
+    }
+
+    /// Reset the transaction of the given SMTP session, which also
+    /// checks that the server did not close the connection.
+    async fn reset(client: &mut SmtpClientStream) -> mail_send::Result<()> {
+        match client {
+            SmtpClientStream::Tcp(client) => client.rset().await,
+            SmtpClientStream::Tls(client) => client.rset().await,
+        }
+    }
+
+    /// The failure of an SMTP transaction.
+    enum TransactionError {
+        /// The transaction failed before the DATA command was issued:
+        /// the server did not accept the message.
+        Envelope(mail_send::Error),
+
+        /// The transaction failed once the DATA command was issued:
+        /// the server may have accepted the message, for example when
+        /// the connection drops before its final reply.
+        Data(mail_send::Error),
+    }
+
+    /// The failure of an attempt to send a message.
+    enum Failure {
+        /// The server did not accept the message, for a reason which
+        /// may go away by itself: the message can be sent again.
+        Transient(AnyBoxedError),
+
+        /// The message cannot be sent, or may have been accepted by
+        /// the server: the message must not be sent again.
+        Permanent(AnyBoxedError),
+    }
+
+    /// Return `true` if the given connection error may go away by
+    /// itself, like a DNS hiccup, a reset connection or a busy server.
+    fn is_transient(err: &(dyn AnyError + 'static)) -> bool {
+        let err = err.as_any();
+
+        if err.is::<TimeoutError>() {
+            return true;
+        }
+
+        if let Some(ProxyError::Unreachable(..)) = err.downcast_ref() {
+            return true;
+        }
+
+        match err.downcast_ref() {
+            Some(Error::ConnectTcpSmtpError(err) | Error::ConnectTlsSmtpError(err)) => {
+                is_transient_reply(err)
+            }
+            _ => false,
+        }
+    }
+
+    /// Return `true` if the given SMTP error may go away by itself:
+    /// I/O errors, timeouts and temporary (4xx) replies.
+    fn is_transient_reply(err: &mail_send::Error) -> bool {
+        match err {
+            mail_send::Error::Io(_)
+            | mail_send::Error::Timeout
+            | mail_send::Error::UnparseableReply => true,
+            mail_send::Error::UnexpectedReply(reply) => reply.code / 100 == 4,
+            _ => false,
+        }
+    }
+
+    impl From<TransactionError> for mail_send::Error {
+        fn from(err: TransactionError) -> Self {
+            match err {
+                TransactionError::Envelope(err) | TransactionError::Data(err) => err,
+            }
+        }
+    }
+
+    /// Send the given message using the given SMTP session.
+    async fn transaction(
+        client: &mut SmtpClientStream,
+        msg: &Message<'_>,
+    ) -> Result<(), TransactionError> {
+        match client {
+            SmtpClientStream::Tcp(client) => send(client, msg).await,
+            SmtpClientStream::Tls(client) => send(client, msg).await,
+        }
+    }
+
+    /// Send the given message, the same way [`SmtpClient::send`] does
+    /// but telling apart failures happening before the DATA command.
+    async fn send<T: AsyncRead + AsyncWrite + Unpin>(
+        client: &mut SmtpClient<T>,
+        msg: &Message<'_>,
+    ) -> Result<(), TransactionError> {
+        let envelope = async {
+            let from = &msg.mail_from;
+            client.mail_from(&from.email, &from.parameters).await?;
+
+            for rcpt in &msg.rcpt_to {
+                client.rcpt_to(&rcpt.email, &rcpt.parameters).await?;
+            }
+
+            Ok(())
+        };
+
+        envelope.await.map_err(TransactionError::Envelope)?;
+
+        // the timeout of the client bounds the whole transmission of
+        // the message, so it is scaled to its size
+        let timeout = client.timeout;
+        client.timeout = data_timeout(timeout, msg.body.len());
+        let sent = client.data(msg.body.as_ref()).await;
+        client.timeout = timeout;
+
+        sent.map_err(TransactionError::Data)
+    }
+
+    /// Close the given SMTP session.
+    async fn quit(client: SmtpClientStream) -> mail_send::Result<()> {
+        match client {
+            SmtpClientStream::Tcp(client) => client.quit().await,
+            SmtpClientStream::Tls(client) => client.quit().await,
+        }
+    }
+
+    impl SendSmtpMessage {
+        pub fn new(smtp_config: SmtpConfig, options: SmtpOptions) -> Self {
+            Self {
+                smtp_config: Arc::new(smtp_config),
+                options: Arc::new(options),
+                session: Default::default(),
+            }
+        }
+
+        /// Connect to the SMTP server, then greet it and authenticate.
+        ///
+        /// Messages are written in several chunks, which Nagle's
+        /// algorithm would hold until the server acknowledges the
+        /// previous ones, so it is disabled.
+        pub async fn connect(&self) -> AnyResult<SmtpClientStream> {
+            let client = self.open().await?;
+
+            let stream = match &client {
+                SmtpClientStream::Tcp(client) => &client.stream,
+                SmtpClientStream::Tls(client) => client.stream.get_ref().0,
+            };
+
+            if let Err(err) = stream.set_nodelay(true) {
+                debug!("cannot disable nagle algorithm of smtp connection: {err}");
+            }
+
+            Ok(client)
+        }
+
+        async fn open(&self) -> AnyResult<SmtpClientStream> {
+            let config = &self.smtp_config;
+            let options = &self.options;
+
+            let mut builder = SmtpClientBuilder::new(config.host.clone(), config.port)
+                .credentials(config.credentials().await?)
+                .implicit_tls(!config.is_start_tls_encryption_enabled())
+                .timeout(options.timeout);
+
+            if let Some(hostname) = &options.helo_hostname {
+                builder = builder.helo_host(hostname.as_str());
+            }
+
+            if config.is_encryption_disabled() {
+                builder = builder.allow_invalid_certs();
+            } else if options.accept_invalid_certs {
+                warn!(
+                    "accepting invalid TLS certificates of SMTP server {}",
+                    config.host
+                );
+                builder = builder.allow_invalid_certs();
+            } else if options.accept_invalid_hostnames {
+                warn!(
+                    "accepting TLS certificates of SMTP server {} issued for other hostnames",
+                    config.host
+                );
+                builder.tls_connector = any_hostname_tls_connector()?;
+            }
+
+            let timeout = options.connect_timeout;
+            let session = async {
+                match &options.proxy {
+                    // email-lib connects by itself, refreshing OAuth 2.0
+                    // access tokens when the authentication fails
+                    None => smtp::build_client(config, builder)
+                        .await
+                        .map(|(_, client)| client)
+                        .map_err(|err| self.with_timeout(err)),
+                    Some(proxy) => self.open_through(proxy, builder).await,
+                }
+            };
+
+            match tokio::time::timeout(timeout, session).await {
+                Ok(client) => client,
+                Err(_) => Err(Box::new(TimeoutError::Connect(
+                    config.host.clone(),
+                    timeout,
+                ))),
+            }
+        }
+
+        /// Open the SMTP session through the given proxy.
+        ///
+        /// mail-send cannot greet a server at the other end of an
+        /// existing stream, so this is done by [`handshake`], access
+        /// tokens being refreshed the same way [`smtp::build_client`]
+        /// does.
+        async fn open_through(
+            &self,
+            proxy: &ProxyConfig,
+            #[cfg_attr(not(feature = "oauth2"), allow(unused_mut))] mut builder: SmtpClientBuilder<
+                String,
+            >,
+        ) -> AnyResult<SmtpClientStream> {
+            let config = &self.smtp_config;
+            let encrypted = config.is_encryption_enabled();
+            let tunnel = || async {
+                proxy::connect(proxy, &config.host, config.port)
+                    .await
+                    .map_err(|err| Box::new(err) as AnyBoxedError)
+            };
+
+            let client = handshake(&builder, tunnel().await?, encrypted).await;
+
+            #[cfg(feature = "oauth2")]
+            if let (
+                Err(Error::ConnectTcpSmtpError(mail_send::Error::AuthenticationFailed(_)))
+                | Err(Error::ConnectTlsSmtpError(mail_send::Error::AuthenticationFailed(_))),
+                email::smtp::config::SmtpAuthConfig::OAuth2(oauth2_config),
+            ) = (&client, &config.auth)
+            {
+                warn!("authentication failed, refreshing access token and retrying…");
+                oauth2_config
+                    .refresh_access_token()
+                    .await
+                    .map_err(|_| Error::RefreshingAccessTokenFailed)?;
+                builder = builder.credentials(config.credentials().await?);
+                let client = handshake(&builder, tunnel().await?, encrypted).await;
+                return client.map_err(|err| self.with_timeout(err));
+            }
+
+            client.map_err(|err| self.with_timeout(err))
+        }
+
+        /// Replace the timeout of the given error by an error naming
+        /// the host and the timeout of the server.
+        fn with_timeout(&self, err: Error) -> AnyBoxedError {
+            match err {
+                Error::ConnectTcpSmtpError(mail_send::Error::Timeout)
+                | Error::ConnectTlsSmtpError(mail_send::Error::Timeout)
+                | Error::SendMessageError(mail_send::Error::Timeout) => {
+                    let host = self.smtp_config.host.clone();
+                    Box::new(TimeoutError::Command(host, self.options.timeout))
+                }
+                err => Box::new(err),
+            }
+        }
+
+        /// Connect to the SMTP server, telling apart failures which
+        /// may go away by themselves.
+        async fn try_connect(&self) -> Result<SmtpClientStream, Failure> {
+            self.connect()
+                .await
+                .map_err(|err| match is_transient(&*err) {
+                    true => Failure::Transient(err),
+                    false => Failure::Permanent(err),
+                })
+        }
+
+        /// Turn the given failed transaction into a failure.
+        ///
+        /// Only failures happening before the DATA command can be
+        /// transient, since the server never received the message.
+        fn failure(&self, err: TransactionError, msg: &Message<'_>) -> Failure {
+            match err {
+                TransactionError::Envelope(err) if is_transient_reply(&err) => {
+                    Failure::Transient(self.with_timeout(Error::SendMessageError(err)))
+                }
+                TransactionError::Data(mail_send::Error::Timeout) => {
+                    let host = self.smtp_config.host.clone();
+                    let timeout = data_timeout(self.options.timeout, msg.body.len());
+                    Failure::Permanent(Box::new(TimeoutError::Data(host, timeout)))
+                }
+                err => Failure::Permanent(self.with_timeout(Error::SendMessageError(err.into()))),
+            }
+        }
+
+        /// Send the given message using the given session, opening
+        /// it if needed.
+        async fn attempt(
+            &self,
+            session: &mut Option<SmtpClientStream>,
+            msg: &Message<'_>,
+        ) -> Result<(), Failure> {
+            // a reused session is reset first, so that a previous
+            // failed transaction does not leak into this one and so
+            // that a connection closed by the server is detected
+            if let Some(client) = session.as_mut() {
+                if let Err(err) = reset(client).await {
+                    debug!("cannot reset smtp session, reconnecting: {err}");
+                    *session = None;
+                }
+            }
+
+            let reused = session.is_some();
+            let client = match session.as_mut() {
+                Some(client) => client,
+                None => session.insert(self.try_connect().await?),
+            };
+
+            let err = match transaction(client, msg).await {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+
+            // the connection of a reused session may still drop before
+            // the message is transmitted (a closed connection gives an
+            // unparseable reply), in which case the message is sent
+            // again once reconnected: once the DATA command has been
+            // issued, the server may have accepted the message, so it
+            // is never sent twice
+            let err = match err {
+                TransactionError::Envelope(
+                    err @ (mail_send::Error::Io(_)
+                    | mail_send::Error::Timeout
+                    | mail_send::Error::UnparseableReply),
+                ) if reused => err,
+                err => return Err(self.failure(err, msg)),
+            };
+
+            debug!("smtp connection lost, reconnecting: {err}");
+            let client = session.insert(self.try_connect().await?);
+
+            match transaction(client, msg).await {
+                Ok(()) => Ok(()),
+                Err(err) => Err(self.failure(err, msg)),
+            }
+        }
+    }
+
+    /// Build a TLS connector accepting certificates issued for another
+    /// hostname, as long as they are otherwise valid.
+    fn any_hostname_tls_connector() -> smtp::Result<TlsConnector> {
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+        let verifier = WebPkiServerVerifier::builder(Arc::new(roots))
+            .build()
+            .map_err(|err| {
+                let err = Box::new(rustls::Error::General(err.to_string()));
+                Error::ConnectTlsSmtpError(mail_send::Error::Tls(err))
+            })?;
+
+        let config = ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AnyHostnameVerifier(verifier)))
+            .with_no_client_auth();
+
+        Ok(TlsConnector::from(Arc::new(config)))
+    }
+
+    /// A certificate verifier accepting certificates issued for another
+    /// hostname.
+    #[derive(Debug)]
+    struct AnyHostnameVerifier(Arc<WebPkiServerVerifier>);
+
+    impl ServerCertVerifier for AnyHostnameVerifier {
+        fn verify_server_cert(
+            &self,
+            end_entity: &CertificateDer<'_>,
+            intermediates: &[CertificateDer<'_>],
+            server_name: &ServerName<'_>,
+            ocsp_response: &[u8],
+            now: UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            let verified = self.0.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            );
+
+            match verified {
+                Err(rustls::Error::InvalidCertificate(CertificateError::NotValidForName)) => {
+                    Ok(ServerCertVerified::assertion())
+                }
+                verified => verified,
+            }
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            self.0.verify_tls12_signature(message, cert, dss)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            self.0.verify_tls13_signature(message, cert, dss)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.supported_verify_schemes()
+        }
+    }
+
+    /// Turn the given error into a report, keeping its causes.
+    ///
+    /// Invalid certificates get a last [`InvalidCertificate`] cause,
+    /// hinting at the option accepting them.
+    pub fn into_report(err: &(dyn AnyError + 'static)) -> Report {
+        let mut causes = vec![err.to_string()];
+        let mut hint = None;
+        let mut source = err.source();
+
+        while let Some(err) = source {
+            causes.push(err.to_string());
+
+            if let Some(mail_send::Error::Tls(err)) = err.downcast_ref() {
+                hint = match **err {
+                    rustls::Error::InvalidCertificate(CertificateError::NotValidForName) => {
+                        Some("accept-invalid-hostnames")
+                    }
+                    rustls::Error::InvalidCertificate(_) => Some("accept-invalid-certs"),
+                    _ => hint,
+                };
+            }
+
+            source = err.source();
+        }
+
+        let report = match hint {
+            Some(option) => Report::new(InvalidCertificate { option }),
+            None => eyre!(causes.pop().unwrap_or_default()),
+        };
+
+        causes
+            .into_iter()
+            .rev()
+            .fold(report, |report, cause| report.wrap_err(cause))
+    }
+
+    /// Greet the SMTP server at the other end of the given stream,
+    /// then upgrade the connection to TLS and authenticate, the same
+    /// way [`SmtpClientBuilder`] does once connected.
+    async fn handshake(
+        builder: &SmtpClientBuilder<String>,
+        stream: TcpStream,
+        encrypted: bool,
+    ) -> smtp::Result<SmtpClientStream> {
+        let mut client = SmtpClient {
+            stream,
+            timeout: builder.timeout,
+        };
+
+        if !encrypted {
+            let greeting = async {
+                client.read().await?.assert_positive_completion()?;
+                authenticate(builder, &mut client).await
+            };
+
+            greeting.await.map_err(Error::ConnectTcpSmtpError)?;
+            return Ok(SmtpClientStream::Tcp(client));
+        }
+
+        let greeting = async {
+            let mut client = if builder.tls_implicit {
+                let mut client = client
+                    .into_tls(&builder.tls_connector, &builder.tls_hostname)
+                    .await?;
+                client.read().await?.assert_positive_completion()?;
+                client
+            } else {
+                client.read().await?.assert_positive_completion()?;
+                client
+                    .capabilities(&builder.local_host, builder.is_lmtp)
+                    .await?;
+                client
+                    .start_tls(&builder.tls_connector, &builder.tls_hostname)
+                    .await?
+            };
+
+            authenticate(builder, &mut client).await?;
+            Ok(client)
+        };
+
+        let client = greeting.await.map_err(Error::ConnectTlsSmtpError)?;
+        Ok(SmtpClientStream::Tls(client))
+    }
+
+    /// Say EHLO then authenticate using the credentials of the given
+    /// builder.
+    async fn authenticate<T: AsyncRead + AsyncWrite + Unpin>(
+        builder: &SmtpClientBuilder<String>,
+        client: &mut SmtpClient<T>,
+    ) -> mail_send::Result<()> {
+        if builder.say_ehlo {
+            let capabilities = client
+                .capabilities(&builder.local_host, builder.is_lmtp)
+                .await?;
+
+            if let Some(credentials) = &builder.credentials {
+                client.authenticate(credentials, &capabilities).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[async_trait]
+    impl SendMessage for SendSmtpMessage {
+        /// Send the given message.
+        ///
+        /// Transient failures are retried with an exponential
+        /// backoff, as long as the server did not receive the
+        /// message.
+        async fn send_message(&self, msg: &[u8]) -> AnyResult<()> {
+            info!(hostname = ?self.options.helo_hostname, "sending smtp message");
+
+            let msg = into_smtp_msg(msg)?;
+            let retries = self.options.retries;
+            let mut session = self.session.0.lock().await;
+            let mut retry = 0;
+
+            loop {
+                let err = match self.attempt(&mut session, &msg).await {
+                    Ok(()) => return Ok(()),
+                    Err(Failure::Transient(err)) if retry < retries => err,
+                    Err(Failure::Transient(err) | Failure::Permanent(err)) => return Err(err),
+                };
+
+                let delay = self
+                    .options
+                    .retry_delay
+                    .saturating_mul(2u32.saturating_pow(retry));
+                retry += 1;
+
+                info!("cannot send smtp message, retry {retry}/{retries} in {delay:?}: {err}");
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
+    /// Build the SMTP message of the given raw message.
+    ///
+    /// The sender is the first address of the From header, while
+    /// recipients are the addresses of the To, Cc and Bcc headers.
+    /// The Bcc header is removed from the transmitted message.
+    fn into_smtp_msg(msg: &[u8]) -> smtp::Result<Message<'static>> {
+        let Some(parsed) = MessageParser::new().parse_headers(msg) else {
+            debug!("cannot parse raw message");
+            return Err(Error::SendMessageMissingSenderError);
+        };
+
+        let mail_from = parsed
+            .from()
+            .and_then(|addrs| addrs.iter().next())
+            .and_then(|addr| addr.address.as_deref())
+            .map(str::trim)
+            .filter(|addr| !addr.is_empty())
+            .ok_or(Error::SendMessageMissingSenderError)?;
+
+        let rcpt_to = send::recipients(&parsed);
+
+        if rcpt_to.is_empty() {
+            return Err(Error::SendMessageMissingRecipientError);
+        }
+
+        Ok(Message {
+            mail_from: mail_from.to_owned().into(),
+            rcpt_to: rcpt_to.into_iter().map(Into::into).collect(),
+            body: header::remove(msg, "Bcc").into(),
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+            time::{Duration, Instant},
+        };
+
+        use email::{
+            account::config::passwd::PasswordConfig,
+            message::send::SendMessage,
+            smtp::config::{SmtpAuthConfig, SmtpConfig},
+            tls::Encryption,
+        };
+        use secret::Secret;
+        use tokio::{
+            io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+            net::TcpListener,
+            sync::Mutex,
+        };
+
+        use super::{into_report, SendSmtpMessage};
+        use crate::{
+            message::send::smtp::{
+                data_timeout, wait_closing_sessions, InvalidCertificate, SmtpOptions, MIN_DATA_RATE,
+            },
+            proxy::{ProxyConfig, ProxyKind},
+        };
+
+        const MSG: &[u8] =
+            b"From: alice@localhost\r\nTo: bob@localhost\r\nSubject: test\r\n\r\nHello!\r\n";
+
+        /// The fault injected once by the test server.
+        #[derive(Clone, Copy, Eq, PartialEq)]
+        enum Fault {
+            None,
+            /// Close the connection when receiving MAIL FROM.
+            CloseOnMailFrom,
+            /// Close the connection once the message is received,
+            /// without replying.
+            CloseAfterData,
+            /// Keep the connection open once the message is received,
+            /// without replying.
+            SilentAfterData,
+            /// Greet the client, then never answer.
+            SilentAfterGreeting,
+            /// Greet the first client with a 421 reply, then close
+            /// the connection.
+            BusyOnConnect,
+        }
+
+        /// A local SMTP server, accepting any credentials.
+        #[derive(Default)]
+        struct Server {
+            connections: AtomicUsize,
+            quits: AtomicUsize,
+            messages: Mutex<Vec<String>>,
+            rcpts: Mutex<Vec<String>>,
+            mails: AtomicUsize,
+        }
+
+        impl Server {
+            /// Start the server, which waits for the given delay before
+            /// greeting clients, standing for a TLS and AUTH handshake.
+            async fn start(delay: Duration, fault: Fault) -> (Arc<Self>, u16) {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let port = listener.local_addr().unwrap().port();
+                let server = Arc::new(Self::default());
+
+                let accepting = server.clone();
+                tokio::spawn(async move {
+                    loop {
+                        let (stream, _) = listener.accept().await.unwrap();
+                        let server = accepting.clone();
+                        tokio::spawn(async move { server.serve(stream, delay, fault).await });
+                    }
+                });
+
+                (server, port)
+            }
+
+            async fn serve(&self, stream: tokio::net::TcpStream, delay: Duration, fault: Fault) {
+                let connection = self.connections.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(delay).await;
+
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+
+                if fault == Fault::BusyOnConnect && connection == 0 {
+                    writer.write_all(b"421 busy\r\n").await.unwrap();
+                    return;
+                }
+
+                writer.write_all(b"220 localhost\r\n").await.unwrap();
+
+                if fault == Fault::SilentAfterGreeting {
+                    std::future::pending::<()>().await;
+                }
+
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let cmd = line.to_uppercase();
+
+                    let reply: &[u8] = if cmd.starts_with("EHLO") {
+                        b"250-localhost\r\n250 AUTH PLAIN\r\n"
+                    } else if cmd.starts_with("AUTH") {
+                        b"235 authenticated\r\n"
+                    } else if cmd.starts_with("MAIL") {
+                        self.mails.fetch_add(1, Ordering::SeqCst);
+
+                        if self.fault(fault, Fault::CloseOnMailFrom) {
+                            return;
+                        }
+
+                        b"250 ok\r\n"
+                    } else if cmd.starts_with("RCPT") {
+                        self.rcpts.lock().await.push(line[8..].to_owned());
+                        b"250 ok\r\n"
+                    } else if cmd.starts_with("DATA") {
+                        writer.write_all(b"354 go ahead\r\n").await.unwrap();
+
+                        let mut msg = Vec::new();
+                        while let Ok(Some(line)) = lines.next_line().await {
+                            if line == "." {
+                                break;
+                            }
+                            msg.push(line);
+                        }
+
+                        self.messages.lock().await.push(msg.join("\n"));
+
+                        if self.fault(fault, Fault::CloseAfterData) {
+                            return;
+                        }
+
+                        if self.fault(fault, Fault::SilentAfterData) {
+                            std::future::pending::<()>().await;
+                        }
+
+                        b"250 queued\r\n"
+                    } else if cmd.starts_with("QUIT") {
+                        self.quits.fetch_add(1, Ordering::SeqCst);
+                        writer.write_all(b"221 bye\r\n").await.unwrap();
+                        return;
+                    } else {
+                        b"250 ok\r\n"
+                    };
+
+                    writer.write_all(reply).await.unwrap();
+                }
+            }
+
+            /// Return `true` if the given fault should be injected now.
+            ///
+            /// Faults are injected during the transaction of the second
+            /// message, so that the session is reused.
+            fn fault(&self, fault: Fault, expected: Fault) -> bool {
+                fault == expected && self.mails.load(Ordering::SeqCst) == 2
+            }
+
+            async fn messages(&self) -> usize {
+                self.messages.lock().await.len()
+            }
+        }
+
+        fn smtp_config(port: u16) -> SmtpConfig {
+            SmtpConfig {
+                host: String::from("127.0.0.1"),
+                port,
+                encryption: Some(Encryption::None),
+                login: String::from("alice"),
+                auth: SmtpAuthConfig::Password(PasswordConfig(Secret::new_raw("password"))),
+            }
+        }
+
+        fn feature(port: u16) -> SendSmtpMessage {
+            SendSmtpMessage::new(smtp_config(port), SmtpOptions::default())
+        }
+
+        /// Start a server accepting connections without ever
+        /// answering, keeping them open.
+        async fn start_silent_server() -> u16 {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+
+            tokio::spawn(async move {
+                let mut streams = Vec::new();
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    streams.push(stream);
+                }
+            });
+
+            port
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn time_out_when_server_does_not_greet() {
+            let port = start_silent_server().await;
+            let options = SmtpOptions {
+                connect_timeout: Duration::from_secs(1),
+                retries: 0,
+                ..Default::default()
+            };
+
+            let start = Instant::now();
+            let err = SendSmtpMessage::new(smtp_config(port), options)
+                .send_message(MSG)
+                .await
+                .unwrap_err();
+
+            assert!(start.elapsed() < Duration::from_secs(5));
+            assert_eq!(
+                err.to_string(),
+                "cannot connect to SMTP server 127.0.0.1: timed out after 1s"
+            );
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn time_out_when_server_does_not_answer() {
+            let (_server, port) = Server::start(Duration::ZERO, Fault::SilentAfterGreeting).await;
+            let options = SmtpOptions {
+                timeout: Duration::from_secs(1),
+                retries: 0,
+                ..Default::default()
+            };
+
+            let start = Instant::now();
+            let err = SendSmtpMessage::new(smtp_config(port), options)
+                .send_message(MSG)
+                .await
+                .unwrap_err();
+
+            assert!(start.elapsed() < Duration::from_secs(5));
+            assert_eq!(
+                err.to_string(),
+                "SMTP server 127.0.0.1 did not answer within 1s"
+            );
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn time_out_when_message_is_not_accepted() {
+            let (server, port) = Server::start(Duration::ZERO, Fault::SilentAfterData).await;
+            let options = SmtpOptions {
+                timeout: Duration::from_secs(1),
+                retries: 0,
+                ..Default::default()
+            };
+
+            let smtp = SendSmtpMessage::new(smtp_config(port), options);
+            smtp.send_message(MSG).await.unwrap();
+
+            let start = Instant::now();
+            let err = smtp.send_message(MSG).await.unwrap_err();
+
+            assert!(start.elapsed() < Duration::from_secs(5));
+            assert_eq!(
+                err.to_string(),
+                "SMTP server 127.0.0.1 did not accept the message within 1s"
+            );
+            assert_eq!(server.messages().await, 2);
+        }
+
+        #[test]
+        fn scale_data_timeout_with_message_size() {
+            let timeout = Duration::from_secs(30);
+            let rate = MIN_DATA_RATE as usize;
+
+            assert_eq!(data_timeout(timeout, 0), timeout);
+            assert_eq!(data_timeout(timeout, rate - 1), timeout);
+            assert_eq!(data_timeout(timeout, 25 * rate), Duration::from_secs(55));
+            assert_eq!(data_timeout(Duration::MAX, rate), Duration::MAX);
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn time_out_when_connection_cannot_be_established() {
+            // the proxy never answers, so the tunnel to the server is
+            // never established
+            let proxy_port = start_silent_server().await;
+            let options = SmtpOptions {
+                proxy: Some(ProxyConfig {
+                    kind: ProxyKind::Socks5,
+                    host: String::from("127.0.0.1"),
+                    port: proxy_port,
+                    login: None,
+                    passwd: Default::default(),
+                }),
+                connect_timeout: Duration::from_secs(1),
+                retries: 0,
+                ..Default::default()
+            };
+
+            let start = Instant::now();
+            let err = SendSmtpMessage::new(smtp_config(25), options)
+                .send_message(MSG)
+                .await
+                .unwrap_err();
+
+            assert!(start.elapsed() < Duration::from_secs(5));
+            assert_eq!(
+                err.to_string(),
+                "cannot connect to SMTP server 127.0.0.1: timed out after 1s"
+            );
+        }
+
+        #[test]
+        fn hint_at_options_accepting_invalid_certificates() {
+            use email::smtp::Error;
+            use tokio_rustls::rustls::{self, CertificateError};
+
+            let into_report = |err: CertificateError| {
+                let err = rustls::Error::InvalidCertificate(err);
+                let err = Error::ConnectTlsSmtpError(mail_send::Error::Tls(Box::new(err)));
+                into_report(&err)
+            };
+
+            let report = |err: CertificateError| {
+                let report = into_report(err);
+                report.chain().map(ToString::to_string).collect::<Vec<_>>()
+            };
+
+            assert_eq!(
+                report(CertificateError::UnknownIssuer),
+                [
+                    "cannot connect to smtp server using tls",
+                    "TLS error: invalid peer certificate: UnknownIssuer",
+                    "set message.send.backend.accept-invalid-certs to true to accept this certificate",
+                ]
+            );
+
+            let chain = report(CertificateError::NotValidForName);
+            assert_eq!(
+                chain.last().unwrap(),
+                "set message.send.backend.accept-invalid-hostnames to true to accept this certificate"
+            );
+
+            let report = into_report(CertificateError::Expired);
+            let cert = report
+                .chain()
+                .find_map(|err| err.downcast_ref::<InvalidCertificate>());
+            let option = "accept-invalid-certs";
+            assert_eq!(cert, Some(&InvalidCertificate { option }));
+        }
+
+        /// Send 50 messages using one session, then using one session
+        /// per message, like before sessions were reused.
+        ///
+        /// The server waits 20ms before greeting each client, like a
+        /// remote server would. Once built in release mode, on a local
+        /// connection without TLS, the 50 messages were sent in 25ms
+        /// using one session, against 1.08s using one session per
+        /// message (4ms against 65ms without delay).
+        #[tokio::test(flavor = "multi_thread")]
+        async fn reuse_session_across_messages() {
+            const COUNT: usize = 50;
+            const DELAY: Duration = Duration::from_millis(20);
+
+            let (server, port) = Server::start(DELAY, Fault::None).await;
+            let start = Instant::now();
+            let smtp = feature(port);
+            for _ in 0..COUNT {
+                smtp.send_message(MSG).await.unwrap();
+            }
+            drop(smtp);
+            wait_closing_sessions().await;
+            let reused = start.elapsed();
+
+            assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+            assert_eq!(server.quits.load(Ordering::SeqCst), 1);
+            assert_eq!(server.messages().await, COUNT);
+
+            let (server, port) = Server::start(DELAY, Fault::None).await;
+            let start = Instant::now();
+            for _ in 0..COUNT {
+                feature(port).send_message(MSG).await.unwrap();
+            }
+            wait_closing_sessions().await;
+            let reconnected = start.elapsed();
+
+            assert_eq!(server.connections.load(Ordering::SeqCst), COUNT);
+            assert_eq!(server.quits.load(Ordering::SeqCst), COUNT);
+            assert_eq!(server.messages().await, COUNT);
+
+            assert!(reconnected >= DELAY * COUNT as u32);
+            assert!(reused * 4 < reconnected, "{reused:?} vs {reconnected:?}");
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn reconnect_when_session_closes_before_data() {
+            let (server, port) = Server::start(Duration::ZERO, Fault::CloseOnMailFrom).await;
+            let smtp = feature(port);
+
+            smtp.send_message(MSG).await.unwrap();
+            smtp.send_message(MSG).await.unwrap();
+
+            assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+            assert_eq!(server.messages().await, 2);
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn never_resend_once_data_is_issued() {
+            let (server, port) = Server::start(Duration::ZERO, Fault::CloseAfterData).await;
+            let smtp = feature(port);
+
+            smtp.send_message(MSG).await.unwrap();
+            assert!(smtp.send_message(MSG).await.is_err());
+            assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+            assert_eq!(server.messages().await, 2);
+
+            // the closed session is detected by the next reset
+            smtp.send_message(MSG).await.unwrap();
+            assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+            assert_eq!(server.messages().await, 3);
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn retry_when_server_is_busy() {
+            let (server, port) = Server::start(Duration::ZERO, Fault::BusyOnConnect).await;
+            let options = SmtpOptions {
+                retries: 1,
+                retry_delay: Duration::ZERO,
+                ..Default::default()
+            };
+
+            SendSmtpMessage::new(smtp_config(port), options)
+                .send_message(MSG)
+                .await
+                .unwrap();
+
+            assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+            assert_eq!(server.messages().await, 1);
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn give_up_once_retries_are_exhausted() {
+            let (server, port) = Server::start(Duration::ZERO, Fault::BusyOnConnect).await;
+            let options = SmtpOptions {
+                retries: 0,
+                ..Default::default()
+            };
+
+            let sent = SendSmtpMessage::new(smtp_config(port), options)
+                .send_message(MSG)
+                .await;
+
+            assert!(sent.is_err());
+            assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+            assert_eq!(server.messages().await, 0);
+        }
+
+        const BCC_MSG: &[u8] = b"From: alice@localhost\r\n\
+            To: bob@localhost\r\n\
+            Bcc: carol@localhost,\r\n\
+            \tfriends: dave@localhost, eve@localhost;\r\n\
+            Subject: test\r\n\
+            bcc: frank@localhost\r\n\
+            \r\n\
+            Hello!\r\n";
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn remove_bcc_from_transmitted_message() {
+            let (server, port) = Server::start(Duration::ZERO, Fault::None).await;
+
+            feature(port).send_message(BCC_MSG).await.unwrap();
+
+            let messages = server.messages.lock().await;
+            assert_eq!(
+                messages[0],
+                "From: alice@localhost\nTo: bob@localhost\nSubject: test\n\nHello!\n"
+            );
+
+            let rcpts = server.rcpts.lock().await;
+            assert_eq!(
+                *rcpts,
+                [
+                    "<bob@localhost>",
+                    "<carol@localhost>",
+                    "<dave@localhost>",
+                    "<eve@localhost>",
+                    "<frank@localhost>",
+                ]
+            );
+        }
+
+        /// Send a message the way the message send command does, then
+        /// check the copy saved to the sent folder.
+        #[cfg(feature = "maildir")]
+        #[tokio::test(flavor = "multi_thread")]
+        async fn keep_bcc_in_sent_copy() {
+            use std::{env::temp_dir, fs};
+
+            use email::{backend::feature::BackendFeatureSource, config::Config};
+            use pimalaya_tui::{
+                himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _,
+            };
+            use uuid::Uuid;
+
+            use crate::{config::TomlConfig, message::send};
+
+            let (server, port) = Server::start(Duration::ZERO, Fault::None).await;
+            let root = temp_dir().join(format!("himalaya-smtp-{}", Uuid::new_v4()));
+            let sent = root.join("Sent");
+            for dir in ["cur", "new", "tmp"] {
+                fs::create_dir_all(sent.join(dir)).unwrap();
+            }
+
+            let config: TomlConfig = toml::from_str(&format!(
+                "[accounts.test]\n\
+                 default = true\n\
+                 email = \"alice@localhost\"\n\
+                 backend.type = \"maildir\"\n\
+                 backend.root-dir = {root:?}\n\
+                 message.send.backend.type = \"smtp\"\n\
+                 message.send.backend.host = \"127.0.0.1\"\n\
+                 message.send.backend.port = {port}\n\
+                 message.send.backend.login = \"alice\"\n\
+                 message.send.backend.encryption.type = \"none\"\n\
+                 message.send.backend.auth.type = \"password\"\n\
+                 message.send.backend.auth.raw = \"password\"\n\
+                 message.send.save-copy = true\n"
+            ))
+            .unwrap();
+
+            let (toml_account_config, account_config) = config
+                .clone()
+                .into_account_configs(None, |c: &Config, name| c.account(name).ok())
+                .unwrap();
+            let extra_config = config.account_extra_config(&account_config.name);
+
+            let backend = BackendBuilder::new(
+                Arc::new(toml_account_config),
+                Arc::new(account_config),
+                |builder| {
+                    let builder = builder
+                        .without_features()
+                        .with_add_message(BackendFeatureSource::Context);
+
+                    send::with_send_message(builder, &extra_config)
+                },
+            )
+            .build()
+            .await
+            .unwrap();
+
+            backend.send_message_then_save_copy(BCC_MSG).await.unwrap();
+
+            let transmitted = server.messages.lock().await.remove(0);
+            assert!(!transmitted.to_lowercase().contains("bcc"));
+
+            let copies: Vec<_> = ["new", "cur"]
+                .into_iter()
+                .flat_map(|dir| fs::read_dir(sent.join(dir)).unwrap())
+                .map(|entry| fs::read(entry.unwrap().path()).unwrap())
+                .collect();
+            let _ = fs::remove_dir_all(&root);
+
+            assert_eq!(copies, [BCC_MSG]);
+        }
+    }
+}
@@ -1,4 +1,6 @@
 pub mod body;
+#[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+pub mod pgp;
 
 use clap::Parser;
 
@@ -0,0 +1,27 @@
+use clap::Parser;
+
+/// The PGP flags parser.
+#[derive(Debug, Default, Parser)]
+pub struct TemplatePgpFlags {
+    /// Sign the message using PGP/MIME.
+    ///
+    /// The body of the template is wrapped into a signed multipart,
+    /// using the PGP configuration of the account. If the message
+    /// cannot be signed, it is not sent.
+    #[arg(long)]
+    pub sign: bool,
+
+    /// Encrypt the message using PGP/MIME.
+    ///
+    /// The message is encrypted for all its recipients (To, Cc and
+    /// Bcc) and for the sender, so that the copy saved to the sent
+    /// folder remains readable. If the public key of one recipient
+    /// cannot be found, the message is not sent. Combined with
+    /// --sign, the message is signed then encrypted.
+    #[arg(long)]
+    pub encrypt: bool,
+
+    /// Do not encrypt the message for the sender.
+    #[arg(long, requires = "encrypt")]
+    pub no_encrypt_to_self: bool,
+}
@@ -3,18 +3,19 @@ use std::sync::Arc;
 use clap::Parser;
 use color_eyre::{eyre::eyre, Result};
 use email::{backend::feature::BackendFeatureSource, config::Config};
-use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
 use crate::{
     account::arg::name::AccountNameFlag,
     config::TomlConfig,
-    envelope::arg::ids::EnvelopeIdArg,
-    folder::arg::name::FolderNameOptionalFlag,
-    message::arg::{body::MessageRawBodyArg, header::HeaderRawArgs},
+    envelope::{arg::ids::EnvelopeIdArg, picker},
+    folder::{self, arg::name::FolderNameOptionalFlag},
+    message::{
+        arg::{body::MessageRawBodyArg, header::HeaderRawArgs, mdn::MessageMdnFlags},
+        header, mdn,
+    },
+    printer::Printer,
 };
 
 /// Generate a template for forwarding a message.
@@ -36,6 +37,9 @@ pub struct TemplateForwardCommand {
     #[command(flatten)]
     pub body: MessageRawBodyArg,
 
+    #[command(flatten)]
+    pub mdn: MessageMdnFlags,
+
     #[command(flatten)]
     pub account: AccountNameFlag,
 }
@@ -52,6 +56,10 @@ impl TemplateForwardCommand {
                 c.account(name).ok()
             })?;
 
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        let extra_config = config.account_extra_config(&account_config.name);
         let account_config = Arc::new(account_config);
 
         let backend = BackendBuilder::new(
@@ -60,6 +68,7 @@ impl TemplateForwardCommand {
             |builder| {
                 builder
                     .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
                     .with_get_messages(BackendFeatureSource::Context)
             },
         )
@@ -67,18 +76,29 @@ impl TemplateForwardCommand {
         .build()
         .await?;
 
-        let id = self.envelope.id;
-        let tpl = backend
-            .get_messages(folder, &[id])
-            .await?
-            .first()
-            .ok_or(eyre!("cannot find message {id}"))?
-            .to_forward_tpl_builder(account_config)
+        let id = picker::id_or_pick(&backend, folder, self.envelope.id).await?;
+        let msgs = backend.get_messages(folder, &[id]).await?;
+        let msg = msgs.first().ok_or(eyre!("cannot find message {id}"))?;
+
+        let copied_headers = header::collect(msg, extra_config.message_forward_copy_headers())?;
+
+        let mut tpl = msg
+            .to_forward_tpl_builder(account_config.clone())
             .with_headers(self.headers.raw)
             .with_body(self.body.raw())
             .build()
             .await?;
 
+        header::insert(&mut tpl, &copied_headers);
+
+        if let Some(hostname) = extra_config.smtp_helo_hostname() {
+            header::insert_message_id(&mut tpl, hostname.as_str());
+        }
+
+        if self.mdn.is_requested(extra_config.is_mdn_requested()) {
+            mdn::request(&mut tpl, &account_config.email);
+        }
+
         printer.out(tpl)
     }
 }
@@ -6,9 +6,8 @@ mod write;
 
 use clap::Subcommand;
 use color_eyre::Result;
-use pimalaya_tui::terminal::cli::printer::Printer;
 
-use crate::config::TomlConfig;
+use crate::{config::TomlConfig, envelope::picker, printer::Printer};
 
 use self::{
     forward::TemplateForwardCommand, reply::TemplateReplyCommand, save::TemplateSaveCommand,
@@ -28,10 +27,10 @@ pub enum TemplateSubcommand {
     #[command(aliases = ["add", "create", "new", "compose"])]
     Write(TemplateWriteCommand),
 
-    #[command(arg_required_else_help = true)]
+    #[command(arg_required_else_help = !picker::is_enabled())]
     Reply(TemplateReplyCommand),
 
-    #[command(arg_required_else_help = true)]
+    #[command(arg_required_else_help = !picker::is_enabled())]
     #[command(alias = "fwd")]
     Forward(TemplateForwardCommand),
 
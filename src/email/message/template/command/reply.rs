@@ -3,18 +3,23 @@ use std::sync::Arc;
 use clap::Parser;
 use color_eyre::{eyre::eyre, Result};
 use email::{backend::feature::BackendFeatureSource, config::Config};
-use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
 use crate::{
     account::arg::name::AccountNameFlag,
     config::TomlConfig,
-    envelope::arg::ids::EnvelopeIdArg,
-    folder::arg::name::FolderNameOptionalFlag,
-    message::arg::{body::MessageRawBodyArg, header::HeaderRawArgs, reply::MessageReplyAllArg},
+    envelope::{arg::ids::EnvelopeIdArg, picker},
+    folder::{self, arg::name::FolderNameOptionalFlag},
+    message::{
+        arg::{
+            body::MessageRawBodyArg, header::HeaderRawArgs, mdn::MessageMdnFlags,
+            reply::MessageReplyAllArg,
+        },
+        header, mdn,
+        reply::{self, Recipients},
+    },
+    printer::Printer,
 };
 
 /// Generate a template for replying to a message.
@@ -40,6 +45,9 @@ pub struct TemplateReplyCommand {
     #[command(flatten)]
     pub body: MessageRawBodyArg,
 
+    #[command(flatten)]
+    pub mdn: MessageMdnFlags,
+
     #[command(flatten)]
     pub account: AccountNameFlag,
 }
@@ -49,7 +57,6 @@ impl TemplateReplyCommand {
         info!("executing reply template command");
 
         let folder = &self.folder.name;
-        let id = self.envelope.id;
 
         let (toml_account_config, account_config) = config
             .clone()
@@ -57,6 +64,10 @@ impl TemplateReplyCommand {
                 c.account(name).ok()
             })?;
 
+        let folder =
+            &folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        let extra_config = config.account_extra_config(&account_config.name);
         let account_config = Arc::new(account_config);
 
         let backend = BackendBuilder::new(
@@ -65,6 +76,7 @@ impl TemplateReplyCommand {
             |builder| {
                 builder
                     .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
                     .with_get_messages(BackendFeatureSource::Context)
             },
         )
@@ -72,18 +84,38 @@ impl TemplateReplyCommand {
         .build()
         .await?;
 
-        let tpl = backend
-            .get_messages(folder, &[id])
-            .await?
-            .first()
-            .ok_or(eyre!("cannot find message {id}"))?
-            .to_reply_tpl_builder(account_config)
+        let id = picker::id_or_pick(&backend, folder, self.envelope.id).await?;
+
+        let msgs = backend.get_messages(folder, &[id]).await?;
+        let msg = msgs.first().ok_or(eyre!("cannot find message {id}"))?;
+
+        let copied_headers = header::collect(msg, extra_config.message_reply_copy_headers())?;
+
+        let mut tpl = msg
+            .to_reply_tpl_builder(account_config.clone())
             .with_headers(self.headers.raw)
             .with_body(self.body.raw())
             .with_reply_all(self.reply.all)
             .build()
             .await?;
 
+        let own = [account_config.email.as_str()]
+            .into_iter()
+            .chain(extra_config.alternates().iter().map(String::as_str))
+            .collect::<Vec<_>>();
+        Recipients::new(msg, &own, self.reply.all, self.reply.list)?.set(&mut tpl);
+        reply::thread(&mut tpl, msg)?;
+
+        header::insert(&mut tpl, &copied_headers);
+
+        if let Some(hostname) = extra_config.smtp_helo_hostname() {
+            header::insert_message_id(&mut tpl, hostname.as_str());
+        }
+
+        if self.mdn.is_requested(extra_config.is_mdn_requested()) {
+            mdn::request(&mut tpl, &account_config.email);
+        }
+
         printer.out(tpl)
     }
 }
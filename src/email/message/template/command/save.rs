@@ -2,10 +2,7 @@ use clap::Parser;
 use color_eyre::Result;
 use email::{backend::feature::BackendFeatureSource, config::Config};
 use mml::MmlCompilerBuilder;
-use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use std::{
     io::{self, BufRead, IsTerminal},
     sync::Arc,
@@ -13,8 +10,11 @@ use std::{
 use tracing::info;
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig, email::template::arg::TemplateRawArg,
-    folder::arg::name::FolderNameOptionalFlag,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    email::template::{arg::TemplateRawArg, compiler},
+    folder::{self, arg::name::FolderNameOptionalFlag},
+    printer::Printer,
 };
 
 /// Save a template to a folder.
@@ -47,6 +47,9 @@ impl TemplateSaveCommand {
                 c.account(name).ok()
             })?;
 
+        let backend_folder =
+            folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
         let account_config = Arc::new(account_config);
 
         let backend = BackendBuilder::new(
@@ -75,16 +78,18 @@ impl TemplateSaveCommand {
                 .join("\n")
         };
 
+        let tpl = config.aliases.expand_tpl(&tpl)?;
+
         #[allow(unused_mut)]
         let mut compiler = MmlCompilerBuilder::new();
 
         #[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
         compiler.set_some_pgp(account_config.pgp.clone());
 
-        let msg = compiler.build(tpl.as_str())?.compile().await?.into_vec()?;
+        let msg = compiler::compile(compiler, &tpl).await?;
 
-        backend.add_message(folder, &msg).await?;
+        backend.add_message(&backend_folder, &msg).await?;
 
-        printer.out(format!("Template successfully saved to {folder}!\n"))
+        printer.info(format!("Template successfully saved to {folder}!\n"))
     }
 }
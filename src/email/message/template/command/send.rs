@@ -7,27 +7,66 @@ use clap::Parser;
 use color_eyre::Result;
 use email::{backend::feature::BackendFeatureSource, config::Config};
 use mml::MmlCompilerBuilder;
-use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
+#[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+use crate::email::template::{self, arg::pgp::TemplatePgpFlags};
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig, email::template::arg::TemplateRawArg,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    email::{
+        message::{
+            arg::mdn::MessageMdnFlags,
+            mdn,
+            send::{self, dry_run, hook},
+        },
+        template::{arg::TemplateRawArg, compiler},
+    },
+    folder::{self, special_use::FolderRole},
+    outbox::{self, arg::schedule::SendLaterFlags, Outbox},
+    printer::Printer,
 };
 
 /// Send a template.
 ///
 /// This command allows you to send a template and save a copy to the
-/// sent folder. The template is compiled into a MIME message before
-/// being sent. If you want to send a raw message, use the message
-/// send command instead.
+/// sent folder. Headers of the template are validated, then the
+/// template is compiled into a MIME message before being sent. If
+/// you want to send a raw message, use the message send command
+/// instead.
 #[derive(Debug, Parser)]
 pub struct TemplateSendCommand {
     #[command(flatten)]
     pub template: TemplateRawArg,
 
+    #[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+    #[command(flatten)]
+    pub pgp: TemplatePgpFlags,
+
+    #[command(flatten)]
+    pub schedule: SendLaterFlags,
+
+    #[command(flatten)]
+    pub mdn: MessageMdnFlags,
+
+    /// Compile the template without sending it.
+    ///
+    /// The compiled message is prepared the same way the sending
+    /// backend does (pre-send hooks, Bcc removal), then printed along
+    /// with its envelope and the backend that would send it. Nothing
+    /// is sent, queued nor saved.
+    #[arg(long)]
+    #[arg(conflicts_with_all = ["send_at", "send_in"])]
+    pub dry_run: bool,
+
+    /// Do not run the pre-send hooks of the account.
+    ///
+    /// The message is sent as is, without being piped to the
+    /// hooks.pre-send nor to the message.send.pre-hook command.
+    #[arg(long)]
+    pub no_hooks: bool,
+
     #[command(flatten)]
     pub account: AccountNameFlag,
 }
@@ -36,26 +75,19 @@ impl TemplateSendCommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing send template command");
 
-        let (toml_account_config, account_config) = config
+        let (toml_account_config, mut account_config) = config
             .clone()
             .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
                 c.account(name).ok()
             })?;
 
-        let account_config = Arc::new(account_config);
+        let mut extra_config = config.account_extra_config(&account_config.name);
 
-        let backend = BackendBuilder::new(
-            Arc::new(toml_account_config),
-            account_config.clone(),
-            |builder| {
-                builder
-                    .without_features()
-                    .with_add_message(BackendFeatureSource::Context)
-                    .with_send_message(BackendFeatureSource::Context)
-            },
-        )
-        .build()
-        .await?;
+        if self.no_hooks {
+            hook::disable(&mut extra_config, &mut account_config);
+        }
+
+        let mut account_config = Arc::new(account_config);
 
         let tpl = if io::stdin().is_terminal() {
             self.template.raw()
@@ -68,16 +100,82 @@ impl TemplateSendCommand {
                 .join("\n")
         };
 
+        let tpl = config.aliases.expand_tpl(&tpl)?;
+        send::validate(tpl.as_bytes())?;
+
+        #[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+        let tpl = template::pgp::wrap(&tpl, &self.pgp);
+
         #[allow(unused_mut)]
         let mut compiler = MmlCompilerBuilder::new();
 
         #[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
         compiler.set_some_pgp(account_config.pgp.clone());
 
-        let msg = compiler.build(tpl.as_str())?.compile().await?.into_vec()?;
+        let mut msg = compiler::compile(compiler, &tpl).await?;
 
-        backend.send_message_then_save_copy(&msg).await?;
+        if self.mdn.is_requested(extra_config.is_mdn_requested()) {
+            msg = mdn::request_raw(&msg, &account_config.email);
+        }
+
+        #[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+        template::pgp::check(&msg, &self.pgp)?;
+
+        #[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+        let msg = template::pgp::encrypt(msg, account_config.pgp.clone(), &self.pgp).await?;
+
+        if self.dry_run {
+            let dry_run =
+                dry_run::prepare(&toml_account_config, &account_config, &extra_config, &msg)
+                    .await?;
+            return printer.out(dry_run);
+        }
+
+        if let Some(send_at) = self.schedule.send_at() {
+            let id = Outbox::new(&account_config.name)?.push(&msg, Some(send_at))?;
+            let send_at = outbox::format(send_at);
+            return printer.info(format!(
+                "Message queued in the outbox as {id}, to be sent at {send_at}!"
+            ));
+        }
+
+        folder::special_use::add_aliases(
+            &toml_account_config,
+            Arc::make_mut(&mut account_config),
+            &[FolderRole::Sent],
+        )
+        .await;
+
+        let queue_on_failure = extra_config.is_send_queue_on_failure_enabled();
+
+        let backend = BackendBuilder::new(
+            Arc::new(toml_account_config),
+            account_config.clone(),
+            |builder| {
+                let builder = builder
+                    .without_features()
+                    .with_add_message(BackendFeatureSource::Context);
+
+                send::with_send_message(builder, &extra_config)
+            },
+        )
+        .build()
+        .await;
+
+        let res = match backend {
+            Ok(backend) if queue_on_failure => outbox::send(printer, &backend, &msg).await,
+            Ok(backend) => Ok(backend.send_message_then_save_copy(&msg).await?),
+            Err(err) => Err(err),
+        };
 
-        printer.out("Message successfully sent!")
+        match res {
+            Ok(()) => printer.info("Message successfully sent!"),
+            Err(err) if queue_on_failure && !hook::is_hook_error(&err) => {
+                let outbox = Outbox::new(&account_config.name)?;
+                let id = outbox::queue_failed(printer, &outbox, &msg, &err)?;
+                printer.info(format!("Message queued in the outbox as {id}!"))
+            }
+            Err(err) => Err(err),
+        }
     }
 }
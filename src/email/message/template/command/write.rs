@@ -3,12 +3,18 @@ use std::sync::Arc;
 use clap::Parser;
 use color_eyre::Result;
 use email::{config::Config, message::Message};
-use pimalaya_tui::terminal::{cli::printer::Printer, config::TomlConfig as _};
+use pimalaya_tui::terminal::config::TomlConfig as _;
 use tracing::info;
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig,
-    email::template::arg::body::TemplateRawBodyArg, message::arg::header::HeaderRawArgs,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    email::template::arg::body::TemplateRawBodyArg,
+    message::{
+        arg::{header::HeaderRawArgs, mdn::MessageMdnFlags},
+        header, mdn,
+    },
+    printer::Printer,
 };
 
 /// Generate a template for writing a new message from scratch.
@@ -23,6 +29,9 @@ pub struct TemplateWriteCommand {
     #[command(flatten)]
     pub body: TemplateRawBodyArg,
 
+    #[command(flatten)]
+    pub mdn: MessageMdnFlags,
+
     #[command(flatten)]
     pub account: AccountNameFlag,
 }
@@ -37,12 +46,23 @@ impl TemplateWriteCommand {
                 c.account(name).ok()
             })?;
 
-        let tpl = Message::new_tpl_builder(Arc::new(account_config))
+        let extra_config = config.account_extra_config(&account_config.name);
+        let account_config = Arc::new(account_config);
+
+        let mut tpl = Message::new_tpl_builder(account_config.clone())
             .with_headers(self.headers.raw)
             .with_body(self.body.raw())
             .build()
             .await?;
 
+        if let Some(hostname) = extra_config.smtp_helo_hostname() {
+            header::insert_message_id(&mut tpl, hostname.as_str());
+        }
+
+        if self.mdn.is_requested(extra_config.is_mdn_requested()) {
+            mdn::request(&mut tpl, &account_config.email);
+        }
+
         printer.out(tpl)
     }
 }
@@ -0,0 +1,95 @@
+//! Module dedicated to the compilation of templates.
+//!
+//! Templates are compiled into MIME messages by the MML compiler.
+//! The compiler only reports that the MML body cannot be parsed, so
+//! parsing errors are located here, using the template they come
+//! from.
+
+use color_eyre::{Report, Result};
+use mml::MmlCompilerBuilder;
+
+use crate::message::header;
+
+/// The headers holding lists of message ids.
+///
+/// The MML compiler drops the angle brackets of the ids when such a
+/// header holds more than one, so their value is copied from the
+/// template.
+const ID_HEADERS: [&str; 2] = ["In-Reply-To", "References"];
+
+/// Compile the given template into a raw MIME message.
+///
+/// MML parsing errors point at the offending line of the template.
+pub async fn compile(compiler: MmlCompilerBuilder, tpl: &str) -> Result<Vec<u8>> {
+    let compile = async {
+        let msg = compiler.build(tpl)?.compile().await?.into_vec()?;
+        Ok::<_, mml::Error>(msg)
+    };
+
+    let msg = compile.await.map_err(|err| locate(err.into(), Some(tpl)))?;
+
+    Ok(restore_ids(tpl, msg))
+}
+
+/// Copy the message id headers of the given template to the given
+/// compiled message.
+fn restore_ids(tpl: &str, mut msg: Vec<u8>) -> Vec<u8> {
+    for name in ID_HEADERS {
+        let Some(val) = header::get(tpl.as_bytes(), name) else {
+            continue;
+        };
+
+        if !val.is_empty() {
+            msg = header::append(&header::remove(&msg, name), name, &val);
+        }
+    }
+
+    msg
+}
+
+/// Locate the given error in the given template, if it is an MML
+/// parsing error.
+///
+/// Lines are counted from the beginning of the template when given,
+/// otherwise from the beginning of the MML body.
+fn locate(err: Report, tpl: Option<&str>) -> Report {
+    let Some(mml::Error::ParseMmlError(errs, body)) = err.downcast_ref::<mml::Error>() else {
+        return err;
+    };
+
+    let Some(parse_err) = errs.first() else {
+        return err;
+    };
+
+    // lines preceding the body: headers and the blank separator
+    let offset = tpl.and_then(|tpl| {
+        let start = match tpl.ends_with(body.as_str()) {
+            true => tpl.len() - body.len(),
+            false => tpl.find(body.as_str())?,
+        };
+        Some(count_lines(&tpl.as_bytes()[..start]))
+    });
+
+    let start = parse_err.span().start.min(body.len());
+    let before = &body.as_bytes()[..start];
+    let line = offset.unwrap_or_default() + count_lines(before) + 1;
+    let column = match before.iter().rposition(|b| *b == b'\n') {
+        Some(i) => String::from_utf8_lossy(&before[i + 1..]).chars().count() + 1,
+        None => String::from_utf8_lossy(before).chars().count() + 1,
+    };
+
+    let reason = parse_err.reason();
+    let content = body.lines().nth(count_lines(before)).unwrap_or_default();
+
+    let location = match offset {
+        Some(_) => format!("template at line {line}, column {column}"),
+        None => format!("MML body at line {line}, column {column}"),
+    };
+
+    let msg = format!("cannot parse {location}: {reason}\n  | {content}");
+    err.wrap_err(msg)
+}
+
+fn count_lines(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|b| **b == b'\n').count()
+}
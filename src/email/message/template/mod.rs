@@ -1,2 +1,5 @@
 pub mod arg;
 pub mod command;
+pub mod compiler;
+#[cfg(any(feature = "pgp-gpg", feature = "pgp-commands", feature = "pgp-native"))]
+pub mod pgp;
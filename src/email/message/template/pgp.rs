@@ -0,0 +1,174 @@
+//! Module dedicated to PGP/MIME templates.
+
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use email::account::config::pgp::PgpConfig;
+use mail_parser::{Address, MessageParser, MimeHeaders};
+use mml::pgp::Pgp;
+use tracing::debug;
+use uuid::Uuid;
+
+use super::arg::pgp::TemplatePgpFlags;
+use crate::message::header;
+
+/// Wrap the body of the given template into a multipart signed using
+/// PGP/MIME, if the sign flag is given.
+///
+/// Encryption is not delegated to the MML compiler, see [`encrypt`].
+pub fn wrap(tpl: &str, flags: &TemplatePgpFlags) -> String {
+    if !flags.sign {
+        return tpl.to_owned();
+    }
+
+    let (headers, body) = match tpl.split_once("\n\n") {
+        Some((headers, body)) => (headers, body),
+        None => (tpl.trim_end_matches('\n'), ""),
+    };
+
+    // the body starts on the line of the opening tag, so that MML
+    // parsing errors point at the lines of the original template
+    format!("{headers}\n\n<#multipart type=mixed sign=pgpmime>{body}\n<#/multipart>\n")
+}
+
+/// Ensure the given compiled message has been signed, if the sign
+/// flag is given.
+///
+/// The MML compiler falls back to the clear part when PGP fails,
+/// which would lead to sending messages that were expected to be
+/// protected.
+pub fn check(msg: &[u8], flags: &TemplatePgpFlags) -> Result<()> {
+    if !flags.sign {
+        return Ok(());
+    }
+
+    let msg = MessageParser::new()
+        .parse_headers(msg)
+        .ok_or(eyre!("cannot parse compiled message"))?;
+
+    let ctype = msg
+        .content_type()
+        .map(|ctype| (ctype.ctype(), ctype.subtype().unwrap_or_default()));
+
+    if !matches!(ctype, Some(("multipart", "signed"))) {
+        bail!("cannot sign message using PGP, run with --debug for more details");
+    }
+
+    Ok(())
+}
+
+/// Encrypt the given compiled message using PGP/MIME, if the encrypt
+/// flag is given.
+///
+/// The MML compiler only encrypts for the To recipients and silently
+/// falls back to the clear part when a key is missing, so the
+/// multipart/encrypted structure (RFC 3156) is built here instead.
+pub async fn encrypt(
+    msg: Vec<u8>,
+    config: Option<PgpConfig>,
+    flags: &TemplatePgpFlags,
+) -> Result<Vec<u8>> {
+    if !flags.encrypt {
+        return Ok(msg);
+    }
+
+    let Some(config) = config else {
+        bail!("cannot encrypt message: PGP is not configured for this account");
+    };
+
+    let pgp = Pgp::from(config);
+    let recipients = get_recipients(&msg, !flags.no_encrypt_to_self)?;
+
+    let mut missing = Vec::new();
+
+    for recipient in &recipients {
+        if let Err(err) = pgp.encrypt([recipient.clone()], Vec::new()).await {
+            debug!("cannot encrypt for {recipient}: {err}");
+            missing.push(recipient.as_str());
+        }
+    }
+
+    if !missing.is_empty() {
+        bail!(
+            "cannot find PGP public key of {}, message not sent",
+            missing.join(", ")
+        );
+    }
+
+    let (headers, body) = header::split(&msg);
+
+    let mut clear_part = Vec::new();
+    let mut encrypted_msg = Vec::new();
+
+    for header in headers {
+        if header::is_content(header) {
+            clear_part.extend_from_slice(header);
+        } else {
+            encrypted_msg.extend_from_slice(header);
+        }
+    }
+
+    clear_part.extend_from_slice(b"\r\n");
+    clear_part.extend_from_slice(body);
+
+    let encrypted_part = pgp.encrypt(recipients, clear_part).await?;
+    let boundary = Uuid::new_v4().to_simple().to_string();
+
+    encrypted_msg.extend_from_slice(
+        format!(
+            "Content-Type: multipart/encrypted; protocol=\"application/pgp-encrypted\";\r\n\
+             \tboundary=\"{boundary}\"\r\n\
+             \r\n\
+             --{boundary}\r\n\
+             Content-Type: application/pgp-encrypted\r\n\
+             Content-Description: PGP/MIME version identification\r\n\
+             \r\n\
+             Version: 1\r\n\
+             \r\n\
+             --{boundary}\r\n\
+             Content-Type: application/octet-stream; name=\"encrypted.asc\"\r\n\
+             Content-Description: OpenPGP encrypted message\r\n\
+             Content-Disposition: inline; filename=\"encrypted.asc\"\r\n\
+             \r\n"
+        )
+        .as_bytes(),
+    );
+
+    for line in encrypted_part.split(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        encrypted_msg.extend_from_slice(line);
+        encrypted_msg.extend_from_slice(b"\r\n");
+    }
+
+    encrypted_msg.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+
+    Ok(encrypted_msg)
+}
+
+/// Get the deduplicated recipients of the given message, including
+/// the sender when `to_self` is `true`.
+fn get_recipients(msg: &[u8], to_self: bool) -> Result<Vec<String>> {
+    let msg = MessageParser::new()
+        .parse_headers(msg)
+        .ok_or(eyre!("cannot parse compiled message"))?;
+
+    let from = msg.from().filter(|_| to_self);
+
+    let mut recipients: Vec<String> = [msg.to(), msg.cc(), msg.bcc(), from]
+        .into_iter()
+        .flatten()
+        .flat_map(Address::iter)
+        .filter_map(|addr| addr.address())
+        .map(str::to_lowercase)
+        .collect();
+
+    recipients.sort();
+    recipients.dedup();
+
+    if recipients.is_empty() {
+        bail!("cannot encrypt message without recipient");
+    }
+
+    Ok(recipients)
+}
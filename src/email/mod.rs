@@ -3,6 +3,6 @@ pub mod message;
 
 #[doc(inline)]
 pub use self::{
-    envelope::flag,
+    envelope::{flag, tag},
     message::{attachment, template},
 };
@@ -0,0 +1,152 @@
+//! Module dedicated to test fixtures.
+//!
+//! Fixtures are shared by the unit tests of several modules, like
+//! the temporary Maildir roots used to test the Maildir backend.
+
+use std::{env::temp_dir, fs, future::Future, path::PathBuf, sync::Arc, thread, time::Duration};
+
+use email::{
+    backend::feature::BackendFeatureSource, config::Config, envelope::list::ListEnvelopesOptions,
+};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
+use tokio::runtime;
+use uuid::Uuid;
+
+use crate::config::TomlConfig;
+
+/// A temporary Maildir root, removed once dropped.
+pub struct MaildirRoot(pub PathBuf);
+
+impl MaildirRoot {
+    /// Create an empty Maildir root in the temporary directory.
+    pub fn new() -> Self {
+        let root = temp_dir().join(format!("himalaya-maildir-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        Self(root)
+    }
+
+    /// Create the given Maildir folder directory, relative to the
+    /// root, with one message per given file name, relative to the
+    /// folder directory.
+    ///
+    /// An empty directory name stands for the root itself, which is
+    /// the inbox of Maildir++ roots.
+    pub fn folder(&self, dir: &str, files: &[&str]) -> PathBuf {
+        let dir = self.0.join(dir);
+
+        for subdir in ["cur", "new", "tmp"] {
+            fs::create_dir_all(dir.join(subdir)).unwrap();
+        }
+
+        for file in files {
+            fs::write(dir.join(file), "Subject: test\r\n\r\ntest\r\n").unwrap();
+        }
+
+        dir
+    }
+}
+
+impl Drop for MaildirRoot {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The database mapping the ids of an account folder to aliases,
+/// removed once dropped.
+///
+/// The database lives in the data directory of the user, see
+/// [`pimalaya_tui::himalaya::id_mapper::IdMapper`], so tests using it
+/// should run on accounts of their own.
+pub struct IdMapperDb(pub PathBuf);
+
+impl IdMapperDb {
+    /// Locate the database of the given account folder.
+    pub fn new(account: &str, folder: &str) -> Self {
+        let digest = md5::compute(account.to_owned() + folder);
+        let path = dirs::data_dir()
+            .unwrap()
+            .join("himalaya")
+            .join(".id-mappers")
+            .join(format!("{digest:x}"));
+        Self(path)
+    }
+}
+
+impl Drop for IdMapperDb {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Run the given future on a runtime of its own, shut down before
+/// returning.
+///
+/// Backends may leave tasks behind, holding the [`IdMapperDb`] they
+/// opened. Shutting their runtime down releases the database, so that
+/// the next backend can open it.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+/// List the ids of the envelopes of the given folder of the default
+/// account, which creates their aliases.
+pub fn envelope_ids(config: &TomlConfig, folder: &str) -> Vec<String> {
+    let (toml_account_config, account_config) = config
+        .clone()
+        .into_account_configs(None, |c: &Config, name| c.account(name).ok())
+        .unwrap();
+
+    block_on(async {
+        let backend = BackendBuilder::new(
+            Arc::new(toml_account_config),
+            Arc::new(account_config),
+            |builder| {
+                builder
+                    .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
+            },
+        )
+        .without_sending_backend()
+        .build()
+        .await
+        .unwrap();
+
+        let opts = ListEnvelopesOptions {
+            page_size: 0,
+            page: 0,
+            query: None,
+        };
+        let envelopes = backend.list_envelopes(folder, opts).await.unwrap();
+        envelopes
+            .iter()
+            .map(|envelope| envelope.id.clone())
+            .collect()
+    })
+}
+
+/// Run the given command until the [`IdMapperDb`] it opens is
+/// available.
+///
+/// The database is opened by each backend operation, and may still be
+/// held for a moment by the background threads of the previous one.
+/// Commands should run on runtimes of their own, see [`block_on`].
+pub fn retry_locked<T>(
+    mut command: impl FnMut() -> color_eyre::Result<T>,
+) -> color_eyre::Result<T> {
+    let mut attempts = 1;
+
+    loop {
+        match command() {
+            Err(err) if attempts < 50 && format!("{err:?}").contains("acquire lock") => {
+                thread::sleep(Duration::from_millis(100));
+                attempts += 1;
+            }
+            res => return res,
+        }
+    }
+}
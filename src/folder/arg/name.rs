@@ -1,23 +1,206 @@
-use clap::Parser;
+use std::env;
+
+use clap::{parser::ValueSource, ArgMatches, Args, Command, FromArgMatches, Parser};
+use color_eyre::{eyre::bail, Result};
 use email::folder::INBOX;
 
+use crate::envelope::arg::query;
+
+/// The environment variable giving the default folder name.
+pub const FOLDER_ENV: &str = "HIMALAYA_FOLDER";
+
+/// The prefix used to give a folder name among query arguments.
+pub const FOLDER_PREFIX: &str = "folder:";
+
 /// The optional folder name flag parser.
-#[derive(Debug, Parser)]
+#[derive(Debug)]
 pub struct FolderNameOptionalFlag {
+    pub name: String,
+
+    /// Whether the name has been given explicitly, using the flag or
+    /// among query arguments, rather than taken from the environment
+    /// or defaulted to the inbox.
+    pub explicit: bool,
+}
+
+/// The optional folder name flag, as declared to clap.
+#[derive(Parser)]
+struct FolderNameFlag {
     /// The name of the folder.
-    #[arg(long = "folder", short = 'f')]
+    ///
+    /// The HIMALAYA_FOLDER environment variable is used when the
+    /// flag is omitted, before the inbox.
+    #[arg(long = "folder", short = 'f', env = FOLDER_ENV)]
     #[arg(name = "folder_name", value_name = "NAME", default_value = INBOX)]
-    pub name: String,
+    name: String,
+}
+
+impl Args for FolderNameOptionalFlag {
+    fn augment_args(cmd: Command) -> Command {
+        FolderNameFlag::augment_args(cmd)
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        FolderNameFlag::augment_args_for_update(cmd)
+    }
+}
+
+impl FromArgMatches for FolderNameOptionalFlag {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        let flag = FolderNameFlag::from_arg_matches(matches)?;
+        let source = matches.value_source("folder_name");
+
+        Ok(Self {
+            name: flag.name,
+            explicit: source == Some(ValueSource::CommandLine),
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
 }
 
 impl Default for FolderNameOptionalFlag {
     fn default() -> Self {
         Self {
-            name: INBOX.to_owned(),
+            name: default_folder_name(),
+            explicit: false,
         }
     }
 }
 
+impl FolderNameOptionalFlag {
+    /// Take the folder name from the given query arguments.
+    ///
+    /// Positional arguments are parsed as a query, unless the first
+    /// one is prefixed by `folder:` or names an existing folder (see
+    /// [`first_arg`](Self::first_arg)). In the former case the folder
+    /// name is taken out of the query.
+    ///
+    /// The folder name ends where the query starts, so that both
+    /// `folder:Archives order by date` and `folder:"My Folder"` can
+    /// be given as one argument. A name looking like a query can be
+    /// quoted (for example `folder:"Lists from 2024"`).
+    ///
+    /// Giving both `--folder` and a different `folder:` argument is
+    /// refused as ambiguous, while the `folder:` argument takes
+    /// precedence over the HIMALAYA_FOLDER environment variable.
+    pub fn take_from_query(&mut self, query: &mut Option<Vec<String>>) -> Result<()> {
+        let Some(args) = query.as_mut() else {
+            return Ok(());
+        };
+
+        let Some(arg) = args.first().and_then(|arg| arg.strip_prefix(FOLDER_PREFIX)) else {
+            return Ok(());
+        };
+
+        let (name, rest) = split_folder_name(arg);
+
+        if name.is_empty() {
+            bail!("missing folder name after `{FOLDER_PREFIX}`");
+        }
+
+        if self.explicit && self.name != name {
+            bail!(
+                "ambiguous folder: both --folder {} and {FOLDER_PREFIX}{name} given",
+                self.name
+            );
+        }
+
+        let rest = rest.trim().to_owned();
+        self.name = name;
+        self.explicit = true;
+
+        if rest.is_empty() {
+            args.remove(0);
+        } else {
+            args[0] = rest;
+        }
+
+        if args.is_empty() {
+            *query = None;
+        }
+
+        Ok(())
+    }
+
+    /// Get the first query argument when it may name a folder, which
+    /// is the case when no folder has been given explicitly.
+    ///
+    /// The argument names a folder only when the folder exists, which
+    /// is left to the caller to check before calling
+    /// [`take_first_arg`](Self::take_first_arg).
+    pub fn first_arg<'a>(&self, query: &'a Option<Vec<String>>) -> Option<&'a str> {
+        if self.explicit {
+            return None;
+        }
+
+        query.as_ref()?.first().map(String::as_str)
+    }
+
+    /// Take the first query argument as the folder name, once it has
+    /// been found among the existing folders.
+    ///
+    /// An argument that also reads as a query, like a folder named
+    /// `from:alice`, is refused as ambiguous: the `folder:` prefix or
+    /// the `--folder` flag must be used instead.
+    pub fn take_first_arg(&mut self, query: &mut Option<Vec<String>>) -> Result<()> {
+        let Some(args) = query.as_mut().filter(|args| !args.is_empty()) else {
+            return Ok(());
+        };
+
+        if !is_folder_like_query(&args[..1]) {
+            let name = &args[0];
+            bail!(
+                "ambiguous argument {name:?}: a folder has this name but it reads as a query, \
+                 use {FOLDER_PREFIX}\"{name}\" to select the folder or --folder to search it"
+            );
+        }
+
+        self.name = args.remove(0);
+        self.explicit = true;
+
+        if args.is_empty() {
+            *query = None;
+        }
+
+        Ok(())
+    }
+}
+
+/// Split the given value of a `folder:` argument into the folder
+/// name and the rest of the query.
+fn split_folder_name(arg: &str) -> (String, &str) {
+    if let Some(quoted) = arg.strip_prefix('"') {
+        return match quoted.split_once('"') {
+            Some((name, rest)) => (name.to_owned(), rest),
+            None => (quoted.to_owned(), ""),
+        };
+    }
+
+    let (name, rest) = query::split_query(arg);
+    (name.trim().to_owned(), rest)
+}
+
+/// Get the folder name used when none is given, from the
+/// HIMALAYA_FOLDER environment variable or defaulting to the inbox.
+pub fn default_folder_name() -> String {
+    env::var(FOLDER_ENV).unwrap_or_else(|_| INBOX.to_owned())
+}
+
+/// Return `true` if the given query arguments start like a folder
+/// name rather than a query.
+pub fn is_folder_like_query(query: &[String]) -> bool {
+    let mut words = query.iter().flat_map(|arg| arg.split_whitespace());
+
+    match words.next() {
+        Some(word) => !query::is_query_start(word, words.next()),
+        None => false,
+    }
+}
+
 /// The optional folder name argument parser.
 #[derive(Debug, Parser)]
 pub struct FolderNameOptionalArg {
@@ -46,7 +229,10 @@ pub struct FolderNameArg {
 #[derive(Debug, Parser)]
 pub struct SourceFolderNameOptionalFlag {
     /// The name of the source folder.
-    #[arg(long = "folder", short = 'f')]
+    ///
+    /// The HIMALAYA_FOLDER environment variable is used when the
+    /// flag is omitted, before the inbox.
+    #[arg(long = "folder", short = 'f', env = FOLDER_ENV)]
     #[arg(name = "source_folder_name", value_name = "SOURCE", default_value = INBOX)]
     pub name: String,
 }
@@ -58,3 +244,228 @@ pub struct TargetFolderNameArg {
     #[arg(name = "target_folder_name", value_name = "TARGET")]
     pub name: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, FromArgMatches, Parser};
+
+    use super::{is_folder_like_query, FolderNameOptionalFlag};
+
+    #[derive(Parser)]
+    struct Command {
+        #[command(flatten)]
+        folder: FolderNameOptionalFlag,
+    }
+
+    fn flag(name: &str, explicit: bool) -> FolderNameOptionalFlag {
+        FolderNameOptionalFlag {
+            name: name.to_owned(),
+            explicit,
+        }
+    }
+
+    fn query(args: &[&str]) -> Option<Vec<String>> {
+        Some(args.iter().map(ToString::to_string).collect())
+    }
+
+    /// Take the folder name out of the given query arguments, then
+    /// return it along with the rest of the query.
+    fn take(args: &[&str]) -> (String, Option<Vec<String>>) {
+        let mut folder = flag("INBOX", false);
+        let mut query = query(args);
+        folder.take_from_query(&mut query).unwrap();
+        (folder.name, query)
+    }
+
+    #[test]
+    fn folder_prefix() {
+        assert_eq!(take(&["folder:Archives"]), (String::from("Archives"), None));
+        assert_eq!(
+            take(&["folder:Archives", "from:alice"]),
+            (String::from("Archives"), query(&["from:alice"])),
+        );
+        assert_eq!(
+            take(&["from:alice", "folder:Archives"]),
+            (
+                String::from("INBOX"),
+                query(&["from:alice", "folder:Archives"])
+            ),
+        );
+        assert_eq!(
+            take(&["Archives"]),
+            (String::from("INBOX"), query(&["Archives"]))
+        );
+
+        let mut folder = flag("INBOX", false);
+        assert!(folder.take_from_query(&mut query(&["folder:"])).is_err());
+    }
+
+    #[test]
+    fn folder_prefix_with_query_in_one_argument() {
+        assert_eq!(
+            take(&["folder:Archives order by date"]),
+            (String::from("Archives"), query(&["order by date"])),
+        );
+        assert_eq!(
+            take(&["folder:Archives from:alice or subject foo"]),
+            (
+                String::from("Archives"),
+                query(&["from:alice or subject foo"])
+            ),
+        );
+        assert_eq!(
+            take(&["folder:Archives (from:alice)"]),
+            (String::from("Archives"), query(&["(from:alice)"])),
+        );
+    }
+
+    #[test]
+    fn folder_names_with_spaces() {
+        assert_eq!(
+            take(&["folder:My Folder"]),
+            (String::from("My Folder"), None)
+        );
+        assert_eq!(
+            take(&["folder:My Folder", "not flag:seen"]),
+            (String::from("My Folder"), query(&["not flag:seen"])),
+        );
+        assert_eq!(
+            take(&["folder:My Folder not flag:seen"]),
+            (String::from("My Folder"), query(&["not flag:seen"])),
+        );
+        assert_eq!(
+            take(&["folder:Purchase order"]),
+            (String::from("Purchase order"), None),
+        );
+        assert_eq!(
+            take(&["folder:\"Lists from 2024\" order by date"]),
+            (String::from("Lists from 2024"), query(&["order by date"])),
+        );
+    }
+
+    #[test]
+    fn folder_names_with_colons() {
+        assert_eq!(
+            take(&["folder:Work:2024"]),
+            (String::from("Work:2024"), None)
+        );
+        assert_eq!(
+            take(&["folder:from:alice"]),
+            (String::from("from:alice"), None)
+        );
+        assert_eq!(
+            take(&["folder:Work:2024 subject:report"]),
+            (String::from("Work:2024"), query(&["subject:report"])),
+        );
+        assert_eq!(
+            take(&["folder:\"to: bob\"", "from:alice"]),
+            (String::from("to: bob"), query(&["from:alice"])),
+        );
+    }
+
+    #[test]
+    fn ambiguous_folder() {
+        let mut folder = flag("Sent", true);
+        assert!(folder
+            .take_from_query(&mut query(&["folder:Archives"]))
+            .is_err());
+
+        let mut folder = flag("Archives", true);
+        folder
+            .take_from_query(&mut query(&["folder:Archives"]))
+            .unwrap();
+        assert_eq!(folder.name, "Archives");
+    }
+
+    /// Parse the given arguments, the folder flag defaulting to the
+    /// given name instead of reading the environment variable, which
+    /// is shared by all tests.
+    fn parse(args: &[&str], default: &'static str) -> FolderNameOptionalFlag {
+        let matches = Command::command()
+            .mut_arg("folder_name", |arg| {
+                arg.env(None::<&str>).default_value(default)
+            })
+            .get_matches_from(args);
+
+        Command::from_arg_matches(&matches).unwrap().folder
+    }
+
+    #[test]
+    fn explicit_flag() {
+        // a value not given on the command line, like the one of the
+        // environment variable, gives way to the folder prefix
+        let mut folder = parse(&["himalaya"], "Sent");
+        assert_eq!(folder.name, "Sent");
+        assert!(!folder.explicit);
+        folder
+            .take_from_query(&mut query(&["folder:Archives"]))
+            .unwrap();
+        assert_eq!(folder.name, "Archives");
+        assert!(folder.explicit);
+
+        // the flag does not, even when it matches the default
+        let mut folder = parse(&["himalaya", "--folder", "Sent"], "Sent");
+        assert!(folder.explicit);
+        assert!(folder
+            .take_from_query(&mut query(&["folder:Archives"]))
+            .is_err());
+
+        let folder = parse(&["himalaya", "-f", "INBOX"], "INBOX");
+        assert!(folder.explicit);
+    }
+
+    #[test]
+    fn first_arg() {
+        let args = query(&["Archives", "from:alice"]);
+        assert_eq!(flag("INBOX", false).first_arg(&args), Some("Archives"));
+        assert_eq!(flag("INBOX", true).first_arg(&args), None);
+        assert_eq!(flag("INBOX", false).first_arg(&None), None);
+
+        let mut folder = flag("INBOX", false);
+        let mut args = query(&["folder:Archives", "Sent"]);
+        folder.take_from_query(&mut args).unwrap();
+        assert_eq!(folder.first_arg(&args), None);
+    }
+
+    #[test]
+    fn take_first_arg() {
+        let mut folder = flag("INBOX", false);
+        let mut args = query(&["Archives", "from:alice"]);
+        folder.take_first_arg(&mut args).unwrap();
+        assert_eq!(folder.name, "Archives");
+        assert!(folder.explicit);
+        assert_eq!(args, query(&["from:alice"]));
+
+        let mut folder = flag("INBOX", false);
+        let mut args = query(&["My Folder"]);
+        folder.take_first_arg(&mut args).unwrap();
+        assert_eq!(folder.name, "My Folder");
+        assert_eq!(args, None);
+
+        // an existing folder reading as a query is ambiguous
+        for name in ["from:alice", "not flag:seen", "order by date"] {
+            let mut folder = flag("INBOX", false);
+            let mut args = query(&[name]);
+            let err = folder.take_first_arg(&mut args).unwrap_err().to_string();
+            assert!(err.starts_with("ambiguous argument"), "{err}");
+            assert_eq!(folder.name, "INBOX");
+            assert_eq!(args, query(&[name]));
+        }
+    }
+
+    #[test]
+    fn folder_like_query() {
+        let is = |args: &[&str]| is_folder_like_query(&query(args).unwrap());
+
+        assert!(is(&["Archives"]));
+        assert!(is(&["Archives", "from:alice"]));
+        assert!(is(&["Purchase order"]));
+        assert!(!is(&["from:alice"]));
+        assert!(!is(&["FROM", "alice"]));
+        assert!(!is(&["not", "flag:seen"]));
+        assert!(!is(&["(from:alice"]));
+        assert!(!is(&["order by date"]));
+        assert!(!is(&["order", "by", "date"]));
+        assert!(!is(&[]));
+    }
+}
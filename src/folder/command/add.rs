@@ -6,14 +6,14 @@ use email::{
     config::Config,
     {backend::feature::BackendFeatureSource, folder::add::AddFolder},
 };
-use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig, folder::arg::name::FolderNameArg,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    folder::{self, arg::name::FolderNameArg},
+    printer::Printer,
 };
 
 /// Create the given folder.
@@ -41,6 +41,9 @@ impl FolderAddCommand {
                 c.account(name).ok()
             })?;
 
+        let backend_folder =
+            folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
         let backend = BackendBuilder::new(
             Arc::new(toml_account_config),
             Arc::new(account_config),
@@ -54,8 +57,8 @@ impl FolderAddCommand {
         .build()
         .await?;
 
-        backend.add_folder(folder).await?;
+        backend.add_folder(&backend_folder).await?;
 
-        printer.out(format!("Folder {folder} successfully created!\n"))
+        printer.info(format!("Folder {folder} successfully created!\n"))
     }
 }
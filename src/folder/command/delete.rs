@@ -8,12 +8,15 @@ use email::{
 };
 use pimalaya_tui::{
     himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _, prompt},
+    terminal::{config::TomlConfig as _, prompt},
 };
 use tracing::info;
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig, folder::arg::name::FolderNameArg,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    folder::{self, arg::name::FolderNameArg},
+    printer::Printer,
 };
 
 /// Delete the given folder.
@@ -48,6 +51,9 @@ impl FolderDeleteCommand {
                 c.account(name).ok()
             })?;
 
+        let backend_folder =
+            folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
         let backend = BackendBuilder::new(
             Arc::new(toml_account_config),
             Arc::new(account_config),
@@ -61,8 +67,8 @@ impl FolderDeleteCommand {
         .build()
         .await?;
 
-        backend.delete_folder(folder).await?;
+        backend.delete_folder(&backend_folder).await?;
 
-        printer.out(format!("Folder {folder} successfully deleted!\n"))
+        printer.info(format!("Folder {folder} successfully deleted!\n"))
     }
 }
@@ -0,0 +1,182 @@
+use std::{
+    fs::File,
+    io::{stderr, BufWriter, IsTerminal, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use color_eyre::{eyre::eyre, Result};
+use email::{
+    backend::feature::BackendFeatureSource,
+    config::Config,
+    envelope::list::ListEnvelopesOptions,
+    search_query::{
+        sort::{SearchEmailsSorterKind::Date, SearchEmailsSorterOrder::Ascending},
+        SearchEmailsQuery,
+    },
+};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
+use tracing::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    envelope::arg::{date::EnvelopeDateRangeFlags, query},
+    folder::{self, arg::name::FolderNameArg},
+    message::mbox,
+    printer::Printer,
+};
+
+/// The amount of messages fetched at once.
+const CHUNK_SIZE: usize = 50;
+
+/// Export the given folder to a mbox file.
+///
+/// This command fetches the raw source of the messages of the given
+/// folder, and writes them to the given file using the mboxrd
+/// format, from the oldest to the most recent. Messages are written
+/// as soon as they are fetched, and their "seen" flag is left as it
+/// is.
+#[derive(Debug, Parser)]
+pub struct FolderExportCommand {
+    #[command(flatten)]
+    pub folder: FolderNameArg,
+
+    /// The path of the mbox file to export messages to.
+    ///
+    /// The file is created if it does not exist, and truncated
+    /// otherwise.
+    #[arg(value_name = "PATH")]
+    pub path: PathBuf,
+
+    /// Export only messages matching the given filter query.
+    ///
+    /// The query follows the same syntax as the envelope list filter
+    /// query (for example "from alice and not flag seen").
+    #[arg(long, value_name = "QUERY")]
+    pub query: Option<String>,
+
+    #[command(flatten)]
+    pub dates: EnvelopeDateRangeFlags,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl FolderExportCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing export folder command");
+
+        let folder = &self.folder.name;
+
+        let (toml_account_config, account_config) = config
+            .clone()
+            .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
+                c.account(name).ok()
+            })?;
+
+        let backend_folder =
+            folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
+        let backend = BackendBuilder::new(
+            Arc::new(toml_account_config),
+            Arc::new(account_config),
+            |builder| {
+                builder
+                    .without_features()
+                    .with_list_envelopes(BackendFeatureSource::Context)
+                    .with_peek_messages(BackendFeatureSource::Context)
+            },
+        )
+        .without_sending_backend()
+        .build()
+        .await?;
+
+        let query = match &self.query {
+            None => None,
+            Some(query) => Some(
+                query::join(&[query.clone()])
+                    .parse::<SearchEmailsQuery>()
+                    .map_err(|err| eyre!("cannot parse query {query:?}: {err}"))?,
+            ),
+        };
+
+        let mut query = self.dates.apply(query).unwrap_or(SearchEmailsQuery {
+            filter: None,
+            sort: None,
+        });
+
+        // messages are exported from the oldest to the most recent
+        query.sort = Some(vec![(Date, Ascending).into()]);
+
+        let opts = ListEnvelopesOptions {
+            page: 0,
+            // a page size of 0 lists all envelopes
+            page_size: 0,
+            query: Some(query),
+        };
+
+        let envelopes = backend.list_envelopes(&backend_folder, opts).await?;
+
+        let ids: Vec<usize> = envelopes
+            .iter()
+            .filter_map(|envelope| envelope.id.parse().ok())
+            .collect();
+
+        let total = ids.len();
+        let progress = stderr().is_terminal();
+        let mut mbox = BufWriter::new(File::create(&self.path)?);
+        let mut count = 0;
+
+        for ids in ids.chunks(CHUNK_SIZE) {
+            let msgs = backend.peek_messages(&backend_folder, ids).await?;
+
+            let msgs = msgs.to_vec();
+
+            // backends do not keep the order of the given ids, so each
+            // chunk needs to be sorted again
+            let mut entries = Vec::with_capacity(msgs.len());
+
+            for msg in &msgs {
+                let parsed = msg.parsed()?;
+
+                let sender = parsed
+                    .from()
+                    .and_then(|from| from.first())
+                    .and_then(|addr| addr.address())
+                    .unwrap_or_default();
+
+                let date = parsed
+                    .date()
+                    .and_then(|date| DateTime::from_timestamp(date.to_timestamp(), 0))
+                    .unwrap_or_else(Utc::now);
+
+                entries.push((date, sender, msg.raw()?));
+            }
+
+            entries.sort_by_key(|(date, _, _)| *date);
+
+            for (date, sender, raw) in entries {
+                mbox::write(&mut mbox, raw, sender, &date)?;
+                count += 1;
+            }
+
+            if progress {
+                printer.log(format!("\rExporting messages: {count}/{total}"))?;
+            }
+        }
+
+        mbox.flush()?;
+
+        if progress && total > 0 {
+            printer.log("\n")?;
+        }
+
+        let path = self.path.display();
+        printer.info(format!(
+            "{count} message(s) from folder {folder} successfully exported to {path}!\n"
+        ))
+    }
+}
@@ -5,14 +5,14 @@ use color_eyre::Result;
 use email::{
     backend::feature::BackendFeatureSource, config::Config, folder::expunge::ExpungeFolder,
 };
-use pimalaya_tui::{
-    himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
-};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
 use tracing::info;
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig, folder::arg::name::FolderNameArg,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    folder::{self, arg::name::FolderNameArg},
+    printer::Printer,
 };
 
 /// Expunge the given folder.
@@ -40,6 +40,9 @@ impl FolderExpungeCommand {
                 c.account(name).ok()
             })?;
 
+        let backend_folder =
+            folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
         let backend = BackendBuilder::new(
             Arc::new(toml_account_config),
             Arc::new(account_config),
@@ -53,8 +56,8 @@ impl FolderExpungeCommand {
         .build()
         .await?;
 
-        backend.expunge_folder(folder).await?;
+        backend.expunge_folder(&backend_folder).await?;
 
-        printer.out(format!("Folder {folder} successfully expunged!\n"))
+        printer.info(format!("Folder {folder} successfully expunged!\n"))
     }
 }
@@ -7,15 +7,17 @@ use email::{
     {backend::feature::BackendFeatureSource, folder::list::ListFolders},
 };
 use pimalaya_tui::{
-    himalaya::{
-        backend::BackendBuilder,
-        config::{Folders, FoldersTable},
-    },
-    terminal::{cli::printer::Printer, config::TomlConfig as _},
+    himalaya::{backend::BackendBuilder, config::Folders},
+    terminal::config::TomlConfig as _,
 };
 use tracing::info;
 
-use crate::{account::arg::name::AccountNameFlag, config::TomlConfig};
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    folder::{self, special_use, table::FoldersTable},
+    printer::Printer,
+};
 
 /// List all folders.
 ///
@@ -30,9 +32,20 @@ pub struct FolderListCommand {
     /// This argument will force the table not to exceed the given
     /// width, in pixels. Columns may shrink with ellipsis in order to
     /// fit the width.
+    ///
+    /// Defaults to the width of the terminal, or to the
+    /// ui.table.piped-width option when the output is not a terminal.
     #[arg(long = "max-width", short = 'w')]
     #[arg(name = "table_max_width", value_name = "PIXELS")]
     pub table_max_width: Option<u16>,
+
+    /// Show the number of unread and total messages of each folder.
+    ///
+    /// Counts are fetched once per folder, which can be slow on
+    /// accounts with many folders. Folders that cannot be counted
+    /// are shown with a "-".
+    #[arg(long)]
+    pub counts: bool,
 }
 
 impl FolderListCommand {
@@ -45,11 +58,13 @@ impl FolderListCommand {
                 c.account(name).ok()
             })?;
 
+        let extra_config = config.account_extra_config(&account_config.name);
         let toml_account_config = Arc::new(toml_account_config);
+        let account_config = Arc::new(account_config);
 
         let backend = BackendBuilder::new(
             toml_account_config.clone(),
-            Arc::new(account_config),
+            account_config.clone(),
             |builder| {
                 builder
                     .without_features()
@@ -60,12 +75,50 @@ impl FolderListCommand {
         .build()
         .await?;
 
-        let folders = Folders::from(backend.list_folders().await?);
+        let mut folders = backend.list_folders().await?;
+
+        let mut roles = special_use::find_roles(
+            &account_config,
+            folders
+                .iter()
+                .map(|folder| (folder.name.as_str(), folder.desc.as_str())),
+        );
+
+        let mut counts = if self.counts {
+            Some(folder::count::count(&toml_account_config, &folders).await?)
+        } else {
+            None
+        };
+
+        let delimiter = folder::delimiter::get(&toml_account_config, &extra_config).await?;
+
+        if delimiter != folder::delimiter::DEFAULT {
+            for folder in folders.iter_mut() {
+                folder.name = folder::from_backend_name(&folder.name, delimiter);
+            }
+
+            roles = roles
+                .drain()
+                .map(|(name, role)| (folder::from_backend_name(&name, delimiter), role))
+                .collect();
+
+            if let Some(counts) = counts.as_mut() {
+                *counts = counts
+                    .drain()
+                    .map(|(name, count)| (folder::from_backend_name(&name, delimiter), count))
+                    .collect();
+            }
+        }
+
+        let folders = Folders::from(folders);
         let table = FoldersTable::from(folders)
             .with_some_width(self.table_max_width)
             .with_some_preset(toml_account_config.folder_list_table_preset())
             .with_some_name_color(toml_account_config.folder_list_table_name_color())
-            .with_some_desc_color(toml_account_config.folder_list_table_desc_color());
+            .with_some_desc_color(toml_account_config.folder_list_table_desc_color())
+            .with_table_config(config.table_config())
+            .with_roles(roles)
+            .with_some_counts(counts);
 
         printer.out(table)?;
         Ok(())
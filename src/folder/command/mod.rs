@@ -1,21 +1,24 @@
 mod add;
 mod delete;
+mod export;
 mod expunge;
 mod list;
 mod purge;
+mod rename;
 
 use clap::Subcommand;
 use color_eyre::Result;
-use pimalaya_tui::terminal::cli::printer::Printer;
 
-use crate::config::TomlConfig;
+use crate::{config::TomlConfig, printer::Printer};
 
 use self::{
-    add::FolderAddCommand, delete::FolderDeleteCommand, expunge::FolderExpungeCommand,
-    list::FolderListCommand, purge::FolderPurgeCommand,
+    add::FolderAddCommand, delete::FolderDeleteCommand, export::FolderExportCommand,
+    expunge::FolderExpungeCommand, list::FolderListCommand, purge::FolderPurgeCommand,
+    rename::FolderRenameCommand,
 };
 
-/// Create, list and purge your folders (as known as mailboxes).
+/// Create, list, rename, export and purge your folders (as known as
+/// mailboxes).
 ///
 /// A folder (as known as mailbox, or directory) is a messages
 /// container. This subcommand allows you to manage them.
@@ -27,9 +30,15 @@ pub enum FolderSubcommand {
     #[command(alias = "lst")]
     List(FolderListCommand),
 
+    #[command(alias = "mv")]
+    Rename(FolderRenameCommand),
+
     #[command()]
     Expunge(FolderExpungeCommand),
 
+    #[command(arg_required_else_help = true)]
+    Export(FolderExportCommand),
+
     #[command()]
     Purge(FolderPurgeCommand),
 
@@ -43,7 +52,9 @@ impl FolderSubcommand {
         match self {
             Self::Add(cmd) => cmd.execute(printer, config).await,
             Self::List(cmd) => cmd.execute(printer, config).await,
+            Self::Rename(cmd) => cmd.execute(printer, config).await,
             Self::Expunge(cmd) => cmd.execute(printer, config).await,
+            Self::Export(cmd) => cmd.execute(printer, config).await,
             Self::Purge(cmd) => cmd.execute(printer, config).await,
             Self::Delete(cmd) => cmd.execute(printer, config).await,
         }
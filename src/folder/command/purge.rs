@@ -5,12 +5,15 @@ use color_eyre::Result;
 use email::{backend::feature::BackendFeatureSource, config::Config, folder::purge::PurgeFolder};
 use pimalaya_tui::{
     himalaya::backend::BackendBuilder,
-    terminal::{cli::printer::Printer, config::TomlConfig as _, prompt},
+    terminal::{config::TomlConfig as _, prompt},
 };
 use tracing::info;
 
 use crate::{
-    account::arg::name::AccountNameFlag, config::TomlConfig, folder::arg::name::FolderNameArg,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    folder::{self, arg::name::FolderNameArg},
+    printer::Printer,
 };
 
 /// Purge the given folder.
@@ -45,6 +48,9 @@ impl FolderPurgeCommand {
                 c.account(name).ok()
             })?;
 
+        let backend_folder =
+            folder::resolve(config, &toml_account_config, &account_config, folder).await?;
+
         let backend = BackendBuilder::new(
             Arc::new(toml_account_config),
             Arc::new(account_config),
@@ -58,8 +64,8 @@ impl FolderPurgeCommand {
         .build()
         .await?;
 
-        backend.purge_folder(folder).await?;
+        backend.purge_folder(&backend_folder).await?;
 
-        printer.out(format!("Folder {folder} successfully purged!\n"))
+        printer.info(format!("Folder {folder} successfully purged!\n"))
     }
 }
@@ -0,0 +1,66 @@
+use clap::Parser;
+use color_eyre::Result;
+use email::config::Config;
+use pimalaya_tui::terminal::config::TomlConfig as _;
+use tracing::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    folder::{self, arg::name::TargetFolderNameArg, rename},
+    printer::Printer,
+};
+
+/// Rename the given folder.
+///
+/// Subfolders are renamed as well. Renaming the inbox is not
+/// supported, neither is the notmuch backend.
+#[derive(Debug, Parser)]
+pub struct FolderRenameCommand {
+    /// The current name of the folder.
+    #[arg(name = "source_folder_name", value_name = "SOURCE")]
+    pub source: String,
+
+    #[command(flatten)]
+    pub target: TargetFolderNameArg,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl FolderRenameCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing rename folder command");
+
+        let source = &self.source;
+        let target = &self.target.name;
+
+        let (toml_account_config, account_config) = config
+            .clone()
+            .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
+                c.account(name).ok()
+            })?;
+
+        let folders = folder::resolve_many(
+            config,
+            &toml_account_config,
+            &account_config,
+            &[source, target],
+        )
+        .await?;
+        let (from, to) = (&folders[0], &folders[1]);
+
+        rename::rename(&toml_account_config, &account_config, from, to).await?;
+
+        for (alias, folder) in rename::find_aliases(&toml_account_config, &account_config, from) {
+            printer.log(format!(
+                "WARNING: folder alias {alias} still points to {folder}, update it in your \
+                 configuration\n"
+            ))?;
+        }
+
+        printer.info(format!(
+            "Folder {source} successfully renamed to {target}!\n"
+        ))
+    }
+}
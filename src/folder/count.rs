@@ -0,0 +1,216 @@
+//! Module dedicated to folder message counts.
+//!
+//! IMAP counts are fetched using the STATUS command, one per folder,
+//! while Maildir counts are computed from the `new` and `cur`
+//! directories.
+
+use std::collections::HashMap;
+
+use color_eyre::{eyre::bail, Result};
+use email::folder::Folders;
+use serde::Serialize;
+
+use crate::account::config::TomlAccountConfig;
+
+/// The message counts of a folder.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct FolderCounts {
+    pub unread: u32,
+    pub total: u32,
+}
+
+/// Count messages of the given folders, using the backend of the
+/// given account.
+///
+/// Folders that cannot be counted (for example IMAP mailboxes that
+/// cannot be selected) are left out of the returned map.
+#[cfg_attr(
+    not(any(feature = "imap", feature = "maildir")),
+    allow(unused_variables)
+)]
+pub async fn count(
+    config: &TomlAccountConfig,
+    folders: &Folders,
+) -> Result<HashMap<String, FolderCounts>> {
+    #[cfg(any(feature = "imap", feature = "maildir"))]
+    use pimalaya_tui::himalaya::config::Backend;
+
+    match &config.backend {
+        #[cfg(feature = "imap")]
+        Some(Backend::Imap(imap_config)) => imap::count(imap_config, folders).await,
+        #[cfg(feature = "maildir")]
+        Some(Backend::Maildir(_)) => Ok(maildir::count(folders)),
+        _ => bail!("message counts are only supported by the IMAP and Maildir backends"),
+    }
+}
+
+#[cfg(feature = "imap")]
+mod imap {
+    use std::{collections::HashMap, sync::Arc};
+
+    use color_eyre::Result;
+    use email::{folder::Folders, imap::config::ImapConfig, imap::ImapClientBuilder};
+    use imap_client::{
+        imap_types::{
+            command::CommandBody,
+            mailbox::Mailbox,
+            response::{Data, StatusBody, StatusKind},
+            status::{StatusDataItem, StatusDataItemName},
+        },
+        tasks::{tasks::TaskError, Task},
+    };
+    use tracing::debug;
+    use utf7_imap::encode_utf7_imap as encode_utf7;
+
+    use super::FolderCounts;
+
+    /// Count messages of the given folders using one STATUS command
+    /// per folder, over a dedicated connection.
+    pub async fn count(
+        config: &ImapConfig,
+        folders: &Folders,
+    ) -> Result<HashMap<String, FolderCounts>> {
+        let mut client = ImapClientBuilder::new(Arc::new(config.clone()), None)
+            .build()
+            .await?;
+
+        let mut counts = HashMap::new();
+
+        for folder in folders.iter() {
+            let mbox = Mailbox::try_from(encode_utf7(folder.name.clone()))?;
+
+            match client.resolve(StatusTask::new(mbox)).await? {
+                Ok(items) => {
+                    counts.insert(folder.name.clone(), FolderCounts::from(items));
+                }
+                Err(err) => {
+                    debug!("cannot get status of folder {}: {err}", folder.name);
+                }
+            }
+        }
+
+        Ok(counts)
+    }
+
+    impl From<Vec<StatusDataItem>> for FolderCounts {
+        fn from(items: Vec<StatusDataItem>) -> Self {
+            let mut counts = FolderCounts::default();
+
+            for item in items {
+                match item {
+                    StatusDataItem::Messages(total) => counts.total = total,
+                    StatusDataItem::Unseen(unread) => counts.unread = unread,
+                    _ => (),
+                }
+            }
+
+            counts
+        }
+    }
+
+    /// The IMAP task requesting the number of messages and unseen
+    /// messages of a mailbox.
+    struct StatusTask {
+        mailbox: Mailbox<'static>,
+        output: Vec<StatusDataItem>,
+    }
+
+    impl StatusTask {
+        fn new(mailbox: Mailbox<'static>) -> Self {
+            Self {
+                mailbox,
+                output: Vec::new(),
+            }
+        }
+    }
+
+    impl Task for StatusTask {
+        type Output = Result<Vec<StatusDataItem>, TaskError>;
+
+        fn command_body(&self) -> CommandBody<'static> {
+            CommandBody::Status {
+                mailbox: self.mailbox.clone(),
+                item_names: vec![StatusDataItemName::Messages, StatusDataItemName::Unseen].into(),
+            }
+        }
+
+        fn process_data(&mut self, data: Data<'static>) -> Option<Data<'static>> {
+            if let Data::Status { items, .. } = data {
+                self.output.extend(items.into_owned());
+                None
+            } else {
+                Some(data)
+            }
+        }
+
+        fn process_tagged(self, status_body: StatusBody<'static>) -> Self::Output {
+            match status_body.kind {
+                StatusKind::Ok => Ok(self.output),
+                StatusKind::No => Err(TaskError::UnexpectedNoResponse(status_body)),
+                StatusKind::Bad => Err(TaskError::UnexpectedBadResponse(status_body)),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "maildir")]
+mod maildir {
+    use std::{collections::HashMap, fs, path::Path};
+
+    use email::folder::Folders;
+    use tracing::debug;
+
+    use super::FolderCounts;
+
+    /// Count messages of the given folders, whose description is the
+    /// path of the Maildir.
+    ///
+    /// Messages of the `new` directory are unread, as well as
+    /// messages of the `cur` directory without the `S` flag.
+    pub fn count(folders: &Folders) -> HashMap<String, FolderCounts> {
+        let mut counts = HashMap::new();
+
+        for folder in folders.iter() {
+            let path = Path::new(&folder.desc);
+            let mut count = FolderCounts::default();
+
+            for dir in ["new", "cur"] {
+                let entries = match fs::read_dir(path.join(dir)) {
+                    Ok(entries) => entries,
+                    Err(err) => {
+                        debug!("cannot read maildir {}/{dir}: {err}", path.display());
+                        continue;
+                    }
+                };
+
+                for entry in entries.flatten() {
+                    if !entry.path().is_file() {
+                        continue;
+                    }
+
+                    let name = entry.file_name();
+                    let name = name.to_string_lossy();
+
+                    if name.starts_with('.') {
+                        continue;
+                    }
+
+                    let seen = dir == "cur"
+                        && name
+                            .rsplit_once(":2,")
+                            .is_some_and(|(_, flags)| flags.contains('S'));
+
+                    count.total += 1;
+
+                    if !seen {
+                        count.unread += 1;
+                    }
+                }
+            }
+
+            counts.insert(folder.name.clone(), count);
+        }
+
+        counts
+    }
+}
@@ -0,0 +1,89 @@
+//! Module dedicated to folder hierarchy delimiters.
+//!
+//! Folder names are `/`-separated on the command line, whatever the
+//! backend: subfolders of Maildir++ folders are dot-separated, and
+//! IMAP servers announce their own delimiter, for example `.` for
+//! Courier-like servers storing subfolders as `INBOX.Sub.Folder`.
+
+use color_eyre::Result;
+
+use crate::account::config::{TomlAccountConfig, TomlAccountExtraConfig};
+
+/// The delimiter of `/`-separated folder names.
+pub const DEFAULT: char = '/';
+
+/// Get the hierarchy delimiter of folder names of the given account.
+///
+/// IMAP accounts use the `backend.delimiter` option when set,
+/// otherwise the delimiter announced by the server, which costs a
+/// dedicated connection the first time.
+#[cfg_attr(not(feature = "imap"), allow(unused_variables))]
+pub async fn get(
+    toml_account_config: &TomlAccountConfig,
+    extra_config: &TomlAccountExtraConfig,
+) -> Result<char> {
+    #[cfg(feature = "imap")]
+    use pimalaya_tui::himalaya::config::Backend;
+
+    if super::maildirpp_root_dir(toml_account_config).is_some() {
+        return Ok('.');
+    }
+
+    #[cfg(feature = "imap")]
+    if let Some(Backend::Imap(imap_config)) = &toml_account_config.backend {
+        return match extra_config.backend_delimiter() {
+            Some(delimiter) => Ok(delimiter),
+            None => imap::fetch(imap_config).await,
+        };
+    }
+
+    Ok(DEFAULT)
+}
+
+#[cfg(feature = "imap")]
+mod imap {
+    use std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex},
+    };
+
+    use color_eyre::Result;
+    use email::imap::{config::ImapConfig, ImapClientBuilder};
+    use tracing::debug;
+
+    /// The delimiters already fetched, by login and server, so that
+    /// commands resolving several folders connect only once.
+    static FETCHED: Mutex<BTreeMap<String, char>> = Mutex::new(BTreeMap::new());
+
+    /// Fetch the hierarchy delimiter of the server, taken from the
+    /// LIST response of top-level mailboxes, over a dedicated
+    /// connection.
+    ///
+    /// Servers without hierarchy fall back to the default delimiter,
+    /// which leaves folder names untouched.
+    pub async fn fetch(config: &ImapConfig) -> Result<char> {
+        let key = format!("{}@{}:{}", config.login, config.host, config.port);
+
+        if let Some(delimiter) = FETCHED.lock().unwrap().get(&key) {
+            return Ok(*delimiter);
+        }
+
+        let mut client = ImapClientBuilder::new(Arc::new(config.clone()), None)
+            .build()
+            .await?;
+
+        let mboxes = client.list("", "%").await?;
+
+        let delimiter = mboxes
+            .iter()
+            .find_map(|(_, delimiter, _)| delimiter.as_ref())
+            .map(|delimiter| delimiter.inner());
+
+        debug!("IMAP hierarchy delimiter: {delimiter:?}");
+
+        let delimiter = delimiter.unwrap_or(super::DEFAULT);
+        FETCHED.lock().unwrap().insert(key, delimiter);
+
+        Ok(delimiter)
+    }
+}
@@ -1,2 +1,445 @@
 pub mod arg;
 pub mod command;
+pub mod count;
+pub mod delimiter;
+pub mod rename;
+pub mod special_use;
+pub mod table;
+
+use std::{collections::HashMap, fs};
+
+use color_eyre::{eyre::bail, Result};
+use email::{account::config::AccountConfig, folder::list::ListFolders};
+use pimalaya_tui::himalaya::backend::Backend;
+use tracing::debug;
+
+use crate::{account::config::TomlAccountConfig, config::TomlConfig};
+
+/// Get the Maildir++ root directory of the given account.
+///
+/// Returns [`None`] if the account does not use the Maildir backend
+/// with the Maildir++ layout.
+#[cfg(feature = "maildir")]
+fn maildirpp_root_dir(config: &TomlAccountConfig) -> Option<std::path::PathBuf> {
+    use pimalaya_tui::himalaya::config::Backend;
+    use shellexpand_utils::shellexpand_path;
+
+    match &config.backend {
+        Some(Backend::Maildir(config)) if config.maildirpp => {
+            Some(shellexpand_path(&config.root_dir))
+        }
+        _ => None,
+    }
+}
+
+/// Get the Maildir++ root directory of the given account.
+#[cfg(not(feature = "maildir"))]
+fn maildirpp_root_dir(_: &TomlAccountConfig) -> Option<std::path::PathBuf> {
+    None
+}
+
+/// Convert the given `/`-separated folder name into the name
+/// understood by the backend, using the given hierarchy delimiter.
+///
+/// Maildir++ subfolders are stored as dot-separated directories at
+/// the root level (`Clients/ACME` is stored in `.Clients.ACME`), and
+/// so are subfolders of IMAP servers using `.` as delimiter. Folder
+/// aliases are left untouched.
+///
+/// Names containing the delimiter itself are refused, since they
+/// would come back from the backend as subfolders.
+pub fn to_backend_name(
+    account_config: &AccountConfig,
+    folder: &str,
+    delimiter: char,
+) -> Result<String> {
+    if delimiter == delimiter::DEFAULT || account_config.find_folder_alias(folder).is_some() {
+        return Ok(folder.to_owned());
+    }
+
+    if folder.contains(delimiter) {
+        bail!(
+            "cannot use folder {folder}: names cannot contain {delimiter:?}, \
+             use {:?} to separate subfolders",
+            delimiter::DEFAULT
+        );
+    }
+
+    Ok(folder.replace(delimiter::DEFAULT, &delimiter.to_string()))
+}
+
+/// Convert the given folder name coming from the backend into its
+/// `/`-separated form, using the given hierarchy delimiter.
+///
+/// This is the reverse of [`to_backend_name`].
+pub fn from_backend_name(folder: &str, delimiter: char) -> String {
+    folder.replace(delimiter, &delimiter::DEFAULT.to_string())
+}
+
+/// Convert the given `/`-separated folder name of the given account
+/// into the name understood by its backend.
+///
+/// See [`resolve_many`].
+pub async fn resolve(
+    config: &TomlConfig,
+    toml_account_config: &TomlAccountConfig,
+    account_config: &AccountConfig,
+    folder: &str,
+) -> Result<String> {
+    let mut folders = resolve_many(config, toml_account_config, account_config, &[folder]).await?;
+    Ok(folders.remove(0))
+}
+
+/// Convert the given `/`-separated folder names of the given account
+/// into the names understood by its backend.
+///
+/// Role names (`sent`, `trash`, etc) that are not aliased resolve to
+/// the special-use folder of the server, if any. The hierarchy
+/// delimiter of IMAP accounts is only fetched when one of the names
+/// has a subfolder and is not an alias.
+pub async fn resolve_many(
+    config: &TomlConfig,
+    toml_account_config: &TomlAccountConfig,
+    account_config: &AccountConfig,
+    folders: &[&str],
+) -> Result<Vec<String>> {
+    let nested = maildirpp_root_dir(toml_account_config).is_some()
+        || folders.iter().any(|folder| {
+            folder.contains(delimiter::DEFAULT)
+                && account_config.find_folder_alias(folder).is_none()
+        });
+
+    let delimiter = if nested {
+        let extra_config = config.account_extra_config(&account_config.name);
+        delimiter::get(toml_account_config, &extra_config).await?
+    } else {
+        delimiter::DEFAULT
+    };
+
+    let roles: Vec<_> = folders
+        .iter()
+        .map(|folder| match account_config.find_folder_alias(folder) {
+            Some(_) => None,
+            None => special_use::FolderRole::from_alias(folder),
+        })
+        .collect();
+
+    let special_use_folders = if roles.iter().any(Option::is_some) {
+        special_use::find_folders(toml_account_config).await?
+    } else {
+        HashMap::new()
+    };
+
+    folders
+        .iter()
+        .zip(roles)
+        .map(
+            |(folder, role)| match role.and_then(|role| special_use_folders.get(&role).cloned()) {
+                Some(folder) => Ok(folder),
+                None => to_backend_name(account_config, folder, delimiter),
+            },
+        )
+        .collect()
+}
+
+/// Return `true` if the given `/`-separated folder name designates a
+/// folder of the given account.
+///
+/// Folder aliases and role names (`sent`, `trash`, etc) designate a
+/// folder without reaching the backend, other names are searched
+/// among the folders listed by the given backend, which should be the
+/// one of the command, so that checking does not cost a connection.
+pub async fn exists(
+    config: &TomlConfig,
+    toml_account_config: &TomlAccountConfig,
+    account_config: &AccountConfig,
+    backend: &Backend,
+    folder: &str,
+) -> Result<bool> {
+    if account_config.find_folder_alias(folder).is_some()
+        || special_use::FolderRole::from_alias(folder).is_some()
+    {
+        return Ok(true);
+    }
+
+    // names containing the hierarchy delimiter cannot exist
+    let Ok(name) = resolve(config, toml_account_config, account_config, folder).await else {
+        return Ok(false);
+    };
+
+    let folders = backend.list_folders().await?;
+    Ok(folders.iter().any(|folder| folder.name == name))
+}
+
+/// Alias Maildir++ subfolders of the given account by their
+/// `/`-separated name.
+///
+/// Subfolders are found by scanning the root directory, so that
+/// `Clients/ACME` can be used anywhere a folder is expected once the
+/// `.Clients.ACME` directory exists. Aliases defined by the user take
+/// precedence.
+pub fn add_maildirpp_aliases(config: &mut TomlAccountConfig) {
+    let Some(root_dir) = maildirpp_root_dir(config) else {
+        return;
+    };
+
+    let entries = match fs::read_dir(&root_dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            debug!("cannot read Maildir++ root dir {root_dir:?}: {err}");
+            return;
+        }
+    };
+
+    let folder_config = config.folder.get_or_insert_with(Default::default);
+    let aliases = folder_config.aliases.get_or_insert_with(Default::default);
+
+    for entry in entries.flatten() {
+        if !entry.path().is_dir() {
+            continue;
+        }
+
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+
+        let Some(name) = name.strip_prefix('.') else {
+            continue;
+        };
+
+        if name.is_empty() || !name.contains('.') {
+            continue;
+        }
+
+        let alias = name.replace('.', "/");
+        let exists = aliases.keys().any(|key| key.eq_ignore_ascii_case(&alias));
+
+        if !exists {
+            aliases.insert(alias, name.to_owned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use email::account::config::AccountConfig;
+
+    #[test]
+    fn to_backend_name_with_default_delimiter() {
+        let config = AccountConfig::default();
+
+        let folder = super::to_backend_name(&config, "Clients/v1.2", '/').unwrap();
+        assert_eq!(folder, "Clients/v1.2");
+    }
+
+    #[test]
+    fn to_backend_name_with_dot_delimiter() {
+        let config = AccountConfig::default();
+
+        let folder = super::to_backend_name(&config, "Clients/ACME", '.').unwrap();
+        assert_eq!(folder, "Clients.ACME");
+        assert_eq!(super::from_backend_name(&folder, '.'), "Clients/ACME");
+    }
+
+    #[test]
+    fn to_backend_name_refuses_delimiter() {
+        let config = AccountConfig::default();
+
+        assert!(super::to_backend_name(&config, "Clients/v1.2", '.').is_err());
+        assert!(super::to_backend_name(&config, "Clients.ACME", '.').is_err());
+    }
+
+    #[cfg(feature = "maildir")]
+    mod maildir {
+        use std::{fs, path::PathBuf, sync::Arc};
+
+        use crate::{
+            account::config::TomlAccountConfig, config::TomlConfig, fixture::MaildirRoot, folder,
+        };
+        use email::{
+            account::config::AccountConfig,
+            backend::feature::BackendFeatureSource,
+            config::Config,
+            folder::{add::AddFolder, delete::DeleteFolder, list::ListFolders, Folder},
+        };
+        use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
+
+        /// Read the configuration of an account using the given
+        /// Maildir root.
+        fn configs(
+            root: &MaildirRoot,
+            maildirpp: bool,
+        ) -> (TomlConfig, TomlAccountConfig, AccountConfig) {
+            let config: TomlConfig = toml::from_str(&format!(
+                "[accounts.test]\n\
+                 default = true\n\
+                 email = \"test@localhost\"\n\
+                 backend.type = \"maildir\"\n\
+                 backend.root-dir = {:?}\n\
+                 backend.maildirpp = {maildirpp}\n",
+                root.0
+            ))
+            .unwrap();
+
+            let (toml_account_config, account_config) = config
+                .clone()
+                .into_account_configs(None, |c: &Config, name| c.account(name).ok())
+                .unwrap();
+
+            (config, toml_account_config, account_config)
+        }
+
+        /// Run the given step the way a command does: read the
+        /// configuration, resolve the folder name, then build the
+        /// backend.
+        ///
+        /// Returns the `/`-separated names of the listed folders.
+        async fn step(
+            root: &MaildirRoot,
+            maildirpp: bool,
+            folder: &str,
+            step: Step,
+        ) -> Vec<String> {
+            let (config, toml_account_config, account_config) = configs(root, maildirpp);
+
+            let backend_folder =
+                folder::resolve(&config, &toml_account_config, &account_config, folder)
+                    .await
+                    .unwrap();
+            let extra_config = config.account_extra_config(&account_config.name);
+            let delimiter = folder::delimiter::get(&toml_account_config, &extra_config)
+                .await
+                .unwrap();
+
+            let backend = BackendBuilder::new(
+                Arc::new(toml_account_config),
+                Arc::new(account_config),
+                |builder| {
+                    builder
+                        .without_features()
+                        .with_add_folder(BackendFeatureSource::Context)
+                        .with_list_folders(BackendFeatureSource::Context)
+                        .with_add_message(BackendFeatureSource::Context)
+                        .with_delete_folder(BackendFeatureSource::Context)
+                },
+            )
+            .without_sending_backend()
+            .build()
+            .await
+            .unwrap();
+
+            match step {
+                Step::Create => backend.add_folder(&backend_folder).await.unwrap(),
+                Step::Deliver => {
+                    let msg = b"Subject: test\r\n\r\ntest\r\n";
+                    backend.add_message(&backend_folder, msg).await.unwrap();
+                }
+                Step::Delete => backend.delete_folder(&backend_folder).await.unwrap(),
+                Step::List => (),
+            }
+
+            let folders = backend.list_folders().await.unwrap();
+            let mut names: Vec<_> = folders
+                .iter()
+                .map(|Folder { name, .. }| folder::from_backend_name(name, delimiter))
+                .collect();
+            names.sort();
+            names
+        }
+
+        enum Step {
+            Create,
+            List,
+            Deliver,
+            Delete,
+        }
+
+        fn messages(dir: PathBuf) -> usize {
+            let count = |subdir| fs::read_dir(dir.join(subdir)).unwrap().count();
+            count("new") + count("cur")
+        }
+
+        async fn round_trip(maildirpp: bool, dir: &str) {
+            let root = MaildirRoot::new();
+            let folder = "Clients/ACME";
+
+            assert_eq!(step(&root, maildirpp, folder, Step::Create).await, [folder]);
+            assert!(root.0.join(dir).is_dir());
+
+            assert_eq!(step(&root, maildirpp, folder, Step::List).await, [folder]);
+
+            assert_eq!(
+                step(&root, maildirpp, folder, Step::Deliver).await,
+                [folder]
+            );
+            assert_eq!(messages(root.0.join(dir)), 1);
+
+            let folders = step(&root, maildirpp, folder, Step::Delete).await;
+            assert!(folders.is_empty(), "{folders:?}");
+            assert!(!root.0.join(dir).join("cur").exists());
+        }
+
+        #[tokio::test]
+        async fn round_trip_maildirpp() {
+            round_trip(true, ".Clients.ACME").await
+        }
+
+        #[tokio::test]
+        async fn round_trip_nested_dirs() {
+            round_trip(false, "Clients/ACME").await
+        }
+
+        #[tokio::test]
+        async fn exists() {
+            let root = MaildirRoot::new();
+            root.folder(".Archives", &[]);
+
+            let (config, toml_account_config, account_config) = configs(&root, true);
+
+            let backend = BackendBuilder::new(
+                Arc::new(toml_account_config.clone()),
+                Arc::new(account_config.clone()),
+                |builder| {
+                    builder
+                        .without_features()
+                        .with_list_folders(BackendFeatureSource::Context)
+                },
+            )
+            .without_sending_backend()
+            .build()
+            .await
+            .unwrap();
+
+            for (folder, exists) in [
+                ("Archives", true),
+                // role names designate a folder, listed or not
+                ("trash", true),
+                ("from", false),
+                ("Clients/v1.2", false),
+            ] {
+                let res = folder::exists(
+                    &config,
+                    &toml_account_config,
+                    &account_config,
+                    &backend,
+                    folder,
+                );
+                assert_eq!(res.await.unwrap(), exists, "{folder}");
+            }
+        }
+
+        #[tokio::test]
+        async fn refuse_dots_with_maildirpp() {
+            let root = MaildirRoot::new();
+            let (config, toml_account_config, account_config) = configs(&root, true);
+
+            let folder = "Clients/v1.2";
+            let res = folder::resolve(&config, &toml_account_config, &account_config, folder);
+            assert!(res.await.is_err());
+
+            let (config, toml_account_config, account_config) = configs(&root, false);
+            let res = folder::resolve(&config, &toml_account_config, &account_config, folder);
+            assert_eq!(res.await.unwrap(), folder);
+        }
+    }
+}
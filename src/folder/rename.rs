@@ -0,0 +1,413 @@
+//! Module dedicated to folder renaming.
+//!
+//! IMAP folders are renamed using the RENAME command, which renames
+//! their subfolders as well. Maildir folders are renamed on disk,
+//! along with their subfolders: nested directories, or dot-separated
+//! directories with the Maildir++ layout.
+
+use color_eyre::{eyre::bail, Result};
+use email::{account::config::AccountConfig, folder::INBOX};
+
+use crate::{
+    account::config::TomlAccountConfig,
+    folder::{self, delimiter},
+};
+
+/// Rename the given folder of the given account.
+///
+/// Folder names are backend names (see [`folder::resolve`]) or
+/// aliases, they are resolved before renaming.
+/// Renaming the inbox is refused: with IMAP, it would move all its
+/// messages to a new folder while keeping an empty inbox.
+#[cfg_attr(
+    not(any(feature = "imap", feature = "maildir")),
+    allow(unused_variables)
+)]
+pub async fn rename(
+    toml_account_config: &TomlAccountConfig,
+    account_config: &AccountConfig,
+    from: &str,
+    to: &str,
+) -> Result<()> {
+    #[cfg(any(feature = "imap", feature = "maildir", feature = "notmuch"))]
+    use pimalaya_tui::himalaya::config::Backend;
+
+    let from = account_config.get_folder_alias(from);
+    let to = account_config.get_folder_alias(to);
+
+    if is_inbox(account_config, &from) {
+        bail!(
+            "cannot rename folder {from}: renaming the inbox would move all its messages \
+             to a new folder and leave the inbox empty, move messages instead"
+        );
+    }
+
+    if is_inbox(account_config, &to) {
+        bail!("cannot rename folder {from} to {to}: the inbox already exists");
+    }
+
+    if from == to {
+        bail!("cannot rename folder {from}: the new name is the same as the current one");
+    }
+
+    match &toml_account_config.backend {
+        #[cfg(feature = "imap")]
+        Some(Backend::Imap(imap_config)) => imap::rename(imap_config, &from, &to).await,
+        #[cfg(feature = "maildir")]
+        Some(Backend::Maildir(mdir_config)) => maildir::rename(mdir_config, &from, &to),
+        #[cfg(feature = "notmuch")]
+        Some(Backend::Notmuch(_)) => bail!(
+            "cannot rename folder {from}: notmuch folders are Maildir directories, \
+             rename them on disk then run notmuch new"
+        ),
+        _ => bail!("folder renaming is only supported by the IMAP and Maildir backends"),
+    }
+}
+
+/// Get the folder aliases pointing to the given folder or to one of
+/// its subfolders, as `(alias, folder)` pairs.
+///
+/// The folder is a backend name or an alias. Aliases of Maildir++
+/// subfolders, added automatically, are left out.
+pub fn find_aliases(
+    toml_account_config: &TomlAccountConfig,
+    account_config: &AccountConfig,
+    folder: &str,
+) -> Vec<(String, String)> {
+    let folder = account_config.get_folder_alias(folder);
+
+    let Some(aliases) = toml_account_config
+        .folder
+        .as_ref()
+        .and_then(|config| config.aliases.as_ref())
+    else {
+        return Vec::new();
+    };
+
+    let mut found: Vec<_> = aliases
+        .iter()
+        .filter(|(alias, target)| {
+            !alias.contains(delimiter::DEFAULT) || folder::from_backend_name(target, '.') != **alias
+        })
+        .filter(|(_, target)| {
+            target.as_str() == folder
+                || target
+                    .strip_prefix(&folder)
+                    .is_some_and(|rest| rest.starts_with(['/', '.']))
+        })
+        .map(|(alias, target)| (alias.clone(), target.clone()))
+        .collect();
+
+    found.sort();
+    found
+}
+
+fn is_inbox(account_config: &AccountConfig, folder: &str) -> bool {
+    folder.eq_ignore_ascii_case(INBOX)
+        || folder.eq_ignore_ascii_case(&account_config.get_inbox_folder_alias())
+}
+
+#[cfg(feature = "imap")]
+mod imap {
+    use std::sync::Arc;
+
+    use color_eyre::{eyre::WrapErr, Result};
+    use email::{imap::config::ImapConfig, imap::ImapClientBuilder};
+    use imap_client::{
+        imap_types::{
+            command::CommandBody,
+            mailbox::Mailbox,
+            response::{StatusBody, StatusKind},
+        },
+        tasks::{tasks::TaskError, Task},
+    };
+    use utf7_imap::encode_utf7_imap as encode_utf7;
+
+    /// Rename the given mailbox using the RENAME command, over a
+    /// dedicated connection.
+    pub async fn rename(config: &ImapConfig, from: &str, to: &str) -> Result<()> {
+        let mut client = ImapClientBuilder::new(Arc::new(config.clone()), None)
+            .build()
+            .await?;
+
+        let task = RenameTask {
+            from: Mailbox::try_from(encode_utf7(from.to_owned()))?,
+            to: Mailbox::try_from(encode_utf7(to.to_owned()))?,
+        };
+
+        client
+            .resolve(task)
+            .await?
+            .wrap_err_with(|| format!("cannot rename IMAP folder {from} to {to}"))?;
+
+        Ok(())
+    }
+
+    /// The IMAP task renaming a mailbox.
+    struct RenameTask {
+        from: Mailbox<'static>,
+        to: Mailbox<'static>,
+    }
+
+    impl Task for RenameTask {
+        type Output = Result<(), TaskError>;
+
+        fn command_body(&self) -> CommandBody<'static> {
+            CommandBody::Rename {
+                from: self.from.clone(),
+                to: self.to.clone(),
+            }
+        }
+
+        fn process_tagged(self, status_body: StatusBody<'static>) -> Self::Output {
+            match status_body.kind {
+                StatusKind::Ok => Ok(()),
+                StatusKind::No => Err(TaskError::UnexpectedNoResponse(status_body)),
+                StatusKind::Bad => Err(TaskError::UnexpectedBadResponse(status_body)),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "maildir")]
+mod maildir {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use color_eyre::{eyre::bail, eyre::WrapErr, Result};
+    use email::maildir::config::MaildirConfig;
+    use shellexpand_utils::shellexpand_path;
+
+    /// Rename the given Maildir folder, along with its subfolders.
+    ///
+    /// Every target directory is checked before anything is renamed.
+    pub fn rename(config: &MaildirConfig, from: &str, to: &str) -> Result<()> {
+        let root = shellexpand_path(&config.root_dir);
+
+        let renames = match config.maildirpp {
+            true => maildirpp_renames(&root, from, to)?,
+            false => vec![(root.join(from), root.join(to))],
+        };
+
+        let Some((src, _)) = renames.first() else {
+            bail!("cannot find folder {from} at {}", root.display());
+        };
+
+        if !src.join("cur").is_dir() {
+            bail!("cannot find folder {from} at {}", src.display());
+        }
+
+        for (_, dest) in &renames {
+            if dest.exists() {
+                bail!(
+                    "cannot rename folder {from} to {to}: {} already exists",
+                    dest.display()
+                );
+            }
+        }
+
+        for (src, dest) in renames {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)
+                    .wrap_err_with(|| format!("cannot create directory {}", parent.display()))?;
+            }
+
+            fs::rename(&src, &dest).wrap_err_with(|| {
+                format!("cannot rename {} to {}", src.display(), dest.display())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the directories to rename with the Maildir++ layout: the
+    /// folder itself first, then its dot-separated subfolders.
+    fn maildirpp_renames(root: &Path, from: &str, to: &str) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let from = format!(".{from}");
+        let to = format!(".{to}");
+        let children = format!("{from}.");
+
+        let mut renames = vec![(root.join(&from), root.join(&to))];
+
+        let entries = fs::read_dir(root)
+            .wrap_err_with(|| format!("cannot read Maildir++ root dir {}", root.display()))?;
+
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+
+            if let Some(rest) = name.strip_prefix(&children) {
+                renames.push((entry.path(), root.join(format!("{to}.{rest}"))));
+            }
+        }
+
+        Ok(renames)
+    }
+}
+
+#[cfg(all(test, feature = "maildir"))]
+mod tests {
+    use email::{account::config::AccountConfig, config::Config};
+    use pimalaya_tui::terminal::config::TomlConfig as _;
+
+    use super::{find_aliases, rename};
+    use crate::{account::config::TomlAccountConfig, config::TomlConfig, fixture::MaildirRoot};
+
+    /// Read the configuration of an account using the given Maildir
+    /// root, with the given extra options.
+    fn configs(
+        root: &MaildirRoot,
+        maildirpp: bool,
+        options: &str,
+    ) -> (TomlAccountConfig, AccountConfig) {
+        let config: TomlConfig = toml::from_str(&format!(
+            "[accounts.test]\n\
+             default = true\n\
+             email = \"test@localhost\"\n\
+             backend.type = \"maildir\"\n\
+             backend.root-dir = {:?}\n\
+             backend.maildirpp = {maildirpp}\n\
+             {options}\n",
+            root.0
+        ))
+        .unwrap();
+
+        config
+            .into_account_configs(None, |c: &Config, name| c.account(name).ok())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn rename_maildirpp_folder_with_subfolders() {
+        let root = MaildirRoot::new();
+        root.folder(".Clients", &["cur/1700000000.test:2,S"]);
+        root.folder(".Clients.ACME", &[]);
+        root.folder(".ClientsArchive", &[]);
+
+        let (toml_account_config, account_config) = configs(&root, true, "");
+        rename(
+            &toml_account_config,
+            &account_config,
+            "Clients",
+            "Customers",
+        )
+        .await
+        .unwrap();
+
+        assert!(root.0.join(".Customers/cur/1700000000.test:2,S").is_file());
+        assert!(root.0.join(".Customers.ACME/cur").is_dir());
+        assert!(!root.0.join(".Clients").exists());
+        assert!(!root.0.join(".Clients.ACME").exists());
+
+        // folders only sharing a prefix are left untouched
+        assert!(root.0.join(".ClientsArchive/cur").is_dir());
+    }
+
+    #[tokio::test]
+    async fn rename_nested_folder() {
+        let root = MaildirRoot::new();
+        root.folder("Clients", &["cur/1700000000.test:2,S"]);
+        root.folder("Clients/ACME", &[]);
+
+        let (toml_account_config, account_config) = configs(&root, false, "");
+        rename(
+            &toml_account_config,
+            &account_config,
+            "Clients",
+            "Archives/Clients",
+        )
+        .await
+        .unwrap();
+
+        let dir = root.0.join("Archives/Clients");
+        assert!(dir.join("cur/1700000000.test:2,S").is_file());
+        assert!(dir.join("ACME/cur").is_dir());
+        assert!(!root.0.join("Clients").exists());
+    }
+
+    #[tokio::test]
+    async fn refuse_existing_target() {
+        let root = MaildirRoot::new();
+        root.folder(".Clients", &[]);
+        root.folder(".Clients.ACME", &[]);
+        root.folder(".Customers.ACME", &[]);
+
+        // a subfolder of the target already exists, nothing is renamed
+        let (toml_account_config, account_config) = configs(&root, true, "");
+        let err = rename(
+            &toml_account_config,
+            &account_config,
+            "Clients",
+            "Customers",
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+
+        assert!(err.ends_with(".Customers.ACME already exists"), "{err}");
+        assert!(root.0.join(".Clients/cur").is_dir());
+        assert!(root.0.join(".Clients.ACME/cur").is_dir());
+        assert!(!root.0.join(".Customers").exists());
+    }
+
+    #[tokio::test]
+    async fn refuse_missing_folder() {
+        let root = MaildirRoot::new();
+
+        let (toml_account_config, account_config) = configs(&root, false, "");
+        let err = rename(
+            &toml_account_config,
+            &account_config,
+            "Clients",
+            "Customers",
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+
+        assert!(err.starts_with("cannot find folder Clients"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn refuse_inbox() {
+        let root = MaildirRoot::new();
+        root.folder(".Clients", &[]);
+
+        let (toml_account_config, account_config) =
+            configs(&root, true, "folder.aliases.inbox = \"Inbox\"");
+
+        for (from, to) in [("INBOX", "Old"), ("Inbox", "Old"), ("Clients", "inbox")] {
+            let res = rename(&toml_account_config, &account_config, from, to);
+            assert!(res.await.is_err(), "{from} → {to}");
+        }
+
+        assert!(root.0.join(".Clients/cur").is_dir());
+    }
+
+    #[test]
+    fn find_aliases_of_subfolders() {
+        let root = MaildirRoot::new();
+
+        let (toml_account_config, account_config) = configs(
+            &root,
+            true,
+            "folder.aliases.clients = \"Clients\"\n\
+             folder.aliases.acme = \"Clients.ACME\"\n\
+             folder.aliases.archive = \"ClientsArchive\"\n\
+             folder.aliases.\"Clients/ACME\" = \"Clients.ACME\"\n",
+        );
+
+        let aliases = find_aliases(&toml_account_config, &account_config, "clients");
+
+        assert_eq!(
+            aliases,
+            [
+                (String::from("acme"), String::from("Clients.ACME")),
+                (String::from("clients"), String::from("Clients")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Module dedicated to special-use folders.
+//!
+//! IMAP servers supporting SPECIAL-USE (RFC 6154) mark the role of
+//! some mailboxes with attributes like `\Sent` or `\Trash`. Those
+//! mailboxes are used as the sent, drafts, trash, junk and archive
+//! folders of accounts that do not alias them explicitly.
+
+use std::{collections::HashMap, fmt};
+
+use color_eyre::Result;
+use email::account::config::AccountConfig;
+use serde::Serialize;
+use tracing::debug;
+
+use crate::account::config::TomlAccountConfig;
+
+/// The role of a special-use folder.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FolderRole {
+    Sent,
+    Drafts,
+    Trash,
+    Junk,
+    Archive,
+}
+
+impl FolderRole {
+    pub const ALL: [FolderRole; 5] = [
+        FolderRole::Sent,
+        FolderRole::Drafts,
+        FolderRole::Trash,
+        FolderRole::Junk,
+        FolderRole::Archive,
+    ];
+
+    /// Get the folder alias of the role, as used in the `folder.aliases`
+    /// configuration.
+    pub fn alias(self) -> &'static str {
+        match self {
+            FolderRole::Sent => "sent",
+            FolderRole::Drafts => "drafts",
+            FolderRole::Trash => "trash",
+            FolderRole::Junk => "junk",
+            FolderRole::Archive => "archive",
+        }
+    }
+
+    /// Parse the role from the given folder alias, case-insensitively.
+    pub fn from_alias(alias: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|role| role.alias().eq_ignore_ascii_case(alias.trim()))
+    }
+
+    /// Parse the role from the given mailbox attribute, like `\Sent`.
+    pub fn from_attribute(attr: &str) -> Option<Self> {
+        Self::from_alias(attr.strip_prefix('\\')?)
+    }
+}
+
+impl fmt::Display for FolderRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.alias())
+    }
+}
+
+/// Get the roles of the given folders, indexed by folder name.
+///
+/// Folders aliased by a role in the account configuration always win
+/// over folders marked by the server: the attributes of the latter
+/// are only read from their description, as IMAP folders describe
+/// themselves by their attributes.
+pub fn find_roles<'a>(
+    account_config: &AccountConfig,
+    folders: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> HashMap<String, FolderRole> {
+    let mut roles = HashMap::new();
+
+    for (name, desc) in folders {
+        let aliased = FolderRole::ALL.into_iter().find(|role| {
+            account_config
+                .find_folder_alias(role.alias())
+                .is_some_and(|alias| alias == name)
+        });
+
+        let marked = desc
+            .split(", ")
+            .filter_map(FolderRole::from_attribute)
+            .find(|role| account_config.find_folder_alias(role.alias()).is_none());
+
+        if let Some(role) = aliased.or(marked) {
+            roles.insert(name.to_owned(), role);
+        }
+    }
+
+    roles
+}
+
+/// Alias the special-use folders of the given roles, unless the
+/// account configuration already aliases them.
+///
+/// Special-use folders are only fetched when one of the roles is not
+/// aliased, over a dedicated connection. Servers that do not mark
+/// their mailboxes, as well as errors, leave aliases untouched: the
+/// backend reports connection errors by itself.
+pub async fn add_aliases(
+    toml_account_config: &TomlAccountConfig,
+    account_config: &mut AccountConfig,
+    roles: &[FolderRole],
+) {
+    let roles: Vec<_> = roles
+        .iter()
+        .filter(|role| account_config.find_folder_alias(role.alias()).is_none())
+        .collect();
+
+    if roles.is_empty() {
+        return;
+    }
+
+    let mut folders = match find_folders(toml_account_config).await {
+        Ok(folders) => folders,
+        Err(err) => {
+            debug!("cannot find special-use folders: {err}");
+            return;
+        }
+    };
+
+    let folder_config = account_config.folder.get_or_insert_with(Default::default);
+    let aliases = folder_config.aliases.get_or_insert_with(Default::default);
+
+    for role in roles {
+        if let Some(folder) = folders.remove(role) {
+            debug!("using special-use folder {folder} as {role} folder");
+            aliases.insert(role.alias().to_owned(), folder);
+        }
+    }
+}
+
+/// Get the special-use folders of the given account, indexed by
+/// role.
+///
+/// Only IMAP accounts have special-use folders.
+#[cfg_attr(not(feature = "imap"), allow(unused_variables))]
+pub async fn find_folders(
+    toml_account_config: &TomlAccountConfig,
+) -> Result<HashMap<FolderRole, String>> {
+    #[cfg(feature = "imap")]
+    use pimalaya_tui::himalaya::config::Backend;
+
+    #[cfg(feature = "imap")]
+    if let Some(Backend::Imap(imap_config)) = &toml_account_config.backend {
+        return imap::find_folders(imap_config).await;
+    }
+
+    Ok(HashMap::new())
+}
+
+#[cfg(feature = "imap")]
+mod imap {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        sync::{Arc, Mutex},
+    };
+
+    use color_eyre::Result;
+    use email::imap::{config::ImapConfig, ImapClientBuilder};
+    use imap_client::imap_types::mailbox::Mailbox;
+    use tracing::debug;
+    use utf7_imap::decode_utf7_imap as decode_utf7;
+
+    use super::FolderRole;
+
+    /// The special-use mailboxes already found, by login and server,
+    /// so that commands resolving several folders connect only once.
+    static FOUND: Mutex<BTreeMap<String, HashMap<FolderRole, String>>> =
+        Mutex::new(BTreeMap::new());
+
+    /// Find the special-use mailboxes from the attributes of the LIST
+    /// response, over a dedicated connection.
+    ///
+    /// The first mailbox marked with a role wins.
+    pub async fn find_folders(config: &ImapConfig) -> Result<HashMap<FolderRole, String>> {
+        let key = format!("{}@{}:{}", config.login, config.host, config.port);
+
+        if let Some(folders) = FOUND.lock().unwrap().get(&key) {
+            return Ok(folders.clone());
+        }
+
+        let mut client = ImapClientBuilder::new(Arc::new(config.clone()), None)
+            .build()
+            .await?;
+
+        let mboxes = client.list("", "*").await?;
+        let mut folders = HashMap::new();
+
+        for (mbox, _, attrs) in mboxes {
+            let name = match mbox {
+                Mailbox::Inbox => String::from("INBOX"),
+                Mailbox::Other(mbox) => String::from_utf8_lossy(mbox.as_ref()).to_string(),
+            };
+
+            for attr in attrs {
+                if let Some(role) = FolderRole::from_attribute(&attr.to_string()) {
+                    folders
+                        .entry(role)
+                        .or_insert_with(|| decode_utf7(name.clone()));
+                }
+            }
+        }
+
+        debug!("IMAP special-use folders: {folders:?}");
+        FOUND.lock().unwrap().insert(key, folders.clone());
+
+        Ok(folders)
+    }
+}
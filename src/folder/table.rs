@@ -0,0 +1,181 @@
+use std::{collections::HashMap, fmt};
+
+use comfy_table::{Cell, ContentArrangement, Row, Table};
+use crossterm::style::Color;
+use pimalaya_tui::himalaya::config::{Folders, ListFoldersTableConfig};
+use serde::{Serialize, Serializer};
+
+use crate::{
+    folder::{count::FolderCounts, special_use::FolderRole},
+    table::{Column, TableConfig},
+};
+
+const NAME_COLUMN: Column = Column::left("name", "NAME");
+const ROLE_COLUMN: Column = Column::left("role", "ROLE");
+const DESC_COLUMN: Column = Column::left("desc", "DESC");
+const COUNTS_COLUMNS: [Column; 2] = [
+    Column::right("unread", "UNREAD"),
+    Column::right("total", "TOTAL"),
+];
+
+/// The printable table of folders.
+pub struct FoldersTable {
+    folders: Folders,
+    width: Option<u16>,
+    config: ListFoldersTableConfig,
+    table_config: TableConfig,
+    roles: HashMap<String, FolderRole>,
+    counts: Option<HashMap<String, FolderCounts>>,
+}
+
+impl FoldersTable {
+    pub fn with_some_width(mut self, width: Option<u16>) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_some_preset(mut self, preset: Option<String>) -> Self {
+        self.config.preset = preset;
+        self
+    }
+
+    pub fn with_some_name_color(mut self, color: Option<Color>) -> Self {
+        self.config.name_color = color;
+        self
+    }
+
+    pub fn with_some_desc_color(mut self, color: Option<Color>) -> Self {
+        self.config.desc_color = color;
+        self
+    }
+
+    pub fn with_table_config(mut self, config: TableConfig) -> Self {
+        self.table_config = config;
+        self
+    }
+
+    /// Show the given special-use roles, indexed by folder name.
+    ///
+    /// The role column is only shown when at least one folder has a
+    /// role.
+    pub fn with_roles(mut self, roles: HashMap<String, FolderRole>) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    /// Show the given message counts, indexed by folder name.
+    ///
+    /// Folders without counts are shown with a `-`.
+    pub fn with_some_counts(mut self, counts: Option<HashMap<String, FolderCounts>>) -> Self {
+        self.counts = counts;
+        self
+    }
+}
+
+impl From<Folders> for FoldersTable {
+    fn from(folders: Folders) -> Self {
+        Self {
+            folders,
+            width: None,
+            config: Default::default(),
+            table_config: Default::default(),
+            roles: HashMap::new(),
+            counts: None,
+        }
+    }
+}
+
+impl fmt::Display for FoldersTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Table::new();
+
+        table
+            .load_preset(self.config.preset())
+            .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+            .add_rows(self.folders.iter().map(|folder| {
+                let style = |key, cell| self.table_config.style(key, cell);
+
+                let mut row = Row::new();
+                row.max_height(1);
+
+                let name = Cell::new(&folder.name).fg(self.config.name_color());
+                row.add_cell(style("name", name));
+
+                if !self.roles.is_empty() {
+                    let role = self.roles.get(&folder.name).map(|role| role.alias());
+                    row.add_cell(style("role", Cell::new(role.unwrap_or_default())));
+                }
+
+                let desc = Cell::new(&folder.desc).fg(self.config.desc_color());
+                row.add_cell(style("desc", desc));
+
+                if let Some(counts) = &self.counts {
+                    let count = counts.get(&folder.name);
+                    let unread = count.map(|c| c.unread.to_string());
+                    let total = count.map(|c| c.total.to_string());
+                    row.add_cell(style("unread", Cell::new(unread.as_deref().unwrap_or("-"))));
+                    row.add_cell(style("total", Cell::new(total.as_deref().unwrap_or("-"))));
+                }
+
+                row
+            }));
+
+        let mut columns = vec![NAME_COLUMN];
+
+        if !self.roles.is_empty() {
+            columns.push(ROLE_COLUMN);
+        }
+
+        columns.push(DESC_COLUMN);
+
+        if self.counts.is_some() {
+            columns.extend(COUNTS_COLUMNS);
+        }
+
+        self.table_config.apply(&mut table, &columns, self.width);
+
+        writeln!(f)?;
+        write!(f, "{table}")?;
+        writeln!(f)?;
+        Ok(())
+    }
+}
+
+impl Serialize for FoldersTable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct FolderCountsEntry {
+            unread: Option<u32>,
+            total: Option<u32>,
+        }
+
+        #[derive(Serialize)]
+        struct FolderEntry<'a> {
+            name: &'a str,
+            desc: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            role: Option<FolderRole>,
+            #[serde(flatten, skip_serializing_if = "Option::is_none")]
+            counts: Option<FolderCountsEntry>,
+        }
+
+        let folders: Vec<_> = self
+            .folders
+            .iter()
+            .map(|folder| FolderEntry {
+                name: &folder.name,
+                desc: &folder.desc,
+                role: self.roles.get(&folder.name).copied(),
+                counts: self.counts.as_ref().map(|counts| {
+                    let count = counts.get(&folder.name);
+                    FolderCountsEntry {
+                        unread: count.map(|c| c.unread),
+                        total: count.map(|c| c.total),
+                    }
+                }),
+            })
+            .collect();
+
+        folders.serialize(serializer)
+    }
+}
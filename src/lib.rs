@@ -1,10 +1,18 @@
 pub mod account;
+pub mod address;
 pub mod cli;
 pub mod completion;
 pub mod config;
 pub mod email;
+#[cfg(all(test, feature = "maildir"))]
+mod fixture;
 pub mod folder;
+pub mod logger;
 pub mod manual;
+pub mod outbox;
+pub mod printer;
+pub mod proxy;
+pub mod table;
 
 #[doc(inline)]
-pub use crate::email::{envelope, flag, message};
+pub use crate::email::{envelope, flag, message, tag};
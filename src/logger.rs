@@ -0,0 +1,528 @@
+//! Module dedicated to logging.
+//!
+//! Logs are written to stderr, and can be written to a file as well
+//! (with timestamps, and without colors). Their level is set from
+//! the command line (`--debug`, `--trace`), or from the `RUST_LOG`
+//! environment variable.
+//!
+//! Secrets are redacted from logs whatever their level, see
+//! [`redact`].
+
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{self, stderr, Write},
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+use color_eyre::{eyre::WrapErr, Result, Section};
+use tracing_error::ErrorLayer;
+use tracing_subscriber::{
+    filter::LevelFilter,
+    fmt::{self, MakeWriter},
+    prelude::*,
+    EnvFilter,
+};
+
+/// The replacement of redacted secrets.
+const REDACTED: &str = "[REDACTED]";
+
+/// The protocol commands followed by an argument to keep (the login
+/// or the mechanism), then by secrets: IMAP LOGIN and AUTHENTICATE,
+/// and SMTP AUTH.
+const AUTH_COMMANDS: [&str; 3] = ["LOGIN", "AUTHENTICATE", "AUTH"];
+
+/// The endings of field names holding secrets, compared
+/// case-insensitively.
+const SECRET_FIELDS: [&str; 4] = ["password", "passwd", "secret", "token"];
+
+/// The installed logger.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Logger {
+    filter: LevelFilter,
+}
+
+impl Logger {
+    /// Install the global logger.
+    ///
+    /// The given level takes precedence over the `RUST_LOG`
+    /// environment variable. Logs are disabled by default, except in
+    /// the given log file where they default to the debug level.
+    pub fn install(level: Option<LevelFilter>, log_file: Option<&Path>) -> Result<Self> {
+        let stderr_filter = filter(level, LevelFilter::OFF);
+        let mut max_level = stderr_filter.max_level_hint().unwrap_or(LevelFilter::OFF);
+
+        let stderr_layer = fmt::layer()
+            .with_writer(Redacted::new(stderr))
+            .with_filter(stderr_filter);
+
+        let file_layer = match log_file {
+            None => None,
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .wrap_err_with(|| format!("cannot open log file at {}", path.display()))?;
+
+                let file_filter = filter(level, LevelFilter::DEBUG);
+                max_level = max_level.max(file_filter.max_level_hint().unwrap_or(LevelFilter::OFF));
+
+                let layer = fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(Redacted::new(Mutex::new(file)))
+                    .with_filter(file_filter);
+
+                Some(layer)
+            }
+        };
+
+        tracing_subscriber::registry()
+            .with(stderr_layer)
+            .with(file_layer)
+            .with(ErrorLayer::default())
+            .init();
+
+        if env::var("RUST_BACKTRACE").is_err() && max_level == LevelFilter::TRACE {
+            env::set_var("RUST_BACKTRACE", "1");
+        }
+
+        let debug = max_level >= LevelFilter::DEBUG;
+
+        color_eyre::config::HookBuilder::new()
+            .capture_span_trace_by_default(debug)
+            .display_location_section(debug)
+            .display_env_section(false)
+            .install()?;
+
+        Ok(Self { filter: max_level })
+    }
+
+    pub fn with_debug_and_trace_notes<T>(&self, mut res: Result<T>) -> Result<T> {
+        if self.filter < LevelFilter::DEBUG {
+            res = res.note("Run with --debug to enable logs with spantrace.");
+        };
+
+        if self.filter < LevelFilter::TRACE {
+            res = res.note("Run with --trace to enable verbose logs with backtrace.")
+        };
+
+        res
+    }
+}
+
+/// Build the filter of the given level, or from the `RUST_LOG`
+/// environment variable, or of the given default level.
+fn filter(level: Option<LevelFilter>, default: LevelFilter) -> EnvFilter {
+    match level {
+        Some(level) => EnvFilter::new(level.to_string()),
+        None => EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new(default.to_string())),
+    }
+}
+
+/// Redact secrets from the given logs.
+///
+/// Redacted secrets are the passwords of IMAP LOGIN commands, the
+/// SASL responses of IMAP AUTHENTICATE and SMTP AUTH commands (which
+/// carry passwords and OAuth 2.0 tokens), raw secrets of debugged
+/// configurations and values of password-like fields (like
+/// `password=…` or `access_token=…`).
+///
+/// SASL responses sent as continuations of a command (like the login
+/// then the password of SMTP AUTH LOGIN) are redacted as well, see
+/// [`Redactor`].
+pub fn redact(logs: &str) -> String {
+    Redactor::default().redact(logs)
+}
+
+/// The redactor of secrets, keeping track of authentication
+/// exchanges across lines.
+///
+/// Once an authentication command is seen, the last word of the
+/// following lines is redacted, except for server challenges (`+` in
+/// IMAP, `334` in SMTP). The exchange ends with the first status
+/// response (a tagged IMAP response or an SMTP reply code), or after
+/// [`CONTINUATION_LINES`] lines.
+#[derive(Debug, Default)]
+struct Redactor {
+    /// The number of lines left in the current authentication
+    /// exchange.
+    continuation: usize,
+}
+
+impl Redactor {
+    fn redact(&mut self, logs: &str) -> String {
+        logs.split_inclusive('\n')
+            .map(|line| self.redact_line(line))
+            .collect()
+    }
+
+    fn redact_line(&mut self, line: &str) -> String {
+        let (line, eol) = match line.strip_suffix('\n') {
+            Some(line) => (line, "\n"),
+            None => (line, ""),
+        };
+
+        let line = redact_raw_secrets(line);
+
+        if self.continuation > 0 {
+            self.continuation -= 1;
+
+            match Exchange::from_line(&line) {
+                Exchange::Response => return redact_last_word(&line) + eol,
+                Exchange::End => self.continuation = 0,
+                Exchange::Challenge => (),
+            }
+        }
+
+        let mut words = line.split(' ');
+        let mut redacted = Vec::new();
+
+        while let Some(word) = words.next() {
+            let command = word.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+
+            if AUTH_COMMANDS.contains(&command) {
+                redacted.push(word.to_owned());
+
+                let arg = words.next();
+                redacted.extend(arg.map(ToOwned::to_owned));
+
+                let rest: Vec<_> = words.collect();
+
+                if !rest.is_empty() {
+                    redacted.push(REDACTED.to_owned());
+                }
+
+                // IMAP LOGIN only continues when sending literals,
+                // like `LOGIN {5}`
+                let literal = rest
+                    .last()
+                    .or(arg.as_ref())
+                    .is_some_and(|w| w.ends_with('}'));
+
+                if command != "LOGIN" || literal {
+                    self.continuation = CONTINUATION_LINES;
+                }
+
+                break;
+            }
+
+            redacted.push(redact_field(word));
+        }
+
+        redacted.join(" ") + eol
+    }
+}
+
+/// The maximum number of lines of an authentication exchange.
+const CONTINUATION_LINES: usize = 8;
+
+/// A line of an authentication exchange.
+#[derive(Debug, Eq, PartialEq)]
+enum Exchange {
+    /// A server challenge, like `+ VXNlcm5hbWU6` or `334 VXNlcm5hbWU6`.
+    Challenge,
+    /// A client response, holding a secret.
+    Response,
+    /// A status response ending the exchange, like `A1 OK` or `235`.
+    End,
+}
+
+impl Exchange {
+    fn from_line(line: &str) -> Self {
+        let line = strip_ansi(line);
+        let words: Vec<_> = line
+            .split_whitespace()
+            .map(|word| word.trim_matches(|c: char| !c.is_ascii_alphanumeric() && c != '+'))
+            .collect();
+
+        if words.iter().any(|word| *word == "+" || *word == "334") {
+            return Self::Challenge;
+        }
+
+        let reply_code = |word: &&str| {
+            word.len() == 3
+                && word.starts_with(['2', '4', '5'])
+                && word.chars().all(|c| c.is_ascii_digit())
+        };
+
+        let tagged_status = |pair: &[&str]| {
+            pair[0] != "*" && ["OK", "NO", "BAD"].contains(&pair[1].to_uppercase().as_str())
+        };
+
+        if words.iter().any(reply_code) || words.windows(2).any(tagged_status) {
+            return Self::End;
+        }
+
+        Self::Response
+    }
+}
+
+/// Redact the last word of the given line.
+///
+/// Lines ending with a space are left untouched, since their last
+/// word is empty, like empty SASL responses.
+fn redact_last_word(line: &str) -> String {
+    match line.rsplit_once(' ') {
+        Some((_, "")) => line.to_owned(),
+        Some((before, _)) => format!("{before} {REDACTED}"),
+        None if line.is_empty() => line.to_owned(),
+        None => REDACTED.to_owned(),
+    }
+}
+
+/// Redact raw secrets of debugged configurations, like
+/// `Raw("password")`.
+fn redact_raw_secrets(line: &str) -> String {
+    let mut redacted = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(start) = rest.find("Raw(\"") {
+        let (before, secret) = rest.split_at(start + 5);
+        redacted.push_str(before);
+        redacted.push_str(REDACTED);
+
+        rest = match secret.find("\")") {
+            Some(end) => &secret[end..],
+            None => "",
+        };
+    }
+
+    redacted.push_str(rest);
+    redacted
+}
+
+fn redact_field(word: &str) -> String {
+    let Some((key, value)) = word.split_once('=') else {
+        return word.to_owned();
+    };
+
+    let key_lowercase = strip_ansi(key).to_lowercase();
+    let secret = SECRET_FIELDS
+        .iter()
+        .any(|field| key_lowercase.ends_with(field));
+
+    if secret && !value.is_empty() {
+        format!("{key}={REDACTED}")
+    } else {
+        word.to_owned()
+    }
+}
+
+/// Remove ANSI escape sequences (like colors) from the given text.
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            stripped.push(c);
+        }
+    }
+
+    stripped
+}
+
+/// The writer maker redacting secrets of logs.
+///
+/// Log events are formatted before being written at once, so that
+/// each write contains whole lines. The redactor is shared by the
+/// writers, so that authentication exchanges spanning several events
+/// are followed.
+struct Redacted<M> {
+    make_writer: M,
+    redactor: Mutex<Redactor>,
+}
+
+impl<M> Redacted<M> {
+    fn new(make_writer: M) -> Self {
+        Self {
+            make_writer,
+            redactor: Mutex::default(),
+        }
+    }
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacted<M> {
+    type Writer = RedactedWriter<'a, M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactedWriter {
+            writer: self.make_writer.make_writer(),
+            redactor: &self.redactor,
+        }
+    }
+}
+
+struct RedactedWriter<'a, W> {
+    writer: W,
+    redactor: &'a Mutex<Redactor>,
+}
+
+impl<W: Write> Write for RedactedWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let logs = String::from_utf8_lossy(buf);
+        let mut redactor = self.redactor.lock().unwrap_or_else(PoisonError::into_inner);
+        self.writer.write_all(redactor.redact(&logs).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{redact, Redactor};
+
+    #[test]
+    fn redact_imap_login() {
+        assert_eq!(
+            redact("DEBUG imap: C: A1 LOGIN alice secret\n"),
+            "DEBUG imap: C: A1 LOGIN alice [REDACTED]\n",
+        );
+        assert_eq!(
+            redact("C: A1 LOGIN \"alice\" \"my secret\"\nS: A1 OK done\n"),
+            "C: A1 LOGIN \"alice\" [REDACTED]\nS: A1 OK done\n",
+        );
+    }
+
+    #[test]
+    fn redact_imap_login_literals() {
+        let logs = concat!(
+            "C: A1 LOGIN {5}\n",
+            "S: + Ready\n",
+            "C: alice {6}\n",
+            "S: + Ready\n",
+            "C: secret\n",
+            "S: A1 OK done\n",
+            "C: A2 SELECT INBOX\n",
+        );
+
+        assert_eq!(
+            redact(logs),
+            concat!(
+                "C: A1 LOGIN {5}\n",
+                "S: + Ready\n",
+                "C: alice [REDACTED]\n",
+                "S: + Ready\n",
+                "C: [REDACTED]\n",
+                "S: A1 OK done\n",
+                "C: A2 SELECT INBOX\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn redact_smtp_auth_plain() {
+        assert_eq!(
+            redact("C: AUTH PLAIN AGFsaWNlAHNlY3JldA==\nS: 235 2.7.0 Accepted\n"),
+            "C: AUTH PLAIN [REDACTED]\nS: 235 2.7.0 Accepted\n",
+        );
+
+        let logs = concat!(
+            "C: AUTH PLAIN\n",
+            "S: 334 \n",
+            "C: AGFsaWNlAHNlY3JldA==\n",
+            "S: 235 2.7.0 Accepted\n",
+            "C: MAIL FROM:<alice@localhost>\n",
+        );
+
+        assert_eq!(
+            redact(logs),
+            concat!(
+                "C: AUTH PLAIN\n",
+                "S: 334 \n",
+                "C: [REDACTED]\n",
+                "S: 235 2.7.0 Accepted\n",
+                "C: MAIL FROM:<alice@localhost>\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn redact_smtp_auth_login_across_writes() {
+        let mut redactor = Redactor::default();
+
+        let writes = [
+            ("C: AUTH LOGIN\n", "C: AUTH LOGIN\n"),
+            ("S: 334 VXNlcm5hbWU6\n", "S: 334 VXNlcm5hbWU6\n"),
+            ("C: YWxpY2U=\n", "C: [REDACTED]\n"),
+            ("S: 334 UGFzc3dvcmQ6\n", "S: 334 UGFzc3dvcmQ6\n"),
+            ("C: c2VjcmV0\n", "C: [REDACTED]\n"),
+            ("S: 235 2.7.0 Accepted\n", "S: 235 2.7.0 Accepted\n"),
+            ("C: QUIT\n", "C: QUIT\n"),
+        ];
+
+        for (logs, expected) in writes {
+            assert_eq!(redactor.redact(logs), expected);
+        }
+    }
+
+    #[test]
+    fn redact_imap_authenticate_continuation() {
+        let logs = concat!(
+            "C: A1 AUTHENTICATE XOAUTH2\n",
+            "S: +\n",
+            "C: dXNlcj1hbGljZQFhdXRoPUJlYXJlciB0b2tlbgEB\n",
+            "S: A1 OK authenticated\n",
+        );
+
+        assert_eq!(
+            redact(logs),
+            concat!(
+                "C: A1 AUTHENTICATE XOAUTH2\n",
+                "S: +\n",
+                "C: [REDACTED]\n",
+                "S: A1 OK authenticated\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn redact_xoauth2_initial_response() {
+        let logs = concat!(
+            "C: A1 AUTHENTICATE XOAUTH2 dXNlcj1hbGljZQFhdXRoPUJlYXJlciB0b2tlbgEB\n",
+            "S: + eyJzdGF0dXMiOiI0MDEifQ==\n",
+            "C: \n",
+            "S: A1 NO invalid credentials\n",
+            "C: A2 LOGOUT\n",
+        );
+
+        assert_eq!(
+            redact(logs),
+            concat!(
+                "C: A1 AUTHENTICATE XOAUTH2 [REDACTED]\n",
+                "S: + eyJzdGF0dXMiOiI0MDEifQ==\n",
+                "C: \n",
+                "S: A1 NO invalid credentials\n",
+                "C: A2 LOGOUT\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn redact_raw_secrets() {
+        assert_eq!(
+            redact("auth: Password(Raw(\"my \\\"secret\\\"\")), login: \"alice\"\n"),
+            "auth: Password(Raw(\"[REDACTED]\")), login: \"alice\"\n",
+        );
+    }
+
+    #[test]
+    fn redact_secret_fields() {
+        assert_eq!(
+            redact("refreshing access_token=abc refresh_token=def client_id=himalaya\n"),
+            "refreshing access_token=[REDACTED] refresh_token=[REDACTED] client_id=himalaya\n",
+        );
+        assert_eq!(
+            redact("OAUTH_TOKEN=abc empty_token= password=hunter2\n"),
+            "OAUTH_TOKEN=[REDACTED] empty_token= password=[REDACTED]\n",
+        );
+    }
+}
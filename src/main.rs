@@ -1,18 +1,15 @@
 use clap::Parser;
-use color_eyre::Result;
+use color_eyre::{eyre::eyre, Result};
 use himalaya::{
-    cli::Cli, config::TomlConfig, envelope::command::list::EnvelopeListCommand,
-    message::command::mailto::MessageMailtoCommand,
-};
-use pimalaya_tui::terminal::{
-    cli::{printer::StdoutPrinter, tracing},
-    config::TomlConfig as _,
+    account::arg::name::ACCOUNT_ENV, cli::Cli, config::TomlConfig,
+    envelope::command::list::EnvelopeListCommand, folder::arg::name::FOLDER_ENV, logger::Logger,
+    message::command::mailto::MessageMailtoCommand, printer::StdoutPrinter, table,
 };
+use pimalaya_tui::terminal::cli::arg::path_parser;
+use tracing::debug;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let tracing = tracing::install()?;
-
     #[cfg(feature = "keyring")]
     secret::keyring::set_global_service_name("himalaya-cli");
 
@@ -23,25 +20,223 @@ async fn main() -> Result<()> {
         .filter(|arg| arg.starts_with("mailto:"));
 
     if let Some(ref url) = mailto {
+        let logger = Logger::install(None, None)?;
+
+        // arguments are not parsed, but the configuration path can
+        // still be given from the environment
+        let config_paths = match std::env::var("HIMALAYA_CONFIG") {
+            Ok(path) => vec![path_parser(&path).map_err(|err| eyre!(err))?],
+            Err(_) => Vec::new(),
+        };
+
         let mut printer = StdoutPrinter::default();
-        let config = TomlConfig::from_default_paths().await?;
+        let config = TomlConfig::from_cli_paths(&config_paths).await?;
 
-        return MessageMailtoCommand::new(url)?
+        let res = MessageMailtoCommand::new(url)?
             .execute(&mut printer, &config)
             .await;
+
+        #[cfg(feature = "smtp")]
+        himalaya::message::send::smtp::wait_closing_sessions().await;
+
+        return logger.with_debug_and_trace_notes(res);
     }
 
     let cli = Cli::parse();
-    let mut printer = StdoutPrinter::new(cli.output);
+    let logger = Logger::install(cli.log_level(), cli.log_file.as_deref())?;
+
+    for var in [ACCOUNT_ENV, FOLDER_ENV] {
+        if let Ok(value) = std::env::var(var) {
+            debug!("using {var}={value} when no flag is given");
+        }
+    }
+
+    if cli.no_color {
+        table::disable_colors();
+    }
+
+    if cli.output_file.is_some() {
+        table::disable_tty();
+    }
+
+    let mut printer = StdoutPrinter::new(cli.output)
+        .with_quiet(cli.quiet)
+        .with_some_output_file(cli.output_file);
     let res = match cli.command {
         Some(cmd) => cmd.execute(&mut printer, cli.config_paths.as_ref()).await,
         None => {
-            let config = TomlConfig::from_paths_or_default(cli.config_paths.as_ref()).await?;
+            let config = TomlConfig::from_cli_paths(&cli.config_paths).await?;
             EnvelopeListCommand::default()
                 .execute(&mut printer, &config)
                 .await
         }
     };
 
-    tracing.with_debug_and_trace_notes(res)
+    // SMTP sessions reused across messages are closed with QUIT once
+    // the command is done with them
+    #[cfg(feature = "smtp")]
+    himalaya::message::send::smtp::wait_closing_sessions().await;
+
+    let res = res.and_then(|()| printer.flush());
+
+    #[cfg(feature = "keyring")]
+    let res = res.map_err(with_keyring_note);
+
+    #[cfg(feature = "oauth2")]
+    let res = res.map_err(with_oauth2_note);
+
+    logger.with_debug_and_trace_notes(res)
+}
+
+/// Add a hint to errors caused by a missing keyring entry.
+#[cfg(feature = "keyring")]
+fn with_keyring_note(err: color_eyre::Report) -> color_eyre::Report {
+    use color_eyre::Section;
+    use secret::keyring::Error;
+
+    let missing_entry = err.chain().any(|err| {
+        matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::GetSecretError(..) | Error::FindSecretError(..))
+        )
+    });
+
+    if missing_entry {
+        err.note("Run `himalaya account configure <account>` to store the secret in your keyring.")
+    } else {
+        err
+    }
+}
+
+/// Add a hint to errors caused by a revoked or expired OAuth 2.0
+/// refresh token.
+#[cfg(feature = "oauth2")]
+fn with_oauth2_note(err: color_eyre::Report) -> color_eyre::Report {
+    use color_eyre::Section;
+    use email::account::Error;
+
+    let refresh_failed = err.chain().any(|err| {
+        matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::RefreshAccessTokenOauthError(..))
+        )
+    });
+
+    if refresh_failed {
+        err.note(
+            "The OAuth 2.0 refresh token may have been revoked (password change, expiration…).",
+        )
+        .suggestion("Run `himalaya account configure <account> --reset` to authorize again.")
+    } else {
+        err
+    }
+}
+
+#[cfg(all(test, feature = "oauth2"))]
+mod tests {
+    use color_eyre::{
+        config::HookBuilder,
+        eyre::{eyre, WrapErr},
+    };
+    use email::account::config::oauth2::OAuth2Config;
+    use secret::Secret;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    const NOTE: &str = "The OAuth 2.0 refresh token may have been revoked";
+
+    /// Start a token endpoint rejecting refresh tokens, the way
+    /// providers do once tokens are revoked.
+    ///
+    /// The server answers one request, then returns it.
+    async fn token_endpoint() -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut req = Vec::new();
+            let mut buf = [0; 1024];
+            while !String::from_utf8_lossy(&req).contains("refresh_token=") {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                req.extend_from_slice(&buf[..n]);
+            }
+
+            let body = r#"{"error":"invalid_grant","error_description":"Token has been revoked."}"#;
+            let res = format!(
+                "HTTP/1.1 400 Bad Request\r\n\
+                 Content-Type: application/json\r\n\
+                 Content-Length: {}\r\n\
+                 Connection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(res.as_bytes()).await.unwrap();
+
+            String::from_utf8_lossy(&req).into_owned()
+        });
+
+        (url, server)
+    }
+
+    /// Install the error report handler, without colors.
+    ///
+    /// Notes can only be added to reports created once the handler is
+    /// installed, like in the main function.
+    fn install() {
+        let _ = HookBuilder::blank().install();
+    }
+
+    /// Render the given report the way it is shown to the user.
+    fn render(err: color_eyre::Report) -> String {
+        format!("{:?}", super::with_oauth2_note(err))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn hint_at_reauthorization_when_refresh_fails() {
+        install();
+
+        let (token_url, server) = token_endpoint().await;
+
+        let config = OAuth2Config {
+            client_id: String::from("himalaya"),
+            auth_url: String::from("http://127.0.0.1/auth"),
+            token_url,
+            refresh_token: Secret::new_raw("revoked-token"),
+            redirect_port: Some(49_152),
+            ..Default::default()
+        };
+
+        let err = config
+            .refresh_access_token()
+            .await
+            .wrap_err("cannot build IMAP client")
+            .unwrap_err();
+
+        let req = server.await.unwrap();
+        assert!(req.starts_with("POST /token "), "{req}");
+        assert!(req.contains("grant_type=refresh_token"), "{req}");
+        assert!(req.contains("refresh_token=revoked-token"), "{req}");
+
+        let report = render(err);
+        assert!(report.contains(NOTE), "{report}");
+        assert!(
+            report.contains("himalaya account configure <account> --reset"),
+            "{report}"
+        );
+    }
+
+    #[test]
+    fn keep_other_errors_untouched() {
+        install();
+
+        let report = render(eyre!("cannot connect to IMAP server"));
+        assert!(!report.contains(NOTE), "{report}");
+    }
 }
@@ -3,11 +3,10 @@ use std::{fs, path::PathBuf};
 use clap::{CommandFactory, Parser};
 use clap_mangen::Man;
 use color_eyre::Result;
-use pimalaya_tui::terminal::cli::printer::Printer;
 use shellexpand_utils::{canonicalize, expand};
 use tracing::info;
 
-use crate::cli::Cli;
+use crate::{cli::Cli, printer::Printer};
 
 /// Generate manual pages to the given directory.
 ///
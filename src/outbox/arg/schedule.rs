@@ -0,0 +1,264 @@
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone};
+use clap::Parser;
+
+/// The scheduled sending flags parser.
+#[derive(Debug, Default, Parser)]
+pub struct SendLaterFlags {
+    /// Queue the message in the outbox, to be sent at the given time.
+    ///
+    /// The time can be absolute (ISO 8601, for example 2024-12-24,
+    /// 2024-12-24T09:00 or 2024-12-24 09:00:00+01:00), a time of today
+    /// or tomorrow (for example 18:30, today 18:30 or tomorrow 9:00),
+    /// or relative to now (for example 30m, 2h or 1d12h). Times are
+    /// evaluated in the local timezone.
+    ///
+    /// Scheduled messages are sent by the outbox flush command, once
+    /// their time has come.
+    #[arg(long, value_name = "TIME", value_parser = parse_datetime)]
+    #[arg(conflicts_with = "send_in")]
+    pub send_at: Option<DateTime<Local>>,
+
+    /// Queue the message in the outbox, to be sent after the given
+    /// duration.
+    ///
+    /// The duration is made of numbers followed by a unit: s for
+    /// seconds, m for minutes, h for hours, d for days and w for
+    /// weeks (for example 30m, 2h or 1d12h).
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub send_in: Option<TimeDelta>,
+}
+
+impl SendLaterFlags {
+    /// Get the time the message should be sent at, if scheduled.
+    pub fn send_at(&self) -> Option<DateTime<Local>> {
+        match self.send_in {
+            Some(delta) => Local::now().checked_add_signed(delta),
+            None => self.send_at,
+        }
+    }
+}
+
+/// Parse the given absolute or relative time.
+fn parse_datetime(time: &str) -> Result<DateTime<Local>, String> {
+    parse_datetime_at(time, Local::now())
+}
+
+/// Parse the given absolute time, or the given time relative to the
+/// given current time.
+fn parse_datetime_at(time: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    let time = time.trim();
+
+    let is_duration =
+        time.starts_with(|c: char| c.is_ascii_digit()) && !time.contains(['-', ':', ' ']);
+
+    let datetime = if is_duration {
+        let delta = parse_duration(time)?;
+        now.checked_add_signed(delta)
+            .ok_or_else(|| format!("time `{time}` is out of range"))?
+    } else if let Ok(datetime) = DateTime::parse_from_rfc3339(time) {
+        datetime.with_timezone(&Local)
+    } else if let Some(datetime) = parse_naive_datetime(time) {
+        from_local(datetime)?
+    } else {
+        let (day, hour) = match time.split_once(char::is_whitespace) {
+            Some((day, hour)) => (day, Some(hour.trim_start())),
+            None if time.contains(':') => ("today", Some(time)),
+            None => (time, None),
+        };
+
+        let day = day.to_lowercase();
+
+        let date = match day.as_str() {
+            "today" => now.date_naive(),
+            "tomorrow" => now.date_naive() + Days::new(1),
+            _ => {
+                return Err(format!(
+                    "invalid time `{time}`, expected an ISO 8601 date, [today|tomorrow] hh:mm or a duration"
+                ))
+            }
+        };
+
+        let hour = match hour {
+            Some(hour) => parse_time(hour).ok_or_else(|| format!("invalid time `{hour}`"))?,
+            None if day == "tomorrow" => now.time(),
+            None => return Err(format!("missing time after `{day}`, expected hh:mm")),
+        };
+
+        from_local(date.and_time(hour))?
+    };
+
+    if datetime < now {
+        return Err(format!("time `{time}` is in the past"));
+    }
+
+    Ok(datetime)
+}
+
+/// Parse the given ISO 8601 date and time without timezone.
+///
+/// A date without time is evaluated at midnight.
+fn parse_naive_datetime(time: &str) -> Option<NaiveDateTime> {
+    if let Ok(date) = NaiveDate::parse_from_str(time, "%Y-%m-%d") {
+        return Some(date.and_time(NaiveTime::MIN));
+    }
+
+    let (date, hour) = time.split_once(['T', ' '])?;
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some(date.and_time(parse_time(hour.trim_start())?))
+}
+
+/// Parse the given time of the day, with or without seconds.
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .ok()
+}
+
+fn from_local(datetime: NaiveDateTime) -> Result<DateTime<Local>, String> {
+    Local
+        .from_local_datetime(&datetime)
+        .earliest()
+        .ok_or_else(|| format!("time `{datetime}` does not exist in the local timezone"))
+}
+
+/// Parse the given duration, made of numbers followed by a unit.
+fn parse_duration(duration: &str) -> Result<TimeDelta, String> {
+    let err =
+        || format!("invalid duration `{duration}`, expected <n><s|m|h|d|w>, for example 1h30m");
+
+    let mut total = TimeDelta::zero();
+    let mut rest = duration.trim();
+
+    if rest.is_empty() {
+        return Err(err());
+    }
+
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or_else(err)?;
+        let n: i64 = rest[..digits].parse().map_err(|_| err())?;
+        let unit = rest[digits..].chars().next().ok_or_else(err)?;
+
+        let delta = match unit {
+            's' => TimeDelta::try_seconds(n),
+            'm' => TimeDelta::try_minutes(n),
+            'h' => TimeDelta::try_hours(n),
+            'd' => TimeDelta::try_days(n),
+            'w' => TimeDelta::try_weeks(n),
+            _ => return Err(err()),
+        };
+
+        total = delta
+            .and_then(|delta| total.checked_add(&delta))
+            .ok_or_else(|| format!("duration `{duration}` is out of range"))?;
+
+        rest = &rest[digits + unit.len_utf8()..];
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Local, TimeDelta, TimeZone};
+
+    use super::{parse_datetime_at, parse_duration};
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(y, m, d, h, min, 0)
+            .earliest()
+            .unwrap()
+    }
+
+    /// The current time of the tests, away from midnight and from
+    /// daylight saving time changes.
+    fn now() -> DateTime<Local> {
+        local(2024, 6, 10, 12, 0)
+    }
+
+    fn parse(time: &str) -> Result<DateTime<Local>, String> {
+        parse_datetime_at(time, now())
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90s"), Ok(TimeDelta::seconds(90)));
+        assert_eq!(parse_duration("30m"), Ok(TimeDelta::minutes(30)));
+        assert_eq!(parse_duration("1h30m"), Ok(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("1d12h"), Ok(TimeDelta::hours(36)));
+        assert_eq!(parse_duration("2w"), Ok(TimeDelta::days(14)));
+        assert_eq!(parse_duration(" 2h "), Ok(TimeDelta::hours(2)));
+    }
+
+    #[test]
+    fn invalid_durations() {
+        for duration in ["", "h", "2", "1h30", "2x", "-1h", "1.5h", "2 h"] {
+            let err = parse_duration(duration).unwrap_err();
+            assert!(err.starts_with("invalid duration"), "{duration}: {err}");
+        }
+    }
+
+    #[test]
+    fn overflowing_durations() {
+        // too large for the number of days
+        let err = parse_duration("9999999999999999d").unwrap_err();
+        assert!(err.ends_with("is out of range"), "{err}");
+
+        // too large for the sum of units
+        let max = TimeDelta::MAX.num_seconds();
+        let err = parse_duration(&format!("{max}s1s")).unwrap_err();
+        assert!(err.ends_with("is out of range"), "{err}");
+
+        // too large for a number
+        let err = parse_duration("99999999999999999999s").unwrap_err();
+        assert!(err.starts_with("invalid duration"), "{err}");
+    }
+
+    #[test]
+    fn relative_times() {
+        assert_eq!(parse("30m"), Ok(now() + TimeDelta::minutes(30)));
+        assert_eq!(parse("1d12h"), Ok(now() + TimeDelta::hours(36)));
+    }
+
+    #[test]
+    fn times_of_today_or_tomorrow() {
+        assert_eq!(parse("18:30"), Ok(local(2024, 6, 10, 18, 30)));
+        assert_eq!(parse("today 18:30"), Ok(local(2024, 6, 10, 18, 30)));
+        assert_eq!(parse("Today 18:30:00"), Ok(local(2024, 6, 10, 18, 30)));
+        assert_eq!(parse("tomorrow 9:00"), Ok(local(2024, 6, 11, 9, 0)));
+        assert_eq!(parse("tomorrow  09:00"), Ok(local(2024, 6, 11, 9, 0)));
+        assert_eq!(parse("tomorrow"), Ok(local(2024, 6, 11, 12, 0)));
+    }
+
+    #[test]
+    fn absolute_times() {
+        assert_eq!(parse("2024-12-24"), Ok(local(2024, 12, 24, 0, 0)));
+        assert_eq!(parse("2024-12-24T09:00"), Ok(local(2024, 12, 24, 9, 0)));
+        assert_eq!(parse("2024-12-24 09:00:00"), Ok(local(2024, 12, 24, 9, 0)));
+
+        let time = parse("2024-12-24T09:00:00+01:00").unwrap();
+        assert_eq!(time.naive_utc().to_string(), "2024-12-24 08:00:00");
+    }
+
+    #[test]
+    fn refuse_past_times() {
+        for time in ["9:00", "today 11:59", "2024-06-01", "2024-06-10T11:00"] {
+            let err = parse(time).unwrap_err();
+            assert!(err.ends_with("is in the past"), "{time}: {err}");
+        }
+    }
+
+    #[test]
+    fn refuse_invalid_times() {
+        assert!(parse("yesterday 9:00")
+            .unwrap_err()
+            .starts_with("invalid time"));
+        assert!(parse("next week").unwrap_err().starts_with("invalid time"));
+        assert!(parse("tomorrow 25:00")
+            .unwrap_err()
+            .starts_with("invalid time"));
+        assert!(parse("today").unwrap_err().starts_with("missing time"));
+        assert!(parse("2x").unwrap_err().starts_with("invalid duration"));
+        assert!(parse("2024-13-01").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use chrono::Local;
+use clap::Parser;
+use color_eyre::{eyre::bail, Result};
+use email::{backend::feature::BackendFeatureSource, config::Config};
+use pimalaya_tui::{himalaya::backend::BackendBuilder, terminal::config::TomlConfig as _};
+use tracing::{info, warn};
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    folder::{self, special_use::FolderRole},
+    message::send,
+    outbox::{self, Outbox},
+    printer::Printer,
+};
+
+/// Send the messages waiting in the outbox.
+///
+/// This command sends the queued messages of the account, from the
+/// oldest to the most recent, and saves a copy of them to the sent
+/// folder. Messages that cannot be sent are kept in the outbox, with
+/// the error of the failed attempt.
+///
+/// Scheduled messages are only sent once their time has come. This
+/// command can be run periodically, for example from a systemd timer
+/// or a cron job, to send them on time.
+#[derive(Debug, Parser)]
+pub struct OutboxFlushCommand {
+    /// Send the given queued messages now, whether they are scheduled
+    /// or not.
+    ///
+    /// Only the given messages are sent.
+    #[arg(long, value_name = "ID", num_args = 1..)]
+    pub now: Vec<String>,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl OutboxFlushCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing flush outbox command");
+
+        let (toml_account_config, mut account_config) = config
+            .clone()
+            .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
+                c.account(name).ok()
+            })?;
+
+        let outbox = Outbox::new(&account_config.name)?;
+        let mut msgs = outbox.list()?;
+
+        if !self.now.is_empty() {
+            if let Some(id) = self
+                .now
+                .iter()
+                .find(|id| !msgs.iter().any(|m| m.id == **id))
+            {
+                bail!("cannot find message {id} in the outbox");
+            }
+
+            msgs.retain(|msg| self.now.contains(&msg.id));
+        } else if msgs.is_empty() {
+            return printer.info("No message to send, the outbox is empty!");
+        } else {
+            let now = Local::now();
+            let total = msgs.len();
+            msgs.retain(|msg| msg.is_due(now));
+
+            if msgs.is_empty() {
+                return printer.info(format!(
+                    "No message to send yet, {total} message(s) scheduled for later!"
+                ));
+            }
+        }
+
+        let extra_config = config.account_extra_config(&account_config.name);
+
+        folder::special_use::add_aliases(
+            &toml_account_config,
+            &mut account_config,
+            &[FolderRole::Sent],
+        )
+        .await;
+
+        let backend = BackendBuilder::new(
+            Arc::new(toml_account_config),
+            Arc::new(account_config),
+            |builder| {
+                let builder = builder
+                    .without_features()
+                    .with_add_message(BackendFeatureSource::Context);
+
+                send::with_send_message(builder, &extra_config)
+            },
+        )
+        .build()
+        .await;
+
+        let backend = match backend {
+            Ok(backend) => backend,
+            Err(err) => {
+                for msg in &msgs {
+                    outbox.record_error(&msg.id, &err)?;
+                }
+
+                return Err(err.wrap_err("cannot build backend, messages are kept in the outbox"));
+            }
+        };
+
+        let total = msgs.len();
+        let mut failures = 0;
+
+        for msg in msgs {
+            let id = &msg.id;
+            let raw = outbox.read(id)?;
+
+            match outbox::send(printer, &backend, &raw).await {
+                Ok(()) => {
+                    outbox.remove(id)?;
+                    printer.log(format!("Message {id} successfully sent!\n"))?;
+                }
+                Err(err) => {
+                    warn!("cannot send queued message {id}: {err:?}");
+                    outbox.record_error(id, &err)?;
+                    printer.log(format!("Message {id} could not be sent: {err}\n"))?;
+                    failures += 1;
+                }
+            }
+        }
+
+        if failures > 0 {
+            bail!("{failures} out of {total} message(s) could not be sent, they are kept in the outbox");
+        }
+
+        printer.info(format!("{total} message(s) successfully sent!"))
+    }
+}
@@ -0,0 +1,55 @@
+use clap::Parser;
+use color_eyre::Result;
+use email::config::Config;
+use pimalaya_tui::terminal::config::TomlConfig as _;
+use tracing::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    outbox::{table::OutboxTable, Outbox},
+    printer::Printer,
+};
+
+/// List the messages waiting in the outbox.
+///
+/// This command lists the queued messages of the account, from the
+/// oldest to the most recent, with the error of their last failed
+/// attempt to be sent.
+#[derive(Debug, Parser)]
+pub struct OutboxListCommand {
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+
+    /// The maximum width the table should not exceed.
+    ///
+    /// This argument will force the table not to exceed the given
+    /// width, in pixels. Columns may shrink with ellipsis in order to
+    /// fit the width.
+    ///
+    /// Defaults to the width of the terminal, or to the
+    /// ui.table.piped-width option when the output is not a terminal.
+    #[arg(long = "max-width", short = 'w')]
+    #[arg(name = "table_max_width", value_name = "PIXELS")]
+    pub table_max_width: Option<u16>,
+}
+
+impl OutboxListCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing list outbox command");
+
+        let (_, account_config) = config
+            .clone()
+            .into_account_configs(self.account.name.as_deref(), |c: &Config, name| {
+                c.account(name).ok()
+            })?;
+
+        let msgs = Outbox::new(&account_config.name)?.list()?;
+
+        let table = OutboxTable::from(msgs)
+            .with_some_width(self.table_max_width)
+            .with_table_config(config.table_config());
+
+        printer.out(table)
+    }
+}
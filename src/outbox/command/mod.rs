@@ -0,0 +1,32 @@
+mod flush;
+mod list;
+
+use clap::Subcommand;
+use color_eyre::Result;
+
+use crate::{config::TomlConfig, printer::Printer};
+
+use self::{flush::OutboxFlushCommand, list::OutboxListCommand};
+
+/// Manage the messages waiting in your outbox.
+///
+/// Messages are queued in the outbox when they are sent with `message
+/// send --queue`, or when they cannot be sent and the account has the
+/// `message.send.queue-on-failure` option enabled.
+#[derive(Debug, Subcommand)]
+pub enum OutboxSubcommand {
+    #[command(alias = "lst")]
+    List(OutboxListCommand),
+
+    #[command(alias = "send")]
+    Flush(OutboxFlushCommand),
+}
+
+impl OutboxSubcommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        match self {
+            Self::List(cmd) => cmd.execute(printer, config).await,
+            Self::Flush(cmd) => cmd.execute(printer, config).await,
+        }
+    }
+}
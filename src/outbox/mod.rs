@@ -0,0 +1,469 @@
+//! Module dedicated to the outbox.
+//!
+//! The outbox is a local directory holding the messages that could
+//! not be sent yet, one directory per account under the cache
+//! directory. Each message is stored as a `<id>.eml` file next to a
+//! `<id>.json` file containing its queue metadata. Files are written
+//! into the `tmp` directory first, then moved into the outbox, so
+//! that a message is never partially queued.
+
+pub mod arg;
+pub mod command;
+pub mod table;
+
+use std::{fs, io, path::PathBuf};
+
+use chrono::{DateTime, Local};
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Report, Result,
+};
+use email::{
+    flag::{Flag, Flags},
+    folder::SENT,
+    message::send::SendMessage,
+};
+use mail_parser::{Address, MessageParser};
+use pimalaya_tui::himalaya::backend::Backend;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::printer::Printer;
+
+/// The outbox of an account.
+#[derive(Clone, Debug)]
+pub struct Outbox {
+    dir: PathBuf,
+}
+
+impl Outbox {
+    /// Open the outbox of the given account.
+    ///
+    /// The outbox directory is only created once a message is
+    /// queued.
+    pub fn new(account: &str) -> Result<Self> {
+        let Some(dir) = dirs::cache_dir() else {
+            bail!("cannot find cache directory for the outbox of account {account}");
+        };
+
+        let dir = dir.join("himalaya").join("outbox").join(account);
+        Ok(Self { dir })
+    }
+
+    /// Queue the given raw message, to be sent at the given time if
+    /// any.
+    ///
+    /// Returns the identifier of the queued message.
+    pub fn push(&self, msg: &[u8], send_at: Option<DateTime<Local>>) -> Result<String> {
+        let meta = QueuedMessageMeta {
+            send_at,
+            ..Default::default()
+        };
+
+        self.push_with_meta(msg, meta)
+    }
+
+    /// Queue the given raw message after a failed attempt to send it.
+    ///
+    /// Returns the identifier of the queued message.
+    pub fn push_failed(&self, msg: &[u8], err: &Report) -> Result<String> {
+        let meta = QueuedMessageMeta {
+            attempts: 1,
+            error: Some(format!("{err:#}")),
+            ..Default::default()
+        };
+
+        self.push_with_meta(msg, meta)
+    }
+
+    fn push_with_meta(&self, msg: &[u8], meta: QueuedMessageMeta) -> Result<String> {
+        let id = Uuid::new_v4().to_simple().to_string()[..8].to_owned();
+
+        self.write_meta(&id, &meta)?;
+        self.write(&format!("{id}.eml"), msg)?;
+
+        debug!("message {id} queued in outbox at {}", self.dir.display());
+        Ok(id)
+    }
+
+    /// List the queued messages, from the oldest to the most recent.
+    pub fn list(&self) -> Result<Vec<QueuedMessage>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                let dir = self.dir.display();
+                return Err(err).wrap_err_with(|| format!("cannot read outbox at {dir}"));
+            }
+        };
+
+        let mut msgs = Vec::new();
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            if !path.extension().is_some_and(|ext| ext == "eml") {
+                continue;
+            }
+
+            let Some(id) = path.file_stem().and_then(|id| id.to_str()) else {
+                continue;
+            };
+
+            let msg = fs::read(&path)
+                .wrap_err_with(|| format!("cannot read queued message at {}", path.display()))?;
+
+            msgs.push(QueuedMessage::new(id.to_owned(), &msg, self.read_meta(id)));
+        }
+
+        msgs.sort_by(|a, b| (a.meta.queued_at, &a.id).cmp(&(b.meta.queued_at, &b.id)));
+        Ok(msgs)
+    }
+
+    /// Read the raw content of the given queued message.
+    pub fn read(&self, id: &str) -> Result<Vec<u8>> {
+        let path = self.path(id, "eml");
+
+        if !path.is_file() {
+            bail!("cannot find message {id} in the outbox");
+        }
+
+        fs::read(&path)
+            .wrap_err_with(|| format!("cannot read queued message at {}", path.display()))
+    }
+
+    /// Remove the given message from the outbox.
+    pub fn remove(&self, id: &str) -> Result<()> {
+        let path = self.path(id, "eml");
+        fs::remove_file(&path)
+            .wrap_err_with(|| format!("cannot remove queued message at {}", path.display()))?;
+
+        if let Err(err) = fs::remove_file(self.path(id, "json")) {
+            debug!("cannot remove metadata of queued message {id}: {err}");
+        }
+
+        Ok(())
+    }
+
+    /// Remove the whole outbox, including its queued messages.
+    pub fn remove_all(&self) -> Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                let dir = self.dir.display();
+                Err(err).wrap_err_with(|| format!("cannot remove outbox at {dir}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a failed attempt to send the given queued message.
+    pub fn record_error(&self, id: &str, err: &Report) -> Result<()> {
+        let mut meta = self.read_meta(id);
+        meta.attempts += 1;
+        meta.error = Some(format!("{err:#}"));
+        self.write_meta(id, &meta)
+    }
+
+    fn path(&self, id: &str, ext: &str) -> PathBuf {
+        self.dir.join(format!("{id}.{ext}"))
+    }
+
+    /// Read the metadata of the given queued message.
+    ///
+    /// Missing or invalid metadata is replaced by default metadata,
+    /// so that the message can still be sent.
+    fn read_meta(&self, id: &str) -> QueuedMessageMeta {
+        let path = self.path(id, "json");
+
+        let meta = fs::read(&path)
+            .map_err(Report::from)
+            .and_then(|meta| Ok(serde_json::from_slice(&meta)?));
+
+        meta.unwrap_or_else(|err| {
+            debug!("cannot read metadata of queued message {id}: {err}");
+            let queued_at = fs::metadata(self.path(id, "eml"))
+                .and_then(|meta| meta.modified())
+                .map(DateTime::from)
+                .unwrap_or_else(|_| Local::now());
+
+            QueuedMessageMeta {
+                queued_at,
+                ..Default::default()
+            }
+        })
+    }
+
+    fn write_meta(&self, id: &str, meta: &QueuedMessageMeta) -> Result<()> {
+        self.write(&format!("{id}.json"), &serde_json::to_vec_pretty(meta)?)
+    }
+
+    fn write(&self, name: &str, content: &[u8]) -> Result<()> {
+        let tmp = self.dir.join("tmp");
+        fs::create_dir_all(&tmp)
+            .wrap_err_with(|| format!("cannot create outbox at {}", self.dir.display()))?;
+
+        let tmp = tmp.join(name);
+        fs::write(&tmp, content)
+            .wrap_err_with(|| format!("cannot write outbox file at {}", tmp.display()))?;
+
+        let path = self.dir.join(name);
+        fs::rename(&tmp, &path)
+            .wrap_err_with(|| format!("cannot move outbox file to {}", path.display()))?;
+
+        Ok(())
+    }
+}
+
+/// The queue metadata of a message.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct QueuedMessageMeta {
+    /// When the message was queued.
+    pub queued_at: DateTime<Local>,
+    /// When the message should be sent, if scheduled.
+    #[serde(default)]
+    pub send_at: Option<DateTime<Local>>,
+    /// The number of failed attempts to send the message.
+    #[serde(default)]
+    pub attempts: u32,
+    /// The error of the last failed attempt.
+    pub error: Option<String>,
+}
+
+impl Default for QueuedMessageMeta {
+    fn default() -> Self {
+        Self {
+            queued_at: Local::now(),
+            send_at: None,
+            attempts: 0,
+            error: None,
+        }
+    }
+}
+
+/// A message of the outbox.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct QueuedMessage {
+    pub id: String,
+    pub to: String,
+    pub subject: String,
+    #[serde(flatten)]
+    pub meta: QueuedMessageMeta,
+}
+
+impl QueuedMessage {
+    /// Return `true` if the message should be sent at the given time.
+    pub fn is_due(&self, now: DateTime<Local>) -> bool {
+        self.meta.send_at.map_or(true, |send_at| send_at <= now)
+    }
+
+    fn new(id: String, msg: &[u8], meta: QueuedMessageMeta) -> Self {
+        let parsed = MessageParser::new().parse_headers(msg);
+
+        let to = parsed
+            .iter()
+            .flat_map(|msg| [msg.to(), msg.cc(), msg.bcc()])
+            .flatten()
+            .flat_map(Address::iter)
+            .filter_map(|addr| addr.address())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let subject = parsed
+            .as_ref()
+            .and_then(|msg| msg.subject())
+            .unwrap_or_default()
+            .to_owned();
+
+        Self {
+            id,
+            to,
+            subject,
+            meta,
+        }
+    }
+}
+
+/// Format the given time of the outbox.
+pub fn format(time: DateTime<Local>) -> String {
+    time.format("%Y-%m-%d %H:%M").to_string()
+}
+
+/// Send the given message, then save a copy to the sent folder.
+///
+/// Contrary to [`Backend::send_message_then_save_copy`], a copy that
+/// cannot be saved is only reported: the message has been sent, so it
+/// must not be queued again.
+pub async fn send(printer: &mut impl Printer, backend: &Backend, msg: &[u8]) -> Result<()> {
+    backend.send_message(msg).await?;
+
+    if backend.account_config.should_save_copy_sent_message() {
+        let flags = Flags::from_iter([Flag::Seen]);
+
+        if let Err(err) = backend.add_message_with_flags(SENT, msg, &flags).await {
+            warn!("message sent but cannot save copy to the sent folder: {err}");
+            debug!("{err:?}");
+            printer.log(format!(
+                "WARNING: message sent but cannot save copy to the sent folder: {err}\n"
+            ))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Queue the given message after a failed attempt to send it, and
+/// report the failure.
+///
+/// Returns the identifier of the queued message.
+pub fn queue_failed(
+    printer: &mut impl Printer,
+    outbox: &Outbox,
+    msg: &[u8],
+    err: &Report,
+) -> Result<String> {
+    let id = outbox.push_failed(msg, err)?;
+
+    warn!("cannot send message, queued in the outbox as {id}");
+    debug!("{err:?}");
+    printer.log(format!(
+        "WARNING: cannot send message, queued in the outbox as {id}: {err}\n"
+    ))?;
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::temp_dir, fs};
+
+    use chrono::{Local, TimeDelta};
+    use color_eyre::eyre::eyre;
+    use uuid::Uuid;
+
+    use super::{Outbox, QueuedMessageMeta};
+
+    const MSG: &[u8] = b"From: alice@localhost\r\n\
+                         To: bob@localhost\r\n\
+                         Cc: Carol <carol@localhost>\r\n\
+                         Subject: Hello\r\n\
+                         \r\n\
+                         Hello, world!\r\n";
+
+    fn outbox() -> Outbox {
+        let dir = temp_dir().join(format!("himalaya-outbox-{}", Uuid::new_v4()));
+        Outbox { dir }
+    }
+
+    #[test]
+    fn push_then_list() {
+        let outbox = outbox();
+        assert!(outbox.list().unwrap().is_empty());
+
+        let now = Local::now();
+        let later = now + TimeDelta::hours(1);
+
+        let meta = QueuedMessageMeta {
+            queued_at: now - TimeDelta::minutes(1),
+            ..Default::default()
+        };
+        let first = outbox
+            .push_with_meta(b"Subject: First\r\n\r\n", meta)
+            .unwrap();
+        let second = outbox.push(MSG, Some(later)).unwrap();
+
+        let msgs = outbox.list().unwrap();
+        let content = outbox.read(&second).unwrap();
+        outbox.remove_all().unwrap();
+
+        assert_eq!(content, MSG);
+        assert_eq!(msgs.len(), 2);
+
+        assert_eq!(msgs[0].id, first);
+        assert_eq!(msgs[0].to, "");
+        assert_eq!(msgs[0].subject, "First");
+        assert!(msgs[0].is_due(now));
+
+        assert_eq!(msgs[1].id, second);
+        assert_eq!(msgs[1].to, "bob@localhost, carol@localhost");
+        assert_eq!(msgs[1].subject, "Hello");
+        assert_eq!(msgs[1].meta.send_at, Some(later));
+        assert_eq!(msgs[1].meta.attempts, 0);
+        assert!(!msgs[1].is_due(now));
+        assert!(msgs[1].is_due(later));
+
+        assert!(!outbox.dir.exists());
+        assert!(outbox.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn record_errors() {
+        let outbox = outbox();
+
+        let id = outbox
+            .push_failed(MSG, &eyre!("connection refused"))
+            .unwrap();
+        let failed = outbox.list().unwrap();
+
+        let err = eyre!("connection reset").wrap_err("cannot send message");
+        outbox.record_error(&id, &err).unwrap();
+        let msgs = outbox.list().unwrap();
+        outbox.remove_all().unwrap();
+
+        assert_eq!(failed[0].meta.attempts, 1);
+        assert_eq!(failed[0].meta.error.as_deref(), Some("connection refused"));
+
+        assert_eq!(msgs[0].meta.attempts, 2);
+        let error = "cannot send message: connection reset";
+        assert_eq!(msgs[0].meta.error.as_deref(), Some(error));
+        assert_eq!(msgs[0].meta.queued_at, failed[0].meta.queued_at);
+    }
+
+    #[test]
+    fn list_without_metadata() {
+        let outbox = outbox();
+
+        let id = outbox.push(MSG, None).unwrap();
+        fs::write(outbox.path(&id, "json"), "invalid").unwrap();
+        let invalid = outbox.list().unwrap();
+
+        fs::remove_file(outbox.path(&id, "json")).unwrap();
+        let missing = outbox.list().unwrap();
+        outbox.remove_all().unwrap();
+
+        for msgs in [invalid, missing] {
+            assert_eq!(msgs.len(), 1);
+            assert_eq!(msgs[0].id, id);
+            assert_eq!(msgs[0].subject, "Hello");
+            assert_eq!(msgs[0].meta.send_at, None);
+            assert_eq!(msgs[0].meta.attempts, 0);
+        }
+    }
+
+    #[test]
+    fn remove() {
+        let outbox = outbox();
+
+        let id = outbox.push(MSG, None).unwrap();
+        let kept = outbox.push(MSG, None).unwrap();
+        outbox.remove(&id).unwrap();
+
+        let msgs = outbox.list().unwrap();
+        let meta_exists = outbox.path(&id, "json").exists();
+        let removed_again = outbox.remove(&id);
+        let read = outbox.read(&id);
+        outbox.remove_all().unwrap();
+
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].id, kept);
+        assert!(!meta_exists);
+        assert!(removed_again.is_err());
+        let err = read.unwrap_err().to_string();
+        assert_eq!(err, format!("cannot find message {id} in the outbox"));
+
+        // removing a missing outbox is not an error
+        outbox.remove_all().unwrap();
+    }
+}
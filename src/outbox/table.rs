@@ -0,0 +1,90 @@
+use std::fmt;
+
+use comfy_table::{presets, Cell, ContentArrangement, Row, Table};
+use serde::{Serialize, Serializer};
+
+use crate::{
+    outbox::{self, QueuedMessage},
+    table::{Column, TableConfig},
+};
+
+const COLUMNS: [Column; 6] = [
+    Column::left("id", "ID"),
+    Column::left("queued", "QUEUED"),
+    Column::left("scheduled", "SCHEDULED"),
+    Column::left("to", "TO"),
+    Column::left("subject", "SUBJECT"),
+    Column::left("error", "ERROR"),
+];
+
+/// The printable table of queued messages.
+pub struct OutboxTable {
+    msgs: Vec<QueuedMessage>,
+    width: Option<u16>,
+    table_config: TableConfig,
+}
+
+impl OutboxTable {
+    pub fn with_some_width(mut self, width: Option<u16>) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_table_config(mut self, config: TableConfig) -> Self {
+        self.table_config = config;
+        self
+    }
+}
+
+impl From<Vec<QueuedMessage>> for OutboxTable {
+    fn from(msgs: Vec<QueuedMessage>) -> Self {
+        Self {
+            msgs,
+            width: None,
+            table_config: Default::default(),
+        }
+    }
+}
+
+impl fmt::Display for OutboxTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Table::new();
+
+        table
+            .load_preset(presets::ASCII_MARKDOWN)
+            .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+            .add_rows(self.msgs.iter().map(|msg| {
+                let style = |key, cell| self.table_config.style(key, cell);
+
+                let queued = outbox::format(msg.meta.queued_at);
+                let send_at = msg.meta.send_at.map(outbox::format).unwrap_or_default();
+                let error = match &msg.meta.error {
+                    Some(err) => format!("({}) {err}", msg.meta.attempts),
+                    None => String::new(),
+                };
+
+                let mut row = Row::new();
+                row.max_height(1);
+                row.add_cell(style("id", Cell::new(&msg.id)));
+                row.add_cell(style("queued", Cell::new(queued)));
+                row.add_cell(style("scheduled", Cell::new(send_at)));
+                row.add_cell(style("to", Cell::new(&msg.to)));
+                row.add_cell(style("subject", Cell::new(&msg.subject)));
+                row.add_cell(style("error", Cell::new(error)));
+                row
+            }));
+
+        self.table_config.apply(&mut table, &COLUMNS, self.width);
+
+        writeln!(f)?;
+        write!(f, "{table}")?;
+        writeln!(f)?;
+        Ok(())
+    }
+}
+
+impl Serialize for OutboxTable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.msgs.serialize(serializer)
+    }
+}
@@ -0,0 +1,171 @@
+//! Module dedicated to printing.
+//!
+//! Commands print two kinds of output: the data they have been asked
+//! for (tables, messages, templates), and informational messages
+//! telling what happened (like "Message successfully sent!"). Both
+//! are printed to the standard output, but informational messages
+//! and logs are silenced in quiet mode, which leaves exit codes as
+//! the only success signal.
+//!
+//! Data can also be written to a file instead of the standard
+//! output, see [`StdoutPrinter::with_some_output_file`].
+
+use std::{
+    fmt, fs,
+    io::{stdout, Write},
+    path::PathBuf,
+};
+
+use color_eyre::{eyre::WrapErr, Result};
+use pimalaya_tui::terminal::cli::printer::{self, OutputFmt, Printer as _};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// The printer of commands.
+///
+/// Extends the printer shared with other Himalaya projects with
+/// informational messages.
+pub trait Printer: printer::Printer {
+    /// Print an informational message, like a success message.
+    ///
+    /// The message is shown right away, even without a line ending,
+    /// so that it can report a step before its result.
+    ///
+    /// Requested data should be printed using
+    /// [`out`](printer::Printer::out) instead, progress messages using
+    /// [`log`](printer::Printer::log), like warnings.
+    fn info<T: fmt::Display + Serialize>(&mut self, data: T) -> Result<()> {
+        self.out(data)
+    }
+
+    /// Print raw data, like a raw message, byte for byte.
+    fn out_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let mut stdout = stdout().lock();
+        stdout.write_all(bytes)?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+/// The printer writing to the standard output.
+pub struct StdoutPrinter {
+    printer: printer::StdoutPrinter,
+    output: OutputFmt,
+    quiet: bool,
+    output_file: Option<PathBuf>,
+    buffer: Vec<u8>,
+}
+
+impl StdoutPrinter {
+    pub fn new(output: OutputFmt) -> Self {
+        Self {
+            printer: printer::StdoutPrinter::new(output.clone()),
+            output,
+            quiet: false,
+            output_file: None,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Silence informational messages and logs.
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Write data to the given file instead of the standard output.
+    ///
+    /// Data is kept in memory, then written to the file by
+    /// [`StdoutPrinter::flush`]. Informational messages are still
+    /// printed to the standard output.
+    pub fn with_some_output_file(mut self, path: Option<PathBuf>) -> Self {
+        self.output_file = path;
+        self
+    }
+
+    /// Write data to the output file, if any.
+    ///
+    /// The file is written atomically: data is first written to a
+    /// temporary file next to it, which is then renamed. An existing
+    /// file is replaced.
+    pub fn flush(&mut self) -> Result<()> {
+        let Some(path) = &self.output_file else {
+            return Ok(());
+        };
+
+        let name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy();
+        let tmp = path.with_file_name(format!(".{name}.{}.tmp", Uuid::new_v4()));
+
+        let res = fs::write(&tmp, &self.buffer).and_then(|()| fs::rename(&tmp, path));
+
+        if let Err(err) = res {
+            let _ = fs::remove_file(&tmp);
+            return Err(err)
+                .wrap_err_with(|| format!("cannot write output file at {}", path.display()));
+        }
+
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl Default for StdoutPrinter {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl printer::Printer for StdoutPrinter {
+    fn out<T: fmt::Display + Serialize>(&mut self, data: T) -> Result<()> {
+        if self.output_file.is_none() {
+            return self.printer.out(data);
+        }
+
+        match self.output {
+            OutputFmt::Plain => write!(self.buffer, "{data}")?,
+            OutputFmt::Json => serde_json::to_writer(&mut self.buffer, &data)
+                .wrap_err("cannot write JSON to output file")?,
+        }
+
+        Ok(())
+    }
+
+    fn log<T: fmt::Display + Serialize>(&mut self, data: T) -> Result<()> {
+        if self.quiet {
+            return Ok(());
+        }
+
+        self.printer.log(data)
+    }
+
+    fn is_json(&self) -> bool {
+        self.printer.is_json()
+    }
+}
+
+impl Printer for StdoutPrinter {
+    fn info<T: fmt::Display + Serialize>(&mut self, data: T) -> Result<()> {
+        if self.quiet {
+            return Ok(());
+        }
+
+        self.printer.out(data)?;
+        stdout().flush()?;
+        Ok(())
+    }
+
+    fn out_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        if self.output_file.is_none() {
+            let mut stdout = stdout().lock();
+            stdout.write_all(bytes)?;
+            stdout.flush()?;
+            return Ok(());
+        }
+
+        self.buffer.extend_from_slice(bytes);
+        Ok(())
+    }
+}
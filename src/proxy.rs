@@ -0,0 +1,696 @@
+//! Module dedicated to proxies.
+//!
+//! SMTP connections can go through a SOCKS5 proxy
+//! (RFC 1928) or through an HTTP proxy supporting the CONNECT
+//! method. The tunnel is established before the TLS handshake, so
+//! that the proxy only sees encrypted traffic. With the `socks5h` scheme, the
+//! destination host name is resolved by the proxy rather than
+//! locally, which avoids leaking DNS requests (for example when
+//! routing through Tor).
+
+use std::{any::Any, error, fmt, io};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use email::AnyError;
+use secret::Secret;
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{lookup_host, TcpStream},
+};
+use tracing::debug;
+use url::Url;
+
+/// The default port of SOCKS5 proxies.
+const SOCKS5_PORT: u16 = 1080;
+
+/// The maximum size of the response of an HTTP proxy to the CONNECT
+/// request.
+const MAX_HTTP_RESPONSE_SIZE: usize = 8192;
+
+/// The kind of proxy.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProxyKind {
+    /// A SOCKS5 proxy, resolving destination host names locally.
+    Socks5,
+    /// A SOCKS5 proxy, resolving destination host names itself.
+    Socks5h,
+    /// An HTTP proxy supporting the CONNECT method.
+    Http,
+}
+
+impl fmt::Display for ProxyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Socks5 => write!(f, "socks5"),
+            Self::Socks5h => write!(f, "socks5h"),
+            Self::Http => write!(f, "http"),
+        }
+    }
+}
+
+/// The proxy configuration of the SMTP sending backend.
+///
+/// The proxy is given either as a URL (`message.send.backend.proxy =
+/// "socks5h://127.0.0.1:9050"`), or as a table with a `url`, an
+/// optional `login` and an optional `passwd` taken from a raw
+/// string, a command or a keyring entry.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(try_from = "ProxyConfigDef")]
+pub struct ProxyConfig {
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    pub login: Option<String>,
+    pub passwd: Secret,
+}
+
+/// The proxy configuration, as found in the TOML configuration.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProxyConfigDef {
+    Url(String),
+    Table {
+        url: String,
+        login: Option<String>,
+        #[serde(default)]
+        passwd: Secret,
+    },
+}
+
+impl TryFrom<ProxyConfigDef> for ProxyConfig {
+    type Error = String;
+
+    fn try_from(def: ProxyConfigDef) -> Result<Self, Self::Error> {
+        let (url, login, passwd) = match def {
+            ProxyConfigDef::Url(url) => (url, None, Secret::default()),
+            ProxyConfigDef::Table { url, login, passwd } => (url, login, passwd),
+        };
+
+        let parsed = Url::parse(&url).map_err(|err| format!("invalid proxy URL {url}: {err}"))?;
+
+        let kind = match parsed.scheme() {
+            "socks5" => ProxyKind::Socks5,
+            "socks5h" => ProxyKind::Socks5h,
+            "http" => ProxyKind::Http,
+            scheme => {
+                return Err(format!(
+                    "invalid proxy URL {url}: unsupported scheme {scheme}, \
+                     use socks5, socks5h or http"
+                ))
+            }
+        };
+
+        let host = match parsed.host_str() {
+            Some(host) if !host.is_empty() => host.trim_matches(['[', ']']).to_owned(),
+            _ => return Err(format!("invalid proxy URL {url}: missing host")),
+        };
+
+        let port = match kind {
+            ProxyKind::Http => parsed.port_or_known_default().unwrap_or(80),
+            _ => parsed.port().unwrap_or(SOCKS5_PORT),
+        };
+
+        let login = login.or_else(|| Some(parsed.username().to_owned()).filter(|l| !l.is_empty()));
+
+        let passwd = match parsed.password() {
+            Some(raw) if passwd.is_empty() => Secret::new_raw(raw),
+            _ => passwd,
+        };
+
+        Ok(Self {
+            kind,
+            host,
+            port,
+            login,
+            passwd,
+        })
+    }
+}
+
+impl fmt::Display for ProxyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}:{}", self.kind, self.host, self.port)
+    }
+}
+
+/// The error of a connection through a proxy.
+#[derive(Debug)]
+pub enum ProxyError {
+    /// The proxy itself cannot be reached.
+    Unreachable(String, io::Error),
+    /// The password of the proxy cannot be retrieved.
+    GetPasswd(String, secret::Error),
+    /// The proxy refused the credentials.
+    Auth(String, String),
+    /// The proxy answered in an unexpected way.
+    Protocol(String, String),
+    /// The proxy cannot reach the destination.
+    Destination(String, String, String),
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unreachable(proxy, err) => write!(f, "cannot connect to proxy {proxy}: {err}"),
+            Self::GetPasswd(proxy, err) => {
+                write!(f, "cannot get password of proxy {proxy}: {err}")
+            }
+            Self::Auth(proxy, reason) => {
+                write!(f, "cannot authenticate to proxy {proxy}: {reason}")
+            }
+            Self::Protocol(proxy, reason) => {
+                write!(f, "unexpected answer from proxy {proxy}: {reason}")
+            }
+            Self::Destination(dest, proxy, reason) => {
+                write!(f, "cannot reach {dest} through proxy {proxy}: {reason}")
+            }
+        }
+    }
+}
+
+impl error::Error for ProxyError {}
+
+impl AnyError for ProxyError {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Open a TCP connection to the given destination through the given
+/// proxy.
+///
+/// The returned stream is a tunnel to the destination, ready for the
+/// TLS handshake or the greeting of the server.
+pub async fn connect(config: &ProxyConfig, host: &str, port: u16) -> Result<TcpStream, ProxyError> {
+    let proxy = config.to_string();
+    debug!("connecting to {host}:{port} through proxy {proxy}");
+
+    let mut stream = TcpStream::connect((config.host.as_str(), config.port))
+        .await
+        .map_err(|err| ProxyError::Unreachable(proxy.clone(), err))?;
+
+    let passwd = match &config.login {
+        Some(_) if !config.passwd.is_empty() => config
+            .passwd
+            .get()
+            .await
+            .map_err(|err| ProxyError::GetPasswd(proxy.clone(), err))?,
+        _ => String::new(),
+    };
+
+    let credentials = config
+        .login
+        .as_deref()
+        .map(|login| (login, passwd.as_str()));
+
+    match config.kind {
+        ProxyKind::Socks5 | ProxyKind::Socks5h => {
+            socks5::connect(&mut stream, config, credentials, host, port).await?
+        }
+        ProxyKind::Http => http::connect(&mut stream, config, credentials, host, port).await?,
+    }
+
+    Ok(stream)
+}
+
+/// Build the error of an I/O failure occurring while talking to the
+/// given proxy.
+fn protocol_error(config: &ProxyConfig) -> impl Fn(io::Error) -> ProxyError + '_ {
+    move |err| ProxyError::Protocol(config.to_string(), err.to_string())
+}
+
+mod socks5 {
+    use super::*;
+
+    const VERSION: u8 = 5;
+    const NO_AUTH: u8 = 0;
+    const USER_PASSWD_AUTH: u8 = 2;
+    const NO_ACCEPTABLE_AUTH: u8 = 0xff;
+    const CONNECT: u8 = 1;
+    const IPV4: u8 = 1;
+    const DOMAIN: u8 = 3;
+    const IPV6: u8 = 4;
+
+    /// Establish a SOCKS5 tunnel to the given destination.
+    pub async fn connect(
+        stream: &mut TcpStream,
+        config: &ProxyConfig,
+        credentials: Option<(&str, &str)>,
+        host: &str,
+        port: u16,
+    ) -> Result<(), ProxyError> {
+        let proxy = config.to_string();
+        let io_err = protocol_error(config);
+
+        let methods: &[u8] = match credentials {
+            Some(_) => &[NO_AUTH, USER_PASSWD_AUTH],
+            None => &[NO_AUTH],
+        };
+
+        let mut greeting = vec![VERSION, methods.len() as u8];
+        greeting.extend(methods);
+        stream.write_all(&greeting).await.map_err(&io_err)?;
+
+        let mut choice = [0; 2];
+        stream.read_exact(&mut choice).await.map_err(&io_err)?;
+
+        match (choice, credentials) {
+            ([VERSION, NO_AUTH], _) => (),
+            ([VERSION, USER_PASSWD_AUTH], Some((login, passwd))) => {
+                authenticate(stream, config, login, passwd).await?
+            }
+            ([VERSION, NO_ACCEPTABLE_AUTH], None) => {
+                let reason = "authentication required, set the proxy login and password";
+                return Err(ProxyError::Auth(proxy, reason.into()));
+            }
+            ([VERSION, NO_ACCEPTABLE_AUTH], Some(_)) => {
+                let reason = "no supported authentication method";
+                return Err(ProxyError::Auth(proxy, reason.into()));
+            }
+            ([version, method], _) => {
+                let reason = format!("unexpected version {version} or method {method}");
+                return Err(ProxyError::Protocol(proxy, reason));
+            }
+        }
+
+        let mut request = vec![VERSION, CONNECT, 0];
+
+        if config.kind == ProxyKind::Socks5h {
+            if host.len() > u8::MAX as usize {
+                let reason = format!("host name {host} is too long");
+                return Err(ProxyError::Protocol(proxy, reason));
+            }
+
+            request.extend([DOMAIN, host.len() as u8]);
+            request.extend(host.as_bytes());
+        } else {
+            let dest = format!("{host}:{port}");
+            let addr = lookup_host((host, port))
+                .await
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .ok_or_else(|| {
+                    let reason = String::from("cannot resolve host name locally");
+                    ProxyError::Destination(dest, proxy.clone(), reason)
+                })?;
+
+            match addr.ip() {
+                std::net::IpAddr::V4(ip) => {
+                    request.push(IPV4);
+                    request.extend(ip.octets());
+                }
+                std::net::IpAddr::V6(ip) => {
+                    request.push(IPV6);
+                    request.extend(ip.octets());
+                }
+            }
+        }
+
+        request.extend(port.to_be_bytes());
+        stream.write_all(&request).await.map_err(&io_err)?;
+
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply).await.map_err(&io_err)?;
+
+        if reply[0] != VERSION {
+            let reason = format!("unexpected version {}", reply[0]);
+            return Err(ProxyError::Protocol(proxy, reason));
+        }
+
+        if reply[1] != 0 {
+            let dest = format!("{host}:{port}");
+            let reason = match reply[1] {
+                1 => "general failure",
+                2 => "connection not allowed by ruleset",
+                3 => "network unreachable",
+                4 => "host unreachable",
+                5 => "connection refused",
+                6 => "TTL expired",
+                7 => "command not supported",
+                8 => "address type not supported",
+                _ => "unknown error",
+            };
+            return Err(ProxyError::Destination(dest, proxy, reason.into()));
+        }
+
+        // the bound address is not used, but still needs to be
+        // consumed before the tunnel can be used
+        let len = match reply[3] {
+            IPV4 => 4,
+            IPV6 => 16,
+            DOMAIN => stream.read_u8().await.map_err(&io_err)? as usize,
+            kind => {
+                let reason = format!("unexpected address type {kind}");
+                return Err(ProxyError::Protocol(proxy, reason));
+            }
+        };
+
+        let mut bound = vec![0; len + 2];
+        stream.read_exact(&mut bound).await.map_err(&io_err)?;
+
+        Ok(())
+    }
+
+    /// Authenticate to the SOCKS5 proxy using the given login and
+    /// password (RFC 1929).
+    async fn authenticate(
+        stream: &mut TcpStream,
+        config: &ProxyConfig,
+        login: &str,
+        passwd: &str,
+    ) -> Result<(), ProxyError> {
+        let proxy = config.to_string();
+        let io_err = protocol_error(config);
+
+        if login.len() > u8::MAX as usize || passwd.len() > u8::MAX as usize {
+            let reason = "login or password too long";
+            return Err(ProxyError::Auth(proxy, reason.into()));
+        }
+
+        let mut request = vec![1, login.len() as u8];
+        request.extend(login.as_bytes());
+        request.push(passwd.len() as u8);
+        request.extend(passwd.as_bytes());
+        stream.write_all(&request).await.map_err(&io_err)?;
+
+        let mut reply = [0; 2];
+        stream.read_exact(&mut reply).await.map_err(&io_err)?;
+
+        if reply[1] != 0 {
+            let reason = "invalid login or password";
+            return Err(ProxyError::Auth(proxy, reason.into()));
+        }
+
+        Ok(())
+    }
+}
+
+mod http {
+    use super::*;
+
+    /// Establish an HTTP CONNECT tunnel to the given destination.
+    pub async fn connect(
+        stream: &mut TcpStream,
+        config: &ProxyConfig,
+        credentials: Option<(&str, &str)>,
+        host: &str,
+        port: u16,
+    ) -> Result<(), ProxyError> {
+        let proxy = config.to_string();
+        let io_err = protocol_error(config);
+
+        let dest = match host.contains(':') {
+            true => format!("[{host}]:{port}"),
+            false => format!("{host}:{port}"),
+        };
+
+        let mut request = format!("CONNECT {dest} HTTP/1.1\r\nHost: {dest}\r\n");
+
+        if let Some((login, passwd)) = credentials {
+            let token = STANDARD.encode(format!("{login}:{passwd}"));
+            request.push_str(&format!("Proxy-Authorization: Basic {token}\r\n"));
+        }
+
+        request.push_str("\r\n");
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(&io_err)?;
+
+        // the response is read byte by byte, so that nothing after
+        // its end is consumed from the tunnel
+        let mut response = Vec::new();
+
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAX_HTTP_RESPONSE_SIZE {
+                let reason = String::from("response too large");
+                return Err(ProxyError::Protocol(proxy, reason));
+            }
+
+            response.push(stream.read_u8().await.map_err(&io_err)?);
+        }
+
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines().next().unwrap_or_default();
+        let mut parts = status_line.splitn(3, ' ');
+
+        let status: u16 = match (parts.next(), parts.next()) {
+            (Some(version), Some(status)) if version.starts_with("HTTP/") => {
+                status.parse().unwrap_or_default()
+            }
+            _ => 0,
+        };
+
+        let reason = status_line.to_owned();
+
+        match status {
+            200..=299 => Ok(()),
+            407 => Err(ProxyError::Auth(proxy, reason)),
+            0 => Err(ProxyError::Protocol(proxy, reason)),
+            _ => Err(ProxyError::Destination(dest, proxy, reason)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+
+    use secret::Secret;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        task::JoinHandle,
+    };
+
+    use super::{connect, ProxyConfig, ProxyError, ProxyKind};
+
+    /// Start a fake proxy accepting one connection, handled by the
+    /// given script.
+    async fn start_proxy<F, Fut>(script: F) -> (u16, JoinHandle<()>)
+    where
+        F: FnOnce(TcpStream) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            script(stream).await;
+        });
+
+        (port, handle)
+    }
+
+    fn proxy_config(kind: ProxyKind, port: u16, login: Option<&str>) -> ProxyConfig {
+        ProxyConfig {
+            kind,
+            host: String::from("127.0.0.1"),
+            port,
+            login: login.map(ToOwned::to_owned),
+            passwd: Secret::new_raw("secret"),
+        }
+    }
+
+    async fn read_vec(stream: &mut TcpStream, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        stream.read_exact(&mut buf).await.unwrap();
+        buf
+    }
+
+    /// Read the HTTP request of the given stream, up to the empty
+    /// line.
+    async fn read_http_request(stream: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+
+        while !request.ends_with(b"\r\n\r\n") {
+            request.push(stream.read_u8().await.unwrap());
+        }
+
+        String::from_utf8(request).unwrap()
+    }
+
+    #[test]
+    fn parse_proxy_urls() {
+        let parse = |url: &str| ProxyConfig::try_from(super::ProxyConfigDef::Url(url.into()));
+
+        let config = parse("socks5h://127.0.0.1").unwrap();
+        assert_eq!(config.kind, ProxyKind::Socks5h);
+        assert_eq!(config.port, 1080);
+        assert_eq!(config.login, None);
+
+        let config = parse("http://me:secret@[::1]").unwrap();
+        assert_eq!(config.kind, ProxyKind::Http);
+        assert_eq!(config.host, "::1");
+        assert_eq!(config.port, 80);
+        assert_eq!(config.login.as_deref(), Some("me"));
+        assert_eq!(config.to_string(), "http://::1:80");
+
+        let err = parse("https://proxy.example.org").unwrap_err();
+        assert!(err.contains("unsupported scheme https"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn tunnel_through_socks5h_proxy() {
+        let (port, proxy) = start_proxy(|mut stream| async move {
+            assert_eq!(read_vec(&mut stream, 3).await, [5, 1, 0]);
+            stream.write_all(&[5, 0]).await.unwrap();
+
+            let host = b"smtp.example.org";
+            let mut request = vec![5, 1, 0, 3, host.len() as u8];
+            request.extend(host);
+            request.extend(587u16.to_be_bytes());
+            assert_eq!(read_vec(&mut stream, request.len()).await, request);
+
+            stream
+                .write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+            stream.write_all(b"220 ready\r\n").await.unwrap();
+        })
+        .await;
+
+        let config = proxy_config(ProxyKind::Socks5h, port, None);
+        let mut stream = connect(&config, "smtp.example.org", 587).await.unwrap();
+
+        // the tunnel starts right after the reply of the proxy
+        let mut greeting = String::new();
+        stream.read_to_string(&mut greeting).await.unwrap();
+        assert_eq!(greeting, "220 ready\r\n");
+
+        proxy.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn authenticate_to_socks5_proxy() {
+        let (port, proxy) = start_proxy(|mut stream| async move {
+            assert_eq!(read_vec(&mut stream, 4).await, [5, 2, 0, 2]);
+            stream.write_all(&[5, 2]).await.unwrap();
+
+            assert_eq!(read_vec(&mut stream, 2).await, [1, 2]);
+            assert_eq!(read_vec(&mut stream, 3).await, b"me\x06");
+            assert_eq!(read_vec(&mut stream, 6).await, b"secret");
+            stream.write_all(&[1, 0]).await.unwrap();
+
+            // the destination is resolved locally with socks5
+            let request = [5, 1, 0, 1, 127, 0, 0, 1, 0, 25];
+            assert_eq!(read_vec(&mut stream, request.len()).await, request);
+            stream.write_all(&[5, 0, 0, 3, 4]).await.unwrap();
+            stream.write_all(b"host\0\0").await.unwrap();
+        })
+        .await;
+
+        let config = proxy_config(ProxyKind::Socks5, port, Some("me"));
+        connect(&config, "127.0.0.1", 25).await.unwrap();
+
+        proxy.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn report_socks5_errors() {
+        let (port, proxy) = start_proxy(|mut stream| async move {
+            read_vec(&mut stream, 3).await;
+            stream.write_all(&[5, 0xff]).await.unwrap();
+        })
+        .await;
+
+        let config = proxy_config(ProxyKind::Socks5h, port, None);
+        let err = connect(&config, "smtp.example.org", 587).await.unwrap_err();
+        assert!(matches!(err, ProxyError::Auth(..)), "{err}");
+        assert!(err.to_string().contains("authentication required"), "{err}");
+        proxy.await.unwrap();
+
+        let (port, proxy) = start_proxy(|mut stream| async move {
+            read_vec(&mut stream, 3).await;
+            stream.write_all(&[5, 0]).await.unwrap();
+            read_vec(&mut stream, 7 + "smtp.example.org".len()).await;
+            stream.write_all(&[5, 5, 0, 1]).await.unwrap();
+        })
+        .await;
+
+        let config = proxy_config(ProxyKind::Socks5h, port, None);
+        let err = connect(&config, "smtp.example.org", 587).await.unwrap_err();
+        let expected = format!(
+            "cannot reach smtp.example.org:587 through proxy socks5h://127.0.0.1:{port}: \
+             connection refused"
+        );
+        assert_eq!(err.to_string(), expected);
+        proxy.await.unwrap();
+
+        let (port, proxy) = start_proxy(|mut stream| async move {
+            read_vec(&mut stream, 4).await;
+            stream.write_all(&[5, 2]).await.unwrap();
+            read_vec(&mut stream, 11).await;
+            stream.write_all(&[1, 1]).await.unwrap();
+        })
+        .await;
+
+        let config = proxy_config(ProxyKind::Socks5h, port, Some("me"));
+        let err = connect(&config, "smtp.example.org", 587).await.unwrap_err();
+        assert!(matches!(err, ProxyError::Auth(..)), "{err}");
+        proxy.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn tunnel_through_http_proxy() {
+        let (port, proxy) = start_proxy(|mut stream| async move {
+            let request = read_http_request(&mut stream).await;
+            let expected = [
+                "CONNECT smtp.example.org:465 HTTP/1.1",
+                "Host: smtp.example.org:465",
+                "Proxy-Authorization: Basic bWU6c2VjcmV0",
+                "",
+                "",
+            ];
+            assert_eq!(request, expected.join("\r\n"));
+
+            let response = "HTTP/1.1 200 Connection established\r\n\r\n220 ready\r\n";
+            stream.write_all(response.as_bytes()).await.unwrap();
+        })
+        .await;
+
+        let config = proxy_config(ProxyKind::Http, port, Some("me"));
+        let mut stream = connect(&config, "smtp.example.org", 465).await.unwrap();
+
+        let mut greeting = String::new();
+        stream.read_to_string(&mut greeting).await.unwrap();
+        assert_eq!(greeting, "220 ready\r\n");
+
+        proxy.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn report_http_errors() {
+        for (response, expected) in [
+            ("HTTP/1.1 407 Proxy Authentication Required", "Auth"),
+            ("HTTP/1.1 502 Bad Gateway", "Destination"),
+            ("SSH-2.0-OpenSSH", "Protocol"),
+        ] {
+            let (port, proxy) = start_proxy(move |mut stream| async move {
+                read_http_request(&mut stream).await;
+                let response = format!("{response}\r\n\r\n");
+                stream.write_all(response.as_bytes()).await.unwrap();
+            })
+            .await;
+
+            let config = proxy_config(ProxyKind::Http, port, None);
+            let err = connect(&config, "::1", 25).await.unwrap_err();
+            let kind = format!("{err:?}");
+            assert!(kind.starts_with(expected), "{response}: {kind}");
+            proxy.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn report_unreachable_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let config = proxy_config(ProxyKind::Socks5, port, None);
+        let err = connect(&config, "127.0.0.1", 25).await.unwrap_err();
+        assert!(matches!(err, ProxyError::Unreachable(..)), "{err}");
+    }
+}
@@ -0,0 +1,694 @@
+use std::{
+    collections::HashMap,
+    env,
+    io::{stdout, IsTerminal},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use comfy_table::{Attribute, Cell, CellAlignment, Color, ColumnConstraint, Row, Table, Width};
+use serde::{de::Error as _, Deserialize, Deserializer};
+use toml::Value;
+
+/// Whether colors have been disabled from the command line.
+static NO_COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Disable colors and attributes of all tables.
+pub fn disable_colors() {
+    NO_COLOR.store(true, Ordering::Relaxed);
+}
+
+/// Whether tables are written somewhere else than the standard
+/// output from the command line.
+static NO_TTY: AtomicBool = AtomicBool::new(false);
+
+/// Render all tables as if the standard output was not a terminal,
+/// for example when they are written to a file.
+pub fn disable_tty() {
+    NO_TTY.store(true, Ordering::Relaxed);
+}
+
+/// Return `true` if tables are rendered for a terminal.
+fn is_tty() -> bool {
+    !NO_TTY.load(Ordering::Relaxed) && stdout().is_terminal()
+}
+
+/// Return `true` if colors are disabled, either from the command line
+/// or from a non-empty `NO_COLOR` environment variable.
+///
+/// See <https://no-color.org>.
+pub fn colors_disabled() -> bool {
+    NO_COLOR.load(Ordering::Relaxed) || env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+/// The table configuration shared by all listings.
+///
+/// Options are indexed by column key, which is the lowercase version
+/// of the default column header (`id`, `subject`, `name`, etc).
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TableConfig {
+    /// Override the alignment of columns.
+    #[serde(default)]
+    pub align: HashMap<String, ColumnAlignment>,
+
+    /// Override the header of columns.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+
+    /// Override the width policy of columns.
+    #[serde(default)]
+    pub widths: HashMap<String, ColumnWidth>,
+
+    /// The maximum width of tables when the standard output is not a
+    /// terminal.
+    ///
+    /// Tables fit the width of the terminal, and are not bounded when
+    /// the output is piped, unless this option is set.
+    pub piped_width: Option<u16>,
+
+    /// Override the colors and attributes of tables.
+    #[serde(default)]
+    pub colors: TableColorsConfig,
+}
+
+impl TableConfig {
+    /// Set up the header, the alignment and the width of the given
+    /// table columns.
+    ///
+    /// This function should be called once all rows have been added
+    /// to the table, otherwise columns may not exist yet. The given
+    /// maximum width, from the command line, takes precedence over the
+    /// width of the terminal and over the piped width.
+    pub fn apply(&self, table: &mut Table, columns: &[Column], width: Option<u16>) {
+        if NO_TTY.load(Ordering::Relaxed) {
+            table.force_no_tty();
+        }
+
+        if let Some(width) = self.piped_width {
+            if !is_tty() {
+                table.set_width(width);
+            }
+        }
+
+        if let Some(width) = width {
+            table.set_width(width);
+        }
+
+        // styling is already disabled when stdout is not a terminal
+        if colors_disabled() {
+            // the terminal width would be lost as well
+            if let Some(width) = table.width() {
+                table.set_width(width);
+            }
+
+            table.force_no_tty();
+        }
+
+        let header = columns.iter().map(|col| {
+            let header = self.headers.get(col.key).map(String::as_str);
+            let cell = Cell::new(header.unwrap_or(col.header));
+
+            match &self.colors.header {
+                Some(style) => style.apply(cell),
+                None => cell,
+            }
+        });
+
+        let mut header = Row::from(header.collect::<Vec<_>>());
+        header.max_height(1);
+        table.set_header(header);
+
+        for (i, col) in columns.iter().enumerate() {
+            let align = match self.align.get(col.key) {
+                Some(align) => CellAlignment::from(*align),
+                None => col.align,
+            };
+
+            let width = match self.widths.get(col.key) {
+                Some(width) => *width,
+                None => col.width,
+            };
+
+            if let Some(column) = table.column_mut(i) {
+                column.set_cell_alignment(align);
+
+                if let Some(constraint) = width.to_constraint() {
+                    column.set_constraint(constraint);
+                }
+            }
+        }
+
+        shrink(table);
+    }
+}
+
+impl TableConfig {
+    /// Apply the configured style of the given column to the given
+    /// cell.
+    pub fn style(&self, key: &str, cell: Cell) -> Cell {
+        match self.colors.columns.get(key) {
+            Some(style) => style.apply(cell),
+            None => cell,
+        }
+    }
+
+    /// Apply the configured styles of the given envelope column to the
+    /// given cell.
+    ///
+    /// Unseen rows are bold by default. Styles of unseen and flagged
+    /// rows are applied after the style of the column.
+    pub fn style_envelope(&self, key: &str, cell: Cell, unseen: bool, flagged: bool) -> Cell {
+        let mut cell = self.style(key, cell);
+
+        if unseen {
+            cell = match &self.colors.unseen {
+                Some(style) => style.apply(cell),
+                None => cell.add_attribute(Attribute::Bold),
+            };
+        }
+
+        if flagged {
+            if let Some(style) = &self.colors.flagged {
+                cell = style.apply(cell);
+            }
+        }
+
+        cell
+    }
+
+    /// Apply the configured style of section rows to the given cell.
+    ///
+    /// Section rows are bold by default.
+    pub fn style_section(&self, cell: Cell) -> Cell {
+        match &self.colors.section {
+            Some(style) => style.apply(cell),
+            None => cell.add_attribute(Attribute::Bold),
+        }
+    }
+}
+
+/// The minimum width of flexible columns under which columns fitting
+/// their content shrink as well.
+const MIN_FLEXIBLE_WIDTH: u16 = 10;
+
+/// Let columns fitting their content shrink when the table is too
+/// narrow to give flexible columns [`MIN_FLEXIBLE_WIDTH`] each.
+///
+/// Otherwise flexible columns would take the remaining width, down to
+/// one character, and wrap letter by letter.
+fn shrink(table: &mut Table) {
+    let Some(width) = table.width() else {
+        return;
+    };
+
+    let contents = table.column_max_content_widths();
+    let mut rigid = 0;
+    let mut flexible = 0;
+
+    for (column, content) in table.column_iter().zip(&contents) {
+        match column.constraint() {
+            Some(ColumnConstraint::ContentWidth) => rigid += *content,
+            // fixed widths include the padding
+            Some(ColumnConstraint::Absolute(Width::Fixed(width))) => {
+                rigid += width.saturating_sub(2)
+            }
+            _ => flexible += 1,
+        }
+    }
+
+    // each column has one space of padding on both sides and one
+    // border on its left, plus the last border of the table
+    let borders = 3 * contents.len() as u16 + 1;
+
+    if flexible == 0 || rigid + borders + flexible * MIN_FLEXIBLE_WIDTH <= width {
+        return;
+    }
+
+    for column in table.column_iter_mut() {
+        if let Some(ColumnConstraint::ContentWidth) = column.constraint() {
+            column.remove_constraint();
+        }
+    }
+}
+
+/// The colors configuration of tables.
+///
+/// Columns are indexed by key, like the other table options.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TableColorsConfig {
+    pub header: Option<Style>,
+    pub unseen: Option<Style>,
+    pub flagged: Option<Style>,
+    pub section: Option<Style>,
+    pub columns: HashMap<String, Style>,
+}
+
+impl<'de> Deserialize<'de> for TableColorsConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let table = toml::Table::deserialize(deserializer)?;
+        let mut config = Self::default();
+
+        for (key, value) in table {
+            let invalid = |err| D::Error::custom(format!("invalid ui.table.colors.{key}: {err}"));
+
+            match key.as_str() {
+                "header" => config.header = Some(Style::try_from(value).map_err(invalid)?),
+                "unseen" => config.unseen = Some(Style::try_from(value).map_err(invalid)?),
+                "flagged" => config.flagged = Some(Style::try_from(value).map_err(invalid)?),
+                "section" => config.section = Some(Style::try_from(value).map_err(invalid)?),
+                "columns" => {
+                    let Value::Table(columns) = value else {
+                        return Err(invalid(String::from("expected a table")));
+                    };
+
+                    for (column, value) in columns {
+                        let style = Style::try_from(value).map_err(|err| {
+                            D::Error::custom(format!(
+                                "invalid ui.table.colors.columns.{column}: {err}"
+                            ))
+                        })?;
+
+                        config.columns.insert(column, style);
+                    }
+                }
+                _ => {
+                    return Err(D::Error::custom(format!(
+                        "unknown key ui.table.colors.{key}, expected one of \
+                         header, unseen, flagged, section or columns"
+                    )));
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// The style of a table element.
+///
+/// A style is either a color name, which sets the foreground color,
+/// or a table with optional `fg`, `bg` and `attributes` keys.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Style {
+    /// Apply the style to the given cell.
+    pub fn apply(&self, mut cell: Cell) -> Cell {
+        if let Some(fg) = self.fg {
+            cell = cell.fg(fg);
+        }
+
+        if let Some(bg) = self.bg {
+            cell = cell.bg(bg);
+        }
+
+        cell.add_attributes(self.attributes.clone())
+    }
+}
+
+impl TryFrom<Value> for Style {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let table = match value {
+            Value::String(color) => {
+                return Ok(Self {
+                    fg: Some(parse_color(&color)?),
+                    ..Default::default()
+                });
+            }
+            Value::Table(table) => table,
+            _ => return Err(String::from("expected a color name or a table")),
+        };
+
+        let mut style = Self::default();
+
+        for (key, value) in table {
+            match (key.as_str(), value) {
+                ("fg", Value::String(color)) => style.fg = Some(parse_color(&color)?),
+                ("bg", Value::String(color)) => style.bg = Some(parse_color(&color)?),
+                ("attributes", Value::Array(attrs)) => {
+                    for attr in attrs {
+                        let Value::String(attr) = attr else {
+                            return Err(String::from("expected attribute names"));
+                        };
+
+                        style.attributes.push(parse_attribute(&attr)?);
+                    }
+                }
+                ("fg" | "bg", _) => return Err(format!("expected a color name for {key}")),
+                ("attributes", _) => return Err(String::from("expected a list of attributes")),
+                _ => {
+                    return Err(format!(
+                        "unknown key {key}, expected one of fg, bg or attributes"
+                    ));
+                }
+            }
+        }
+
+        Ok(style)
+    }
+}
+
+/// Parse the given ANSI color name.
+///
+/// Both `default` and `none` stand for the default color of the
+/// terminal.
+fn parse_color(name: &str) -> Result<Color, String> {
+    let color = match name.to_lowercase().as_str() {
+        "default" | "none" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        "grey" | "gray" => Color::Grey,
+        "dark-grey" | "dark-gray" => Color::DarkGrey,
+        "dark-red" => Color::DarkRed,
+        "dark-green" => Color::DarkGreen,
+        "dark-yellow" => Color::DarkYellow,
+        "dark-blue" => Color::DarkBlue,
+        "dark-magenta" => Color::DarkMagenta,
+        "dark-cyan" => Color::DarkCyan,
+        _ => {
+            return Err(format!(
+                "unknown color {name:?}, expected one of black, red, green, yellow, \
+                 blue, magenta, cyan, white, grey, their dark- variant, default or none"
+            ));
+        }
+    };
+
+    Ok(color)
+}
+
+/// Parse the given text attribute name.
+fn parse_attribute(name: &str) -> Result<Attribute, String> {
+    let attr = match name.to_lowercase().as_str() {
+        "bold" => Attribute::Bold,
+        "dim" => Attribute::Dim,
+        "italic" => Attribute::Italic,
+        "underlined" => Attribute::Underlined,
+        "reverse" => Attribute::Reverse,
+        _ => {
+            return Err(format!(
+                "unknown attribute {name:?}, expected one of bold, dim, italic, \
+                 underlined or reverse"
+            ));
+        }
+    };
+
+    Ok(attr)
+}
+
+/// The column declaration.
+#[derive(Clone, Copy, Debug)]
+pub struct Column {
+    /// The key used to customize the column from the configuration.
+    pub key: &'static str,
+
+    /// The default header of the column.
+    pub header: &'static str,
+
+    /// The default alignment of the column.
+    pub align: CellAlignment,
+
+    /// The default width policy of the column.
+    pub width: ColumnWidth,
+}
+
+impl Column {
+    pub const fn left(key: &'static str, header: &'static str) -> Self {
+        Self {
+            key,
+            header,
+            align: CellAlignment::Left,
+            width: ColumnWidth::Flexible,
+        }
+    }
+
+    pub const fn right(key: &'static str, header: &'static str) -> Self {
+        Self {
+            key,
+            header,
+            align: CellAlignment::Right,
+            width: ColumnWidth::Flexible,
+        }
+    }
+
+    pub const fn with_width(mut self, width: ColumnWidth) -> Self {
+        self.width = width;
+        self
+    }
+}
+
+/// The column width policy, as defined in the configuration.
+///
+/// Policies are written either as a string (`"flexible"` or
+/// `"content"`) or as a table (`{ fixed = 10 }` or `{ max = 30 }`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColumnWidth {
+    /// The column shrinks when the table does not fit the width.
+    #[default]
+    Flexible,
+
+    /// The column always fits its content.
+    Content,
+
+    /// The column always has the given width.
+    Fixed(u16),
+
+    /// The column does not exceed the given width.
+    Max(u16),
+}
+
+impl ColumnWidth {
+    fn to_constraint(self) -> Option<ColumnConstraint> {
+        match self {
+            Self::Flexible => None,
+            Self::Content => Some(ColumnConstraint::ContentWidth),
+            Self::Fixed(width) => Some(ColumnConstraint::Absolute(Width::Fixed(width))),
+            Self::Max(width) => Some(ColumnConstraint::UpperBoundary(Width::Fixed(width))),
+        }
+    }
+}
+
+/// The column alignment, as defined in the configuration.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColumnAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+impl From<ColumnAlignment> for CellAlignment {
+    fn from(align: ColumnAlignment) -> Self {
+        match align {
+            ColumnAlignment::Left => CellAlignment::Left,
+            ColumnAlignment::Center => CellAlignment::Center,
+            ColumnAlignment::Right => CellAlignment::Right,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use comfy_table::{presets, ContentArrangement, Table};
+
+    use super::{Column, ColumnAlignment, ColumnWidth, TableConfig};
+
+    const COLUMNS: [Column; 3] = [
+        Column::right("id", "ID"),
+        Column::left("subject", "SUBJECT"),
+        Column::right("size", "SIZE"),
+    ];
+
+    fn table(config: &TableConfig, width: Option<u16>) -> String {
+        let mut table = Table::new();
+
+        table
+            .load_preset(presets::ASCII_MARKDOWN)
+            .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+            .force_no_tty()
+            .add_row(["1", "Hello", "12K"])
+            .add_row(["22", "日本語の件名", "3K"])
+            .add_row(["333", "Mixed 中文 subject", "1.2M"]);
+
+        config.apply(&mut table, &COLUMNS, width);
+
+        table.to_string()
+    }
+
+    #[test]
+    fn default_alignment() {
+        let table = table(&TableConfig::default(), None);
+
+        let expected = [
+            "|  ID | SUBJECT            | SIZE |",
+            "|-----|--------------------|------|",
+            "|   1 | Hello              |  12K |",
+            "|  22 | 日本語の件名       |   3K |",
+            "| 333 | Mixed 中文 subject | 1.2M |",
+        ];
+
+        assert_eq!(table, expected.join("\n"));
+    }
+
+    #[test]
+    fn custom_alignment_and_headers() {
+        let config = TableConfig {
+            align: [
+                (String::from("id"), ColumnAlignment::Left),
+                (String::from("subject"), ColumnAlignment::Center),
+            ]
+            .into(),
+            headers: [(String::from("subject"), String::from("件名"))].into(),
+            ..Default::default()
+        };
+
+        let table = table(&config, None);
+
+        let expected = [
+            "| ID  |        件名        | SIZE |",
+            "|-----|--------------------|------|",
+            "| 1   |        Hello       |  12K |",
+            "| 22  |    日本語の件名    |   3K |",
+            "| 333 | Mixed 中文 subject | 1.2M |",
+        ];
+
+        assert_eq!(table, expected.join("\n"));
+    }
+
+    #[test]
+    fn shrink_cjk_content() {
+        let config = TableConfig {
+            widths: [(String::from("id"), ColumnWidth::Content)].into(),
+            ..Default::default()
+        };
+
+        let table = table(&config, Some(24));
+
+        let expected = [
+            "|  ID | SUBJECT | SIZE |",
+            "|-----|---------|------|",
+            "|   1 | Hello   |  12K |",
+            "|  22 | 日本語  |   3K |",
+            "|     | の件名  |      |",
+            "| 333 | Mixed   | 1.2M |",
+            "|     | 中文    |      |",
+            "|     | subject |      |",
+        ];
+
+        assert_eq!(table, expected.join("\n"));
+    }
+
+    const ENVELOPE_COLUMNS: [Column; 5] = [
+        Column::left("id", "ID").with_width(ColumnWidth::Content),
+        Column::left("flags", "FLAGS").with_width(ColumnWidth::Content),
+        Column::left("subject", "SUBJECT"),
+        Column::left("from", "FROM"),
+        Column::left("date", "DATE").with_width(ColumnWidth::Content),
+    ];
+
+    fn envelopes(config: &TableConfig, width: Option<u16>) -> String {
+        let mut table = Table::new();
+
+        table
+            .load_preset(presets::ASCII_MARKDOWN)
+            .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+            .force_no_tty()
+            .add_row([
+                "12",
+                "*",
+                "Quarterly report and budget review",
+                "Alice Liddell <alice@localhost>",
+                "2024-03-10 09:30",
+            ])
+            .add_row(["345", "", "Hi", "Bob <bob@localhost>", "2024-03-11 18:05"]);
+
+        config.apply(&mut table, &ENVELOPE_COLUMNS, width);
+
+        table.to_string()
+    }
+
+    #[test]
+    fn keep_content_columns_when_wide_enough() {
+        let table = envelopes(&TableConfig::default(), Some(60));
+
+        let expected = [
+            "| ID  | FLAGS | SUBJECT    | FROM       | DATE             |",
+            "|-----|-------|------------|------------|------------------|",
+            "| 12  | *     | Quarterly  | Alice      | 2024-03-10 09:30 |",
+            "|     |       | report and | Liddell    |                  |",
+            "|     |       | budget     | <alice@loc |                  |",
+            "|     |       | review     | alhost>    |                  |",
+            "| 345 |       | Hi         | Bob <bob@l | 2024-03-11 18:05 |",
+            "|     |       |            | ocalhost>  |                  |",
+        ];
+
+        assert_eq!(table, expected.join("\n"));
+    }
+
+    /// At 40 columns, fitting the date would leave one character to
+    /// the subject and the sender, so the date shrinks as well.
+    #[test]
+    fn shrink_content_columns_at_40_columns() {
+        let table = envelopes(&TableConfig::default(), Some(40));
+
+        let expected = [
+            "| ID  | FLAGS | SUB... | FROM  | DATE  |",
+            "|-----|-------|--------|-------|-------|",
+            "| 12  | *     | Quarte | Alice | 2024- |",
+            "|     |       | rly    | Lidde | 03-10 |",
+            "|     |       | report | ll    | 09:30 |",
+            "|     |       | and    | <alic |       |",
+            "|     |       | budget | e@loc |       |",
+            "|     |       | review | alhos |       |",
+            "|     |       |        | t>    |       |",
+            "| 345 |       | Hi     | Bob   | 2024- |",
+            "|     |       |        | <bob@ | 03-11 |",
+            "|     |       |        | local | 18:05 |",
+            "|     |       |        | host> |       |",
+        ];
+
+        assert_eq!(table, expected.join("\n"));
+    }
+
+    #[test]
+    fn keep_fixed_columns_at_40_columns() {
+        let config = TableConfig {
+            widths: [(String::from("date"), ColumnWidth::Fixed(18))].into(),
+            ..Default::default()
+        };
+
+        let table = envelopes(&config, Some(40));
+
+        assert!(table.lines().all(|line| line.ends_with("|")));
+        assert!(table.contains("| 2024-03-10 09:30 |"));
+    }
+
+    #[test]
+    fn bound_piped_tables() {
+        super::disable_tty();
+
+        let config = TableConfig {
+            piped_width: Some(40),
+            ..Default::default()
+        };
+
+        let table = envelopes(&config, None);
+        assert!(table.lines().all(|line| line.chars().count() <= 40));
+
+        // the width from the command line takes precedence
+        let table = envelopes(&config, Some(60));
+        assert!(table.lines().any(|line| line.chars().count() == 60));
+    }
+}